@@ -0,0 +1,344 @@
+//! Bounded-parallel execution of many DDA requests
+//!
+//! [`PureRustRunner::run_batch`] schedules requests (different files, or
+//! different channel sets on the same file) across a fixed number of worker
+//! threads. Inputs are parsed once per file through a shared
+//! [`AnalysisSession`], a failure in one item never aborts the others, and
+//! results are yielded as each item finishes rather than in submission order.
+
+use crate::cancellation::CancellationToken;
+use crate::engine::{PureRustProgress, PureRustRunner};
+use crate::error::{DDAError, Result};
+use crate::session::AnalysisSession;
+use crate::types::{DDARequest, DDAResult};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+
+/// Aggregated progress across every item in a batch.
+#[derive(Debug, Clone)]
+pub struct BatchProgress {
+    /// Index (into the submitted requests) of the item that just reported
+    pub item_index: usize,
+    pub file_path: String,
+    /// Progress of that item
+    pub item: PureRustProgress,
+    pub completed: usize,
+    pub failed: usize,
+    pub total: usize,
+    fraction: f64,
+}
+
+impl BatchProgress {
+    /// Completed fraction of the whole batch, weighting items equally.
+    pub fn fraction(&self) -> f64 {
+        self.fraction
+    }
+
+    /// Completed percentage of the whole batch, rounded down.
+    pub fn percent(&self) -> u8 {
+        (self.fraction * 100.0).floor() as u8
+    }
+
+    /// Single-line status (`Progress: 42% | item 3/8 | <file> | <stage>`).
+    pub fn status_line(&self) -> String {
+        format!(
+            "Progress: {}% | item {}/{} | {} | {}",
+            self.percent(),
+            self.item_index + 1,
+            self.total,
+            self.file_path,
+            self.item.stage_label
+        )
+    }
+}
+
+/// Outcome of a single batch item.
+#[derive(Debug)]
+pub struct BatchItemResult {
+    /// Index of the request in the submitted batch
+    pub index: usize,
+    pub file_path: String,
+    pub result: Result<DDAResult>,
+}
+
+struct BatchState {
+    fractions: Vec<f64>,
+    completed: usize,
+    failed: usize,
+}
+
+/// Lock `mutex` even if a panicking progress callback poisoned it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Handle to a running batch; iterate it to receive results as they finish.
+///
+/// Dropping the handle cancels items that have not finished yet.
+pub struct BatchRun {
+    receiver: Receiver<BatchItemResult>,
+    cancel: CancellationToken,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl BatchRun {
+    /// Stop scheduling new items and interrupt running ones.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+}
+
+impl Iterator for BatchRun {
+    type Item = BatchItemResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for BatchRun {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl PureRustRunner {
+    /// Run `requests` on ASCII inputs with at most `concurrency` items in flight.
+    pub fn run_batch(&self, requests: Vec<DDARequest>, concurrency: usize) -> BatchRun {
+        self.run_batch_with_progress(requests, concurrency, |_| {})
+    }
+
+    /// Like [`run_batch`](Self::run_batch), reporting aggregated progress.
+    ///
+    /// `on_progress` is called from worker threads, one call at a time.
+    pub fn run_batch_with_progress<F>(
+        &self,
+        requests: Vec<DDARequest>,
+        concurrency: usize,
+        on_progress: F,
+    ) -> BatchRun
+    where
+        F: Fn(&BatchProgress) + Send + Sync + 'static,
+    {
+        let total = requests.len();
+        let requests = Arc::new(requests);
+        let session = Arc::new(AnalysisSession::new(self.clone()));
+        let state = Arc::new(Mutex::new(BatchState {
+            fractions: vec![0.0; total],
+            completed: 0,
+            failed: 0,
+        }));
+        let on_progress = Arc::new(on_progress);
+        // Serializes callbacks without holding `state` while they run
+        let reporting = Arc::new(Mutex::new(()));
+        let next_index = Arc::new(AtomicUsize::new(0));
+        let cancel = CancellationToken::new();
+        let (sender, receiver) = mpsc::channel();
+
+        let workers = (0..concurrency.clamp(1, total.max(1)))
+            .map(|_| {
+                let runner = self.clone();
+                let requests = Arc::clone(&requests);
+                let session = Arc::clone(&session);
+                let state = Arc::clone(&state);
+                let on_progress = Arc::clone(&on_progress);
+                let reporting = Arc::clone(&reporting);
+                let next_index = Arc::clone(&next_index);
+                let cancel = cancel.clone();
+                let sender = sender.clone();
+
+                std::thread::spawn(move || loop {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+                    let index = next_index.fetch_add(1, Ordering::SeqCst);
+                    let Some(request) = requests.get(index) else {
+                        break;
+                    };
+
+                    let report = |item: &PureRustProgress| {
+                        let progress = {
+                            let mut state = lock(&state);
+                            state.fractions[index] = item.fraction();
+                            BatchProgress {
+                                item_index: index,
+                                file_path: request.file_path.clone(),
+                                item: item.clone(),
+                                completed: state.completed,
+                                failed: state.failed,
+                                total,
+                                fraction: state.fractions.iter().sum::<f64>() / total as f64,
+                            }
+                        };
+                        let _reporting = lock(&reporting);
+                        on_progress(&progress);
+                    };
+                    let result = catch_unwind(AssertUnwindSafe(|| {
+                        let samples = session.samples_for(&request.file_path)?;
+                        runner.run_on_matrix_with_cancellation(
+                            request, &samples, None, &cancel, report,
+                        )
+                    }))
+                    .unwrap_or_else(|_| {
+                        Err(DDAError::ExecutionFailed(format!(
+                            "analysis of {} panicked",
+                            request.file_path
+                        )))
+                    });
+
+                    {
+                        let mut state = lock(&state);
+                        state.fractions[index] = 1.0;
+                        if result.is_ok() {
+                            state.completed += 1;
+                        } else {
+                            state.failed += 1;
+                        }
+                    }
+
+                    let item = BatchItemResult {
+                        index,
+                        file_path: request.file_path.clone(),
+                        result,
+                    };
+                    if sender.send(item).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        BatchRun {
+            receiver,
+            cancel,
+            workers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        AlgorithmSelection, DelayParameters, PreprocessingOptions, TimeRange, WindowParameters,
+    };
+    use std::io::Write;
+
+    fn request_for(path: &str) -> DDARequest {
+        DDARequest {
+            file_path: path.to_string(),
+            channels: Some(vec![0, 1]),
+            time_range: TimeRange {
+                start: 0.0,
+                end: 399.0,
+            },
+            preprocessing_options: PreprocessingOptions {
+                highpass: None,
+                lowpass: None,
+            },
+            algorithm_selection: AlgorithmSelection {
+                enabled_variants: vec!["ST".to_string()],
+                select_mask: None,
+            },
+            window_parameters: WindowParameters {
+                window_length: 100,
+                window_step: 50,
+                ct_window_length: None,
+                ct_window_step: None,
+            },
+            delay_parameters: DelayParameters { delays: vec![1, 2] },
+            ct_channel_pairs: None,
+            cd_channel_pairs: None,
+            model_parameters: None,
+            model_terms: None,
+            variant_configs: None,
+            sampling_rate: None,
+        }
+    }
+
+    #[test]
+    fn test_batch_isolates_failures_and_reports_progress() {
+        let mut file = tempfile::Builder::new()
+            .suffix(".ascii")
+            .tempfile()
+            .unwrap();
+        for t in 0..400 {
+            let x = (t as f64 * 0.05).sin();
+            writeln!(file, "{x:.12} {:.12}", (t as f64 * 0.03).cos()).unwrap();
+        }
+        let good = file.path().to_str().unwrap().to_string();
+        let requests = vec![
+            request_for(&good),
+            request_for("/nonexistent/input.ascii"),
+            request_for(&good),
+        ];
+
+        let last_fraction = Arc::new(Mutex::new(0.0));
+        let observed = Arc::clone(&last_fraction);
+        let mut results = PureRustRunner::default()
+            .run_batch_with_progress(requests, 2, move |progress| {
+                *observed.lock().unwrap() = progress.fraction();
+            })
+            .collect::<Vec<_>>();
+        results.sort_by_key(|item| item.index);
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].result.is_ok());
+        assert!(results[1].result.is_err());
+        assert!(results[2].result.is_ok());
+        assert!(*last_fraction.lock().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_panicking_progress_callback_fails_only_its_items() {
+        let mut file = tempfile::Builder::new()
+            .suffix(".ascii")
+            .tempfile()
+            .unwrap();
+        for t in 0..400 {
+            writeln!(
+                file,
+                "{:.12} {:.12}",
+                (t as f64 * 0.05).sin(),
+                (t as f64 * 0.03).cos()
+            )
+            .unwrap();
+        }
+        let path = file.path().to_str().unwrap().to_string();
+        let requests = vec![request_for(&path), request_for(&path), request_for(&path)];
+
+        let mut results = PureRustRunner::default()
+            .run_batch_with_progress(requests, 2, |progress| {
+                if progress.item_index == 0 {
+                    panic!("progress callback failed");
+                }
+            })
+            .collect::<Vec<_>>();
+        results.sort_by_key(|item| item.index);
+
+        // Every item still reports, including the one whose callback panicked
+        assert_eq!(results.len(), 3);
+        assert!(results[0].result.is_err());
+        assert!(results[1].result.is_ok());
+        assert!(results[2].result.is_ok());
+    }
+
+    #[test]
+    fn test_empty_batch_finishes() {
+        assert_eq!(
+            PureRustRunner::default().run_batch(Vec::new(), 4).count(),
+            0
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(
@@ -15,6 +15,16 @@ pub struct Cli {
     /// Increase verbosity (-v, -vv, -vvv)
     #[arg(short, long, action = clap::ArgAction::Count, global = true)]
     pub verbose: u8,
+
+    /// Named parameter profile from the config file for run and batch;
+    /// flags given on the command line take precedence
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// CLI config file with [profiles.<name>] tables
+    /// (default: ~/.config/ddalab/cli.toml)
+    #[arg(long, env = "DDALAB_CONFIG", global = true)]
+    pub config: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -25,10 +35,20 @@ pub enum Command {
     Info(InfoArgs),
     /// List available DDA analysis variants
     Variants(VariantsArgs),
-    /// Validate a data file
+    /// Validate a data file or a DDA output file
     Validate(ValidateArgs),
     /// Run batch DDA analysis across multiple files
     Batch(BatchArgs),
+    /// Convert a data file, optionally selecting channels and a time range
+    Convert(ConvertArgs),
+    /// Compare two results or result directories
+    Diff(DiffArgs),
+    /// Analyze data files as they appear in a directory
+    Watch(WatchArgs),
+    /// Analyze a BIDS dataset into a derivatives/ddalab tree
+    Bids(BidsArgs),
+    /// Render a result's Q matrix as a PNG or SVG heatmap
+    Render(RenderArgs),
     #[command(hide = true)]
     Serve(ServeArgs),
 }
@@ -39,6 +59,16 @@ pub struct RunArgs {
     #[arg(long)]
     pub file: String,
 
+    /// Files continuing --file, in order. All files are analyzed as one
+    /// continuous recording; they must be ASCII/TXT/CSV with the same
+    /// number of channels. --start/--end count from the start of --file.
+    #[arg(long, num_args = 1.., conflicts_with_all = ["remote", "cache", "cache_dir"])]
+    pub stitch: Option<Vec<String>>,
+
+    /// With --stitch, write which windows came from which file as JSON
+    #[arg(long, requires = "stitch")]
+    pub boundary_map: Option<String>,
+
     /// 0-based channel indices
     /// Optional when --variant-configs provides per-variant channels/pairs.
     #[arg(long, num_args = 1..)]
@@ -148,9 +178,64 @@ pub struct RunArgs {
     #[arg(long, default_value_t = false)]
     pub compact: bool,
 
+    /// Output format: the full result as JSON, one JSON line per Q-matrix
+    /// row with summary statistics, or a variant/label/window/q table as
+    /// CSV or Parquet (Parquet requires --output)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    pub output_format: OutputFormat,
+
     /// Suppress progress messages on stderr
     #[arg(long, default_value_t = false)]
     pub quiet: bool,
+
+    /// Emit `Progress: N%` lines on stderr while the analysis runs
+    #[arg(long, default_value_t = false)]
+    pub progress: bool,
+
+    /// Do not draw a progress bar (drawn only when stderr is a terminal)
+    #[arg(long, default_value_t = false)]
+    pub no_progress: bool,
+
+    /// Reuse results of identical earlier analyses from the result cache
+    #[arg(long, default_value_t = false)]
+    pub cache: bool,
+
+    /// Result cache directory (implies --cache; default: user cache dir)
+    #[arg(long, env = "DDALAB_CACHE_DIR")]
+    pub cache_dir: Option<String>,
+
+    /// Number of threads solving channels, pairs and groups in parallel
+    /// (default: all cores, or DDALAB_RAYON_THREADS). Rows keep the
+    /// channel order either way.
+    #[arg(short = 'j', long)]
+    pub jobs: Option<usize>,
+
+    /// Run on a ddalab-server instead of locally (e.g. https://dda.example.edu).
+    /// The file is uploaded, the job polled and its result downloaded.
+    #[arg(long)]
+    pub remote: Option<String>,
+
+    /// Session token for --remote, from the server's /auth/login
+    #[arg(long, env = "DDALAB_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// With --remote, --file is a path under the server's files directory
+    /// and nothing is uploaded
+    #[arg(long, default_value_t = false, requires = "remote")]
+    pub server_path: bool,
+
+    /// With --remote, the ID of the team parameter preset the job follows
+    #[arg(long, requires = "remote")]
+    pub preset: Option<String>,
+}
+
+/// Shape of the result written by `run`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Jsonl,
+    Csv,
+    Parquet,
 }
 
 #[derive(Args)]
@@ -172,10 +257,23 @@ pub struct VariantsArgs {
 }
 
 #[derive(Args)]
+#[group(id = "target", required = true, args = ["file", "output"])]
 pub struct ValidateArgs {
     /// Input data file path
     #[arg(long)]
-    pub file: String,
+    pub file: Option<String>,
+
+    /// DDA output to check against the variant layout (native text or result JSON)
+    #[arg(long)]
+    pub output: Option<String>,
+
+    /// Variant of a native output file (default: detected from the file-name suffix)
+    #[arg(long, requires = "output")]
+    pub variant: Option<String>,
+
+    /// Expected result rows (channels, groups or pairs) per window
+    #[arg(long, requires = "output")]
+    pub rows: Option<usize>,
 
     /// Output as JSON
     #[arg(long, default_value_t = false)]
@@ -184,6 +282,11 @@ pub struct ValidateArgs {
 
 #[derive(Args)]
 pub struct BatchArgs {
+    /// Directory to search for data files (EDF, ASCII/TXT/CSV, ...) or a
+    /// glob pattern (e.g., "data/*.edf")
+    #[arg(group = "input")]
+    pub path: Option<String>,
+
     /// Glob pattern to match input files (e.g., "data/*.edf")
     #[arg(long, group = "input")]
     pub glob: Option<String>,
@@ -273,18 +376,49 @@ pub struct BatchArgs {
     #[arg(long)]
     pub sr: Option<f64>,
 
+    /// TOML file with analysis parameters, in place of the parameter flags.
+    /// Keys match the flag names, e.g. variants = ["ST"], wl = 200,
+    /// channels = [0, 1], ct_pairs = ["0,1"]
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "channels", "variants", "wl", "ws", "ct_wl", "ct_ws", "delays", "model", "dm",
+            "order", "nr_tau", "ct_pairs", "cd_pairs", "variant_configs", "highpass",
+            "lowpass", "sr",
+        ]
+    )]
+    pub params: Option<String>,
+
     /// Legacy native DDA binary path (ignored; native backend disabled)
     #[arg(long, env = "DDA_BINARY_PATH")]
     pub binary: Option<String>,
 
-    /// Output directory for per-file JSON results (default: JSONL to stdout)
+    /// Output directory with one result directory per input and a
+    /// manifest.json summary (default: JSONL to stdout)
     #[arg(long)]
     pub output_dir: Option<String>,
 
+    /// Where to write the summary manifest (default: manifest.json in --output-dir)
+    #[arg(long)]
+    pub manifest: Option<String>,
+
     /// Continue processing remaining files after a failure
     #[arg(long, default_value_t = false)]
     pub continue_on_error: bool,
 
+    /// File recording which inputs finished, rewritten after each one
+    /// (default: .batch-state.json in --output-dir)
+    #[arg(long)]
+    pub state_file: Option<String>,
+
+    /// Skip inputs the state file records as succeeded and retry the rest
+    #[arg(long, default_value_t = false)]
+    pub resume: bool,
+
+    /// Number of files analyzed concurrently
+    #[arg(short = 'j', long, default_value_t = 1)]
+    pub jobs: usize,
+
     /// List matched files without running analysis
     #[arg(long, default_value_t = false)]
     pub dry_run: bool,
@@ -296,6 +430,201 @@ pub struct BatchArgs {
     /// Suppress progress messages on stderr
     #[arg(long, default_value_t = false)]
     pub quiet: bool,
+
+    /// Do not draw progress bars (drawn only when stderr is a terminal)
+    #[arg(long, default_value_t = false)]
+    pub no_progress: bool,
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// Input data file (ASCII/TXT/CSV, one sample per row)
+    pub input: String,
+
+    /// Output file; `.csv` is comma-delimited, anything else space-delimited,
+    /// and a `.gz` suffix compresses
+    pub output: String,
+
+    /// 0-based channel indices to keep (default: all)
+    #[arg(long, num_args = 1..)]
+    pub channels: Option<Vec<usize>>,
+
+    /// Start time in seconds (requires --sr)
+    #[arg(long, requires = "sr", conflicts_with = "start_sample")]
+    pub start: Option<f64>,
+
+    /// End time in seconds (requires --sr)
+    #[arg(long, requires = "sr", conflicts_with = "end_sample")]
+    pub end: Option<f64>,
+
+    /// Start sample index (alternative to --start)
+    #[arg(long)]
+    pub start_sample: Option<u64>,
+
+    /// End sample index, exclusive (alternative to --end)
+    #[arg(long)]
+    pub end_sample: Option<u64>,
+
+    /// Sampling rate in Hz
+    #[arg(long)]
+    pub sr: Option<f64>,
+
+    /// Suppress the summary on stderr
+    #[arg(long, default_value_t = false)]
+    pub quiet: bool,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Reference result JSON file or directory of results
+    pub a: String,
+
+    /// Result file or directory to compare against the reference
+    pub b: String,
+
+    /// Largest absolute deviation accepted per value
+    #[arg(long, default_value_t = 1e-6)]
+    pub tolerance: f64,
+
+    /// Output as JSON
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Directory to watch, including subdirectories
+    pub dir: String,
+
+    /// TOML file with analysis parameters (same keys as `batch --params`)
+    #[arg(long)]
+    pub params: String,
+
+    /// Result tree; each input gets <relative dir>/<stem>/dda.json
+    #[arg(long)]
+    pub output_dir: String,
+
+    /// Seconds a file's size and modification time must stay unchanged
+    /// before it is considered complete
+    #[arg(long, default_value_t = 5.0)]
+    pub settle_secs: f64,
+
+    /// Also analyze files already in the directory that have no
+    /// up-to-date result
+    #[arg(long, default_value_t = false)]
+    pub existing: bool,
+
+    /// Analyze the files already in the directory, then exit
+    #[arg(long, default_value_t = false)]
+    pub once: bool,
+
+    /// Number of files analyzed concurrently
+    #[arg(short = 'j', long, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Write results in compact JSON
+    #[arg(long, default_value_t = false)]
+    pub compact: bool,
+
+    /// Only report failures on stderr
+    #[arg(long, default_value_t = false)]
+    pub quiet: bool,
+}
+
+#[derive(Args)]
+pub struct BidsArgs {
+    /// BIDS dataset root (the directory holding dataset_description.json)
+    pub root: String,
+
+    /// Subject labels; ranges such as 01..20 keep the zero padding
+    #[arg(long, num_args = 1..)]
+    pub sub: Option<Vec<String>>,
+
+    /// Session labels or ranges
+    #[arg(long, num_args = 1..)]
+    pub ses: Option<Vec<String>>,
+
+    /// Task labels
+    #[arg(long, num_args = 1..)]
+    pub task: Option<Vec<String>>,
+
+    /// Run labels or ranges
+    #[arg(long, num_args = 1..)]
+    pub run: Option<Vec<String>>,
+
+    /// TOML file with analysis parameters (same keys as `batch --params`);
+    /// a sidecar SamplingFrequency is used when it sets no sr
+    #[arg(long)]
+    pub params: Option<String>,
+
+    /// Derivatives directory (default: <root>/derivatives/ddalab)
+    #[arg(long)]
+    pub output_dir: Option<String>,
+
+    /// Number of recordings analyzed concurrently
+    #[arg(short = 'j', long, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// List the selected recordings without analyzing them
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Write results in compact JSON
+    #[arg(long, default_value_t = false)]
+    pub compact: bool,
+
+    /// Only report failures on stderr
+    #[arg(long, default_value_t = false)]
+    pub quiet: bool,
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// Result JSON file, or a result directory holding dda.json
+    pub result: String,
+
+    /// Output image; a .png or .svg extension selects the format
+    #[arg(short, long)]
+    pub output: String,
+
+    /// Variant to render, by ID or name (default: the first in the result)
+    #[arg(long)]
+    pub variant: Option<String>,
+
+    /// Color map
+    #[arg(long, value_enum, default_value_t = Colormap::Viridis)]
+    pub colormap: Colormap,
+
+    /// Plot log10(|Q|) instead of Q; zero values are left blank
+    #[arg(long, default_value_t = false)]
+    pub log10: bool,
+
+    /// Value at the low end of the color scale (default: 2nd percentile)
+    #[arg(long)]
+    pub vmin: Option<f64>,
+
+    /// Value at the high end of the color scale (default: 98th percentile)
+    #[arg(long)]
+    pub vmax: Option<f64>,
+
+    /// Width of the heatmap in pixels; windows are averaged to fit
+    #[arg(long, default_value_t = 1200)]
+    pub width: u32,
+
+    /// Height of each Q-matrix row in pixels
+    #[arg(long, default_value_t = 12)]
+    pub row_height: u32,
+}
+
+/// Color maps available to `render`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Colormap {
+    Viridis,
+    Plasma,
+    Inferno,
+    Magma,
+    Coolwarm,
+    Grayscale,
 }
 
 #[derive(Args)]
@@ -2,33 +2,358 @@ use crate::cli::BatchArgs;
 use crate::dda_params;
 use crate::exit_codes;
 use crate::output;
-use std::path::Path;
+use crate::progress::BatchBars;
+use dda_rs::{DDARequest, PureRustRunner};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
 
-const BIDS_EXTENSIONS: &[&str] = &["edf", "set", "vhdr", "fif", "csv", "txt"];
+pub(crate) const BIDS_EXTENSIONS: &[&str] = &["edf", "set", "vhdr", "fif", "csv", "txt"];
+/// Extensions picked up when a plain directory is given
+const DATA_EXTENSIONS: &[&str] = &["edf", "set", "vhdr", "fif", "csv", "txt", "ascii"];
 const BIDS_MAX_DEPTH: usize = 6;
+/// Result file inside each per-input directory
+const RESULT_FILE_NAME: &str = "dda.json";
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const STATE_FILE_NAME: &str = ".batch-state.json";
+
+/// Analysis parameters shared by every file of a batch or watch session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct AnalysisParams {
+    pub channels: Option<Vec<usize>>,
+    pub variants: Vec<String>,
+    pub wl: u32,
+    pub ws: u32,
+    pub ct_wl: Option<u32>,
+    pub ct_ws: Option<u32>,
+    pub delays: Vec<i32>,
+    pub model: Option<Vec<i32>>,
+    pub dm: u32,
+    pub order: u32,
+    pub nr_tau: u32,
+    pub ct_pairs: Option<Vec<String>>,
+    pub cd_pairs: Option<Vec<String>>,
+    pub variant_configs: Option<String>,
+    pub highpass: Option<f64>,
+    pub lowpass: Option<f64>,
+    pub sr: Option<f64>,
+}
+
+/// The defaults of the batch parameter flags
+impl Default for AnalysisParams {
+    fn default() -> Self {
+        Self {
+            channels: None,
+            variants: vec!["ST".to_string()],
+            wl: dda_rs::DEFAULT_WINDOW_LENGTH,
+            ws: dda_rs::DEFAULT_WINDOW_STEP,
+            ct_wl: None,
+            ct_ws: None,
+            delays: vec![dda_rs::DEFAULT_DELAYS[0], dda_rs::DEFAULT_DELAYS[1]],
+            model: None,
+            dm: dda_rs::DEFAULT_MODEL_DIMENSION,
+            order: dda_rs::DEFAULT_POLYNOMIAL_ORDER,
+            nr_tau: dda_rs::DEFAULT_NUM_TAU,
+            ct_pairs: None,
+            cd_pairs: None,
+            variant_configs: None,
+            highpass: None,
+            lowpass: None,
+            sr: None,
+        }
+    }
+}
+
+impl From<&BatchArgs> for AnalysisParams {
+    fn from(args: &BatchArgs) -> Self {
+        Self {
+            channels: args.channels.clone(),
+            variants: args.variants.clone(),
+            wl: args.wl,
+            ws: args.ws,
+            ct_wl: args.ct_wl,
+            ct_ws: args.ct_ws,
+            delays: args.delays.clone(),
+            model: args.model.clone(),
+            dm: args.dm,
+            order: args.order,
+            nr_tau: args.nr_tau,
+            ct_pairs: args.ct_pairs.clone(),
+            cd_pairs: args.cd_pairs.clone(),
+            variant_configs: args.variant_configs.clone(),
+            highpass: args.highpass,
+            lowpass: args.lowpass,
+            sr: args.sr,
+        }
+    }
+}
+
+impl AnalysisParams {
+    /// Normalize the channel, pair and variant selection
+    pub(crate) fn prepare(self) -> Result<PreparedParams, String> {
+        let selection = dda_params::prepare_selection(
+            self.channels.clone(),
+            &self.variants,
+            self.ct_pairs.as_deref(),
+            self.cd_pairs.as_deref(),
+            self.variant_configs.as_deref(),
+        )?;
+        Ok(PreparedParams {
+            params: self,
+            selection,
+        })
+    }
+}
+
+/// [`AnalysisParams`] with the selection normalized, ready to build requests
+pub(crate) struct PreparedParams {
+    params: AnalysisParams,
+    selection: dda_params::PreparedSelection,
+}
+
+impl PreparedParams {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        dda_params::validate_common_params(
+            &self.selection.channels,
+            &self.selection.variants,
+            &self.params.delays,
+            self.params.wl,
+            self.params.ws,
+            &self.selection.ct_pairs,
+            &self.selection.cd_pairs,
+        )
+    }
+
+    /// Request for one input file, checked against the pure Rust backend
+    pub(crate) fn request_for(&self, file_path: &str) -> Result<DDARequest, String> {
+        let params = &self.params;
+        dda_params::validate_file(file_path).map_err(|error| format!("Error: {}", error))?;
+        let request = dda_params::build_dda_request(dda_params::RequestConfig {
+            file_path,
+            channels: &self.selection.channels,
+            variants: &self.selection.variants,
+            window_length: params.wl,
+            window_step: params.ws,
+            delays: &params.delays,
+            model_terms: params.model.clone(),
+            dm: params.dm,
+            order: params.order,
+            nr_tau: params.nr_tau,
+            ct_window_length: params.ct_wl,
+            ct_window_step: params.ct_ws,
+            ct_channel_pairs: self.selection.ct_pairs.clone(),
+            cd_channel_pairs: self.selection.cd_pairs.clone(),
+            sampling_rate: params.sr,
+            start: None,
+            end: None,
+            highpass: params.highpass,
+            lowpass: params.lowpass,
+            variant_configs: self.selection.variant_configs.clone(),
+        })
+        .map_err(|error| format!("Error building request: {}", error))?;
+        dda_params::pure_rust_support_reason(&request)
+            .map_err(|reason| format!("Pure Rust DDA cannot execute this request: {}", reason))?;
+        Ok(request)
+    }
+}
+
+/// Analysis parameters from a `--params` TOML file; keys match the flags
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BatchParams {
+    pub channels: Option<Vec<usize>>,
+    pub variants: Option<Vec<String>>,
+    pub wl: Option<u32>,
+    pub ws: Option<u32>,
+    pub ct_wl: Option<u32>,
+    pub ct_ws: Option<u32>,
+    pub delays: Option<Vec<i32>>,
+    pub model: Option<Vec<i32>>,
+    pub dm: Option<u32>,
+    pub order: Option<u32>,
+    pub nr_tau: Option<u32>,
+    pub ct_pairs: Option<Vec<String>>,
+    pub cd_pairs: Option<Vec<String>>,
+    pub variant_configs: Option<String>,
+    pub highpass: Option<f64>,
+    pub lowpass: Option<f64>,
+    pub sr: Option<f64>,
+}
+
+impl BatchParams {
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read params file '{}': {}", path.display(), e))?;
+        let mut params: BatchParams = toml::from_str(&text)
+            .map_err(|e| format!("Invalid params file '{}': {}", path.display(), e))?;
+        if let Some(dir) = path.parent() {
+            params.resolve_paths(dir);
+        }
+        Ok(params)
+    }
+
+    /// Make a relative variant config relative to `dir`, the file's directory
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        if let Some(configs) = &self.variant_configs {
+            if Path::new(configs).is_relative() {
+                self.variant_configs = Some(dir.join(configs).to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    /// Set every parameter present in the file on `args`
+    pub(crate) fn apply(self, args: &mut AnalysisParams) {
+        args.channels = self.channels.or(args.channels.take());
+        if let Some(variants) = self.variants {
+            args.variants = variants;
+        }
+        args.wl = self.wl.unwrap_or(args.wl);
+        args.ws = self.ws.unwrap_or(args.ws);
+        args.ct_wl = self.ct_wl.or(args.ct_wl);
+        args.ct_ws = self.ct_ws.or(args.ct_ws);
+        if let Some(delays) = self.delays {
+            args.delays = delays;
+        }
+        args.model = self.model.or(args.model.take());
+        args.dm = self.dm.unwrap_or(args.dm);
+        args.order = self.order.unwrap_or(args.order);
+        args.nr_tau = self.nr_tau.unwrap_or(args.nr_tau);
+        args.ct_pairs = self.ct_pairs.or(args.ct_pairs.take());
+        args.cd_pairs = self.cd_pairs.or(args.cd_pairs.take());
+        args.variant_configs = self.variant_configs.or(args.variant_configs.take());
+        args.highpass = self.highpass.or(args.highpass);
+        args.lowpass = self.lowpass.or(args.lowpass);
+        args.sr = self.sr.or(args.sr);
+    }
+}
+
+/// Machine-readable summary of a batch run
+#[derive(Debug, Serialize)]
+struct BatchManifest {
+    created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<String>,
+    elapsed_seconds: f64,
+    total: usize,
+    succeeded: usize,
+    failed: usize,
+    skipped: usize,
+    /// Succeeded in an earlier run and not analyzed again
+    resumed: usize,
+    files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
+    input: String,
+    status: EntryStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum EntryStatus {
+    Succeeded,
+    Failed,
+    /// Not run because an earlier file failed
+    Skipped,
+}
+
+/// Outcome of every finished input, so `--resume` can pick up where an
+/// interrupted run stopped
+#[derive(Debug, Serialize, Deserialize)]
+struct BatchState {
+    params: AnalysisParams,
+    inputs: BTreeMap<String, StateEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateEntry {
+    status: EntryStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    finished_at: String,
+}
+
+impl BatchState {
+    fn load(path: &Path) -> Result<Option<Self>, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(format!(
+                    "Failed to read state file '{}': {}",
+                    path.display(),
+                    error
+                ))
+            }
+        };
+        serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("Invalid state file '{}': {}", path.display(), e))
+    }
+
+    /// Whether `input` succeeded earlier and its result is still there
+    fn succeeded(&self, input: &str) -> Option<&StateEntry> {
+        self.inputs.get(input).filter(|entry| {
+            entry.status == EntryStatus::Succeeded
+                && entry
+                    .output
+                    .as_ref()
+                    .is_none_or(|output| Path::new(output).is_file())
+        })
+    }
+
+    fn record(&mut self, entry: &ManifestEntry) {
+        self.inputs.insert(
+            entry.input.clone(),
+            StateEntry {
+                status: entry.status,
+                output: entry.output.clone(),
+                error: entry.error.clone(),
+                finished_at: chrono::Utc::now().to_rfc3339(),
+            },
+        );
+    }
+
+    /// Replace the state file, never leaving a partly written one behind
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let partial = path.with_extension("json.partial");
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Error serializing batch state: {}", e))?;
+        std::fs::write(&partial, json)
+            .and_then(|()| std::fs::rename(&partial, path))
+            .map_err(|e| format!("Failed to write state file '{}': {}", path.display(), e))
+    }
+}
 
 pub async fn execute(args: BatchArgs) -> i32 {
-    let selection = match dda_params::prepare_selection(
-        args.channels.clone(),
-        &args.variants,
-        args.ct_pairs.as_deref(),
-        args.cd_pairs.as_deref(),
-        args.variant_configs.as_deref(),
-    ) {
-        Ok(selection) => selection,
+    let mut params = AnalysisParams::from(&args);
+    if let Some(path) = &args.params {
+        match BatchParams::load(Path::new(path)) {
+            Ok(file) => file.apply(&mut params),
+            Err(msg) => {
+                eprintln!("Error: {}", msg);
+                return exit_codes::INPUT_ERROR;
+            }
+        }
+    }
+
+    let state_params = params.clone();
+    let prepared = match params.prepare() {
+        Ok(prepared) => prepared,
         Err(msg) => {
             eprintln!("Error: {}", msg);
             return exit_codes::INPUT_ERROR;
         }
     };
-    let dda_params::PreparedSelection {
-        variants: normalized_variants,
-        channels: effective_channels,
-        ct_pairs: effective_ct_pairs,
-        cd_pairs: effective_cd_pairs,
-        variant_configs,
-    } = selection;
 
     // Resolve file list
     let files = match resolve_files(&args) {
@@ -56,15 +381,7 @@ pub async fn execute(args: BatchArgs) -> i32 {
     }
 
     // Validate shared params
-    if let Err(msg) = dda_params::validate_common_params(
-        &effective_channels,
-        &normalized_variants,
-        &args.delays,
-        args.wl,
-        args.ws,
-        &effective_ct_pairs,
-        &effective_cd_pairs,
-    ) {
+    if let Err(msg) = prepared.validate() {
         eprintln!("Error: {}", msg);
         return exit_codes::INPUT_ERROR;
     }
@@ -77,82 +394,185 @@ pub async fn execute(args: BatchArgs) -> i32 {
         }
     }
 
+    let state_path = args.state_file.as_ref().map(PathBuf::from).or_else(|| {
+        args.output_dir
+            .as_ref()
+            .map(|dir| Path::new(dir).join(STATE_FILE_NAME))
+    });
+    let mut state = match load_state(&args, state_path.as_deref(), state_params) {
+        Ok(state) => state,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+    let save_state = |state: &BatchState| {
+        if let Some(path) = &state_path {
+            if let Err(error) = state.save(path) {
+                eprintln!("Warning: {}", error);
+            }
+        }
+    };
+
     let total = files.len();
     let mut succeeded = 0usize;
     let mut failed = 0usize;
+    let mut resumed = 0usize;
     let start_time = Instant::now();
+    let result_dirs = result_dir_names(&files);
+    let mut entries: Vec<ManifestEntry> = files
+        .iter()
+        .map(|input| ManifestEntry {
+            input: input.clone(),
+            status: EntryStatus::Skipped,
+            output: None,
+            error: None,
+        })
+        .collect();
+
+    // Build every request up front; per-file parameter errors count as failures
+    let mut requests = Vec::with_capacity(total);
+    // File index behind each request
+    let mut request_files = Vec::with_capacity(total);
+    for (file_index, file_path) in files.iter().enumerate() {
+        if args.resume {
+            if let Some(done) = state.succeeded(file_path) {
+                entries[file_index].status = EntryStatus::Succeeded;
+                entries[file_index].output = done.output.clone();
+                succeeded += 1;
+                resumed += 1;
+                continue;
+            }
+        }
+        match prepared.request_for(file_path) {
+            Ok(request) => {
+                requests.push(request);
+                request_files.push(file_index);
+            }
+            Err(error) => {
+                eprintln!("  {}: {}", file_path, error);
+                entries[file_index].status = EntryStatus::Failed;
+                entries[file_index].error = Some(error);
+                failed += 1;
+                state.record(&entries[file_index]);
+                save_state(&state);
+                if !args.continue_on_error {
+                    requests.clear();
+                    break;
+                }
+            }
+        }
+    }
 
-    for (i, file_path) in files.iter().enumerate() {
+    if resumed > 0 && !args.quiet {
+        eprintln!("Resuming: {}/{} file(s) already succeeded", resumed, total);
+    }
+
+    let scheduled = requests.len();
+    let bars = Arc::new(BatchBars::new(!args.quiet && !args.no_progress));
+    let reporter = Arc::clone(&bars);
+    let run =
+        PureRustRunner::default().run_batch_with_progress(requests, args.jobs, move |progress| {
+            reporter.update(progress)
+        });
+    for (finished, item) in run.enumerate() {
+        bars.item_done(item.index);
         if !args.quiet {
-            eprintln!("[{}/{}] {}...", i + 1, total, file_path);
+            bars.println(&format!(
+                "[{}/{}] {}",
+                finished + 1,
+                scheduled,
+                item.file_path
+            ));
         }
 
-        let outcome: Result<(), String> = async {
-            dda_params::validate_file(file_path).map_err(|error| format!("Error: {}", error))?;
-            let request = dda_params::build_dda_request(dda_params::RequestConfig {
-                file_path,
-                channels: &effective_channels,
-                variants: &normalized_variants,
-                window_length: args.wl,
-                window_step: args.ws,
-                delays: &args.delays,
-                model_terms: args.model.clone(),
-                dm: args.dm,
-                order: args.order,
-                nr_tau: args.nr_tau,
-                ct_window_length: args.ct_wl,
-                ct_window_step: args.ct_ws,
-                ct_channel_pairs: effective_ct_pairs.clone(),
-                cd_channel_pairs: effective_cd_pairs.clone(),
-                sampling_rate: args.sr,
-                start: None,
-                end: None,
-                highpass: args.highpass,
-                lowpass: args.lowpass,
-                variant_configs: variant_configs.clone(),
-            })
-            .map_err(|error| format!("Error building request: {}", error))?;
-            let result = dda_params::execute_request(&request, None, None)
-                .await
-                .map_err(|error| format!("DDA execution failed: {}", error))?;
-
-            if let Some(dir) = &args.output_dir {
+        let file_index = request_files[item.index];
+        let outcome: Result<Option<PathBuf>, String> = (|| {
+            let result = item.result.map_err(|error| {
+                format!("DDA execution failed: Pure Rust DDA failed: {}", error)
+            })?;
+
+            let written = if let Some(dir) = &args.output_dir {
                 let json = output::to_json(&result, args.compact)
                     .map_err(|error| format!("Error serializing result: {}", error))?;
-                let stem = Path::new(file_path)
-                    .file_stem()
-                    .and_then(|value| value.to_str())
-                    .unwrap_or("output");
-                let out_path = Path::new(dir).join(format!("{}_dda.json", stem));
+                let result_dir = Path::new(dir).join(&result_dirs[file_index]);
+                std::fs::create_dir_all(&result_dir).map_err(|error| {
+                    format!(
+                        "Error creating result directory '{}': {}",
+                        result_dir.display(),
+                        error
+                    )
+                })?;
+                let out_path = result_dir.join(RESULT_FILE_NAME);
                 output::write_output(&json, out_path.to_str())
                     .map_err(|error| format!("Error writing output: {}", error))?;
+                Some(out_path)
             } else {
                 let json = output::to_json(&result, true)
                     .map_err(|error| format!("Error serializing result: {}", error))?;
                 output::write_output(&json, None)
                     .map_err(|error| format!("Error writing to stdout: {}", error))?;
-            }
+                None
+            };
             if !args.quiet {
-                eprintln!("  Backend: pure-rust");
+                bars.println("  Backend: pure-rust");
+            }
+            Ok(written)
+        })();
+
+        let entry = &mut entries[file_index];
+        let stop = match outcome {
+            Ok(written) => {
+                entry.status = EntryStatus::Succeeded;
+                entry.output = written.map(|path| path.to_string_lossy().into_owned());
+                succeeded += 1;
+                false
             }
-            Ok(())
-        }
-        .await;
-
-        match outcome {
-            Ok(()) => succeeded += 1,
             Err(error) => {
-                eprintln!("  {}", error);
+                bars.println(&format!("  {}", error));
+                entry.status = EntryStatus::Failed;
+                entry.error = Some(error);
                 failed += 1;
-                if !args.continue_on_error {
-                    break;
-                }
+                !args.continue_on_error
             }
+        };
+        state.record(entry);
+        save_state(&state);
+        if stop {
+            // Dropping the run cancels the remaining files
+            break;
         }
     }
 
+    bars.finish();
     let elapsed = start_time.elapsed();
 
+    let manifest_path = args.manifest.clone().or_else(|| {
+        args.output_dir.as_ref().map(|dir| {
+            Path::new(dir)
+                .join(MANIFEST_FILE_NAME)
+                .to_string_lossy()
+                .into_owned()
+        })
+    });
+    if let Some(path) = manifest_path {
+        let manifest = BatchManifest {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            params: args.params.clone(),
+            elapsed_seconds: elapsed.as_secs_f64(),
+            total,
+            succeeded,
+            failed,
+            skipped: total - succeeded - failed,
+            resumed,
+            files: entries,
+        };
+        if let Err(error) = output::write_json(&manifest, false, Some(&path)) {
+            eprintln!("Error: {}", error);
+            return exit_codes::EXECUTION_ERROR;
+        }
+    }
+
     if !args.quiet {
         eprintln!(
             "Batch complete: {}/{} succeeded, {}/{} failed, {:.1}s",
@@ -173,15 +593,51 @@ pub async fn execute(args: BatchArgs) -> i32 {
     }
 }
 
+/// State carried over by `--resume`, or a fresh one
+fn load_state(
+    args: &BatchArgs,
+    path: Option<&Path>,
+    params: AnalysisParams,
+) -> Result<BatchState, String> {
+    let fresh = |params| BatchState {
+        params,
+        inputs: BTreeMap::new(),
+    };
+    if !args.resume {
+        return Ok(fresh(params));
+    }
+    let path = path.ok_or("--resume needs --output-dir or --state-file")?;
+    match BatchState::load(path)? {
+        Some(state) if state.params != params => Err(format!(
+            "State file '{}' was written with different analysis parameters; \
+             rerun without --resume to start over",
+            path.display()
+        )),
+        Some(state) => Ok(state),
+        None => {
+            if !args.quiet {
+                eprintln!("No state file at {}; starting from scratch", path.display());
+            }
+            Ok(fresh(params))
+        }
+    }
+}
+
 fn resolve_files(args: &BatchArgs) -> Result<Vec<String>, String> {
-    if let Some(ref pattern) = args.glob {
+    if let Some(ref path) = args.path {
+        if Path::new(path).is_dir() {
+            resolve_data_dir(path)
+        } else {
+            resolve_glob(path)
+        }
+    } else if let Some(ref pattern) = args.glob {
         resolve_glob(pattern)
     } else if let Some(ref files) = args.files {
         Ok(files.clone())
     } else if let Some(ref dir) = args.bids_dir {
         resolve_bids_dir(dir)
     } else {
-        Err("One of --glob, --files, or --bids-dir must be specified".to_string())
+        Err("A path, --glob, --files, or --bids-dir must be specified".to_string())
     }
 }
 
@@ -215,12 +671,19 @@ fn resolve_bids_dir(dir: &str) -> Result<Vec<String>, String> {
     }
 
     let mut files: Vec<String> = Vec::new();
-    walk_bids_dir(root, 0, &mut files);
+    walk_dir(root, 0, BIDS_EXTENSIONS, &mut files);
     files.sort();
     Ok(files)
 }
 
-fn walk_bids_dir(dir: &Path, depth: usize, files: &mut Vec<String>) {
+fn resolve_data_dir(dir: &str) -> Result<Vec<String>, String> {
+    let mut files: Vec<String> = Vec::new();
+    walk_dir(Path::new(dir), 0, DATA_EXTENSIONS, &mut files);
+    files.sort();
+    Ok(files)
+}
+
+pub(crate) fn walk_dir(dir: &Path, depth: usize, extensions: &[&str], files: &mut Vec<String>) {
     if depth > BIDS_MAX_DEPTH {
         return;
     }
@@ -241,10 +704,10 @@ fn walk_bids_dir(dir: &Path, depth: usize, files: &mut Vec<String>) {
         }
 
         if path.is_dir() {
-            walk_bids_dir(&path, depth + 1, files);
+            walk_dir(&path, depth + 1, extensions, files);
         } else if path.is_file() {
             if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                if BIDS_EXTENSIONS.contains(&ext) {
+                if extensions.contains(&ext) {
                     if let Some(s) = path.to_str() {
                         files.push(s.to_string());
                     }
@@ -254,6 +717,28 @@ fn walk_bids_dir(dir: &Path, depth: usize, files: &mut Vec<String>) {
     }
 }
 
+/// One result directory name per input: the file stem, suffixed when
+/// several inputs share it (e.g. the same recording name per subject)
+fn result_dir_names(files: &[String]) -> Vec<String> {
+    let mut used = HashSet::new();
+    files
+        .iter()
+        .map(|file| {
+            let stem = Path::new(file)
+                .file_stem()
+                .and_then(|value| value.to_str())
+                .unwrap_or("output");
+            let mut name = stem.to_string();
+            let mut suffix = 2;
+            while !used.insert(name.clone()) {
+                name = format!("{}-{}", stem, suffix);
+                suffix += 1;
+            }
+            name
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -261,6 +746,7 @@ mod tests {
 
     fn make_batch_args() -> BatchArgs {
         BatchArgs {
+            path: None,
             glob: None,
             files: None,
             bids_dir: None,
@@ -281,12 +767,18 @@ mod tests {
             highpass: None,
             lowpass: None,
             sr: None,
+            params: None,
             binary: None,
             output_dir: None,
+            manifest: None,
             continue_on_error: false,
+            state_file: None,
+            resume: false,
             dry_run: false,
             compact: false,
             quiet: false,
+            jobs: 1,
+            no_progress: false,
         }
     }
 
@@ -355,4 +847,57 @@ mod tests {
         let result = resolve_glob(&pattern).unwrap();
         assert_eq!(result.len(), 2);
     }
+
+    #[test]
+    fn test_resolve_path_accepts_directory_or_glob() {
+        let tmp = tempfile::tempdir().unwrap();
+        let sub_dir = tmp.path().join("session");
+        fs::create_dir_all(&sub_dir).unwrap();
+        fs::write(tmp.path().join("a.edf"), "").unwrap();
+        fs::write(sub_dir.join("b.ascii"), "").unwrap();
+        fs::write(sub_dir.join("notes.md"), "").unwrap();
+
+        let mut args = make_batch_args();
+        args.path = Some(tmp.path().to_str().unwrap().to_string());
+        assert_eq!(resolve_files(&args).unwrap().len(), 2);
+
+        args.path = Some(format!("{}/*.edf", tmp.path().to_str().unwrap()));
+        assert_eq!(resolve_files(&args).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_params_file_overrides_defaults() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("params.toml");
+        fs::write(
+            &path,
+            "variants = [\"ST\", \"CT\"]\nwl = 64\nchannels = [0, 1]\nct_pairs = [\"0,1\"]\nvariant_configs = \"variants.json\"\n",
+        )
+        .unwrap();
+
+        let mut args = AnalysisParams::default();
+        BatchParams::load(&path).unwrap().apply(&mut args);
+        assert_eq!(args.variants, vec!["ST", "CT"]);
+        assert_eq!(args.wl, 64);
+        assert_eq!(args.ws, dda_rs::DEFAULT_WINDOW_STEP);
+        assert_eq!(args.channels, Some(vec![0, 1]));
+        assert_eq!(args.ct_pairs, Some(vec!["0,1".to_string()]));
+        assert_eq!(
+            args.variant_configs.as_deref().map(Path::new),
+            Some(tmp.path().join("variants.json").as_path())
+        );
+
+        fs::write(&path, "window = 64\n").unwrap();
+        assert!(BatchParams::load(&path).unwrap_err().contains("window"));
+    }
+
+    #[test]
+    fn test_result_dir_names_are_unique() {
+        let files = [
+            "sub-01/eeg/rest.edf".to_string(),
+            "sub-02/eeg/rest.edf".to_string(),
+            "sub-02/eeg/task.edf".to_string(),
+        ];
+        assert_eq!(result_dir_names(&files), vec!["rest", "rest-2", "task"]);
+    }
 }
@@ -0,0 +1,399 @@
+use crate::cli::BidsArgs;
+use crate::commands::batch::{self, AnalysisParams, BatchParams, BIDS_EXTENSIONS};
+use crate::exit_codes;
+use crate::output;
+use dda_rs::PureRustRunner;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// BIDS version the derivatives dataset description declares
+const BIDS_VERSION: &str = "1.9.0";
+/// Derivatives pipeline directory below the dataset root
+const DERIVATIVES_DIR: &str = "derivatives/ddalab";
+/// Suffixes of electrophysiology recordings
+const DATA_SUFFIXES: &[&str] = &["eeg", "ieeg", "meg"];
+
+/// Entities and suffix of a BIDS file name such as `sub-01_task-rest_eeg`
+#[derive(Debug, PartialEq, Eq)]
+struct BidsName {
+    entities: Vec<(String, String)>,
+    suffix: String,
+}
+
+impl BidsName {
+    fn parse(stem: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = stem.split('_').collect();
+        let suffix = parts.pop().filter(|suffix| {
+            !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_alphanumeric())
+        })?;
+        let entities = parts
+            .into_iter()
+            .map(|part| {
+                let (key, value) = part.split_once('-')?;
+                (!key.is_empty() && !value.is_empty()).then(|| (key.to_string(), value.to_string()))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            entities,
+            suffix: suffix.to_string(),
+        })
+    }
+
+    fn of(path: &Path) -> Option<Self> {
+        Self::parse(path.file_stem()?.to_str()?)
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.entities
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether every entity of `self` appears in `other` with the same value
+    fn is_subset_of(&self, other: &BidsName) -> bool {
+        self.entities
+            .iter()
+            .all(|(key, value)| other.get(key) == Some(value.as_str()))
+    }
+
+    /// `sub-01_task-rest_run-01`, the name without its suffix
+    fn entity_prefix(&self) -> String {
+        self.entities
+            .iter()
+            .map(|(key, value)| format!("{}-{}", key, value))
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+}
+
+/// Expand labels and numeric ranges; `01..03` gives `01`, `02`, `03`
+fn expand_labels(values: &[String]) -> Result<Vec<String>, String> {
+    let mut labels = Vec::new();
+    for value in values {
+        let Some((start, end)) = value.split_once("..") else {
+            labels.push(value.clone());
+            continue;
+        };
+        let invalid = || format!("Invalid label range '{}': expected e.g. 01..20", value);
+        let first: u32 = start.parse().map_err(|_| invalid())?;
+        let last: u32 = end.parse().map_err(|_| invalid())?;
+        if first > last {
+            return Err(invalid());
+        }
+        labels.extend((first..=last).map(|n| format!("{:0width$}", n, width = start.len())));
+    }
+    Ok(labels)
+}
+
+/// Entity values a recording must have to be selected
+fn entity_filters(args: &BidsArgs) -> Result<Vec<(&'static str, Vec<String>)>, String> {
+    let mut filters = Vec::new();
+    for (key, values) in [
+        ("sub", &args.sub),
+        ("ses", &args.ses),
+        ("task", &args.task),
+        ("run", &args.run),
+    ] {
+        if let Some(values) = values {
+            let labels = if key == "task" {
+                values.clone()
+            } else {
+                expand_labels(values)?
+            };
+            filters.push((key, labels));
+        }
+    }
+    Ok(filters)
+}
+
+/// Recordings below the `sub-*` directories of `root` matching `filters`
+fn select_recordings(root: &Path, filters: &[(&str, Vec<String>)]) -> Vec<(PathBuf, BidsName)> {
+    let mut files = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(root) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() && entry.file_name().to_string_lossy().starts_with("sub-") {
+                batch::walk_dir(&path, 1, BIDS_EXTENSIONS, &mut files);
+            }
+        }
+    }
+    files.sort();
+
+    files
+        .into_iter()
+        .map(PathBuf::from)
+        .filter_map(|path| {
+            let name = BidsName::of(&path)?;
+            let selected = DATA_SUFFIXES.contains(&name.suffix.as_str())
+                && filters.iter().all(|(key, labels)| {
+                    name.get(key)
+                        .is_some_and(|value| labels.iter().any(|label| label == value))
+                });
+            selected.then_some((path, name))
+        })
+        .collect()
+}
+
+/// `SamplingFrequency` from the sidecar applying to a recording
+///
+/// Follows the BIDS inheritance principle: the nearest directory with a
+/// matching `.json` wins, and within a directory the most specific one.
+fn sidecar_sampling_frequency(path: &Path, name: &BidsName, root: &Path) -> Option<f64> {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        let mut candidates: Vec<(usize, PathBuf)> = std::fs::read_dir(current)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|candidate| candidate.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|candidate| {
+                let sidecar = BidsName::of(&candidate)?;
+                (sidecar.suffix == name.suffix && sidecar.is_subset_of(name))
+                    .then_some((sidecar.entities.len(), candidate))
+            })
+            .collect();
+        candidates.sort();
+        for (_, candidate) in candidates.iter().rev() {
+            let frequency = std::fs::read_to_string(candidate)
+                .ok()
+                .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+                .and_then(|sidecar| sidecar.get("SamplingFrequency")?.as_f64());
+            if frequency.is_some() {
+                return frequency;
+            }
+        }
+        if current == root {
+            break;
+        }
+        dir = current.parent();
+    }
+    None
+}
+
+/// `<output_dir>/<dir relative to the root>/<entities>_dda.json`
+fn result_path(path: &Path, name: &BidsName, root: &Path, output_dir: &Path) -> PathBuf {
+    let relative_dir = path
+        .parent()
+        .and_then(|dir| dir.strip_prefix(root).ok())
+        .unwrap_or(Path::new(""));
+    output_dir
+        .join(relative_dir)
+        .join(format!("{}_dda.json", name.entity_prefix()))
+}
+
+/// `dataset_description.json` marking the output as a BIDS derivative
+fn write_dataset_description(output_dir: &Path, root: &Path) -> Result<(), String> {
+    let path = output_dir.join("dataset_description.json");
+    if path.exists() {
+        return Ok(());
+    }
+    let description = json!({
+        "Name": "DDALAB delay differential analysis",
+        "BIDSVersion": BIDS_VERSION,
+        "DatasetType": "derivative",
+        "GeneratedBy": [{
+            "Name": "ddalab",
+            "Version": env!("CARGO_PKG_VERSION"),
+            "Description": "Delay differential analysis (pure Rust backend)",
+        }],
+        "SourceDatasets": [{ "URL": format!("file://{}", root.display()) }],
+    });
+    output::write_json(&description, false, path.to_str())
+}
+
+pub fn execute(args: BidsArgs) -> i32 {
+    let root = match Path::new(&args.root).canonicalize() {
+        Ok(root) if root.is_dir() => root,
+        _ => {
+            eprintln!("Error: BIDS directory not found: {}", args.root);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+
+    let mut params = AnalysisParams::default();
+    let prepared = match args
+        .params
+        .as_deref()
+        .map_or(Ok(()), |path| {
+            BatchParams::load(Path::new(path)).map(|file| file.apply(&mut params))
+        })
+        .and_then(|()| params.prepare())
+        .and_then(|prepared| prepared.validate().map(|()| prepared))
+    {
+        Ok(prepared) => prepared,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+
+    let filters = match entity_filters(&args) {
+        Ok(filters) => filters,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+    let recordings = select_recordings(&root, &filters);
+    if recordings.is_empty() {
+        eprintln!("Error: No recordings match the selected entities");
+        return exit_codes::INPUT_ERROR;
+    }
+
+    if args.dry_run {
+        for (path, _) in &recordings {
+            println!("{}", path.display());
+        }
+        if !args.quiet {
+            eprintln!("Found {} recording(s)", recordings.len());
+        }
+        return exit_codes::SUCCESS;
+    }
+
+    let output_dir = args
+        .output_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| root.join(DERIVATIVES_DIR));
+    if let Err(msg) = std::fs::create_dir_all(&output_dir)
+        .map_err(|e| {
+            format!(
+                "Failed to create output directory '{}': {}",
+                output_dir.display(),
+                e
+            )
+        })
+        .and_then(|()| write_dataset_description(&output_dir, &root))
+    {
+        eprintln!("Error: {}", msg);
+        return exit_codes::EXECUTION_ERROR;
+    }
+
+    let total = recordings.len();
+    let (mut succeeded, mut failed) = (0usize, 0usize);
+    let start_time = Instant::now();
+
+    let mut requests = Vec::with_capacity(total);
+    // Recording behind each request
+    let mut request_recordings = Vec::with_capacity(total);
+    for (index, (path, name)) in recordings.iter().enumerate() {
+        match prepared.request_for(&path.to_string_lossy()) {
+            Ok(mut request) => {
+                if request.sampling_rate.is_none() {
+                    request.sampling_rate = sidecar_sampling_frequency(path, name, &root);
+                }
+                requests.push(request);
+                request_recordings.push(index);
+            }
+            Err(error) => {
+                eprintln!("  {}: {}", path.display(), error);
+                failed += 1;
+            }
+        }
+    }
+
+    let scheduled = requests.len();
+    let run = PureRustRunner::default().run_batch(requests, args.jobs);
+    for (finished, item) in run.enumerate() {
+        let (path, name) = &recordings[request_recordings[item.index]];
+        let out_path = result_path(path, name, &root, &output_dir);
+        let outcome = item
+            .result
+            .map_err(|error| format!("DDA execution failed: Pure Rust DDA failed: {}", error))
+            .and_then(|result| output::to_json(&result, args.compact))
+            .and_then(|json| {
+                let dir = out_path.parent().unwrap_or(&output_dir);
+                std::fs::create_dir_all(dir).map_err(|error| {
+                    format!(
+                        "Error creating result directory '{}': {}",
+                        dir.display(),
+                        error
+                    )
+                })?;
+                output::write_output(&json, out_path.to_str())
+            });
+        match outcome {
+            Ok(()) => {
+                succeeded += 1;
+                if !args.quiet {
+                    eprintln!(
+                        "[{}/{}] {} -> {}",
+                        finished + 1,
+                        scheduled,
+                        path.display(),
+                        out_path.display()
+                    );
+                }
+            }
+            Err(error) => {
+                failed += 1;
+                eprintln!("  {}: {}", path.display(), error);
+            }
+        }
+    }
+
+    if !args.quiet {
+        eprintln!(
+            "BIDS run complete: {}/{} succeeded, {}/{} failed, {:.1}s",
+            succeeded,
+            total,
+            failed,
+            total,
+            start_time.elapsed().as_secs_f64()
+        );
+    }
+    if failed == 0 {
+        exit_codes::SUCCESS
+    } else if succeeded > 0 {
+        exit_codes::PARTIAL_FAILURE
+    } else {
+        exit_codes::EXECUTION_ERROR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_names_and_ranges() {
+        let name = BidsName::parse("sub-01_ses-2_task-rest_run-03_eeg").unwrap();
+        assert_eq!(name.suffix, "eeg");
+        assert_eq!(name.get("task"), Some("rest"));
+        assert_eq!(name.entity_prefix(), "sub-01_ses-2_task-rest_run-03");
+        assert!(BidsName::parse("recording_final").is_none());
+
+        let labels = expand_labels(&["08..11".to_string(), "pilot".to_string()]).unwrap();
+        assert_eq!(labels, ["08", "09", "10", "11", "pilot"]);
+        assert!(expand_labels(&["5..2".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_sidecar_inheritance() {
+        let root = tempfile::tempdir().unwrap();
+        let eeg_dir = root.path().join("sub-01").join("eeg");
+        std::fs::create_dir_all(&eeg_dir).unwrap();
+        std::fs::write(
+            root.path().join("task-rest_eeg.json"),
+            r#"{"SamplingFrequency": 256}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            eeg_dir.join("sub-01_task-rest_run-02_eeg.json"),
+            r#"{"SamplingFrequency": 512.5}"#,
+        )
+        .unwrap();
+
+        let frequency = |file: &str| {
+            let path = eeg_dir.join(file);
+            let name = BidsName::of(&path).unwrap();
+            sidecar_sampling_frequency(&path, &name, root.path())
+        };
+        assert_eq!(frequency("sub-01_task-rest_run-01_eeg.edf"), Some(256.0));
+        assert_eq!(frequency("sub-01_task-rest_run-02_eeg.edf"), Some(512.5));
+        assert_eq!(frequency("sub-01_task-motor_eeg.edf"), None);
+    }
+}
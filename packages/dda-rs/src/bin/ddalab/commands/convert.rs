@@ -0,0 +1,158 @@
+use crate::cli::ConvertArgs;
+use crate::dda_params;
+use crate::exit_codes;
+use dda_rs::{
+    create_export_writer, load_ascii_matrix_from_path, write_q_matrix_with_options, FileType,
+    TextExportOptions, TextFormat,
+};
+use std::path::Path;
+
+pub fn execute(args: ConvertArgs) -> i32 {
+    if let Err(msg) = dda_params::validate_file(&args.input) {
+        eprintln!("Error: {}", msg);
+        return exit_codes::INPUT_ERROR;
+    }
+    let ext = Path::new(&args.input)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+    if FileType::from_extension(ext) != Some(FileType::ASCII) {
+        eprintln!(
+            "Error: Converting '{}' files is not supported; only ASCII/TXT/CSV inputs can be read",
+            ext
+        );
+        return exit_codes::INPUT_ERROR;
+    }
+
+    let samples = match load_ascii_matrix_from_path(&args.input) {
+        Ok(samples) => samples,
+        Err(error) => {
+            eprintln!("Error: Failed to read {}: {}", args.input, error);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+    let num_channels = samples.first().map_or(0, Vec::len);
+
+    let (start, end) = match sample_range(&args, samples.len()) {
+        Ok(range) => range,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+    let channels = args
+        .channels
+        .clone()
+        .unwrap_or_else(|| (0..num_channels).collect());
+    if let Some(&channel) = channels.iter().find(|&&channel| channel >= num_channels) {
+        eprintln!(
+            "Error: Channel {} out of range; {} has {} channel(s)",
+            channel, args.input, num_channels
+        );
+        return exit_codes::INPUT_ERROR;
+    }
+
+    // The exporter writes `[channel][row]`; rows here are samples
+    let columns: Vec<Vec<f64>> = channels
+        .iter()
+        .map(|&channel| samples[start..end].iter().map(|row| row[channel]).collect())
+        .collect();
+    let labels: Vec<String> = channels
+        .iter()
+        .map(|channel| format!("Ch{}", channel + 1))
+        .collect();
+
+    let output = Path::new(&args.output);
+    let mut options = TextExportOptions::from_path(output);
+    // `#` header and no index column, so the result reads back as ASCII input
+    if options.format == TextFormat::Csv {
+        options.delimiter = Some(',');
+    }
+    options.format = TextFormat::Ascii;
+
+    let written = create_export_writer(output, options.compression.unwrap_or_default()).and_then(
+        |mut writer| {
+            write_q_matrix_with_options(&mut writer, &columns, &labels, &options)?;
+            writer.finish()
+        },
+    );
+    if let Err(error) = written {
+        eprintln!("Error: Failed to write {}: {}", args.output, error);
+        return exit_codes::EXECUTION_ERROR;
+    }
+
+    if !args.quiet {
+        eprintln!(
+            "Wrote {} sample(s) x {} channel(s) to {}",
+            end - start,
+            channels.len(),
+            args.output
+        );
+    }
+    exit_codes::SUCCESS
+}
+
+/// Half-open sample range selected by the time or sample bounds
+fn sample_range(args: &ConvertArgs, num_samples: usize) -> Result<(usize, usize), String> {
+    let to_sample = |seconds: f64| -> Result<usize, String> {
+        let sr = args.sr.unwrap_or_default();
+        if sr.is_nan() || sr <= 0.0 || seconds.is_nan() || seconds < 0.0 {
+            return Err("Time bounds need a positive --sr and non-negative seconds".to_string());
+        }
+        Ok((seconds * sr).round() as usize)
+    };
+
+    let start = match (args.start_sample, args.start) {
+        (Some(sample), _) => sample as usize,
+        (None, Some(seconds)) => to_sample(seconds)?,
+        (None, None) => 0,
+    };
+    let end = match (args.end_sample, args.end) {
+        (Some(sample), _) => sample as usize,
+        (None, Some(seconds)) => to_sample(seconds)?,
+        (None, None) => num_samples,
+    }
+    .min(num_samples);
+
+    if start >= end {
+        return Err(format!(
+            "Empty sample range {}..{} (input has {} samples)",
+            start, end, num_samples
+        ));
+    }
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_convert_args() -> ConvertArgs {
+        ConvertArgs {
+            input: "in.ascii".to_string(),
+            output: "out.csv".to_string(),
+            channels: None,
+            start: None,
+            end: None,
+            start_sample: None,
+            end_sample: None,
+            sr: None,
+            quiet: true,
+        }
+    }
+
+    #[test]
+    fn test_sample_range_from_seconds_and_samples() {
+        let mut args = make_convert_args();
+        assert_eq!(sample_range(&args, 100).unwrap(), (0, 100));
+
+        args.sr = Some(10.0);
+        args.start = Some(1.0);
+        args.end = Some(20.0);
+        assert_eq!(sample_range(&args, 100).unwrap(), (10, 100));
+
+        args.end = None;
+        args.end_sample = Some(5);
+        assert!(sample_range(&args, 100).is_err());
+    }
+}
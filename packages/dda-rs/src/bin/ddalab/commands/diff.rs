@@ -0,0 +1,183 @@
+use crate::cli::DiffArgs;
+use crate::exit_codes;
+use crate::output;
+use dda_rs::{diff_results, read_result_path, ResultDiff};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Batch summaries live beside results but are not results themselves
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+#[derive(Serialize)]
+struct FileDiff {
+    file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<ResultDiff>,
+    /// Why the file could not be compared
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl FileDiff {
+    fn within_tolerance(&self) -> bool {
+        self.error.is_none() && self.diff.as_ref().is_some_and(ResultDiff::within_tolerance)
+    }
+}
+
+#[derive(Serialize)]
+struct DiffOutput {
+    tolerance: f64,
+    within_tolerance: bool,
+    max_abs_deviation: f64,
+    files: Vec<FileDiff>,
+}
+
+pub fn execute(args: DiffArgs) -> i32 {
+    if args.tolerance.is_nan() || args.tolerance < 0.0 {
+        eprintln!("Error: --tolerance must be a non-negative number");
+        return exit_codes::INPUT_ERROR;
+    }
+    let (a, b) = (Path::new(&args.a), Path::new(&args.b));
+    let pairs = match (a.is_dir(), b.is_dir()) {
+        (true, true) => match (result_files(a), result_files(b)) {
+            (Ok(files_a), Ok(files_b)) => files_a.union(&files_b).cloned().collect(),
+            (Err(error), _) | (_, Err(error)) => {
+                eprintln!("Error: {}", error);
+                return exit_codes::INPUT_ERROR;
+            }
+        },
+        (false, false) if a.is_file() && b.is_file() => vec![PathBuf::new()],
+        _ => {
+            eprintln!("Error: Compare two result files or two result directories");
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+
+    let files: Vec<FileDiff> = pairs
+        .into_iter()
+        .map(|relative| diff_file(&a.join(&relative), &b.join(&relative), &relative, &args))
+        .collect();
+    let result = DiffOutput {
+        tolerance: args.tolerance,
+        within_tolerance: files.iter().all(FileDiff::within_tolerance),
+        max_abs_deviation: files
+            .iter()
+            .filter_map(|file| file.diff.as_ref())
+            .map(ResultDiff::max_abs_deviation)
+            .fold(0.0, f64::max),
+        files,
+    };
+
+    if args.json {
+        if let Err(error) = output::write_json(&result, false, None) {
+            eprintln!("Error: {}", error);
+            return exit_codes::EXECUTION_ERROR;
+        }
+    } else {
+        print_report(&result);
+    }
+
+    if result.within_tolerance {
+        exit_codes::SUCCESS
+    } else {
+        exit_codes::DIFFERENCES_FOUND
+    }
+}
+
+fn diff_file(a: &Path, b: &Path, relative: &Path, args: &DiffArgs) -> FileDiff {
+    let file = if relative.as_os_str().is_empty() {
+        format!("{} vs {}", args.a, args.b)
+    } else {
+        relative.to_string_lossy().into_owned()
+    };
+    let missing = match (a.is_file(), b.is_file()) {
+        (true, false) => Some("only in the first directory"),
+        (false, true) => Some("only in the second directory"),
+        _ => None,
+    };
+    if let Some(missing) = missing {
+        return FileDiff {
+            file,
+            diff: None,
+            error: Some(missing.to_string()),
+        };
+    }
+    match (read_result_path(a), read_result_path(b)) {
+        (Ok(result_a), Ok(result_b)) => FileDiff {
+            file,
+            diff: Some(diff_results(&result_a, &result_b, args.tolerance)),
+            error: None,
+        },
+        (Err(error), _) | (_, Err(error)) => FileDiff {
+            file,
+            diff: None,
+            error: Some(error.to_string()),
+        },
+    }
+}
+
+/// Result files under `root`, relative to it
+fn result_files(root: &Path) -> Result<BTreeSet<PathBuf>, String> {
+    let pattern = root.join("**").join("*.json*");
+    let pattern = pattern.to_string_lossy();
+    let paths = glob::glob(&pattern)
+        .map_err(|e| format!("Invalid directory '{}': {}", root.display(), e))?;
+    Ok(paths
+        .flatten()
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            path.is_file()
+                && (name.ends_with(".json") || name.ends_with(".json.gz"))
+                && name != MANIFEST_FILE_NAME
+        })
+        .filter_map(|path| path.strip_prefix(root).ok().map(Path::to_path_buf))
+        .collect())
+}
+
+fn print_report(result: &DiffOutput) {
+    for file in &result.files {
+        println!("{}", file.file);
+        if let Some(error) = &file.error {
+            println!("  ! {}", error);
+        }
+        let Some(diff) = &file.diff else {
+            continue;
+        };
+        for variant in &diff.variants {
+            println!(
+                "  {}: max {:.3e}, mean {:.3e} over {} value(s), {} beyond tolerance{}",
+                variant.variant_id,
+                variant.max_abs_deviation,
+                variant.mean_abs_deviation,
+                variant.values,
+                variant.exceeding,
+                if variant.non_finite_mismatches > 0 {
+                    format!(", {} NaN/inf mismatch(es)", variant.non_finite_mismatches)
+                } else {
+                    String::new()
+                }
+            );
+            if let Some(worst) = variant.worst.as_ref().filter(|_| variant.exceeding > 0) {
+                println!(
+                    "    worst at row {} window {}: {} vs {}",
+                    worst.row, worst.window, worst.a, worst.b
+                );
+            }
+        }
+        for difference in &diff.structural {
+            println!("  ! {}", difference);
+        }
+    }
+    println!(
+        "{} file(s), max deviation {:.3e}: {} tolerance {:e}",
+        result.files.len(),
+        result.max_abs_deviation,
+        if result.within_tolerance {
+            "within"
+        } else {
+            "BEYOND"
+        },
+        result.tolerance
+    );
+}
@@ -1,6 +1,12 @@
 pub mod batch;
+pub mod bids;
+pub mod convert;
+pub mod diff;
 pub mod info;
+pub mod remote;
+pub mod render;
 pub mod run;
 pub mod serve;
 pub mod validate;
 pub mod variants;
+pub mod watch;
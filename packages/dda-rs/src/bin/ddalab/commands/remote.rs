@@ -0,0 +1,484 @@
+//! `ddalab run --remote`: run an analysis on a ddalab-server
+//!
+//! The file is uploaded (or named by its path on the server with
+//! `--server-path`), submitted through the server's jobs API, polled until
+//! the job finishes, and the result downloaded and written like a local
+//! run's. The server describes jobs with its own parameter set, so only the
+//! flags that have an equivalent there are accepted.
+
+use crate::cli::{OutputFormat, RunArgs};
+use crate::exit_codes;
+use crate::output;
+use crate::progress::{self, ProgressDisplay};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+/// Time between two status requests
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Longest wait honored when the server defers a download to off-peak hours
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(3600);
+/// The server's SVD dimension count; no `run` flag corresponds to it
+const SERVER_SVD_DIMENSIONS: u32 = 3;
+
+/// Channel and pair selection after `dda_params::prepare_selection`
+pub struct Selection<'a> {
+    pub variants: &'a [String],
+    pub channels: &'a [usize],
+    pub ct_pairs: &'a Option<Vec<[usize; 2]>>,
+    pub cd_pairs: &'a Option<Vec<[usize; 2]>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubmitResponse {
+    job_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JobStatus {
+    status: String,
+    #[serde(default)]
+    progress: u8,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+pub fn execute(args: &RunArgs, server: &str, selection: Selection<'_>) -> i32 {
+    let parameters = match server_parameters(args, &selection) {
+        Ok(parameters) => parameters,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+    if !args.server_path {
+        if let Err(msg) = crate::dda_params::validate_file(&args.file) {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    }
+    if args.output_format == OutputFormat::Parquet && args.output.is_none() {
+        eprintln!("Error: --output-format parquet requires --output");
+        return exit_codes::INPUT_ERROR;
+    }
+
+    let client = Client::new(server, args.token.as_deref());
+    if !args.quiet {
+        eprintln!("Submitting {} to {}...", args.file, client.base);
+    }
+    let job_id = match submit(&client, args, &parameters) {
+        Ok(job_id) => job_id,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::EXECUTION_ERROR;
+        }
+    };
+    if !args.quiet {
+        eprintln!("  Job: {}", job_id);
+    }
+
+    let display = ProgressDisplay::new(!args.quiet && !args.no_progress && !args.progress);
+    let finished = wait_for_job(&client, &job_id, args, &display);
+    display.finish();
+    if let Err(msg) = finished {
+        eprintln!("Error: {}", msg);
+        return exit_codes::EXECUTION_ERROR;
+    }
+
+    let body = match download(&client, &job_id, args.quiet) {
+        Ok(body) => body,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::EXECUTION_ERROR;
+        }
+    };
+    let written = match serde_json::from_slice::<dda_rs::DDAResult>(&body) {
+        Ok(result) => output::write_result(
+            &result,
+            args.output_format,
+            args.compact,
+            args.output.as_deref(),
+        ),
+        // Servers running another DDA binary return its own output
+        Err(_) if args.output_format == OutputFormat::Json => {
+            match serde_json::from_slice::<Value>(&body) {
+                Ok(value) => output::write_json(&value, args.compact, args.output.as_deref()),
+                Err(_) => {
+                    output::write_output(&String::from_utf8_lossy(&body), args.output.as_deref())
+                }
+            }
+        }
+        Err(_) => Err(format!(
+            "The server's result is not a ddalab result and cannot be written as {:?}; use --output-format json",
+            args.output_format
+        )),
+    };
+    if let Err(msg) = written {
+        eprintln!("Error: {}", msg);
+        return exit_codes::EXECUTION_ERROR;
+    }
+    if !args.quiet {
+        eprintln!("  Backend: remote ({})", client.base);
+        if let Some(path) = &args.output {
+            eprintln!("Results written to {}", path);
+        }
+    }
+    exit_codes::SUCCESS
+}
+
+/// Job parameters in the server's terms
+///
+/// Windows become seconds, so `--sr` is required. Flags without a server
+/// equivalent are refused rather than silently dropped.
+fn server_parameters(args: &RunArgs, selection: &Selection<'_>) -> Result<Value, String> {
+    let sr = args
+        .sr
+        .filter(|sr| *sr > 0.0)
+        .ok_or("--remote needs --sr: the server takes window lengths in seconds")?;
+
+    let mut unsupported: Vec<&str> = Vec::new();
+    for variant in selection.variants {
+        if !matches!(variant.as_str(), "ST" | "CT" | "CD") {
+            unsupported.push(variant);
+        }
+    }
+    let flags = [
+        (args.model.is_some(), "--model"),
+        (args.variant_configs.is_some(), "--variant-configs"),
+        (args.highpass.is_some(), "--highpass"),
+        (args.lowpass.is_some(), "--lowpass"),
+        (args.ct_wl.is_some(), "--ct-wl"),
+        (args.ct_ws.is_some(), "--ct-ws"),
+        (args.cache || args.cache_dir.is_some(), "--cache"),
+        (args.delays != dda_rs::DEFAULT_DELAYS, "--delays"),
+        (args.order != dda_rs::DEFAULT_POLYNOMIAL_ORDER, "--order"),
+        (args.nr_tau != dda_rs::DEFAULT_NUM_TAU, "--nr-tau"),
+    ];
+    unsupported.extend(flags.iter().filter(|(set, _)| *set).map(|(_, flag)| *flag));
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "not supported with --remote: {}",
+            unsupported.join(", ")
+        ));
+    }
+    crate::dda_params::validate_common_params(
+        selection.channels,
+        selection.variants,
+        &args.delays,
+        args.wl,
+        args.ws,
+        selection.ct_pairs,
+        selection.cd_pairs,
+    )?;
+
+    let pairs = |variant: &str, pairs: &Option<Vec<[usize; 2]>>| -> Vec<(String, String)> {
+        if !selection.variants.iter().any(|v| v == variant) {
+            return Vec::new();
+        }
+        pairs
+            .iter()
+            .flatten()
+            .map(|[a, b]| (a.to_string(), b.to_string()))
+            .collect()
+    };
+    let seconds =
+        |time: Option<f64>, sample: Option<u64>| sample.map(|sample| sample as f64 / sr).or(time);
+
+    Ok(json!({
+        "channels": selection.channels.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        "ct_pairs": pairs("CT", selection.ct_pairs),
+        "cd_pairs": pairs("CD", selection.cd_pairs),
+        "time_window": args.wl as f64 / sr,
+        "delta": args.ws as f64 / sr,
+        "embedding_dim": args.dm,
+        "svd_dimensions": SERVER_SVD_DIMENSIONS,
+        "start_time": seconds(args.start, args.start_sample),
+        "end_time": seconds(args.end, args.end_sample),
+    }))
+}
+
+/// Blocking HTTP client for one server
+struct Client {
+    base: String,
+    token: Option<String>,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    fn new(server: &str, token: Option<&str>) -> Self {
+        Self {
+            base: server.trim_end_matches('/').to_string(),
+            token: token.map(str::to_string),
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(30))
+                .build(),
+        }
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = self
+            .agent
+            .request(method, &format!("{}{}", self.base, path));
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+}
+
+/// Describe a failed request, including the server's error message
+fn describe(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let url = response.get_url().to_string();
+            let body = response.into_string().unwrap_or_default();
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|value| {
+                    ["error", "message"]
+                        .iter()
+                        .find_map(|key| value.get(*key).and_then(Value::as_str).map(str::to_string))
+                })
+                .unwrap_or(body);
+            let hint = match code {
+                401 => " (check --token)",
+                403 if message.contains("passkey") || message.contains("MFA") => {
+                    " (verify with a second factor in the app first)"
+                }
+                _ => "",
+            };
+            format!("{} returned {}: {}{}", url, code, message.trim(), hint)
+        }
+        ureq::Error::Transport(transport) => format!("Cannot reach server: {}", transport),
+    }
+}
+
+/// Upload or reference the input file and submit the job
+fn submit(client: &Client, args: &RunArgs, parameters: &Value) -> Result<String, String> {
+    let response = if args.server_path {
+        let mut body = json!({ "server_path": args.file, "parameters": parameters });
+        if let Some(preset) = &args.preset {
+            body["preset_id"] = json!(preset);
+        }
+        client
+            .request("POST", "/api/jobs/submit")
+            .send_json(body)
+            .map_err(describe)?
+    } else {
+        let boundary = format!("ddalab-{}", uuid::Uuid::new_v4().simple());
+        let file_name = Path::new(&args.file)
+            .file_name()
+            .map(|name| name.to_string_lossy().replace('"', "_"))
+            .unwrap_or_else(|| "upload".to_string());
+        let file = std::fs::File::open(&args.file)
+            .map_err(|e| format!("Failed to open '{}': {}", args.file, e))?;
+        let file_size = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+        let mut head = String::new();
+        let mut field = |name: &str, value: &str| {
+            head.push_str(&format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                boundary, name, value
+            ));
+        };
+        field("parameters", &parameters.to_string());
+        if let Some(preset) = &args.preset {
+            field("preset_id", preset);
+        }
+        head.push_str(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            boundary, file_name
+        ));
+        let tail = format!("\r\n--{}--\r\n", boundary);
+        let length = head.len() as u64 + file_size + tail.len() as u64;
+
+        // Stream the file instead of holding recordings in memory
+        let body = std::io::Cursor::new(head.into_bytes())
+            .chain(file)
+            .chain(std::io::Cursor::new(tail.into_bytes()));
+        client
+            .request("POST", "/api/jobs/upload")
+            .set(
+                "Content-Type",
+                &format!("multipart/form-data; boundary={}", boundary),
+            )
+            .set("Content-Length", &length.to_string())
+            .send(body)
+            .map_err(describe)?
+    };
+
+    response
+        .into_json::<SubmitResponse>()
+        .map(|submitted| submitted.job_id)
+        .map_err(|e| format!("Unexpected submit response: {}", e))
+}
+
+/// Poll the job until it leaves the queue, drawing its progress
+fn wait_for_job(
+    client: &Client,
+    job_id: &str,
+    args: &RunArgs,
+    display: &ProgressDisplay,
+) -> Result<(), String> {
+    let mut last_status = String::new();
+    let mut last_percent: Option<u8> = None;
+    loop {
+        let status: JobStatus = client
+            .request("GET", &format!("/api/jobs/{}", job_id))
+            .call()
+            .map_err(describe)?
+            .into_json()
+            .map_err(|e| format!("Unexpected job status response: {}", e))?;
+
+        if status.status != last_status {
+            if !args.quiet {
+                display.println(&format!("  Status: {}", status.status));
+            }
+            last_status = status.status.clone();
+        }
+        display.set_lines(vec![progress::bar_line(
+            f64::from(status.progress) / 100.0,
+            display.elapsed(),
+            status.message.as_deref().unwrap_or(&status.status),
+        )]);
+        if args.progress && last_percent != Some(status.progress) {
+            last_percent = Some(status.progress);
+            eprintln!("Progress: {}%", status.progress);
+        }
+
+        match status.status.as_str() {
+            "completed" => return Ok(()),
+            "failed" | "cancelled" => {
+                return Err(format!(
+                    "Job {} {}: {}",
+                    job_id,
+                    status.status,
+                    status
+                        .error
+                        .or(status.message)
+                        .unwrap_or_else(|| "no details".to_string())
+                ))
+            }
+            _ => std::thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+/// Download the job's result, waiting when the server defers it
+fn download(client: &Client, job_id: &str, quiet: bool) -> Result<Vec<u8>, String> {
+    loop {
+        match client
+            .request("GET", &format!("/api/jobs/{}/download", job_id))
+            .call()
+        {
+            Ok(response) => {
+                let mut body = Vec::new();
+                response
+                    .into_reader()
+                    .read_to_end(&mut body)
+                    .map_err(|e| format!("Download failed: {}", e))?;
+                return Ok(body);
+            }
+            Err(ureq::Error::Status(503, response)) if response.header("Retry-After").is_some() => {
+                let wait = response
+                    .header("Retry-After")
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or(POLL_INTERVAL)
+                    .min(MAX_RETRY_AFTER);
+                if !quiet {
+                    eprintln!(
+                        "  Download deferred by the server; retrying in {}s",
+                        wait.as_secs()
+                    );
+                }
+                std::thread::sleep(wait);
+            }
+            Err(error) => return Err(describe(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn args(extra: &[&str]) -> RunArgs {
+        let mut argv = vec!["ddalab", "run", "--file", "x.edf", "--remote", "http://srv"];
+        argv.extend_from_slice(extra);
+        match crate::cli::Cli::parse_from(argv).command {
+            crate::cli::Command::Run(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    fn parameters(args: &RunArgs) -> Result<Value, String> {
+        let selection = crate::dda_params::prepare_selection(
+            args.channels.clone(),
+            &args.variants,
+            args.ct_pairs.as_deref(),
+            args.cd_pairs.as_deref(),
+            None,
+        )
+        .unwrap();
+        server_parameters(
+            args,
+            &Selection {
+                variants: &selection.variants,
+                channels: &selection.channels,
+                ct_pairs: &selection.ct_pairs,
+                cd_pairs: &selection.cd_pairs,
+            },
+        )
+    }
+
+    #[test]
+    fn test_server_parameters() {
+        let value = parameters(&args(&[
+            "--channels",
+            "0",
+            "2",
+            "--variants",
+            "ST",
+            "CT",
+            "--ct-pairs",
+            "0,2",
+            "--sr",
+            "100",
+            "--wl",
+            "200",
+            "--ws",
+            "50",
+            "--start-sample",
+            "1000",
+        ]))
+        .unwrap();
+        assert_eq!(value["channels"], json!(["0", "2"]));
+        assert_eq!(value["ct_pairs"], json!([["0", "2"]]));
+        assert_eq!(value["cd_pairs"], json!([]));
+        assert_eq!(value["time_window"], json!(2.0));
+        assert_eq!(value["delta"], json!(0.5));
+        assert_eq!(value["start_time"], json!(10.0));
+        assert_eq!(value["end_time"], Value::Null);
+
+        let error = parameters(&args(&["--channels", "0"])).unwrap_err();
+        assert!(error.contains("--sr"), "{}", error);
+        let error = parameters(&args(&[
+            "--channels",
+            "0",
+            "--sr",
+            "100",
+            "--variants",
+            "ST",
+            "DE",
+            "--highpass",
+            "1",
+        ]))
+        .unwrap_err();
+        assert_eq!(error, "not supported with --remote: DE, --highpass");
+    }
+}
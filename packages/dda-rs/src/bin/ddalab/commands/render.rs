@@ -0,0 +1,451 @@
+use crate::cli::{Colormap, RenderArgs};
+use crate::exit_codes;
+use dda_rs::{read_result_path, DDAResult};
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Result file inside a batch or watch result directory
+const RESULT_FILE_NAME: &str = "dda.json";
+/// Color of windows without a finite value
+const MISSING: [u8; 3] = [48, 48, 48];
+/// SVG space left of the heatmap for row labels
+const LABEL_WIDTH: u32 = 120;
+/// SVG space right of the heatmap for the color bar and its labels
+const COLORBAR_WIDTH: u32 = 90;
+/// SVG margin above and below the heatmap
+const MARGIN: u32 = 10;
+
+/// Anchor colors of each map, evenly spaced from 0 to 1
+fn anchors(colormap: Colormap) -> &'static [[u8; 3]] {
+    match colormap {
+        Colormap::Viridis => &[
+            [68, 1, 84],
+            [71, 45, 123],
+            [59, 82, 139],
+            [44, 114, 142],
+            [33, 145, 140],
+            [40, 174, 128],
+            [94, 201, 98],
+            [173, 220, 48],
+            [253, 231, 37],
+        ],
+        Colormap::Plasma => &[
+            [13, 8, 135],
+            [76, 2, 161],
+            [126, 3, 168],
+            [169, 35, 149],
+            [204, 71, 120],
+            [229, 107, 93],
+            [248, 149, 64],
+            [253, 197, 39],
+            [240, 249, 33],
+        ],
+        Colormap::Inferno => &[
+            [0, 0, 4],
+            [31, 12, 72],
+            [85, 15, 109],
+            [136, 34, 106],
+            [186, 54, 85],
+            [227, 89, 51],
+            [249, 142, 9],
+            [248, 201, 50],
+            [252, 255, 164],
+        ],
+        Colormap::Magma => &[
+            [0, 0, 4],
+            [28, 16, 68],
+            [79, 18, 123],
+            [129, 37, 129],
+            [181, 54, 122],
+            [229, 80, 100],
+            [251, 135, 97],
+            [254, 194, 135],
+            [252, 253, 191],
+        ],
+        Colormap::Coolwarm => &[
+            [59, 76, 192],
+            [98, 130, 234],
+            [141, 176, 254],
+            [184, 208, 249],
+            [221, 221, 221],
+            [245, 196, 173],
+            [244, 154, 123],
+            [222, 96, 77],
+            [180, 4, 38],
+        ],
+        Colormap::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+    }
+}
+
+/// Color of `t` in `[0, 1]`, interpolated between the map's anchors
+fn color_at(colormap: Colormap, t: f64) -> [u8; 3] {
+    let anchors = anchors(colormap);
+    let scaled = t.clamp(0.0, 1.0) * (anchors.len() - 1) as f64;
+    let index = (scaled.floor() as usize).min(anchors.len() - 2);
+    let frac = scaled - index as f64;
+    let (a, b) = (anchors[index], anchors[index + 1]);
+    std::array::from_fn(|i| (a[i] as f64 + (b[i] as f64 - a[i] as f64) * frac).round() as u8)
+}
+
+/// The Q matrix to draw, with one label per row
+struct Heatmap {
+    title: String,
+    labels: Vec<String>,
+    rows: Vec<Vec<f64>>,
+}
+
+/// Pick the variant named `variant`, or the first one
+fn select_matrix(result: &DDAResult, variant: Option<&str>) -> Result<Heatmap, String> {
+    let variants = result.variant_results.as_deref().unwrap_or_default();
+    let label_rows = |labels: Option<&[String]>, rows: usize| -> Vec<String> {
+        match labels {
+            Some(labels) if labels.len() == rows => labels.to_vec(),
+            _ if result.channels.len() == rows => result.channels.clone(),
+            _ => (0..rows).map(|row| format!("row {}", row)).collect(),
+        }
+    };
+
+    let chosen = match variant {
+        Some(name) => Some(
+            variants
+                .iter()
+                .find(|v| {
+                    v.variant_id.eq_ignore_ascii_case(name)
+                        || v.variant_name.eq_ignore_ascii_case(name)
+                })
+                .ok_or_else(|| {
+                    let known: Vec<&str> = variants.iter().map(|v| v.variant_id.as_str()).collect();
+                    format!(
+                        "Variant '{}' not in result (available: {})",
+                        name,
+                        if known.is_empty() {
+                            "none".to_string()
+                        } else {
+                            known.join(", ")
+                        }
+                    )
+                })?,
+        ),
+        None => variants.iter().find(|v| !v.q_matrix.is_empty()),
+    };
+    let heatmap = match chosen {
+        Some(v) => Heatmap {
+            title: v.variant_name.clone(),
+            labels: label_rows(v.channel_labels.as_deref(), v.q_matrix.len()),
+            rows: v.q_matrix.clone(),
+        },
+        None => Heatmap {
+            title: "Q matrix".to_string(),
+            labels: label_rows(None, result.q_matrix.len()),
+            rows: result.q_matrix.clone(),
+        },
+    };
+    if heatmap.rows.iter().all(Vec::is_empty) {
+        return Err("Result has an empty Q matrix".to_string());
+    }
+    Ok(heatmap)
+}
+
+/// Average each row into at most `width` bins, ignoring non-finite values
+fn bin_columns(rows: &[Vec<f64>], width: usize) -> Vec<Vec<f64>> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let bins = columns.min(width).max(1);
+    rows.iter()
+        .map(|row| {
+            (0..bins)
+                .map(|bin| {
+                    let start = bin * row.len() / bins;
+                    let end = ((bin + 1) * row.len() / bins).max(start + 1).min(row.len());
+                    let (sum, count) = row
+                        .get(start..end)
+                        .unwrap_or_default()
+                        .iter()
+                        .filter(|v| v.is_finite())
+                        .fold((0.0, 0usize), |(s, c), v| (s + v, c + 1));
+                    if count > 0 {
+                        sum / count as f64
+                    } else {
+                        f64::NAN
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// 2nd and 98th percentile of the finite values
+fn percentile_range(rows: &[Vec<f64>]) -> (f64, f64) {
+    let mut values: Vec<f64> = rows
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|v| v.is_finite())
+        .collect();
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    values.sort_by(f64::total_cmp);
+    let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+    (at(0.02), at(0.98))
+}
+
+/// Maps values to colors over a fixed range
+struct Scale {
+    colormap: Colormap,
+    low: f64,
+    high: f64,
+}
+
+impl Scale {
+    fn color(&self, value: f64) -> [u8; 3] {
+        if !value.is_finite() {
+            return MISSING;
+        }
+        let t = if self.high > self.low {
+            (value - self.low) / (self.high - self.low)
+        } else {
+            0.5
+        };
+        color_at(self.colormap, t)
+    }
+}
+
+/// RGB PNG, one pixel column per horizontal pixel and `row_height` per row
+fn render_png(
+    bins: &[Vec<f64>],
+    scale: &Scale,
+    width: u32,
+    row_height: u32,
+) -> Result<Vec<u8>, String> {
+    let height = bins.len() as u32 * row_height;
+    let mut pixels = Vec::with_capacity((width * height * 3) as usize);
+    for row in bins {
+        let line: Vec<u8> = (0..width as usize)
+            .flat_map(|x| scale.color(row[x * row.len() / width as usize]))
+            .collect();
+        for _ in 0..row_height {
+            pixels.extend_from_slice(&line);
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(png)
+}
+
+fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Heatmap with row labels, a title and a color bar
+///
+/// Runs of equal color within a row become one rectangle.
+fn render_svg(heatmap: &Heatmap, bins: &[Vec<f64>], scale: &Scale, args: &RenderArgs) -> String {
+    let (width, row_height) = (args.width, args.row_height);
+    let top = MARGIN + 20;
+    let plot_height = bins.len() as u32 * row_height;
+    let total_width = LABEL_WIDTH + width + COLORBAR_WIDTH;
+    let total_height = top + plot_height + MARGIN;
+    let label_size = row_height.clamp(6, 12);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{total_width}" height="{total_height}" viewBox="0 0 {total_width} {total_height}" font-family="sans-serif">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" font-size="14">{}{}</text>"#,
+        LABEL_WIDTH,
+        MARGIN + 12,
+        escape_xml(&heatmap.title),
+        if args.log10 { " (log10 |Q|)" } else { "" }
+    );
+    let _ = writeln!(svg, r#"<g shape-rendering="crispEdges">"#);
+    for (index, row) in bins.iter().enumerate() {
+        let y = top + index as u32 * row_height;
+        let cell = width as f64 / row.len() as f64;
+        let mut start = 0;
+        while start < row.len() {
+            let color = scale.color(row[start]);
+            let mut end = start + 1;
+            while end < row.len() && scale.color(row[end]) == color {
+                end += 1;
+            }
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{:.2}" y="{}" width="{:.2}" height="{}" fill="{}"/>"#,
+                LABEL_WIDTH as f64 + start as f64 * cell,
+                y,
+                (end - start) as f64 * cell,
+                row_height,
+                hex(color)
+            );
+            start = end;
+        }
+        if let Some(label) = heatmap.labels.get(index) {
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{:.1}" font-size="{}" text-anchor="end" dominant-baseline="middle">{}</text>"#,
+                LABEL_WIDTH - 6,
+                y as f64 + row_height as f64 / 2.0,
+                label_size,
+                escape_xml(label)
+            );
+        }
+    }
+    let _ = writeln!(svg, "</g>");
+
+    // Color bar, high values on top
+    let anchors = anchors(args.colormap);
+    let bar_x = LABEL_WIDTH + width + 12;
+    let _ = writeln!(
+        svg,
+        r#"<defs><linearGradient id="colorbar" x1="0" y1="1" x2="0" y2="0">"#
+    );
+    for (index, color) in anchors.iter().enumerate() {
+        let _ = writeln!(
+            svg,
+            r#"<stop offset="{:.3}" stop-color="{}"/>"#,
+            index as f64 / (anchors.len() - 1) as f64,
+            hex(*color)
+        );
+    }
+    let _ = writeln!(svg, "</linearGradient></defs>");
+    let _ = writeln!(
+        svg,
+        r#"<rect x="{bar_x}" y="{top}" width="14" height="{plot_height}" fill="url(#colorbar)"/>"#
+    );
+    for (value, y) in [(scale.high, top + 4), (scale.low, top + plot_height)] {
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-size="10">{}</text>"#,
+            bar_x + 18,
+            y,
+            format_value(value)
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn format_value(value: f64) -> String {
+    if value != 0.0 && (value.abs() >= 1e4 || value.abs() < 1e-3) {
+        format!("{:.2e}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+pub fn execute(args: RenderArgs) -> i32 {
+    let format = Path::new(&args.output)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    if !matches!(format.as_deref(), Some("png" | "svg")) {
+        eprintln!("Error: Output must end in .png or .svg: {}", args.output);
+        return exit_codes::INPUT_ERROR;
+    }
+    if args.width == 0 || args.row_height == 0 {
+        eprintln!("Error: --width and --row-height must be positive");
+        return exit_codes::INPUT_ERROR;
+    }
+
+    let path = Path::new(&args.result);
+    let path = if path.is_dir() {
+        path.join(RESULT_FILE_NAME)
+    } else {
+        path.to_path_buf()
+    };
+    let result = match read_result_path(&path) {
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!(
+                "Error: Failed to read result '{}': {}",
+                path.display(),
+                error
+            );
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+    let mut heatmap = match select_matrix(&result, args.variant.as_deref()) {
+        Ok(heatmap) => heatmap,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+
+    if args.log10 {
+        for value in heatmap.rows.iter_mut().flatten() {
+            *value = if *value != 0.0 {
+                value.abs().log10()
+            } else {
+                f64::NAN
+            };
+        }
+    }
+    let bins = bin_columns(&heatmap.rows, args.width as usize);
+    let (low, high) = percentile_range(&bins);
+    let scale = Scale {
+        colormap: args.colormap,
+        low: args.vmin.unwrap_or(low),
+        high: args.vmax.unwrap_or(high),
+    };
+
+    let written = if format.as_deref() == Some("svg") {
+        std::fs::write(&args.output, render_svg(&heatmap, &bins, &scale, &args))
+            .map_err(|e| e.to_string())
+    } else {
+        render_png(&bins, &scale, args.width, args.row_height)
+            .and_then(|png| std::fs::write(&args.output, png).map_err(|e| e.to_string()))
+    };
+    if let Err(error) = written {
+        eprintln!("Error: Failed to write '{}': {}", args.output, error);
+        return exit_codes::EXECUTION_ERROR;
+    }
+    exit_codes::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bins_and_colors() {
+        let rows = vec![vec![1.0, 3.0, f64::NAN, f64::NAN, 5.0], vec![2.0; 5]];
+        let bins = bin_columns(&rows, 2);
+        assert_eq!(bins[0][0], 2.0);
+        assert_eq!(bins[0][1], 5.0);
+        assert_eq!(bin_columns(&rows, 10)[1].len(), 5);
+
+        for colormap in [Colormap::Viridis, Colormap::Grayscale, Colormap::Coolwarm] {
+            let anchors = anchors(colormap);
+            assert_eq!(color_at(colormap, 0.0), anchors[0]);
+            assert_eq!(color_at(colormap, 1.0), anchors[anchors.len() - 1]);
+        }
+        assert_eq!(color_at(Colormap::Grayscale, 0.5), [128, 128, 128]);
+
+        let scale = Scale {
+            colormap: Colormap::Grayscale,
+            low: 0.0,
+            high: 10.0,
+        };
+        assert_eq!(scale.color(20.0), [255, 255, 255]);
+        assert_eq!(scale.color(f64::NAN), MISSING);
+        assert_eq!(escape_xml("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+    }
+}
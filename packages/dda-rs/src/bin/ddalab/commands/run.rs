@@ -1,7 +1,10 @@
-use crate::cli::RunArgs;
+use crate::cli::{OutputFormat, RunArgs};
+use crate::commands::remote;
 use crate::dda_params;
 use crate::exit_codes;
 use crate::output;
+use crate::progress::{self, ProgressDisplay};
+use dda_rs::{CacheKey, PureRustRunner, ResultCache};
 
 pub async fn execute(args: RunArgs) -> i32 {
     let selection = match dda_params::prepare_selection(
@@ -25,9 +28,31 @@ pub async fn execute(args: RunArgs) -> i32 {
         variant_configs,
     } = selection;
 
+    if let Some(server) = &args.remote {
+        return remote::execute(
+            &args,
+            server,
+            remote::Selection {
+                variants: &normalized_variants,
+                channels: &effective_channels,
+                ct_pairs: &effective_ct_pairs,
+                cd_pairs: &effective_cd_pairs,
+            },
+        );
+    }
+
     // Validate file
-    if let Err(msg) = dda_params::validate_file(&args.file) {
-        eprintln!("Error: {}", msg);
+    let stitched_files: Vec<&str> = std::iter::once(args.file.as_str())
+        .chain(args.stitch.iter().flatten().map(String::as_str))
+        .collect();
+    for file in &stitched_files {
+        if let Err(msg) = dda_params::validate_file(file) {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    }
+    if args.output_format == OutputFormat::Parquet && args.output.is_none() {
+        eprintln!("Error: --output-format parquet requires --output");
         return exit_codes::INPUT_ERROR;
     }
 
@@ -86,6 +111,9 @@ pub async fn execute(args: RunArgs) -> i32 {
 
     if !args.quiet {
         eprintln!("Running DDA analysis on {}...", args.file);
+        if let Some(parts) = &args.stitch {
+            eprintln!("  Stitched with: {}", parts.join(", "));
+        }
         eprintln!("  Variants: {}", normalized_variants.join(", "));
         eprintln!("  Channels: {:?}", effective_channels);
         eprintln!("  Window: length={}, step={}", args.wl, args.ws);
@@ -97,8 +125,96 @@ pub async fn execute(args: RunArgs) -> i32 {
         }
     }
 
-    let result = match dda_params::execute_request(&request, start_bound, end_bound).await {
-        Ok(result) => result,
+    let report_progress = args.progress;
+    // `Progress: N%` lines are meant for other programs; they replace the bar
+    let display = ProgressDisplay::new(!args.quiet && !args.no_progress && !report_progress);
+    let mut last_percent: Option<u8> = None;
+    let on_progress = |progress: &dda_rs::PureRustProgress| {
+        display.set_lines(vec![progress::bar_line(
+            progress.fraction(),
+            display.elapsed(),
+            &format!(
+                "{} | window {}/{}",
+                progress.stage_label, progress.window_index, progress.total_windows
+            ),
+        )]);
+        if !report_progress {
+            return;
+        }
+        let percent = progress.percent();
+        if last_percent != Some(percent) {
+            last_percent = Some(percent);
+            eprintln!("{}", progress.status_line());
+        }
+    };
+
+    let cache = match open_cache(&args) {
+        Ok(cache) => cache,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+    let cache_key = match &cache {
+        Some(_) => match CacheKey::for_request(
+            &PureRustRunner::default(),
+            &request,
+            start_bound,
+            end_bound,
+        ) {
+            Ok(key) => Some(key),
+            Err(error) => {
+                eprintln!("Error: failed to hash input for cache: {}", error);
+                return exit_codes::INPUT_ERROR;
+            }
+        },
+        None => None,
+    };
+
+    let mut boundary_map = None;
+    let outcome = if args.stitch.is_some() {
+        dda_params::execute_stitched_request_with_progress(
+            &request,
+            &stitched_files,
+            start_bound,
+            end_bound,
+            on_progress,
+        )
+        .await
+        .map(|run| {
+            boundary_map = Some(run.boundary_map);
+            (run.result, false)
+        })
+    } else {
+        let run = dda_params::execute_request_with_progress(
+            &request,
+            start_bound,
+            end_bound,
+            on_progress,
+        );
+        match (&cache, &cache_key) {
+            (Some(cache), Some(key)) => match cache.lookup(key, &request) {
+                Ok(Some(cached)) => Ok((cached, true)),
+                Ok(None) => run.await.map(|result| {
+                    cache.insert(key, &result);
+                    (result, false)
+                }),
+                Err(error) => {
+                    log::warn!("Ignoring unreadable result cache entry: {}", error);
+                    run.await.map(|result| (result, false))
+                }
+            },
+            _ => run.await.map(|result| (result, false)),
+        }
+    };
+    display.finish();
+    let result = match outcome {
+        Ok((result, cache_hit)) => {
+            if cache_hit && !args.quiet {
+                eprintln!("  Cache: hit");
+            }
+            result
+        }
         Err(error) => {
             eprintln!("DDA execution failed: {}", error);
             return exit_codes::EXECUTION_ERROR;
@@ -107,14 +223,35 @@ pub async fn execute(args: RunArgs) -> i32 {
     if !args.quiet {
         eprintln!("  Backend: pure-rust");
     }
-    let json = match output::to_json(&result, args.compact) {
-        Ok(json) => json,
-        Err(error) => {
-            eprintln!("Error serializing result: {}", error);
-            return exit_codes::EXECUTION_ERROR;
+    if let Some(map) = &boundary_map {
+        if !args.quiet {
+            let spanning: usize = map
+                .boundaries
+                .iter()
+                .map(|boundary| boundary.spanning_windows.len())
+                .sum();
+            eprintln!(
+                "  Stitched: {} files, {} windows span a file boundary",
+                map.files.len(),
+                spanning
+            );
         }
-    };
-    if let Err(error) = output::write_output(&json, args.output.as_deref()) {
+        if let Some(path) = &args.boundary_map {
+            let written = serde_json::to_string_pretty(map)
+                .map_err(|e| e.to_string())
+                .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+            if let Err(error) = written {
+                eprintln!("Error: failed to write boundary map {}: {}", path, error);
+                return exit_codes::EXECUTION_ERROR;
+            }
+        }
+    }
+    if let Err(error) = output::write_result(
+        &result,
+        args.output_format,
+        args.compact,
+        args.output.as_deref(),
+    ) {
         eprintln!("Error: {}", error);
         return exit_codes::EXECUTION_ERROR;
     }
@@ -126,6 +263,19 @@ pub async fn execute(args: RunArgs) -> i32 {
     exit_codes::SUCCESS
 }
 
+/// Result cache selected by `--cache`/`--cache-dir`, if any.
+fn open_cache(args: &RunArgs) -> Result<Option<ResultCache>, String> {
+    if let Some(dir) = &args.cache_dir {
+        return Ok(Some(ResultCache::in_directory(dir)));
+    }
+    if !args.cache {
+        return Ok(None);
+    }
+    dda_rs::DirectoryStore::default_root()
+        .map(|root| Some(ResultCache::in_directory(root)))
+        .ok_or_else(|| "no user cache directory available; pass --cache-dir".to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::cli::RunArgs;
@@ -135,6 +285,8 @@ mod tests {
     fn make_test_args() -> RunArgs {
         RunArgs {
             file: "/tmp/test.edf".to_string(),
+            stitch: None,
+            boundary_map: None,
             channels: Some(vec![0, 1, 2]),
             variants: vec!["ST".to_string()],
             wl: 200,
@@ -159,7 +311,17 @@ mod tests {
             binary: None,
             output: None,
             compact: false,
+            output_format: crate::cli::OutputFormat::Json,
             quiet: false,
+            progress: false,
+            no_progress: false,
+            cache: false,
+            cache_dir: None,
+            jobs: None,
+            remote: None,
+            token: None,
+            server_path: false,
+            preset: None,
         }
     }
 
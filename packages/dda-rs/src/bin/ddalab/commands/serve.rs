@@ -2,8 +2,8 @@ use crate::cli::ServeArgs;
 use crate::dda_params;
 use crate::exit_codes;
 use dda_rs::{
-    DDAResult, PureRustProgress, VariantChannelConfig, DEFAULT_DELAYS, DEFAULT_MODEL_DIMENSION,
-    DEFAULT_NUM_TAU, DEFAULT_POLYNOMIAL_ORDER,
+    AnalysisSession, DDAResult, PureRustProgress, VariantChannelConfig, DEFAULT_DELAYS,
+    DEFAULT_MODEL_DIMENSION, DEFAULT_NUM_TAU, DEFAULT_POLYNOMIAL_ORDER,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -214,7 +214,11 @@ fn resolve_channel_labels(labels: &[String], column_count: usize) -> Vec<String>
     }
 }
 
-async fn run_group<F>(params: RunGroupParams, on_progress: F) -> Result<RunGroupResponse, String>
+async fn run_group<F>(
+    session: &AnalysisSession,
+    params: RunGroupParams,
+    on_progress: F,
+) -> Result<RunGroupResponse, String>
 where
     F: FnMut(&PureRustProgress),
 {
@@ -232,10 +236,15 @@ where
         params.end_sample,
         params.analysis.sr,
     );
-    let result =
-        dda_params::execute_request_with_progress(&request, start_bound, end_bound, on_progress)
-            .await
-            .map_err(|error| format!("DDA execution failed: {}", error))?;
+    let result = dda_params::execute_request_in_session_with_progress(
+        session,
+        &request,
+        start_bound,
+        end_bound,
+        on_progress,
+    )
+    .await
+    .map_err(|error| format!("DDA execution failed: {}", error))?;
     Ok(RunGroupResponse::new(result))
 }
 
@@ -324,6 +333,8 @@ pub async fn execute(args: ServeArgs) -> i32 {
     let _ = args.disable_native_fallback;
 
     let preview_columns = args.preview_columns.max(16);
+    // Repeated run_group calls over the same recording reuse the parsed input.
+    let session = AnalysisSession::default();
     let stdin = io::stdin();
     let stdout = io::stdout();
     let mut reader = io::BufReader::new(stdin.lock());
@@ -372,7 +383,7 @@ pub async fn execute(args: ServeArgs) -> i32 {
             "run_group" => match serde_json::from_value::<RunGroupParams>(request.params) {
                 Ok(params) => {
                     let mut throttle = ProgressThrottle::new();
-                    match run_group(params, |progress| {
+                    match run_group(&session, params, |progress| {
                         if throttle.should_emit(progress) {
                             let _ = write_progress(&mut writer, progress);
                             throttle.mark_emitted();
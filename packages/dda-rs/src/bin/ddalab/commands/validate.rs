@@ -1,7 +1,8 @@
 use crate::cli::ValidateArgs;
 use crate::exit_codes;
 use crate::output;
-use dda_rs::FileType;
+use dda_rs::validate::{validate_output_path, Severity, ValidationReport};
+use dda_rs::{FileType, VariantMetadata};
 use serde::Serialize;
 use std::path::Path;
 
@@ -16,8 +17,85 @@ struct ValidateOutput {
     error: Option<String>,
 }
 
+#[derive(Serialize)]
+struct ValidateOutputFile {
+    output: String,
+    valid: bool,
+    #[serde(flatten)]
+    report: ValidationReport,
+}
+
 pub fn execute(args: ValidateArgs) -> i32 {
-    let path = Path::new(&args.file);
+    match args.output.clone() {
+        Some(path) => execute_output(path, args),
+        None => execute_input(args.file.unwrap_or_default(), args.json),
+    }
+}
+
+fn execute_output(path: String, args: ValidateArgs) -> i32 {
+    let variant = match args.variant.as_deref() {
+        Some(abbrev) => match VariantMetadata::from_abbrev(&abbrev.to_uppercase()) {
+            Some(variant) => Some(variant),
+            None => {
+                eprintln!("Error: Unknown variant '{}'", abbrev);
+                return exit_codes::INPUT_ERROR;
+            }
+        },
+        None => None,
+    };
+    let report = match validate_output_path(&path, variant, args.rows) {
+        Ok(report) => report,
+        Err(error) => {
+            eprintln!("Error: Failed to read {}: {}", path, error);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+    let valid = report.is_valid();
+
+    if args.json {
+        let result = ValidateOutputFile {
+            output: path,
+            valid,
+            report,
+        };
+        if let Err(error) = output::write_json(&result, false, None) {
+            eprintln!("Error: {}", error);
+            return exit_codes::EXECUTION_ERROR;
+        }
+    } else {
+        for diagnostic in &report.diagnostics {
+            let severity = match diagnostic.severity {
+                Severity::Error => "Error",
+                Severity::Warning => "Warning",
+            };
+            match &diagnostic.location {
+                Some(location) => eprintln!(
+                    "{} [{}] {}: {}",
+                    severity, diagnostic.code, location, diagnostic.message
+                ),
+                None => eprintln!("{} [{}] {}", severity, diagnostic.code, diagnostic.message),
+            }
+        }
+        if valid {
+            println!(
+                "Output '{}' conforms to {} ({} windows x {} rows)",
+                path,
+                report.variant.as_deref().unwrap_or("the DDA layout"),
+                report.windows,
+                report.rows
+            );
+        }
+    }
+
+    if valid {
+        exit_codes::SUCCESS
+    } else {
+        exit_codes::INPUT_ERROR
+    }
+}
+
+fn execute_input(file: String, json: bool) -> i32 {
+    let path = Path::new(&file);
 
     let exists = path.exists();
     let readable = path.is_file() && std::fs::File::open(path).is_ok();
@@ -34,9 +112,9 @@ pub fn execute(args: ValidateArgs) -> i32 {
     };
 
     let error = if !exists {
-        Some(format!("File not found: {}", args.file))
+        Some(format!("File not found: {}", file))
     } else if !readable {
-        Some(format!("File is not readable: {}", args.file))
+        Some(format!("File is not readable: {}", file))
     } else if !supported {
         Some(format!(
             "Unsupported file extension '{}'. Supported: edf, ascii, txt, csv",
@@ -47,7 +125,7 @@ pub fn execute(args: ValidateArgs) -> i32 {
     };
 
     let result = ValidateOutput {
-        file: args.file,
+        file,
         exists,
         readable,
         supported,
@@ -56,7 +134,7 @@ pub fn execute(args: ValidateArgs) -> i32 {
         error,
     };
 
-    if args.json {
+    if json {
         if let Err(error) = output::write_json(&result, false, None) {
             eprintln!("Error: {}", error);
             return exit_codes::EXECUTION_ERROR;
@@ -0,0 +1,379 @@
+use crate::cli::WatchArgs;
+use crate::commands::batch::{AnalysisParams, BatchParams, PreparedParams};
+use crate::dda_params;
+use crate::exit_codes;
+use crate::output;
+use dda_rs::PureRustRunner;
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often pending files are checked for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Result file inside each per-input directory
+const RESULT_FILE_NAME: &str = "dda.json";
+/// Suffixes of files still being written by acquisition or copy tools
+const PARTIAL_SUFFIXES: &[&str] = &[".part", ".partial", ".tmp", ".crdownload"];
+
+/// Size and modification time of a file at one point in time
+type Stamp = (u64, SystemTime);
+
+/// A file seen in the directory that is not yet known to be complete
+struct Pending {
+    stamp: Option<Stamp>,
+    unchanged_since: Instant,
+}
+
+pub fn execute(args: WatchArgs) -> i32 {
+    let watch_dir = match Path::new(&args.dir).canonicalize() {
+        Ok(dir) if dir.is_dir() => dir,
+        _ => {
+            eprintln!("Error: Directory not found: {}", args.dir);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+    if !args.settle_secs.is_finite() || args.settle_secs < 0.0 {
+        eprintln!("Error: --settle-secs must be a non-negative number of seconds");
+        return exit_codes::INPUT_ERROR;
+    }
+    let settle = Duration::from_secs_f64(args.settle_secs);
+
+    let mut params = AnalysisParams::default();
+    let prepared = match BatchParams::load(Path::new(&args.params))
+        .and_then(|file| {
+            file.apply(&mut params);
+            params.prepare()
+        })
+        .and_then(|prepared| prepared.validate().map(|()| prepared))
+    {
+        Ok(prepared) => prepared,
+        Err(msg) => {
+            eprintln!("Error: {}", msg);
+            return exit_codes::INPUT_ERROR;
+        }
+    };
+
+    let output_dir = match std::fs::create_dir_all(&args.output_dir)
+        .and_then(|()| Path::new(&args.output_dir).canonicalize())
+    {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!(
+                "Error: Failed to create output directory '{}': {}",
+                args.output_dir, e
+            );
+            return exit_codes::EXECUTION_ERROR;
+        }
+    };
+
+    // Start watching before the initial scan so no file slips in between
+    let (sender, events) = mpsc::channel();
+    let _watcher = if args.once {
+        drop(sender);
+        None
+    } else {
+        let watcher = notify::recommended_watcher(sender).and_then(|mut watcher| {
+            watcher
+                .watch(&watch_dir, RecursiveMode::Recursive)
+                .map(|()| watcher)
+        });
+        match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(error) => {
+                eprintln!("Error: Failed to watch '{}': {}", args.dir, error);
+                return exit_codes::EXECUTION_ERROR;
+            }
+        }
+    };
+
+    let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+    // Stamp of each file when it was last analyzed
+    let mut analyzed: HashMap<PathBuf, Stamp> = HashMap::new();
+    if args.existing || args.once {
+        let mut files = Vec::new();
+        scan_dir(&watch_dir, &output_dir, &mut files);
+        for path in files {
+            // A one-off run cannot wait for empty placeholders to fill
+            let empty = stamp_of(&path).is_some_and(|(len, _)| len == 0);
+            if (!args.once || !empty) && !has_current_result(&path, &watch_dir, &output_dir) {
+                track(&mut pending, path);
+            }
+        }
+    }
+
+    if !args.quiet {
+        if args.once {
+            eprintln!("Analyzing {} file(s) in {}", pending.len(), args.dir);
+        } else {
+            eprintln!(
+                "Watching {} (results in {}), press Ctrl+C to stop",
+                args.dir, args.output_dir
+            );
+        }
+    }
+
+    let (mut succeeded, mut failed) = (0usize, 0usize);
+    loop {
+        let ready = take_settled(&mut pending, &analyzed, settle);
+        if !ready.is_empty() {
+            for (path, stamp, outcome) in analyze(&ready, &prepared, &watch_dir, &output_dir, &args)
+            {
+                match outcome {
+                    Ok(result_path) => {
+                        succeeded += 1;
+                        if !args.quiet {
+                            eprintln!("{} -> {}", path.display(), result_path.display());
+                        }
+                    }
+                    Err(error) => {
+                        failed += 1;
+                        eprintln!("  {}: {}", path.display(), error);
+                    }
+                }
+                // Failed files are retried only once they change again
+                analyzed.insert(path, stamp);
+            }
+        }
+        if args.once && pending.is_empty() {
+            break;
+        }
+
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                if event.kind.is_create() || event.kind.is_modify() {
+                    for path in event.paths {
+                        if is_candidate(&path, &output_dir) {
+                            track(&mut pending, path);
+                        }
+                    }
+                }
+            }
+            Ok(Err(error)) => log::warn!("Watch error: {}", error),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                if !args.once {
+                    eprintln!("Error: Stopped receiving file events for '{}'", args.dir);
+                    return exit_codes::EXECUTION_ERROR;
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+
+    if !args.quiet {
+        eprintln!("Watch complete: {} succeeded, {} failed", succeeded, failed);
+    }
+    if failed == 0 {
+        exit_codes::SUCCESS
+    } else if succeeded > 0 {
+        exit_codes::PARTIAL_FAILURE
+    } else {
+        exit_codes::EXECUTION_ERROR
+    }
+}
+
+fn track(pending: &mut HashMap<PathBuf, Pending>, path: PathBuf) {
+    pending.entry(path).or_insert_with(|| Pending {
+        stamp: None,
+        unchanged_since: Instant::now(),
+    });
+}
+
+fn stamp_of(path: &Path) -> Option<Stamp> {
+    let metadata = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// Remove and return the pending files unchanged for at least `settle`
+///
+/// Files that disappeared are dropped, and files whose stamp matches their
+/// last analysis (e.g. events caused by reading them) are not run again.
+fn take_settled(
+    pending: &mut HashMap<PathBuf, Pending>,
+    analyzed: &HashMap<PathBuf, Stamp>,
+    settle: Duration,
+) -> Vec<(PathBuf, Stamp)> {
+    let mut ready = Vec::new();
+    pending.retain(|path, entry| {
+        let Some(stamp) = stamp_of(path) else {
+            return false;
+        };
+        if analyzed.get(path) == Some(&stamp) {
+            return false;
+        }
+        if entry.stamp != Some(stamp) {
+            entry.stamp = Some(stamp);
+            entry.unchanged_since = Instant::now();
+        }
+        // Empty files are usually placeholders about to be written
+        if stamp.0 == 0 || entry.unchanged_since.elapsed() < settle {
+            return true;
+        }
+        ready.push((path.clone(), stamp));
+        false
+    });
+    ready.sort();
+    ready
+}
+
+/// Run the analysis on `files` and write each result into the output tree
+fn analyze(
+    files: &[(PathBuf, Stamp)],
+    prepared: &PreparedParams,
+    watch_dir: &Path,
+    output_dir: &Path,
+    args: &WatchArgs,
+) -> Vec<(PathBuf, Stamp, Result<PathBuf, String>)> {
+    let mut outcomes = Vec::with_capacity(files.len());
+    let mut requests = Vec::new();
+    // Entry in `files` behind each request
+    let mut request_files = Vec::new();
+    for (index, (path, stamp)) in files.iter().enumerate() {
+        match prepared.request_for(&path.to_string_lossy()) {
+            Ok(request) => {
+                requests.push(request);
+                request_files.push(index);
+            }
+            Err(error) => outcomes.push((path.clone(), *stamp, Err(error))),
+        }
+    }
+
+    let run = PureRustRunner::default().run_batch(requests, args.jobs);
+    for item in run {
+        let (path, stamp) = &files[request_files[item.index]];
+        let outcome = item
+            .result
+            .map_err(|error| format!("DDA execution failed: Pure Rust DDA failed: {}", error))
+            .and_then(|result| output::to_json(&result, args.compact))
+            .and_then(|json| {
+                let result_path = result_path(path, watch_dir, output_dir);
+                write_atomically(&json, &result_path).map(|()| result_path)
+            });
+        outcomes.push((path.clone(), *stamp, outcome));
+    }
+    outcomes
+}
+
+/// `<output_dir>/<dir relative to the watched directory>/<stem>/dda.json`
+fn result_path(input: &Path, watch_dir: &Path, output_dir: &Path) -> PathBuf {
+    let relative = input.strip_prefix(watch_dir).unwrap_or(input);
+    let stem = relative
+        .file_stem()
+        .map(|stem| stem.to_os_string())
+        .unwrap_or_default();
+    let mut path = output_dir.to_path_buf();
+    if let Some(parent) = relative.parent() {
+        path.push(parent);
+    }
+    path.push(stem);
+    path.push(RESULT_FILE_NAME);
+    path
+}
+
+/// Whether a result newer than the input already exists
+fn has_current_result(input: &Path, watch_dir: &Path, output_dir: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (
+        modified(input),
+        modified(&result_path(input, watch_dir, output_dir)),
+    ) {
+        (Some(input), Some(result)) => result >= input,
+        _ => false,
+    }
+}
+
+/// Write beside the destination first so readers never see a partial result
+fn write_atomically(json: &str, path: &Path) -> Result<(), String> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir).map_err(|error| {
+        format!(
+            "Error creating result directory '{}': {}",
+            dir.display(),
+            error
+        )
+    })?;
+    let partial = path.with_extension("json.part");
+    output::write_output(json, partial.to_str())?;
+    std::fs::rename(&partial, path)
+        .map_err(|error| format!("Failed to move result to '{}': {}", path.display(), error))
+}
+
+/// A supported data file that is not hidden, partial or inside the output tree
+fn is_candidate(path: &Path, output_dir: &Path) -> bool {
+    if path.starts_with(output_dir) || !dda_params::is_supported_extension(path) {
+        return false;
+    }
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    !name.starts_with('.') && !PARTIAL_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+fn scan_dir(dir: &Path, output_dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if path.is_dir() {
+            if !hidden && !path.starts_with(output_dir) {
+                scan_dir(&path, output_dir, files);
+            }
+        } else if is_candidate(&path, output_dir) {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_files_settle_before_analysis() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("rec.ascii");
+        fs::write(&file, "1 2\n").unwrap();
+
+        let mut pending = HashMap::new();
+        let mut analyzed = HashMap::new();
+        track(&mut pending, file.clone());
+        // First sighting only records the stamp
+        assert!(take_settled(&mut pending, &analyzed, Duration::from_secs(60)).is_empty());
+        assert_eq!(pending.len(), 1);
+
+        let ready = take_settled(&mut pending, &analyzed, Duration::ZERO);
+        assert_eq!(ready.len(), 1);
+        assert!(pending.is_empty());
+
+        // Unchanged files are not analyzed twice; removed files are dropped
+        analyzed.insert(file.clone(), ready[0].1);
+        track(&mut pending, file.clone());
+        assert!(take_settled(&mut pending, &analyzed, Duration::ZERO).is_empty());
+        assert!(pending.is_empty());
+        track(&mut pending, tmp.path().join("gone.ascii"));
+        assert!(take_settled(&mut pending, &analyzed, Duration::ZERO).is_empty());
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_candidates_and_result_paths() {
+        let watch_dir = Path::new("/data/incoming");
+        let output_dir = Path::new("/data/incoming/results");
+        assert!(is_candidate(&watch_dir.join("a/rec.edf"), output_dir));
+        assert!(!is_candidate(&watch_dir.join("rec.edf.part"), output_dir));
+        assert!(!is_candidate(&watch_dir.join(".rec.edf"), output_dir));
+        assert!(!is_candidate(&watch_dir.join("notes.md"), output_dir));
+        assert!(!is_candidate(&output_dir.join("rec.csv"), output_dir));
+
+        assert_eq!(
+            result_path(&watch_dir.join("sub-01/rec.edf"), watch_dir, output_dir),
+            output_dir.join("sub-01/rec/dda.json")
+        );
+    }
+}
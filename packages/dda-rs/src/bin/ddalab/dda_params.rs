@@ -2,21 +2,15 @@ use crate::cli;
 use dda_rs::{
     format_select_mask, generate_select_mask, run_request_on_ascii_file_with_progress,
     run_request_on_f64_matrix_file_with_progress, run_request_on_matrix_with_progress,
-    AlgorithmSelection, DDARequest, DDAResult, DelayParameters, FileType, ModelParameters,
-    PreprocessingOptions, PureRustProgress, TimeRange, VariantChannelConfig, WindowParameters,
+    AlgorithmSelection, AnalysisSession, DDARequest, DDAResult, DelayParameters, FileType,
+    ModelParameters, PreprocessingOptions, PureRustProgress, PureRustRunner, StitchedRecording,
+    StitchedRun, TimeRange, VariantChannelConfig, WindowParameters,
 };
 use serde::Deserialize;
 use std::collections::{BTreeSet, HashMap};
 use std::path::Path;
 
 fn pure_rust_common_support_reason(request: &DDARequest) -> Result<(), String> {
-    if request.preprocessing_options.highpass.is_some()
-        || request.preprocessing_options.lowpass.is_some()
-    {
-        return Err(
-            "pure Rust DDA does not yet implement highpass/lowpass preprocessing".to_string(),
-        );
-    }
     if request
         .delay_parameters
         .delays
@@ -59,14 +53,6 @@ pub fn pure_rust_matrix_support_reason(request: &DDARequest) -> Result<(), Strin
     pure_rust_common_support_reason(request)
 }
 
-pub async fn execute_request(
-    request: &DDARequest,
-    start_bound: Option<u64>,
-    end_bound: Option<u64>,
-) -> Result<DDAResult, String> {
-    execute_request_with_progress(request, start_bound, end_bound, |_| {}).await
-}
-
 pub async fn execute_request_with_progress<F>(
     request: &DDARequest,
     start_bound: Option<u64>,
@@ -89,6 +75,61 @@ where
     .map_err(|error| format!("Pure Rust DDA failed: {}", error))
 }
 
+/// Like [`execute_request_with_progress`], but reuses parsed input cached in `session`.
+pub async fn execute_request_in_session_with_progress<F>(
+    session: &AnalysisSession,
+    request: &DDARequest,
+    start_bound: Option<u64>,
+    end_bound: Option<u64>,
+    on_progress: F,
+) -> Result<DDAResult, String>
+where
+    F: FnMut(&PureRustProgress),
+{
+    pure_rust_support_reason(request)
+        .map_err(|reason| format!("Pure Rust DDA cannot execute this request: {}", reason))?;
+
+    session
+        .run_ascii_file_with_progress(
+            request,
+            &request.file_path,
+            start_bound,
+            end_bound,
+            on_progress,
+        )
+        .map_err(|error| format!("Pure Rust DDA failed: {}", error))
+}
+
+/// Like [`execute_request_with_progress`], but on `files` joined into one recording.
+pub async fn execute_stitched_request_with_progress<F>(
+    request: &DDARequest,
+    files: &[&str],
+    start_bound: Option<u64>,
+    end_bound: Option<u64>,
+    on_progress: F,
+) -> Result<StitchedRun, String>
+where
+    F: FnMut(&PureRustProgress),
+{
+    for file in files {
+        let mut part_request = request.clone();
+        part_request.file_path = file.to_string();
+        pure_rust_support_reason(&part_request)
+            .map_err(|reason| format!("Pure Rust DDA cannot execute this request: {}", reason))?;
+    }
+
+    let recording = StitchedRecording::from_ascii_files(files)
+        .map_err(|error| format!("Cannot stitch input files: {}", error))?;
+    let mut request = request.clone();
+    if let (Some(start), Some(end)) = (start_bound, end_bound) {
+        request.time_range.start = start as f64;
+        request.time_range.end = end as f64;
+    }
+    PureRustRunner::default()
+        .run_stitched_with_progress(&request, &recording, on_progress)
+        .map_err(|error| format!("Pure Rust DDA failed: {}", error))
+}
+
 pub async fn execute_request_on_matrix_with_progress<F>(
     request: &DDARequest,
     samples: &[Vec<f64>],
@@ -483,6 +524,9 @@ pub struct RequestConfig<'a> {
 
 /// Build a DDA request from normalized CLI or sidecar options.
 pub fn build_dda_request(config: RequestConfig<'_>) -> Result<DDARequest, String> {
+    if (config.highpass.is_some() || config.lowpass.is_some()) && config.sampling_rate.is_none() {
+        return Err("highpass/lowpass filtering needs the sampling rate (--sr)".to_string());
+    }
     let normalized_variants = normalize_variants(config.variants)?;
     let variant_refs: Vec<&str> = normalized_variants.iter().map(|s| s.as_str()).collect();
     let mask = generate_select_mask(&variant_refs);
@@ -571,7 +615,6 @@ pub fn compute_bounds(
 }
 
 /// Check if a file extension is supported for DDA analysis.
-#[allow(dead_code)]
 pub fn is_supported_extension(path: &Path) -> bool {
     path.extension()
         .and_then(|e| e.to_str())
@@ -787,7 +830,9 @@ mod tests {
         ))
         .unwrap();
 
-        let result = execute_request(&request, None, None).await.unwrap();
+        let result = execute_request_with_progress(&request, None, None, |_| {})
+            .await
+            .unwrap();
         let variants = result.variant_results.unwrap();
         assert_eq!(variants.len(), 1);
         assert_eq!(variants[0].variant_id, "ST");
@@ -806,7 +851,9 @@ mod tests {
         ))
         .unwrap();
 
-        let error = execute_request(&request, None, None).await.unwrap_err();
+        let error = execute_request_with_progress(&request, None, None, |_| {})
+            .await
+            .unwrap_err();
         assert!(error.contains("Pure Rust DDA cannot execute this request"));
         assert!(error.contains("ASCII/TXT/CSV"));
     }
@@ -2,3 +2,4 @@ pub const SUCCESS: i32 = 0;
 pub const INPUT_ERROR: i32 = 1;
 pub const EXECUTION_ERROR: i32 = 3;
 pub const PARTIAL_FAILURE: i32 = 4;
+pub const DIFFERENCES_FOUND: i32 = 5;
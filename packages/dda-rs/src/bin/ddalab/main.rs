@@ -1,11 +1,14 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use rayon::ThreadPoolBuilder;
+use std::path::PathBuf;
 
 mod cli;
 mod commands;
 mod dda_params;
 mod exit_codes;
 mod output;
+mod profiles;
+mod progress;
 
 use cli::Cli;
 
@@ -17,9 +20,29 @@ enum RayonMode {
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    if let Some(name) = cli.profile.clone() {
+        let path = cli
+            .config
+            .clone()
+            .map(PathBuf::from)
+            .or_else(profiles::default_config_path)
+            .ok_or_else(|| "No config directory available; pass --config".to_string());
+        let applied = path
+            .and_then(|path| profiles::load_profile(&path, &name))
+            .and_then(|profile| profiles::apply_profile(profile, &mut cli.command, &matches));
+        if let Err(msg) = applied {
+            eprintln!("Error: {}", msg);
+            std::process::exit(exit_codes::INPUT_ERROR);
+        }
+    }
     let prefer_ui_responsiveness = matches!(&cli.command, cli::Command::Serve(_));
-    configure_rayon_pool(prefer_ui_responsiveness);
+    let jobs = match &cli.command {
+        cli::Command::Run(args) => args.jobs,
+        _ => None,
+    };
+    configure_rayon_pool(prefer_ui_responsiveness, jobs);
 
     let log_level = match cli.verbose {
         0 => log::LevelFilter::Warn,
@@ -38,21 +61,28 @@ async fn main() {
         cli::Command::Variants(args) => commands::variants::execute(args),
         cli::Command::Validate(args) => commands::validate::execute(args),
         cli::Command::Batch(args) => commands::batch::execute(args).await,
+        cli::Command::Convert(args) => commands::convert::execute(args),
+        cli::Command::Diff(args) => commands::diff::execute(args),
+        cli::Command::Watch(args) => commands::watch::execute(args),
+        cli::Command::Bids(args) => commands::bids::execute(args),
+        cli::Command::Render(args) => commands::render::execute(args),
         cli::Command::Serve(args) => commands::serve::execute(args).await,
     };
 
     std::process::exit(exit_code);
 }
 
-fn configure_rayon_pool(prefer_ui_responsiveness: bool) {
+fn configure_rayon_pool(prefer_ui_responsiveness: bool, jobs: Option<usize>) {
     let available_threads = std::thread::available_parallelism()
         .map(|count| count.get())
         .unwrap_or(1);
     let mode_override = std::env::var("DDALAB_RAYON_MODE").ok();
-    let explicit_threads = std::env::var("DDALAB_RAYON_THREADS")
-        .ok()
-        .and_then(|value| value.parse::<usize>().ok())
-        .filter(|value| *value > 0);
+    let explicit_threads = jobs.filter(|value| *value > 0).or_else(|| {
+        std::env::var("DDALAB_RAYON_THREADS")
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|value| *value > 0)
+    });
     let target_threads = resolve_rayon_thread_count(
         prefer_ui_responsiveness,
         available_threads,
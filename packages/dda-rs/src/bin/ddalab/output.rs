@@ -1,11 +1,42 @@
+use crate::cli::OutputFormat;
+use dda_rs::{create_export_writer, Compression, DDAResult};
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde::Serialize;
 use std::io::Write;
 use std::path::Path;
+use std::sync::Arc;
+
+/// Variant id of results without `variant_results`
+const PRIMARY_VARIANT: &str = "primary";
+/// Column layout of the CSV and Parquet tables
+const Q_TABLE_SCHEMA: &str = "message dda_q_matrix {
+    REQUIRED BYTE_ARRAY variant (UTF8);
+    REQUIRED BYTE_ARRAY label (UTF8);
+    REQUIRED INT64 window;
+    REQUIRED DOUBLE q;
+}";
 
 /// Write JSON string to stdout or a file.
+///
+/// Paths ending in `.gz` are written through a streaming gzip encoder.
 pub fn write_output(json: &str, output_path: Option<&str>) -> Result<(), String> {
     match output_path {
-        Some(path) => std::fs::write(Path::new(path), json)
-            .map_err(|e| format!("Failed to write output file '{}': {}", path, e)),
+        Some(path) => {
+            let path_ref = Path::new(path);
+            let compression = Compression::from_path(path_ref);
+            create_export_writer(path_ref, compression)
+                .map_err(|e| e.to_string())
+                .and_then(|mut writer| {
+                    writer
+                        .write_all(json.as_bytes())
+                        .map_err(|e| e.to_string())?;
+                    writer.finish().map_err(|e| e.to_string())
+                })
+                .map_err(|e| format!("Failed to write output file '{}': {}", path, e))
+        }
         None => {
             let stdout = std::io::stdout();
             let mut handle = stdout.lock();
@@ -35,3 +66,229 @@ pub fn write_json<T: serde::Serialize>(
     let json = to_json(value, compact)?;
     write_output(&json, output_path)
 }
+
+/// One Q-matrix row with its summary statistics (a `jsonl` line)
+#[derive(Debug, Serialize)]
+pub struct RowSummary<'a> {
+    pub variant: &'a str,
+    pub label: String,
+    pub windows: usize,
+    /// Windows with a finite value; the statistics cover only these
+    pub finite: usize,
+    pub mean: Option<f64>,
+    pub std: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub q: &'a [f64],
+}
+
+/// Variant id, row labels and Q matrix
+type LabeledMatrix<'a> = (&'a str, &'a [String], &'a [Vec<f64>]);
+
+/// Every Q-matrix row of every variant, in result order
+pub fn row_summaries(result: &DDAResult) -> Vec<RowSummary<'_>> {
+    let matrices: Vec<LabeledMatrix<'_>> = match result.variant_results.as_deref() {
+        Some(variants) if !variants.is_empty() => variants
+            .iter()
+            .map(|variant| {
+                (
+                    variant.variant_id.as_str(),
+                    variant.channel_labels.as_deref().unwrap_or_default(),
+                    variant.q_matrix.as_slice(),
+                )
+            })
+            .collect(),
+        _ => vec![(
+            PRIMARY_VARIANT,
+            result.channels.as_slice(),
+            result.q_matrix.as_slice(),
+        )],
+    };
+
+    let mut rows = Vec::new();
+    for (variant, labels, q_matrix) in matrices {
+        for (index, q) in q_matrix.iter().enumerate() {
+            let finite: Vec<f64> = q.iter().copied().filter(|v| v.is_finite()).collect();
+            let count = finite.len();
+            let mean = (count > 0).then(|| finite.iter().sum::<f64>() / count as f64);
+            let std = mean.map(|mean| {
+                (finite.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64).sqrt()
+            });
+            rows.push(RowSummary {
+                variant,
+                label: labels
+                    .get(index)
+                    .cloned()
+                    .unwrap_or_else(|| format!("row{}", index)),
+                windows: q.len(),
+                finite: count,
+                mean,
+                std,
+                min: finite.iter().copied().reduce(f64::min),
+                max: finite.iter().copied().reduce(f64::max),
+                q,
+            });
+        }
+    }
+    rows
+}
+
+/// One JSON object per line, see [`RowSummary`]
+pub fn to_jsonl(result: &DDAResult) -> Result<String, String> {
+    let lines = row_summaries(result)
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("JSON serialization failed: {}", e))?;
+    Ok(lines.join("\n"))
+}
+
+/// Long table with one `variant,label,window,q` line per value
+pub fn to_csv(result: &DDAResult) -> String {
+    let mut csv = String::from("variant,label,window,q");
+    for row in row_summaries(result) {
+        let (variant, label) = (csv_field(row.variant), csv_field(&row.label));
+        for (window, value) in row.q.iter().enumerate() {
+            csv.push_str(&format!("\n{},{},{},{}", variant, label, window, value));
+        }
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Write the table of [`to_csv`] as a single-row-group Parquet file
+pub fn write_parquet(result: &DDAResult, path: &str) -> Result<(), String> {
+    let rows = row_summaries(result);
+    let mut variants = Vec::new();
+    let mut labels = Vec::new();
+    let mut windows = Vec::new();
+    let mut values = Vec::new();
+    for row in &rows {
+        for (window, value) in row.q.iter().enumerate() {
+            variants.push(ByteArray::from(row.variant));
+            labels.push(ByteArray::from(row.label.as_str()));
+            windows.push(window as i64);
+            values.push(*value);
+        }
+    }
+
+    let write = || -> parquet::errors::Result<()> {
+        let schema = Arc::new(parse_message_type(Q_TABLE_SCHEMA)?);
+        let file = std::fs::File::create(path)?;
+        let properties = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(file, schema, properties)?;
+        let mut row_group = writer.next_row_group()?;
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            match index {
+                0 => column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&variants, None, None)?,
+                1 => column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&labels, None, None)?,
+                2 => column
+                    .typed::<Int64Type>()
+                    .write_batch(&windows, None, None)?,
+                _ => column
+                    .typed::<DoubleType>()
+                    .write_batch(&values, None, None)?,
+            };
+            column.close()?;
+            index += 1;
+        }
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    };
+    write().map_err(|e| format!("Failed to write Parquet file '{}': {}", path, e))
+}
+
+/// Write `result` in `format` to stdout or a file
+pub fn write_result(
+    result: &DDAResult,
+    format: OutputFormat,
+    compact: bool,
+    output_path: Option<&str>,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Json => write_json(result, compact, output_path),
+        OutputFormat::Jsonl => write_output(&to_jsonl(result)?, output_path),
+        OutputFormat::Csv => write_output(&to_csv(result), output_path),
+        OutputFormat::Parquet => match output_path {
+            Some(path) => write_parquet(result, path),
+            None => Err("Parquet output requires an output file".to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dda_rs::{DelayParameters, VariantResult, WindowParameters};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn result() -> DDAResult {
+        let mut result = DDAResult::new(
+            "r".to_string(),
+            "in.ascii".to_string(),
+            vec!["a".to_string(), "b".to_string()],
+            Vec::new(),
+            WindowParameters {
+                window_length: 64,
+                window_step: 32,
+                ct_window_length: None,
+                ct_window_step: None,
+            },
+            DelayParameters { delays: vec![1, 2] },
+        );
+        result.variant_results = Some(vec![VariantResult {
+            variant_id: "CT".to_string(),
+            variant_name: "Cross Timeseries (CT)".to_string(),
+            q_matrix: vec![vec![1.0, f64::NAN, 3.0]],
+            channel_labels: Some(vec!["a,b".to_string()]),
+            error_values: None,
+            row_channels: None,
+        }]);
+        result
+    }
+
+    #[test]
+    fn test_table_formats() {
+        let result = result();
+        let rows = row_summaries(&result);
+        assert_eq!((rows[0].windows, rows[0].finite), (3, 2));
+        assert_eq!((rows[0].mean, rows[0].std), (Some(2.0), Some(1.0)));
+        assert_eq!((rows[0].min, rows[0].max), (Some(1.0), Some(3.0)));
+
+        let line: serde_json::Value = serde_json::from_str(&to_jsonl(&result).unwrap()).unwrap();
+        assert_eq!(line["label"], "a,b");
+        assert!(line["q"][1].is_null());
+
+        let csv = to_csv(&result);
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            [
+                "variant,label,window,q",
+                "CT,\"a,b\",0,1",
+                "CT,\"a,b\",1,NaN",
+                "CT,\"a,b\",2,3"
+            ]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("q.parquet");
+        write_parquet(&result, path.to_str().unwrap()).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 3);
+        assert_eq!(metadata.schema_descr().num_columns(), 4);
+    }
+}
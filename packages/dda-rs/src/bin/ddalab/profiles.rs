@@ -0,0 +1,182 @@
+use crate::cli::Command;
+use crate::commands::batch::BatchParams;
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// `cli.toml` below the user configuration directory
+const CONFIG_FILE_NAME: &str = "cli.toml";
+
+/// The CLI configuration file
+///
+/// ```toml
+/// [profiles.clinical]
+/// variants = ["ST", "CT"]
+/// wl = 200
+/// ws = 100
+/// delays = [7, 10]
+/// ct_pairs = ["0,1"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CliConfig {
+    #[serde(default)]
+    profiles: BTreeMap<String, toml::Table>,
+}
+
+/// Named parameter set; keys match the `run` and `batch` flags
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub params: BatchParams,
+    pub binary: Option<String>,
+}
+
+/// `$XDG_CONFIG_HOME/ddalab/cli.toml`, by default `~/.config/ddalab/cli.toml`
+pub fn default_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join("ddalab").join(CONFIG_FILE_NAME))
+}
+
+/// Read profile `name` from the configuration file at `path`
+pub fn load_profile(path: &Path, name: &str) -> Result<Profile, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read config file '{}': {}", path.display(), e))?;
+    let invalid = |e: toml::de::Error| format!("Invalid config file '{}': {}", path.display(), e);
+    let mut config: CliConfig = toml::from_str(&text).map_err(invalid)?;
+    let mut table = config.profiles.remove(name).ok_or_else(|| {
+        let known: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+        format!(
+            "Unknown profile '{}' in '{}' (available: {})",
+            name,
+            path.display(),
+            if known.is_empty() {
+                "none".to_string()
+            } else {
+                known.join(", ")
+            }
+        )
+    })?;
+
+    let binary = match table.remove("binary") {
+        Some(toml::Value::String(binary)) => Some(binary),
+        Some(_) => return Err(format!("Profile '{}': binary must be a string", name)),
+        None => None,
+    };
+    let mut params: BatchParams = table
+        .try_into()
+        .map_err(|e| format!("Profile '{}': {}", name, e))?;
+    if let Some(dir) = path.parent() {
+        params.resolve_paths(dir);
+    }
+    Ok(Profile { params, binary })
+}
+
+/// Copy profile values onto the arguments whose flags were not given
+macro_rules! fill_args {
+    ($profile:expr, $args:expr, $given:expr;
+     values: $($value:ident),*;
+     options: $($option:ident),*) => {{
+        let params = $profile.params;
+        $(
+            if let Some(value) = params.$value {
+                if !$given(stringify!($value)) {
+                    $args.$value = value;
+                }
+            }
+        )*
+        $(
+            if let Some(value) = params.$option {
+                if !$given(stringify!($option)) {
+                    $args.$option = Some(value);
+                }
+            }
+        )*
+        if let Some(binary) = $profile.binary {
+            if !$given("binary") {
+                $args.binary = Some(binary);
+            }
+        }
+    }};
+}
+
+/// Use `profile` for every `run` or `batch` parameter not set on the command
+/// line; `matches` are the parsed top-level arguments
+pub fn apply_profile(
+    profile: Profile,
+    command: &mut Command,
+    matches: &ArgMatches,
+) -> Result<(), String> {
+    let sub_matches = matches.subcommand().map(|(_, sub_matches)| sub_matches);
+    let given = |id: &str| {
+        sub_matches.is_some_and(|sub_matches| {
+            matches!(
+                sub_matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        })
+    };
+    match command {
+        Command::Run(args) => fill_args!(profile, args, given;
+            values: variants, wl, ws, delays, dm, order, nr_tau;
+            options: channels, ct_wl, ct_ws, model, ct_pairs, cd_pairs, variant_configs,
+                highpass, lowpass, sr),
+        Command::Batch(args) => fill_args!(profile, args, given;
+            values: variants, wl, ws, delays, dm, order, nr_tau;
+            options: channels, ct_wl, ct_ws, model, ct_pairs, cd_pairs, variant_configs,
+                highpass, lowpass, sr),
+        _ => return Err("--profile applies to the run and batch commands only".to_string()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::{CommandFactory, FromArgMatches};
+
+    #[test]
+    fn test_profile_fills_flags_not_given() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        std::fs::write(
+            &path,
+            "[profiles.clinical]\nwl = 64\nws = 16\nchannels = [0, 2]\nvariant_configs = \"variants.json\"\nbinary = \"/opt/dda\"\n\n[profiles.fast]\nwl = 32\n",
+        )
+        .unwrap();
+
+        let matches = Cli::command()
+            .try_get_matches_from(["ddalab", "run", "--file", "a.ascii", "--ws", "8"])
+            .unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        let profile = load_profile(&path, "clinical").unwrap();
+        apply_profile(profile, &mut cli.command, &matches).unwrap();
+        let Command::Run(args) = cli.command else {
+            panic!("expected run");
+        };
+        assert_eq!((args.wl, args.ws), (64, 8));
+        assert_eq!(args.channels, Some(vec![0, 2]));
+        if std::env::var_os("DDA_BINARY_PATH").is_none() {
+            assert_eq!(args.binary.as_deref(), Some("/opt/dda"));
+        }
+        assert_eq!(
+            args.variant_configs,
+            Some(
+                dir.path()
+                    .join("variants.json")
+                    .to_string_lossy()
+                    .into_owned()
+            )
+        );
+
+        let error = load_profile(&path, "research").unwrap_err();
+        assert!(error.contains("available: clinical, fast"), "{}", error);
+        std::fs::write(&path, "[profiles.bad]\nwindow = 64\n").unwrap();
+        assert!(load_profile(&path, "bad").is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! Progress bars with an ETA, redrawn in place on stderr
+//!
+//! Nothing is drawn unless stderr is a terminal, so redirected output and CI
+//! logs stay clean without passing `--no-progress`.
+
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Characters between the brackets of a bar
+const BAR_WIDTH: usize = 28;
+/// Minimum time between two redraws
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A block of progress lines at the bottom of the terminal
+pub struct ProgressDisplay {
+    enabled: bool,
+    started: Instant,
+    state: Mutex<DrawState>,
+}
+
+#[derive(Default)]
+struct DrawState {
+    lines: Vec<String>,
+    /// Lines currently on screen
+    drawn: usize,
+    last_draw: Option<Instant>,
+}
+
+impl ProgressDisplay {
+    /// A display that draws only if `enabled` and stderr is a terminal
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: enabled && std::io::stderr().is_terminal(),
+            started: Instant::now(),
+            state: Mutex::new(DrawState::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Replace the drawn lines, at most every [`REDRAW_INTERVAL`]
+    pub fn set_lines(&self, lines: Vec<String>) {
+        self.replace_lines(lines, true);
+    }
+
+    fn replace_lines(&self, lines: Vec<String>, throttle: bool) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.lines = lines;
+        if throttle
+            && state
+                .last_draw
+                .is_some_and(|last| last.elapsed() < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        state.last_draw = Some(Instant::now());
+        redraw(&mut state, None);
+    }
+
+    /// Print `message` on stderr above the progress lines
+    pub fn println(&self, message: &str) {
+        if !self.enabled {
+            eprintln!("{}", message);
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        redraw(&mut state, Some(message));
+    }
+
+    /// Remove the progress lines from the terminal
+    pub fn finish(&self) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.lines.clear();
+        redraw(&mut state, None);
+    }
+}
+
+/// An overall bar and one bar per file in flight, for batch runs
+pub struct BatchBars {
+    display: ProgressDisplay,
+    state: Mutex<BatchBarsState>,
+}
+
+#[derive(Default)]
+struct BatchBarsState {
+    overall: String,
+    /// File name, fraction and start time of each running item
+    active: BTreeMap<usize, (String, f64, Instant)>,
+}
+
+impl BatchBarsState {
+    fn lines(&self) -> Vec<String> {
+        std::iter::once(self.overall.clone())
+            .chain(
+                self.active
+                    .values()
+                    .map(|(name, fraction, started)| bar_line(*fraction, started.elapsed(), name)),
+            )
+            .collect()
+    }
+}
+
+impl BatchBars {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            display: ProgressDisplay::new(enabled),
+            state: Mutex::new(BatchBarsState::default()),
+        }
+    }
+
+    /// Record a progress report from a batch worker and redraw
+    pub fn update(&self, progress: &dda_rs::BatchProgress) {
+        if !self.display.is_enabled() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.overall = bar_line(
+            progress.fraction(),
+            self.display.elapsed(),
+            &format!(
+                "{}/{} files done",
+                progress.completed + progress.failed,
+                progress.total
+            ),
+        );
+        let entry = state.active.entry(progress.item_index).or_insert_with(|| {
+            let name = Path::new(&progress.file_path)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| progress.file_path.clone());
+            (name, 0.0, Instant::now())
+        });
+        entry.1 = progress.item.fraction();
+        self.display.set_lines(state.lines());
+    }
+
+    /// Remove the bar of a finished item
+    pub fn item_done(&self, index: usize) {
+        if !self.display.is_enabled() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.active.remove(&index).is_some() {
+            self.display.replace_lines(state.lines(), false);
+        }
+    }
+
+    pub fn println(&self, message: &str) {
+        self.display.println(message);
+    }
+
+    pub fn finish(&self) {
+        self.display.finish();
+    }
+}
+
+/// Erase the lines on screen, print `message` if any, then draw the lines
+fn redraw(state: &mut DrawState, message: Option<&str>) {
+    let mut out = String::new();
+    if state.drawn > 0 {
+        out.push_str(&format!("\x1b[{}A", state.drawn));
+    }
+    out.push_str("\r\x1b[J");
+    if let Some(message) = message {
+        out.push_str(message);
+        out.push('\n');
+    }
+    for line in &state.lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+    state.drawn = state.lines.len();
+
+    let mut stderr = std::io::stderr().lock();
+    let _ = stderr.write_all(out.as_bytes());
+    let _ = stderr.flush();
+}
+
+/// `[#########...........]  42% ETA 0:12 | <message>`
+pub fn bar_line(fraction: f64, elapsed: Duration, message: &str) -> String {
+    let fraction = if fraction.is_finite() {
+        fraction.clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let filled = (fraction * BAR_WIDTH as f64).round() as usize;
+    format!(
+        "[{}{}] {:>3}% ETA {} | {}",
+        "#".repeat(filled),
+        ".".repeat(BAR_WIDTH - filled),
+        (fraction * 100.0).floor() as u8,
+        eta(fraction, elapsed),
+        message
+    )
+}
+
+/// Remaining time assuming the current rate holds
+fn eta(fraction: f64, elapsed: Duration) -> String {
+    if fraction <= 0.0 {
+        return "--:--".to_string();
+    }
+    let remaining = elapsed.as_secs_f64() * (1.0 - fraction) / fraction;
+    format_duration(remaining.round() as u64)
+}
+
+/// `m:ss`, or `h:mm:ss` from one hour on
+fn format_duration(secs: u64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_line_and_eta() {
+        let line = bar_line(0.25, Duration::from_secs(30), "window 2/8");
+        assert_eq!(
+            line,
+            "[#######.....................]  25% ETA 1:30 | window 2/8"
+        );
+        assert!(bar_line(0.0, Duration::from_secs(1), "").contains("ETA --:--"));
+        assert!(bar_line(f64::NAN, Duration::ZERO, "").starts_with("[...."));
+        assert_eq!(format_duration(3725), "1:02:05");
+    }
+}
@@ -0,0 +1,267 @@
+//! Content-addressed caching of DDA results
+//!
+//! A [`CacheKey`] digests the input file's bytes, the request parameters
+//! (with the file path removed, so renamed or copied inputs still hit), the
+//! analysis bounds, the engine options and the crate version. A
+//! [`ResultCache`] looks results up by that key in a pluggable
+//! [`ResultStore`]; [`DirectoryStore`] keeps them as JSON files on disk.
+
+use crate::engine::PureRustRunner;
+use crate::error::{DDAError, Result};
+use crate::types::{DDARequest, DDAResult};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Hex-encoded SHA-256 identifying one analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// Key for running `request` with `runner` on the file at `request.file_path`.
+    pub fn for_request(
+        runner: &PureRustRunner,
+        request: &DDARequest,
+        start_bound: Option<u64>,
+        end_bound: Option<u64>,
+    ) -> Result<Self> {
+        let mut hasher = Sha256::new();
+        hasher.update(b"dda-rs\0");
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update(b"\0");
+        hasher.update(format!("{:?}", runner.options()).as_bytes());
+        hasher.update(b"\0");
+        hasher.update(format!("{:?}", runner.preprocessing().steps()).as_bytes());
+        hasher.update(b"\0");
+
+        let mut reader = BufReader::new(File::open(&request.file_path)?);
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        hasher.update(b"\0");
+
+        // serde_json maps are ordered, so variant configs hash deterministically
+        let mut parameters = request.clone();
+        parameters.file_path.clear();
+        let parameters = serde_json::to_value(&parameters)
+            .map_err(|e| DDAError::InvalidParameter(format!("unhashable request: {}", e)))?;
+        hasher.update(parameters.to_string().as_bytes());
+        hasher.update(format!("\0{:?}:{:?}", start_bound, end_bound).as_bytes());
+
+        let digest = hasher.finalize();
+        Ok(Self(
+            digest.iter().map(|byte| format!("{byte:02x}")).collect(),
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Backing storage for cached results.
+pub trait ResultStore: Send + Sync {
+    fn get(&self, key: &CacheKey) -> Result<Option<DDAResult>>;
+    fn put(&self, key: &CacheKey, result: &DDAResult) -> Result<()>;
+}
+
+/// Stores each result as `<root>/<first two hex digits>/<key>.json`.
+pub struct DirectoryStore {
+    root: PathBuf,
+}
+
+impl DirectoryStore {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Per-user default location (`~/.cache/ddalab/results` on Linux).
+    pub fn default_root() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("ddalab").join("results"))
+    }
+
+    fn path_for(&self, key: &CacheKey) -> PathBuf {
+        self.root
+            .join(&key.as_str()[..2])
+            .join(format!("{}.json", key.as_str()))
+    }
+}
+
+impl ResultStore for DirectoryStore {
+    fn get(&self, key: &CacheKey) -> Result<Option<DDAResult>> {
+        let path = self.path_for(key);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // A truncated or stale-format entry is a miss, not an error
+        Ok(serde_json::from_slice(&bytes).ok())
+    }
+
+    fn put(&self, key: &CacheKey, result: &DDAResult) -> Result<()> {
+        let path = self.path_for(key);
+        let dir = path.parent().unwrap_or(&self.root);
+        std::fs::create_dir_all(dir)?;
+        let json = serde_json::to_vec(result)
+            .map_err(|e| DDAError::ParseError(format!("failed to serialize result: {}", e)))?;
+
+        // Write then rename so concurrent readers never see a partial entry
+        let tmp = dir.join(format!(".{}.{}.tmp", key.as_str(), std::process::id()));
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+/// Short-circuits analyses whose result is already in a [`ResultStore`].
+pub struct ResultCache {
+    store: Box<dyn ResultStore>,
+}
+
+impl ResultCache {
+    pub fn new<S: ResultStore + 'static>(store: S) -> Self {
+        Self {
+            store: Box::new(store),
+        }
+    }
+
+    /// Cache backed by a [`DirectoryStore`] at `root`.
+    pub fn in_directory<P: AsRef<Path>>(root: P) -> Self {
+        Self::new(DirectoryStore::new(root.as_ref()))
+    }
+
+    /// Cached result for `key`, attributed to `request`'s input path.
+    pub fn lookup(&self, key: &CacheKey, request: &DDARequest) -> Result<Option<DDAResult>> {
+        Ok(self.store.get(key)?.map(|mut cached| {
+            cached.file_path.clone_from(&request.file_path);
+            cached
+        }))
+    }
+
+    /// Store `result` under `key`.
+    ///
+    /// Failures are logged rather than returned so a read-only or full cache
+    /// never fails an analysis.
+    pub fn insert(&self, key: &CacheKey, result: &DDAResult) {
+        if let Err(e) = self.store.put(key, result) {
+            log::warn!("Failed to cache DDA result {}: {}", key.as_str(), e);
+        }
+    }
+
+    /// Return the cached result for `key`, or run `compute` and store its result.
+    ///
+    /// The flag is `true` on a cache hit.
+    pub fn get_or_run<F>(
+        &self,
+        key: &CacheKey,
+        request: &DDARequest,
+        compute: F,
+    ) -> Result<(DDAResult, bool)>
+    where
+        F: FnOnce() -> Result<DDAResult>,
+    {
+        if let Some(cached) = self.lookup(key, request)? {
+            return Ok((cached, true));
+        }
+        let result = compute()?;
+        self.insert(key, &result);
+        Ok((result, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        AlgorithmSelection, DelayParameters, PreprocessingOptions, TimeRange, WindowParameters,
+    };
+    use std::io::Write;
+
+    fn request_for(path: &Path) -> DDARequest {
+        DDARequest {
+            file_path: path.to_str().unwrap().to_string(),
+            channels: Some(vec![0, 1]),
+            time_range: TimeRange {
+                start: 0.0,
+                end: 399.0,
+            },
+            preprocessing_options: PreprocessingOptions {
+                highpass: None,
+                lowpass: None,
+            },
+            algorithm_selection: AlgorithmSelection {
+                enabled_variants: vec!["ST".to_string()],
+                select_mask: None,
+            },
+            window_parameters: WindowParameters {
+                window_length: 100,
+                window_step: 50,
+                ct_window_length: None,
+                ct_window_step: None,
+            },
+            delay_parameters: DelayParameters { delays: vec![1, 2] },
+            ct_channel_pairs: None,
+            cd_channel_pairs: None,
+            model_parameters: None,
+            model_terms: None,
+            variant_configs: None,
+            sampling_rate: None,
+        }
+    }
+
+    fn write_input(dir: &Path, name: &str, scale: f64) -> PathBuf {
+        let path = dir.join(name);
+        let mut file = File::create(&path).unwrap();
+        for t in 0..400 {
+            let x = (t as f64 * 0.05).sin() * scale;
+            writeln!(file, "{x:.12} {:.12}", (t as f64 * 0.03).cos()).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_key_depends_on_content_not_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = write_input(dir.path(), "a.txt", 1.0);
+        let copy = write_input(dir.path(), "copy.txt", 1.0);
+        let other = write_input(dir.path(), "other.txt", 2.0);
+        let runner = PureRustRunner::default();
+
+        let key = |path: &Path| CacheKey::for_request(&runner, &request_for(path), None, None);
+        assert_eq!(key(&a).unwrap(), key(&copy).unwrap());
+        assert_ne!(key(&a).unwrap(), key(&other).unwrap());
+
+        let mut wider = request_for(&a);
+        wider.window_parameters.window_step = 25;
+        assert_ne!(
+            key(&a).unwrap(),
+            CacheKey::for_request(&runner, &wider, None, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_directory_cache_short_circuits_second_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = write_input(dir.path(), "input.txt", 1.0);
+        let request = request_for(&input);
+        let runner = PureRustRunner::default();
+        let cache = ResultCache::in_directory(dir.path().join("cache"));
+        let key = CacheKey::for_request(&runner, &request, None, None).unwrap();
+
+        let run = || crate::input_io::run_request_on_ascii_file(&request, &input, None, None);
+        let (first, hit) = cache.get_or_run(&key, &request, run).unwrap();
+        assert!(!hit);
+        let (second, hit) = cache
+            .get_or_run(&key, &request, || panic!("should be cached"))
+            .unwrap();
+        assert!(hit);
+        assert_eq!(first.id, second.id);
+    }
+}
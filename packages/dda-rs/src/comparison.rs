@@ -0,0 +1,418 @@
+//! Cross-variant comparison statistics
+//!
+//! Variants of one run produce separate `[row][window]` matrices whose rows
+//! mean different things (a channel for ST, a group or pair for CT/CD/DE/SY).
+//! Here every variant is reduced to one series per input channel, the mean
+//! over the rows involving that channel, and two variants are compared per
+//! channel on the windows they share: Pearson correlation, histogram mutual
+//! information, and the Jensen-Shannon divergence of their standardized
+//! value distributions.
+
+use crate::error::{DDAError, Result};
+use crate::types::{DDAResult, VariantResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Window markers closer than this (in samples) are the same window
+const MARKER_TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ComparisonConfig {
+    /// Histogram bins for mutual information and divergence
+    pub bins: usize,
+    /// Channels with fewer shared finite windows are reported without statistics
+    pub min_windows: usize,
+}
+
+impl Default for ComparisonConfig {
+    fn default() -> Self {
+        Self {
+            bins: 16,
+            min_windows: 3,
+        }
+    }
+}
+
+/// Agreement of two variants on one input channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelComparison {
+    pub channel: usize,
+    /// Shared windows where both variants are finite
+    pub windows: usize,
+    /// Pearson correlation; NaN when either series is constant or too short
+    pub correlation: f64,
+    /// Mutual information in nats
+    pub mutual_information: f64,
+    /// Jensen-Shannon divergence in nats, `[0, ln 2]`
+    pub divergence: f64,
+}
+
+/// Per-channel comparison of two variants.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantComparison {
+    pub variant_a: String,
+    pub variant_b: String,
+    /// Windows present in both variants
+    pub common_windows: usize,
+    /// Channels covered by both variants, ascending
+    pub channels: Vec<ChannelComparison>,
+}
+
+impl VariantComparison {
+    pub fn channel(&self, channel: usize) -> Option<&ChannelComparison> {
+        self.channels.iter().find(|c| c.channel == channel)
+    }
+
+    /// Mean correlation over channels with a defined correlation.
+    pub fn mean_correlation(&self) -> f64 {
+        let finite = self
+            .channels
+            .iter()
+            .map(|c| c.correlation)
+            .filter(|r| r.is_finite())
+            .collect::<Vec<_>>();
+        if finite.is_empty() {
+            f64::NAN
+        } else {
+            finite.iter().sum::<f64>() / finite.len() as f64
+        }
+    }
+}
+
+/// Compare every pair of variants in `result`, in variant order.
+pub fn compare_all_variants(
+    result: &DDAResult,
+    config: &ComparisonConfig,
+) -> Result<Vec<VariantComparison>> {
+    let variants = result.variant_results.as_deref().unwrap_or_default();
+    let mut comparisons = Vec::new();
+    for (i, a) in variants.iter().enumerate() {
+        for b in &variants[i + 1..] {
+            comparisons.push(compare_variants(a, b, config)?);
+        }
+    }
+    Ok(comparisons)
+}
+
+/// Compare two variants per channel on their common window axis.
+///
+/// Windows are matched by their markers (`error_values`) when both variants
+/// carry them, otherwise by index. Both variants need `row_channels`.
+pub fn compare_variants(
+    a: &VariantResult,
+    b: &VariantResult,
+    config: &ComparisonConfig,
+) -> Result<VariantComparison> {
+    if config.bins < 2 {
+        return Err(DDAError::InvalidParameter(
+            "Variant comparison requires at least 2 histogram bins".to_string(),
+        ));
+    }
+    let (windows_a, windows_b) = common_windows(a, b);
+    let series_a = channel_series(a)?;
+    let series_b = channel_series(b)?;
+
+    let channels = series_a
+        .iter()
+        .filter_map(|(&channel, values_a)| {
+            let values_b = series_b.get(&channel)?;
+            let (x, y): (Vec<f64>, Vec<f64>) = windows_a
+                .iter()
+                .zip(&windows_b)
+                .map(|(&wa, &wb)| (values_a[wa], values_b[wb]))
+                .filter(|(x, y)| x.is_finite() && y.is_finite())
+                .unzip();
+            let enough = x.len() >= config.min_windows.max(2);
+            Some(ChannelComparison {
+                channel,
+                windows: x.len(),
+                correlation: if enough { pearson(&x, &y) } else { f64::NAN },
+                mutual_information: if enough {
+                    mutual_information(&x, &y, config.bins)
+                } else {
+                    f64::NAN
+                },
+                divergence: if enough {
+                    js_divergence(&x, &y, config.bins)
+                } else {
+                    f64::NAN
+                },
+            })
+        })
+        .collect();
+
+    Ok(VariantComparison {
+        variant_a: a.variant_id.clone(),
+        variant_b: b.variant_id.clone(),
+        common_windows: windows_a.len(),
+        channels,
+    })
+}
+
+/// Indices into `a` and `b` of the windows both contain.
+fn common_windows(a: &VariantResult, b: &VariantResult) -> (Vec<usize>, Vec<usize>) {
+    let len_a = a.q_matrix.first().map_or(0, Vec::len);
+    let len_b = b.q_matrix.first().map_or(0, Vec::len);
+    fn markers(v: &VariantResult, len: usize) -> Option<&[f64]> {
+        v.error_values
+            .as_deref()
+            .filter(|markers| markers.len() == len)
+    }
+    match (markers(a, len_a), markers(b, len_b)) {
+        (Some(ma), Some(mb)) => {
+            // Both marker lists ascend, so a merge walk finds the shared windows
+            let (mut i, mut j) = (0, 0);
+            let (mut ia, mut ib) = (Vec::new(), Vec::new());
+            while i < ma.len() && j < mb.len() {
+                if (ma[i] - mb[j]).abs() <= MARKER_TOLERANCE {
+                    ia.push(i);
+                    ib.push(j);
+                    i += 1;
+                    j += 1;
+                } else if ma[i] < mb[j] {
+                    i += 1;
+                } else {
+                    j += 1;
+                }
+            }
+            (ia, ib)
+        }
+        _ => {
+            let n = len_a.min(len_b);
+            ((0..n).collect(), (0..n).collect())
+        }
+    }
+}
+
+/// Per-channel mean over the rows that involve each channel.
+fn channel_series(variant: &VariantResult) -> Result<BTreeMap<usize, Vec<f64>>> {
+    let row_channels = variant.row_channels.as_ref().ok_or_else(|| {
+        DDAError::InvalidParameter(format!(
+            "Variant {} has no row_channels to align by channel",
+            variant.variant_id
+        ))
+    })?;
+    let windows = variant.q_matrix.first().map_or(0, Vec::len);
+    let mut sums: BTreeMap<usize, (Vec<f64>, Vec<usize>)> = BTreeMap::new();
+    for (row, channels) in variant.q_matrix.iter().zip(row_channels) {
+        for &channel in channels {
+            let (sum, count) = sums
+                .entry(channel)
+                .or_insert_with(|| (vec![0.0; windows], vec![0; windows]));
+            for (w, &value) in row.iter().enumerate().take(windows) {
+                if value.is_finite() {
+                    sum[w] += value;
+                    count[w] += 1;
+                }
+            }
+        }
+    }
+    Ok(sums
+        .into_iter()
+        .map(|(channel, (sum, count))| {
+            let mean = sum
+                .iter()
+                .zip(&count)
+                .map(|(&s, &n)| if n > 0 { s / n as f64 } else { f64::NAN })
+                .collect();
+            (channel, mean)
+        })
+        .collect())
+}
+
+fn mean_std(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, var.sqrt())
+}
+
+fn pearson(x: &[f64], y: &[f64]) -> f64 {
+    let (mx, sx) = mean_std(x);
+    let (my, sy) = mean_std(y);
+    if sx == 0.0 || sy == 0.0 {
+        return f64::NAN;
+    }
+    let cov = x
+        .iter()
+        .zip(y)
+        .map(|(a, b)| (a - mx) * (b - my))
+        .sum::<f64>()
+        / x.len() as f64;
+    (cov / (sx * sy)).clamp(-1.0, 1.0)
+}
+
+/// Bin index of each value over `[min, max]`; constant input falls in bin 0.
+fn bin_indices(values: &[f64], min: f64, max: f64, bins: usize) -> Vec<usize> {
+    let width = (max - min) / bins as f64;
+    values
+        .iter()
+        .map(|&v| {
+            if width > 0.0 {
+                (((v - min) / width) as usize).min(bins - 1)
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+fn range(values: &[f64]) -> (f64, f64) {
+    values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+            (lo.min(v), hi.max(v))
+        })
+}
+
+fn mutual_information(x: &[f64], y: &[f64], bins: usize) -> f64 {
+    let (x_lo, x_hi) = range(x);
+    let (y_lo, y_hi) = range(y);
+    let bx = bin_indices(x, x_lo, x_hi, bins);
+    let by = bin_indices(y, y_lo, y_hi, bins);
+    let n = x.len() as f64;
+    let mut joint = vec![0usize; bins * bins];
+    let mut px = vec![0usize; bins];
+    let mut py = vec![0usize; bins];
+    for (&i, &j) in bx.iter().zip(&by) {
+        joint[i * bins + j] += 1;
+        px[i] += 1;
+        py[j] += 1;
+    }
+    joint
+        .iter()
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .map(|(idx, &count)| {
+            let p = count as f64 / n;
+            let marginal = px[idx / bins] as f64 * py[idx % bins] as f64 / (n * n);
+            p * (p / marginal).ln()
+        })
+        .sum::<f64>()
+        .max(0.0)
+}
+
+/// Jensen-Shannon divergence of the z-scored distributions, so variants on
+/// different scales are compared by shape.
+fn js_divergence(x: &[f64], y: &[f64], bins: usize) -> f64 {
+    let standardize = |values: &[f64]| {
+        let (mean, std) = mean_std(values);
+        values
+            .iter()
+            .map(|v| if std > 0.0 { (v - mean) / std } else { 0.0 })
+            .collect::<Vec<_>>()
+    };
+    let (zx, zy) = (standardize(x), standardize(y));
+    let (lo_x, hi_x) = range(&zx);
+    let (lo_y, hi_y) = range(&zy);
+    let (lo, hi) = (lo_x.min(lo_y), hi_x.max(hi_y));
+    let histogram = |values: &[f64]| {
+        let mut counts = vec![0.0; bins];
+        for idx in bin_indices(values, lo, hi, bins) {
+            counts[idx] += 1.0;
+        }
+        counts
+            .iter()
+            .map(|c| c / values.len() as f64)
+            .collect::<Vec<_>>()
+    };
+    let (p, q) = (histogram(&zx), histogram(&zy));
+    let kl = |a: &[f64], m: &[f64]| {
+        a.iter()
+            .zip(m)
+            .filter(|(&ai, _)| ai > 0.0)
+            .map(|(ai, mi)| ai * (ai / mi).ln())
+            .sum::<f64>()
+    };
+    let m = p
+        .iter()
+        .zip(&q)
+        .map(|(a, b)| 0.5 * (a + b))
+        .collect::<Vec<_>>();
+    (0.5 * kl(&p, &m) + 0.5 * kl(&q, &m)).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(
+        id: &str,
+        q_matrix: Vec<Vec<f64>>,
+        rows: Vec<Vec<usize>>,
+        markers: Vec<f64>,
+    ) -> VariantResult {
+        VariantResult {
+            variant_id: id.to_string(),
+            variant_name: id.to_string(),
+            q_matrix,
+            channel_labels: None,
+            error_values: Some(markers),
+            row_channels: Some(rows),
+        }
+    }
+
+    #[test]
+    fn test_aligns_on_shared_windows_per_channel() {
+        let ramp = (0..20).map(|w| w as f64).collect::<Vec<_>>();
+        let noisy = (0..20)
+            .map(|w| ((w * 7919) % 13) as f64)
+            .collect::<Vec<_>>();
+        let st = variant(
+            "ST",
+            vec![ramp.clone(), noisy.clone()],
+            vec![vec![0], vec![1]],
+            (0..20).map(|w| 100.0 + 10.0 * w as f64).collect(),
+        );
+        // CT starts five windows later and its single pair row covers both channels
+        let ct_row = ramp[5..].iter().map(|v| 2.0 * v + 1.0).collect::<Vec<_>>();
+        let ct = variant(
+            "CT",
+            vec![ct_row],
+            vec![vec![0, 1]],
+            (5..20).map(|w| 100.0 + 10.0 * w as f64).collect(),
+        );
+
+        let comparison = compare_variants(&st, &ct, &ComparisonConfig::default()).unwrap();
+        assert_eq!(comparison.common_windows, 15);
+        assert_eq!(comparison.channels.len(), 2);
+
+        let linear = comparison.channel(0).unwrap();
+        assert_eq!(linear.windows, 15);
+        assert!((linear.correlation - 1.0).abs() < 1e-12);
+        assert!(linear.mutual_information > 1.0);
+        assert!(linear.divergence < 1e-12);
+
+        let other = comparison.channel(1).unwrap();
+        assert!(other.correlation.abs() < 0.9);
+        assert!(other.divergence > linear.divergence);
+    }
+
+    #[test]
+    fn test_compare_all_variants_requires_row_channels() {
+        let mut result = DDAResult::new(
+            "id".to_string(),
+            "in.txt".to_string(),
+            vec!["a".to_string()],
+            vec![vec![1.0, 2.0, 3.0]],
+            crate::types::WindowParameters {
+                window_length: 10,
+                window_step: 10,
+                ct_window_length: None,
+                ct_window_step: None,
+            },
+            crate::types::DelayParameters { delays: vec![1] },
+        );
+        let mut de = variant("DE", vec![vec![3.0, 1.0, 2.0]], vec![vec![0]], vec![]);
+        let st = variant("ST", vec![vec![1.0, 2.0, 3.0]], vec![vec![0]], vec![]);
+        result.variant_results = Some(vec![st.clone(), de.clone()]);
+        let comparisons = compare_all_variants(&result, &ComparisonConfig::default()).unwrap();
+        assert_eq!(comparisons.len(), 1);
+        assert_eq!(comparisons[0].common_windows, 3);
+        assert!((comparisons[0].mean_correlation() + 0.5).abs() < 1e-12);
+
+        de.row_channels = None;
+        result.variant_results = Some(vec![st, de]);
+        assert!(compare_all_variants(&result, &ComparisonConfig::default()).is_err());
+    }
+}
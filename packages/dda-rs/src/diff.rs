@@ -0,0 +1,328 @@
+//! Numerical and structural differences between two DDA results
+//!
+//! Used to check that an engine or binary upgrade reproduces earlier
+//! results. Variants are matched by id, rows by channel label (by position
+//! when either side has no labels) and windows by position. Values differing
+//! by more than the tolerance, NaN on only one side, and any shape or label
+//! mismatch count as differences.
+
+use crate::error::{DDAError, Result};
+use crate::types::{DDAResult, VariantResult};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Id under which a result without `variant_results` is compared
+const PRIMARY_VARIANT: &str = "primary";
+
+/// Largest deviation found in a variant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deviation {
+    pub row: String,
+    pub window: usize,
+    pub a: f64,
+    pub b: f64,
+}
+
+/// Value comparison of one variant present in both results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantDiff {
+    pub variant_id: String,
+    /// Values compared where both sides are finite
+    pub values: usize,
+    pub max_abs_deviation: f64,
+    pub mean_abs_deviation: f64,
+    /// Values deviating by more than the tolerance
+    pub exceeding: usize,
+    /// Positions that are NaN or infinite on only one side
+    pub non_finite_mismatches: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worst: Option<Deviation>,
+}
+
+/// Differences between two results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultDiff {
+    pub tolerance: f64,
+    pub variants: Vec<VariantDiff>,
+    /// Missing variants, rows or windows and other shape mismatches
+    pub structural: Vec<String>,
+}
+
+impl ResultDiff {
+    pub fn max_abs_deviation(&self) -> f64 {
+        self.variants
+            .iter()
+            .map(|variant| variant.max_abs_deviation)
+            .fold(0.0, f64::max)
+    }
+
+    /// No structural differences and every value within the tolerance
+    pub fn within_tolerance(&self) -> bool {
+        self.structural.is_empty()
+            && self
+                .variants
+                .iter()
+                .all(|variant| variant.exceeding == 0 && variant.non_finite_mismatches == 0)
+    }
+}
+
+/// Read a result JSON file, gzip-compressed when the name ends in `.gz`
+pub fn read_result_path<P: AsRef<Path>>(path: P) -> Result<DDAResult> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    serde_json::from_reader(BufReader::new(reader))
+        .map_err(|e| DDAError::ParseError(format!("{}: {}", path.display(), e)))
+}
+
+/// Compare `b` against `a`
+pub fn diff_results(a: &DDAResult, b: &DDAResult, tolerance: f64) -> ResultDiff {
+    let variants_a = variants_of(a);
+    let variants_b = variants_of(b);
+    let mut diff = ResultDiff {
+        tolerance,
+        variants: Vec::new(),
+        structural: Vec::new(),
+    };
+
+    for (id, _) in &variants_b {
+        if !variants_a.iter().any(|(other, _)| other == id) {
+            diff.structural
+                .push(format!("{}: only in the second result", id));
+        }
+    }
+    for (id, variant_a) in &variants_a {
+        match variants_b.iter().find(|(other, _)| other == id) {
+            Some((_, variant_b)) => {
+                let variant = diff_variant(id, variant_a, variant_b, tolerance, &mut diff);
+                diff.variants.push(variant);
+            }
+            None => diff
+                .structural
+                .push(format!("{}: only in the first result", id)),
+        }
+    }
+
+    if let (Some(markers_a), Some(markers_b)) = (&a.error_values, &b.error_values) {
+        let shifted = markers_a
+            .iter()
+            .zip(markers_b)
+            .position(|(x, y)| (x - y).abs() > tolerance);
+        if markers_a.len() != markers_b.len() {
+            diff.structural.push(format!(
+                "{} window markers vs {}",
+                markers_a.len(),
+                markers_b.len()
+            ));
+        } else if let Some(window) = shifted {
+            diff.structural
+                .push(format!("window markers differ from window {}", window));
+        }
+    }
+    diff
+}
+
+/// Q-matrix of one variant with its row labels
+struct Matrix<'a> {
+    labels: Option<&'a [String]>,
+    rows: &'a [Vec<f64>],
+}
+
+/// Every variant by id, or the primary matrix alone
+fn variants_of(result: &DDAResult) -> Vec<(&str, Matrix<'_>)> {
+    match result.variant_results.as_deref() {
+        Some(variants) if !variants.is_empty() => variants
+            .iter()
+            .map(|variant: &VariantResult| {
+                let matrix = Matrix {
+                    labels: variant.channel_labels.as_deref(),
+                    rows: &variant.q_matrix,
+                };
+                (variant.variant_id.as_str(), matrix)
+            })
+            .collect(),
+        _ => {
+            let matrix = Matrix {
+                labels: (result.channels.len() == result.q_matrix.len())
+                    .then_some(result.channels.as_slice()),
+                rows: &result.q_matrix,
+            };
+            vec![(PRIMARY_VARIANT, matrix)]
+        }
+    }
+}
+
+fn diff_variant(
+    id: &str,
+    a: &Matrix<'_>,
+    b: &Matrix<'_>,
+    tolerance: f64,
+    diff: &mut ResultDiff,
+) -> VariantDiff {
+    let (rows_a, rows_b) = (a.rows, b.rows);
+    // Row pairs to compare: by label when both sides are labelled
+    let pairs: Vec<(String, usize, usize)> = match (a.labels, b.labels) {
+        (Some(labels_a), Some(labels_b))
+            if labels_a.len() == rows_a.len() && labels_b.len() == rows_b.len() =>
+        {
+            for label in labels_b.iter().filter(|label| !labels_a.contains(label)) {
+                diff.structural
+                    .push(format!("{}: row '{}' only in the second result", id, label));
+            }
+            labels_a
+                .iter()
+                .enumerate()
+                .filter_map(|(row_a, label)| {
+                    match labels_b.iter().position(|other| other == label) {
+                        Some(row_b) => Some((label.clone(), row_a, row_b)),
+                        None => {
+                            diff.structural
+                                .push(format!("{}: row '{}' only in the first result", id, label));
+                            None
+                        }
+                    }
+                })
+                .collect()
+        }
+        _ => {
+            if rows_a.len() != rows_b.len() {
+                diff.structural
+                    .push(format!("{}: {} rows vs {}", id, rows_a.len(), rows_b.len()));
+            }
+            (0..rows_a.len().min(rows_b.len()))
+                .map(|row| (row.to_string(), row, row))
+                .collect()
+        }
+    };
+
+    let mut variant = VariantDiff {
+        variant_id: id.to_string(),
+        values: 0,
+        max_abs_deviation: 0.0,
+        mean_abs_deviation: 0.0,
+        exceeding: 0,
+        non_finite_mismatches: 0,
+        worst: None,
+    };
+    let mut total = 0.0;
+    let mut reported_windows = false;
+    for (label, row_a, row_b) in pairs {
+        let (values_a, values_b) = (&rows_a[row_a], &rows_b[row_b]);
+        if values_a.len() != values_b.len() && !reported_windows {
+            diff.structural.push(format!(
+                "{}: {} windows vs {} (row '{}')",
+                id,
+                values_a.len(),
+                values_b.len(),
+                label
+            ));
+            reported_windows = true;
+        }
+        for (window, (&a, &b)) in values_a.iter().zip(values_b).enumerate() {
+            if !a.is_finite() || !b.is_finite() {
+                let same = (a.is_nan() && b.is_nan()) || a == b;
+                if !same {
+                    variant.non_finite_mismatches += 1;
+                }
+                continue;
+            }
+            let deviation = (a - b).abs();
+            variant.values += 1;
+            total += deviation;
+            if deviation > tolerance {
+                variant.exceeding += 1;
+            }
+            if deviation > variant.max_abs_deviation {
+                variant.max_abs_deviation = deviation;
+                variant.worst = Some(Deviation {
+                    row: label.clone(),
+                    window,
+                    a,
+                    b,
+                });
+            }
+        }
+    }
+    if variant.values > 0 {
+        variant.mean_abs_deviation = total / variant.values as f64;
+    }
+    variant
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DelayParameters, WindowParameters};
+
+    fn result(variants: Vec<VariantResult>) -> DDAResult {
+        let mut result = DDAResult::new(
+            "r".to_string(),
+            "in.ascii".to_string(),
+            vec!["a".to_string(), "b".to_string()],
+            Vec::new(),
+            WindowParameters {
+                window_length: 64,
+                window_step: 32,
+                ct_window_length: None,
+                ct_window_step: None,
+            },
+            DelayParameters { delays: vec![1, 2] },
+        );
+        result.variant_results = Some(variants);
+        result
+    }
+
+    fn variant(id: &str, labels: &[&str], q_matrix: Vec<Vec<f64>>) -> VariantResult {
+        VariantResult {
+            variant_id: id.to_string(),
+            variant_name: id.to_string(),
+            q_matrix,
+            channel_labels: Some(labels.iter().map(|label| label.to_string()).collect()),
+            error_values: None,
+            row_channels: None,
+        }
+    }
+
+    #[test]
+    fn test_aligns_rows_by_label_and_reports_structure() {
+        let a = result(vec![
+            variant(
+                "ST",
+                &["a", "b"],
+                vec![vec![1.0, 2.0, f64::NAN], vec![3.0, 4.0, 5.0]],
+            ),
+            variant("CT", &["a-b"], vec![vec![0.5]]),
+        ]);
+        let same = result(vec![
+            variant(
+                "ST",
+                &["b", "a"],
+                vec![vec![3.0, 4.0, 5.0], vec![1.0, 2.0 + 1e-9, f64::NAN]],
+            ),
+            variant("CT", &["a-b"], vec![vec![0.5]]),
+        ]);
+        let diff = diff_results(&a, &same, 1e-6);
+        assert!(diff.within_tolerance(), "{:?}", diff);
+        assert!(diff.max_abs_deviation() > 0.0);
+        assert_eq!(diff.variants[0].values, 5);
+
+        let changed = result(vec![variant(
+            "ST",
+            &["a", "c"],
+            vec![vec![1.5, 2.0, 0.0], vec![3.0, 4.0, 5.0]],
+        )]);
+        let diff = diff_results(&a, &changed, 1e-6);
+        assert!(!diff.within_tolerance());
+        let st = &diff.variants[0];
+        assert_eq!((st.exceeding, st.non_finite_mismatches), (1, 1));
+        assert_eq!(st.worst.as_ref().unwrap().window, 0);
+        assert_eq!(diff.structural.len(), 3, "{:?}", diff.structural);
+    }
+}
@@ -1,14 +1,18 @@
 mod dataset;
+mod incremental;
 mod model;
 mod solver;
+mod stitch;
 mod variant_config;
 mod window;
 
 #[cfg(test)]
 mod tests;
 
+use crate::cancellation::CancellationToken;
 use crate::ccd_stats::{legacy_rmse_gain_from_rmse, log_mse_ratio_from_rmse, partial_r2_from_rmse};
 use crate::error::{DDAError, Result};
+use crate::preprocessing::PreprocessingPipeline;
 use crate::types::{CcdConditioningStrategy, DDARequest, DDAResult, VariantResult};
 use dataset::{AnalysisBounds, MatrixDataset};
 use model::ModelSpec;
@@ -31,6 +35,8 @@ use variant_config::{
 };
 use window::PreparedWindow;
 
+pub use incremental::IncrementalRun;
+
 pub(crate) const PARALLEL_BATCH_MIN_LEN: usize = 4;
 const CCD_NORMALIZED_EPSILON: f64 = 1e-12;
 
@@ -81,6 +87,34 @@ pub struct PureRustProgress {
     pub item_label: String,
 }
 
+impl PureRustProgress {
+    /// Completed fraction of all steps in `[0, 1]`.
+    pub fn fraction(&self) -> f64 {
+        if self.total_steps == 0 {
+            return 0.0;
+        }
+        (self.step_index as f64 / self.total_steps as f64).clamp(0.0, 1.0)
+    }
+
+    /// Completed percentage of all steps, rounded down.
+    pub fn percent(&self) -> u8 {
+        (self.fraction() * 100.0).floor() as u8
+    }
+
+    /// Single-line, human-readable status (`Progress: 42% | <stage> | window 3/8`).
+    ///
+    /// The `Progress: N%` prefix is what job runners tailing stderr parse.
+    pub fn status_line(&self) -> String {
+        format!(
+            "Progress: {}% | {} | window {}/{}",
+            self.percent(),
+            self.stage_label,
+            self.window_index,
+            self.total_windows
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CcdConditioningInspection {
     pub pairs: Vec<[usize; 2]>,
@@ -110,6 +144,7 @@ pub struct CcdConditioningSubsetProfile {
 #[derive(Debug, Clone)]
 pub struct PureRustRunner {
     options: PureRustOptions,
+    preprocessing: PreprocessingPipeline,
 }
 
 impl Default for PureRustRunner {
@@ -120,7 +155,25 @@ impl Default for PureRustRunner {
 
 impl PureRustRunner {
     pub fn new(options: PureRustOptions) -> Self {
-        Self { options }
+        Self {
+            options,
+            preprocessing: PreprocessingPipeline::default(),
+        }
+    }
+
+    /// Run `pipeline` on the samples before analysis, after any highpass or
+    /// lowpass filtering the request asks for
+    pub fn with_preprocessing(mut self, pipeline: PreprocessingPipeline) -> Self {
+        self.preprocessing = pipeline;
+        self
+    }
+
+    pub fn options(&self) -> &PureRustOptions {
+        &self.options
+    }
+
+    pub fn preprocessing(&self) -> &PreprocessingPipeline {
+        &self.preprocessing
     }
 
     pub fn run_on_matrix(
@@ -129,7 +182,7 @@ impl PureRustRunner {
         samples: &[Vec<f64>],
         channel_labels: Option<&[String]>,
     ) -> Result<DDAResult> {
-        self.run_on_matrix_internal(request, samples, channel_labels, None)
+        self.run_preprocessed(request, samples, channel_labels, None, None)
     }
 
     pub fn run_on_matrix_with_progress<F>(
@@ -143,7 +196,30 @@ impl PureRustRunner {
         F: FnMut(&PureRustProgress),
     {
         let mut callback = on_progress;
-        self.run_on_matrix_internal(request, samples, channel_labels, Some(&mut callback))
+        self.run_preprocessed(request, samples, channel_labels, Some(&mut callback), None)
+    }
+
+    /// Run with progress reporting, stopping early with [`DDAError::Cancelled`]
+    /// once `cancel` is triggered. The token is checked between windows.
+    pub fn run_on_matrix_with_cancellation<F>(
+        &self,
+        request: &DDARequest,
+        samples: &[Vec<f64>],
+        channel_labels: Option<&[String]>,
+        cancel: &CancellationToken,
+        on_progress: F,
+    ) -> Result<DDAResult>
+    where
+        F: FnMut(&PureRustProgress),
+    {
+        let mut callback = on_progress;
+        self.run_preprocessed(
+            request,
+            samples,
+            channel_labels,
+            Some(&mut callback),
+            Some(cancel),
+        )
     }
 
     pub fn inspect_ccd_conditioning_sets_on_matrix(
@@ -261,13 +337,51 @@ impl PureRustRunner {
             .collect())
     }
 
+    /// The request's filters and the runner's pipeline, in the order they run
+    fn pipeline_for(&self, request: &DDARequest) -> PreprocessingPipeline {
+        let mut pipeline = PreprocessingPipeline::from_options(&request.preprocessing_options);
+        pipeline.extend(&self.preprocessing);
+        pipeline
+    }
+
+    fn run_preprocessed(
+        &self,
+        request: &DDARequest,
+        samples: &[Vec<f64>],
+        channel_labels: Option<&[String]>,
+        on_progress: Option<&mut dyn FnMut(&PureRustProgress)>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<DDAResult> {
+        let pipeline = self.pipeline_for(request);
+        if pipeline.is_empty() {
+            return self.run_on_matrix_internal(
+                request,
+                samples,
+                channel_labels,
+                on_progress,
+                cancel,
+            );
+        }
+        let processed = pipeline.apply(samples, request.sampling_rate)?;
+        let mut result =
+            self.run_on_matrix_internal(request, &processed, channel_labels, on_progress, cancel)?;
+        result.preprocessing = pipeline.steps();
+        Ok(result)
+    }
+
     fn run_on_matrix_internal(
         &self,
         request: &DDARequest,
         samples: &[Vec<f64>],
         channel_labels: Option<&[String]>,
         mut on_progress: Option<&mut dyn FnMut(&PureRustProgress)>,
+        cancel: Option<&CancellationToken>,
     ) -> Result<DDAResult> {
+        let check_cancelled = || match cancel {
+            Some(token) if token.is_cancelled() => Err(DDAError::Cancelled),
+            _ => Ok(()),
+        };
+        check_cancelled()?;
         let dataset = MatrixDataset::new(samples, channel_labels)?;
         let variant_mode = VariantMode::from_request(request);
         let model = ModelSpec::from_request(request)?;
@@ -423,6 +537,7 @@ impl PureRustRunner {
         if needs_prepared_windows {
             let mut windows = Vec::with_capacity(num_windows);
             for window_idx in 0..num_windows {
+                check_cancelled()?;
                 report(
                     "prepare-window",
                     "Preparing analysis window",
@@ -474,6 +589,7 @@ impl PureRustRunner {
         let mut sy_matrix = empty_result_matrix(enabled_sy, sy_rows, num_windows);
 
         for window_idx in 0..num_windows {
+            check_cancelled()?;
             let prepared_storage;
             let prepared = if let Some(windows) = prepared_windows.as_ref() {
                 &windows[window_idx]
@@ -901,6 +1017,7 @@ impl PureRustRunner {
             "Single Timeseries (ST)",
             st_matrix,
             &labels_for_channels(&dataset.channel_labels, &st_channels),
+            st_channels.iter().map(|&channel| vec![channel]).collect(),
             &native_window_markers,
         );
         push_variant_result(
@@ -909,6 +1026,7 @@ impl PureRustRunner {
             "Cross-Timeseries (CT)",
             ct_matrix,
             &labels_for_groups(&dataset.channel_labels, &ct_groups, "&"),
+            ct_groups.clone(),
             &native_window_markers,
         );
         push_variant_result(
@@ -917,6 +1035,7 @@ impl PureRustRunner {
             "Cross-Dynamical (CD)",
             cd_matrix,
             &labels_for_pairs(&dataset.channel_labels, &cd_pairs, " <- "),
+            rows_for_pairs(&cd_pairs),
             &native_window_markers,
         );
         for (variant_id, variant_name, q_matrix) in [
@@ -962,6 +1081,7 @@ impl PureRustRunner {
                 variant_name,
                 q_matrix,
                 &ccd_labels,
+                rows_for_pairs(&ccd_pairs),
                 &native_window_markers,
             );
         }
@@ -971,6 +1091,7 @@ impl PureRustRunner {
             "Dynamical Ergodicity (DE)",
             de_matrix,
             &labels_for_groups(&dataset.channel_labels, &de_groups, "&"),
+            de_groups.clone(),
             &native_window_markers,
         );
         push_variant_result(
@@ -979,6 +1100,7 @@ impl PureRustRunner {
             "Synchronization (SY)",
             sy_matrix,
             &labels_for_sy(&dataset.channel_labels, &sy_pairs, variant_mode.sy_mode),
+            rows_for_sy(&sy_pairs, variant_mode.sy_mode),
             &native_window_markers,
         );
 
@@ -998,6 +1120,7 @@ impl PureRustRunner {
             delay_parameters: request.delay_parameters.clone(),
             created_at: chrono::Utc::now().to_rfc3339(),
             error_values: Some(native_window_markers),
+            preprocessing: Vec::new(),
         })
     }
 
@@ -1014,7 +1137,7 @@ impl PureRustRunner {
         let mut runs = Vec::new();
         for perturbed in perturbed_requests {
             if let Ok(result) =
-                self.run_on_matrix_internal(&perturbed, samples, channel_labels, None)
+                self.run_on_matrix_internal(&perturbed, samples, channel_labels, None, None)
             {
                 if let Some(variant) = result.variant_results.as_ref().and_then(|variants| {
                     variants.iter().find(|variant| variant.variant_id == "CCD")
@@ -1072,6 +1195,7 @@ fn push_variant_result(
     variant_name: &str,
     q_matrix: Option<Vec<Vec<f64>>>,
     channel_labels: &[String],
+    row_channels: Vec<Vec<usize>>,
     window_markers: &[f64],
 ) {
     if let Some(q_matrix) = q_matrix {
@@ -1081,10 +1205,26 @@ fn push_variant_result(
             q_matrix,
             channel_labels: Some(channel_labels.to_vec()),
             error_values: Some(window_markers.to_vec()),
+            row_channels: Some(row_channels),
         });
     }
 }
 
+fn rows_for_pairs(pairs: &[[usize; 2]]) -> Vec<Vec<usize>> {
+    pairs.iter().map(|pair| pair.to_vec()).collect()
+}
+
+fn rows_for_sy(pairs: &[[usize; 2]], mode: u8) -> Vec<Vec<usize>> {
+    if mode == 2 {
+        pairs
+            .iter()
+            .flat_map(|pair| [vec![pair[0], pair[1]], vec![pair[1], pair[0]]])
+            .collect()
+    } else {
+        rows_for_pairs(pairs)
+    }
+}
+
 fn empty_result_matrix(enabled: bool, rows: usize, columns: usize) -> Option<Vec<Vec<f64>>> {
     enabled.then(|| vec![vec![f64::NAN; columns]; rows])
 }
@@ -1854,3 +1994,22 @@ where
         on_progress,
     )
 }
+
+pub fn run_request_on_matrix_with_cancellation<F>(
+    request: &DDARequest,
+    samples: &[Vec<f64>],
+    channel_labels: Option<&[String]>,
+    cancel: &CancellationToken,
+    on_progress: F,
+) -> Result<DDAResult>
+where
+    F: FnMut(&PureRustProgress),
+{
+    PureRustRunner::default().run_on_matrix_with_cancellation(
+        request,
+        samples,
+        channel_labels,
+        cancel,
+        on_progress,
+    )
+}
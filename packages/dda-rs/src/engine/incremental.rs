@@ -0,0 +1,150 @@
+//! Incremental re-analysis of grown inputs
+//!
+//! Each window of the window-local variants depends only on the samples it
+//! covers, so when a recording grows (or the analysis end moves later) the
+//! windows of a previous result can be kept and only the new ones computed.
+//! The previous result is matched through its window markers, which encode
+//! the analysis start, window length, step, embedding and largest delay.
+
+use super::dataset::{AnalysisBounds, MatrixDataset};
+use super::model::ModelSpec;
+use super::{analysis_window_count, PureRustRunner};
+use crate::error::{DDAError, Result};
+use crate::types::{DDARequest, DDAResult};
+
+/// Variants whose per-window values do not depend on other windows.
+const WINDOW_LOCAL_VARIANTS: &[&str] = &["ST", "CT", "CD", "DE", "SY"];
+
+/// Result of [`PureRustRunner::run_incremental_on_matrix`].
+#[derive(Debug, Clone)]
+pub struct IncrementalRun {
+    pub result: DDAResult,
+    /// Windows copied from the previous result
+    pub reused_windows: usize,
+    /// Windows computed by this run
+    pub computed_windows: usize,
+}
+
+impl PureRustRunner {
+    /// Run `request`, reusing the windows of `previous` that are still valid.
+    ///
+    /// `previous` must come from the same request with an earlier (or equal)
+    /// end. The last previous window is always recomputed because it may have
+    /// been padded at the old end of the data. Falls back to a full run when
+    /// `previous` does not line up with `request` or contains variants whose
+    /// windows are coupled (CCD family).
+    pub fn run_incremental_on_matrix(
+        &self,
+        request: &DDARequest,
+        samples: &[Vec<f64>],
+        channel_labels: Option<&[String]>,
+        previous: &DDAResult,
+    ) -> Result<IncrementalRun> {
+        let dataset = MatrixDataset::new(samples, channel_labels)?;
+        let model = ModelSpec::from_request(request)?;
+        let bounds = AnalysisBounds::from_request(request, dataset.rows)?;
+        let total_windows = analysis_window_count(&bounds, &model)?;
+        let marker_offset = model.window_length + model.max_delay + 2 * model.dm;
+
+        let reusable = reusable_window_count(previous, &bounds, &model, marker_offset)
+            .min(total_windows.saturating_sub(1));
+        if reusable == 0 {
+            let result = self.run_on_matrix(request, samples, channel_labels)?;
+            return Ok(IncrementalRun {
+                result,
+                reused_windows: 0,
+                computed_windows: total_windows,
+            });
+        }
+
+        let mut tail_request = request.clone();
+        tail_request.time_range.start = (bounds.start + reusable * model.window_step) as f64;
+        let mut merged = self.run_on_matrix(&tail_request, samples, channel_labels)?;
+        let computed_windows = total_windows - reusable;
+
+        let previous_variants = previous.variant_results.as_deref().unwrap_or(&[]);
+        for variant in merged.variant_results.iter_mut().flatten() {
+            let old = previous_variants
+                .iter()
+                .find(|old| {
+                    old.variant_id == variant.variant_id && old.row_channels == variant.row_channels
+                })
+                .ok_or_else(|| {
+                    DDAError::InvalidParameter(format!(
+                        "previous result has no matching {} rows",
+                        variant.variant_id
+                    ))
+                })?;
+            for (row, old_row) in variant.q_matrix.iter_mut().zip(&old.q_matrix) {
+                let mut joined = old_row[..reusable].to_vec();
+                joined.append(row);
+                *row = joined;
+            }
+            if let (Some(markers), Some(old_markers)) =
+                (variant.error_values.as_mut(), old.error_values.as_ref())
+            {
+                let mut joined = old_markers[..reusable].to_vec();
+                joined.append(markers);
+                *markers = joined;
+            }
+        }
+        if let (Some(markers), Some(old_markers)) =
+            (merged.error_values.as_mut(), previous.error_values.as_ref())
+        {
+            let mut joined = old_markers[..reusable].to_vec();
+            joined.append(markers);
+            *markers = joined;
+        }
+        merged.q_matrix = merged
+            .variant_results
+            .as_ref()
+            .and_then(|variants| variants.first())
+            .map(|variant| variant.q_matrix.clone())
+            .unwrap_or_default();
+
+        Ok(IncrementalRun {
+            result: merged,
+            reused_windows: reusable,
+            computed_windows,
+        })
+    }
+}
+
+/// Number of leading windows of `previous` that can be reused, excluding its
+/// last window.
+fn reusable_window_count(
+    previous: &DDAResult,
+    bounds: &AnalysisBounds,
+    model: &ModelSpec,
+    marker_offset: usize,
+) -> usize {
+    let Some(markers) = previous.error_values.as_ref() else {
+        return 0;
+    };
+    let Some(variants) = previous.variant_results.as_ref() else {
+        return 0;
+    };
+    if variants.is_empty()
+        || variants.iter().any(|variant| {
+            !WINDOW_LOCAL_VARIANTS.contains(&variant.variant_id.as_str())
+                || variant.row_channels.is_none()
+                || variant
+                    .q_matrix
+                    .iter()
+                    .any(|row| row.len() != markers.len())
+        })
+    {
+        return 0;
+    }
+    let aligned = markers
+        .iter()
+        .enumerate()
+        .take_while(|(idx, &marker)| {
+            marker == (bounds.start + idx * model.window_step + marker_offset) as f64
+        })
+        .count();
+    if aligned != markers.len() {
+        return 0;
+    }
+    aligned.saturating_sub(1)
+}
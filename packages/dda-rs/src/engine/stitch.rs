@@ -0,0 +1,74 @@
+//! Analysis of stitched multi-file recordings
+//!
+//! The stitched samples are analyzed like a single file, so preprocessing
+//! runs across file boundaries and windows may span two files. Each window's
+//! sample range follows from the analysis start, the window step and the
+//! window marker offset; the recording maps those ranges onto its files.
+
+use super::dataset::{AnalysisBounds, MatrixDataset};
+use super::model::ModelSpec;
+use super::{analysis_window_count, PureRustProgress, PureRustRunner};
+use crate::error::{DDAError, Result};
+use crate::stitch::{StitchedRecording, StitchedRun};
+use crate::types::DDARequest;
+
+impl PureRustRunner {
+    /// Run `request` on a stitched recording as one continuous input.
+    ///
+    /// The request's sampling rate defaults to the recording's; a request
+    /// that names a different rate is rejected. Window ranges in the
+    /// boundary map use the primary window length, not a CT-specific one.
+    pub fn run_stitched(
+        &self,
+        request: &DDARequest,
+        recording: &StitchedRecording,
+    ) -> Result<StitchedRun> {
+        self.run_stitched_with_progress(request, recording, |_| {})
+    }
+
+    pub fn run_stitched_with_progress<F>(
+        &self,
+        request: &DDARequest,
+        recording: &StitchedRecording,
+        on_progress: F,
+    ) -> Result<StitchedRun>
+    where
+        F: FnMut(&PureRustProgress),
+    {
+        let mut request = request.clone();
+        match (request.sampling_rate, recording.sampling_rate()) {
+            (Some(requested), Some(recorded)) if (requested - recorded).abs() > f64::EPSILON => {
+                return Err(DDAError::InvalidParameter(format!(
+                    "Request sampling rate {} Hz does not match the recording's {} Hz",
+                    requested, recorded
+                )));
+            }
+            (None, recorded) => request.sampling_rate = recorded,
+            _ => {}
+        }
+
+        let samples = recording.samples();
+        let dataset = MatrixDataset::new(samples, recording.channel_labels())?;
+        let model = ModelSpec::from_request(&request)?;
+        let bounds = AnalysisBounds::from_request(&request, dataset.rows)?;
+        let window_count = analysis_window_count(&bounds, &model)?;
+        let window_span = model.window_length + model.max_delay + 2 * model.dm;
+        let windows: Vec<_> = (0..window_count)
+            .map(|window_idx| {
+                let start = bounds.start + window_idx * model.window_step;
+                start..(start + window_span).min(dataset.rows)
+            })
+            .collect();
+
+        let result = self.run_on_matrix_with_progress(
+            &request,
+            samples,
+            recording.channel_labels(),
+            on_progress,
+        )?;
+        Ok(StitchedRun {
+            result,
+            boundary_map: recording.boundary_map(&windows),
+        })
+    }
+}
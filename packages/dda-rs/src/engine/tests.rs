@@ -557,3 +557,189 @@ fn auto_group_omp_overbudget_stays_within_one_membership_error() {
         "auto_group_omp should stay within one membership error on the planted two-confound case when over-budgeted (selected={selected:?}, errors={membership_errors})"
     );
 }
+
+#[test]
+fn cancellation_stops_run_between_windows() {
+    let samples = synthetic_samples();
+    let mut request = ccd_auto_request(
+        "synthetic".to_string(),
+        CcdConditioningStrategy::AutoSharedParents,
+    );
+    request.algorithm_selection.enabled_variants = vec!["ST".to_string()];
+    request.variant_configs = None;
+    request.cd_channel_pairs = None;
+
+    let token = crate::cancellation::CancellationToken::new();
+    let mut windows_seen = 0usize;
+    let result = PureRustRunner::default().run_on_matrix_with_cancellation(
+        &request,
+        &samples,
+        None,
+        &token,
+        |progress| {
+            if progress.total_windows > 0 {
+                windows_seen += 1;
+                token.cancel();
+            }
+        },
+    );
+    assert!(matches!(result, Err(DDAError::Cancelled)));
+    assert!(windows_seen <= 2, "run continued after cancellation");
+
+    let pre_cancelled = crate::cancellation::CancellationToken::new();
+    pre_cancelled.cancel();
+    assert!(matches!(
+        PureRustRunner::default().run_on_matrix_with_cancellation(
+            &request,
+            &samples,
+            None,
+            &pre_cancelled,
+            |_| {}
+        ),
+        Err(DDAError::Cancelled)
+    ));
+}
+
+#[test]
+fn variant_results_record_row_channels_for_typed_access() {
+    let samples = synthetic_samples();
+    let mut request = ccd_auto_request(
+        "synthetic".to_string(),
+        CcdConditioningStrategy::AutoSharedParents,
+    );
+    request.algorithm_selection.enabled_variants = vec!["ST".to_string(), "CD".to_string()];
+    request.variant_configs = None;
+    request.cd_channel_pairs = Some(vec![[1, 0], [2, 1]]);
+
+    let result = PureRustRunner::default()
+        .run_on_matrix(&request, &samples, None)
+        .expect("run");
+    let st = match result
+        .typed_variant("ST")
+        .expect("ST present")
+        .expect("typed")
+    {
+        crate::TypedVariantResult::SingleTimeseries(st) => st,
+        other => panic!("unexpected {:?}", other.variant_id()),
+    };
+    assert_eq!(st.channels, vec![0, 1, 2]);
+    assert_eq!(st.channel(2), Some(result.q_matrix[2].as_slice()));
+
+    let cd = match result
+        .typed_variant("CD")
+        .expect("CD present")
+        .expect("typed")
+    {
+        crate::TypedVariantResult::CrossDynamical(cd) => cd,
+        other => panic!("unexpected {:?}", other.variant_id()),
+    };
+    assert!(cd.directed(1, 2).is_some());
+    assert!(cd.directed(2, 1).is_none());
+}
+
+#[test]
+fn incremental_run_matches_full_run_after_append() {
+    let samples = synthetic_samples();
+    let mut request = ccd_auto_request(
+        "synthetic".to_string(),
+        CcdConditioningStrategy::AutoSharedParents,
+    );
+    request.algorithm_selection.enabled_variants = vec!["ST".to_string(), "CD".to_string()];
+    request.variant_configs = None;
+    request.cd_channel_pairs = Some(vec![[1, 0], [2, 1]]);
+    request.time_range.end = f64::INFINITY;
+
+    let runner = PureRustRunner::default();
+    let previous = runner
+        .run_on_matrix(&request, &samples[..1000], None)
+        .expect("previous run");
+    let full = runner
+        .run_on_matrix(&request, &samples, None)
+        .expect("full run");
+    let incremental = runner
+        .run_incremental_on_matrix(&request, &samples, None, &previous)
+        .expect("incremental run");
+
+    let previous_windows = previous.error_values.as_ref().unwrap().len();
+    assert_eq!(incremental.reused_windows, previous_windows - 1);
+    assert_eq!(
+        incremental.reused_windows + incremental.computed_windows,
+        full.error_values.as_ref().unwrap().len()
+    );
+    assert_eq!(incremental.result.error_values, full.error_values);
+    for (merged, expected) in incremental
+        .result
+        .variant_results
+        .as_ref()
+        .unwrap()
+        .iter()
+        .zip(full.variant_results.as_ref().unwrap())
+    {
+        assert_eq!(merged.variant_id, expected.variant_id);
+        assert_eq!(merged.error_values, expected.error_values);
+        for (row, expected_row) in merged.q_matrix.iter().zip(&expected.q_matrix) {
+            for (a, b) in row.iter().zip(expected_row) {
+                assert!((a - b).abs() < 1e-9 || (a.is_nan() && b.is_nan()));
+            }
+        }
+    }
+
+    // Coupled variants are always recomputed in full
+    let mut ccd_request = ccd_auto_request(
+        "synthetic".to_string(),
+        CcdConditioningStrategy::AutoSharedParents,
+    );
+    ccd_request.time_range.end = f64::INFINITY;
+    let ccd_previous = runner
+        .run_on_matrix(&ccd_request, &samples[..1000], None)
+        .expect("previous CCD run");
+    let ccd = runner
+        .run_incremental_on_matrix(&ccd_request, &samples, None, &ccd_previous)
+        .expect("CCD run");
+    assert_eq!(ccd.reused_windows, 0);
+}
+
+#[test]
+fn stitched_run_matches_run_on_joined_samples() {
+    let samples = synthetic_samples();
+    let mut request = ccd_auto_request(
+        "synthetic".to_string(),
+        CcdConditioningStrategy::AutoSharedParents,
+    );
+    request.algorithm_selection.enabled_variants = vec!["ST".to_string()];
+    request.variant_configs = None;
+    request.time_range.end = f64::INFINITY;
+
+    let recording = crate::StitchedRecording::from_parts(vec![
+        crate::RecordingPart::new("first", samples[..900].to_vec()).with_sampling_rate(256.0),
+        crate::RecordingPart::new("second", samples[900..].to_vec()).with_sampling_rate(256.0),
+    ])
+    .expect("stitch");
+    let runner = PureRustRunner::default();
+    let stitched = runner
+        .run_stitched(&request, &recording)
+        .expect("stitched run");
+    let full = runner
+        .run_on_matrix(&request, &samples, None)
+        .expect("full run");
+
+    assert_eq!(stitched.result.error_values, full.error_values);
+    assert_eq!(stitched.result.q_matrix.len(), full.q_matrix.len());
+    for (row, expected_row) in stitched.result.q_matrix.iter().zip(&full.q_matrix) {
+        for (a, b) in row.iter().zip(expected_row) {
+            assert!((a - b).abs() < 1e-9 || (a.is_nan() && b.is_nan()));
+        }
+    }
+
+    let map = &stitched.boundary_map;
+    let windows = full.error_values.as_ref().unwrap().len();
+    assert_eq!(map.files.len(), 2);
+    assert_eq!(map.files[0].windows.start, 0);
+    assert_eq!(map.files[0].windows.end, map.files[1].windows.start);
+    assert_eq!(map.files[1].windows.end, windows);
+    assert_eq!(map.boundaries[0].sample, 900);
+    assert!(!map.boundaries[0].spanning_windows.is_empty());
+
+    request.sampling_rate = Some(512.0);
+    assert!(runner.run_stitched(&request, &recording).is_err());
+}
@@ -22,6 +22,9 @@ pub enum DDAError {
 
     #[error("Invalid parameter: {0}")]
     InvalidParameter(String),
+
+    #[error("DDA analysis was cancelled")]
+    Cancelled,
 }
 
 pub type Result<T> = std::result::Result<T, DDAError>;
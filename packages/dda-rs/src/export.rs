@@ -0,0 +1,392 @@
+//! Text export of DDA Q-matrices (CSV / whitespace-delimited ASCII)
+//!
+//! Rows are analysis windows and columns are channels (or channel pairs),
+//! matching the layout users load into spreadsheets and MATLAB. Output is
+//! written row by row through a buffered writer, optionally gzip-compressed,
+//! so hour-long exports never materialize the whole text in memory.
+
+use crate::error::{DDAError, Result};
+use crate::types::{DDAResult, VariantResult};
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Text layout of an exported Q-matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TextFormat {
+    /// Comma-separated with a header row of channel labels
+    #[default]
+    Csv,
+    /// Space-separated with a `#`-prefixed header (readable by the ASCII loader)
+    Ascii,
+}
+
+/// Compression applied to the exported stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+}
+
+impl Compression {
+    /// Infer compression from a target path (`.gz` suffix selects gzip)
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("gz") => Self::Gzip,
+            _ => Self::None,
+        }
+    }
+
+    // Retain the Option-returning lookup used by the other option enums.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Some(Self::None),
+            "gzip" | "gz" => Some(Self::Gzip),
+            _ => None,
+        }
+    }
+}
+
+/// Options controlling a text export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextExportOptions {
+    pub format: TextFormat,
+    /// Explicit compression; when `None` it is inferred from the target path
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    /// Decimal separator for numeric values (`,` for many European locales)
+    #[serde(default = "default_decimal_separator")]
+    pub decimal_separator: char,
+    /// Column delimiter; when `None` it follows the format and decimal separator
+    #[serde(default)]
+    pub delimiter: Option<char>,
+}
+
+fn default_decimal_separator() -> char {
+    '.'
+}
+
+impl Default for TextExportOptions {
+    fn default() -> Self {
+        Self {
+            format: TextFormat::default(),
+            compression: None,
+            decimal_separator: default_decimal_separator(),
+            delimiter: None,
+        }
+    }
+}
+
+impl TextExportOptions {
+    /// Infer format and compression from a path such as `out.csv.gz` or `out.txt`
+    pub fn from_path(path: &Path) -> Self {
+        let compression = Compression::from_path(path);
+        let inner = if compression == Compression::Gzip {
+            path.file_stem().map(Path::new).unwrap_or(path)
+        } else {
+            path
+        };
+        let format = match inner.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => TextFormat::Csv,
+            Some(_) => TextFormat::Ascii,
+            None => TextFormat::Csv,
+        };
+        Self {
+            format,
+            compression: Some(compression),
+            ..Self::default()
+        }
+    }
+
+    /// Use `,` decimals with `;`-delimited CSV, as expected by European tools
+    pub fn with_comma_decimal(mut self) -> Self {
+        self.decimal_separator = ',';
+        self
+    }
+
+    /// Column delimiter actually written.
+    ///
+    /// CSV switches to `;` when `,` is the decimal separator.
+    pub fn resolved_delimiter(&self) -> char {
+        self.delimiter.unwrap_or(match self.format {
+            TextFormat::Csv if self.decimal_separator == ',' => ';',
+            TextFormat::Csv => ',',
+            TextFormat::Ascii => ' ',
+        })
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.resolved_delimiter() == self.decimal_separator {
+            return Err(DDAError::InvalidParameter(format!(
+                "delimiter '{}' must differ from the decimal separator",
+                self.decimal_separator
+            )));
+        }
+        Ok(())
+    }
+
+    fn resolved_compression(&self, path: &Path) -> Compression {
+        self.compression
+            .unwrap_or_else(|| Compression::from_path(path))
+    }
+}
+
+/// Buffered file writer, optionally wrapped in a streaming gzip encoder.
+pub enum ExportWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl ExportWriter {
+    /// Flush buffered data and, for gzip, write the stream trailer.
+    ///
+    /// Call this instead of relying on `Drop`, which swallows I/O errors.
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Self::Plain(mut writer) => writer.flush()?,
+            Self::Gzip(encoder) => encoder.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl Write for ExportWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Open a buffered writer for `path`, wrapping it in a streaming gzip encoder
+/// when requested.
+pub fn create_export_writer(path: &Path, compression: Compression) -> Result<ExportWriter> {
+    let file = BufWriter::new(File::create(path)?);
+    Ok(match compression {
+        Compression::None => ExportWriter::Plain(file),
+        Compression::Gzip => {
+            ExportWriter::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+        }
+    })
+}
+
+/// Write a Q-matrix (`[channel][window]`) as text, one window per row.
+pub fn write_q_matrix<W: Write>(
+    writer: &mut W,
+    q_matrix: &[Vec<f64>],
+    labels: &[String],
+    format: TextFormat,
+) -> Result<()> {
+    let options = TextExportOptions {
+        format,
+        ..TextExportOptions::default()
+    };
+    write_q_matrix_with_options(writer, q_matrix, labels, &options)
+}
+
+/// Like [`write_q_matrix`], honoring the delimiter and decimal separator in `options`.
+pub fn write_q_matrix_with_options<W: Write>(
+    writer: &mut W,
+    q_matrix: &[Vec<f64>],
+    labels: &[String],
+    options: &TextExportOptions,
+) -> Result<()> {
+    options.validate()?;
+    let format = options.format;
+    let num_windows = q_matrix.first().map_or(0, Vec::len);
+    if let Some((idx, row)) = q_matrix
+        .iter()
+        .enumerate()
+        .find(|(_, row)| row.len() != num_windows)
+    {
+        return Err(DDAError::InvalidParameter(format!(
+            "Q-matrix row {} has {} windows but row 0 has {}",
+            idx,
+            row.len(),
+            num_windows
+        )));
+    }
+
+    let separator = options.resolved_delimiter().to_string();
+    let separator = separator.as_str();
+
+    let header = (0..q_matrix.len())
+        .map(|idx| {
+            labels
+                .get(idx)
+                .cloned()
+                .unwrap_or_else(|| format!("Ch{}", idx + 1))
+        })
+        .collect::<Vec<_>>();
+    match format {
+        TextFormat::Csv => {
+            let escaped = header
+                .iter()
+                .map(|label| csv_escape(label, separator))
+                .collect::<Vec<_>>();
+            writeln!(writer, "window{}{}", separator, escaped.join(separator))?;
+        }
+        TextFormat::Ascii => writeln!(writer, "# {}", header.join(separator))?,
+    }
+
+    let mut line = String::new();
+    for window in 0..num_windows {
+        line.clear();
+        if format == TextFormat::Csv {
+            line.push_str(&window.to_string());
+            line.push_str(separator);
+        }
+        for (col, row) in q_matrix.iter().enumerate() {
+            if col > 0 {
+                line.push_str(separator);
+            }
+            let value = row[window];
+            if value.is_nan() {
+                line.push_str("nan");
+            } else if options.decimal_separator == '.' {
+                line.push_str(&value.to_string());
+            } else {
+                line.push_str(
+                    &value
+                        .to_string()
+                        .replace('.', &options.decimal_separator.to_string()),
+                );
+            }
+        }
+        line.push('\n');
+        writer.write_all(line.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Export a single variant's Q-matrix to `path`.
+pub fn export_variant_to_path<P: AsRef<Path>>(
+    variant: &VariantResult,
+    path: P,
+    options: &TextExportOptions,
+) -> Result<()> {
+    let path = path.as_ref();
+    let labels = variant.channel_labels.clone().unwrap_or_default();
+    let mut writer = create_export_writer(path, options.resolved_compression(path))?;
+    write_q_matrix_with_options(&mut writer, &variant.q_matrix, &labels, options)?;
+    writer.finish()
+}
+
+/// Export the primary Q-matrix of a result to `path`.
+pub fn export_result_to_path<P: AsRef<Path>>(
+    result: &DDAResult,
+    path: P,
+    options: &TextExportOptions,
+) -> Result<()> {
+    let path = path.as_ref();
+    let mut writer = create_export_writer(path, options.resolved_compression(path))?;
+    write_q_matrix_with_options(&mut writer, &result.q_matrix, &result.channels, options)?;
+    writer.finish()
+}
+
+fn csv_escape(value: &str, separator: &str) -> String {
+    if value.contains(separator) || value.contains(['"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn sample() -> (Vec<Vec<f64>>, Vec<String>) {
+        (
+            vec![vec![1.0, 2.0, 3.0], vec![0.5, f64::NAN, 1.5]],
+            vec!["Fp1".to_string(), "Fp2".to_string()],
+        )
+    }
+
+    #[test]
+    fn test_options_from_path() {
+        let csv_gz = TextExportOptions::from_path(Path::new("out.csv.gz"));
+        assert_eq!(csv_gz.format, TextFormat::Csv);
+        assert_eq!(csv_gz.compression, Some(Compression::Gzip));
+
+        let txt = TextExportOptions::from_path(Path::new("out.txt"));
+        assert_eq!(txt.format, TextFormat::Ascii);
+        assert_eq!(txt.compression, Some(Compression::None));
+    }
+
+    #[test]
+    fn test_write_csv_layout() {
+        let (q, labels) = sample();
+        let mut out = Vec::new();
+        write_q_matrix(&mut out, &q, &labels, TextFormat::Csv).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "window,Fp1,Fp2");
+        assert_eq!(lines[1], "0,1,0.5");
+        assert_eq!(lines[2], "1,2,nan");
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn test_comma_decimal_switches_csv_delimiter() {
+        let (q, labels) = sample();
+        let options = TextExportOptions::default().with_comma_decimal();
+        let mut out = Vec::new();
+        write_q_matrix_with_options(&mut out, &q, &labels, &options).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "window;Fp1;Fp2");
+        assert_eq!(lines[1], "0;1;0,5");
+
+        let clashing = TextExportOptions {
+            delimiter: Some(','),
+            ..options
+        };
+        assert!(write_q_matrix_with_options(&mut Vec::new(), &q, &labels, &clashing).is_err());
+    }
+
+    #[test]
+    fn test_gzip_export_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("q.txt.gz");
+        let (q, labels) = sample();
+        let variant = VariantResult {
+            variant_id: "ST".to_string(),
+            variant_name: "Single Timeseries".to_string(),
+            q_matrix: q,
+            channel_labels: Some(labels),
+            error_values: None,
+            row_channels: None,
+        };
+        export_variant_to_path(&variant, &path, &TextExportOptions::from_path(&path)).unwrap();
+
+        let mut decoded = String::new();
+        GzDecoder::new(File::open(&path).unwrap())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert!(decoded.starts_with("# Fp1 Fp2\n1 0.5\n"));
+    }
+
+    #[test]
+    fn test_ragged_matrix_rejected() {
+        let q = vec![vec![1.0, 2.0], vec![1.0]];
+        let mut out = Vec::new();
+        assert!(write_q_matrix(&mut out, &q, &[], TextFormat::Csv).is_err());
+    }
+}
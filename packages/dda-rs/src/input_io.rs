@@ -28,12 +28,22 @@ where
     F: FnMut(&PureRustProgress),
 {
     let samples = load_ascii_matrix_from_path(path)?;
+    let adjusted_request = bounded_request(request, start_bound, end_bound);
+    run_request_on_matrix_with_progress(&adjusted_request, &samples, None, on_progress)
+}
+
+/// Apply explicit sample bounds to a request's time range.
+pub(crate) fn bounded_request(
+    request: &DDARequest,
+    start_bound: Option<u64>,
+    end_bound: Option<u64>,
+) -> DDARequest {
     let mut adjusted_request = request.clone();
     if let (Some(start), Some(end)) = (start_bound, end_bound) {
         adjusted_request.time_range.start = start as f64;
         adjusted_request.time_range.end = end as f64;
     }
-    run_request_on_matrix_with_progress(&adjusted_request, &samples, None, on_progress)
+    adjusted_request
 }
 
 pub fn run_request_on_f64_matrix_file_with_progress<P: AsRef<Path>, F>(
@@ -1,26 +1,75 @@
+pub mod batch;
+pub mod cache;
+pub mod cancellation;
 pub mod ccd_stats;
+pub mod comparison;
+pub mod diff;
 pub mod engine;
 pub mod error;
+pub mod export;
 pub mod input_io;
 pub mod mmap_utils;
 pub mod network_motifs;
+pub mod output_reader;
+pub mod preprocessing;
 pub mod profiling;
+pub mod session;
+pub mod stitch;
+pub mod surrogates;
+pub mod sweep;
+pub mod typed_results;
 pub mod types;
+pub mod validate;
 pub mod variants;
 
+pub use batch::{BatchItemResult, BatchProgress, BatchRun};
+pub use cache::{CacheKey, DirectoryStore, ResultCache, ResultStore};
+pub use cancellation::CancellationToken;
 pub use ccd_stats::*;
+pub use comparison::{
+    compare_all_variants, compare_variants, ChannelComparison, ComparisonConfig, VariantComparison,
+};
+pub use diff::{diff_results, read_result_path, Deviation, ResultDiff, VariantDiff};
 pub use engine::{
     inspect_ccd_conditioning_sets_on_matrix, profile_ccd_conditioning_subsets_on_matrix,
-    run_request_on_matrix, run_request_on_matrix_with_progress,
-    score_ccd_conditioning_subsets_on_matrix, CcdConditioningInspection,
-    CcdConditioningSubsetProfile, CcdConditioningSubsetScore, NormalizationMode, PureRustOptions,
-    PureRustProgress, PureRustRunner, SvdBackend,
+    run_request_on_matrix, run_request_on_matrix_with_cancellation,
+    run_request_on_matrix_with_progress, score_ccd_conditioning_subsets_on_matrix,
+    CcdConditioningInspection, CcdConditioningSubsetProfile, CcdConditioningSubsetScore,
+    IncrementalRun, NormalizationMode, PureRustOptions, PureRustProgress, PureRustRunner,
+    SvdBackend,
 };
 pub use error::{DDAError, Result};
+pub use export::{
+    create_export_writer, export_result_to_path, export_variant_to_path, write_q_matrix,
+    write_q_matrix_with_options, Compression, ExportWriter, TextExportOptions, TextFormat,
+};
 pub use input_io::{
     load_ascii_matrix_from_path, load_f64_matrix_from_path, run_request_on_ascii_file,
     run_request_on_ascii_file_with_progress, run_request_on_f64_matrix_file_with_progress,
 };
 pub use network_motifs::*;
+pub use output_reader::{open_q_matrix, read_q_matrix_from_path, QMatrixReader, WindowRow};
+pub use preprocessing::{
+    CommonAverageReference, Detrend, HighPass, LowPass, PreprocessingPipeline, PreprocessingStep,
+    Preprocessor,
+};
+pub use session::{AnalysisSession, SessionStats, DEFAULT_MAX_CACHED_BYTES};
+pub use stitch::{
+    BoundaryMap, FileBoundary, FileSegment, RecordingPart, SegmentWindows, StitchedRecording,
+    StitchedRun,
+};
+pub use surrogates::{
+    surrogate_matrix, surrogate_series, SurrogateConfig, SurrogateMethod, SurrogateTestResult,
+    VariantSignificance,
+};
+pub use sweep::{SweepEntry, SweepGrid, SweepPoint, SweepResult, VariantSummary};
+pub use typed_results::{
+    CrossDynamicalResult, CrossTimeseriesResult, DirectedPair, DynamicalErgodicityResult,
+    SingleTimeseriesResult, SynchronizationResult, TypedVariantResult,
+};
 pub use types::*;
+pub use validate::{
+    validate_native_output, validate_output_path, validate_result, Diagnostic, Severity,
+    ValidationReport,
+};
 pub use variants::*;
@@ -1,8 +1,15 @@
 //! Network motif transformation for CD-DDA results
 //!
 //! Transforms CD-DDA Q-matrices into normalized adjacency matrices
-//! for circular network graph visualization.
+//! for circular network graph visualization, and tests directed 3-node
+//! motifs (the 16-class triad census) against randomized null graphs.
+//! Weighted graphs additionally report motif intensity and coherence
+//! (Onnela et al. 2005), so CT/CD coupling strength is not discarded by
+//! thresholding.
 
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 /// Network motif data for visualization
@@ -200,6 +207,664 @@ pub fn transform_cd_to_network_motifs(
     })
 }
 
+impl NetworkMotifData {
+    /// Directed graph of the thresholded edges of one adjacency matrix.
+    pub fn graph(&self, matrix_index: usize) -> Option<DirectedGraph> {
+        let matrix = self.adjacency_matrices.get(matrix_index)?;
+        let edges = matrix
+            .edges
+            .iter()
+            .map(|edge| (edge.from, edge.to))
+            .collect::<Vec<_>>();
+        DirectedGraph::from_edges(self.num_nodes, &edges).ok()
+    }
+
+    /// Weighted directed graph of one adjacency matrix.
+    pub fn weighted_graph(
+        &self,
+        matrix_index: usize,
+        config: &WeightedMotifConfig,
+    ) -> Option<WeightedDirectedGraph> {
+        let matrix = self.adjacency_matrices.get(matrix_index)?;
+        WeightedDirectedGraph::from_weights(self.num_nodes, &matrix.matrix, config).ok()
+    }
+}
+
+/// Unweighted directed graph without self-loops.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectedGraph {
+    num_nodes: usize,
+    /// Row-major `num_nodes × num_nodes` adjacency
+    adjacency: Vec<bool>,
+}
+
+impl DirectedGraph {
+    /// Build a graph from `(from, to)` pairs; self-loops are ignored.
+    pub fn from_edges(num_nodes: usize, edges: &[(usize, usize)]) -> Result<Self, String> {
+        let mut adjacency = vec![false; num_nodes * num_nodes];
+        for &(from, to) in edges {
+            if from >= num_nodes || to >= num_nodes {
+                return Err(format!(
+                    "Edge ({}, {}) is out of range for {} nodes",
+                    from, to, num_nodes
+                ));
+            }
+            if from != to {
+                adjacency[from * num_nodes + to] = true;
+            }
+        }
+        Ok(Self {
+            num_nodes,
+            adjacency,
+        })
+    }
+
+    /// Build a graph from a row-major weight matrix, keeping weights above `threshold`.
+    pub fn from_weights(num_nodes: usize, weights: &[f64], threshold: f64) -> Result<Self, String> {
+        if weights.len() != num_nodes * num_nodes {
+            return Err(format!(
+                "Weight matrix has {} entries, expected {}",
+                weights.len(),
+                num_nodes * num_nodes
+            ));
+        }
+        let edges = weights
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| **w > threshold)
+            .map(|(idx, _)| (idx / num_nodes, idx % num_nodes))
+            .collect::<Vec<_>>();
+        Self::from_edges(num_nodes, &edges)
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    pub fn has_edge(&self, from: usize, to: usize) -> bool {
+        from < self.num_nodes && to < self.num_nodes && self.adjacency[from * self.num_nodes + to]
+    }
+
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        self.adjacency
+            .iter()
+            .enumerate()
+            .filter(|(_, present)| **present)
+            .map(|(idx, _)| (idx / self.num_nodes, idx % self.num_nodes))
+            .collect()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.adjacency.iter().filter(|present| **present).count()
+    }
+
+    /// Count every node triple by its isomorphism class.
+    pub fn triad_census(&self) -> TriadCensus {
+        let n = self.num_nodes;
+        let mut counts = [0u64; 16];
+        for a in 0..n {
+            for b in (a + 1)..n {
+                for c in (b + 1)..n {
+                    counts[self.classify_triad(a, b, c) as usize] += 1;
+                }
+            }
+        }
+        TriadCensus { counts }
+    }
+
+    fn classify_triad(&self, a: usize, b: usize, c: usize) -> TriadClass {
+        let nodes = [a, b, c];
+        let mut out_degree = [0usize; 3];
+        let mut in_degree = [0usize; 3];
+        let (mut mutual, mut asymmetric) = (0, 0);
+        for i in 0..3 {
+            for j in (i + 1)..3 {
+                let forward = self.has_edge(nodes[i], nodes[j]);
+                let backward = self.has_edge(nodes[j], nodes[i]);
+                match (forward, backward) {
+                    (true, true) => mutual += 1,
+                    (true, false) | (false, true) => asymmetric += 1,
+                    (false, false) => {}
+                }
+                if forward {
+                    out_degree[i] += 1;
+                    in_degree[j] += 1;
+                }
+                if backward {
+                    out_degree[j] += 1;
+                    in_degree[i] += 1;
+                }
+            }
+        }
+
+        match (mutual, asymmetric) {
+            (0, 0) => TriadClass::T003,
+            (0, 1) => TriadClass::T012,
+            (1, 0) => TriadClass::T102,
+            (0, 2) => {
+                if out_degree.contains(&2) {
+                    TriadClass::T021D
+                } else if in_degree.contains(&2) {
+                    TriadClass::T021U
+                } else {
+                    TriadClass::T021C
+                }
+            }
+            (1, 1) => {
+                // The asymmetric edge either points into the mutual dyad (D) or out of it (U)
+                let outsider = (0..3)
+                    .find(|&i| out_degree[i] + in_degree[i] == 1)
+                    .unwrap_or(0);
+                if out_degree[outsider] == 1 {
+                    TriadClass::T111D
+                } else {
+                    TriadClass::T111U
+                }
+            }
+            (0, 3) => {
+                if out_degree.iter().all(|&d| d == 1) {
+                    TriadClass::T030C
+                } else {
+                    TriadClass::T030T
+                }
+            }
+            (2, 0) => TriadClass::T201,
+            (1, 2) => {
+                // The node outside the mutual dyad sends both edges (D), receives both (U),
+                // or lies on a path through the dyad (C)
+                let outsider = (0..3)
+                    .find(|&i| out_degree[i] + in_degree[i] == 2 && !self.in_mutual(nodes, i))
+                    .unwrap_or(0);
+                match out_degree[outsider] {
+                    2 => TriadClass::T120D,
+                    0 => TriadClass::T120U,
+                    _ => TriadClass::T120C,
+                }
+            }
+            (2, 1) => TriadClass::T210,
+            _ => TriadClass::T300,
+        }
+    }
+
+    fn in_mutual(&self, nodes: [usize; 3], i: usize) -> bool {
+        (0..3).any(|j| {
+            j != i && self.has_edge(nodes[i], nodes[j]) && self.has_edge(nodes[j], nodes[i])
+        })
+    }
+
+    /// Randomize by directed double-edge swaps, preserving every node's in- and out-degree.
+    fn rewired(&self, swaps_per_edge: usize, rng: &mut ChaCha8Rng) -> Self {
+        let mut graph = self.clone();
+        let mut edges = graph.edges();
+        if edges.len() < 2 {
+            return graph;
+        }
+        let n = graph.num_nodes;
+        let attempts = swaps_per_edge * edges.len() * 10;
+        let target = swaps_per_edge * edges.len();
+        let mut swaps = 0;
+        for _ in 0..attempts {
+            if swaps >= target {
+                break;
+            }
+            let i = rng.gen_range(0..edges.len());
+            let j = rng.gen_range(0..edges.len());
+            let (a, b) = edges[i];
+            let (c, d) = edges[j];
+            if a == c || b == d || a == d || c == b {
+                continue;
+            }
+            if graph.adjacency[a * n + d] || graph.adjacency[c * n + b] {
+                continue;
+            }
+            graph.adjacency[a * n + b] = false;
+            graph.adjacency[c * n + d] = false;
+            graph.adjacency[a * n + d] = true;
+            graph.adjacency[c * n + b] = true;
+            edges[i] = (a, d);
+            edges[j] = (c, b);
+            swaps += 1;
+        }
+        graph
+    }
+
+    /// Random graph with the same node and edge counts.
+    fn shuffled_edges(&self, rng: &mut ChaCha8Rng) -> Self {
+        let n = self.num_nodes;
+        let mut slots = (0..n * n)
+            .filter(|idx| idx / n != idx % n)
+            .collect::<Vec<_>>();
+        slots.shuffle(rng);
+        let mut adjacency = vec![false; n * n];
+        for &idx in slots.iter().take(self.edge_count()) {
+            adjacency[idx] = true;
+        }
+        Self {
+            num_nodes: n,
+            adjacency,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WeightedMotifConfig {
+    /// Edges with weight at or below this value are dropped
+    pub threshold: f64,
+    /// Divide weights by the largest weight so intensities lie in `[0, 1]`
+    pub normalize: bool,
+}
+
+impl Default for WeightedMotifConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 0.0,
+            normalize: true,
+        }
+    }
+}
+
+/// Directed graph with positive edge weights and no self-loops.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeightedDirectedGraph {
+    num_nodes: usize,
+    /// Row-major `num_nodes × num_nodes` weights; 0 where there is no edge
+    weights: Vec<f64>,
+}
+
+impl WeightedDirectedGraph {
+    /// Build a graph from a row-major weight matrix; NaN and self-loop weights are ignored.
+    pub fn from_weights(
+        num_nodes: usize,
+        weights: &[f64],
+        config: &WeightedMotifConfig,
+    ) -> Result<Self, String> {
+        if weights.len() != num_nodes * num_nodes {
+            return Err(format!(
+                "Weight matrix has {} entries, expected {}",
+                weights.len(),
+                num_nodes * num_nodes
+            ));
+        }
+        let mut kept = weights
+            .iter()
+            .enumerate()
+            .map(|(idx, &w)| {
+                if idx / num_nodes != idx % num_nodes && w > config.threshold && w > 0.0 {
+                    w
+                } else {
+                    0.0
+                }
+            })
+            .collect::<Vec<_>>();
+        if config.normalize {
+            let max = kept.iter().copied().fold(0.0, f64::max);
+            if max > 0.0 {
+                kept.iter_mut().for_each(|w| *w /= max);
+            }
+        }
+        Ok(Self {
+            num_nodes,
+            weights: kept,
+        })
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    /// Weight of `from -> to`, 0 when absent.
+    pub fn weight(&self, from: usize, to: usize) -> f64 {
+        if from < self.num_nodes && to < self.num_nodes {
+            self.weights[from * self.num_nodes + to]
+        } else {
+            0.0
+        }
+    }
+
+    /// Unweighted structure of the graph.
+    pub fn graph(&self) -> DirectedGraph {
+        DirectedGraph {
+            num_nodes: self.num_nodes,
+            adjacency: self.weights.iter().map(|w| *w > 0.0).collect(),
+        }
+    }
+
+    /// Triad census with the intensity and coherence of every connected motif class.
+    ///
+    /// A triad's intensity is the geometric mean of its edge weights and its
+    /// coherence the ratio of geometric to arithmetic mean (1 when all edges
+    /// are equally strong).
+    pub fn motif_intensity(&self) -> WeightedMotifProfile {
+        let graph = self.graph();
+        let n = self.num_nodes;
+        let mut counts = [0u64; 16];
+        let mut intensity = [0.0f64; 16];
+        let mut coherence = [0.0f64; 16];
+        for a in 0..n {
+            for b in (a + 1)..n {
+                for c in (b + 1)..n {
+                    let class = graph.classify_triad(a, b, c) as usize;
+                    counts[class] += 1;
+                    let edges = [(a, b), (b, a), (a, c), (c, a), (b, c), (c, b)]
+                        .iter()
+                        .map(|&(from, to)| self.weight(from, to))
+                        .filter(|w| *w > 0.0)
+                        .collect::<Vec<_>>();
+                    if edges.is_empty() {
+                        continue;
+                    }
+                    let k = edges.len() as f64;
+                    let geometric = (edges.iter().map(|w| w.ln()).sum::<f64>() / k).exp();
+                    let arithmetic = edges.iter().sum::<f64>() / k;
+                    intensity[class] += geometric;
+                    coherence[class] += geometric / arithmetic;
+                }
+            }
+        }
+
+        let motifs = TriadClass::ALL
+            .iter()
+            .copied()
+            .filter(|class| class.is_connected())
+            .map(|class| {
+                let idx = class as usize;
+                let count = counts[idx];
+                let mean = |total: f64| {
+                    if count > 0 {
+                        total / count as f64
+                    } else {
+                        0.0
+                    }
+                };
+                WeightedMotifStatistic {
+                    class,
+                    count,
+                    total_intensity: intensity[idx],
+                    mean_intensity: mean(intensity[idx]),
+                    mean_coherence: mean(coherence[idx]),
+                }
+            })
+            .collect();
+
+        WeightedMotifProfile {
+            census: TriadCensus { counts },
+            motifs,
+        }
+    }
+}
+
+/// Strength of one connected motif class in a weighted graph.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WeightedMotifStatistic {
+    pub class: TriadClass,
+    pub count: u64,
+    /// Sum of triad intensities; the weighted analogue of `count`
+    pub total_intensity: f64,
+    pub mean_intensity: f64,
+    /// Mean coherence in `(0, 1]`; 0 when the class does not occur
+    pub mean_coherence: f64,
+}
+
+/// Weighted motif distribution of a graph.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WeightedMotifProfile {
+    pub census: TriadCensus,
+    /// One entry per connected triad class, in [`TriadClass::ALL`] order
+    pub motifs: Vec<WeightedMotifStatistic>,
+}
+
+impl WeightedMotifProfile {
+    pub fn motif(&self, class: TriadClass) -> Option<&WeightedMotifStatistic> {
+        self.motifs.iter().find(|motif| motif.class == class)
+    }
+}
+
+/// Isomorphism classes of directed triads in MAN notation (mutual, asymmetric, null dyads).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TriadClass {
+    #[serde(rename = "003")]
+    T003,
+    #[serde(rename = "012")]
+    T012,
+    #[serde(rename = "102")]
+    T102,
+    /// A <- B -> C
+    #[serde(rename = "021D")]
+    T021D,
+    /// A -> B <- C
+    #[serde(rename = "021U")]
+    T021U,
+    /// A -> B -> C
+    #[serde(rename = "021C")]
+    T021C,
+    /// A <-> B <- C
+    #[serde(rename = "111D")]
+    T111D,
+    /// A <-> B -> C
+    #[serde(rename = "111U")]
+    T111U,
+    /// A -> B <- C, A -> C (feed-forward loop)
+    #[serde(rename = "030T")]
+    T030T,
+    /// A <- B <- C, A -> C (feedback loop)
+    #[serde(rename = "030C")]
+    T030C,
+    #[serde(rename = "201")]
+    T201,
+    /// A <- B -> C, A <-> C
+    #[serde(rename = "120D")]
+    T120D,
+    /// A -> B <- C, A <-> C
+    #[serde(rename = "120U")]
+    T120U,
+    /// A -> B -> C, A <-> C
+    #[serde(rename = "120C")]
+    T120C,
+    #[serde(rename = "210")]
+    T210,
+    #[serde(rename = "300")]
+    T300,
+}
+
+impl TriadClass {
+    pub const ALL: [TriadClass; 16] = [
+        TriadClass::T003,
+        TriadClass::T012,
+        TriadClass::T102,
+        TriadClass::T021D,
+        TriadClass::T021U,
+        TriadClass::T021C,
+        TriadClass::T111D,
+        TriadClass::T111U,
+        TriadClass::T030T,
+        TriadClass::T030C,
+        TriadClass::T201,
+        TriadClass::T120D,
+        TriadClass::T120U,
+        TriadClass::T120C,
+        TriadClass::T210,
+        TriadClass::T300,
+    ];
+
+    /// MAN code, e.g. `"030T"`.
+    pub fn code(self) -> &'static str {
+        match self {
+            TriadClass::T003 => "003",
+            TriadClass::T012 => "012",
+            TriadClass::T102 => "102",
+            TriadClass::T021D => "021D",
+            TriadClass::T021U => "021U",
+            TriadClass::T021C => "021C",
+            TriadClass::T111D => "111D",
+            TriadClass::T111U => "111U",
+            TriadClass::T030T => "030T",
+            TriadClass::T030C => "030C",
+            TriadClass::T201 => "201",
+            TriadClass::T120D => "120D",
+            TriadClass::T120U => "120U",
+            TriadClass::T120C => "120C",
+            TriadClass::T210 => "210",
+            TriadClass::T300 => "300",
+        }
+    }
+
+    /// Whether the triad is weakly connected, i.e. a 3-node motif.
+    pub fn is_connected(self) -> bool {
+        !matches!(self, TriadClass::T003 | TriadClass::T012 | TriadClass::T102)
+    }
+}
+
+/// Number of node triples in each [`TriadClass`], indexed in [`TriadClass::ALL`] order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TriadCensus {
+    pub counts: [u64; 16],
+}
+
+impl TriadCensus {
+    pub fn count(&self, class: TriadClass) -> u64 {
+        self.counts[class as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MotifNullModel {
+    /// Directed double-edge swaps that keep every node's in- and out-degree
+    DegreePreservingRewiring { swaps_per_edge: usize },
+    /// Uniformly random graph with the same number of edges
+    EdgeCountPreserving,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MotifNullConfig {
+    pub model: MotifNullModel,
+    pub n_randomizations: usize,
+    pub rng_seed: u64,
+}
+
+impl Default for MotifNullConfig {
+    fn default() -> Self {
+        Self {
+            model: MotifNullModel::DegreePreservingRewiring { swaps_per_edge: 10 },
+            n_randomizations: 100,
+            rng_seed: 0,
+        }
+    }
+}
+
+/// Observed count of one connected motif against its null distribution.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MotifStatistic {
+    pub class: TriadClass,
+    pub observed: u64,
+    pub null_mean: f64,
+    pub null_std: f64,
+    /// `(observed - null mean) / null std`; NaN when the null has no spread
+    pub z_score: f64,
+    /// Over-representation p-value, `(1 + #null >= observed) / (1 + n)`
+    pub p_value: f64,
+}
+
+/// Motif census of a graph scored against randomized graphs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MotifSignificance {
+    pub census: TriadCensus,
+    pub config: MotifNullConfig,
+    /// One entry per connected triad class, in [`TriadClass::ALL`] order
+    pub motifs: Vec<MotifStatistic>,
+}
+
+impl MotifSignificance {
+    pub fn motif(&self, class: TriadClass) -> Option<&MotifStatistic> {
+        self.motifs.iter().find(|motif| motif.class == class)
+    }
+
+    /// Z-scores normalized to unit length (Milo et al. 2004); undefined z-scores count as 0.
+    pub fn significance_profile(&self) -> Vec<f64> {
+        let z = self
+            .motifs
+            .iter()
+            .map(|motif| {
+                if motif.z_score.is_finite() {
+                    motif.z_score
+                } else {
+                    0.0
+                }
+            })
+            .collect::<Vec<_>>();
+        let norm = z.iter().map(|v| v * v).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            z.iter().map(|v| v / norm).collect()
+        } else {
+            z
+        }
+    }
+}
+
+/// Score the 3-node motifs of `graph` against `config.n_randomizations` null graphs.
+pub fn motif_significance(
+    graph: &DirectedGraph,
+    config: &MotifNullConfig,
+) -> Result<MotifSignificance, String> {
+    if config.n_randomizations == 0 {
+        return Err("Motif significance requires n_randomizations > 0".to_string());
+    }
+    if let MotifNullModel::DegreePreservingRewiring { swaps_per_edge: 0 } = config.model {
+        return Err("Degree-preserving rewiring requires swaps_per_edge > 0".to_string());
+    }
+
+    let census = graph.triad_census();
+    let nulls = (0..config.n_randomizations)
+        .into_par_iter()
+        .map(|idx| {
+            let mut rng = ChaCha8Rng::seed_from_u64(config.rng_seed.wrapping_add(idx as u64));
+            let randomized = match config.model {
+                MotifNullModel::DegreePreservingRewiring { swaps_per_edge } => {
+                    graph.rewired(swaps_per_edge, &mut rng)
+                }
+                MotifNullModel::EdgeCountPreserving => graph.shuffled_edges(&mut rng),
+            };
+            randomized.triad_census()
+        })
+        .collect::<Vec<_>>();
+
+    let n = nulls.len() as f64;
+    let motifs = TriadClass::ALL
+        .iter()
+        .copied()
+        .filter(|class| class.is_connected())
+        .map(|class| {
+            let observed = census.count(class);
+            let null = nulls
+                .iter()
+                .map(|c| c.count(class) as f64)
+                .collect::<Vec<_>>();
+            let null_mean = null.iter().sum::<f64>() / n;
+            let null_std = (null.iter().map(|v| (v - null_mean).powi(2)).sum::<f64>() / n).sqrt();
+            let z_score = if null_std > 0.0 {
+                (observed as f64 - null_mean) / null_std
+            } else {
+                f64::NAN
+            };
+            let at_least = null.iter().filter(|&&v| v >= observed as f64).count();
+            MotifStatistic {
+                class,
+                observed,
+                null_mean,
+                null_std,
+                z_score,
+                p_value: (1 + at_least) as f64 / (1.0 + n),
+            }
+        })
+        .collect();
+
+    Ok(MotifSignificance {
+        census,
+        config: config.clone(),
+        motifs,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -265,4 +930,108 @@ mod tests {
         // Pair 1: 0.25 -> (0.25 - 0) / 0.5 = 0.5 (kept, > 0.25)
         assert!(!middle_matrix.edges.is_empty());
     }
+
+    #[test]
+    fn test_triad_census_classifies_each_class() {
+        // One canonical triad per class on nodes 0, 1, 2
+        let cases: [(&[(usize, usize)], TriadClass); 16] = [
+            (&[], TriadClass::T003),
+            (&[(0, 1)], TriadClass::T012),
+            (&[(0, 1), (1, 0)], TriadClass::T102),
+            (&[(1, 0), (1, 2)], TriadClass::T021D),
+            (&[(0, 1), (2, 1)], TriadClass::T021U),
+            (&[(0, 1), (1, 2)], TriadClass::T021C),
+            (&[(0, 1), (1, 0), (2, 1)], TriadClass::T111D),
+            (&[(0, 1), (1, 0), (1, 2)], TriadClass::T111U),
+            (&[(0, 1), (2, 1), (0, 2)], TriadClass::T030T),
+            (&[(0, 1), (1, 2), (2, 0)], TriadClass::T030C),
+            (&[(0, 1), (1, 0), (1, 2), (2, 1)], TriadClass::T201),
+            (&[(1, 0), (1, 2), (0, 2), (2, 0)], TriadClass::T120D),
+            (&[(0, 1), (2, 1), (0, 2), (2, 0)], TriadClass::T120U),
+            (&[(0, 1), (1, 2), (0, 2), (2, 0)], TriadClass::T120C),
+            (&[(0, 1), (1, 0), (1, 2), (2, 1), (0, 2)], TriadClass::T210),
+            (
+                &[(0, 1), (1, 0), (1, 2), (2, 1), (0, 2), (2, 0)],
+                TriadClass::T300,
+            ),
+        ];
+        for (edges, class) in cases {
+            let census = DirectedGraph::from_edges(3, edges).unwrap().triad_census();
+            assert_eq!(census.count(class), 1, "expected {}", class.code());
+            assert_eq!(census.counts.iter().sum::<u64>(), 1);
+        }
+
+        let census = DirectedGraph::from_edges(5, &[(0, 1), (1, 2)])
+            .unwrap()
+            .triad_census();
+        assert_eq!(census.counts.iter().sum::<u64>(), 10);
+    }
+
+    #[test]
+    fn test_weighted_motif_intensity_and_coherence() {
+        // Feed-forward loop 0 -> 1 -> 2, 0 -> 2 with unequal weights, plus a weak edge 2 -> 3
+        #[rustfmt::skip]
+        let weights = vec![
+            0.0, 0.8, 0.2, 0.0,
+            0.0, 0.0, 0.8, 0.0,
+            0.0, 0.0, 0.0, 0.05,
+            0.0, 0.0, 0.0, 0.0,
+        ];
+        let config = WeightedMotifConfig {
+            threshold: 0.1,
+            normalize: true,
+        };
+        let graph = WeightedDirectedGraph::from_weights(4, &weights, &config).unwrap();
+        assert_eq!(graph.weight(0, 1), 1.0);
+        assert_eq!(graph.weight(2, 3), 0.0);
+
+        let profile = graph.motif_intensity();
+        assert_eq!(profile.census, graph.graph().triad_census());
+        let ffl = profile.motif(TriadClass::T030T).unwrap();
+        assert_eq!(ffl.count, 1);
+        // Normalized weights 1, 1, 0.25: geometric mean 0.25^(1/3), arithmetic mean 0.75
+        let geometric = 0.25f64.powf(1.0 / 3.0);
+        assert!((ffl.mean_intensity - geometric).abs() < 1e-12);
+        assert!((ffl.mean_coherence - geometric / 0.75).abs() < 1e-12);
+        assert_eq!(profile.motif(TriadClass::T300).unwrap().mean_coherence, 0.0);
+    }
+
+    #[test]
+    fn test_feed_forward_loops_are_over_represented() {
+        // Chain of feed-forward loops: i -> i+1, i+1 -> i+2, i -> i+2
+        let n = 12;
+        let edges = (0..n - 2)
+            .flat_map(|i| [(i, i + 1), (i + 1, i + 2), (i, i + 2)])
+            .collect::<Vec<_>>();
+        let graph = DirectedGraph::from_edges(n, &edges).unwrap();
+        let config = MotifNullConfig {
+            n_randomizations: 50,
+            rng_seed: 3,
+            ..Default::default()
+        };
+        let result = motif_significance(&graph, &config).unwrap();
+
+        assert_eq!(result.motifs.len(), 13);
+        let ffl = result.motif(TriadClass::T030T).unwrap();
+        assert_eq!(ffl.observed, (n - 2) as u64);
+        assert!(ffl.z_score > 2.0, "z = {}", ffl.z_score);
+        assert!(ffl.p_value < 0.05);
+
+        let profile = result.significance_profile();
+        let norm = profile.iter().map(|v| v * v).sum::<f64>();
+        assert!((norm - 1.0).abs() < 1e-9);
+
+        // Rewiring preserves every node's degrees
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let rewired = graph.rewired(10, &mut rng);
+        for node in 0..n {
+            let degrees = |g: &DirectedGraph| {
+                let out = (0..n).filter(|&j| g.has_edge(node, j)).count();
+                let inc = (0..n).filter(|&j| g.has_edge(j, node)).count();
+                (out, inc)
+            };
+            assert_eq!(degrees(&graph), degrees(&rewired));
+        }
+        assert_ne!(rewired, graph);
+    }
 }
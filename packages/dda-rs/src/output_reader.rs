@@ -0,0 +1,313 @@
+//! Streaming reader for exported Q-matrix text (CSV / whitespace-delimited ASCII)
+//!
+//! The inverse of [`crate::export`]: files are read line by line, one window
+//! per row, so long runs can be consumed as an iterator without holding the
+//! text in memory. [`read_q_matrix_from_path`] collects into the in-memory
+//! `[channel][window]` layout and enforces an optional memory cap.
+
+use crate::error::{DDAError, Result};
+use crate::export::{Compression, TextExportOptions, TextFormat};
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::path::Path;
+
+/// One exported window: the Q value of every column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowRow {
+    pub window: usize,
+    pub values: Vec<f64>,
+}
+
+/// Iterator over the window rows of an exported Q-matrix.
+pub struct QMatrixReader<R> {
+    lines: Lines<R>,
+    labels: Vec<String>,
+    format: TextFormat,
+    delimiter: char,
+    decimal_separator: char,
+    line_idx: usize,
+    next_window: usize,
+}
+
+impl<R: BufRead> QMatrixReader<R> {
+    /// Read the header of `reader`; `options` describe how the file was exported.
+    pub fn new(reader: R, options: &TextExportOptions) -> Result<Self> {
+        let mut lines = reader.lines();
+        let mut line_idx = 0;
+        let header = loop {
+            let Some(line) = lines.next() else {
+                return Err(DDAError::ParseError(
+                    "Q-matrix export has no header row".to_string(),
+                ));
+            };
+            line_idx += 1;
+            let line = line?;
+            if !line.trim().is_empty() {
+                break line;
+            }
+        };
+
+        let delimiter = options.resolved_delimiter();
+        let labels = match options.format {
+            TextFormat::Csv => {
+                let mut fields = split_csv_header(&header, delimiter);
+                if fields.first().map(String::as_str) != Some("window") {
+                    return Err(DDAError::ParseError(
+                        "CSV Q-matrix export must start with a 'window' column".to_string(),
+                    ));
+                }
+                fields.remove(0);
+                fields
+            }
+            TextFormat::Ascii => {
+                let Some(names) = header.trim().strip_prefix('#') else {
+                    return Err(DDAError::ParseError(
+                        "ASCII Q-matrix export must start with a '#' header".to_string(),
+                    ));
+                };
+                split_fields(names, delimiter).map(str::to_string).collect()
+            }
+        };
+
+        Ok(Self {
+            lines,
+            labels,
+            format: options.format,
+            delimiter,
+            decimal_separator: options.decimal_separator,
+            line_idx,
+            next_window: 0,
+        })
+    }
+
+    /// Column labels from the header.
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    fn parse_row(&self, line: &str) -> Result<WindowRow> {
+        let mut fields = split_fields(line, self.delimiter);
+        let window = match self.format {
+            TextFormat::Csv => {
+                let field = fields.next().unwrap_or_default();
+                field.parse::<usize>().map_err(|_| {
+                    DDAError::ParseError(format!(
+                        "Line {}: invalid window index '{}'",
+                        self.line_idx, field
+                    ))
+                })?
+            }
+            TextFormat::Ascii => self.next_window,
+        };
+        let values = fields
+            .map(|field| self.parse_value(field))
+            .collect::<Result<Vec<_>>>()?;
+        if values.len() != self.labels.len() {
+            return Err(DDAError::ParseError(format!(
+                "Line {} has {} values but the header names {} columns",
+                self.line_idx,
+                values.len(),
+                self.labels.len()
+            )));
+        }
+        Ok(WindowRow { window, values })
+    }
+
+    fn parse_value(&self, field: &str) -> Result<f64> {
+        if field.eq_ignore_ascii_case("nan") {
+            return Ok(f64::NAN);
+        }
+        let parsed = if self.decimal_separator == '.' {
+            field.parse::<f64>()
+        } else {
+            field.replace(self.decimal_separator, ".").parse::<f64>()
+        };
+        parsed.map_err(|_| {
+            DDAError::ParseError(format!(
+                "Line {}: failed to parse value '{}'",
+                self.line_idx, field
+            ))
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for QMatrixReader<R> {
+    type Item = Result<WindowRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err.into())),
+            };
+            self.line_idx += 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let row = self.parse_row(trimmed);
+            if row.is_ok() {
+                self.next_window += 1;
+            }
+            return Some(row);
+        }
+    }
+}
+
+/// Open an exported Q-matrix, decompressing `.gz` files (or as `options` specify).
+pub fn open_q_matrix<P: AsRef<Path>>(
+    path: P,
+    options: &TextExportOptions,
+) -> Result<QMatrixReader<Box<dyn BufRead>>> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let compression = options
+        .compression
+        .unwrap_or_else(|| Compression::from_path(path));
+    let reader: Box<dyn BufRead> = match compression {
+        Compression::None => Box::new(BufReader::new(file)),
+        Compression::Gzip => Box::new(BufReader::new(GzDecoder::new(file))),
+    };
+    QMatrixReader::new(reader, options)
+}
+
+/// Read an exported Q-matrix into `(labels, [channel][window])`.
+///
+/// With `max_bytes` set, reading stops with an error once the collected
+/// values would exceed that many bytes.
+pub fn read_q_matrix_from_path<P: AsRef<Path>>(
+    path: P,
+    options: &TextExportOptions,
+    max_bytes: Option<usize>,
+) -> Result<(Vec<String>, Vec<Vec<f64>>)> {
+    let path = path.as_ref();
+    let reader = open_q_matrix(path, options)?;
+    let labels = reader.labels().to_vec();
+    let columns = labels.len().max(1);
+    let row_bytes = columns * std::mem::size_of::<f64>();
+    let max_windows = max_bytes.map(|cap| cap / row_bytes);
+
+    let mut q_matrix = vec![Vec::new(); labels.len()];
+
+    for (count, row) in reader.enumerate() {
+        let row = row?;
+        if max_windows.is_some_and(|cap| count >= cap) {
+            return Err(DDAError::InvalidParameter(format!(
+                "Q-matrix exceeds the memory cap of {} bytes after {} windows",
+                max_bytes.unwrap_or_default(),
+                count
+            )));
+        }
+        for (column, value) in q_matrix.iter_mut().zip(row.values) {
+            column.push(value);
+        }
+    }
+    for column in &mut q_matrix {
+        column.shrink_to_fit();
+    }
+    Ok((labels, q_matrix))
+}
+
+fn split_fields(line: &str, delimiter: char) -> impl Iterator<Item = &str> {
+    let whitespace = delimiter.is_ascii_whitespace();
+    line.split(move |c: char| {
+        if whitespace {
+            c.is_ascii_whitespace()
+        } else {
+            c == delimiter
+        }
+    })
+    .map(str::trim)
+    .filter(move |field| !whitespace || !field.is_empty())
+}
+
+/// Split a CSV header, undoing the quoting applied to labels on export.
+fn split_csv_header(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{create_export_writer, write_q_matrix_with_options};
+    use std::io::Cursor;
+
+    fn sample() -> (Vec<Vec<f64>>, Vec<String>) {
+        (
+            vec![vec![1.0, 2.5, 3.0], vec![0.5, f64::NAN, 1.5]],
+            vec!["Fp1".to_string(), "Fp2;ref".to_string()],
+        )
+    }
+
+    fn same(a: &[Vec<f64>], b: &[Vec<f64>]) -> bool {
+        a.len() == b.len()
+            && a.iter().zip(b).all(|(x, y)| {
+                x.len() == y.len()
+                    && x.iter()
+                        .zip(y)
+                        .all(|(u, v)| u == v || (u.is_nan() && v.is_nan()))
+            })
+    }
+
+    #[test]
+    fn test_round_trips_export_layouts() {
+        let (q, labels) = sample();
+        let layouts = [
+            TextExportOptions::default(),
+            TextExportOptions::default().with_comma_decimal(),
+            TextExportOptions {
+                format: TextFormat::Ascii,
+                ..TextExportOptions::default()
+            },
+        ];
+        for options in layouts {
+            let mut out = Vec::new();
+            write_q_matrix_with_options(&mut out, &q, &labels, &options).unwrap();
+            let reader = QMatrixReader::new(Cursor::new(out), &options).unwrap();
+            if options.format == TextFormat::Csv {
+                assert_eq!(reader.labels(), labels.as_slice());
+            }
+            let rows = reader.collect::<Result<Vec<_>>>().unwrap();
+            assert_eq!(rows.len(), 3);
+            assert_eq!(rows[1].window, 1);
+            assert_eq!(rows[1].values[0], 2.5);
+            assert!(rows[1].values[1].is_nan());
+        }
+    }
+
+    #[test]
+    fn test_read_from_gzip_path_with_memory_cap() {
+        let (q, labels) = sample();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("q.csv.gz");
+        let options = TextExportOptions::from_path(&path);
+        let mut writer = create_export_writer(&path, Compression::Gzip).unwrap();
+        write_q_matrix_with_options(&mut writer, &q, &labels, &options).unwrap();
+        writer.finish().unwrap();
+
+        let (read_labels, read_q) = read_q_matrix_from_path(&path, &options, None).unwrap();
+        assert_eq!(read_labels, labels);
+        assert!(same(&read_q, &q));
+
+        // Two columns of f64 per window: 32 bytes hold two windows, not three
+        let err = read_q_matrix_from_path(&path, &options, Some(32)).unwrap_err();
+        assert!(err.to_string().contains("memory cap"));
+    }
+}
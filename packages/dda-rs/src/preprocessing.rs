@@ -0,0 +1,369 @@
+//! Preprocessing applied to the sample matrix before analysis
+//!
+//! A [`PreprocessingPipeline`] is an ordered list of [`Preprocessor`] stages.
+//! [`PureRustRunner`](crate::PureRustRunner) runs the request's own
+//! highpass/lowpass options first, then the stages it was configured with, on
+//! a copy of the samples (rows are time points, columns are channels). Every
+//! stage is recorded in [`DDAResult::preprocessing`](crate::DDAResult) so a
+//! result says what was done to the data behind it.
+//!
+//! Built-in stages:
+//!
+//! - [`HighPass`] and [`LowPass`]: second-order Butterworth filters run
+//!   forward and backward, so they add no phase shift.
+//! - [`Detrend`]: removes each channel's least-squares line.
+//! - [`CommonAverageReference`]: subtracts the mean across channels.
+
+use crate::error::{DDAError, Result};
+use crate::types::PreprocessingOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+use std::sync::Arc;
+
+/// Provenance record of one applied stage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreprocessingStep {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, f64>,
+}
+
+impl PreprocessingStep {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            parameters: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_parameter(mut self, key: impl Into<String>, value: f64) -> Self {
+        self.parameters.insert(key.into(), value);
+        self
+    }
+}
+
+/// One stage of a [`PreprocessingPipeline`]
+pub trait Preprocessor: Send + Sync + std::fmt::Debug {
+    /// What the stage does, as recorded in the result
+    fn step(&self) -> PreprocessingStep;
+
+    /// Transform `samples` (rows are time points) in place
+    fn apply(&self, samples: &mut [Vec<f64>], sampling_rate: Option<f64>) -> Result<()>;
+}
+
+/// Ordered preprocessing stages
+#[derive(Debug, Clone, Default)]
+pub struct PreprocessingPipeline {
+    stages: Vec<Arc<dyn Preprocessor>>,
+}
+
+impl PreprocessingPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stages for a request's highpass/lowpass options
+    pub fn from_options(options: &PreprocessingOptions) -> Self {
+        let mut pipeline = Self::new();
+        if let Some(cutoff) = options.highpass {
+            pipeline.push(HighPass::new(cutoff));
+        }
+        if let Some(cutoff) = options.lowpass {
+            pipeline.push(LowPass::new(cutoff));
+        }
+        pipeline
+    }
+
+    pub fn push(&mut self, stage: impl Preprocessor + 'static) {
+        self.stages.push(Arc::new(stage));
+    }
+
+    pub fn with(mut self, stage: impl Preprocessor + 'static) -> Self {
+        self.push(stage);
+        self
+    }
+
+    /// Append every stage of `other`
+    pub fn extend(&mut self, other: &PreprocessingPipeline) {
+        self.stages.extend(other.stages.iter().cloned());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub fn steps(&self) -> Vec<PreprocessingStep> {
+        self.stages.iter().map(|stage| stage.step()).collect()
+    }
+
+    /// Processed copy of `samples`
+    pub fn apply(&self, samples: &[Vec<f64>], sampling_rate: Option<f64>) -> Result<Vec<Vec<f64>>> {
+        let mut processed = samples.to_vec();
+        for stage in &self.stages {
+            stage.apply(&mut processed, sampling_rate)?;
+        }
+        Ok(processed)
+    }
+}
+
+/// Second-order Butterworth highpass, zero phase
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighPass {
+    pub cutoff_hz: f64,
+}
+
+impl HighPass {
+    pub fn new(cutoff_hz: f64) -> Self {
+        Self { cutoff_hz }
+    }
+}
+
+impl Preprocessor for HighPass {
+    fn step(&self) -> PreprocessingStep {
+        PreprocessingStep::new("highpass").with_parameter("cutoff_hz", self.cutoff_hz)
+    }
+
+    fn apply(&self, samples: &mut [Vec<f64>], sampling_rate: Option<f64>) -> Result<()> {
+        let biquad = Biquad::butterworth(FilterKind::HighPass, self.cutoff_hz, sampling_rate)?;
+        filter_channels(samples, &biquad);
+        Ok(())
+    }
+}
+
+/// Second-order Butterworth lowpass, zero phase
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LowPass {
+    pub cutoff_hz: f64,
+}
+
+impl LowPass {
+    pub fn new(cutoff_hz: f64) -> Self {
+        Self { cutoff_hz }
+    }
+}
+
+impl Preprocessor for LowPass {
+    fn step(&self) -> PreprocessingStep {
+        PreprocessingStep::new("lowpass").with_parameter("cutoff_hz", self.cutoff_hz)
+    }
+
+    fn apply(&self, samples: &mut [Vec<f64>], sampling_rate: Option<f64>) -> Result<()> {
+        let biquad = Biquad::butterworth(FilterKind::LowPass, self.cutoff_hz, sampling_rate)?;
+        filter_channels(samples, &biquad);
+        Ok(())
+    }
+}
+
+/// Removes each channel's least-squares line
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Detrend;
+
+impl Preprocessor for Detrend {
+    fn step(&self) -> PreprocessingStep {
+        PreprocessingStep::new("detrend")
+    }
+
+    fn apply(&self, samples: &mut [Vec<f64>], _sampling_rate: Option<f64>) -> Result<()> {
+        let n = samples.len();
+        if n < 2 {
+            return Ok(());
+        }
+        let mean_t = (n - 1) as f64 / 2.0;
+        let var_t: f64 = (0..n).map(|t| (t as f64 - mean_t).powi(2)).sum();
+        for channel in 0..channel_count(samples) {
+            let mean_x = samples.iter().map(|row| row[channel]).sum::<f64>() / n as f64;
+            let cov: f64 = samples
+                .iter()
+                .enumerate()
+                .map(|(t, row)| (t as f64 - mean_t) * (row[channel] - mean_x))
+                .sum();
+            let slope = cov / var_t;
+            for (t, row) in samples.iter_mut().enumerate() {
+                row[channel] -= mean_x + slope * (t as f64 - mean_t);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Re-references every channel to the mean across channels
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CommonAverageReference;
+
+impl Preprocessor for CommonAverageReference {
+    fn step(&self) -> PreprocessingStep {
+        PreprocessingStep::new("common_average_reference")
+    }
+
+    fn apply(&self, samples: &mut [Vec<f64>], _sampling_rate: Option<f64>) -> Result<()> {
+        for row in samples.iter_mut() {
+            if row.is_empty() {
+                continue;
+            }
+            let mean = row.iter().sum::<f64>() / row.len() as f64;
+            row.iter_mut().for_each(|value| *value -= mean);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FilterKind {
+    HighPass,
+    LowPass,
+}
+
+/// Normalized biquad coefficients (a0 = 1)
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    /// Bilinear-transform Butterworth section (Q = 1/sqrt(2))
+    fn butterworth(kind: FilterKind, cutoff_hz: f64, sampling_rate: Option<f64>) -> Result<Self> {
+        let name = match kind {
+            FilterKind::HighPass => "highpass",
+            FilterKind::LowPass => "lowpass",
+        };
+        let sampling_rate = sampling_rate.filter(|sr| *sr > 0.0).ok_or_else(|| {
+            DDAError::InvalidParameter(format!("{} filtering needs the sampling rate", name))
+        })?;
+        if !(cutoff_hz > 0.0 && cutoff_hz < sampling_rate / 2.0) {
+            return Err(DDAError::InvalidParameter(format!(
+                "{} cutoff {} Hz must be between 0 and the Nyquist frequency ({} Hz)",
+                name,
+                cutoff_hz,
+                sampling_rate / 2.0
+            )));
+        }
+
+        let w0 = 2.0 * PI * cutoff_hz / sampling_rate;
+        let cos = w0.cos();
+        let alpha = w0.sin() / (2.0 * FRAC_1_SQRT_2);
+        let a0 = 1.0 + alpha;
+        let b = match kind {
+            FilterKind::HighPass => [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            FilterKind::LowPass => [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+        };
+        Ok(Self {
+            b: b.map(|value| value / a0),
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        })
+    }
+
+    /// Direct form II transposed, starting from the steady state for `x[0]`
+    fn run(&self, x: &mut [f64]) {
+        let Some(&first) = x.first() else {
+            return;
+        };
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+        let gain = (b0 + b1 + b2) / (1.0 + a1 + a2);
+        let mut z1 = (gain - b0) * first;
+        let mut z2 = (b2 - a2 * gain) * first;
+        for value in x.iter_mut() {
+            let input = *value;
+            let output = b0 * input + z1;
+            z1 = b1 * input - a1 * output + z2;
+            z2 = b2 * input - a2 * output;
+            *value = output;
+        }
+    }
+
+    /// Forward-backward filtering over an odd reflection of the edges
+    fn filtfilt(&self, x: &[f64]) -> Vec<f64> {
+        let n = x.len();
+        let pad = (3 * 3).min(n.saturating_sub(1));
+        let mut padded = Vec::with_capacity(n + 2 * pad);
+        padded.extend((1..=pad).rev().map(|i| 2.0 * x[0] - x[i]));
+        padded.extend_from_slice(x);
+        padded.extend((1..=pad).map(|i| 2.0 * x[n - 1] - x[n - 1 - i]));
+
+        self.run(&mut padded);
+        padded.reverse();
+        self.run(&mut padded);
+        padded.reverse();
+        padded[pad..pad + n].to_vec()
+    }
+}
+
+fn channel_count(samples: &[Vec<f64>]) -> usize {
+    samples.iter().map(Vec::len).min().unwrap_or(0)
+}
+
+fn filter_channels(samples: &mut [Vec<f64>], biquad: &Biquad) {
+    for channel in 0..channel_count(samples) {
+        let column: Vec<f64> = samples.iter().map(|row| row[channel]).collect();
+        for (row, value) in samples.iter_mut().zip(biquad.filtfilt(&column)) {
+            row[channel] = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: f64 = 256.0;
+
+    fn sine(freq: f64, t: usize) -> f64 {
+        (2.0 * PI * freq * t as f64 / SR).sin()
+    }
+
+    fn rms(values: impl Iterator<Item = f64>) -> f64 {
+        let values: Vec<f64> = values.collect();
+        (values.iter().map(|v| v * v).sum::<f64>() / values.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn test_filters_keep_passband_and_remove_stopband() {
+        let samples: Vec<Vec<f64>> = (0..2048)
+            .map(|t| vec![5.0 + sine(10.0, t), sine(5.0, t) + sine(100.0, t)])
+            .collect();
+        let pipeline = PreprocessingPipeline::new()
+            .with(HighPass::new(1.0))
+            .with(LowPass::new(40.0));
+        let out = pipeline.apply(&samples, Some(SR)).unwrap();
+        let middle = &out[256..1792];
+
+        // DC offset removed, 10 Hz kept
+        let mean = middle.iter().map(|row| row[0]).sum::<f64>() / middle.len() as f64;
+        assert!(mean.abs() < 0.01, "mean {}", mean);
+        let kept = rms(middle.iter().map(|row| row[0]));
+        assert!((kept - FRAC_1_SQRT_2).abs() < 0.02, "rms {}", kept);
+        // 100 Hz removed, 5 Hz kept
+        let residual = rms(middle
+            .iter()
+            .zip(256..)
+            .map(|(row, t)| row[1] - sine(5.0, t)));
+        assert!(residual < 0.05, "residual {}", residual);
+
+        assert_eq!(
+            pipeline.steps(),
+            vec![
+                PreprocessingStep::new("highpass").with_parameter("cutoff_hz", 1.0),
+                PreprocessingStep::new("lowpass").with_parameter("cutoff_hz", 40.0),
+            ]
+        );
+        assert!(pipeline.apply(&samples, None).is_err());
+        assert!(LowPass::new(128.0)
+            .apply(&mut samples.clone(), Some(SR))
+            .is_err());
+    }
+
+    #[test]
+    fn test_detrend_and_common_average_reference() {
+        let mut samples: Vec<Vec<f64>> = (0..100)
+            .map(|t| vec![3.0 + 0.5 * t as f64, 1.0, -1.0])
+            .collect();
+        Detrend.apply(&mut samples, None).unwrap();
+        assert!(samples.iter().flatten().all(|value| value.abs() < 1e-9));
+
+        let mut samples = vec![vec![1.0, 2.0, 6.0]];
+        CommonAverageReference.apply(&mut samples, None).unwrap();
+        assert_eq!(samples, vec![vec![-2.0, -1.0, 3.0]]);
+    }
+}
@@ -0,0 +1,316 @@
+//! Warm-started analysis sessions
+//!
+//! Analyzing a long recording in consecutive, overlapping chunks re-parses
+//! the same ASCII input on every run. An [`AnalysisSession`] keeps the parsed
+//! sample matrix per input file and reuses it across runs with different time
+//! ranges. Cached entries are keyed by canonical path and invalidated when the
+//! file's size or modification time changes. The cache holds at most
+//! [`DEFAULT_MAX_CACHED_BYTES`] of samples unless configured otherwise, and
+//! evicts the least recently used inputs to make room.
+
+use crate::engine::{IncrementalRun, PureRustProgress, PureRustRunner};
+use crate::error::Result;
+use crate::input_io::{bounded_request, load_ascii_matrix_from_path};
+use crate::types::{DDARequest, DDAResult};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Default budget for cached sample matrices (512 MiB).
+pub const DEFAULT_MAX_CACHED_BYTES: usize = 512 * 1024 * 1024;
+
+/// Identity of an input file's contents as seen by the filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct InputFingerprint {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl InputFingerprint {
+    fn of(path: &Path) -> Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+struct CachedInput {
+    fingerprint: InputFingerprint,
+    samples: Arc<Vec<Vec<f64>>>,
+    bytes: usize,
+    last_used: u64,
+}
+
+fn matrix_bytes(samples: &[Vec<f64>]) -> usize {
+    samples
+        .iter()
+        .map(|row| row.len() * std::mem::size_of::<f64>())
+        .sum()
+}
+
+/// Cache hit/miss counters for an [`AnalysisSession`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub cached_inputs: usize,
+    pub cached_bytes: usize,
+}
+
+/// Runs repeated analyses over the same inputs without re-parsing them.
+pub struct AnalysisSession {
+    runner: PureRustRunner,
+    inputs: Mutex<HashMap<PathBuf, CachedInput>>,
+    max_cached_bytes: usize,
+    /// Orders cache accesses for least-recently-used eviction
+    clock: AtomicU64,
+    stats: Mutex<SessionStats>,
+}
+
+impl Default for AnalysisSession {
+    fn default() -> Self {
+        Self::new(PureRustRunner::default())
+    }
+}
+
+impl AnalysisSession {
+    pub fn new(runner: PureRustRunner) -> Self {
+        Self {
+            runner,
+            inputs: Mutex::new(HashMap::new()),
+            max_cached_bytes: DEFAULT_MAX_CACHED_BYTES,
+            clock: AtomicU64::new(0),
+            stats: Mutex::new(SessionStats::default()),
+        }
+    }
+
+    /// Cap the memory held by cached inputs.
+    ///
+    /// Inputs larger than the cap are parsed on every run.
+    pub fn with_max_cached_bytes(mut self, max_cached_bytes: usize) -> Self {
+        self.max_cached_bytes = max_cached_bytes;
+        self
+    }
+
+    pub fn runner(&self) -> &PureRustRunner {
+        &self.runner
+    }
+
+    /// Load (or reuse) the parsed sample matrix for an ASCII input.
+    pub fn samples_for<P: AsRef<Path>>(&self, path: P) -> Result<Arc<Vec<Vec<f64>>>> {
+        let path = path.as_ref();
+        let key = std::fs::canonicalize(path)?;
+        let fingerprint = InputFingerprint::of(&key)?;
+        let tick = self.clock.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(cached) = self.inputs.lock().unwrap().get_mut(&key) {
+            if cached.fingerprint == fingerprint {
+                cached.last_used = tick;
+                self.stats.lock().unwrap().hits += 1;
+                return Ok(Arc::clone(&cached.samples));
+            }
+        }
+
+        let samples = Arc::new(load_ascii_matrix_from_path(&key)?);
+        let bytes = matrix_bytes(&samples);
+        let mut inputs = self.inputs.lock().unwrap();
+        let mut stats = self.stats.lock().unwrap();
+        stats.misses += 1;
+        inputs.remove(&key);
+        if bytes <= self.max_cached_bytes {
+            let mut cached_bytes: usize = inputs.values().map(|cached| cached.bytes).sum();
+            while cached_bytes + bytes > self.max_cached_bytes {
+                let Some(oldest) = inputs
+                    .iter()
+                    .min_by_key(|(_, cached)| cached.last_used)
+                    .map(|(path, _)| path.clone())
+                else {
+                    break;
+                };
+                if let Some(evicted) = inputs.remove(&oldest) {
+                    cached_bytes -= evicted.bytes;
+                    stats.evictions += 1;
+                }
+            }
+            inputs.insert(
+                key,
+                CachedInput {
+                    fingerprint,
+                    samples: Arc::clone(&samples),
+                    bytes,
+                    last_used: tick,
+                },
+            );
+        }
+        Self::record_size(&inputs, &mut stats);
+        Ok(samples)
+    }
+
+    fn record_size(inputs: &HashMap<PathBuf, CachedInput>, stats: &mut SessionStats) {
+        stats.cached_inputs = inputs.len();
+        stats.cached_bytes = inputs.values().map(|cached| cached.bytes).sum();
+    }
+
+    pub fn run_ascii_file<P: AsRef<Path>>(
+        &self,
+        request: &DDARequest,
+        path: P,
+        start_bound: Option<u64>,
+        end_bound: Option<u64>,
+    ) -> Result<DDAResult> {
+        self.run_ascii_file_with_progress(request, path, start_bound, end_bound, |_| {})
+    }
+
+    pub fn run_ascii_file_with_progress<P: AsRef<Path>, F>(
+        &self,
+        request: &DDARequest,
+        path: P,
+        start_bound: Option<u64>,
+        end_bound: Option<u64>,
+        on_progress: F,
+    ) -> Result<DDAResult>
+    where
+        F: FnMut(&PureRustProgress),
+    {
+        let samples = self.samples_for(path)?;
+        let adjusted_request = bounded_request(request, start_bound, end_bound);
+        self.runner
+            .run_on_matrix_with_progress(&adjusted_request, &samples, None, on_progress)
+    }
+
+    /// Re-run `request` after its input grew, reusing the windows of `previous`.
+    pub fn run_ascii_file_incremental<P: AsRef<Path>>(
+        &self,
+        request: &DDARequest,
+        path: P,
+        previous: &DDAResult,
+    ) -> Result<IncrementalRun> {
+        let samples = self.samples_for(path)?;
+        self.runner
+            .run_incremental_on_matrix(request, &samples, None, previous)
+    }
+
+    /// Drop the cached matrix for one input.
+    pub fn invalidate<P: AsRef<Path>>(&self, path: P) {
+        let key =
+            std::fs::canonicalize(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_path_buf());
+        let mut inputs = self.inputs.lock().unwrap();
+        inputs.remove(&key);
+        Self::record_size(&inputs, &mut self.stats.lock().unwrap());
+    }
+
+    /// Drop all cached inputs.
+    pub fn clear(&self) {
+        let mut inputs = self.inputs.lock().unwrap();
+        inputs.clear();
+        Self::record_size(&inputs, &mut self.stats.lock().unwrap());
+    }
+
+    pub fn stats(&self) -> SessionStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_fixture(file: &mut tempfile::NamedTempFile, rows: usize) {
+        file.as_file().set_len(0).unwrap();
+        let mut handle = file.reopen().unwrap();
+        for t in 0..rows {
+            let x = (t as f64 * 0.05).sin();
+            writeln!(handle, "{x:.12} {:.12}", x * 0.5).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_samples_reused_until_source_changes() {
+        let mut file = tempfile::Builder::new()
+            .suffix(".ascii")
+            .tempfile()
+            .unwrap();
+        write_fixture(&mut file, 64);
+        let session = AnalysisSession::default();
+
+        let first = session.samples_for(file.path()).unwrap();
+        let second = session.samples_for(file.path()).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(session.stats().hits, 1);
+        assert_eq!(session.stats().misses, 1);
+
+        write_fixture(&mut file, 96);
+        let reloaded = session.samples_for(file.path()).unwrap();
+        assert_eq!(reloaded.len(), 96);
+        assert_eq!(session.stats().misses, 2);
+        assert_eq!(session.stats().cached_inputs, 1);
+    }
+
+    #[test]
+    fn test_invalidate_forces_reload() {
+        let mut file = tempfile::Builder::new()
+            .suffix(".ascii")
+            .tempfile()
+            .unwrap();
+        write_fixture(&mut file, 32);
+        let session = AnalysisSession::default();
+
+        session.samples_for(file.path()).unwrap();
+        session.invalidate(file.path());
+        assert_eq!(session.stats().cached_inputs, 0);
+        session.samples_for(file.path()).unwrap();
+        assert_eq!(session.stats().misses, 2);
+    }
+
+    #[test]
+    fn test_least_recently_used_input_evicted() {
+        let files: Vec<_> = (0..3)
+            .map(|_| {
+                let mut file = tempfile::Builder::new()
+                    .suffix(".ascii")
+                    .tempfile()
+                    .unwrap();
+                write_fixture(&mut file, 32);
+                file
+            })
+            .collect();
+        // Two fixtures of 32 rows by 2 channels fit, three do not
+        let session = AnalysisSession::default().with_max_cached_bytes(2 * 32 * 2 * 8);
+
+        session.samples_for(files[0].path()).unwrap();
+        session.samples_for(files[1].path()).unwrap();
+        session.samples_for(files[0].path()).unwrap();
+        session.samples_for(files[2].path()).unwrap();
+        let stats = session.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.cached_inputs, 2);
+        assert_eq!(stats.cached_bytes, 2 * 32 * 2 * 8);
+
+        // files[1] was the least recently used
+        session.samples_for(files[0].path()).unwrap();
+        assert_eq!(session.stats().hits, 2);
+        session.samples_for(files[1].path()).unwrap();
+        assert_eq!(session.stats().misses, 4);
+    }
+
+    #[test]
+    fn test_input_over_budget_not_cached() {
+        let mut file = tempfile::Builder::new()
+            .suffix(".ascii")
+            .tempfile()
+            .unwrap();
+        write_fixture(&mut file, 32);
+        let session = AnalysisSession::default().with_max_cached_bytes(64);
+
+        assert_eq!(session.samples_for(file.path()).unwrap().len(), 32);
+        assert_eq!(session.stats().cached_inputs, 0);
+        session.samples_for(file.path()).unwrap();
+        assert_eq!(session.stats().misses, 2);
+    }
+}
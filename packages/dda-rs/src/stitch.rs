@@ -0,0 +1,346 @@
+//! Multi-file analysis with session stitching
+//!
+//! Long recordings are often split into consecutive files, e.g. hourly
+//! exports of an overnight session. Analyzing them one by one restarts the
+//! filters at every file and drops the windows that would span a file
+//! boundary. A [`StitchedRecording`] joins the files into one sample matrix
+//! after checking that they agree on channel layout and sampling rate;
+//! [`PureRustRunner::run_stitched`](crate::PureRustRunner::run_stitched)
+//! analyzes it as one continuous recording and returns a [`BoundaryMap`]
+//! saying which windows came from which file.
+
+use crate::error::{DDAError, Result};
+use crate::input_io::load_ascii_matrix_from_path;
+use crate::types::DDAResult;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+
+/// Relative difference below which two sampling rates count as equal
+const SAMPLING_RATE_TOLERANCE: f64 = 1e-9;
+
+/// One file of a split recording
+#[derive(Debug, Clone)]
+pub struct RecordingPart {
+    /// File name or other label used in errors and the boundary map
+    pub source: String,
+    /// Rows are time points, columns are channels
+    pub samples: Vec<Vec<f64>>,
+    pub channel_labels: Option<Vec<String>>,
+    pub sampling_rate: Option<f64>,
+}
+
+impl RecordingPart {
+    pub fn new(source: impl Into<String>, samples: Vec<Vec<f64>>) -> Self {
+        Self {
+            source: source.into(),
+            samples,
+            channel_labels: None,
+            sampling_rate: None,
+        }
+    }
+
+    pub fn with_channel_labels(mut self, labels: Vec<String>) -> Self {
+        self.channel_labels = Some(labels);
+        self
+    }
+
+    pub fn with_sampling_rate(mut self, sampling_rate: f64) -> Self {
+        self.sampling_rate = Some(sampling_rate);
+        self
+    }
+
+    /// Load an ASCII/TXT/CSV file; its channel labels and rate are unknown
+    pub fn from_ascii_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Ok(Self::new(
+            path.display().to_string(),
+            load_ascii_matrix_from_path(path)?,
+        ))
+    }
+}
+
+/// Sample range of one file within a stitched recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSegment {
+    pub source: String,
+    pub start_sample: usize,
+    /// One past the file's last sample
+    pub end_sample: usize,
+}
+
+/// Consecutive files joined into one continuous sample matrix
+#[derive(Debug, Clone)]
+pub struct StitchedRecording {
+    samples: Vec<Vec<f64>>,
+    channel_labels: Option<Vec<String>>,
+    sampling_rate: Option<f64>,
+    segments: Vec<FileSegment>,
+}
+
+impl StitchedRecording {
+    /// Join `parts` in order
+    ///
+    /// Every part must have the same number of channels. Channel labels and
+    /// sampling rates are optional per part, but parts that declare them
+    /// must agree with each other.
+    pub fn from_parts(parts: Vec<RecordingPart>) -> Result<Self> {
+        let Some(first) = parts.first() else {
+            return Err(DDAError::InvalidParameter(
+                "Stitching needs at least one input file".to_string(),
+            ));
+        };
+        let channel_count = first.samples.first().map_or(0, Vec::len);
+        let mut channel_labels: Option<(String, Vec<String>)> = None;
+        let mut sampling_rate: Option<(String, f64)> = None;
+
+        for part in &parts {
+            if part.samples.is_empty() {
+                return Err(DDAError::InvalidParameter(format!(
+                    "{} has no samples",
+                    part.source
+                )));
+            }
+            let columns = part.samples[0].len();
+            if columns != channel_count {
+                return Err(DDAError::InvalidParameter(format!(
+                    "{} has {} channels but {} has {}",
+                    part.source, columns, first.source, channel_count
+                )));
+            }
+            if let Some(labels) = &part.channel_labels {
+                match &channel_labels {
+                    Some((source, expected)) if expected != labels => {
+                        return Err(DDAError::InvalidParameter(format!(
+                            "{} has channels [{}] but {} has [{}]",
+                            part.source,
+                            labels.join(", "),
+                            source,
+                            expected.join(", ")
+                        )));
+                    }
+                    Some(_) => {}
+                    None => channel_labels = Some((part.source.clone(), labels.clone())),
+                }
+            }
+            if let Some(rate) = part.sampling_rate {
+                match sampling_rate {
+                    Some((ref source, expected)) if !rates_match(expected, rate) => {
+                        return Err(DDAError::InvalidParameter(format!(
+                            "{} is sampled at {} Hz but {} at {} Hz",
+                            part.source, rate, source, expected
+                        )));
+                    }
+                    Some(_) => {}
+                    None => sampling_rate = Some((part.source.clone(), rate)),
+                }
+            }
+        }
+
+        let mut samples = Vec::with_capacity(parts.iter().map(|part| part.samples.len()).sum());
+        let mut segments = Vec::with_capacity(parts.len());
+        for part in parts {
+            let start_sample = samples.len();
+            samples.extend(part.samples);
+            segments.push(FileSegment {
+                source: part.source,
+                start_sample,
+                end_sample: samples.len(),
+            });
+        }
+
+        Ok(Self {
+            samples,
+            channel_labels: channel_labels.map(|(_, labels)| labels),
+            sampling_rate: sampling_rate.map(|(_, rate)| rate),
+            segments,
+        })
+    }
+
+    /// Load and join ASCII/TXT/CSV files in the given order
+    pub fn from_ascii_files<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        let parts = paths
+            .iter()
+            .map(RecordingPart::from_ascii_file)
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_parts(parts)
+    }
+
+    pub fn samples(&self) -> &[Vec<f64>] {
+        &self.samples
+    }
+
+    pub fn channel_labels(&self) -> Option<&[String]> {
+        self.channel_labels.as_deref()
+    }
+
+    pub fn sampling_rate(&self) -> Option<f64> {
+        self.sampling_rate
+    }
+
+    pub fn segments(&self) -> &[FileSegment] {
+        &self.segments
+    }
+
+    /// Index of the segment holding `sample`
+    pub fn segment_at(&self, sample: usize) -> Option<usize> {
+        self.segments
+            .iter()
+            .position(|segment| (segment.start_sample..segment.end_sample).contains(&sample))
+    }
+
+    /// Map windows, given as the sample ranges they cover, onto the files
+    pub fn boundary_map(&self, windows: &[Range<usize>]) -> BoundaryMap {
+        let files = self
+            .segments
+            .iter()
+            .map(|segment| {
+                let starting: Vec<usize> = windows
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, window)| {
+                        (segment.start_sample..segment.end_sample).contains(&window.start)
+                    })
+                    .map(|(index, _)| index)
+                    .collect();
+                SegmentWindows {
+                    source: segment.source.clone(),
+                    start_sample: segment.start_sample,
+                    end_sample: segment.end_sample,
+                    windows: starting
+                        .first()
+                        .map_or(0..0, |&first| first..first + starting.len()),
+                }
+            })
+            .collect();
+
+        let boundaries = self
+            .segments
+            .windows(2)
+            .map(|pair| {
+                let sample = pair[1].start_sample;
+                let spanning: Vec<usize> = windows
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, window)| window.start < sample && sample < window.end)
+                    .map(|(index, _)| index)
+                    .collect();
+                FileBoundary {
+                    sample,
+                    previous: pair[0].source.clone(),
+                    next: pair[1].source.clone(),
+                    spanning_windows: spanning
+                        .first()
+                        .map_or(0..0, |&first| first..first + spanning.len()),
+                }
+            })
+            .collect();
+
+        BoundaryMap { files, boundaries }
+    }
+}
+
+fn rates_match(a: f64, b: f64) -> bool {
+    (a - b).abs() <= SAMPLING_RATE_TOLERANCE * a.abs().max(b.abs())
+}
+
+/// Windows that start in one file of a stitched recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentWindows {
+    pub source: String,
+    pub start_sample: usize,
+    pub end_sample: usize,
+    /// Indices of the Q-matrix columns whose windows start in this file
+    pub windows: Range<usize>,
+}
+
+/// Where one file ends and the next begins
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileBoundary {
+    /// First sample of the next file
+    pub sample: usize,
+    pub previous: String,
+    pub next: String,
+    /// Windows that cover samples of both files
+    pub spanning_windows: Range<usize>,
+}
+
+/// Which Q-matrix columns of a stitched analysis came from which file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoundaryMap {
+    pub files: Vec<SegmentWindows>,
+    pub boundaries: Vec<FileBoundary>,
+}
+
+/// Result of [`PureRustRunner::run_stitched`](crate::PureRustRunner::run_stitched)
+#[derive(Debug, Clone)]
+pub struct StitchedRun {
+    pub result: DDAResult,
+    pub boundary_map: BoundaryMap,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(source: &str, rows: usize, cols: usize) -> RecordingPart {
+        RecordingPart::new(source, vec![vec![0.0; cols]; rows])
+    }
+
+    #[test]
+    fn test_parts_are_joined_in_order() {
+        let recording = StitchedRecording::from_parts(vec![
+            part("hour1", 10, 2).with_sampling_rate(256.0),
+            part("hour2", 6, 2),
+            part("hour3", 4, 2).with_sampling_rate(256.0),
+        ])
+        .unwrap();
+        assert_eq!(recording.samples().len(), 20);
+        assert_eq!(recording.sampling_rate(), Some(256.0));
+        let ranges: Vec<(usize, usize)> = recording
+            .segments()
+            .iter()
+            .map(|segment| (segment.start_sample, segment.end_sample))
+            .collect();
+        assert_eq!(ranges, [(0, 10), (10, 16), (16, 20)]);
+        assert_eq!(recording.segment_at(15), Some(1));
+        assert_eq!(recording.segment_at(20), None);
+    }
+
+    #[test]
+    fn test_mismatched_layouts_are_rejected() {
+        let channels = StitchedRecording::from_parts(vec![part("a", 4, 2), part("b", 4, 3)]);
+        assert!(channels
+            .unwrap_err()
+            .to_string()
+            .contains("b has 3 channels"));
+
+        let rates = StitchedRecording::from_parts(vec![
+            part("a", 4, 2).with_sampling_rate(256.0),
+            part("b", 4, 2).with_sampling_rate(512.0),
+        ]);
+        assert!(rates.unwrap_err().to_string().contains("512 Hz"));
+
+        let labels = StitchedRecording::from_parts(vec![
+            part("a", 4, 2).with_channel_labels(vec!["Fz".into(), "Cz".into()]),
+            part("b", 4, 2).with_channel_labels(vec!["Cz".into(), "Fz".into()]),
+        ]);
+        assert!(labels.is_err());
+        assert!(StitchedRecording::from_parts(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_boundary_map_assigns_windows_to_files() {
+        let recording =
+            StitchedRecording::from_parts(vec![part("a", 10, 1), part("b", 10, 1)]).unwrap();
+        // Windows of 6 samples every 3 samples
+        let windows: Vec<Range<usize>> = (0..5).map(|w| w * 3..w * 3 + 6).collect();
+        let map = recording.boundary_map(&windows);
+
+        assert_eq!(map.files[0].windows, 0..4);
+        assert_eq!(map.files[1].windows, 4..5);
+        assert_eq!(map.boundaries.len(), 1);
+        assert_eq!(map.boundaries[0].sample, 10);
+        assert_eq!(map.boundaries[0].spanning_windows, 2..4);
+    }
+}
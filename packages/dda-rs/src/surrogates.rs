@@ -0,0 +1,403 @@
+//! Surrogate-data significance testing
+//!
+//! Each surrogate keeps selected properties of the input while destroying the
+//! structure under test:
+//!
+//! - [`SurrogateMethod::PhaseRandomized`] keeps each channel's power spectrum
+//!   (linear autocorrelation) and randomizes Fourier phases.
+//! - [`SurrogateMethod::Aaft`] additionally keeps each channel's amplitude
+//!   distribution (amplitude-adjusted Fourier transform).
+//! - [`SurrogateMethod::TimeShuffled`] keeps only the amplitude distribution.
+//!
+//! Channels are randomized independently, so cross-channel coupling is also
+//! removed. [`PureRustRunner::run_surrogate_test`] runs the request on the real
+//! data and on every surrogate, then scores each Q value against its null
+//! distribution.
+
+use crate::engine::PureRustRunner;
+use crate::error::{DDAError, Result};
+use crate::types::{DDARequest, DDAResult};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SurrogateMethod {
+    PhaseRandomized,
+    Aaft,
+    TimeShuffled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SurrogateConfig {
+    pub method: SurrogateMethod,
+    pub n_surrogates: usize,
+    pub rng_seed: u64,
+}
+
+impl Default for SurrogateConfig {
+    fn default() -> Self {
+        Self {
+            method: SurrogateMethod::PhaseRandomized,
+            n_surrogates: 99,
+            rng_seed: 0,
+        }
+    }
+}
+
+/// Significance of one variant's Q matrix, laid out `[row][window]` like the matrix itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VariantSignificance {
+    pub variant_id: String,
+    /// Two-sided empirical p-values, `(1 + extreme) / (1 + n)`
+    pub p_values: Vec<Vec<f64>>,
+    /// `(observed - null mean) / null standard deviation`
+    pub z_scores: Vec<Vec<f64>>,
+    /// Null samples that were finite for each entry
+    pub null_counts: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SurrogateTestResult {
+    /// Result on the real data
+    pub observed: DDAResult,
+    pub config: SurrogateConfig,
+    pub variants: Vec<VariantSignificance>,
+}
+
+impl SurrogateTestResult {
+    pub fn variant(&self, variant_id: &str) -> Option<&VariantSignificance> {
+        self.variants
+            .iter()
+            .find(|variant| variant.variant_id.eq_ignore_ascii_case(variant_id))
+    }
+}
+
+/// Surrogate of a single series.
+pub fn surrogate_series(series: &[f64], method: SurrogateMethod, rng_seed: u64) -> Vec<f64> {
+    let mut rng = ChaCha8Rng::seed_from_u64(rng_seed);
+    match method {
+        SurrogateMethod::PhaseRandomized => phase_randomize(series, &mut rng),
+        SurrogateMethod::Aaft => aaft(series, &mut rng),
+        SurrogateMethod::TimeShuffled => {
+            let mut shuffled = series.to_vec();
+            shuffled.shuffle(&mut rng);
+            shuffled
+        }
+    }
+}
+
+/// Surrogate of a `[sample][channel]` matrix, randomizing every channel independently.
+pub fn surrogate_matrix(
+    samples: &[Vec<f64>],
+    method: SurrogateMethod,
+    rng_seed: u64,
+) -> Vec<Vec<f64>> {
+    let cols = samples.first().map_or(0, Vec::len);
+    let channels = (0..cols)
+        .map(|col| {
+            let series = samples.iter().map(|row| row[col]).collect::<Vec<_>>();
+            // Distinct, reproducible stream per channel
+            let seed = rng_seed
+                .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+                .wrapping_add(col as u64);
+            surrogate_series(&series, method, seed)
+        })
+        .collect::<Vec<_>>();
+    (0..samples.len())
+        .map(|row| channels.iter().map(|channel| channel[row]).collect())
+        .collect()
+}
+
+impl PureRustRunner {
+    /// Run `request` on `samples` and on `config.n_surrogates` surrogates of it,
+    /// scoring every Q value of every variant against its surrogate distribution.
+    pub fn run_surrogate_test(
+        &self,
+        request: &DDARequest,
+        samples: &[Vec<f64>],
+        channel_labels: Option<&[String]>,
+        config: &SurrogateConfig,
+    ) -> Result<SurrogateTestResult> {
+        if config.n_surrogates == 0 {
+            return Err(DDAError::InvalidParameter(
+                "surrogate testing requires n_surrogates > 0".to_string(),
+            ));
+        }
+        let observed = self.run_on_matrix(request, samples, channel_labels)?;
+        let null_runs = (0..config.n_surrogates)
+            .into_par_iter()
+            .map(|idx| {
+                let seed = config.rng_seed.wrapping_add(idx as u64);
+                let surrogate = surrogate_matrix(samples, config.method, seed);
+                self.run_on_matrix(request, &surrogate, channel_labels)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let variants = observed
+            .variant_results
+            .iter()
+            .flatten()
+            .map(|variant| {
+                let nulls = null_runs
+                    .iter()
+                    .map(|run| {
+                        run.variant_results
+                            .iter()
+                            .flatten()
+                            .find(|v| v.variant_id == variant.variant_id)
+                            .map(|v| v.q_matrix.as_slice())
+                            .ok_or_else(|| {
+                                DDAError::ExecutionFailed(format!(
+                                    "surrogate run is missing variant {}",
+                                    variant.variant_id
+                                ))
+                            })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(score_against_nulls(
+                    &variant.variant_id,
+                    &variant.q_matrix,
+                    &nulls,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SurrogateTestResult {
+            observed,
+            config: config.clone(),
+            variants,
+        })
+    }
+}
+
+fn score_against_nulls(
+    variant_id: &str,
+    observed: &[Vec<f64>],
+    nulls: &[&[Vec<f64>]],
+) -> VariantSignificance {
+    let mut p_values = Vec::with_capacity(observed.len());
+    let mut z_scores = Vec::with_capacity(observed.len());
+    let mut null_counts = Vec::with_capacity(observed.len());
+
+    for (row, values) in observed.iter().enumerate() {
+        let mut p_row = Vec::with_capacity(values.len());
+        let mut z_row = Vec::with_capacity(values.len());
+        let mut n_row = Vec::with_capacity(values.len());
+        for (window, &value) in values.iter().enumerate() {
+            let null = nulls
+                .iter()
+                .filter_map(|q| q.get(row).and_then(|r| r.get(window)).copied())
+                .filter(|v| v.is_finite())
+                .collect::<Vec<_>>();
+            let (p, z) = two_sided_score(value, &null);
+            p_row.push(p);
+            z_row.push(z);
+            n_row.push(null.len());
+        }
+        p_values.push(p_row);
+        z_scores.push(z_row);
+        null_counts.push(n_row);
+    }
+
+    VariantSignificance {
+        variant_id: variant_id.to_string(),
+        p_values,
+        z_scores,
+        null_counts,
+    }
+}
+
+fn two_sided_score(observed: f64, null: &[f64]) -> (f64, f64) {
+    if !observed.is_finite() || null.is_empty() {
+        return (f64::NAN, f64::NAN);
+    }
+    let n = null.len() as f64;
+    let mean = null.iter().sum::<f64>() / n;
+    let variance = null.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    let deviation = (observed - mean).abs();
+    let extreme = null
+        .iter()
+        .filter(|v| (*v - mean).abs() >= deviation)
+        .count();
+    let p = (1.0 + extreme as f64) / (1.0 + n);
+    let z = if variance > 0.0 {
+        (observed - mean) / variance.sqrt()
+    } else {
+        f64::NAN
+    };
+    (p, z)
+}
+
+fn phase_randomize(series: &[f64], rng: &mut ChaCha8Rng) -> Vec<f64> {
+    let n = series.len();
+    if n < 3 {
+        return series.to_vec();
+    }
+    let mut planner = FftPlanner::<f64>::new();
+    let mut spectrum = series
+        .iter()
+        .map(|&x| Complex::new(x, 0.0))
+        .collect::<Vec<_>>();
+    planner.plan_fft_forward(n).process(&mut spectrum);
+
+    // Keep DC (and Nyquist for even n) so the surrogate stays real with the same mean
+    for k in 1..n.div_ceil(2) {
+        let phase = rng.gen_range(0.0..2.0 * PI);
+        let rotated = Complex::from_polar(spectrum[k].norm(), phase);
+        spectrum[k] = rotated;
+        spectrum[n - k] = rotated.conj();
+    }
+
+    planner.plan_fft_inverse(n).process(&mut spectrum);
+    spectrum.iter().map(|c| c.re / n as f64).collect()
+}
+
+fn aaft(series: &[f64], rng: &mut ChaCha8Rng) -> Vec<f64> {
+    let n = series.len();
+    let mut sorted = series.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    // Gaussian series with the ranks of the data, phase-randomized, then
+    // mapped back onto the data's values by rank
+    let mut gaussian = (0..n).map(|_| standard_normal(rng)).collect::<Vec<_>>();
+    gaussian.sort_by(f64::total_cmp);
+    let mut rescaled = vec![0.0; n];
+    for (rank, idx) in ranks(series).into_iter().enumerate() {
+        rescaled[idx] = gaussian[rank];
+    }
+    let randomized = phase_randomize(&rescaled, rng);
+    let mut surrogate = vec![0.0; n];
+    for (rank, idx) in ranks(&randomized).into_iter().enumerate() {
+        surrogate[idx] = sorted[rank];
+    }
+    surrogate
+}
+
+/// Indices of `values` in ascending order of value.
+fn ranks(values: &[f64]) -> Vec<usize> {
+    let mut order = (0..values.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    order
+}
+
+fn standard_normal(rng: &mut ChaCha8Rng) -> f64 {
+    // Box-Muller; 1 - u keeps the logarithm finite
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        AlgorithmSelection, DelayParameters, PreprocessingOptions, TimeRange, WindowParameters,
+    };
+
+    fn series(n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|t| (t as f64 * 0.07).sin() + 0.3 * (t as f64 * 0.31).cos())
+            .collect()
+    }
+
+    fn sorted(values: &[f64]) -> Vec<f64> {
+        let mut values = values.to_vec();
+        values.sort_by(f64::total_cmp);
+        values
+    }
+
+    fn amplitude_spectrum(values: &[f64]) -> Vec<f64> {
+        let mut buffer = values
+            .iter()
+            .map(|&x| Complex::new(x, 0.0))
+            .collect::<Vec<_>>();
+        FftPlanner::<f64>::new()
+            .plan_fft_forward(values.len())
+            .process(&mut buffer);
+        buffer.iter().map(|c| c.norm()).collect()
+    }
+
+    #[test]
+    fn test_phase_randomized_keeps_spectrum() {
+        let original = series(257);
+        let surrogate = surrogate_series(&original, SurrogateMethod::PhaseRandomized, 7);
+        assert_ne!(surrogate, original);
+        for (a, b) in amplitude_spectrum(&original)
+            .iter()
+            .zip(amplitude_spectrum(&surrogate))
+        {
+            assert!((a - b).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_aaft_and_shuffle_keep_value_distribution() {
+        let original = series(256);
+        for method in [SurrogateMethod::Aaft, SurrogateMethod::TimeShuffled] {
+            let surrogate = surrogate_series(&original, method, 3);
+            assert_ne!(surrogate, original);
+            assert_eq!(sorted(&surrogate), sorted(&original));
+            assert_eq!(surrogate, surrogate_series(&original, method, 3));
+        }
+    }
+
+    #[test]
+    fn test_surrogate_test_scores_every_q_value() {
+        let samples = (0..400)
+            .map(|t| {
+                let x = (t as f64 * 0.05).sin();
+                vec![x, 0.6 * x + (t as f64 * 0.07).cos() * 0.1]
+            })
+            .collect::<Vec<_>>();
+        let request = DDARequest {
+            file_path: "memory".to_string(),
+            channels: Some(vec![0, 1]),
+            time_range: TimeRange {
+                start: 0.0,
+                end: 399.0,
+            },
+            preprocessing_options: PreprocessingOptions {
+                highpass: None,
+                lowpass: None,
+            },
+            algorithm_selection: AlgorithmSelection {
+                enabled_variants: vec!["ST".to_string()],
+                select_mask: None,
+            },
+            window_parameters: WindowParameters {
+                window_length: 100,
+                window_step: 50,
+                ct_window_length: None,
+                ct_window_step: None,
+            },
+            delay_parameters: DelayParameters { delays: vec![1, 2] },
+            ct_channel_pairs: None,
+            cd_channel_pairs: None,
+            model_parameters: None,
+            model_terms: None,
+            variant_configs: None,
+            sampling_rate: None,
+        };
+        let config = SurrogateConfig {
+            method: SurrogateMethod::TimeShuffled,
+            n_surrogates: 9,
+            rng_seed: 11,
+        };
+
+        let result = PureRustRunner::default()
+            .run_surrogate_test(&request, &samples, None, &config)
+            .unwrap();
+        let st = result.variant("ST").unwrap();
+        let q = &result.observed.variant_results.as_ref().unwrap()[0].q_matrix;
+        assert_eq!(st.p_values.len(), q.len());
+        assert_eq!(st.p_values[0].len(), q[0].len());
+        for p in st.p_values.iter().flatten() {
+            assert!(*p >= 0.1 && *p <= 1.0);
+        }
+    }
+}
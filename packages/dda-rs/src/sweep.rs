@@ -0,0 +1,322 @@
+//! Parameter sweeps for model selection
+//!
+//! A [`SweepGrid`] lists candidate values per parameter axis; the sweep runs
+//! the cartesian product of those values against one parsed input and
+//! reduces each run to per-variant summary statistics. Parameter points that
+//! the engine rejects (for example a window shorter than the largest delay)
+//! are reported with their error instead of failing the whole sweep.
+
+use crate::engine::PureRustRunner;
+use crate::error::Result;
+use crate::session::AnalysisSession;
+use crate::types::{DDARequest, DDAResult, ModelParameters};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Candidate values per axis; an empty axis keeps the base request's value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SweepGrid {
+    pub delays: Vec<Vec<i32>>,
+    /// Embedding settings (dm, order, nr_tau)
+    pub model_parameters: Vec<ModelParameters>,
+    pub window_lengths: Vec<u32>,
+    pub window_steps: Vec<u32>,
+}
+
+/// One parameter tuple of a sweep.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepPoint {
+    pub delays: Vec<i32>,
+    pub model_parameters: Option<ModelParameters>,
+    pub window_length: u32,
+    pub window_step: u32,
+}
+
+impl SweepPoint {
+    /// `base` with this point's parameters substituted.
+    pub fn apply(&self, base: &DDARequest) -> DDARequest {
+        let mut request = base.clone();
+        request.delay_parameters.delays.clone_from(&self.delays);
+        request.model_parameters.clone_from(&self.model_parameters);
+        request.window_parameters.window_length = self.window_length;
+        request.window_parameters.window_step = self.window_step;
+        request
+    }
+}
+
+impl SweepGrid {
+    /// Cartesian product of the axes, window step varying fastest.
+    pub fn points(&self, base: &DDARequest) -> Vec<SweepPoint> {
+        fn axis<T: Clone>(values: &[T], fallback: T) -> Vec<T> {
+            if values.is_empty() {
+                vec![fallback]
+            } else {
+                values.to_vec()
+            }
+        }
+        let delays = axis(&self.delays, base.delay_parameters.delays.clone());
+        let models = axis(
+            &self
+                .model_parameters
+                .iter()
+                .cloned()
+                .map(Some)
+                .collect::<Vec<_>>(),
+            base.model_parameters.clone(),
+        );
+        let lengths = axis(&self.window_lengths, base.window_parameters.window_length);
+        let steps = axis(&self.window_steps, base.window_parameters.window_step);
+
+        let mut points = Vec::new();
+        for delays in &delays {
+            for model in &models {
+                for &window_length in &lengths {
+                    for &window_step in &steps {
+                        points.push(SweepPoint {
+                            delays: delays.clone(),
+                            model_parameters: model.clone(),
+                            window_length,
+                            window_step,
+                        });
+                    }
+                }
+            }
+        }
+        points
+    }
+}
+
+/// Summary statistics over the finite values of one variant's Q matrix.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VariantSummary {
+    pub variant_id: String,
+    pub rows: usize,
+    pub windows: usize,
+    /// Share of Q values that are finite
+    pub finite_fraction: f64,
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl VariantSummary {
+    pub fn from_q_matrix(variant_id: &str, q_matrix: &[Vec<f64>]) -> Self {
+        let total = q_matrix.iter().map(Vec::len).sum::<usize>();
+        let finite = q_matrix
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|v| v.is_finite())
+            .collect::<Vec<_>>();
+        let n = finite.len() as f64;
+        let (mean, std, min, max) = if finite.is_empty() {
+            (f64::NAN, f64::NAN, f64::NAN, f64::NAN)
+        } else {
+            let mean = finite.iter().sum::<f64>() / n;
+            let variance = finite.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+            (
+                mean,
+                variance.sqrt(),
+                finite.iter().copied().fold(f64::INFINITY, f64::min),
+                finite.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            )
+        };
+        Self {
+            variant_id: variant_id.to_string(),
+            rows: q_matrix.len(),
+            windows: q_matrix.first().map_or(0, Vec::len),
+            finite_fraction: if total == 0 { 0.0 } else { n / total as f64 },
+            mean,
+            std,
+            min,
+            max,
+        }
+    }
+}
+
+/// Outcome of one sweep point.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepEntry {
+    pub point: SweepPoint,
+    pub variants: Vec<VariantSummary>,
+    /// Set when the engine rejected this parameter combination
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SweepEntry {
+    pub fn variant(&self, variant_id: &str) -> Option<&VariantSummary> {
+        self.variants
+            .iter()
+            .find(|summary| summary.variant_id.eq_ignore_ascii_case(variant_id))
+    }
+}
+
+/// Sweep results in grid order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepResult {
+    pub file_path: String,
+    pub entries: Vec<SweepEntry>,
+}
+
+impl SweepResult {
+    /// Successful entry minimizing `score`; NaN scores are ignored.
+    pub fn best_by<F>(&self, score: F) -> Option<&SweepEntry>
+    where
+        F: Fn(&SweepEntry) -> f64,
+    {
+        self.entries
+            .iter()
+            .filter(|entry| entry.error.is_none())
+            .map(|entry| (score(entry), entry))
+            .filter(|(value, _)| !value.is_nan())
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, entry)| entry)
+    }
+}
+
+impl PureRustRunner {
+    /// Run every point of `grid` on an in-memory sample matrix.
+    pub fn sweep_on_matrix(
+        &self,
+        request: &DDARequest,
+        samples: &[Vec<f64>],
+        channel_labels: Option<&[String]>,
+        grid: &SweepGrid,
+    ) -> SweepResult {
+        let entries = grid
+            .points(request)
+            .into_par_iter()
+            .map(|point| {
+                let outcome = self.run_on_matrix(&point.apply(request), samples, channel_labels);
+                sweep_entry(point, outcome)
+            })
+            .collect();
+        SweepResult {
+            file_path: request.file_path.clone(),
+            entries,
+        }
+    }
+}
+
+impl AnalysisSession {
+    /// Run every point of `grid` on `request.file_path`, parsing the input once.
+    pub fn sweep(&self, request: &DDARequest, grid: &SweepGrid) -> Result<SweepResult> {
+        let samples = self.samples_for(&request.file_path)?;
+        Ok(self.runner().sweep_on_matrix(request, &samples, None, grid))
+    }
+}
+
+fn sweep_entry(point: SweepPoint, outcome: Result<DDAResult>) -> SweepEntry {
+    match outcome {
+        Ok(result) => SweepEntry {
+            point,
+            variants: result
+                .variant_results
+                .iter()
+                .flatten()
+                .map(|variant| {
+                    VariantSummary::from_q_matrix(&variant.variant_id, &variant.q_matrix)
+                })
+                .collect(),
+            error: None,
+        },
+        Err(error) => SweepEntry {
+            point,
+            variants: Vec::new(),
+            error: Some(error.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        AlgorithmSelection, DelayParameters, PreprocessingOptions, TimeRange, WindowParameters,
+    };
+
+    fn base_request() -> DDARequest {
+        DDARequest {
+            file_path: "memory".to_string(),
+            channels: Some(vec![0, 1]),
+            time_range: TimeRange {
+                start: 0.0,
+                end: 399.0,
+            },
+            preprocessing_options: PreprocessingOptions {
+                highpass: None,
+                lowpass: None,
+            },
+            algorithm_selection: AlgorithmSelection {
+                enabled_variants: vec!["ST".to_string()],
+                select_mask: None,
+            },
+            window_parameters: WindowParameters {
+                window_length: 100,
+                window_step: 50,
+                ct_window_length: None,
+                ct_window_step: None,
+            },
+            delay_parameters: DelayParameters { delays: vec![1, 2] },
+            ct_channel_pairs: None,
+            cd_channel_pairs: None,
+            model_parameters: None,
+            model_terms: None,
+            variant_configs: None,
+            sampling_rate: None,
+        }
+    }
+
+    #[test]
+    fn test_points_cover_cartesian_product() {
+        let grid = SweepGrid {
+            delays: vec![vec![1, 2], vec![3, 5]],
+            window_lengths: vec![64, 128, 256],
+            ..Default::default()
+        };
+        let points = grid.points(&base_request());
+        assert_eq!(points.len(), 6);
+        assert!(points.iter().all(|p| p.window_step == 50));
+        assert_eq!(points[3].delays, vec![3, 5]);
+        assert_eq!(points[3].window_length, 64);
+    }
+
+    #[test]
+    fn test_sweep_records_rejected_points() {
+        let samples = (0..400)
+            .map(|t| {
+                let x = (t as f64 * 0.05).sin();
+                vec![x, (t as f64 * 0.03).cos()]
+            })
+            .collect::<Vec<_>>();
+        let model = |dm| ModelParameters {
+            dm,
+            order: 4,
+            nr_tau: 2,
+        };
+        let grid = SweepGrid {
+            model_parameters: vec![model(4), model(0)],
+            window_lengths: vec![64, 128],
+            ..Default::default()
+        };
+        let result =
+            PureRustRunner::default().sweep_on_matrix(&base_request(), &samples, None, &grid);
+
+        assert_eq!(result.entries.len(), 4);
+        let ok = &result.entries[0];
+        assert!(ok.error.is_none());
+        let st = ok.variant("ST").unwrap();
+        assert_eq!(st.rows, 2);
+        assert!(st.finite_fraction > 0.0);
+        // dm = 0 is rejected by the engine
+        assert!(result.entries[2].error.is_some());
+        assert!(result.entries[3].error.is_some());
+
+        let best = result
+            .best_by(|entry| entry.variant("ST").unwrap().std)
+            .unwrap();
+        assert_eq!(best.point.model_parameters.as_ref().unwrap().dm, 4);
+    }
+}
@@ -0,0 +1,307 @@
+//! Typed, variant-specific views of DDA results
+//!
+//! [`VariantResult`] stores every variant as a plain `[row][window]` matrix.
+//! What a row means depends on the variant: one channel for ST, a channel
+//! group for CT/DE, an ordered `target <- source` pair for CD and the CCD
+//! family, and a channel pair for SY. The types here resolve those rows from
+//! the engine's `row_channels` so callers can index by channel instead of
+//! relying on matrix layout conventions.
+
+use crate::error::{DDAError, Result};
+use crate::types::{DDAResult, VariantResult};
+
+/// Directed channel pair of a cross-dynamical row (`target <- source`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DirectedPair {
+    pub target: usize,
+    pub source: usize,
+}
+
+/// ST: one row per channel.
+#[derive(Debug, Clone)]
+pub struct SingleTimeseriesResult {
+    pub channels: Vec<usize>,
+    pub labels: Vec<String>,
+    pub q_matrix: Vec<Vec<f64>>,
+}
+
+impl SingleTimeseriesResult {
+    /// Per-window values for `channel`.
+    pub fn channel(&self, channel: usize) -> Option<&[f64]> {
+        row_for(
+            &self.q_matrix,
+            self.channels.iter().position(|&c| c == channel),
+        )
+    }
+}
+
+/// CT: one row per channel group (a pair unless CT window settings say otherwise).
+#[derive(Debug, Clone)]
+pub struct CrossTimeseriesResult {
+    pub groups: Vec<Vec<usize>>,
+    pub labels: Vec<String>,
+    pub q_matrix: Vec<Vec<f64>>,
+}
+
+impl CrossTimeseriesResult {
+    /// Per-window values for the unordered pair `(a, b)`.
+    pub fn pair(&self, a: usize, b: usize) -> Option<&[f64]> {
+        self.group(&[a, b])
+    }
+
+    /// Per-window values for a group, ignoring channel order.
+    pub fn group(&self, channels: &[usize]) -> Option<&[f64]> {
+        row_for(&self.q_matrix, find_group(&self.groups, channels))
+    }
+}
+
+/// CD and the CCD family: one row per directed pair.
+#[derive(Debug, Clone)]
+pub struct CrossDynamicalResult {
+    /// Concrete measure (`CD`, `CCD`, `CCDLOG`, `CCDSIG`, ...)
+    pub variant_id: String,
+    pub pairs: Vec<DirectedPair>,
+    pub labels: Vec<String>,
+    pub q_matrix: Vec<Vec<f64>>,
+}
+
+impl CrossDynamicalResult {
+    /// Per-window values for the influence of `source` on `target`.
+    pub fn directed(&self, source: usize, target: usize) -> Option<&[f64]> {
+        row_for(
+            &self.q_matrix,
+            self.pairs
+                .iter()
+                .position(|pair| pair.source == source && pair.target == target),
+        )
+    }
+}
+
+/// DE: one row per channel group.
+#[derive(Debug, Clone)]
+pub struct DynamicalErgodicityResult {
+    pub groups: Vec<Vec<usize>>,
+    pub labels: Vec<String>,
+    pub q_matrix: Vec<Vec<f64>>,
+}
+
+impl DynamicalErgodicityResult {
+    /// Per-window values for a group, ignoring channel order.
+    pub fn group(&self, channels: &[usize]) -> Option<&[f64]> {
+        row_for(&self.q_matrix, find_group(&self.groups, channels))
+    }
+}
+
+/// SY: one row per pair, or one per direction when run in directed mode.
+#[derive(Debug, Clone)]
+pub struct SynchronizationResult {
+    /// `[from, to]` for each row
+    pub pairs: Vec<[usize; 2]>,
+    pub labels: Vec<String>,
+    pub q_matrix: Vec<Vec<f64>>,
+}
+
+impl SynchronizationResult {
+    /// Per-window values for `from -> to`, falling back to an undirected row.
+    pub fn pair(&self, from: usize, to: usize) -> Option<&[f64]> {
+        let exact = self.pairs.iter().position(|p| *p == [from, to]);
+        row_for(
+            &self.q_matrix,
+            exact.or_else(|| self.pairs.iter().position(|p| *p == [to, from])),
+        )
+    }
+
+    /// Whether rows are split by direction.
+    pub fn is_directed(&self) -> bool {
+        self.pairs
+            .iter()
+            .any(|p| self.pairs.contains(&[p[1], p[0]]))
+    }
+}
+
+/// A variant result with its row layout resolved.
+#[derive(Debug, Clone)]
+pub enum TypedVariantResult {
+    SingleTimeseries(SingleTimeseriesResult),
+    CrossTimeseries(CrossTimeseriesResult),
+    CrossDynamical(CrossDynamicalResult),
+    DynamicalErgodicity(DynamicalErgodicityResult),
+    Synchronization(SynchronizationResult),
+}
+
+impl TypedVariantResult {
+    pub fn from_variant(variant: &VariantResult) -> Result<Self> {
+        let rows = variant.row_channels.as_ref().ok_or_else(|| {
+            DDAError::InvalidParameter(format!(
+                "variant {} has no row channel indices",
+                variant.variant_id
+            ))
+        })?;
+        if rows.len() != variant.q_matrix.len() {
+            return Err(DDAError::InvalidParameter(format!(
+                "variant {} has {} row channel entries for {} Q-matrix rows",
+                variant.variant_id,
+                rows.len(),
+                variant.q_matrix.len()
+            )));
+        }
+        let labels = variant.channel_labels.clone().unwrap_or_default();
+        let q_matrix = variant.q_matrix.clone();
+        let id = variant.variant_id.to_ascii_uppercase();
+
+        Ok(match id.as_str() {
+            "ST" => Self::SingleTimeseries(SingleTimeseriesResult {
+                channels: rows
+                    .iter()
+                    .map(|row| expect_arity(&id, row, 1).map(|row| row[0]))
+                    .collect::<Result<_>>()?,
+                labels,
+                q_matrix,
+            }),
+            "CT" => Self::CrossTimeseries(CrossTimeseriesResult {
+                groups: rows.clone(),
+                labels,
+                q_matrix,
+            }),
+            "DE" => Self::DynamicalErgodicity(DynamicalErgodicityResult {
+                groups: rows.clone(),
+                labels,
+                q_matrix,
+            }),
+            "SY" => Self::Synchronization(SynchronizationResult {
+                pairs: rows
+                    .iter()
+                    .map(|row| expect_arity(&id, row, 2).map(|row| [row[0], row[1]]))
+                    .collect::<Result<_>>()?,
+                labels,
+                q_matrix,
+            }),
+            "CD" | "CCD" | "CCDLOG" | "CCDPR2" | "CCDSIG" | "CCDSTAB" | "TRCCD" | "MVCCD" => {
+                Self::CrossDynamical(CrossDynamicalResult {
+                    variant_id: id.clone(),
+                    pairs: rows
+                        .iter()
+                        .map(|row| {
+                            expect_arity(&id, row, 2).map(|row| DirectedPair {
+                                target: row[0],
+                                source: row[1],
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                    labels,
+                    q_matrix,
+                })
+            }
+            _ => {
+                return Err(DDAError::InvalidParameter(format!(
+                    "unknown variant {}",
+                    variant.variant_id
+                )))
+            }
+        })
+    }
+
+    pub fn variant_id(&self) -> &str {
+        match self {
+            Self::SingleTimeseries(_) => "ST",
+            Self::CrossTimeseries(_) => "CT",
+            Self::CrossDynamical(result) => &result.variant_id,
+            Self::DynamicalErgodicity(_) => "DE",
+            Self::Synchronization(_) => "SY",
+        }
+    }
+}
+
+impl DDAResult {
+    /// Typed views of every variant in this result.
+    pub fn typed_variants(&self) -> Result<Vec<TypedVariantResult>> {
+        self.variant_results
+            .iter()
+            .flatten()
+            .map(TypedVariantResult::from_variant)
+            .collect()
+    }
+
+    /// Typed view of one variant, if it was computed.
+    pub fn typed_variant(&self, variant_id: &str) -> Option<Result<TypedVariantResult>> {
+        self.variant_results
+            .iter()
+            .flatten()
+            .find(|variant| variant.variant_id.eq_ignore_ascii_case(variant_id))
+            .map(TypedVariantResult::from_variant)
+    }
+}
+
+fn expect_arity<'a>(variant_id: &str, row: &'a [usize], arity: usize) -> Result<&'a [usize]> {
+    if row.len() == arity {
+        Ok(row)
+    } else {
+        Err(DDAError::InvalidParameter(format!(
+            "variant {} expects {} channel(s) per row, found {}",
+            variant_id,
+            arity,
+            row.len()
+        )))
+    }
+}
+
+fn find_group(groups: &[Vec<usize>], channels: &[usize]) -> Option<usize> {
+    let mut wanted = channels.to_vec();
+    wanted.sort_unstable();
+    groups.iter().position(|group| {
+        let mut group = group.clone();
+        group.sort_unstable();
+        group == wanted
+    })
+}
+
+fn row_for(q_matrix: &[Vec<f64>], index: Option<usize>) -> Option<&[f64]> {
+    index.and_then(|idx| q_matrix.get(idx)).map(Vec::as_slice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(id: &str, rows: Vec<Vec<usize>>) -> VariantResult {
+        VariantResult {
+            variant_id: id.to_string(),
+            variant_name: id.to_string(),
+            q_matrix: (0..rows.len()).map(|row| vec![row as f64; 3]).collect(),
+            channel_labels: None,
+            error_values: None,
+            row_channels: Some(rows),
+        }
+    }
+
+    #[test]
+    fn test_cross_dynamical_indexes_directed_pairs() {
+        let typed =
+            TypedVariantResult::from_variant(&variant("CD", vec![vec![1, 0], vec![0, 2]])).unwrap();
+        let TypedVariantResult::CrossDynamical(cd) = typed else {
+            panic!("expected CD result");
+        };
+        // Row 0 is "1 <- 0": source 0 drives target 1
+        assert_eq!(cd.directed(0, 1), Some(&[0.0; 3][..]));
+        assert_eq!(cd.directed(2, 0), Some(&[1.0; 3][..]));
+        assert_eq!(cd.directed(1, 0), None);
+    }
+
+    #[test]
+    fn test_cross_timeseries_pair_is_unordered() {
+        let typed =
+            TypedVariantResult::from_variant(&variant("CT", vec![vec![0, 1], vec![1, 2]])).unwrap();
+        let TypedVariantResult::CrossTimeseries(ct) = typed else {
+            panic!("expected CT result");
+        };
+        assert_eq!(ct.pair(2, 1), Some(&[1.0; 3][..]));
+        assert_eq!(ct.pair(0, 2), None);
+    }
+
+    #[test]
+    fn test_missing_row_channels_is_an_error() {
+        let mut legacy = variant("ST", vec![vec![0]]);
+        legacy.row_channels = None;
+        assert!(TypedVariantResult::from_variant(&legacy).is_err());
+    }
+}
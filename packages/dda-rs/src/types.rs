@@ -1,3 +1,4 @@
+use crate::preprocessing::PreprocessingStep;
 use serde::{Deserialize, Serialize};
 
 /// Default DDA parameter values shared across wrappers.
@@ -17,7 +18,7 @@ pub struct TimeRange {
 }
 
 /// Preprocessing options
-/// Note: Preprocessing should be done before DDA analysis, not by this package
+/// Filters applied by the pure Rust runner before analysis; see [`crate::preprocessing`]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PreprocessingOptions {
     pub highpass: Option<f64>,
@@ -182,6 +183,9 @@ pub struct VariantResult {
     pub channel_labels: Option<Vec<String>>, // Optional channel labels specific to this variant
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_values: Option<Vec<f64>>, // Error/rho values per window from DDA output
+    /// Input channel indices behind each Q-matrix row (a channel, group, or ordered pair)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_channels: Option<Vec<Vec<usize>>>,
 }
 
 /// DDA analysis result
@@ -198,6 +202,9 @@ pub struct DDAResult {
     pub created_at: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_values: Option<Vec<f64>>, // Error/rho values per window from DDA output
+    /// Preprocessing applied to the data before analysis, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preprocessing: Vec<PreprocessingStep>,
 }
 
 impl DDAResult {
@@ -220,6 +227,7 @@ impl DDAResult {
             delay_parameters,
             created_at: chrono::Utc::now().to_rfc3339(),
             error_values: None,
+            preprocessing: Vec::new(),
         }
     }
 
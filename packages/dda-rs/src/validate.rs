@@ -0,0 +1,571 @@
+//! Conformance checks for DDA output against the specification layout
+//!
+//! Two kinds of output are checked: native-layout text files (one row per
+//! window, two window-bound columns followed by `stride` columns per result
+//! row, named with the variant's output suffix) and serialized [`DDAResult`]
+//! JSON as written by `ddalab run`. Problems are reported as structured
+//! diagnostics instead of failing on the first one, so callers can show every
+//! issue at once.
+
+use crate::error::Result;
+use crate::types::DDAResult;
+use crate::variants::{ChannelFormat, VariantMetadata, VARIANT_REGISTRY};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Columns before the per-row blocks in native output (window start and end)
+pub const NATIVE_WINDOW_COLUMNS: usize = 2;
+
+/// Relative tolerance when comparing window marker spacing
+const MARKER_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One conformance problem
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable machine-readable code, e.g. `STRIDE_MISMATCH`
+    pub code: String,
+    pub message: String,
+    /// Line number, variant id, or other locator
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+}
+
+/// Result of validating one output
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Variant the output was checked against, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// Windows seen
+    pub windows: usize,
+    /// Result rows (channels, groups or pairs) per window
+    pub rows: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    /// No error-level diagnostics
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+    }
+
+    fn error(&mut self, code: &str, message: String, location: Option<String>) {
+        self.push(Severity::Error, code, message, location);
+    }
+
+    fn warning(&mut self, code: &str, message: String, location: Option<String>) {
+        self.push(Severity::Warning, code, message, location);
+    }
+
+    fn push(&mut self, severity: Severity, code: &str, message: String, location: Option<String>) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code: code.to_string(),
+            message,
+            location,
+        });
+    }
+}
+
+/// Variant whose output suffix ends `file_name` (longest suffix wins, so `_CD_DDA_ST` beats `_ST`)
+pub fn variant_for_output_name(file_name: &str) -> Option<&'static VariantMetadata> {
+    let stem = file_name.strip_suffix(".gz").unwrap_or(file_name);
+    VARIANT_REGISTRY
+        .iter()
+        .filter(|v| stem.ends_with(v.output_suffix))
+        .max_by_key(|v| v.output_suffix.len())
+}
+
+/// Check native-layout output text for `variant`.
+///
+/// `expected_rows`, when known from the request, is the number of channels,
+/// groups or pairs each window must contain.
+pub fn validate_native_output<R: BufRead>(
+    reader: R,
+    variant: &VariantMetadata,
+    expected_rows: Option<usize>,
+) -> Result<ValidationReport> {
+    let mut report = ValidationReport {
+        variant: Some(variant.abbreviation.to_string()),
+        ..Default::default()
+    };
+    if variant.reserved {
+        report.error(
+            "RESERVED_VARIANT",
+            format!(
+                "{} is reserved and must not produce output",
+                variant.abbreviation
+            ),
+            None,
+        );
+    }
+    let stride = variant.stride as usize;
+    let mut expected_columns = None;
+    let mut previous_start = f64::NEG_INFINITY;
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let location = Some(format!("line {}", idx + 1));
+        let mut values = Vec::new();
+        for token in trimmed.split_ascii_whitespace() {
+            match parse_native_value(token) {
+                Some(value) => values.push(value),
+                None => {
+                    report.error(
+                        "PARSE_ERROR",
+                        format!("'{}' is not a number", token),
+                        location.clone(),
+                    );
+                    values.clear();
+                    break;
+                }
+            }
+        }
+        if values.is_empty() {
+            continue;
+        }
+        report.windows += 1;
+
+        match expected_columns {
+            None => {
+                expected_columns = Some(values.len());
+                check_native_dimensions(
+                    &mut report,
+                    values.len(),
+                    stride,
+                    expected_rows,
+                    location.clone(),
+                );
+            }
+            Some(expected) if expected != values.len() => {
+                report.error(
+                    "COLUMN_COUNT_MISMATCH",
+                    format!(
+                        "{} columns, but the first window has {}",
+                        values.len(),
+                        expected
+                    ),
+                    location.clone(),
+                );
+                continue;
+            }
+            Some(_) => {}
+        }
+
+        if values.len() >= NATIVE_WINDOW_COLUMNS {
+            let (start, end) = (values[0], values[1]);
+            if end < start {
+                report.error(
+                    "WINDOW_BOUNDS",
+                    format!("window ends ({}) before it starts ({})", end, start),
+                    location.clone(),
+                );
+            }
+            if start < previous_start {
+                report.error(
+                    "WINDOW_ORDER",
+                    format!(
+                        "window start {} precedes the previous window's {}",
+                        start, previous_start
+                    ),
+                    location,
+                );
+            }
+            previous_start = start;
+        }
+    }
+
+    if report.windows == 0 {
+        report.error(
+            "EMPTY_OUTPUT",
+            "output contains no windows".to_string(),
+            None,
+        );
+    }
+    Ok(report)
+}
+
+fn check_native_dimensions(
+    report: &mut ValidationReport,
+    columns: usize,
+    stride: usize,
+    expected_rows: Option<usize>,
+    location: Option<String>,
+) {
+    if columns <= NATIVE_WINDOW_COLUMNS {
+        report.error(
+            "MISSING_COLUMNS",
+            format!(
+                "{} columns leave no room for results after the window bounds",
+                columns
+            ),
+            location,
+        );
+        return;
+    }
+    let data_columns = columns - NATIVE_WINDOW_COLUMNS;
+    if !data_columns.is_multiple_of(stride) {
+        report.error(
+            "STRIDE_MISMATCH",
+            format!(
+                "{} result columns are not a multiple of the variant stride {}",
+                data_columns, stride
+            ),
+            location,
+        );
+        return;
+    }
+    report.rows = data_columns / stride;
+    if let Some(expected) = expected_rows.filter(|&expected| expected != report.rows) {
+        report.error(
+            "DIMENSION_MISMATCH",
+            format!(
+                "{} result rows per window, expected {}",
+                report.rows, expected
+            ),
+            location,
+        );
+    }
+}
+
+fn parse_native_value(token: &str) -> Option<f64> {
+    if token.eq_ignore_ascii_case("nan") {
+        return Some(f64::NAN);
+    }
+    token.parse().ok()
+}
+
+/// Check the internal consistency of a serialized result.
+pub fn validate_result(result: &DDAResult) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let windows = result.error_values.as_ref().map(Vec::len);
+    if let Some(markers) = result.error_values.as_deref() {
+        check_markers(
+            &mut report,
+            markers,
+            result.window_parameters.window_step,
+            None,
+        );
+    }
+
+    let variants = result.variant_results.as_deref().unwrap_or_default();
+    if variants.is_empty() && result.q_matrix.is_empty() {
+        report.error(
+            "EMPTY_OUTPUT",
+            "result contains no Q-matrix".to_string(),
+            None,
+        );
+    }
+    for variant in variants {
+        let location = Some(variant.variant_id.clone());
+        let Some(metadata) = VariantMetadata::from_abbrev(&variant.variant_id) else {
+            // Variants outside the spec registry (e.g. the CCD family) are only checked for shape
+            check_shape(&mut report, &variant.q_matrix, windows, location);
+            continue;
+        };
+        if metadata.reserved {
+            report.error(
+                "RESERVED_VARIANT",
+                format!(
+                    "{} is reserved and must not produce output",
+                    metadata.abbreviation
+                ),
+                location.clone(),
+            );
+        }
+        let rows = variant.q_matrix.len();
+        check_shape(&mut report, &variant.q_matrix, windows, location.clone());
+        if let Some(labels) = &variant.channel_labels {
+            if labels.len() != rows {
+                report.error(
+                    "LABEL_COUNT",
+                    format!("{} channel labels for {} rows", labels.len(), rows),
+                    location.clone(),
+                );
+            }
+        }
+        if let Some(row_channels) = &variant.row_channels {
+            if row_channels.len() != rows {
+                report.error(
+                    "DIMENSION_MISMATCH",
+                    format!(
+                        "{} row channel entries for {} rows",
+                        row_channels.len(),
+                        rows
+                    ),
+                    location.clone(),
+                );
+            }
+            if metadata.channel_format == ChannelFormat::DirectedPairs {
+                if let Some(bad) = row_channels.iter().position(|row| row.len() != 2) {
+                    report.error(
+                        "ROW_ARITY",
+                        format!(
+                            "row {} of a directed-pair variant names {} channels",
+                            bad,
+                            row_channels[bad].len()
+                        ),
+                        location.clone(),
+                    );
+                }
+            }
+        }
+        if let (Some(variant_markers), Some(markers)) =
+            (&variant.error_values, &result.error_values)
+        {
+            if variant_markers != markers {
+                check_markers(
+                    &mut report,
+                    variant_markers,
+                    result.window_parameters.window_step,
+                    location.clone(),
+                );
+            }
+        }
+    }
+
+    if let Some(first) = variants.first() {
+        if first.q_matrix != result.q_matrix {
+            report.warning(
+                "PRIMARY_MISMATCH",
+                format!(
+                    "q_matrix differs from the first variant ({})",
+                    first.variant_id
+                ),
+                None,
+            );
+        }
+        report.variant = Some(first.variant_id.clone());
+        report.rows = first.q_matrix.len();
+        report.windows = first.q_matrix.first().map_or(0, Vec::len);
+    } else {
+        report.rows = result.q_matrix.len();
+        report.windows = result.q_matrix.first().map_or(0, Vec::len);
+    }
+    report
+}
+
+fn check_shape(
+    report: &mut ValidationReport,
+    q_matrix: &[Vec<f64>],
+    windows: Option<usize>,
+    location: Option<String>,
+) {
+    let expected = windows.or_else(|| q_matrix.first().map(Vec::len));
+    if let Some(expected) = expected {
+        if let Some((row, values)) = q_matrix
+            .iter()
+            .enumerate()
+            .find(|(_, values)| values.len() != expected)
+        {
+            report.error(
+                "RAGGED_MATRIX",
+                format!(
+                    "row {} has {} windows, expected {}",
+                    row,
+                    values.len(),
+                    expected
+                ),
+                location,
+            );
+        }
+    }
+}
+
+fn check_markers(
+    report: &mut ValidationReport,
+    markers: &[f64],
+    step: u32,
+    location: Option<String>,
+) {
+    let step = step as f64;
+    if let Some(idx) = markers.windows(2).position(|pair| {
+        let spacing = pair[1] - pair[0];
+        (spacing - step).abs() > MARKER_TOLERANCE * step.max(1.0)
+    }) {
+        report.error(
+            "MARKER_STRIDE",
+            format!(
+                "window markers {} and {} are not one window step ({}) apart",
+                idx,
+                idx + 1,
+                step
+            ),
+            location,
+        );
+    }
+}
+
+/// Validate an output file, choosing the check from its name.
+///
+/// `.json` (optionally `.gz`) files are read as [`DDAResult`]; other files
+/// are checked as native output for `variant`, or for the variant named by
+/// the file's output suffix.
+pub fn validate_output_path<P: AsRef<Path>>(
+    path: P,
+    variant: Option<&VariantMetadata>,
+    expected_rows: Option<usize>,
+) -> Result<ValidationReport> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let file = File::open(path)?;
+    let reader: Box<dyn Read> = if file_name.ends_with(".gz") {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let reader = BufReader::new(reader);
+
+    let stem = file_name.strip_suffix(".gz").unwrap_or(&file_name);
+    if stem.ends_with(".json") {
+        let result = match serde_json::from_reader::<_, DDAResult>(reader) {
+            Ok(result) => result,
+            Err(e) => {
+                let mut report = ValidationReport::default();
+                report.error("PARSE_ERROR", format!("not a DDA result: {}", e), None);
+                return Ok(report);
+            }
+        };
+        let mut report = validate_result(&result);
+        if let Some(expected) = expected_rows.filter(|&expected| expected != report.rows) {
+            report.error(
+                "DIMENSION_MISMATCH",
+                format!("{} result rows, expected {}", report.rows, expected),
+                report.variant.clone(),
+            );
+        }
+        return Ok(report);
+    }
+
+    match variant.or_else(|| variant_for_output_name(&file_name)) {
+        Some(variant) => validate_native_output(reader, variant, expected_rows),
+        None => {
+            let mut report = ValidationReport::default();
+            report.error(
+                "UNKNOWN_VARIANT",
+                format!(
+                    "cannot tell the variant of '{}'; name it with a variant output suffix",
+                    file_name
+                ),
+                None,
+            );
+            Ok(report)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DelayParameters, VariantResult, WindowParameters};
+    use crate::variants::{CD, ST};
+    use std::io::Cursor;
+
+    fn codes(report: &ValidationReport) -> Vec<&str> {
+        report.diagnostics.iter().map(|d| d.code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_native_output_layout() {
+        // Two ST channels: 2 bound columns + 2 * 4
+        let good = "0 99 1 2 3 0.1 4 5 6 0.2\n10 109 1 2 3 0.1 4 5 6 nan\n";
+        let report = validate_native_output(Cursor::new(good), &ST, Some(2)).unwrap();
+        assert!(report.is_valid(), "{:?}", report.diagnostics);
+        assert_eq!((report.windows, report.rows), (2, 2));
+
+        let report = validate_native_output(Cursor::new(good), &ST, Some(3)).unwrap();
+        assert_eq!(codes(&report), vec!["DIMENSION_MISMATCH"]);
+
+        // CD has stride 2, so 8 result columns are 4 pairs, but 7 are not a whole number
+        let odd = "0 99 1 2 3 4 5 6 7\n";
+        let report = validate_native_output(Cursor::new(odd), &CD, None).unwrap();
+        assert_eq!(codes(&report), vec!["STRIDE_MISMATCH"]);
+
+        let ragged = "10 20 1 2\n0 10 1\n0 10 x 2\n";
+        let report = validate_native_output(Cursor::new(ragged), &CD, None).unwrap();
+        assert_eq!(codes(&report), vec!["COLUMN_COUNT_MISMATCH", "PARSE_ERROR"]);
+    }
+
+    #[test]
+    fn test_variant_from_output_suffix() {
+        assert_eq!(
+            variant_for_output_name("run_ST").unwrap().abbreviation,
+            "ST"
+        );
+        assert_eq!(
+            variant_for_output_name("run_CD_DDA_ST.gz")
+                .unwrap()
+                .abbreviation,
+            "CD"
+        );
+        assert!(variant_for_output_name("run.txt").is_none());
+    }
+
+    #[test]
+    fn test_result_consistency() {
+        let window_parameters = WindowParameters {
+            window_length: 100,
+            window_step: 10,
+            ct_window_length: None,
+            ct_window_step: None,
+        };
+        let q = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]];
+        let mut result = DDAResult::new(
+            "id".to_string(),
+            "in.txt".to_string(),
+            vec!["a".to_string(), "b".to_string()],
+            q.clone(),
+            window_parameters,
+            DelayParameters { delays: vec![1, 2] },
+        );
+        result.error_values = Some(vec![110.0, 120.0, 130.0]);
+        result.variant_results = Some(vec![VariantResult {
+            variant_id: "CD".to_string(),
+            variant_name: "Cross-Dynamical (CD)".to_string(),
+            q_matrix: q,
+            channel_labels: Some(vec!["a->b".to_string(), "b->a".to_string()]),
+            error_values: None,
+            row_channels: Some(vec![vec![0, 1], vec![1, 0]]),
+        }]);
+        assert!(validate_result(&result).is_valid());
+
+        result.error_values = Some(vec![110.0, 120.0, 135.0]);
+        let variant = &mut result.variant_results.as_mut().unwrap()[0];
+        variant.row_channels = Some(vec![vec![0, 1], vec![1]]);
+        variant.q_matrix[1].pop();
+        let report = validate_result(&result);
+        assert_eq!(
+            codes(&report),
+            vec![
+                "MARKER_STRIDE",
+                "RAGGED_MATRIX",
+                "ROW_ARITY",
+                "PRIMARY_MISMATCH"
+            ]
+        );
+    }
+}
@@ -198,6 +198,38 @@ fn test_validate_json_output() {
     assert_eq!(parsed.get("supported").unwrap(), true);
 }
 
+#[test]
+fn test_validate_output_stride_mismatch() {
+    let dir = tempfile::tempdir().unwrap();
+    let good = dir.path().join("run_CD_DDA_ST");
+    std::fs::write(&good, "0 99 1 2 3 4\n10 109 1 2 3 4\n").unwrap();
+    let bad = dir.path().join("run_ST");
+    std::fs::write(&bad, "0 99 1 2 3 4 5\n").unwrap();
+
+    ddalab()
+        .arg("validate")
+        .arg("--output")
+        .arg(good.to_str().unwrap())
+        .arg("--rows")
+        .arg("2")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("conforms to CD"));
+
+    let output = ddalab()
+        .arg("validate")
+        .arg("--output")
+        .arg(bad.to_str().unwrap())
+        .arg("--json")
+        .assert()
+        .failure()
+        .code(1);
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    let parsed: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(parsed["valid"], false);
+    assert_eq!(parsed["diagnostics"][0]["code"], "STRIDE_MISMATCH");
+}
+
 // =============================================================================
 // RUN SUBCOMMAND — ARGUMENT VALIDATION
 // =============================================================================
@@ -272,6 +304,323 @@ fn test_run_ascii_uses_pure_rust_backend() {
     assert!(parsed.get("variant_results").is_some());
 }
 
+#[test]
+fn test_run_stitch_writes_boundary_map() {
+    let first = write_ascii_fixture();
+    let second = write_ascii_fixture();
+    let map_dir = tempfile::tempdir().unwrap();
+    let map_path = map_dir.path().join("boundaries.json");
+
+    let output = ddalab()
+        .arg("run")
+        .arg("--file")
+        .arg(first.path().to_str().unwrap())
+        .arg("--stitch")
+        .arg(second.path().to_str().unwrap())
+        .arg("--boundary-map")
+        .arg(map_path.to_str().unwrap())
+        .arg("--channels")
+        .arg("0")
+        .arg("1")
+        .arg("--wl")
+        .arg("64")
+        .arg("--ws")
+        .arg("32")
+        .arg("--delays")
+        .arg("1")
+        .arg("2")
+        .assert()
+        .success();
+
+    let stderr = String::from_utf8(output.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("Stitched: 2 files"));
+
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    let result: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let windows = result["q_matrix"][0].as_array().unwrap().len();
+
+    let map: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&map_path).unwrap()).unwrap();
+    assert_eq!(map["files"].as_array().unwrap().len(), 2);
+    assert_eq!(map["files"][1]["start_sample"], 256);
+    assert_eq!(map["files"][1]["windows"]["end"], windows);
+    assert_eq!(map["boundaries"][0]["sample"], 256);
+}
+
+#[test]
+fn test_run_progress_reports_percent_lines() {
+    let ascii = write_ascii_fixture();
+
+    let output = ddalab()
+        .env_remove("DDA_BINARY_PATH")
+        .arg("run")
+        .arg("--file")
+        .arg(ascii.path().to_str().unwrap())
+        .arg("--channels")
+        .arg("0")
+        .arg("1")
+        .arg("--variants")
+        .arg("ST")
+        .arg("--wl")
+        .arg("64")
+        .arg("--ws")
+        .arg("32")
+        .arg("--delays")
+        .arg("1")
+        .arg("2")
+        .arg("--quiet")
+        .arg("--progress")
+        .assert()
+        .success();
+
+    let stderr = String::from_utf8(output.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("Progress: 100%"));
+    assert!(!stderr.contains("Backend: pure-rust"));
+}
+
+#[test]
+fn test_run_draws_no_progress_bar_when_stderr_is_not_a_terminal() {
+    let ascii = write_ascii_fixture();
+
+    let run = |extra: &[&str]| {
+        let output = ddalab()
+            .arg("run")
+            .arg("--file")
+            .arg(ascii.path())
+            .args(["--channels", "0", "1", "--wl", "64", "--ws", "32"])
+            .args(extra)
+            .assert()
+            .success();
+        String::from_utf8(output.get_output().stderr.clone()).unwrap()
+    };
+    for stderr in [run(&[]), run(&["--no-progress"])] {
+        assert!(stderr.contains("Backend: pure-rust"));
+        assert!(!stderr.contains('\x1b'), "{:?}", stderr);
+        assert!(!stderr.contains("ETA"));
+    }
+}
+
+#[test]
+fn test_run_cache_dir_reuses_identical_analysis() {
+    let ascii = write_ascii_fixture();
+    let cache_dir = tempfile::tempdir().unwrap();
+
+    let run = || {
+        ddalab()
+            .env_remove("DDA_BINARY_PATH")
+            .arg("run")
+            .arg("--file")
+            .arg(ascii.path().to_str().unwrap())
+            .arg("--channels")
+            .arg("0")
+            .arg("1")
+            .arg("--wl")
+            .arg("64")
+            .arg("--ws")
+            .arg("32")
+            .arg("--cache-dir")
+            .arg(cache_dir.path())
+            .assert()
+            .success()
+    };
+
+    let first = run();
+    let first_stderr = String::from_utf8(first.get_output().stderr.clone()).unwrap();
+    assert!(!first_stderr.contains("Cache: hit"));
+
+    let second = run();
+    let second_stderr = String::from_utf8(second.get_output().stderr.clone()).unwrap();
+    assert!(second_stderr.contains("Cache: hit"));
+    assert_eq!(first.get_output().stdout, second.get_output().stdout);
+}
+
+#[test]
+fn test_run_output_formats() {
+    let ascii = write_ascii_fixture();
+    let run = |format: &str| {
+        let mut cmd = ddalab();
+        cmd.env_remove("DDA_BINARY_PATH")
+            .arg("run")
+            .arg("--file")
+            .arg(ascii.path().to_str().unwrap())
+            .arg("--channels")
+            .arg("0")
+            .arg("1")
+            .arg("--wl")
+            .arg("64")
+            .arg("--ws")
+            .arg("32")
+            .arg("--quiet")
+            .arg("--output-format")
+            .arg(format);
+        cmd
+    };
+
+    let jsonl = run("jsonl").assert().success();
+    let stdout = String::from_utf8(jsonl.get_output().stdout.clone()).unwrap();
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["variant"], "ST");
+    assert!(lines[0]["mean"].is_number());
+
+    let csv = run("csv").assert().success();
+    let stdout = String::from_utf8(csv.get_output().stdout.clone()).unwrap();
+    assert_eq!(stdout.lines().next(), Some("variant,label,window,q"));
+    let windows = lines[0]["windows"].as_u64().unwrap() as usize;
+    assert_eq!(stdout.lines().count(), 1 + 2 * windows);
+
+    run("parquet")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("requires --output"));
+}
+
+#[test]
+fn test_run_profile_from_config_file() {
+    let ascii = write_ascii_fixture();
+    let config_dir = tempfile::tempdir().unwrap();
+    let config = config_dir.path().join("cli.toml");
+    std::fs::write(
+        &config,
+        "[profiles.quick]
+channels = [0, 1]
+wl = 64
+ws = 32
+delays = [1, 2]
+",
+    )
+    .unwrap();
+
+    let run = |extra: &[&str]| {
+        let output = ddalab()
+            .env_remove("DDA_BINARY_PATH")
+            .arg("run")
+            .arg("--file")
+            .arg(ascii.path().to_str().unwrap())
+            .arg("--profile")
+            .arg("quick")
+            .arg("--config")
+            .arg(&config)
+            .arg("--quiet")
+            .args(extra)
+            .assert()
+            .success();
+        let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+        serde_json::from_str::<serde_json::Value>(&stdout).unwrap()
+    };
+
+    let result = run(&[]);
+    assert_eq!(result["window_parameters"]["window_length"], 64);
+    assert_eq!(result["channels"].as_array().unwrap().len(), 2);
+    // Flags on the command line take precedence over the profile
+    let result = run(&["--wl", "48"]);
+    assert_eq!(result["window_parameters"]["window_length"], 48);
+
+    ddalab()
+        .arg("run")
+        .arg("--file")
+        .arg(ascii.path().to_str().unwrap())
+        .arg("--profile")
+        .arg("missing")
+        .arg("--config")
+        .arg(&config)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("available: quick"));
+}
+
+#[test]
+fn test_run_jobs_keeps_channel_order() {
+    let mut wide = tempfile::Builder::new()
+        .suffix(".ascii")
+        .tempfile()
+        .unwrap();
+    for t in 0..256 {
+        let row: Vec<String> = (1..=6)
+            .map(|c| format!("{:.12}", (t as f64 * 0.01 * c as f64).sin()))
+            .collect();
+        writeln!(wide, "{}", row.join(" ")).unwrap();
+    }
+
+    let run = |jobs: &str| {
+        let output = ddalab()
+            .env_remove("DDA_BINARY_PATH")
+            .arg("run")
+            .arg("--file")
+            .arg(wide.path().to_str().unwrap())
+            .arg("--channels")
+            .args(["0", "1", "2", "3", "4", "5"])
+            .arg("--wl")
+            .arg("64")
+            .arg("--ws")
+            .arg("32")
+            .arg("--quiet")
+            .arg("--jobs")
+            .arg(jobs)
+            .assert()
+            .success();
+        let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+        let result: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+        result["variant_results"].clone()
+    };
+
+    let serial = run("1");
+    assert_eq!(serial[0]["q_matrix"].as_array().unwrap().len(), 6);
+    assert_eq!(serial, run("4"));
+}
+
+#[test]
+fn test_run_filters_before_analysis_and_records_it() {
+    let ascii = write_ascii_fixture();
+    let run = |extra: &[&str]| {
+        let output = ddalab()
+            .arg("run")
+            .arg("--file")
+            .arg(ascii.path())
+            .args([
+                "--channels",
+                "0",
+                "1",
+                "--wl",
+                "64",
+                "--ws",
+                "32",
+                "--quiet",
+            ])
+            .args(extra)
+            .assert()
+            .success();
+        serde_json::from_slice::<serde_json::Value>(&output.get_output().stdout).unwrap()
+    };
+
+    let plain = run(&[]);
+    assert!(plain.get("preprocessing").is_none());
+    let filtered = run(&["--sr", "32", "--highpass", "0.5", "--lowpass", "8"]);
+    assert_eq!(
+        filtered["preprocessing"],
+        serde_json::json!([
+            {"name": "highpass", "parameters": {"cutoff_hz": 0.5}},
+            {"name": "lowpass", "parameters": {"cutoff_hz": 8.0}},
+        ])
+    );
+    assert_ne!(
+        filtered["variant_results"][0]["q_matrix"],
+        plain["variant_results"][0]["q_matrix"]
+    );
+
+    ddalab()
+        .arg("run")
+        .arg("--file")
+        .arg(ascii.path())
+        .args(["--channels", "0", "--highpass", "0.5"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("--sr"));
+}
+
 #[test]
 fn test_run_invalid_variant() {
     let tmp = tempfile::Builder::new().suffix(".edf").tempfile().unwrap();
@@ -343,3 +692,560 @@ fn test_run_unsupported_file_extension() {
         .code(1)
         .stderr(predicate::str::contains("Unsupported"));
 }
+
+// =============================================================================
+// BATCH SUBCOMMAND
+// =============================================================================
+
+#[test]
+fn test_batch_directory_with_params_writes_manifest() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let ascii = write_ascii_fixture();
+    std::fs::copy(ascii.path(), data_dir.path().join("rest.ascii")).unwrap();
+    std::fs::write(data_dir.path().join("bad.edf"), "").unwrap();
+
+    let work = tempfile::tempdir().unwrap();
+    let params = work.path().join("params.toml");
+    std::fs::write(
+        &params,
+        "channels = [0, 1]\nvariants = [\"ST\"]\nwl = 64\nws = 32\ndelays = [1, 2]\n",
+    )
+    .unwrap();
+    let out_dir = work.path().join("results");
+
+    ddalab()
+        .env_remove("DDA_BINARY_PATH")
+        .arg("batch")
+        .arg(data_dir.path())
+        .arg("--params")
+        .arg(&params)
+        .arg("--output-dir")
+        .arg(&out_dir)
+        .arg("--continue-on-error")
+        .arg("--quiet")
+        .assert()
+        .code(4);
+
+    let result: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(out_dir.join("rest/dda.json")).unwrap())
+            .unwrap();
+    assert!(result.get("variant_results").is_some());
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(out_dir.join("manifest.json")).unwrap())
+            .unwrap();
+    assert_eq!(manifest["total"], 2);
+    assert_eq!(manifest["succeeded"], 1);
+    assert_eq!(manifest["failed"], 1);
+    let files = manifest["files"].as_array().unwrap();
+    assert_eq!(files[0]["status"], "failed");
+    assert_eq!(files[1]["status"], "succeeded");
+
+    // Parameter flags cannot be mixed with a params file
+    ddalab()
+        .arg("batch")
+        .arg(data_dir.path())
+        .arg("--params")
+        .arg(&params)
+        .arg("--wl")
+        .arg("64")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_batch_resume_skips_finished_inputs() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let ascii = write_ascii_fixture();
+    let done = data_dir.path().join("a.ascii");
+    let missing = data_dir.path().join("b.ascii");
+    std::fs::copy(ascii.path(), &done).unwrap();
+    let out_dir = data_dir.path().join("results");
+
+    let batch = |extra: &[&str]| {
+        let mut cmd = ddalab();
+        cmd.arg("batch")
+            .arg("--files")
+            .arg(&done)
+            .arg(&missing)
+            .args(["--channels", "0", "1", "--wl", "64", "--ws", "32"])
+            .arg("--output-dir")
+            .arg(&out_dir)
+            .args(["--continue-on-error", "--quiet"])
+            .args(extra);
+        cmd
+    };
+    batch(&[]).assert().code(4);
+    let state: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(out_dir.join(".batch-state.json")).unwrap())
+            .unwrap();
+    assert_eq!(
+        state["inputs"][done.to_str().unwrap()]["status"],
+        "succeeded"
+    );
+    assert_eq!(
+        state["inputs"][missing.to_str().unwrap()]["status"],
+        "failed"
+    );
+
+    // The finished input is not analyzed again; the failed one is retried
+    std::fs::write(out_dir.join("a/dda.json"), "{}").unwrap();
+    std::fs::copy(ascii.path(), &missing).unwrap();
+    batch(&["--resume"]).assert().success();
+    assert_eq!(
+        std::fs::read_to_string(out_dir.join("a/dda.json")).unwrap(),
+        "{}"
+    );
+    assert!(out_dir.join("b/dda.json").is_file());
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(out_dir.join("manifest.json")).unwrap())
+            .unwrap();
+    assert_eq!(manifest["succeeded"], 2);
+    assert_eq!(manifest["resumed"], 1);
+
+    batch(&["--resume", "--dm", "3"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("different analysis parameters"));
+}
+
+// =============================================================================
+// CONVERT SUBCOMMAND
+// =============================================================================
+
+#[test]
+fn test_convert_selects_channels_and_range() {
+    let ascii = write_ascii_fixture();
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("subset.csv");
+
+    ddalab()
+        .arg("convert")
+        .arg(ascii.path())
+        .arg(&out)
+        .arg("--channels")
+        .arg("1")
+        .arg("--start-sample")
+        .arg("10")
+        .arg("--end-sample")
+        .arg("20")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("10 sample(s) x 1 channel(s)"));
+
+    let text = std::fs::read_to_string(&out).unwrap();
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("# Ch2"));
+    let values: Vec<f64> = lines.map(|line| line.parse().unwrap()).collect();
+    assert_eq!(values.len(), 10);
+    let expected = 0.6 * (10.0f64 * 0.05).sin() + (10.0f64 * 0.07).cos() * 0.1;
+    assert!((values[0] - expected).abs() < 1e-9);
+
+    ddalab()
+        .arg("convert")
+        .arg(ascii.path())
+        .arg(&out)
+        .arg("--channels")
+        .arg("5")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("out of range"));
+}
+
+// =============================================================================
+// DIFF SUBCOMMAND
+// =============================================================================
+
+#[test]
+fn test_diff_result_directories() {
+    let ascii = write_ascii_fixture();
+    let root = tempfile::tempdir().unwrap();
+    let (dir_a, dir_b) = (root.path().join("a"), root.path().join("b"));
+    for dir in [&dir_a, &dir_b] {
+        std::fs::create_dir_all(dir.join("rest")).unwrap();
+        ddalab()
+            .env_remove("DDA_BINARY_PATH")
+            .args(["run", "--channels", "0", "1", "--wl", "64", "--ws", "32"])
+            .args(["--delays", "1", "2", "--file"])
+            .arg(ascii.path())
+            .arg("-o")
+            .arg(dir.join("rest/dda.json"))
+            .assert()
+            .success();
+    }
+
+    ddalab()
+        .arg("diff")
+        .arg(&dir_a)
+        .arg(&dir_b)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("within tolerance"));
+
+    // Perturb one value and drop the file from one side
+    let path = dir_b.join("rest/dda.json");
+    let mut result: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let value = &mut result["variant_results"][0]["q_matrix"][0][0];
+    *value = serde_json::json!(value.as_f64().unwrap() + 1e-3);
+    std::fs::write(&path, result.to_string()).unwrap();
+    std::fs::copy(&path, dir_a.join("extra.json")).unwrap();
+
+    let output = ddalab()
+        .arg("diff")
+        .arg(&dir_a)
+        .arg(&dir_b)
+        .arg("--json")
+        .assert()
+        .code(5);
+    let report: serde_json::Value = serde_json::from_slice(&output.get_output().stdout).unwrap();
+    assert_eq!(report["within_tolerance"], false);
+    let files = report["files"].as_array().unwrap();
+    assert_eq!(files[0]["error"], "only in the first directory");
+    assert_eq!(files[1]["diff"]["variants"][0]["exceeding"], 1);
+}
+
+// =============================================================================
+// WATCH SUBCOMMAND
+// =============================================================================
+
+#[test]
+fn test_watch_once_analyzes_existing_files() {
+    let watch_dir = tempfile::tempdir().unwrap();
+    let ascii = write_ascii_fixture();
+    std::fs::create_dir(watch_dir.path().join("sub-01")).unwrap();
+    std::fs::copy(ascii.path(), watch_dir.path().join("sub-01/rest.ascii")).unwrap();
+    std::fs::copy(ascii.path(), watch_dir.path().join("rest.ascii.part")).unwrap();
+
+    let work = tempfile::tempdir().unwrap();
+    let params = work.path().join("params.toml");
+    std::fs::write(
+        &params,
+        "channels = [0, 1]\nvariants = [\"ST\"]\nwl = 64\nws = 32\ndelays = [1, 2]\n",
+    )
+    .unwrap();
+    let out_dir = work.path().join("results");
+
+    let watch = || {
+        let mut cmd = ddalab();
+        cmd.arg("watch")
+            .arg(watch_dir.path())
+            .arg("--params")
+            .arg(&params)
+            .arg("--output-dir")
+            .arg(&out_dir)
+            .arg("--settle-secs")
+            .arg("0")
+            .arg("--once");
+        cmd
+    };
+    watch()
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("1 succeeded, 0 failed"));
+
+    let result: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(out_dir.join("sub-01/rest/dda.json")).unwrap(),
+    )
+    .unwrap();
+    assert!(result.get("variant_results").is_some());
+    assert!(!out_dir.join("rest").exists());
+
+    // Up-to-date results are not recomputed
+    watch()
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Analyzing 0 file(s)"));
+}
+
+// =============================================================================
+// BIDS SUBCOMMAND
+// =============================================================================
+
+#[test]
+fn test_bids_writes_derivatives_for_selected_runs() {
+    let root = tempfile::tempdir().unwrap();
+    let ascii = write_ascii_fixture();
+    for (sub, task) in [
+        ("01", "rest"),
+        ("02", "rest"),
+        ("03", "rest"),
+        ("01", "motor"),
+    ] {
+        let eeg_dir = root.path().join(format!("sub-{sub}/eeg"));
+        std::fs::create_dir_all(&eeg_dir).unwrap();
+        std::fs::copy(
+            ascii.path(),
+            eeg_dir.join(format!("sub-{sub}_task-{task}_eeg.txt")),
+        )
+        .unwrap();
+    }
+    std::fs::write(
+        root.path().join("task-rest_eeg.json"),
+        r#"{"SamplingFrequency": 256}"#,
+    )
+    .unwrap();
+    let params = root.path().join("params.toml");
+    std::fs::write(
+        &params,
+        "channels = [0, 1]\nwl = 64\nws = 32\ndelays = [1, 2]\n",
+    )
+    .unwrap();
+
+    ddalab()
+        .arg("bids")
+        .arg(root.path())
+        .args(["--task", "rest", "--sub", "01..02", "--params"])
+        .arg(&params)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("2/2 succeeded"));
+
+    let derivatives = root.path().join("derivatives/ddalab");
+    let description: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(derivatives.join("dataset_description.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(description["DatasetType"], "derivative");
+    assert_eq!(description["GeneratedBy"][0]["Name"], "ddalab");
+    assert!(derivatives
+        .join("sub-01/eeg/sub-01_task-rest_dda.json")
+        .exists());
+    assert!(derivatives
+        .join("sub-02/eeg/sub-02_task-rest_dda.json")
+        .exists());
+    assert!(!derivatives.join("sub-03").exists());
+    assert!(!derivatives
+        .join("sub-01/eeg/sub-01_task-motor_dda.json")
+        .exists());
+}
+
+// =============================================================================
+// RENDER SUBCOMMAND
+// =============================================================================
+
+#[test]
+fn test_render_writes_png_and_svg_heatmaps() {
+    let ascii = write_ascii_fixture();
+    let dir = tempfile::tempdir().unwrap();
+    let result = dir.path().join("dda.json");
+    ddalab()
+        .arg("run")
+        .arg("--file")
+        .arg(ascii.path())
+        .args([
+            "--channels",
+            "0",
+            "1",
+            "--wl",
+            "64",
+            "--ws",
+            "32",
+            "--quiet",
+        ])
+        .arg("--output")
+        .arg(&result)
+        .assert()
+        .success();
+
+    let png = dir.path().join("heatmap.png");
+    ddalab()
+        .arg("render")
+        .arg(dir.path())
+        .arg("-o")
+        .arg(&png)
+        .args(["--width", "40", "--row-height", "5", "--log10"])
+        .assert()
+        .success();
+    let bytes = std::fs::read(&png).unwrap();
+    assert!(bytes.starts_with(b"\x89PNG"));
+    // IHDR: width then height, big-endian
+    assert_eq!(&bytes[16..24], &[0, 0, 0, 40, 0, 0, 0, 10]);
+
+    let svg = dir.path().join("heatmap.svg");
+    ddalab()
+        .arg("render")
+        .arg(&result)
+        .arg("-o")
+        .arg(&svg)
+        .args(["--variant", "st", "--colormap", "magma"])
+        .assert()
+        .success();
+    let text = std::fs::read_to_string(&svg).unwrap();
+    assert!(text.starts_with("<svg"));
+    assert!(text.contains("linearGradient"));
+
+    ddalab()
+        .arg("render")
+        .arg(&result)
+        .args(["-o", "heatmap.jpg"])
+        .assert()
+        .code(1);
+    ddalab()
+        .arg("render")
+        .arg(&result)
+        .arg("-o")
+        .arg(&svg)
+        .args(["--variant", "CT"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("available: ST"));
+}
+
+// =============================================================================
+// REMOTE RUN
+// =============================================================================
+
+/// Serve `responses` to one request each, returning what was requested
+fn mock_server(
+    responses: Vec<(&'static str, Vec<u8>)>,
+) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    use std::io::{BufRead, BufReader, Read};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+            let mut request_body = vec![0; length];
+            reader.read_exact(&mut request_body).unwrap();
+            requests.push(format!(
+                "{}\n{}",
+                head,
+                String::from_utf8_lossy(&request_body)
+            ));
+
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+        }
+        requests
+    });
+    (url, handle)
+}
+
+#[test]
+fn test_run_remote_uploads_polls_and_downloads() {
+    let ascii = write_ascii_fixture();
+    let dir = tempfile::tempdir().unwrap();
+    let local = dir.path().join("local.json");
+    ddalab()
+        .arg("run")
+        .arg("--file")
+        .arg(ascii.path())
+        .args([
+            "--channels",
+            "0",
+            "1",
+            "--wl",
+            "64",
+            "--ws",
+            "32",
+            "--quiet",
+        ])
+        .arg("--output")
+        .arg(&local)
+        .assert()
+        .success();
+    let result = std::fs::read(&local).unwrap();
+
+    let (url, server) = mock_server(vec![
+        (
+            "200 OK",
+            br#"{"job_id":"job-1","status":"pending","message":"queued"}"#.to_vec(),
+        ),
+        (
+            "200 OK",
+            br#"{"id":"job-1","status":"completed","progress":100,"message":"done"}"#.to_vec(),
+        ),
+        ("200 OK", result.clone()),
+    ]);
+    let remote = dir.path().join("remote.json");
+    ddalab()
+        .arg("run")
+        .arg("--file")
+        .arg(ascii.path())
+        .args([
+            "--channels",
+            "0",
+            "1",
+            "--wl",
+            "64",
+            "--ws",
+            "32",
+            "--sr",
+            "32",
+        ])
+        .args(["--remote", &url, "--token", "secret", "--quiet"])
+        .arg("--output")
+        .arg(&remote)
+        .assert()
+        .success();
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("POST /api/jobs/upload "));
+    assert!(requests[0].contains("Authorization: Bearer secret"));
+    assert!(requests[0].contains(r#""time_window":2.0"#));
+    assert!(requests[0].contains(r#""channels":["0","1"]"#));
+    assert!(requests[0].contains("filename=\""));
+    assert!(requests[1].starts_with("GET /api/jobs/job-1 "));
+    assert!(requests[2].starts_with("GET /api/jobs/job-1/download "));
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&std::fs::read(&remote).unwrap()).unwrap(),
+        serde_json::from_slice::<serde_json::Value>(&result).unwrap()
+    );
+}
+
+#[test]
+fn test_run_remote_reports_failed_job() {
+    let ascii = write_ascii_fixture();
+    let (url, server) = mock_server(vec![
+        (
+            "200 OK",
+            br#"{"job_id":"job-2","status":"pending","message":"queued"}"#.to_vec(),
+        ),
+        (
+            "200 OK",
+            br#"{"id":"job-2","status":"failed","progress":40,"error":"binary crashed"}"#.to_vec(),
+        ),
+    ]);
+    ddalab()
+        .arg("run")
+        .arg("--file")
+        .arg(ascii.path())
+        .args(["--channels", "0", "--sr", "32", "--remote", &url, "--quiet"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("binary crashed"));
+    server.join().unwrap();
+
+    ddalab()
+        .arg("run")
+        .arg("--file")
+        .arg(ascii.path())
+        .args(["--channels", "0", "--remote", "http://127.0.0.1:9"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("--sr"));
+}
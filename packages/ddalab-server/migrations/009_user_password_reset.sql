@@ -0,0 +1,2 @@
+-- Force a password change on next login (set for bulk-imported accounts)
+ALTER TABLE users ADD COLUMN IF NOT EXISTS password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,22 @@
+-- Parameter presets published by team admins
+CREATE TABLE IF NOT EXISTS team_presets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    description TEXT,
+    parameters JSONB NOT NULL DEFAULT '{}',
+    locked_fields TEXT[] NOT NULL DEFAULT '{}',
+    required BOOLEAN NOT NULL DEFAULT FALSE,
+    created_by UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(team_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_team_presets_team ON team_presets(team_id);
+
+DROP TRIGGER IF EXISTS team_presets_updated_at ON team_presets;
+CREATE TRIGGER team_presets_updated_at
+    BEFORE UPDATE ON team_presets
+    FOR EACH ROW
+    EXECUTE FUNCTION update_teams_updated_at();
@@ -0,0 +1,32 @@
+-- Organizations group teams and users so one server can host several
+-- departments whose data stays apart
+CREATE TABLE IF NOT EXISTS organizations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_by UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Organization membership with an org-scoped role
+CREATE TABLE IF NOT EXISTS organization_members (
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL,
+    role TEXT NOT NULL DEFAULT 'member' CHECK (role IN ('owner', 'admin', 'member')),
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    added_by UUID,
+    PRIMARY KEY (organization_id, user_id)
+);
+
+-- Teams optionally belong to one organization
+ALTER TABLE teams ADD COLUMN IF NOT EXISTS organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_organization_members_user ON organization_members(user_id);
+CREATE INDEX IF NOT EXISTS idx_teams_organization ON teams(organization_id) WHERE organization_id IS NOT NULL;
+
+DROP TRIGGER IF EXISTS organizations_updated_at ON organizations;
+CREATE TRIGGER organizations_updated_at
+    BEFORE UPDATE ON organizations
+    FOR EACH ROW
+    EXECUTE FUNCTION update_teams_updated_at();
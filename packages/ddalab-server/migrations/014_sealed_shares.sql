@@ -0,0 +1,10 @@
+-- End-to-end encrypted shares: clients publish an X25519 public key and
+-- seal a share's title, description and content for its recipients, so
+-- the server stores only ciphertext and wrapped keys
+CREATE TABLE IF NOT EXISTS user_share_keys (
+    user_id VARCHAR(255) PRIMARY KEY,
+    public_key TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE shared_results ADD COLUMN IF NOT EXISTS sealed JSONB;
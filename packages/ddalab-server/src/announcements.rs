@@ -0,0 +1,147 @@
+//! Server-wide announcements (message of the day)
+//!
+//! Announcements are stored in Postgres; the board keeps the unexpired ones
+//! in memory for `/info` and broadcasts every change to connected clients.
+
+use chrono::Utc;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::storage::Announcement;
+
+/// A change to the set of announcements
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnouncementNotice {
+    Published(Announcement),
+    Withdrawn(Uuid),
+}
+
+/// Unexpired announcements; cheap to clone
+#[derive(Clone)]
+pub struct AnnouncementBoard {
+    announcements: Arc<RwLock<Vec<Announcement>>>,
+    notices: broadcast::Sender<AnnouncementNotice>,
+}
+
+impl Default for AnnouncementBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnnouncementBoard {
+    pub fn new() -> Self {
+        let (notices, _) = broadcast::channel(16);
+        Self {
+            announcements: Arc::new(RwLock::new(Vec::new())),
+            notices,
+        }
+    }
+
+    /// Replace the board's contents, e.g. with the stored announcements at startup
+    pub fn load(&self, announcements: Vec<Announcement>) {
+        *self.announcements.write().unwrap() = announcements;
+    }
+
+    /// Announcements shown right now, dropping expired ones
+    pub fn current(&self) -> Vec<Announcement> {
+        let now = Utc::now();
+        let mut announcements = self.announcements.write().unwrap();
+        announcements.retain(|a| a.ends_at.is_none_or(|end| now < end));
+        announcements
+            .iter()
+            .filter(|a| a.is_current(now))
+            .cloned()
+            .collect()
+    }
+
+    /// Add an announcement and notify subscribers
+    ///
+    /// Scheduled announcements are pushed straight away too; clients show
+    /// them from `starts_at`.
+    pub fn publish(&self, announcement: Announcement) {
+        self.announcements
+            .write()
+            .unwrap()
+            .push(announcement.clone());
+        let _ = self
+            .notices
+            .send(AnnouncementNotice::Published(announcement));
+    }
+
+    /// Remove an announcement; returns false if it was not on the board
+    pub fn withdraw(&self, id: Uuid) -> bool {
+        let removed = {
+            let mut announcements = self.announcements.write().unwrap();
+            let before = announcements.len();
+            announcements.retain(|a| a.id != id);
+            announcements.len() != before
+        };
+        if removed {
+            let _ = self.notices.send(AnnouncementNotice::Withdrawn(id));
+        }
+        removed
+    }
+
+    /// Subscribe to announcement changes
+    pub fn subscribe(&self) -> broadcast::Receiver<AnnouncementNotice> {
+        self.notices.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::AnnouncementSeverity;
+    use chrono::Duration;
+
+    fn announcement(title: &str, starts_in: i64, ends_in: Option<i64>) -> Announcement {
+        let now = Utc::now();
+        Announcement {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            message: "Details".to_string(),
+            severity: AnnouncementSeverity::Info,
+            starts_at: now + Duration::minutes(starts_in),
+            ends_at: ends_in.map(|m| now + Duration::minutes(m)),
+            created_by: "admin@example.org".to_string(),
+            created_at: now,
+        }
+    }
+
+    #[test]
+    fn test_current_skips_scheduled_and_expired() {
+        let board = AnnouncementBoard::new();
+        board.load(vec![
+            announcement("Now", -5, None),
+            announcement("Later", 60, Some(120)),
+            announcement("Over", -60, Some(-1)),
+        ]);
+        let titles: Vec<String> = board.current().into_iter().map(|a| a.title).collect();
+        assert_eq!(titles, ["Now"]);
+        assert_eq!(board.announcements.read().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_publish_and_withdraw_broadcast_notices() {
+        let board = AnnouncementBoard::new();
+        let mut notices = board.subscribe();
+
+        let deployed = announcement("New DDA binary deployed", 0, None);
+        board.publish(deployed.clone());
+        assert_eq!(
+            notices.try_recv().unwrap(),
+            AnnouncementNotice::Published(deployed.clone())
+        );
+        assert_eq!(board.current(), std::slice::from_ref(&deployed));
+
+        assert!(board.withdraw(deployed.id));
+        assert_eq!(
+            notices.try_recv().unwrap(),
+            AnnouncementNotice::Withdrawn(deployed.id)
+        );
+        assert!(!board.withdraw(deployed.id));
+        assert!(notices.try_recv().is_err());
+    }
+}
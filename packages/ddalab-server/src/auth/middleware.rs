@@ -1,6 +1,6 @@
 use axum::{
     extract::{ConnectInfo, Request, State},
-    http::{header, StatusCode},
+    http::{header, HeaderMap, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
     Json,
@@ -10,7 +10,11 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use tracing::warn;
 
-use crate::auth::session::{AuthRateLimiter, SessionManager};
+use crate::auth::session::{AuthRateLimiter, MfaLevel, SessionManager};
+use crate::auth::tokens::ApiTokenScope;
+use crate::auth::webauthn::ChallengeStore;
+use crate::metrics::{AuthFailure, ServerMetrics};
+use crate::storage::UserId;
 use crate::sync::verify_psk;
 
 /// Authentication state shared with middleware
@@ -20,6 +24,10 @@ pub struct AuthState {
     pub rate_limiter: AuthRateLimiter,
     pub broker_password_hash: String,
     pub require_auth: bool,
+    /// Outstanding WebAuthn challenges
+    pub webauthn_challenges: ChallengeStore,
+    /// Counts failed authentications
+    pub metrics: ServerMetrics,
 }
 
 impl AuthState {
@@ -29,13 +37,84 @@ impl AuthState {
             rate_limiter: AuthRateLimiter::default(), // 10 attempts per minute
             broker_password_hash: crate::sync::hash_psk(broker_password),
             require_auth,
+            webauthn_challenges: ChallengeStore::default(),
+            metrics: ServerMetrics::default(),
         }
     }
 
+    /// Count failed authentications in shared metrics
+    pub fn with_metrics(mut self, metrics: ServerMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     /// Verify broker password
     pub fn verify_broker_password(&self, password: &str) -> bool {
         verify_psk(password, &self.broker_password_hash)
     }
+
+    /// The user behind a request's session or access token
+    ///
+    /// Handlers resolve their caller here rather than relying on
+    /// [`auth_middleware`], which lets everything through when
+    /// authentication is not required. Access token scopes are enforced
+    /// for every route by [`api_token_scope_middleware`] before handlers
+    /// run.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<UserId, SessionRejection> {
+        let token = bearer_token(headers).ok_or(SessionRejection::Missing)?;
+        self.session_manager
+            .validate_token(token)
+            .map(|(_, user_id)| user_id)
+            .ok_or(SessionRejection::Invalid)
+    }
+
+    /// Refuse a request made with an access token whose scope does not
+    /// cover it; session tokens and anonymous requests pass
+    pub fn check_api_token_scope(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<(), ApiTokenScope> {
+        let Some(scope) = bearer_token(headers)
+            .and_then(|token| self.session_manager.api_token_scope(token))
+        else {
+            return Ok(());
+        };
+        if scope.allows(method, path) {
+            Ok(())
+        } else {
+            Err(scope)
+        }
+    }
+}
+
+/// Why a request has no valid session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionRejection {
+    /// No `Authorization` header
+    Missing,
+    /// Unknown or expired token
+    Invalid,
+}
+
+impl SessionRejection {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::Missing => "Missing authorization",
+            Self::Invalid => "Invalid session",
+        }
+    }
+}
+
+/// The token in a request's `Authorization` header
+///
+/// Both `Bearer <token>` and a raw token are accepted.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .map(|v| v.strip_prefix("Bearer ").unwrap_or(v))
 }
 
 /// Authentication middleware for protected routes
@@ -55,31 +134,21 @@ pub async fn auth_middleware(
     // Check if rate limited BEFORE any auth attempt
     if state.rate_limiter.is_rate_limited(client_ip) {
         warn!("Rate limited request from {}", client_ip);
+        state.metrics.auth_failure(AuthFailure::RateLimited);
         return rate_limited_response();
     }
 
-    // Extract Authorization header
-    let auth_header = request
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok());
-
-    let Some(auth_value) = auth_header else {
+    let Some(token) = bearer_token(request.headers()) else {
         // Record failure for missing auth header
         state.rate_limiter.record_failure(client_ip);
+        state.metrics.auth_failure(AuthFailure::MissingCredentials);
         return unauthorized_response("Missing Authorization header");
     };
 
-    // Support both "Bearer <token>" and raw token
-    let token = if auth_value.starts_with("Bearer ") {
-        &auth_value[7..]
-    } else {
-        auth_value
-    };
-
     // Validate token
     if state.session_manager.validate_token(token).is_none() {
         // Record failure for invalid token
+        state.metrics.auth_failure(AuthFailure::InvalidSession);
         let is_limited = state.rate_limiter.record_failure(client_ip);
         if is_limited {
             warn!("IP {} is now rate limited after failed auth", client_ip);
@@ -89,6 +158,67 @@ pub async fn auth_middleware(
 
     // Clear rate limit on successful auth
     state.rate_limiter.clear(client_ip);
+    // Activity keeps the session alive up to its absolute lifetime
+    state.session_manager.touch(token);
+
+    next.run(request).await
+}
+
+/// Scope gate for access tokens on every route
+///
+/// Unlike [`auth_middleware`] this also runs when authentication is not
+/// required, since handlers still resolve a token to its user then.
+pub async fn api_token_scope_middleware(
+    State(state): State<Arc<AuthState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(scope) =
+        state.check_api_token_scope(request.method(), request.uri().path(), request.headers())
+    {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Forbidden",
+                "code": "TOKEN_SCOPE",
+                "message": format!("This {} token does not allow this request", scope.as_str())
+            })),
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Second-factor gate for sensitive routes (admin, data egress)
+///
+/// Runs after [`auth_middleware`]. Sessions of users flagged to require MFA
+/// pass only once they completed a passkey assertion or used a recovery code.
+pub async fn require_mfa_middleware(
+    State(state): State<Arc<AuthState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.require_auth {
+        return next.run(request).await;
+    }
+
+    let token = bearer_token(request.headers());
+    let Some((required, level)) = token.and_then(|t| state.session_manager.mfa_status(t)) else {
+        return unauthorized_response("Invalid or expired session token");
+    };
+
+    if required && level < MfaLevel::MultiFactor {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "Forbidden",
+                "code": "MFA_REQUIRED",
+                "message": "Verify with a passkey or recovery code to access this resource"
+            })),
+        )
+            .into_response();
+    }
 
     next.run(request).await
 }
@@ -128,6 +258,14 @@ pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::auth::tokens::{hash_api_token, ApiTokenGrant, API_TOKEN_PREFIX};
+    use axum::{
+        body::Body,
+        middleware,
+        routing::{get, post},
+        Router,
+    };
+    use tower::ServiceExt;
 
     #[test]
     fn test_constant_time_eq() {
@@ -144,4 +282,128 @@ mod tests {
         assert!(auth_state.verify_broker_password("test_password"));
         assert!(!auth_state.verify_broker_password("wrong_password"));
     }
+
+    #[test]
+    fn test_authenticate() {
+        let session_manager = SessionManager::new(3600);
+        let (token, _) = session_manager.create_session("alice@lab.org".to_string(), None);
+        let auth_state = AuthState::new(session_manager, "test_password", true);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            auth_state.authenticate(&headers),
+            Err(SessionRejection::Missing)
+        );
+
+        headers.insert(header::AUTHORIZATION, "Bearer nope".parse().unwrap());
+        assert_eq!(
+            auth_state.authenticate(&headers),
+            Err(SessionRejection::Invalid)
+        );
+
+        for value in [format!("Bearer {}", token), token] {
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            assert_eq!(
+                auth_state.authenticate(&headers).as_deref(),
+                Ok("alice@lab.org")
+            );
+        }
+    }
+
+    async fn get_share_info(state: &Arc<AuthState>, token: &str) -> StatusCode {
+        let app = Router::new()
+            .route("/api/shares/{token}", get(|| async { "info" }))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_mfa_middleware,
+            ));
+        let request = Request::builder()
+            .uri("/api/shares/abc")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_password_only_session_rejected_when_mfa_required() {
+        let session_manager = SessionManager::new(3600);
+        let (flagged, _) = session_manager.create_session("alice@lab.org".to_string(), None);
+        session_manager.set_mfa_required(&flagged, true);
+        let (unflagged, _) = session_manager.create_session("bob@lab.org".to_string(), None);
+        let state = Arc::new(AuthState::new(session_manager, "test_password", true));
+
+        assert_eq!(
+            get_share_info(&state, &flagged).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(get_share_info(&state, &unflagged).await, StatusCode::OK);
+        assert_eq!(
+            get_share_info(&state, "unknown").await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        state.session_manager.elevate_to_multi_factor(&flagged);
+        assert_eq!(get_share_info(&state, &flagged).await, StatusCode::OK);
+    }
+
+    async fn call_with_token(state: &Arc<AuthState>, method: Method, token: &str) -> StatusCode {
+        let app = Router::new()
+            .route("/api/jobs/submit", post(|| async { "submitted" }))
+            .route("/api/jobs", get(|| async { "jobs" }))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                api_token_scope_middleware,
+            ));
+        let uri = if method == Method::POST {
+            "/api/jobs/submit"
+        } else {
+            "/api/jobs"
+        };
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_token_scope_enforced_without_required_auth() {
+        let session_manager = SessionManager::new(3600);
+        let (session, _) = session_manager.create_session("alice@lab.org".to_string(), None);
+        let mut tokens = Vec::new();
+        for scope in [ApiTokenScope::Read, ApiTokenScope::Submit] {
+            let token = format!("{}{}", API_TOKEN_PREFIX, scope.as_str());
+            session_manager.register_api_token(
+                hash_api_token(&token),
+                ApiTokenGrant {
+                    token_id: uuid::Uuid::new_v4(),
+                    user_id: "alice@lab.org".to_string(),
+                    scope,
+                    expires_at: None,
+                    mfa_required: false,
+                    mfa_level: MfaLevel::Password,
+                },
+            );
+            tokens.push(token);
+        }
+        let state = Arc::new(AuthState::new(session_manager, "test_password", false));
+        let [read, submit] = [&tokens[0], &tokens[1]];
+
+        // The token still names its user to handlers
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, format!("Bearer {}", read).parse().unwrap());
+        assert_eq!(state.authenticate(&headers).as_deref(), Ok("alice@lab.org"));
+
+        assert_eq!(
+            call_with_token(&state, Method::POST, read).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(call_with_token(&state, Method::GET, read).await, StatusCode::OK);
+        assert_eq!(call_with_token(&state, Method::POST, submit).await, StatusCode::OK);
+        assert_eq!(call_with_token(&state, Method::POST, &session).await, StatusCode::OK);
+        assert_eq!(call_with_token(&state, Method::POST, "unknown").await, StatusCode::OK);
+    }
 }
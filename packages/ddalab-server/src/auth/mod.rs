@@ -1,7 +1,20 @@
 mod middleware;
 mod password;
+mod recovery;
 mod session;
+mod tokens;
+pub mod webauthn;
 
-pub use middleware::{auth_middleware, AuthState};
+pub use middleware::{
+    api_token_scope_middleware, auth_middleware, bearer_token, constant_time_eq,
+    require_mfa_middleware, AuthState, SessionRejection,
+};
 pub use password::{hash_password, verify_password};
-pub use session::{AuthRateLimiter, SessionManager, generate_session_token};
+pub use recovery::{find_recovery_code, generate_recovery_codes, hash_recovery_code, RECOVERY_CODE_COUNT};
+pub use session::{
+    AuthRateLimiter, MfaLevel, RefreshError, RefreshedSession, SessionManager, SessionSummary,
+    generate_session_token, DEFAULT_ABSOLUTE_TIMEOUT_SECONDS,
+};
+pub use tokens::{
+    generate_api_token, hash_api_token, ApiTokenGrant, ApiTokenScope, API_TOKEN_PREFIX,
+};
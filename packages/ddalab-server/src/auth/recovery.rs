@@ -0,0 +1,74 @@
+//! One-time recovery codes for users who lose their passkeys
+//!
+//! Codes are shown once and stored as Argon2 hashes like passwords. They are
+//! compared after dropping separators and case, so `ABCDE-FGHJK`, `abcde fghjk`
+//! and `abcdefghjk` are the same code.
+
+use rand::Rng;
+
+use super::password::{hash_password, verify_password, PasswordError};
+
+/// Codes issued per batch; issuing a new batch invalidates the previous one
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// No 0/O, 1/I/L: codes are often read off paper
+const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// A fresh batch of codes formatted `xxxxx-xxxxx`
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::rngs::OsRng;
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Hash a code for storage
+pub fn hash_recovery_code(code: &str) -> Result<String, PasswordError> {
+    hash_password(&normalize(code))
+}
+
+/// Index of the hash `code` matches, if any
+pub fn find_recovery_code(code: &str, hashes: &[String]) -> Option<usize> {
+    let code = normalize(code);
+    if code.is_empty() {
+        return None;
+    }
+    hashes
+        .iter()
+        .position(|hash| verify_password(&code, hash).unwrap_or(false))
+}
+
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_codes_match_loosely_typed_input() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes
+            .iter()
+            .all(|code| code.len() == 11 && code.as_bytes()[5] == b'-'));
+
+        let hashes: Vec<String> = codes[..2]
+            .iter()
+            .map(|code| hash_recovery_code(code).unwrap())
+            .collect();
+        let typed = codes[1].to_uppercase().replace('-', " ");
+        assert_eq!(find_recovery_code(&typed, &hashes), Some(1));
+        assert_eq!(find_recovery_code(&codes[2], &hashes), None);
+        assert_eq!(find_recovery_code("--", &hashes), None);
+    }
+}
@@ -1,11 +1,13 @@
 use chrono::{Duration, Utc};
 use parking_lot::RwLock;
 use rand::RngCore;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::sync::Arc;
 use uuid::Uuid;
 
+use super::tokens::{hash_api_token, ApiTokenGrant, ApiTokenScope, API_TOKEN_PREFIX};
 use crate::crypto::EncryptionKey;
 use crate::storage::{UserId, UserSession};
 
@@ -16,12 +18,30 @@ pub fn generate_session_token() -> String {
     hex::encode(bytes)
 }
 
+/// Absolute session lifetime when none is configured
+pub const DEFAULT_ABSOLUTE_TIMEOUT_SECONDS: u64 = 12 * 3600;
+
 /// In-memory session manager for active sessions
+///
+/// A session token stays valid while it is used at least every
+/// `timeout_seconds` (each authenticated request slides the deadline), but
+/// never past `absolute_timeout_seconds` from login. Until then the refresh
+/// token issued with the session trades for a new session token and refresh
+/// token, even after an idle expiry. Each refresh token works once; presenting
+/// one again revokes the session, since only a stolen copy would be reused.
 pub struct SessionManager {
     /// Active sessions indexed by session token
     sessions: Arc<RwLock<HashMap<String, ActiveSession>>>,
-    /// Session timeout in seconds
+    /// Current refresh token -> session token
+    refresh_tokens: Arc<RwLock<HashMap<String, String>>>,
+    /// Refresh tokens already exchanged -> their session
+    retired_refresh_tokens: Arc<RwLock<HashMap<String, Uuid>>>,
+    /// Live access tokens indexed by token hash
+    api_tokens: Arc<RwLock<HashMap<String, ApiTokenGrant>>>,
+    /// Idle session timeout in seconds
     timeout_seconds: u64,
+    /// Session lifetime from login in seconds, across refreshes
+    absolute_timeout_seconds: u64,
 }
 
 /// Active session with encryption key
@@ -32,22 +52,86 @@ pub struct ActiveSession {
     pub encryption_key: Option<EncryptionKey>,
     pub created_at: chrono::DateTime<Utc>,
     pub expires_at: chrono::DateTime<Utc>,
+    /// No refresh extends the session past this
+    pub absolute_expires_at: chrono::DateTime<Utc>,
+    pub refresh_token: String,
+    /// Sensitive routes need a second factor (copied from the user at login)
+    pub mfa_required: bool,
+    pub mfa_level: MfaLevel,
+}
+
+/// A session as administrators see it, without its tokens
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub session_id: Uuid,
+    pub user_id: UserId,
+    pub created_at: chrono::DateTime<Utc>,
+    /// Idle deadline; the session can still be refreshed after it
+    pub expires_at: chrono::DateTime<Utc>,
+    pub absolute_expires_at: chrono::DateTime<Utc>,
+    pub mfa_level: MfaLevel,
+}
+
+/// Tokens issued by a successful refresh
+#[derive(Debug, Clone)]
+pub struct RefreshedSession {
+    pub session_token: String,
+    pub refresh_token: String,
+    pub expires_in_seconds: u64,
+    pub refresh_expires_in_seconds: u64,
+}
+
+/// Why a refresh token was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RefreshError {
+    #[error("Unknown refresh token")]
+    Unknown,
+    #[error("Session reached its maximum lifetime")]
+    Expired,
+    #[error("Refresh token was already used; session revoked")]
+    Reused,
+}
+
+/// Strongest authentication a session has completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MfaLevel {
+    Password,
+    /// Password plus a passkey assertion or recovery code
+    MultiFactor,
 }
 
 impl SessionManager {
     pub fn new(timeout_seconds: u64) -> Self {
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            refresh_tokens: Arc::new(RwLock::new(HashMap::new())),
+            retired_refresh_tokens: Arc::new(RwLock::new(HashMap::new())),
+            api_tokens: Arc::new(RwLock::new(HashMap::new())),
             timeout_seconds,
+            absolute_timeout_seconds: DEFAULT_ABSOLUTE_TIMEOUT_SECONDS.max(timeout_seconds),
         }
     }
 
+    /// Cap sessions, refreshes included, at this many seconds from login
+    pub fn with_absolute_timeout(mut self, absolute_timeout_seconds: u64) -> Self {
+        self.absolute_timeout_seconds = absolute_timeout_seconds;
+        self
+    }
+
+    /// Next idle deadline for a session, capped by its absolute lifetime
+    fn idle_deadline(&self, absolute_expires_at: chrono::DateTime<Utc>) -> chrono::DateTime<Utc> {
+        (Utc::now() + Duration::seconds(self.timeout_seconds as i64)).min(absolute_expires_at)
+    }
+
     /// Create a new session
     pub fn create_session(&self, user_id: UserId, encryption_key: Option<EncryptionKey>) -> (String, UserSession) {
         let session_id = Uuid::new_v4();
         let token = generate_session_token();
+        let refresh_token = generate_session_token();
         let now = Utc::now();
-        let expires_at = now + Duration::seconds(self.timeout_seconds as i64);
+        let absolute_expires_at = now + Duration::seconds(self.absolute_timeout_seconds as i64);
+        let expires_at = self.idle_deadline(absolute_expires_at);
 
         let active_session = ActiveSession {
             session_id,
@@ -56,6 +140,10 @@ impl SessionManager {
             encryption_key,
             created_at: now,
             expires_at,
+            absolute_expires_at,
+            refresh_token: refresh_token.clone(),
+            mfa_required: false,
+            mfa_level: MfaLevel::Password,
         };
 
         let user_session = UserSession {
@@ -69,12 +157,17 @@ impl SessionManager {
         };
 
         self.sessions.write().insert(token.clone(), active_session);
+        self.refresh_tokens.write().insert(refresh_token, token.clone());
 
         (token, user_session)
     }
 
-    /// Validate a session token and return the session if valid
+    /// Validate a session or access token and return the session (or
+    /// access token) ID and user if valid
     pub fn validate_token(&self, token: &str) -> Option<(Uuid, UserId)> {
+        if let Some(grant) = self.api_token(token) {
+            return Some((grant.token_id, grant.user_id));
+        }
         let sessions = self.sessions.read();
         sessions.get(token).and_then(|session| {
             if Utc::now() < session.expires_at {
@@ -85,6 +178,71 @@ impl SessionManager {
         })
     }
 
+    /// Refresh token of a valid session and seconds until it stops working
+    pub fn refresh_token(&self, token: &str) -> Option<(String, u64)> {
+        let sessions = self.sessions.read();
+        sessions
+            .get(token)
+            .filter(|session| Utc::now() < session.expires_at)
+            .map(|session| {
+                (
+                    session.refresh_token.clone(),
+                    seconds_until(session.absolute_expires_at),
+                )
+            })
+    }
+
+    /// Push a valid session's idle deadline out again after it was used
+    pub fn touch(&self, token: &str) {
+        let mut sessions = self.sessions.write();
+        if let Some(session) = sessions.get_mut(token) {
+            if Utc::now() < session.expires_at {
+                session.expires_at = self.idle_deadline(session.absolute_expires_at);
+            }
+        }
+    }
+
+    /// Trade a refresh token for a new session token and refresh token
+    ///
+    /// The session keeps its ID, user, keys and second-factor state; the old
+    /// tokens stop working.
+    pub fn refresh(&self, refresh_token: &str) -> Result<RefreshedSession, RefreshError> {
+        let mut sessions = self.sessions.write();
+        let mut refresh_tokens = self.refresh_tokens.write();
+        let mut retired = self.retired_refresh_tokens.write();
+
+        if let Some(session_id) = retired.get(refresh_token).copied() {
+            sessions.retain(|_, session| session.session_id != session_id);
+            refresh_tokens.retain(|_, token| sessions.contains_key(token));
+            return Err(RefreshError::Reused);
+        }
+
+        let old_token = refresh_tokens
+            .remove(refresh_token)
+            .ok_or(RefreshError::Unknown)?;
+        let mut session = sessions.remove(&old_token).ok_or(RefreshError::Unknown)?;
+        if Utc::now() >= session.absolute_expires_at {
+            return Err(RefreshError::Expired);
+        }
+
+        retired.insert(refresh_token.to_string(), session.session_id);
+        let session_token = generate_session_token();
+        let new_refresh_token = generate_session_token();
+        session.token = session_token.clone();
+        session.refresh_token = new_refresh_token.clone();
+        session.expires_at = self.idle_deadline(session.absolute_expires_at);
+
+        let refreshed = RefreshedSession {
+            session_token: session_token.clone(),
+            refresh_token: new_refresh_token.clone(),
+            expires_in_seconds: seconds_until(session.expires_at),
+            refresh_expires_in_seconds: seconds_until(session.absolute_expires_at),
+        };
+        refresh_tokens.insert(new_refresh_token, session_token.clone());
+        sessions.insert(session_token, session);
+        Ok(refreshed)
+    }
+
     /// Get encryption key for a session
     pub fn get_encryption_key(&self, token: &str) -> Option<EncryptionKey> {
         let sessions = self.sessions.read();
@@ -108,22 +266,143 @@ impl SessionManager {
         }
     }
 
+    /// Second-factor requirement and level of a valid session
+    pub fn mfa_status(&self, token: &str) -> Option<(bool, MfaLevel)> {
+        if let Some(grant) = self.api_token(token) {
+            return Some((grant.mfa_required, grant.mfa_level));
+        }
+        let sessions = self.sessions.read();
+        sessions
+            .get(token)
+            .filter(|session| Utc::now() < session.expires_at)
+            .map(|session| (session.mfa_required, session.mfa_level))
+    }
+
+    /// Mark whether sensitive routes need a second factor in this session
+    pub fn set_mfa_required(&self, token: &str, required: bool) -> bool {
+        let mut sessions = self.sessions.write();
+        if let Some(session) = sessions.get_mut(token) {
+            session.mfa_required = required;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Record a completed second factor for a session
+    pub fn elevate_to_multi_factor(&self, token: &str) -> bool {
+        let mut sessions = self.sessions.write();
+        if let Some(session) = sessions.get_mut(token) {
+            session.mfa_level = MfaLevel::MultiFactor;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Accept an access token with the given hash from now on
+    pub fn register_api_token(&self, token_hash: String, grant: ApiTokenGrant) {
+        self.api_tokens.write().insert(token_hash, grant);
+    }
+
+    /// Stop accepting an access token
+    pub fn revoke_api_token(&self, token_id: Uuid) {
+        self.api_tokens
+            .write()
+            .retain(|_, grant| grant.token_id != token_id);
+    }
+
+    /// The grant behind a live access token
+    fn api_token(&self, token: &str) -> Option<ApiTokenGrant> {
+        if !token.starts_with(API_TOKEN_PREFIX) {
+            return None;
+        }
+        self.api_tokens
+            .read()
+            .get(&hash_api_token(token))
+            .filter(|grant| !grant.is_expired())
+            .cloned()
+    }
+
+    /// Scope of an access token; `None` for session tokens, which are
+    /// not limited
+    pub fn api_token_scope(&self, token: &str) -> Option<ApiTokenScope> {
+        self.api_token(token).map(|grant| grant.scope)
+    }
+
     /// Revoke a session
     pub fn revoke_session(&self, token: &str) {
-        self.sessions.write().remove(token);
+        if let Some(session) = self.sessions.write().remove(token) {
+            self.refresh_tokens.write().remove(&session.refresh_token);
+        }
     }
 
     /// Revoke all sessions for a user
     pub fn revoke_user_sessions(&self, user_id: &UserId) {
-        self.sessions.write().retain(|_, session| session.user_id != *user_id);
+        let mut sessions = self.sessions.write();
+        sessions.retain(|_, session| session.user_id != *user_id);
+        self.refresh_tokens
+            .write()
+            .retain(|_, token| sessions.contains_key(token));
+    }
+
+    /// Revoke one session by its ID, returning whose it was
+    pub fn revoke_session_by_id(&self, session_id: Uuid) -> Option<UserId> {
+        let (token, user_id) = self
+            .sessions
+            .read()
+            .iter()
+            .find(|(_, session)| session.session_id == session_id)
+            .map(|(token, session)| (token.clone(), session.user_id.clone()))?;
+        self.revoke_session(&token);
+        Some(user_id)
+    }
+
+    /// Stop accepting a user's access tokens until they are registered again
+    pub fn revoke_user_api_tokens(&self, user_id: &UserId) {
+        self.api_tokens
+            .write()
+            .retain(|_, grant| grant.user_id != *user_id);
+    }
+
+    /// Sessions that can still be used or refreshed, optionally only one
+    /// user's, newest first
+    pub fn list_sessions(&self, user_id: Option<&str>) -> Vec<SessionSummary> {
+        let now = Utc::now();
+        let mut sessions: Vec<SessionSummary> = self
+            .sessions
+            .read()
+            .values()
+            .filter(|session| session.absolute_expires_at > now)
+            .filter(|session| user_id.is_none_or(|user_id| session.user_id == user_id))
+            .map(|session| SessionSummary {
+                session_id: session.session_id,
+                user_id: session.user_id.clone(),
+                created_at: session.created_at,
+                expires_at: session.expires_at,
+                absolute_expires_at: session.absolute_expires_at,
+                mfa_level: session.mfa_level,
+            })
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at));
+        sessions
     }
 
-    /// Clean up expired sessions
+    /// Clean up sessions past their absolute lifetime
+    ///
+    /// Idle-expired sessions are kept until then so they can be refreshed.
     pub fn cleanup_expired(&self) -> usize {
         let now = Utc::now();
         let mut sessions = self.sessions.write();
         let before = sessions.len();
-        sessions.retain(|_, session| session.expires_at > now);
+        sessions.retain(|_, session| session.absolute_expires_at > now);
+        self.refresh_tokens
+            .write()
+            .retain(|_, token| sessions.contains_key(token));
+        self.retired_refresh_tokens
+            .write()
+            .retain(|_, session_id| sessions.values().any(|s| s.session_id == *session_id));
+        self.api_tokens.write().retain(|_, grant| !grant.is_expired());
         before - sessions.len()
     }
 
@@ -137,11 +416,19 @@ impl Clone for SessionManager {
     fn clone(&self) -> Self {
         Self {
             sessions: Arc::clone(&self.sessions),
+            refresh_tokens: Arc::clone(&self.refresh_tokens),
+            retired_refresh_tokens: Arc::clone(&self.retired_refresh_tokens),
+            api_tokens: Arc::clone(&self.api_tokens),
             timeout_seconds: self.timeout_seconds,
+            absolute_timeout_seconds: self.absolute_timeout_seconds,
         }
     }
 }
 
+fn seconds_until(at: chrono::DateTime<Utc>) -> u64 {
+    (at - Utc::now()).num_seconds().max(0) as u64
+}
+
 /// Rate limiter for authentication attempts
 /// Uses a sliding window algorithm to track failed attempts per IP
 pub struct AuthRateLimiter {
@@ -169,7 +456,7 @@ impl AuthRateLimiter {
         let window_start = now - Duration::seconds(self.window_seconds);
 
         let mut attempts = self.attempts.write();
-        let ip_attempts = attempts.entry(ip).or_insert_with(Vec::new);
+        let ip_attempts = attempts.entry(ip).or_default();
 
         // Remove old attempts outside the window
         ip_attempts.retain(|ts| *ts > window_start);
@@ -271,6 +558,77 @@ mod tests {
         assert!(manager.validate_token(&token).is_none());
     }
 
+    #[test]
+    fn test_list_and_revoke_sessions_by_id() {
+        let manager = SessionManager::new(3600);
+        let (alice, alice_session) = manager.create_session("alice".to_string(), None);
+        let (bob, _) = manager.create_session("bob".to_string(), None);
+
+        assert_eq!(manager.list_sessions(None).len(), 2);
+        let listed = manager.list_sessions(Some("alice"));
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].session_id, alice_session.session_id);
+
+        assert_eq!(
+            manager.revoke_session_by_id(alice_session.session_id),
+            Some("alice".to_string())
+        );
+        assert!(manager.validate_token(&alice).is_none());
+        assert!(manager.validate_token(&bob).is_some());
+        assert_eq!(manager.revoke_session_by_id(alice_session.session_id), None);
+    }
+
+    #[test]
+    fn test_mfa_markers() {
+        let manager = SessionManager::new(3600);
+        let (token, _) = manager.create_session("user".to_string(), None);
+        assert_eq!(manager.mfa_status(&token), Some((false, MfaLevel::Password)));
+
+        assert!(manager.set_mfa_required(&token, true));
+        assert!(manager.elevate_to_multi_factor(&token));
+        assert_eq!(manager.mfa_status(&token), Some((true, MfaLevel::MultiFactor)));
+        assert!(!manager.elevate_to_multi_factor("invalid_token"));
+        assert_eq!(manager.mfa_status("invalid_token"), None);
+    }
+
+    #[test]
+    fn test_refresh_rotates_tokens_and_detects_reuse() {
+        let manager = SessionManager::new(3600);
+        let (token, _) = manager.create_session("user".to_string(), None);
+        assert!(manager.set_mfa_required(&token, true));
+        let (refresh_token, _) = manager.refresh_token(&token).unwrap();
+
+        let refreshed = manager.refresh(&refresh_token).unwrap();
+        assert!(manager.validate_token(&token).is_none());
+        assert_eq!(
+            manager.validate_token(&refreshed.session_token).unwrap().1,
+            "user"
+        );
+        assert_eq!(
+            manager.mfa_status(&refreshed.session_token),
+            Some((true, MfaLevel::Password))
+        );
+        assert!(refreshed.expires_in_seconds <= 3600);
+
+        // Replaying the exchanged refresh token ends the session
+        assert_eq!(manager.refresh(&refresh_token).unwrap_err(), RefreshError::Reused);
+        assert!(manager.validate_token(&refreshed.session_token).is_none());
+        assert_eq!(
+            manager.refresh(&refreshed.refresh_token).unwrap_err(),
+            RefreshError::Unknown
+        );
+    }
+
+    #[test]
+    fn test_absolute_timeout_caps_sessions() {
+        let manager = SessionManager::new(3600).with_absolute_timeout(0);
+        let (token, _) = manager.create_session("user".to_string(), None);
+        assert!(manager.validate_token(&token).is_none());
+
+        let refresh_token = manager.refresh_tokens.read().keys().next().unwrap().clone();
+        assert_eq!(manager.refresh(&refresh_token).unwrap_err(), RefreshError::Expired);
+    }
+
     #[test]
     fn test_encryption_key() {
         let manager = SessionManager::new(3600);
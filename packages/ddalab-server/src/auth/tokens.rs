@@ -0,0 +1,129 @@
+//! Long-lived personal access tokens for scripts and CI pipelines
+//!
+//! A token stands in for a session token in the `Authorization` header. Only
+//! its SHA-256 hash is stored: tokens carry 256 random bits, so unlike
+//! passwords they need no slow hash, and the hash doubles as the lookup key.
+//! Each token has a scope limiting what it may call.
+
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::session::MfaLevel;
+use crate::storage::UserId;
+
+/// Marks a bearer value as an access token rather than a session token
+pub const API_TOKEN_PREFIX: &str = "ddalab_pat_";
+
+/// What an access token may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiTokenScope {
+    /// Read-only requests outside the admin API
+    Read,
+    /// Read, plus submitting and cancelling jobs
+    Submit,
+    /// Everything the token's user may do
+    Admin,
+}
+
+impl ApiTokenScope {
+    /// Whether a request with this method and path is within the scope
+    pub fn allows(&self, method: &Method, path: &str) -> bool {
+        if *self == ApiTokenScope::Admin {
+            return true;
+        }
+        if path.starts_with("/api/admin") {
+            return false;
+        }
+        if method == Method::GET || method == Method::HEAD {
+            return true;
+        }
+        *self == ApiTokenScope::Submit && method == Method::POST && path.starts_with("/api/jobs")
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenScope::Read => "read",
+            ApiTokenScope::Submit => "submit",
+            ApiTokenScope::Admin => "admin",
+        }
+    }
+}
+
+impl std::str::FromStr for ApiTokenScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(ApiTokenScope::Read),
+            "submit" => Ok(ApiTokenScope::Submit),
+            "admin" => Ok(ApiTokenScope::Admin),
+            other => Err(format!("Unknown token scope '{}'", other)),
+        }
+    }
+}
+
+/// What a valid access token grants, kept in memory for every live token
+#[derive(Debug, Clone)]
+pub struct ApiTokenGrant {
+    pub token_id: Uuid,
+    pub user_id: UserId,
+    pub scope: ApiTokenScope,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Copied from the session that created the token
+    pub mfa_required: bool,
+    pub mfa_level: MfaLevel,
+}
+
+impl ApiTokenGrant {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Utc::now())
+    }
+}
+
+/// Generate a new access token
+pub fn generate_api_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    format!("{}{}", API_TOKEN_PREFIX, hex::encode(bytes))
+}
+
+/// Hash an access token for storage and lookup
+pub fn hash_api_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes_limit_methods_and_admin_paths() {
+        let read = ApiTokenScope::Read;
+        assert!(read.allows(&Method::GET, "/api/jobs"));
+        assert!(!read.allows(&Method::POST, "/api/jobs/submit"));
+        assert!(!read.allows(&Method::GET, "/api/admin/egress"));
+
+        let submit = ApiTokenScope::Submit;
+        assert!(submit.allows(&Method::POST, "/api/jobs/submit"));
+        assert!(submit.allows(&Method::POST, "/api/jobs/upload"));
+        assert!(!submit.allows(&Method::DELETE, "/api/shares/abc"));
+        assert!(!submit.allows(&Method::POST, "/api/admin/jobs/x/priority"));
+
+        assert!(ApiTokenScope::Admin.allows(&Method::POST, "/api/admin/maintenance"));
+        assert_eq!("submit".parse::<ApiTokenScope>(), Ok(ApiTokenScope::Submit));
+    }
+
+    #[test]
+    fn test_tokens_are_prefixed_and_hashed() {
+        let token = generate_api_token();
+        assert!(token.starts_with(API_TOKEN_PREFIX));
+        assert_ne!(token, generate_api_token());
+        assert_eq!(hash_api_token(&token), hash_api_token(&token));
+        assert_eq!(hash_api_token(&token).len(), 64);
+    }
+}
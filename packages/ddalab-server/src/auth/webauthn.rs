@@ -0,0 +1,546 @@
+//! WebAuthn (passkey) ceremonies for the second factor
+//!
+//! The server is a relying party for ES256 credentials, the algorithm every
+//! platform authenticator and security key supports. Attestation is not
+//! requested, so a registered key is trusted as far as the session that
+//! registered it; the sign counter is checked on every assertion to catch
+//! cloned authenticators.
+
+use base64::engine::general_purpose::{URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use ciborium::value::Value as CborValue;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use parking_lot::RwLock;
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// COSE algorithm identifier of ES256 (ECDSA P-256 with SHA-256)
+const COSE_ALG_ES256: i128 = -7;
+/// How long a client has to answer a challenge
+const CHALLENGE_TTL_SECONDS: i64 = 300;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// Relying party settings, from `WEBAUTHN_RP_ID`, `WEBAUTHN_RP_NAME` and
+/// `WEBAUTHN_ORIGINS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebAuthnConfig {
+    /// Domain credentials are scoped to, e.g. `ddalab.example.edu`
+    pub rp_id: String,
+    /// Name authenticators show when registering
+    pub rp_name: String,
+    /// Origins ceremonies may come from
+    pub origins: Vec<String>,
+}
+
+impl WebAuthnConfig {
+    /// None unless `WEBAUTHN_RP_ID` is set; origins default to `https://<rp id>`
+    pub fn from_env(default_name: &str) -> Option<Self> {
+        let rp_id = std::env::var("WEBAUTHN_RP_ID")
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())?;
+        let origins: Vec<String> = std::env::var("WEBAUTHN_ORIGINS")
+            .map(|s| {
+                s.split(',')
+                    .map(|origin| origin.trim().trim_end_matches('/').to_string())
+                    .filter(|origin| !origin.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Some(Self {
+            rp_name: std::env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| default_name.to_string()),
+            origins: if origins.is_empty() {
+                vec![format!("https://{}", rp_id)]
+            } else {
+                origins
+            },
+            rp_id,
+        })
+    }
+}
+
+/// Why a ceremony was rejected
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum WebAuthnError {
+    #[error("Malformed {0}")]
+    Malformed(&'static str),
+    #[error("Unexpected ceremony type '{0}'")]
+    WrongType(String),
+    #[error("Challenge does not match")]
+    ChallengeMismatch,
+    #[error("Origin '{0}' is not allowed")]
+    OriginNotAllowed(String),
+    #[error("Credential is scoped to another relying party")]
+    RpIdMismatch,
+    #[error("User presence was not confirmed")]
+    UserNotPresent,
+    #[error("Only ES256 credentials are supported")]
+    UnsupportedAlgorithm,
+    #[error("Signature verification failed")]
+    BadSignature,
+    #[error("Sign counter went backwards; the authenticator may be cloned")]
+    CounterRegression,
+}
+
+/// A credential created by a registration ceremony
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisteredKey {
+    pub credential_id: Vec<u8>,
+    /// SEC1 uncompressed P-256 point
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+/// Ceremony a challenge was issued for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ceremony {
+    Registration,
+    Authentication,
+}
+
+/// A challenge and when it expires
+type PendingChallenge = (Vec<u8>, DateTime<Utc>);
+
+/// Outstanding challenges, one per user and ceremony
+#[derive(Clone, Default)]
+pub struct ChallengeStore {
+    pending: Arc<RwLock<HashMap<(String, Ceremony), PendingChallenge>>>,
+}
+
+impl ChallengeStore {
+    /// Issue a fresh challenge, replacing any earlier one for the same ceremony
+    pub fn issue(&self, user: &str, ceremony: Ceremony) -> Vec<u8> {
+        let mut challenge = vec![0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut challenge);
+        let expires_at = Utc::now() + Duration::seconds(CHALLENGE_TTL_SECONDS);
+        let mut pending = self.pending.write();
+        pending.retain(|_, (_, expires)| *expires > Utc::now());
+        pending.insert(
+            (user.to_string(), ceremony),
+            (challenge.clone(), expires_at),
+        );
+        challenge
+    }
+
+    /// Consume the challenge of a ceremony; each can be answered once
+    pub fn take(&self, user: &str, ceremony: Ceremony) -> Option<Vec<u8>> {
+        self.pending
+            .write()
+            .remove(&(user.to_string(), ceremony))
+            .filter(|(_, expires)| *expires > Utc::now())
+            .map(|(challenge, _)| challenge)
+    }
+}
+
+/// `PublicKeyCredentialCreationOptions` for `navigator.credentials.create`
+pub fn creation_options(
+    config: &WebAuthnConfig,
+    user_id: &[u8],
+    user_name: &str,
+    display_name: &str,
+    challenge: &[u8],
+    exclude: &[Vec<u8>],
+) -> Value {
+    json!({
+        "publicKey": {
+            "rp": { "id": config.rp_id, "name": config.rp_name },
+            "user": {
+                "id": encode(user_id),
+                "name": user_name,
+                "displayName": display_name,
+            },
+            "challenge": encode(challenge),
+            "pubKeyCredParams": [{ "type": "public-key", "alg": COSE_ALG_ES256 }],
+            "timeout": CHALLENGE_TTL_SECONDS * 1000,
+            "excludeCredentials": descriptors(exclude),
+            "authenticatorSelection": {
+                "residentKey": "preferred",
+                "userVerification": "preferred",
+            },
+            "attestation": "none",
+        }
+    })
+}
+
+/// `PublicKeyCredentialRequestOptions` for `navigator.credentials.get`
+pub fn request_options(config: &WebAuthnConfig, challenge: &[u8], allow: &[Vec<u8>]) -> Value {
+    json!({
+        "publicKey": {
+            "rpId": config.rp_id,
+            "challenge": encode(challenge),
+            "timeout": CHALLENGE_TTL_SECONDS * 1000,
+            "allowCredentials": descriptors(allow),
+            "userVerification": "preferred",
+        }
+    })
+}
+
+fn descriptors(credential_ids: &[Vec<u8>]) -> Vec<Value> {
+    credential_ids
+        .iter()
+        .map(|id| json!({ "type": "public-key", "id": encode(id) }))
+        .collect()
+}
+
+/// Verify the response to [`creation_options`] and extract the new key
+pub fn verify_registration(
+    config: &WebAuthnConfig,
+    challenge: &[u8],
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+) -> Result<RegisteredKey, WebAuthnError> {
+    check_client_data(config, "webauthn.create", challenge, client_data_json)?;
+
+    let attestation: CborValue = ciborium::de::from_reader(attestation_object)
+        .map_err(|_| WebAuthnError::Malformed("attestation object"))?;
+    let auth_data = cbor_map_get(&attestation, &CborValue::Text("authData".to_string()))
+        .and_then(CborValue::as_bytes)
+        .ok_or(WebAuthnError::Malformed("attestation object"))?;
+    let data = AuthenticatorData::parse(auth_data)?;
+    data.check(config)?;
+    if data.flags & FLAG_ATTESTED_CREDENTIAL == 0 {
+        return Err(WebAuthnError::Malformed("authenticator data"));
+    }
+
+    // aaguid (16) | credential id length (2) | credential id | COSE key
+    let rest = data.rest;
+    if rest.len() < 18 {
+        return Err(WebAuthnError::Malformed("attested credential data"));
+    }
+    let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+    let credential_id = rest
+        .get(18..18 + id_len)
+        .ok_or(WebAuthnError::Malformed("attested credential data"))?
+        .to_vec();
+    let cose_key: CborValue = ciborium::de::from_reader(&rest[18 + id_len..])
+        .map_err(|_| WebAuthnError::Malformed("credential public key"))?;
+    let public_key = cose_key_to_sec1(&cose_key)?;
+
+    Ok(RegisteredKey {
+        credential_id,
+        public_key,
+        sign_count: data.sign_count,
+    })
+}
+
+/// Verify the response to [`request_options`] against a stored key and
+/// return the authenticator's new sign counter
+pub fn verify_assertion(
+    config: &WebAuthnConfig,
+    challenge: &[u8],
+    public_key: &[u8],
+    stored_sign_count: u32,
+    client_data_json: &[u8],
+    authenticator_data: &[u8],
+    signature: &[u8],
+) -> Result<u32, WebAuthnError> {
+    check_client_data(config, "webauthn.get", challenge, client_data_json)?;
+    let data = AuthenticatorData::parse(authenticator_data)?;
+    data.check(config)?;
+
+    let key = VerifyingKey::from_sec1_bytes(public_key)
+        .map_err(|_| WebAuthnError::Malformed("stored public key"))?;
+    let signature =
+        Signature::from_der(signature).map_err(|_| WebAuthnError::Malformed("signature"))?;
+    let mut signed = authenticator_data.to_vec();
+    signed.extend_from_slice(&Sha256::digest(client_data_json));
+    key.verify(&signed, &signature)
+        .map_err(|_| WebAuthnError::BadSignature)?;
+
+    // Authenticators without a counter always report 0
+    if (data.sign_count != 0 || stored_sign_count != 0) && data.sign_count <= stored_sign_count {
+        return Err(WebAuthnError::CounterRegression);
+    }
+    Ok(data.sign_count)
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+    #[serde(rename = "crossOrigin", default)]
+    cross_origin: bool,
+}
+
+fn check_client_data(
+    config: &WebAuthnConfig,
+    kind: &str,
+    challenge: &[u8],
+    client_data_json: &[u8],
+) -> Result<(), WebAuthnError> {
+    let client_data: ClientData = serde_json::from_slice(client_data_json)
+        .map_err(|_| WebAuthnError::Malformed("client data"))?;
+    if client_data.kind != kind {
+        return Err(WebAuthnError::WrongType(client_data.kind));
+    }
+    let answered = decode(&client_data.challenge).ok_or(WebAuthnError::Malformed("client data"))?;
+    if !super::middleware::constant_time_eq(&answered, challenge) {
+        return Err(WebAuthnError::ChallengeMismatch);
+    }
+    if client_data.cross_origin || !config.origins.contains(&client_data.origin) {
+        return Err(WebAuthnError::OriginNotAllowed(client_data.origin));
+    }
+    Ok(())
+}
+
+struct AuthenticatorData<'a> {
+    rp_id_hash: &'a [u8],
+    flags: u8,
+    sign_count: u32,
+    /// Attested credential data and extensions
+    rest: &'a [u8],
+}
+
+impl<'a> AuthenticatorData<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self, WebAuthnError> {
+        if bytes.len() < 37 {
+            return Err(WebAuthnError::Malformed("authenticator data"));
+        }
+        Ok(Self {
+            rp_id_hash: &bytes[..32],
+            flags: bytes[32],
+            sign_count: u32::from_be_bytes([bytes[33], bytes[34], bytes[35], bytes[36]]),
+            rest: &bytes[37..],
+        })
+    }
+
+    fn check(&self, config: &WebAuthnConfig) -> Result<(), WebAuthnError> {
+        if self.rp_id_hash != Sha256::digest(config.rp_id.as_bytes()).as_slice() {
+            return Err(WebAuthnError::RpIdMismatch);
+        }
+        if self.flags & FLAG_USER_PRESENT == 0 {
+            return Err(WebAuthnError::UserNotPresent);
+        }
+        Ok(())
+    }
+}
+
+/// Convert an EC2 P-256 COSE key to a SEC1 uncompressed point
+fn cose_key_to_sec1(key: &CborValue) -> Result<Vec<u8>, WebAuthnError> {
+    let int = |label: i64| {
+        cbor_map_get(key, &CborValue::Integer(label.into()))
+            .and_then(CborValue::as_integer)
+            .map(i128::from)
+    };
+    let coordinate = |label: i64| {
+        cbor_map_get(key, &CborValue::Integer(label.into()))
+            .and_then(CborValue::as_bytes)
+            .filter(|bytes| bytes.len() == 32)
+    };
+
+    // kty 2 = EC2, crv 1 = P-256
+    if int(1) != Some(2) || int(3) != Some(COSE_ALG_ES256) || int(-1) != Some(1) {
+        return Err(WebAuthnError::UnsupportedAlgorithm);
+    }
+    let (Some(x), Some(y)) = (coordinate(-2), coordinate(-3)) else {
+        return Err(WebAuthnError::Malformed("credential public key"));
+    };
+    let mut point = Vec::with_capacity(65);
+    point.push(0x04);
+    point.extend_from_slice(x);
+    point.extend_from_slice(y);
+    VerifyingKey::from_sec1_bytes(&point)
+        .map_err(|_| WebAuthnError::Malformed("credential public key"))?;
+    Ok(point)
+}
+
+fn cbor_map_get<'a>(map: &'a CborValue, key: &CborValue) -> Option<&'a CborValue> {
+    map.as_map()?.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+/// Base64url without padding, as WebAuthn JSON uses
+pub fn encode(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Base64url, with or without padding
+pub fn decode(value: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(value)
+        .or_else(|_| URL_SAFE.decode(value))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::SigningKey;
+
+    const ORIGIN: &str = "https://ddalab.example.edu";
+
+    fn config() -> WebAuthnConfig {
+        WebAuthnConfig {
+            rp_id: "ddalab.example.edu".to_string(),
+            rp_name: "DDALAB".to_string(),
+            origins: vec![ORIGIN.to_string()],
+        }
+    }
+
+    fn client_data(kind: &str, challenge: &[u8], origin: &str) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "type": kind,
+            "challenge": encode(challenge),
+            "origin": origin,
+        }))
+        .unwrap()
+    }
+
+    fn auth_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    /// An attestation object with "none" attestation for `key`
+    fn attestation_object(key: &SigningKey, credential_id: &[u8]) -> Vec<u8> {
+        let point = key.verifying_key().to_encoded_point(false);
+        let cose_key = CborValue::Map(vec![
+            (CborValue::Integer(1.into()), CborValue::Integer(2.into())),
+            (
+                CborValue::Integer(3.into()),
+                CborValue::Integer((-7).into()),
+            ),
+            (
+                CborValue::Integer((-1).into()),
+                CborValue::Integer(1.into()),
+            ),
+            (
+                CborValue::Integer((-2).into()),
+                CborValue::Bytes(point.x().unwrap().to_vec()),
+            ),
+            (
+                CborValue::Integer((-3).into()),
+                CborValue::Bytes(point.y().unwrap().to_vec()),
+            ),
+        ]);
+        let mut data = auth_data("ddalab.example.edu", 0x41, 0);
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+        data.extend_from_slice(credential_id);
+        ciborium::ser::into_writer(&cose_key, &mut data).unwrap();
+
+        let object = CborValue::Map(vec![
+            (
+                CborValue::Text("fmt".into()),
+                CborValue::Text("none".into()),
+            ),
+            (CborValue::Text("attStmt".into()), CborValue::Map(vec![])),
+            (CborValue::Text("authData".into()), CborValue::Bytes(data)),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&object, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn assertion(
+        key: &SigningKey,
+        challenge: &[u8],
+        sign_count: u32,
+    ) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let client_data = client_data("webauthn.get", challenge, ORIGIN);
+        let data = auth_data("ddalab.example.edu", 0x05, sign_count);
+        let mut signed = data.clone();
+        signed.extend_from_slice(&Sha256::digest(&client_data));
+        let signature: Signature = key.sign(&signed);
+        (client_data, data, signature.to_der().as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_registration_and_assertion() {
+        let config = config();
+        let key = SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let challenge = [7u8; 32];
+
+        let registered = verify_registration(
+            &config,
+            &challenge,
+            &client_data("webauthn.create", &challenge, ORIGIN),
+            &attestation_object(&key, b"cred-1"),
+        )
+        .unwrap();
+        assert_eq!(registered.credential_id, b"cred-1");
+        assert_eq!(registered.public_key.len(), 65);
+
+        let (client_data, data, signature) = assertion(&key, &challenge, 5);
+        let verify = |stored: u32, signature: &[u8]| {
+            verify_assertion(
+                &config,
+                &challenge,
+                &registered.public_key,
+                stored,
+                &client_data,
+                &data,
+                signature,
+            )
+        };
+        assert_eq!(verify(0, &signature), Ok(5));
+        assert_eq!(verify(5, &signature), Err(WebAuthnError::CounterRegression));
+
+        let other = SigningKey::from_slice(&[0x22; 32]).unwrap();
+        let (_, _, forged) = assertion(&other, &challenge, 5);
+        assert_eq!(verify(0, &forged), Err(WebAuthnError::BadSignature));
+    }
+
+    #[test]
+    fn test_rejects_foreign_ceremonies() {
+        let config = config();
+        let key = SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let attestation = attestation_object(&key, b"cred-1");
+        let register = |client_data: Vec<u8>| {
+            verify_registration(&config, &[7u8; 32], &client_data, &attestation)
+        };
+
+        assert_eq!(
+            register(client_data("webauthn.create", &[8u8; 32], ORIGIN)),
+            Err(WebAuthnError::ChallengeMismatch)
+        );
+        assert!(matches!(
+            register(client_data(
+                "webauthn.create",
+                &[7u8; 32],
+                "https://evil.example"
+            )),
+            Err(WebAuthnError::OriginNotAllowed(_))
+        ));
+        assert!(matches!(
+            register(client_data("webauthn.get", &[7u8; 32], ORIGIN)),
+            Err(WebAuthnError::WrongType(_))
+        ));
+
+        let other_rp = WebAuthnConfig {
+            rp_id: "example.org".to_string(),
+            origins: vec![ORIGIN.to_string()],
+            ..config.clone()
+        };
+        assert_eq!(
+            verify_registration(
+                &other_rp,
+                &[7u8; 32],
+                &client_data("webauthn.create", &[7u8; 32], ORIGIN),
+                &attestation
+            ),
+            Err(WebAuthnError::RpIdMismatch)
+        );
+
+        let store = ChallengeStore::default();
+        let challenge = store.issue("a@example.edu", Ceremony::Authentication);
+        assert_eq!(store.take("a@example.edu", Ceremony::Registration), None);
+        assert_eq!(
+            store.take("a@example.edu", Ceremony::Authentication),
+            Some(challenge)
+        );
+        assert_eq!(store.take("a@example.edu", Ceremony::Authentication), None);
+    }
+}
@@ -1,8 +1,10 @@
+mod user_csv;
 mod users;
 
 pub use users::UserCommands;
 
 use clap::{Parser, Subcommand};
+use uuid::Uuid;
 
 /// DDALAB Server - Institutional DDA Analysis Server
 #[derive(Parser)]
@@ -32,4 +34,19 @@ pub enum Commands {
         #[arg(short, long)]
         user: Option<String>,
     },
+
+    /// Run DDA jobs for a server with DDA_EXECUTOR=workers (needs EXECUTOR_TOKEN)
+    WorkerNode {
+        /// URL of the server handing out runs
+        #[arg(long)]
+        server: String,
+
+        /// Execute only this run, then exit (used by Kubernetes Jobs)
+        #[arg(long)]
+        run: Option<Uuid>,
+
+        /// Name shown in the server's logs; the host name by default
+        #[arg(long)]
+        name: Option<String>,
+    },
 }
@@ -0,0 +1,253 @@
+use crate::storage::TeamRole;
+
+/// Columns written by `user export` (and accepted by `user import`)
+pub const EXPORT_HEADER: &[&str] = &["email", "name", "role", "teams", "active"];
+
+/// Team membership as written in the `teams` column (`name` or `name:admin`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeamAssignment {
+    pub team: String,
+    pub role: TeamRole,
+}
+
+/// One user row of an import/export CSV
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserRecord {
+    pub email: String,
+    pub name: String,
+    pub is_admin: bool,
+    pub teams: Vec<TeamAssignment>,
+    /// Initial password; generated during import when absent
+    pub password: Option<String>,
+    pub active: bool,
+}
+
+/// Parse a users CSV with a header row.
+///
+/// `email` and `name` columns are required; `role` (`admin`/`user`),
+/// `teams` (`;`-separated), `password` and `active` are optional.
+pub fn parse_users_csv(text: &str) -> Result<Vec<UserRecord>, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let (_, header_line) = lines.next().ok_or("CSV file is empty")?;
+    let header: Vec<String> = split_csv_line(header_line)
+        .map_err(|e| format!("line 1: {}", e))?
+        .into_iter()
+        .map(|h| h.trim().to_ascii_lowercase())
+        .collect();
+    let column = |name: &str| header.iter().position(|h| h == name);
+
+    let email_col = column("email").ok_or("missing required column 'email'")?;
+    let name_col = column("name").ok_or("missing required column 'name'")?;
+    let role_col = column("role");
+    let teams_col = column("teams");
+    let password_col = column("password");
+    let active_col = column("active");
+
+    let mut records = Vec::new();
+    for (idx, line) in lines {
+        let line_no = idx + 1;
+        let fields = split_csv_line(line).map_err(|e| format!("line {}: {}", line_no, e))?;
+        let field = |col: Option<usize>| {
+            col.and_then(|c| fields.get(c))
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+        };
+
+        let email =
+            field(Some(email_col)).ok_or_else(|| format!("line {}: email is empty", line_no))?;
+        if !email.contains('@') {
+            return Err(format!("line {}: invalid email '{}'", line_no, email));
+        }
+        let name =
+            field(Some(name_col)).ok_or_else(|| format!("line {}: name is empty", line_no))?;
+
+        let is_admin = match field(role_col).map(|r| r.to_ascii_lowercase()).as_deref() {
+            None | Some("user") | Some("member") => false,
+            Some("admin") => true,
+            Some(other) => {
+                return Err(format!("line {}: unknown role '{}'", line_no, other));
+            }
+        };
+
+        let teams = field(teams_col)
+            .map(|value| parse_teams(value).map_err(|e| format!("line {}: {}", line_no, e)))
+            .transpose()?
+            .unwrap_or_default();
+
+        let active = match field(active_col).map(|a| a.to_ascii_lowercase()).as_deref() {
+            None | Some("true") | Some("yes") | Some("1") => true,
+            Some("false") | Some("no") | Some("0") => false,
+            Some(other) => {
+                return Err(format!(
+                    "line {}: invalid active value '{}'",
+                    line_no, other
+                ));
+            }
+        };
+
+        records.push(UserRecord {
+            email: email.to_string(),
+            name: name.to_string(),
+            is_admin,
+            teams,
+            password: field(password_col).map(str::to_string),
+            active,
+        });
+    }
+
+    Ok(records)
+}
+
+/// Render users as CSV using [`EXPORT_HEADER`] (passwords are never exported)
+pub fn write_users_csv(records: &[UserRecord]) -> String {
+    let mut out = EXPORT_HEADER.join(",");
+    out.push('\n');
+    for record in records {
+        let teams = record
+            .teams
+            .iter()
+            .map(|t| match t.role {
+                TeamRole::Admin => format!("{}:admin", t.team),
+                TeamRole::Member => t.team.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(";");
+        let row = [
+            record.email.as_str(),
+            record.name.as_str(),
+            if record.is_admin { "admin" } else { "user" },
+            teams.as_str(),
+            if record.active { "true" } else { "false" },
+        ];
+        out.push_str(
+            &row.iter()
+                .map(|v| csv_escape(v))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+        out.push('\n');
+    }
+    out
+}
+
+fn parse_teams(value: &str) -> Result<Vec<TeamAssignment>, String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|entry| match entry.rsplit_once(':') {
+            Some((team, role)) => {
+                let role = match role.trim().to_ascii_lowercase().as_str() {
+                    "admin" => TeamRole::Admin,
+                    "member" => TeamRole::Member,
+                    other => return Err(format!("unknown team role '{}'", other)),
+                };
+                Ok(TeamAssignment {
+                    team: team.trim().to_string(),
+                    role,
+                })
+            }
+            None => Ok(TeamAssignment {
+                team: entry.to_string(),
+                role: TeamRole::Member,
+            }),
+        })
+        .collect()
+}
+
+/// Split one CSV line, honoring double-quoted fields with `""` escapes
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("unterminated quoted field".to_string());
+    }
+    fields.push(current);
+    Ok(fields)
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_minimal_and_full_rows() {
+        let csv = "email,name,role,teams,password\n\
+                   ada@example.edu,Ada Lovelace,,,\n\
+                   grace@example.edu,\"Hopper, Grace\",admin,Course A:admin;Lab,s3cret\n";
+        let records = parse_users_csv(csv).unwrap();
+        assert_eq!(records.len(), 2);
+
+        assert!(!records[0].is_admin);
+        assert!(records[0].teams.is_empty());
+        assert_eq!(records[0].password, None);
+        assert!(records[0].active);
+
+        assert_eq!(records[1].name, "Hopper, Grace");
+        assert!(records[1].is_admin);
+        assert_eq!(
+            records[1].teams,
+            vec![
+                TeamAssignment {
+                    team: "Course A".to_string(),
+                    role: TeamRole::Admin
+                },
+                TeamAssignment {
+                    team: "Lab".to_string(),
+                    role: TeamRole::Member
+                },
+            ]
+        );
+        assert_eq!(records[1].password.as_deref(), Some("s3cret"));
+    }
+
+    #[test]
+    fn test_parse_reports_line_numbers() {
+        let err = parse_users_csv("email,name,role\na@b.c,A,superuser\n").unwrap_err();
+        assert!(err.contains("line 2"));
+        assert!(parse_users_csv("name\nA\n").unwrap_err().contains("email"));
+    }
+
+    #[test]
+    fn test_export_round_trips() {
+        let records = vec![UserRecord {
+            email: "grace@example.edu".to_string(),
+            name: "Hopper, Grace".to_string(),
+            is_admin: true,
+            teams: vec![TeamAssignment {
+                team: "Course A".to_string(),
+                role: TeamRole::Admin,
+            }],
+            password: None,
+            active: false,
+        }];
+        let csv = write_users_csv(&records);
+        assert_eq!(parse_users_csv(&csv).unwrap(), records);
+    }
+}
@@ -1,9 +1,17 @@
+use chrono::Utc;
 use clap::Subcommand;
 use rand::Rng;
 use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use uuid::Uuid;
 
+use super::user_csv::{self, TeamAssignment, UserRecord};
 use crate::auth::hash_password;
-use crate::storage::{CreateUser, PostgresUserStore, UserStore};
+use crate::storage::{
+    CreateUser, MfaStore, PostgresMfaStore, PostgresTeamStore, PostgresUserStore, TeamMember,
+    TeamRole, TeamStore, UserStore,
+};
 
 /// User management subcommands
 #[derive(Subcommand)]
@@ -75,6 +83,52 @@ pub enum UserCommands {
         email: String,
     },
 
+    /// Require a passkey or recovery code for admin and data egress routes
+    RequireMfa {
+        /// User's email address
+        #[arg(short, long)]
+        email: String,
+    },
+
+    /// Stop requiring a second factor for a user
+    WaiveMfa {
+        /// User's email address
+        #[arg(short, long)]
+        email: String,
+    },
+
+    /// Remove a user's passkeys and recovery codes (e.g. after losing them)
+    ResetMfa {
+        /// User's email address
+        #[arg(short, long)]
+        email: String,
+    },
+
+    /// Import users from a CSV file (columns: email, name[, role, teams, password, active])
+    Import {
+        /// Path to the CSV file
+        file: PathBuf,
+
+        /// Institution to assign users to (required when the CSV lists teams)
+        #[arg(long)]
+        institution: Option<Uuid>,
+
+        /// Require a password change at first login, even for supplied passwords
+        #[arg(long)]
+        force_reset: bool,
+
+        /// Validate the file without creating any users
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Export all users to CSV (passwords are not exported)
+    Export {
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
     /// Delete a user
     Delete {
         /// User's email address
@@ -90,7 +144,7 @@ pub enum UserCommands {
 impl UserCommands {
     /// Execute the user command
     pub async fn execute(self, pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
-        let user_store = PostgresUserStore::new(pool);
+        let user_store = PostgresUserStore::new(pool.clone());
 
         match self {
             UserCommands::Create {
@@ -110,6 +164,7 @@ impl UserCommands {
                         password_hash,
                         is_admin: admin,
                         institution_id: None,
+                        password_reset_required: false,
                     })
                     .await?;
 
@@ -158,6 +213,7 @@ impl UserCommands {
                 println!("  Name:       {}", user.display_name);
                 println!("  Admin:      {}", if user.is_admin { "Yes" } else { "No" });
                 println!("  Active:     {}", if user.is_active { "Yes" } else { "No" });
+                println!("  MFA:        {}", if user.mfa_required { "Required" } else { "Optional" });
                 println!("  Created:    {}", user.created_at);
                 println!(
                     "  Last Login: {}",
@@ -211,6 +267,54 @@ impl UserCommands {
                 println!("✅ Admin privileges revoked from {}.", email);
             }
 
+            UserCommands::RequireMfa { email } => {
+                let user = user_store.get_user_by_email(&email).await?;
+                user_store.set_mfa_required(user.id, true).await?;
+
+                println!("✅ {} must verify with a passkey or recovery code for admin and data egress routes.", email);
+                println!("   Takes effect at the user's next login.");
+            }
+
+            UserCommands::WaiveMfa { email } => {
+                let user = user_store.get_user_by_email(&email).await?;
+                user_store.set_mfa_required(user.id, false).await?;
+
+                println!("✅ A second factor is no longer required for {}.", email);
+            }
+
+            UserCommands::ResetMfa { email } => {
+                let user = user_store.get_user_by_email(&email).await?;
+                PostgresMfaStore::new(pool).reset_user(user.id).await?;
+
+                println!("✅ Passkeys and recovery codes of {} have been removed.", email);
+                if user.mfa_required {
+                    println!("   MFA is still required; the user can enroll a new passkey after logging in.");
+                }
+            }
+
+            UserCommands::Import {
+                file,
+                institution,
+                force_reset,
+                dry_run,
+            } => {
+                let team_store = PostgresTeamStore::new(pool);
+                import_users(&user_store, &team_store, &file, institution, force_reset, dry_run)
+                    .await?;
+            }
+
+            UserCommands::Export { output } => {
+                let team_store = PostgresTeamStore::new(pool);
+                let csv = export_users(&user_store, &team_store).await?;
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, csv)?;
+                        eprintln!("✅ Users exported to {}", path.display());
+                    }
+                    None => print!("{}", csv),
+                }
+            }
+
             UserCommands::Delete { email, force } => {
                 let user = user_store.get_user_by_email(&email).await?;
 
@@ -233,6 +337,149 @@ impl UserCommands {
     }
 }
 
+/// Create users listed in a CSV file, skipping emails that already exist
+async fn import_users(
+    user_store: &PostgresUserStore,
+    team_store: &PostgresTeamStore,
+    file: &PathBuf,
+    institution: Option<Uuid>,
+    force_reset: bool,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(file)?;
+    let records = user_csv::parse_users_csv(&text)?;
+
+    // Validate the whole file before creating anyone
+    let mut seen = HashSet::new();
+    for record in &records {
+        if !seen.insert(record.email.to_ascii_lowercase()) {
+            return Err(format!("Duplicate email in CSV: {}", record.email).into());
+        }
+    }
+
+    let mut team_ids = HashMap::new();
+    if records.iter().any(|r| !r.teams.is_empty()) {
+        let institution =
+            institution.ok_or("--institution is required when the CSV assigns teams")?;
+        for team in team_store.list_institution_teams(institution).await? {
+            team_ids.insert(team.name, team.id);
+        }
+        let unknown: Vec<&str> = records
+            .iter()
+            .flat_map(|r| r.teams.iter())
+            .map(|t| t.team.as_str())
+            .filter(|name| !team_ids.contains_key(*name))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!("Unknown team(s) in CSV: {}", unknown.join(", ")).into());
+        }
+    }
+
+    if dry_run {
+        println!("✅ {} user(s) validated, nothing imported (dry run).", records.len());
+        return Ok(());
+    }
+
+    let mut created = 0usize;
+    let mut skipped = 0usize;
+    let mut credentials = Vec::new();
+
+    for record in records {
+        if user_store.get_user_by_email(&record.email).await.is_ok() {
+            println!("⏭️  {} already exists, skipping", record.email);
+            skipped += 1;
+            continue;
+        }
+
+        // Generated passwords are always one-time
+        let generated = record.password.is_none();
+        let password = record.password.unwrap_or_else(generate_secure_password);
+        let password_hash =
+            hash_password(&password).map_err(|e| format!("Failed to hash password: {}", e))?;
+
+        let user = user_store
+            .create_user(CreateUser {
+                email: record.email.clone(),
+                display_name: record.name,
+                password_hash,
+                is_admin: record.is_admin,
+                institution_id: institution,
+                password_reset_required: force_reset || generated,
+            })
+            .await?;
+
+        if !record.active {
+            user_store.set_user_active(user.id, false).await?;
+        }
+
+        for assignment in &record.teams {
+            team_store
+                .add_team_member(&TeamMember {
+                    team_id: team_ids[&assignment.team],
+                    user_id: user.id,
+                    role: assignment.role,
+                    added_at: Utc::now(),
+                    added_by: None,
+                })
+                .await?;
+        }
+
+        if generated {
+            credentials.push((user.email, password));
+        }
+        created += 1;
+    }
+
+    println!("✅ Imported {} user(s), skipped {} existing.", created, skipped);
+    if !credentials.is_empty() {
+        println!();
+        println!("{:<40} {:<20}", "Email", "Initial Password");
+        println!("{}", "-".repeat(61));
+        for (email, password) in credentials {
+            println!("{:<40} {:<20}", email, password);
+        }
+        println!();
+        println!("⚠️  Please securely share these credentials; users must change them at first login.");
+    }
+
+    Ok(())
+}
+
+/// Render all users and their team memberships as CSV
+async fn export_users(
+    user_store: &PostgresUserStore,
+    team_store: &PostgresTeamStore,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut records = Vec::new();
+    for user in user_store.list_users().await? {
+        let mut teams = Vec::new();
+        for team in team_store.list_user_teams(user.id).await? {
+            let role = if team_store.is_team_admin(team.id, user.id).await? {
+                TeamRole::Admin
+            } else {
+                TeamRole::Member
+            };
+            teams.push(TeamAssignment {
+                team: team.name,
+                role,
+            });
+        }
+
+        records.push(UserRecord {
+            email: user.email,
+            name: user.display_name,
+            is_admin: user.is_admin,
+            teams,
+            password: None,
+            active: user.is_active,
+        });
+    }
+
+    Ok(user_csv::write_users_csv(&records))
+}
+
 /// Generate a secure random password
 fn generate_secure_password() -> String {
     const CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghjkmnpqrstuvwxyz23456789!@#$%&*";
@@ -1,5 +1,20 @@
 use std::env;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::auth::webauthn::WebAuthnConfig;
+use crate::auth::DEFAULT_ABSOLUTE_TIMEOUT_SECONDS;
+use crate::crypto::StorageKey;
+use crate::jobs::{
+    parse_worker_pools, RemoteConfig, ResourceLimits, RunPolicy, UserQuota, WorkerPool,
+};
+use crate::retention::{ArchiveHook, RetentionPolicy, DEFAULT_GRACE_DAYS};
+use crate::tls::TlsConfig;
+use crate::middleware::{parse_origin_policies, OriginPolicy};
+use crate::notifications::SmtpConfig;
+use crate::relay::RelayConfig;
+use crate::sync::{FederationConfig, OfflineQueuePolicy};
+use crate::transfer::{OffPeakWindow, TransferPolicy};
 
 /// Server configuration loaded from environment variables
 #[derive(Debug, Clone)]
@@ -28,10 +43,22 @@ pub struct ServerConfig {
     pub require_auth: bool,
     /// Enable application-layer encryption
     pub enable_encryption: bool,
-    /// Session timeout in seconds
+    /// Session idle timeout in seconds
     pub session_timeout_seconds: u64,
+    /// Session lifetime from login in seconds, across refreshes
+    pub session_absolute_timeout_seconds: u64,
     /// Heartbeat timeout in seconds (for stale connection cleanup)
     pub heartbeat_timeout_seconds: i64,
+    /// How long notices for offline users are kept
+    /// (`OFFLINE_MESSAGE_TTL_HOURS`) and how many per user
+    /// (`OFFLINE_QUEUE_LIMIT`)
+    pub offline_queue: OfflineQueuePolicy,
+    /// Peering with other institutions' brokers (disabled unless
+    /// `FEDERATION_NAMESPACE` is set)
+    pub federation: Option<FederationConfig>,
+    /// Share downloads through the broker for owners behind NAT (disabled
+    /// unless `RELAY_ENABLED` is set)
+    pub relay: Option<RelayConfig>,
     /// Maximum concurrent DDA jobs
     pub max_concurrent_jobs: usize,
     /// Directory for job output files
@@ -42,8 +69,51 @@ pub struct ServerConfig {
     pub max_upload_size: u64,
     /// Base directory for server-side files users can reference
     pub server_files_directory: Option<PathBuf>,
-    /// CORS allowed origins (comma-separated in env var)
-    pub cors_origins: Vec<String>,
+    /// Hide server files outside every registered dataset from everyone
+    /// but administrators
+    pub server_files_restricted: bool,
+    /// Browser origins allowed to call the API or embed the viewer: full
+    /// access for each `CORS_ORIGINS` entry, overridden per origin by
+    /// `ORIGIN_POLICIES` (e.g. "https://dash.example.org read-only embed")
+    pub origin_policies: Vec<OriginPolicy>,
+    /// Compress responses (gzip/brotli) for clients that accept it
+    pub enable_compression: bool,
+    /// Smallest response body, in bytes, worth compressing
+    pub compression_min_size: u16,
+    /// Default bandwidth cap and off-peak deferral for downloads
+    pub transfer_policy: TransferPolicy,
+    /// Timeout and retry policy for DDA executions
+    pub run_policy: RunPolicy,
+    /// Per-user limits on running and queued jobs and stored data
+    pub user_quota: UserQuota,
+    /// Estimated memory and CPUs running jobs may hold together
+    pub resource_limits: ResourceLimits,
+    /// Job classes with their own concurrency limits (`WORKER_POOLS`, e.g.
+    /// "small:4:channels=32:seconds=600,large:1"); none by default
+    pub worker_pools: Vec<WorkerPool>,
+    /// Server-wide retention of finished jobs' results and uploads
+    /// (`RETENTION_DAYS`, `RETENTION_GRACE_DAYS`); files are kept when unset
+    pub retention: Option<RetentionPolicy>,
+    /// Command run on each file before retention deletes it
+    /// (`RETENTION_ARCHIVE_COMMAND`)
+    pub retention_archive: Option<ArchiveHook>,
+    /// Days audit log entries are kept (`AUDIT_RETENTION_DAYS`); forever
+    /// when unset
+    pub audit_retention_days: Option<u32>,
+    /// Passkey second factor (disabled unless `WEBAUTHN_RP_ID` is set)
+    pub webauthn: Option<WebAuthnConfig>,
+    /// Outgoing mail for job and share notifications (disabled unless
+    /// `SMTP_HOST` is set)
+    pub smtp: Option<SmtpConfig>,
+    /// Master key encrypting uploads and results at rest (disabled unless
+    /// `STORAGE_ENCRYPTION_KEY` or `STORAGE_ENCRYPTION_KEY_FILE` is set)
+    pub storage_encryption: Option<StorageKey>,
+    /// HTTPS for the API and WebSocket (disabled unless `TLS_CERT_PATH` and
+    /// `TLS_KEY_PATH`, or `ACME_DOMAINS`, are set)
+    pub tls: Option<TlsConfig>,
+    /// DDA runs on worker nodes or Kubernetes Jobs instead of this host
+    /// (`DDA_EXECUTOR`); local when unset
+    pub remote_execution: Option<RemoteConfig>,
 }
 
 impl ServerConfig {
@@ -64,6 +134,171 @@ impl ServerConfig {
         let database_url = env::var("DATABASE_URL")
             .map_err(|_| ConfigError::MissingEnvVar("DATABASE_URL".to_string()))?;
 
+        let off_peak = env::var("TRANSFER_OFF_PEAK_HOURS")
+            .ok()
+            .map(|v| v.parse::<OffPeakWindow>())
+            .transpose()
+            .map_err(ConfigError::InvalidValue)?;
+        let transfer_policy = TransferPolicy {
+            max_bytes_per_second: env::var("TRANSFER_MAX_BYTES_PER_SECOND")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            defer_above_bytes: env::var("TRANSFER_DEFER_ABOVE_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            off_peak,
+        };
+
+        let seconds = |name: &str| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .and_then(|v| Duration::try_from_secs_f64(v).ok())
+        };
+        let defaults = RunPolicy::default();
+        let run_policy = RunPolicy {
+            timeout: seconds("DDA_TIMEOUT_SECONDS").filter(|t| !t.is_zero()),
+            max_retries: env::var("DDA_MAX_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_retries),
+            backoff: seconds("DDA_RETRY_BACKOFF_SECONDS").unwrap_or(defaults.backoff),
+            kill_grace: seconds("DDA_KILL_GRACE_SECONDS").unwrap_or(defaults.kill_grace),
+        };
+
+        let user_quota = UserQuota {
+            max_running: env::var("USER_MAX_RUNNING_JOBS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            max_queued: env::var("USER_MAX_QUEUED_JOBS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            max_disk_bytes: env::var("USER_DISK_QUOTA_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+        };
+
+        // Limits default to the host's own memory and CPUs
+        let host = ResourceLimits::from_host();
+        let resource_limits = ResourceLimits {
+            memory_bytes: env::var("JOB_MEMORY_LIMIT_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .or(host.memory_bytes),
+            cpus: env::var("JOB_CPU_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .or(host.cpus),
+        };
+
+        let worker_pools = env::var("WORKER_POOLS")
+            .ok()
+            .map(|v| parse_worker_pools(&v))
+            .transpose()
+            .map_err(ConfigError::InvalidValue)?
+            .unwrap_or_default();
+
+        let retention = env::var("RETENTION_DAYS")
+            .ok()
+            .map(|v| {
+                let invalid = || {
+                    ConfigError::InvalidValue(
+                        "RETENTION_DAYS must be a positive number of days".to_string(),
+                    )
+                };
+                let retain_days = v.trim().parse::<u32>().map_err(|_| invalid())?;
+                if retain_days == 0 {
+                    return Err(invalid());
+                }
+                let grace_days = match env::var("RETENTION_GRACE_DAYS") {
+                    Ok(v) => v.trim().parse().map_err(|_| {
+                        ConfigError::InvalidValue(
+                            "RETENTION_GRACE_DAYS must be a number of days".to_string(),
+                        )
+                    })?,
+                    Err(_) => DEFAULT_GRACE_DAYS,
+                };
+                Ok(RetentionPolicy {
+                    retain_days,
+                    grace_days,
+                })
+            })
+            .transpose()?;
+        let retention_archive = env::var("RETENTION_ARCHIVE_COMMAND")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| v.parse::<ArchiveHook>())
+            .transpose()
+            .map_err(ConfigError::InvalidValue)?;
+        let mut offline_queue = OfflineQueuePolicy::default();
+        if let Ok(v) = env::var("OFFLINE_MESSAGE_TTL_HOURS") {
+            offline_queue.ttl = match v.trim().parse::<u32>() {
+                Ok(hours) if hours > 0 => Duration::from_secs(u64::from(hours) * 3600),
+                _ => {
+                    return Err(ConfigError::InvalidValue(
+                        "OFFLINE_MESSAGE_TTL_HOURS must be a positive number of hours".to_string(),
+                    ))
+                }
+            };
+        }
+        if let Ok(v) = env::var("OFFLINE_QUEUE_LIMIT") {
+            offline_queue.limit = v.trim().parse().map_err(|_| {
+                ConfigError::InvalidValue(
+                    "OFFLINE_QUEUE_LIMIT must be a number of messages".to_string(),
+                )
+            })?;
+        }
+        let federation = FederationConfig::from_env().map_err(ConfigError::InvalidValue)?;
+        let relay = RelayConfig::from_env().map_err(ConfigError::InvalidValue)?;
+        let audit_retention_days = env::var("AUDIT_RETENTION_DAYS")
+            .ok()
+            .map(|v| match v.trim().parse::<u32>() {
+                Ok(days) if days > 0 => Ok(days),
+                _ => Err(ConfigError::InvalidValue(
+                    "AUDIT_RETENTION_DAYS must be a positive number of days".to_string(),
+                )),
+            })
+            .transpose()?;
+
+        let mut origin_policies: Vec<OriginPolicy> = env::var("CORS_ORIGINS")
+            .map(|s| {
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|origin| !origin.is_empty())
+                    .map(OriginPolicy::full)
+                    .collect()
+            })
+            .unwrap_or_else(|_| {
+                [
+                    "http://localhost:3000",
+                    "http://localhost:3003",
+                    "http://127.0.0.1:3000",
+                    "http://127.0.0.1:3003",
+                    "tauri://localhost",
+                    "https://tauri.localhost",
+                ]
+                .into_iter()
+                .map(OriginPolicy::full)
+                .collect()
+            });
+        if let Ok(value) = env::var("ORIGIN_POLICIES") {
+            for policy in parse_origin_policies(&value).map_err(ConfigError::InvalidValue)? {
+                origin_policies.retain(|existing| existing.origin != policy.origin);
+                origin_policies.push(policy);
+            }
+        }
+
+        let institution_name =
+            env::var("INSTITUTION_NAME").unwrap_or_else(|_| "DDALAB Server".to_string());
+        let webauthn = WebAuthnConfig::from_env(&institution_name);
+        let smtp = SmtpConfig::from_env().map_err(ConfigError::InvalidValue)?;
+        let storage_encryption = StorageKey::from_env().map_err(ConfigError::InvalidValue)?;
+        let data_directory = env::var("DATA_DIRECTORY")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("/app/data"));
+        let tls = TlsConfig::from_env(&data_directory).map_err(ConfigError::InvalidValue)?;
+        let remote_execution = RemoteConfig::from_env().map_err(ConfigError::InvalidValue)?;
+
         Ok(Self {
             port: env::var("DDALAB_PORT")
                 .unwrap_or_else(|_| "8080".to_string())
@@ -72,8 +307,7 @@ impl ServerConfig {
             bind_addr: env::var("DDALAB_BIND_ADDR")
                 .unwrap_or_else(|_| "0.0.0.0".to_string()),
             database_url,
-            institution_name: env::var("INSTITUTION_NAME")
-                .unwrap_or_else(|_| "DDALAB Server".to_string()),
+            institution_name,
             broker_password,
             enable_mdns: env::var("ENABLE_MDNS")
                 .map(|v| v.to_lowercase() == "true")
@@ -83,9 +317,7 @@ impl ServerConfig {
             dda_binary_path: env::var("DDA_BINARY_PATH")
                 .ok()
                 .map(PathBuf::from),
-            data_directory: env::var("DATA_DIRECTORY")
-                .map(PathBuf::from)
-                .unwrap_or_else(|_| PathBuf::from("/app/data")),
+            data_directory,
             enable_server_side_analysis: env::var("ENABLE_SERVER_SIDE_ANALYSIS")
                 .map(|v| v.to_lowercase() == "true")
                 .unwrap_or(true),
@@ -99,10 +331,17 @@ impl ServerConfig {
                 .unwrap_or_else(|_| "3600".to_string())
                 .parse()
                 .unwrap_or(3600),
+            session_absolute_timeout_seconds: env::var("SESSION_ABSOLUTE_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_ABSOLUTE_TIMEOUT_SECONDS),
             heartbeat_timeout_seconds: env::var("HEARTBEAT_TIMEOUT_SECONDS")
                 .unwrap_or_else(|_| "300".to_string())
                 .parse()
                 .unwrap_or(300),
+            offline_queue,
+            federation,
+            relay,
             max_concurrent_jobs: env::var("MAX_CONCURRENT_JOBS")
                 .unwrap_or_else(|_| "2".to_string())
                 .parse()
@@ -120,16 +359,30 @@ impl ServerConfig {
             server_files_directory: env::var("SERVER_FILES_DIRECTORY")
                 .ok()
                 .map(PathBuf::from),
-            cors_origins: env::var("CORS_ORIGINS")
-                .map(|s| s.split(',').map(|s| s.trim().to_string()).collect())
-                .unwrap_or_else(|_| vec![
-                    "http://localhost:3000".to_string(),
-                    "http://localhost:3003".to_string(),
-                    "http://127.0.0.1:3000".to_string(),
-                    "http://127.0.0.1:3003".to_string(),
-                    "tauri://localhost".to_string(),
-                    "https://tauri.localhost".to_string(),
-                ]),
+            server_files_restricted: env::var("SERVER_FILES_RESTRICTED")
+                .map(|v| v.to_lowercase() == "true")
+                .unwrap_or(false),
+            origin_policies,
+            enable_compression: env::var("ENABLE_COMPRESSION")
+                .map(|v| v.to_lowercase() == "true")
+                .unwrap_or(true),
+            compression_min_size: env::var("COMPRESSION_MIN_SIZE")
+                .unwrap_or_else(|_| "1024".to_string())
+                .parse()
+                .unwrap_or(1024),
+            transfer_policy,
+            run_policy,
+            user_quota,
+            resource_limits,
+            worker_pools,
+            retention,
+            retention_archive,
+            audit_retention_days,
+            webauthn,
+            smtp,
+            storage_encryption,
+            tls,
+            remote_execution,
         })
     }
 
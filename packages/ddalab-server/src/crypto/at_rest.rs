@@ -0,0 +1,330 @@
+//! Encryption at rest for uploaded recordings and job results
+//!
+//! Each file gets its own random key, which is stored in the file's header
+//! wrapped (AES-256-GCM) by the server's master key. The contents follow in
+//! 64 KiB chunks, each sealed with its own nonce: a random prefix, the
+//! chunk's counter and a flag marking the last chunk, so chunks cannot be
+//! reordered, dropped or the file truncated without decryption failing.
+//! Large recordings are thus processed in constant memory.
+//!
+//! Files without the header are read as they are, so encryption can be
+//! turned on for a server that already holds plaintext files.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use rand::RngCore;
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::encryption::{decrypt_payload, encrypt_payload, EncryptionKey};
+
+/// Start of every encrypted file
+const MAGIC: &[u8; 8] = b"DDALABE1";
+
+/// Plaintext bytes per chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// AES-GCM tag appended to every sealed chunk
+const TAG_SIZE: usize = 16;
+
+/// Nonce and sealed file key in the header
+const WRAPPED_KEY_SIZE: usize = 12 + 32 + TAG_SIZE;
+
+/// Random part of every chunk nonce
+const NONCE_PREFIX_SIZE: usize = 7;
+
+/// Server master key that wraps the key of every encrypted file
+#[derive(Clone)]
+pub struct StorageKey(EncryptionKey);
+
+impl std::fmt::Debug for StorageKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StorageKey(..)")
+    }
+}
+
+impl StorageKey {
+    pub fn new(key: EncryptionKey) -> Self {
+        Self(key)
+    }
+
+    /// Parse a 32-byte key given as 64 hex digits or base64
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let bytes = if value.len() == 64 {
+            hex::decode(value).ok()
+        } else {
+            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, value).ok()
+        }
+        .ok_or_else(|| "Storage encryption key must be hex or base64".to_string())?;
+        let key: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| "Storage encryption key must be 32 bytes".to_string())?;
+        Ok(Self(EncryptionKey::new(key)))
+    }
+
+    /// From `STORAGE_ENCRYPTION_KEY`, or the file `STORAGE_ENCRYPTION_KEY_FILE`
+    /// names; `None` leaves files unencrypted
+    pub fn from_env() -> Result<Option<Self>, String> {
+        if let Ok(value) = std::env::var("STORAGE_ENCRYPTION_KEY") {
+            return Self::parse(&value).map(Some);
+        }
+        match std::env::var("STORAGE_ENCRYPTION_KEY_FILE") {
+            Ok(path) => {
+                let value = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+                Self::parse(&value).map(Some)
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Encrypt everything `reader` yields into `writer`
+    pub fn encrypt(&self, mut reader: impl Read, mut writer: impl Write) -> io::Result<()> {
+        let file_key = EncryptionKey::random();
+        let (wrap_nonce, wrapped) =
+            encrypt_payload(&self.0, file_key.as_bytes()).map_err(invalid_data)?;
+        let mut prefix = [0u8; NONCE_PREFIX_SIZE];
+        rand::rngs::OsRng.fill_bytes(&mut prefix);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&wrap_nonce)?;
+        writer.write_all(&wrapped)?;
+        writer.write_all(&prefix)?;
+
+        let cipher = Aes256Gcm::new_from_slice(file_key.as_bytes()).map_err(invalid_data)?;
+        let mut chunk = vec![0u8; CHUNK_SIZE];
+        let mut counter: u32 = 0;
+        loop {
+            // Every chunk but the last is full, so a short one ends the file
+            let len = fill(&mut reader, &mut chunk)?;
+            let last = len < CHUNK_SIZE;
+            let sealed = cipher
+                .encrypt(
+                    Nonce::from_slice(&chunk_nonce(&prefix, counter, last)),
+                    &chunk[..len],
+                )
+                .map_err(invalid_data)?;
+            writer.write_all(&sealed)?;
+            if last {
+                return writer.flush();
+            }
+            counter = counter
+                .checked_add(1)
+                .ok_or_else(|| invalid_data("File too large to encrypt"))?;
+        }
+    }
+
+    /// Decrypt an encrypted file's contents from `reader` into `writer`
+    pub fn decrypt(&self, mut reader: impl Read, mut writer: impl Write) -> io::Result<()> {
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not an encrypted file"));
+        }
+        let mut wrapped = [0u8; WRAPPED_KEY_SIZE];
+        reader.read_exact(&mut wrapped)?;
+        let file_key =
+            decrypt_payload(&self.0, &wrapped[..12], &wrapped[12..]).map_err(invalid_data)?;
+        let mut prefix = [0u8; NONCE_PREFIX_SIZE];
+        reader.read_exact(&mut prefix)?;
+
+        let cipher = Aes256Gcm::new_from_slice(&file_key).map_err(invalid_data)?;
+        let mut sealed = vec![0u8; CHUNK_SIZE + TAG_SIZE];
+        let mut counter: u32 = 0;
+        loop {
+            let len = fill(&mut reader, &mut sealed)?;
+            let last = len < sealed.len();
+            let chunk = cipher
+                .decrypt(
+                    Nonce::from_slice(&chunk_nonce(&prefix, counter, last)),
+                    &sealed[..len],
+                )
+                .map_err(|_| invalid_data("Encrypted file is corrupted or truncated"))?;
+            writer.write_all(&chunk)?;
+            if last {
+                return writer.flush();
+            }
+            counter = counter
+                .checked_add(1)
+                .ok_or_else(|| invalid_data("Encrypted file is corrupted"))?;
+        }
+    }
+
+    /// Write `data` to `path` encrypted
+    pub fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.encrypt(data, BufWriter::new(File::create(path)?))
+    }
+
+    /// Replace a plaintext file with its encrypted form; files already
+    /// encrypted are left alone
+    pub fn encrypt_in_place(&self, path: &Path) -> io::Result<()> {
+        if is_encrypted(path)? {
+            return Ok(());
+        }
+        let staging = staging_path(path);
+        let result = File::create(&staging).and_then(|staged| {
+            self.encrypt(File::open(path)?, BufWriter::new(staged))?;
+            std::fs::rename(&staging, path)
+        });
+        if result.is_err() {
+            std::fs::remove_file(&staging).ok();
+        }
+        result
+    }
+
+    /// Write the plaintext of `path` to `destination`, copying files that
+    /// are not encrypted
+    pub fn decrypt_to(&self, path: &Path, destination: &Path) -> io::Result<()> {
+        if !is_encrypted(path)? {
+            return std::fs::copy(path, destination).map(|_| ());
+        }
+        self.decrypt(
+            File::open(path)?,
+            BufWriter::new(File::create(destination)?),
+        )
+    }
+}
+
+/// Whether `path` holds an encrypted file
+pub fn is_encrypted(path: &Path) -> io::Result<bool> {
+    let mut magic = [0u8; MAGIC.len()];
+    let len = fill(&mut File::open(path)?, &mut magic)?;
+    Ok(len == MAGIC.len() && &magic == MAGIC)
+}
+
+/// The plaintext of a file, which may or may not be encrypted
+pub fn read_file(key: Option<&StorageKey>, path: &Path) -> io::Result<Vec<u8>> {
+    let data = std::fs::read(path)?;
+    match key {
+        Some(key) if data.starts_with(MAGIC) => {
+            let mut plaintext = Vec::with_capacity(data.len());
+            key.decrypt(data.as_slice(), &mut plaintext)?;
+            Ok(plaintext)
+        }
+        _ => Ok(data),
+    }
+}
+
+/// Write a file, encrypted when a key is given
+pub fn write_file(key: Option<&StorageKey>, path: &Path, data: &[u8]) -> io::Result<()> {
+    match key {
+        Some(key) => key.write(path, data),
+        None => std::fs::write(path, data),
+    }
+}
+
+fn staging_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".encrypting");
+    path.with_file_name(name)
+}
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_SIZE], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_SIZE..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce
+}
+
+/// Read until `buf` is full or the input ends
+fn fill(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+fn invalid_data(e: impl ToString) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(key: &StorageKey, plaintext: &[u8]) -> Vec<u8> {
+        let mut sealed = Vec::new();
+        key.encrypt(plaintext, &mut sealed).unwrap();
+        assert!(sealed.starts_with(MAGIC));
+        let mut opened = Vec::new();
+        key.decrypt(sealed.as_slice(), &mut opened).unwrap();
+        opened
+    }
+
+    #[test]
+    fn test_roundtrip_across_chunk_boundaries() {
+        let key = StorageKey::new(EncryptionKey::random());
+        for len in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE,
+        ] {
+            let plaintext: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            assert_eq!(roundtrip(&key, &plaintext), plaintext, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_tampering_and_truncation_are_detected() {
+        let key = StorageKey::new(EncryptionKey::random());
+        let plaintext = vec![7u8; 2 * CHUNK_SIZE + 10];
+        let mut sealed = Vec::new();
+        key.encrypt(plaintext.as_slice(), &mut sealed).unwrap();
+        let decrypt = |data: &[u8], key: &StorageKey| key.decrypt(data, &mut Vec::new());
+
+        // Dropping the final chunk leaves a file of whole chunks
+        let header = MAGIC.len() + WRAPPED_KEY_SIZE + NONCE_PREFIX_SIZE;
+        let whole_chunks = header + 2 * (CHUNK_SIZE + TAG_SIZE);
+        assert!(decrypt(&sealed[..whole_chunks], &key).is_err());
+        assert!(decrypt(&sealed[..sealed.len() - 1], &key).is_err());
+
+        let mut flipped = sealed.clone();
+        flipped[header + 5] ^= 1;
+        assert!(decrypt(&flipped, &key).is_err());
+
+        let other = StorageKey::new(EncryptionKey::random());
+        assert!(decrypt(&sealed, &other).is_err());
+        assert!(decrypt(&sealed, &key).is_ok());
+    }
+
+    #[test]
+    fn test_files_and_plaintext_passthrough() {
+        let dir = std::env::temp_dir().join(format!("ddalab-at-rest-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key = StorageKey::new(EncryptionKey::random());
+        let path = dir.join("result.json");
+
+        std::fs::write(&path, b"{\"q_matrix\": []}").unwrap();
+        assert!(!is_encrypted(&path).unwrap());
+        assert_eq!(read_file(Some(&key), &path).unwrap(), b"{\"q_matrix\": []}");
+
+        key.encrypt_in_place(&path).unwrap();
+        assert!(is_encrypted(&path).unwrap());
+        assert!(!staging_path(&path).exists());
+        // A second pass does not encrypt twice
+        key.encrypt_in_place(&path).unwrap();
+        assert_eq!(read_file(Some(&key), &path).unwrap(), b"{\"q_matrix\": []}");
+
+        let copy = dir.join("copy.json");
+        key.decrypt_to(&path, &copy).unwrap();
+        assert_eq!(std::fs::read(&copy).unwrap(), b"{\"q_matrix\": []}");
+
+        assert!(StorageKey::parse(&"ab".repeat(32)).is_ok());
+        assert!(StorageKey::parse("c2hvcnQ=").is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,7 +1,14 @@
+mod at_rest;
 mod ecdh;
 mod encryption;
+mod sealed;
 mod types;
 
+pub use at_rest::{is_encrypted, read_file, write_file, StorageKey};
 pub use ecdh::{EcdhKeyPair, derive_shared_secret};
 pub use encryption::{encrypt_payload, decrypt_payload, EncryptionKey};
+pub use sealed::{
+    decode_public_key, seal, SealError, SealedPayload, ShareKeyPair, WrappedKey,
+    MAX_SEALED_CIPHERTEXT_LENGTH, MAX_SEALED_RECIPIENTS,
+};
 pub use types::{EncryptedRequest, EncryptedResponse, KeyExchangeRequest, KeyExchangeResponse};
@@ -0,0 +1,297 @@
+//! Sealed share payloads
+//!
+//! A sharing client encrypts a share's title, description and content with
+//! a random AES-256-GCM content key, then wraps that key for every
+//! recipient: an ephemeral X25519 key pair is agreed with the recipient's
+//! published share key and HKDF-SHA256 derives the key that encrypts the
+//! content key. The broker stores and forwards the sealed payload without
+//! being able to open it.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashSet;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+use super::encryption::{decrypt_payload, encrypt_payload, EncryptionError, EncryptionKey};
+
+/// Longest base64-encoded ciphertext the broker stores
+pub const MAX_SEALED_CIPHERTEXT_LENGTH: usize = 4 * 1024 * 1024;
+
+/// Most recipients one payload can be sealed for
+pub const MAX_SEALED_RECIPIENTS: usize = 1000;
+
+/// AES-GCM authentication tag length
+const TAG_LENGTH: usize = 16;
+
+/// A user's long-term key pair for opening payloads sealed for them
+///
+/// Only the public key leaves the client.
+pub struct ShareKeyPair {
+    secret: StaticSecret,
+    public_key: PublicKey,
+}
+
+impl ShareKeyPair {
+    /// Generate a new key pair
+    pub fn generate() -> Self {
+        Self::from_secret_bytes(StaticSecret::random_from_rng(OsRng).to_bytes())
+    }
+
+    /// Restore a key pair from its stored secret
+    pub fn from_secret_bytes(secret: [u8; 32]) -> Self {
+        let secret = StaticSecret::from(secret);
+        let public_key = PublicKey::from(&secret);
+        Self { secret, public_key }
+    }
+
+    /// The secret, for the client to store
+    pub fn secret_bytes(&self) -> [u8; 32] {
+        self.secret.to_bytes()
+    }
+
+    /// Get the public key bytes for publishing
+    pub fn public_key_bytes(&self) -> [u8; 32] {
+        self.public_key.to_bytes()
+    }
+}
+
+/// Content encrypted for a set of recipients
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SealedPayload {
+    /// Base64-encoded nonce (12 bytes)
+    pub nonce: String,
+    /// Base64-encoded ciphertext (includes auth tag)
+    pub ciphertext: String,
+    /// The content key, wrapped for each recipient
+    pub keys: Vec<WrappedKey>,
+}
+
+/// A payload's content key wrapped for one recipient
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WrappedKey {
+    pub recipient: String,
+    /// Base64-encoded ephemeral X25519 public key (32 bytes)
+    pub ephemeral_public_key: String,
+    /// Base64-encoded nonce (12 bytes)
+    pub nonce: String,
+    /// Base64-encoded encrypted content key (48 bytes with its auth tag)
+    pub wrapped_key: String,
+}
+
+/// Sealing errors
+#[derive(Debug, thiserror::Error)]
+pub enum SealError {
+    #[error("Invalid {0} encoding or length")]
+    InvalidField(&'static str),
+    #[error("A sealed payload needs at least one recipient")]
+    NoRecipients,
+    #[error("At most {} recipients are allowed", MAX_SEALED_RECIPIENTS)]
+    TooManyRecipients,
+    #[error("Recipient {0} appears more than once")]
+    DuplicateRecipient(String),
+    #[error("Sealed ciphertext is too large")]
+    TooLarge,
+    #[error("Payload is not sealed for {0}")]
+    NotARecipient(String),
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+}
+
+/// Decode a base64-encoded X25519 public key
+pub fn decode_public_key(value: &str) -> Result<[u8; 32], SealError> {
+    decode_array(value, "public key")
+}
+
+fn decode_array<const N: usize>(value: &str, field: &'static str) -> Result<[u8; N], SealError> {
+    BASE64
+        .decode(value)
+        .ok()
+        .and_then(|bytes| <[u8; N]>::try_from(bytes).ok())
+        .ok_or(SealError::InvalidField(field))
+}
+
+/// Derive the key wrapping a content key for one recipient
+fn wrapping_key(
+    shared_secret: &[u8; 32],
+    ephemeral_public: &[u8; 32],
+    recipient_public: &[u8; 32],
+) -> Result<EncryptionKey, SealError> {
+    let hkdf = Hkdf::<Sha256>::new(Some(b"ddalab-share-key-v1"), shared_secret);
+    let mut info = [0u8; 64];
+    info[..32].copy_from_slice(ephemeral_public);
+    info[32..].copy_from_slice(recipient_public);
+
+    let mut key = [0u8; 32];
+    hkdf.expand(&info, &mut key)
+        .map_err(|_| SealError::Encryption(EncryptionError::InvalidKey))?;
+    Ok(EncryptionKey::new(key))
+}
+
+/// Encrypt `plaintext` for each `(user id, share public key)` recipient
+pub fn seal(
+    plaintext: &[u8],
+    recipients: &[(String, [u8; 32])],
+) -> Result<SealedPayload, SealError> {
+    let content_key = EncryptionKey::random();
+    let (nonce, ciphertext) = encrypt_payload(&content_key, plaintext)?;
+
+    let keys = recipients
+        .iter()
+        .map(|(recipient, public_key)| {
+            let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+            let ephemeral_public = PublicKey::from(&ephemeral).to_bytes();
+            let shared = ephemeral.diffie_hellman(&PublicKey::from(*public_key));
+            let key = wrapping_key(shared.as_bytes(), &ephemeral_public, public_key)?;
+            let (nonce, wrapped) = encrypt_payload(&key, content_key.as_bytes())?;
+            Ok(WrappedKey {
+                recipient: recipient.clone(),
+                ephemeral_public_key: BASE64.encode(ephemeral_public),
+                nonce: BASE64.encode(nonce),
+                wrapped_key: BASE64.encode(wrapped),
+            })
+        })
+        .collect::<Result<Vec<_>, SealError>>()?;
+
+    let payload = SealedPayload {
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+        keys,
+    };
+    payload.validate()?;
+    Ok(payload)
+}
+
+impl SealedPayload {
+    /// Check the payload is well formed, without opening it
+    pub fn validate(&self) -> Result<(), SealError> {
+        if self.ciphertext.len() > MAX_SEALED_CIPHERTEXT_LENGTH {
+            return Err(SealError::TooLarge);
+        }
+        decode_array::<12>(&self.nonce, "nonce")?;
+        let ciphertext = BASE64
+            .decode(&self.ciphertext)
+            .map_err(|_| SealError::InvalidField("ciphertext"))?;
+        if ciphertext.len() < TAG_LENGTH {
+            return Err(SealError::InvalidField("ciphertext"));
+        }
+
+        if self.keys.is_empty() {
+            return Err(SealError::NoRecipients);
+        }
+        if self.keys.len() > MAX_SEALED_RECIPIENTS {
+            return Err(SealError::TooManyRecipients);
+        }
+        let mut recipients = HashSet::new();
+        for key in &self.keys {
+            if !recipients.insert(key.recipient.as_str()) {
+                return Err(SealError::DuplicateRecipient(key.recipient.clone()));
+            }
+            decode_array::<32>(&key.ephemeral_public_key, "ephemeral public key")?;
+            decode_array::<12>(&key.nonce, "key nonce")?;
+            decode_array::<{ 32 + TAG_LENGTH }>(&key.wrapped_key, "wrapped key")?;
+        }
+        Ok(())
+    }
+
+    /// Decrypt the payload as `recipient`
+    pub fn open(&self, recipient: &str, key_pair: &ShareKeyPair) -> Result<Vec<u8>, SealError> {
+        let wrapped = self
+            .keys
+            .iter()
+            .find(|key| key.recipient == recipient)
+            .ok_or_else(|| SealError::NotARecipient(recipient.to_string()))?;
+
+        let ephemeral_public: [u8; 32] =
+            decode_array(&wrapped.ephemeral_public_key, "ephemeral public key")?;
+        let shared = key_pair
+            .secret
+            .diffie_hellman(&PublicKey::from(ephemeral_public));
+        let key = wrapping_key(
+            shared.as_bytes(),
+            &ephemeral_public,
+            &key_pair.public_key_bytes(),
+        )?;
+        let content_key: [u8; 32] = decrypt_payload(
+            &key,
+            &decode_array::<12>(&wrapped.nonce, "key nonce")?,
+            &BASE64
+                .decode(&wrapped.wrapped_key)
+                .map_err(|_| SealError::InvalidField("wrapped key"))?,
+        )?
+        .try_into()
+        .map_err(|_| SealError::InvalidField("wrapped key"))?;
+
+        let ciphertext = BASE64
+            .decode(&self.ciphertext)
+            .map_err(|_| SealError::InvalidField("ciphertext"))?;
+        Ok(decrypt_payload(
+            &EncryptionKey::new(content_key),
+            &decode_array::<12>(&self.nonce, "nonce")?,
+            &ciphertext,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let alice = ShareKeyPair::generate();
+        let bob = ShareKeyPair::generate();
+        let mallory = ShareKeyPair::generate();
+
+        let payload = seal(
+            b"{\"title\":\"Seizure onset\"}",
+            &[
+                ("alice@example.org".to_string(), alice.public_key_bytes()),
+                ("bob@example.org".to_string(), bob.public_key_bytes()),
+            ],
+        )
+        .unwrap();
+        payload.validate().unwrap();
+
+        assert_eq!(
+            payload.open("bob@example.org", &bob).unwrap(),
+            b"{\"title\":\"Seizure onset\"}"
+        );
+        let restored = ShareKeyPair::from_secret_bytes(alice.secret_bytes());
+        assert!(payload.open("alice@example.org", &restored).is_ok());
+
+        // Someone else's key does not unwrap Bob's copy
+        assert!(payload.open("bob@example.org", &mallory).is_err());
+        assert!(matches!(
+            payload.open("mallory@example.org", &mallory),
+            Err(SealError::NotARecipient(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_rejects_malformed_payloads() {
+        let bob = ShareKeyPair::generate();
+        let payload = seal(
+            b"content",
+            &[("bob@example.org".to_string(), bob.public_key_bytes())],
+        )
+        .unwrap();
+
+        let mut duplicate = payload.clone();
+        duplicate.keys.push(payload.keys[0].clone());
+        assert!(matches!(
+            duplicate.validate(),
+            Err(SealError::DuplicateRecipient(_))
+        ));
+
+        let mut plaintext = payload.clone();
+        plaintext.ciphertext = "not base64!".to_string();
+        assert!(plaintext.validate().is_err());
+
+        let mut unwrapped = payload;
+        unwrapped.keys.clear();
+        assert!(matches!(unwrapped.validate(), Err(SealError::NoRecipients)));
+    }
+}
@@ -18,6 +18,7 @@ pub enum AccessCheckResult {
 pub enum AccessDeniedReason {
     Expired,
     WrongInstitution,
+    WrongOrganization,
     NotInTeam,
     NotInUserList,
     PhiCrossInstitution,
@@ -30,6 +31,9 @@ impl std::fmt::Display for AccessDeniedReason {
         match self {
             Self::Expired => write!(f, "Share has expired"),
             Self::WrongInstitution => write!(f, "User is not in the share's institution"),
+            Self::WrongOrganization => {
+                write!(f, "User is not in the share's organization")
+            }
             Self::NotInTeam => write!(f, "User is not a member of the required team"),
             Self::NotInUserList => write!(f, "User is not in the allowed users list"),
             Self::PhiCrossInstitution => {
@@ -41,16 +45,30 @@ impl std::fmt::Display for AccessDeniedReason {
     }
 }
 
+/// The user asking for a share, with their memberships
+#[derive(Debug, Clone, Copy)]
+pub struct Requester<'a> {
+    pub user_id: &'a str,
+    pub institution_id: &'a str,
+    pub team_ids: &'a [String],
+    pub organization_ids: &'a [String],
+}
+
 /// Check if a user can access a share
 pub fn check_access(
-    user_id: &str,
-    user_institution_id: &str,
-    user_team_ids: &[String],
+    requester: &Requester<'_>,
     share_policy: &AccessPolicy,
     classification: DataClassification,
     institution_config: &InstitutionConfig,
     download_count: u32,
 ) -> AccessCheckResult {
+    let Requester {
+        user_id,
+        institution_id: user_institution_id,
+        team_ids: user_team_ids,
+        organization_ids: user_organization_ids,
+    } = *requester;
+
     // 1. Check expiration
     if share_policy.is_expired() {
         return AccessCheckResult::Denied {
@@ -76,7 +94,16 @@ pub fn check_access(
         };
     }
 
-    // 4. HIPAA mode enforcement
+    // 4. Organization boundary check
+    if let Some(organization_id) = &share_policy.organization_id {
+        if !user_organization_ids.contains(organization_id) {
+            return AccessCheckResult::Denied {
+                reason: AccessDeniedReason::WrongOrganization,
+            };
+        }
+    }
+
+    // 5. HIPAA mode enforcement
     if institution_config.hipaa_mode && classification == DataClassification::Phi {
         // PHI cannot be public
         if matches!(share_policy.policy_type, AccessPolicyType::Public) {
@@ -86,18 +113,22 @@ pub fn check_access(
         }
     }
 
-    // 5. Policy-specific checks
+    // 6. Policy-specific checks
     let access_allowed = match &share_policy.policy_type {
         AccessPolicyType::Public => true,
         AccessPolicyType::Institution => true, // Already checked same institution
         AccessPolicyType::Team { team_id } => user_team_ids.contains(team_id),
         AccessPolicyType::Users { user_ids } => user_ids.contains(&user_id.to_string()),
+        AccessPolicyType::Organization { organization_id } => {
+            user_organization_ids.contains(organization_id)
+        }
     };
 
     if !access_allowed {
         let reason = match &share_policy.policy_type {
             AccessPolicyType::Team { .. } => AccessDeniedReason::NotInTeam,
             AccessPolicyType::Users { .. } => AccessDeniedReason::NotInUserList,
+            AccessPolicyType::Organization { .. } => AccessDeniedReason::WrongOrganization,
             _ => AccessDeniedReason::WrongInstitution,
         };
         return AccessCheckResult::Denied { reason };
@@ -132,13 +163,24 @@ mod tests {
         }
     }
 
+    fn requester<'a>(institution_id: &'a str, organization_ids: &'a [String]) -> Requester<'a> {
+        Requester {
+            user_id: "user-1",
+            institution_id,
+            team_ids: &[],
+            organization_ids,
+        }
+    }
+
     fn public_policy(institution_id: &str) -> AccessPolicy {
         AccessPolicy {
             policy_type: AccessPolicyType::Public,
             institution_id: institution_id.to_string(),
+            organization_id: None,
             permissions: vec![Permission::View, Permission::Download],
             expires_at: Utc::now() + Duration::days(30),
             max_downloads: None,
+            transfer: None,
         }
     }
 
@@ -148,9 +190,7 @@ mod tests {
         let inst = default_institution();
 
         let result = check_access(
-            "user-1",
-            "inst-1",
-            &[],
+            &requester("inst-1", &[]),
             &policy,
             DataClassification::Unclassified,
             &inst,
@@ -166,9 +206,7 @@ mod tests {
         let inst = default_institution();
 
         let result = check_access(
-            "user-1",
-            "inst-2", // Different institution
-            &[],
+            &requester("inst-2", &[]), // Different institution
             &policy,
             DataClassification::Unclassified,
             &inst,
@@ -190,9 +228,7 @@ mod tests {
         let inst = default_institution();
 
         let result = check_access(
-            "user-1",
-            "inst-1",
-            &[],
+            &requester("inst-1", &[]),
             &policy,
             DataClassification::Unclassified,
             &inst,
@@ -213,9 +249,7 @@ mod tests {
         let inst = default_institution(); // hipaa_mode: true
 
         let result = check_access(
-            "user-1",
-            "inst-1",
-            &[],
+            &requester("inst-1", &[]),
             &policy,
             DataClassification::Phi,
             &inst,
@@ -237,9 +271,7 @@ mod tests {
         inst.hipaa_mode = false;
 
         let result = check_access(
-            "user-1",
-            "inst-1",
-            &[],
+            &requester("inst-1", &[]),
             &policy,
             DataClassification::Phi,
             &inst,
@@ -248,4 +280,46 @@ mod tests {
 
         assert!(matches!(result, AccessCheckResult::Granted { .. }));
     }
+
+    #[test]
+    fn test_organization_boundary() {
+        let mut policy = public_policy("inst-1");
+        policy.organization_id = Some("org-cardiology".to_string());
+        let inst = default_institution();
+        let check = |orgs: &[String], policy: &AccessPolicy| {
+            check_access(
+                &requester("inst-1", orgs),
+                policy,
+                DataClassification::Unclassified,
+                &inst,
+                0,
+            )
+        };
+
+        let outsider = ["org-neurology".to_string()];
+        assert!(matches!(
+            check(&outsider, &policy),
+            AccessCheckResult::Denied {
+                reason: AccessDeniedReason::WrongOrganization
+            }
+        ));
+        let member = ["org-cardiology".to_string()];
+        assert!(matches!(
+            check(&member, &policy),
+            AccessCheckResult::Granted { .. }
+        ));
+
+        policy.organization_id = None;
+        policy.policy_type = AccessPolicyType::Organization {
+            organization_id: "org-cardiology".to_string(),
+        };
+        assert!(matches!(
+            check(&member, &policy),
+            AccessCheckResult::Granted { .. }
+        ));
+        assert!(matches!(
+            check(&outsider, &policy),
+            AccessCheckResult::Denied { .. }
+        ));
+    }
 }
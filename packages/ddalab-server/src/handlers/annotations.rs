@@ -0,0 +1,443 @@
+//! Annotation sync endpoints
+//!
+//! Desktop clients keep annotations in their local database and push each
+//! change with `PUT /api/annotations/{id}`, under the id they created it
+//! with. Every change gets a server-wide `revision`; a client pulls what
+//! changed since the last revision it saw with `GET /api/annotations?since=`,
+//! deletions included, and sends the revision it edited from to detect
+//! conflicting edits. Annotations shared with a team are seen and edited by
+//! all of its members.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::state::ServerState;
+use crate::storage::{
+    Annotation, AnnotationStore, PostgresAnnotationStore, PostgresTeamStore, StorageError,
+    TeamStore, User,
+};
+
+/// Longest annotation id accepted
+const MAX_ID_LENGTH: usize = 128;
+
+/// Longest label or channel name accepted
+const MAX_LABEL_LENGTH: usize = 256;
+
+/// Longest notes accepted
+const MAX_NOTES_LENGTH: usize = 16 * 1024;
+
+/// Largest client payload accepted, serialized
+const MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// Most annotations returned by one sync request
+const MAX_CHANGES: i64 = 1000;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn annotation_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn not_found() -> ApiError {
+    annotation_error(StatusCode::NOT_FOUND, "Annotation not found", "NOT_FOUND")
+}
+
+fn storage_error(e: StorageError) -> ApiError {
+    match e {
+        StorageError::NotFound(_) => not_found(),
+        StorageError::Conflict(message) => {
+            annotation_error(StatusCode::CONFLICT, &message, "REVISION_CONFLICT")
+        }
+        e => {
+            error!("Annotation storage failed: {}", e);
+            annotation_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal error",
+                "INTERNAL_ERROR",
+            )
+        }
+    }
+}
+
+fn get_store(state: &ServerState) -> PostgresAnnotationStore {
+    PostgresAnnotationStore::new(state.db_pool.clone())
+}
+
+/// Create or replace an annotation
+#[derive(Debug, Deserialize)]
+pub struct SaveAnnotationRequest {
+    /// SHA-256 of the recording, in hex
+    pub file_hash: String,
+    /// Share with a team the caller belongs to
+    #[serde(default)]
+    pub team_id: Option<Uuid>,
+    pub label: String,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub channel_name: Option<String>,
+    pub start_seconds: f64,
+    #[serde(default)]
+    pub end_seconds: Option<f64>,
+    #[serde(default)]
+    pub payload: Option<serde_json::Value>,
+    /// Revision the change was made from; the save is refused with
+    /// `409 Conflict` if the annotation has changed since
+    #[serde(default)]
+    pub revision: Option<i64>,
+}
+
+/// Which annotations to pull
+#[derive(Debug, Deserialize)]
+pub struct AnnotationChangesQuery {
+    /// Only annotations of this recording
+    #[serde(default)]
+    pub file_hash: Option<String>,
+    /// Only changes after this revision, deletions included; without it,
+    /// every annotation that is not deleted
+    #[serde(default)]
+    pub since: Option<i64>,
+}
+
+/// Revision to delete from
+#[derive(Debug, Deserialize)]
+pub struct DeleteAnnotationQuery {
+    #[serde(default)]
+    pub revision: Option<i64>,
+}
+
+/// Annotations changed since a revision
+#[derive(Debug, Serialize)]
+pub struct AnnotationChanges {
+    pub annotations: Vec<Annotation>,
+    /// Pass as `since` on the next pull
+    pub revision: i64,
+    /// More changes are waiting; pull again straight away
+    pub has_more: bool,
+}
+
+async fn caller(state: &ServerState, headers: &HeaderMap) -> Result<User, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| annotation_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| annotation_error(StatusCode::UNAUTHORIZED, "Unknown user", "UNAUTHORIZED"))
+}
+
+/// Whether `user` may see and change `annotation`
+async fn can_access(
+    state: &ServerState,
+    user: &User,
+    annotation: &Annotation,
+) -> Result<bool, ApiError> {
+    if annotation.owner == user.email {
+        return Ok(true);
+    }
+    match annotation.team_id {
+        Some(team_id) => is_member(state, user, team_id).await,
+        None => Ok(false),
+    }
+}
+
+async fn is_member(state: &ServerState, user: &User, team_id: Uuid) -> Result<bool, ApiError> {
+    PostgresTeamStore::new(state.db_pool.clone())
+        .is_team_member(team_id, user.id)
+        .await
+        .map_err(storage_error)
+}
+
+/// A recording's SHA-256, lowercased
+fn normalize_file_hash(file_hash: &str) -> Result<String, ApiError> {
+    let file_hash = file_hash.trim().to_ascii_lowercase();
+    if file_hash.len() != 64 || !file_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(annotation_error(
+            StatusCode::BAD_REQUEST,
+            "file_hash must be a SHA-256 in hex",
+            "INVALID_INPUT",
+        ));
+    }
+    Ok(file_hash)
+}
+
+/// Check a request, returning the recording's normalized hash
+fn validate(id: &str, request: &SaveAnnotationRequest) -> Result<String, ApiError> {
+    let invalid =
+        |message: &str| annotation_error(StatusCode::BAD_REQUEST, message, "INVALID_INPUT");
+    if id.is_empty()
+        || id.len() > MAX_ID_LENGTH
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+    {
+        return Err(invalid(
+            "Annotation id must be 1 to 128 letters, digits, '-', '_', '.' or ':'",
+        ));
+    }
+    if request.label.trim().is_empty() || request.label.len() > MAX_LABEL_LENGTH {
+        return Err(invalid("Label must be 1 to 256 characters"));
+    }
+    if request.notes.len() > MAX_NOTES_LENGTH {
+        return Err(invalid("Notes too long"));
+    }
+    if request
+        .channel_name
+        .as_ref()
+        .is_some_and(|channel| channel.len() > MAX_LABEL_LENGTH)
+    {
+        return Err(invalid("Channel name too long"));
+    }
+    if !request.start_seconds.is_finite() || request.start_seconds < 0.0 {
+        return Err(invalid("start_seconds must be a non-negative number"));
+    }
+    if request
+        .end_seconds
+        .is_some_and(|end| !end.is_finite() || end < request.start_seconds)
+    {
+        return Err(invalid("end_seconds must not be before start_seconds"));
+    }
+    match &request.payload {
+        None | Some(serde_json::Value::Object(_)) => {}
+        Some(_) => return Err(invalid("payload must be an object")),
+    }
+    if request
+        .payload
+        .as_ref()
+        .is_some_and(|payload| payload.to_string().len() > MAX_PAYLOAD_BYTES)
+    {
+        return Err(invalid("payload too large"));
+    }
+    normalize_file_hash(&request.file_hash)
+}
+
+async fn save(
+    state: &ServerState,
+    user: &User,
+    id: String,
+    request: SaveAnnotationRequest,
+) -> Result<(bool, Annotation), ApiError> {
+    let file_hash = validate(&id, &request)?;
+    if let Some(team_id) = request.team_id {
+        if !is_member(state, user, team_id).await? {
+            return Err(annotation_error(
+                StatusCode::FORBIDDEN,
+                "You can only share annotations with your own teams",
+                "FORBIDDEN",
+            ));
+        }
+    }
+
+    let store = get_store(state);
+    let existing = match store.get_annotation(&id).await {
+        Ok(existing) => Some(existing),
+        Err(StorageError::NotFound(_)) => None,
+        Err(e) => return Err(storage_error(e)),
+    };
+    if let Some(existing) = &existing {
+        // Someone else's private annotation is reported as missing
+        if !can_access(state, user, existing).await? {
+            return Err(not_found());
+        }
+        if existing.owner != user.email && existing.team_id != request.team_id {
+            return Err(annotation_error(
+                StatusCode::FORBIDDEN,
+                "Only the owner can change who an annotation is shared with",
+                "FORBIDDEN",
+            ));
+        }
+    }
+
+    let now = chrono::Utc::now();
+    let annotation = Annotation {
+        id,
+        file_hash,
+        owner: user.email.clone(),
+        team_id: request.team_id,
+        label: request.label.trim().to_string(),
+        notes: request.notes,
+        channel_name: request.channel_name,
+        start_seconds: request.start_seconds,
+        end_seconds: request.end_seconds,
+        payload: request
+            .payload
+            .unwrap_or_else(|| serde_json::Value::Object(Default::default())),
+        revision: 0,
+        deleted: false,
+        created_at: now,
+        updated_at: now,
+    };
+    let saved = store
+        .save_annotation(&annotation, request.revision)
+        .await
+        .map_err(storage_error)?;
+    Ok((existing.is_none(), saved))
+}
+
+/// Annotations the caller can see, or those changed since a revision
+pub async fn list_annotations(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Query(query): Query<AnnotationChangesQuery>,
+) -> Result<Json<AnnotationChanges>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let file_hash = query
+        .file_hash
+        .as_deref()
+        .map(normalize_file_hash)
+        .transpose()?;
+    let since = query.since.unwrap_or(0);
+
+    let annotations = get_store(&state)
+        .changed_annotations(
+            &user.email,
+            file_hash.as_deref(),
+            since,
+            query.since.is_some(),
+            MAX_CHANGES,
+        )
+        .await
+        .map_err(storage_error)?;
+
+    Ok(Json(AnnotationChanges {
+        revision: annotations.last().map_or(since, |a| a.revision),
+        has_more: annotations.len() as i64 == MAX_CHANGES,
+        annotations,
+    }))
+}
+
+/// Create an annotation under a new id
+pub async fn create_annotation(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<SaveAnnotationRequest>,
+) -> Result<(StatusCode, Json<Annotation>), ApiError> {
+    let user = caller(&state, &headers).await?;
+    let (_, annotation) = save(&state, &user, Uuid::new_v4().to_string(), request).await?;
+    info!(
+        "{} annotated {} ({})",
+        user.email, annotation.file_hash, annotation.id
+    );
+
+    Ok((StatusCode::CREATED, Json(annotation)))
+}
+
+/// One annotation, deleted or not
+pub async fn get_annotation(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(annotation_id): Path<String>,
+) -> Result<Json<Annotation>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let annotation = get_store(&state)
+        .get_annotation(&annotation_id)
+        .await
+        .map_err(storage_error)?;
+    if !can_access(&state, &user, &annotation).await? {
+        return Err(not_found());
+    }
+    Ok(Json(annotation))
+}
+
+/// Create or replace an annotation under the client's id
+pub async fn save_annotation(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(annotation_id): Path<String>,
+    Json(request): Json<SaveAnnotationRequest>,
+) -> Result<(StatusCode, Json<Annotation>), ApiError> {
+    let user = caller(&state, &headers).await?;
+    let (created, annotation) = save(&state, &user, annotation_id, request).await?;
+    info!(
+        "{} saved annotation {} at revision {}",
+        user.email, annotation.id, annotation.revision
+    );
+
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(annotation)))
+}
+
+/// Delete an annotation, leaving a tombstone for the next sync
+pub async fn delete_annotation(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(annotation_id): Path<String>,
+    Query(query): Query<DeleteAnnotationQuery>,
+) -> Result<Json<Annotation>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let store = get_store(&state);
+    let annotation = store
+        .get_annotation(&annotation_id)
+        .await
+        .map_err(storage_error)?;
+    if annotation.deleted || !can_access(&state, &user, &annotation).await? {
+        return Err(not_found());
+    }
+
+    let deleted = store
+        .delete_annotation(&annotation_id, query.revision)
+        .await
+        .map_err(storage_error)?;
+    info!("{} deleted annotation {}", user.email, annotation_id);
+
+    Ok(Json(deleted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(start: f64, end: Option<f64>) -> SaveAnnotationRequest {
+        SaveAnnotationRequest {
+            file_hash: "AB".repeat(32),
+            team_id: None,
+            label: "Spike".to_string(),
+            notes: String::new(),
+            channel_name: Some("Fp1".to_string()),
+            start_seconds: start,
+            end_seconds: end,
+            payload: Some(serde_json::json!({"color": "#ff0000"})),
+            revision: None,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(
+            validate("a1:b-2", &request(1.0, Some(2.5))).unwrap(),
+            "ab".repeat(32)
+        );
+        assert!(validate("a/b", &request(1.0, None)).is_err());
+        assert!(validate("a", &request(-1.0, None)).is_err());
+        assert!(validate("a", &request(2.0, Some(1.0))).is_err());
+        assert!(validate("a", &request(f64::NAN, None)).is_err());
+
+        let mut short_hash = request(1.0, None);
+        short_hash.file_hash = "abc".to_string();
+        assert!(validate("a", &short_hash).is_err());
+
+        let mut array_payload = request(1.0, None);
+        array_payload.payload = Some(serde_json::json!([1, 2]));
+        assert!(validate("a", &array_payload).is_err());
+    }
+}
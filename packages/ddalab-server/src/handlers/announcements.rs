@@ -0,0 +1,232 @@
+//! Announcement endpoints
+//!
+//! Administrators publish announcements, e.g. planned downtime or a newly
+//! deployed DDA binary. Every user sees the current ones in `/info` and over
+//! the WebSocket; signed-in users also get which ones they have read.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::handlers::egress::require_admin;
+use crate::state::ServerState;
+use crate::storage::{
+    Announcement, AnnouncementSeverity, AnnouncementStore, CreateAnnouncement,
+    PostgresAnnouncementStore,
+};
+
+const MAX_TITLE_LENGTH: usize = 255;
+const MAX_MESSAGE_LENGTH: usize = 4096;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn announcement_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn internal_error(e: impl std::fmt::Display) -> ApiError {
+    warn!("Announcement storage error: {}", e);
+    announcement_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error",
+        "INTERNAL_ERROR",
+    )
+}
+
+fn get_store(state: &ServerState) -> PostgresAnnouncementStore {
+    PostgresAnnouncementStore::new(state.db_pool.clone())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAnnouncementRequest {
+    pub title: String,
+    pub message: String,
+    #[serde(default)]
+    pub severity: AnnouncementSeverity,
+    /// Defaults to now
+    pub starts_at: Option<DateTime<Utc>>,
+    /// Shown until withdrawn when omitted
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+/// An announcement with the caller's read state
+#[derive(Debug, Serialize)]
+pub struct AnnouncementView {
+    #[serde(flatten)]
+    pub announcement: Announcement,
+    pub read: bool,
+}
+
+/// Put the stored unexpired announcements on the board
+pub async fn load_announcements(
+    state: &ServerState,
+) -> Result<usize, crate::storage::StorageError> {
+    let announcements = get_store(state).unexpired_announcements().await?;
+    let loaded = announcements.len();
+    state.announcements.load(announcements);
+    Ok(loaded)
+}
+
+fn caller_id(state: &ServerState, headers: &HeaderMap) -> Result<String, ApiError> {
+    state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| announcement_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))
+}
+
+/// Current announcements with the caller's read state
+pub async fn list_announcements(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<AnnouncementView>>, ApiError> {
+    let user_id = caller_id(&state, &headers)?;
+    let read = get_store(&state)
+        .read_announcements(&user_id)
+        .await
+        .map_err(internal_error)?;
+
+    let views = state
+        .announcements
+        .current()
+        .into_iter()
+        .map(|announcement| AnnouncementView {
+            read: read.contains(&announcement.id),
+            announcement,
+        })
+        .collect();
+    Ok(Json(views))
+}
+
+/// Mark an announcement as read by the caller
+pub async fn mark_announcement_read(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(announcement_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = caller_id(&state, &headers)?;
+    if !state
+        .announcements
+        .current()
+        .iter()
+        .any(|a| a.id == announcement_id)
+    {
+        return Err(announcement_error(
+            StatusCode::NOT_FOUND,
+            "Announcement not found",
+            "NOT_FOUND",
+        ));
+    }
+
+    get_store(&state)
+        .mark_read(announcement_id, &user_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Every announcement, including expired ones (admin only)
+pub async fn list_all_announcements(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Announcement>>, ApiError> {
+    require_admin(&state, &headers)
+        .await
+        .map_err(|(status, Json(e))| announcement_error(status, &e.error, &e.code))?;
+
+    let announcements = get_store(&state)
+        .list_announcements()
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(announcements))
+}
+
+/// Publish an announcement (admin only)
+pub async fn create_announcement(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateAnnouncementRequest>,
+) -> Result<Json<Announcement>, ApiError> {
+    let admin = require_admin(&state, &headers)
+        .await
+        .map_err(|(status, Json(e))| announcement_error(status, &e.error, &e.code))?;
+
+    let title = request.title.trim();
+    if title.is_empty() || title.len() > MAX_TITLE_LENGTH {
+        return Err(announcement_error(
+            StatusCode::BAD_REQUEST,
+            "Title must be 1-255 characters",
+            "INVALID_INPUT",
+        ));
+    }
+    let message = request.message.trim();
+    if message.is_empty() || message.len() > MAX_MESSAGE_LENGTH {
+        return Err(announcement_error(
+            StatusCode::BAD_REQUEST,
+            "Message must be 1-4096 characters",
+            "INVALID_INPUT",
+        ));
+    }
+    let starts_at = request.starts_at.unwrap_or_else(Utc::now);
+    if request
+        .ends_at
+        .is_some_and(|end| end <= starts_at.max(Utc::now()))
+    {
+        return Err(announcement_error(
+            StatusCode::BAD_REQUEST,
+            "'ends_at' must be in the future and after 'starts_at'",
+            "INVALID_INPUT",
+        ));
+    }
+
+    let announcement = get_store(&state)
+        .create_announcement(CreateAnnouncement {
+            title,
+            message,
+            severity: request.severity,
+            starts_at,
+            ends_at: request.ends_at,
+            created_by: &admin,
+        })
+        .await
+        .map_err(internal_error)?;
+    state.announcements.publish(announcement.clone());
+    info!("{} published announcement '{}'", admin, announcement.title);
+
+    Ok(Json(announcement))
+}
+
+/// Withdraw an announcement (admin only)
+pub async fn delete_announcement(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(announcement_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let admin = require_admin(&state, &headers)
+        .await
+        .map_err(|(status, Json(e))| announcement_error(status, &e.error, &e.code))?;
+
+    get_store(&state)
+        .delete_announcement(announcement_id)
+        .await
+        .map_err(|_| {
+            announcement_error(StatusCode::NOT_FOUND, "Announcement not found", "NOT_FOUND")
+        })?;
+    state.announcements.withdraw(announcement_id);
+    info!("{} withdrew announcement {}", admin, announcement_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
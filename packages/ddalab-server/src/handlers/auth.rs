@@ -1,15 +1,17 @@
 use axum::{
-    extract::State,
-    http::{header, StatusCode},
+    extract::{ConnectInfo, State},
+    http::StatusCode,
     Json,
 };
 use axum_extra::TypedHeader;
 use headers::{authorization::Bearer, Authorization};
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tracing::{info, warn};
 
-use crate::auth::verify_password;
+use crate::auth::{verify_password, RefreshError};
+use crate::metrics::AuthFailure;
 use crate::crypto::{EcdhKeyPair, EncryptionKey};
 use crate::state::ServerState;
 use crate::storage::StorageError;
@@ -31,6 +33,14 @@ pub struct LoginResponse {
     pub session_token: String,
     pub user_id: String,
     pub expires_in_seconds: u64,
+    /// Trade for a new session token at `/auth/refresh`; works once
+    pub refresh_token: String,
+    /// Seconds until the session can no longer be refreshed
+    pub refresh_expires_in_seconds: u64,
+    /// Client must prompt for a new password before continuing
+    pub password_reset_required: bool,
+    /// Admin and data egress routes need a passkey or recovery code first
+    pub mfa_required: bool,
 }
 
 /// Key exchange request (for encrypted sessions)
@@ -57,6 +67,21 @@ pub struct LogoutRequest {
     pub session_token: String,
 }
 
+/// Refresh request
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// New tokens replacing the session and refresh tokens just used
+#[derive(Debug, Serialize)]
+pub struct RefreshResponse {
+    pub session_token: String,
+    pub refresh_token: String,
+    pub expires_in_seconds: u64,
+    pub refresh_expires_in_seconds: u64,
+}
+
 /// Session validation response
 #[derive(Debug, Serialize)]
 pub struct SessionResponse {
@@ -75,6 +100,7 @@ pub async fn login(
         Ok(user) => user,
         Err(StorageError::UserNotFound(_)) => {
             warn!("Login attempt for unknown user: {}", request.user_id);
+            state.metrics.auth_failure(AuthFailure::InvalidPassword);
             return Err((
                 StatusCode::UNAUTHORIZED,
                 Json(ErrorResponse {
@@ -115,6 +141,7 @@ pub async fn login(
         }
         Ok(false) => {
             warn!("Invalid password for user: {}", request.user_id);
+            state.metrics.auth_failure(AuthFailure::InvalidPassword);
             return Err((
                 StatusCode::UNAUTHORIZED,
                 Json(ErrorResponse {
@@ -146,11 +173,23 @@ pub async fn login(
         user.email.clone(),
         None, // Encryption key set later via key exchange
     );
+    if user.mfa_required {
+        state.auth_state.session_manager.set_mfa_required(&token, true);
+    }
+    let (refresh_token, refresh_expires_in_seconds) = state
+        .auth_state
+        .session_manager
+        .refresh_token(&token)
+        .unwrap_or_default();
 
     Ok(Json(LoginResponse {
         session_token: token,
         user_id: user.email,
         expires_in_seconds: state.config.session_timeout_seconds,
+        refresh_token,
+        refresh_expires_in_seconds,
+        password_reset_required: user.password_reset_required,
+        mfa_required: user.mfa_required,
     }))
 }
 
@@ -228,6 +267,48 @@ pub async fn key_exchange(
     }))
 }
 
+/// Trade a refresh token for a new session token and refresh token
+///
+/// Works after the session went idle, until its absolute lifetime ends.
+pub async fn refresh_session(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(request): Json<RefreshRequest>,
+) -> Result<Json<RefreshResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let rate_limiter = &state.auth_state.rate_limiter;
+    if rate_limiter.is_rate_limited(addr.ip()) {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse {
+                error: "Too many failed authentication attempts".to_string(),
+                code: "RATE_LIMITED".to_string(),
+            }),
+        ));
+    }
+
+    match state.auth_state.session_manager.refresh(&request.refresh_token) {
+        Ok(refreshed) => Ok(Json(RefreshResponse {
+            session_token: refreshed.session_token,
+            refresh_token: refreshed.refresh_token,
+            expires_in_seconds: refreshed.expires_in_seconds,
+            refresh_expires_in_seconds: refreshed.refresh_expires_in_seconds,
+        })),
+        Err(e) => {
+            rate_limiter.record_failure(addr.ip());
+            if e == RefreshError::Reused {
+                warn!("Refresh token replayed from {}; session revoked", addr.ip());
+            }
+            Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                    code: "REFRESH_FAILED".to_string(),
+                }),
+            ))
+        }
+    }
+}
+
 /// Logout endpoint
 pub async fn logout(
     State(state): State<Arc<ServerState>>,
@@ -0,0 +1,508 @@
+//! Dataset access control for server files
+//!
+//! Administrators register directories of the server files as datasets with
+//! an owner and grant users or teams `read` (see the files) or `submit`
+//! (also run jobs on them). A file belongs to the innermost dataset that
+//! contains it; files outside every dataset are open to all users unless
+//! `SERVER_FILES_RESTRICTED` is set, in which case only administrators see
+//! them.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path as FsPath, PathBuf};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::state::ServerState;
+use crate::storage::{
+    Dataset, DatasetGrant, DatasetPermission, DatasetStore, Grantee, PostgresDatasetStore,
+    PostgresTeamStore, StorageError, TeamStore, User,
+};
+
+/// Longest dataset description accepted
+const MAX_DESCRIPTION_LENGTH: usize = 4096;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn dataset_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn storage_error(e: StorageError) -> ApiError {
+    match e {
+        StorageError::NotFound(message) => {
+            dataset_error(StatusCode::NOT_FOUND, &message, "NOT_FOUND")
+        }
+        StorageError::DuplicateName(path) => dataset_error(
+            StatusCode::CONFLICT,
+            &format!("'{}' is already a dataset", path),
+            "DUPLICATE_DATASET",
+        ),
+        e => {
+            error!("Dataset storage failed: {}", e);
+            dataset_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal error",
+                "INTERNAL_ERROR",
+            )
+        }
+    }
+}
+
+fn get_store(state: &ServerState) -> PostgresDatasetStore {
+    PostgresDatasetStore::new(state.db_pool.clone())
+}
+
+/// What a user may do with each part of the server files
+#[derive(Debug, Clone)]
+pub(crate) struct DatasetAccess {
+    /// Administrators see and submit on everything
+    admin: bool,
+    /// Permission on files outside every dataset
+    default: Option<DatasetPermission>,
+    /// Each dataset's directory and the user's permission on it
+    datasets: Vec<(PathBuf, Option<DatasetPermission>)>,
+}
+
+impl DatasetAccess {
+    pub(crate) fn new(
+        user_id: &str,
+        admin: bool,
+        restricted: bool,
+        datasets: &[Dataset],
+        grants: &[DatasetGrant],
+    ) -> Self {
+        let datasets = datasets
+            .iter()
+            .map(|dataset| {
+                let permission = if dataset.owner == user_id {
+                    Some(DatasetPermission::Submit)
+                } else {
+                    grants
+                        .iter()
+                        .filter(|grant| grant.dataset_id == dataset.id)
+                        .map(|grant| grant.permission)
+                        .max()
+                };
+                (PathBuf::from(&dataset.path), permission)
+            })
+            .collect();
+        Self {
+            admin,
+            default: (!restricted).then_some(DatasetPermission::Submit),
+            datasets,
+        }
+    }
+
+    /// The user's permission on a file or directory, by its path relative
+    /// to the server files directory
+    pub(crate) fn permission(&self, path: &FsPath) -> Option<DatasetPermission> {
+        if self.admin {
+            return Some(DatasetPermission::Submit);
+        }
+        self.datasets
+            .iter()
+            .filter(|(root, _)| path.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .map_or(self.default, |(_, permission)| *permission)
+    }
+
+    /// Whether a file or directory shows up in listings: the user can read
+    /// it, or it leads to a dataset they can read
+    pub(crate) fn visible(&self, path: &FsPath) -> bool {
+        self.permission(path).is_some()
+            || self
+                .datasets
+                .iter()
+                .any(|(root, permission)| permission.is_some() && root.starts_with(path))
+    }
+}
+
+/// Load what the submitter may do with the server files
+pub(crate) async fn dataset_access(
+    state: &ServerState,
+    user_id: &str,
+) -> Result<DatasetAccess, (StatusCode, String)> {
+    let store = get_store(state);
+    let load_failed = |e: StorageError| {
+        error!("Failed to load dataset permissions: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to load dataset permissions".to_string(),
+        )
+    };
+    let datasets = store.list_datasets().await.map_err(load_failed)?;
+    let grants = store.grants_for_user(user_id).await.map_err(load_failed)?;
+    let admin = state
+        .user_store
+        .get_user_by_email(user_id)
+        .await
+        .map(|user| user.is_admin)
+        .unwrap_or(false);
+    Ok(DatasetAccess::new(
+        user_id,
+        admin,
+        state.config.server_files_restricted,
+        &datasets,
+        &grants,
+    ))
+}
+
+/// Register a directory as a dataset
+#[derive(Debug, Deserialize)]
+pub struct CreateDatasetRequest {
+    /// Directory relative to the server files directory
+    pub path: String,
+    /// Email of the user who owns the dataset
+    pub owner: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Change a dataset's owner or description
+#[derive(Debug, Deserialize)]
+pub struct UpdateDatasetRequest {
+    pub owner: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Grant a user or team access to a dataset
+#[derive(Debug, Deserialize)]
+pub struct SaveGrantRequest {
+    pub grantee: Grantee,
+    pub permission: DatasetPermission,
+}
+
+/// A dataset with its grants
+#[derive(Debug, Serialize)]
+pub struct DatasetResponse {
+    #[serde(flatten)]
+    pub dataset: Dataset,
+    pub grants: Vec<DatasetGrant>,
+}
+
+async fn require_admin(state: &ServerState, headers: &HeaderMap) -> Result<User, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| dataset_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    let user = state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| dataset_error(StatusCode::UNAUTHORIZED, "Unknown user", "UNAUTHORIZED"))?;
+    if !user.is_admin {
+        warn!("Non-admin user {} requested dataset management", email);
+        return Err(dataset_error(
+            StatusCode::FORBIDDEN,
+            "Administrator access required",
+            "FORBIDDEN",
+        ));
+    }
+    Ok(user)
+}
+
+/// A dataset directory as stored: relative to the server files directory
+/// with links resolved, so it matches the paths files are checked under
+fn resolve_dataset_path(state: &ServerState, path: &str) -> Result<String, ApiError> {
+    let invalid = |message: &str| dataset_error(StatusCode::BAD_REQUEST, message, "INVALID_INPUT");
+    let base = state
+        .config
+        .server_files_directory
+        .as_ref()
+        .ok_or_else(|| invalid("Server-side file access is not configured"))?;
+    let requested = FsPath::new(path.trim());
+    if requested
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(invalid("Dataset path must be relative, without '..'"));
+    }
+
+    let canonical_base = base.canonicalize().map_err(|e| {
+        error!("Server files directory invalid: {}", e);
+        dataset_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Server configuration error",
+            "INTERNAL_ERROR",
+        )
+    })?;
+    let canonical = base
+        .join(requested)
+        .canonicalize()
+        .map_err(|_| invalid("Dataset path does not exist"))?;
+    let relative = canonical
+        .strip_prefix(&canonical_base)
+        .map_err(|_| invalid("Dataset path is outside the server files directory"))?;
+    if relative.as_os_str().is_empty() {
+        return Err(invalid(
+            "The whole server files directory cannot be a dataset",
+        ));
+    }
+    Ok(relative.to_string_lossy().to_string())
+}
+
+async fn check_owner(state: &ServerState, owner: &str) -> Result<(), ApiError> {
+    state
+        .user_store
+        .get_user_by_email(owner)
+        .await
+        .map(|_| ())
+        .map_err(|_| {
+            dataset_error(
+                StatusCode::BAD_REQUEST,
+                &format!("Unknown user '{}'", owner),
+                "INVALID_INPUT",
+            )
+        })
+}
+
+fn check_description(description: &Option<String>) -> Result<(), ApiError> {
+    if description
+        .as_ref()
+        .is_some_and(|desc| desc.len() > MAX_DESCRIPTION_LENGTH)
+    {
+        return Err(dataset_error(
+            StatusCode::BAD_REQUEST,
+            "Description too long",
+            "INVALID_INPUT",
+        ));
+    }
+    Ok(())
+}
+
+/// Every dataset with its grants (admin only)
+pub async fn list_datasets(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<DatasetResponse>>, ApiError> {
+    require_admin(&state, &headers).await?;
+    let store = get_store(&state);
+    let datasets = store.list_datasets().await.map_err(storage_error)?;
+    let mut response = Vec::with_capacity(datasets.len());
+    for dataset in datasets {
+        let grants = store.list_grants(dataset.id).await.map_err(storage_error)?;
+        response.push(DatasetResponse { dataset, grants });
+    }
+    Ok(Json(response))
+}
+
+/// Register a directory as a dataset (admin only)
+pub async fn create_dataset(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateDatasetRequest>,
+) -> Result<(StatusCode, Json<Dataset>), ApiError> {
+    let admin = require_admin(&state, &headers).await?;
+    let path = resolve_dataset_path(&state, &request.path)?;
+    check_owner(&state, &request.owner).await?;
+    check_description(&request.description)?;
+
+    let dataset = Dataset {
+        id: Uuid::new_v4(),
+        path,
+        owner: request.owner,
+        description: request.description,
+        created_by: admin.email.clone(),
+        created_at: chrono::Utc::now(),
+    };
+    get_store(&state)
+        .create_dataset(&dataset)
+        .await
+        .map_err(storage_error)?;
+    info!(
+        "{} registered dataset {} owned by {}",
+        admin.email, dataset.path, dataset.owner
+    );
+
+    Ok((StatusCode::CREATED, Json(dataset)))
+}
+
+/// Change a dataset's owner or description (admin only)
+pub async fn update_dataset(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(dataset_id): Path<Uuid>,
+    Json(request): Json<UpdateDatasetRequest>,
+) -> Result<Json<Dataset>, ApiError> {
+    let admin = require_admin(&state, &headers).await?;
+    check_owner(&state, &request.owner).await?;
+    check_description(&request.description)?;
+
+    let store = get_store(&state);
+    let dataset = Dataset {
+        owner: request.owner,
+        description: request.description,
+        ..store.get_dataset(dataset_id).await.map_err(storage_error)?
+    };
+    store
+        .update_dataset(&dataset)
+        .await
+        .map_err(storage_error)?;
+    info!(
+        "{} updated dataset {} (owner {})",
+        admin.email, dataset.path, dataset.owner
+    );
+
+    Ok(Json(dataset))
+}
+
+/// Remove a dataset and its grants (admin only)
+pub async fn delete_dataset(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(dataset_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let admin = require_admin(&state, &headers).await?;
+    get_store(&state)
+        .delete_dataset(dataset_id)
+        .await
+        .map_err(storage_error)?;
+    info!("{} removed dataset {}", admin.email, dataset_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Grant a user or team access, replacing their previous grant (admin only)
+pub async fn save_dataset_grant(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(dataset_id): Path<Uuid>,
+    Json(request): Json<SaveGrantRequest>,
+) -> Result<Json<DatasetGrant>, ApiError> {
+    let admin = require_admin(&state, &headers).await?;
+    let store = get_store(&state);
+    let dataset = store.get_dataset(dataset_id).await.map_err(storage_error)?;
+    match &request.grantee {
+        Grantee::User(email) => check_owner(&state, email).await?,
+        Grantee::Team(team_id) => {
+            PostgresTeamStore::new(state.db_pool.clone())
+                .get_team(*team_id)
+                .await
+                .map_err(|_| {
+                    dataset_error(
+                        StatusCode::BAD_REQUEST,
+                        &format!("Unknown team {}", team_id),
+                        "INVALID_INPUT",
+                    )
+                })?;
+        }
+    }
+
+    let grant = store
+        .save_grant(&DatasetGrant {
+            id: Uuid::new_v4(),
+            dataset_id,
+            grantee: request.grantee,
+            permission: request.permission,
+            granted_by: admin.email.clone(),
+            created_at: chrono::Utc::now(),
+        })
+        .await
+        .map_err(storage_error)?;
+    info!(
+        "{} granted {:?} {} on dataset {}",
+        admin.email,
+        grant.grantee,
+        grant.permission.as_str(),
+        dataset.path
+    );
+
+    Ok(Json(grant))
+}
+
+/// Withdraw a grant (admin only)
+pub async fn delete_dataset_grant(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path((dataset_id, grant_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    let admin = require_admin(&state, &headers).await?;
+    get_store(&state)
+        .delete_grant(dataset_id, grant_id)
+        .await
+        .map_err(storage_error)?;
+    info!(
+        "{} withdrew grant {} on dataset {}",
+        admin.email, grant_id, dataset_id
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset(path: &str, owner: &str) -> Dataset {
+        Dataset {
+            id: Uuid::new_v4(),
+            path: path.to_string(),
+            owner: owner.to_string(),
+            description: None,
+            created_by: "admin@example.edu".to_string(),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    fn grant(dataset: &Dataset, permission: DatasetPermission) -> DatasetGrant {
+        DatasetGrant {
+            id: Uuid::new_v4(),
+            dataset_id: dataset.id,
+            grantee: Grantee::Team(Uuid::new_v4()),
+            permission,
+            granted_by: "admin@example.edu".to_string(),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_innermost_dataset_decides() {
+        let sleep = dataset("studies/sleep", "owner@example.edu");
+        let public = dataset("studies/sleep/public", "owner@example.edu");
+        let grants = [grant(&public, DatasetPermission::Read)];
+        let datasets = [sleep, public];
+
+        let access = DatasetAccess::new("bob@example.edu", false, false, &datasets, &grants);
+        let path = |p: &str| PathBuf::from(p);
+        assert_eq!(
+            access.permission(&path("other/a.edf")),
+            Some(DatasetPermission::Submit)
+        );
+        assert_eq!(access.permission(&path("studies/sleep/s01.edf")), None);
+        assert_eq!(
+            access.permission(&path("studies/sleep/public/s01.edf")),
+            Some(DatasetPermission::Read)
+        );
+        // Component-wise: a sibling sharing the prefix is not in the dataset
+        assert!(access.permission(&path("studies/sleepy/a.edf")).is_some());
+        // Directories leading to a readable dataset stay listed
+        assert!(access.visible(&path("studies/sleep")));
+        assert!(!access.visible(&path("studies/sleep/private")));
+
+        let owner = DatasetAccess::new("owner@example.edu", false, true, &datasets, &grants);
+        assert_eq!(
+            owner.permission(&path("studies/sleep/s01.edf")),
+            Some(DatasetPermission::Submit)
+        );
+        assert_eq!(owner.permission(&path("other/a.edf")), None);
+        assert!(owner.visible(&path("studies")));
+
+        let admin = DatasetAccess::new("admin@example.edu", true, true, &datasets, &grants);
+        assert_eq!(
+            admin.permission(&path("studies/sleep/s01.edf")),
+            Some(DatasetPermission::Submit)
+        );
+    }
+}
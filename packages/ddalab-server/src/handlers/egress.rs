@@ -0,0 +1,165 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, warn};
+
+use crate::state::ServerState;
+use crate::storage::{DatasetEgressSummary, EgressEntry, EgressStore, PostgresEgressStore};
+
+/// Default reporting window when no range is given
+const DEFAULT_REPORT_DAYS: i64 = 30;
+/// Maximum raw entries returned for a single dataset
+const MAX_DETAIL_ENTRIES: i64 = 1000;
+
+/// Helper to get egress store using shared pool
+pub(crate) fn get_store(state: &ServerState) -> PostgresEgressStore {
+    PostgresEgressStore::new(state.db_pool.clone())
+}
+
+/// Append an entry to the egress ledger without blocking the response
+pub(crate) fn record_egress(state: &ServerState, entry: EgressEntry) {
+    let store = get_store(state);
+    tokio::spawn(async move {
+        if let Err(e) = store.record(entry).await {
+            error!("Failed to record egress entry: {}", e);
+        }
+    });
+}
+
+/// Query params for the egress report
+#[derive(Debug, Deserialize)]
+pub struct EgressReportQuery {
+    /// Start of the reporting window (defaults to 30 days ago)
+    pub from: Option<DateTime<Utc>>,
+    /// End of the reporting window (defaults to now)
+    pub to: Option<DateTime<Utc>>,
+    /// Include raw ledger entries for this dataset
+    pub dataset: Option<String>,
+}
+
+/// Egress report response
+#[derive(Debug, Serialize)]
+pub struct EgressReportResponse {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub total_bytes: i64,
+    pub total_events: i64,
+    pub datasets: Vec<DatasetEgressSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<Vec<EgressEntry>>,
+}
+
+/// Error response
+#[derive(Debug, Serialize)]
+pub struct EgressErrorResponse {
+    pub error: String,
+    pub code: String,
+}
+
+fn egress_error(
+    status: StatusCode,
+    error: &str,
+    code: &str,
+) -> (StatusCode, Json<EgressErrorResponse>) {
+    (
+        status,
+        Json(EgressErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+/// Ensure the caller is an authenticated server administrator
+pub(crate) async fn require_admin(
+    state: &ServerState,
+    headers: &axum::http::HeaderMap,
+) -> Result<String, (StatusCode, Json<EgressErrorResponse>)> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| egress_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+
+    let user = state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| {
+            egress_error(
+                StatusCode::FORBIDDEN,
+                "Administrator access required",
+                "FORBIDDEN",
+            )
+        })?;
+
+    if !user.is_admin {
+        warn!("Non-admin user {} requested an admin endpoint", email);
+        return Err(egress_error(
+            StatusCode::FORBIDDEN,
+            "Administrator access required",
+            "FORBIDDEN",
+        ));
+    }
+
+    Ok(email)
+}
+
+/// Aggregate egress per dataset over a time range (admin only)
+pub async fn egress_report(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+    Query(query): Query<EgressReportQuery>,
+) -> Result<Json<EgressReportResponse>, (StatusCode, Json<EgressErrorResponse>)> {
+    require_admin(&state, &headers).await?;
+
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query
+        .from
+        .unwrap_or_else(|| to - Duration::days(DEFAULT_REPORT_DAYS));
+    if from > to {
+        return Err(egress_error(
+            StatusCode::BAD_REQUEST,
+            "'from' must not be after 'to'",
+            "INVALID_INPUT",
+        ));
+    }
+
+    let store = get_store(&state);
+    let datasets = store.report_by_dataset(from, to).await.map_err(|e| {
+        egress_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &e.to_string(),
+            "REPORT_ERROR",
+        )
+    })?;
+
+    let entries = match query.dataset.as_deref() {
+        Some(dataset) => Some(
+            store
+                .entries_for_dataset(dataset, from, to, MAX_DETAIL_ENTRIES)
+                .await
+                .map_err(|e| {
+                    egress_error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        &e.to_string(),
+                        "REPORT_ERROR",
+                    )
+                })?,
+        ),
+        None => None,
+    };
+
+    Ok(Json(EgressReportResponse {
+        from,
+        to,
+        total_bytes: datasets.iter().map(|d| d.total_bytes).sum(),
+        total_events: datasets.iter().map(|d| d.event_count).sum(),
+        datasets,
+        entries,
+    }))
+}
@@ -0,0 +1,148 @@
+//! Worker node endpoints
+//!
+//! With `DDA_EXECUTOR` set to `workers` or `kubernetes`, worker nodes
+//! authenticate with the `EXECUTOR_TOKEN` bearer token and:
+//! `POST /api/executor/claim` a run, waiting up to 25 s for one;
+//! `GET /api/executor/runs/{id}/input` its input;
+//! `POST /api/executor/runs/{id}/output` the lines the binary printed, at
+//! least every 10 s; `PUT /api/executor/runs/{id}/result` the result; and
+//! `POST /api/executor/runs/{id}/finish` how it ended. A `404` for a
+//! claimed run means it was cancelled or timed out and the node should stop.
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::sync::Arc;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::auth::bearer_token;
+use crate::jobs::{ClaimRequest, RemoteExecutor, RunError, RunOutcome, RunReport};
+use crate::state::ServerState;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn executor_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn run_error(e: RunError) -> ApiError {
+    match e {
+        RunError::NotFound => executor_error(StatusCode::NOT_FOUND, "Run not found", "NOT_FOUND"),
+        RunError::NotClaimed => executor_error(
+            StatusCode::CONFLICT,
+            "Run has not been claimed",
+            "NOT_CLAIMED",
+        ),
+    }
+}
+
+fn io_error(run_id: Uuid, e: std::io::Error) -> ApiError {
+    error!("Worker node transfer for run {} failed: {}", run_id, e);
+    executor_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error",
+        "INTERNAL_ERROR",
+    )
+}
+
+/// The remote executor, if the caller presents its token
+fn authorize<'a>(
+    state: &'a ServerState,
+    headers: &HeaderMap,
+) -> Result<&'a Arc<RemoteExecutor>, ApiError> {
+    let executor = state.remote_executor.as_ref().ok_or_else(|| {
+        executor_error(
+            StatusCode::NOT_FOUND,
+            "Remote execution is not enabled",
+            "NOT_FOUND",
+        )
+    })?;
+    let token = bearer_token(headers).unwrap_or_default();
+    if !executor.authorize(token) {
+        warn!("Rejected worker node request with an invalid token");
+        return Err(executor_error(
+            StatusCode::UNAUTHORIZED,
+            "Invalid executor token",
+            "UNAUTHORIZED",
+        ));
+    }
+    Ok(executor)
+}
+
+/// Claim a run, or `204 No Content` when none was offered in time
+pub async fn claim_run(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(claim): Json<ClaimRequest>,
+) -> Result<Response, ApiError> {
+    let executor = authorize(&state, &headers)?;
+    Ok(match executor.claim(&claim.node, claim.run_id).await {
+        Some(assignment) => Json(assignment).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+/// The plaintext input of a claimed run
+pub async fn get_run_input(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(run_id): Path<Uuid>,
+) -> Result<Response, ApiError> {
+    let executor = authorize(&state, &headers)?;
+    let path = executor.input_path(run_id).map_err(run_error)?;
+    let data = tokio::fs::read(&path)
+        .await
+        .map_err(|e| io_error(run_id, e))?;
+    Ok(([(header::CONTENT_TYPE, "application/octet-stream")], data).into_response())
+}
+
+/// Output of a claimed run; an empty report keeps the run alive
+pub async fn report_run_output(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(run_id): Path<Uuid>,
+    Json(report): Json<RunReport>,
+) -> Result<StatusCode, ApiError> {
+    let executor = authorize(&state, &headers)?;
+    executor.report(run_id, report).map_err(run_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The result of a claimed run, before it is finished
+pub async fn upload_run_result(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(run_id): Path<Uuid>,
+    body: Bytes,
+) -> Result<StatusCode, ApiError> {
+    let executor = authorize(&state, &headers)?;
+    let path = executor.result_path(run_id).map_err(run_error)?;
+    tokio::fs::write(&path, &body)
+        .await
+        .map_err(|e| io_error(run_id, e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// End a claimed run
+pub async fn finish_run(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(run_id): Path<Uuid>,
+    Json(outcome): Json<RunOutcome>,
+) -> Result<StatusCode, ApiError> {
+    let executor = authorize(&state, &headers)?;
+    executor.finish(run_id, outcome).map_err(run_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,6 +1,6 @@
 use axum::{
     extract::{Path, State},
-    http::{header, StatusCode},
+    http::StatusCode,
     Json,
 };
 use chrono::{Duration, Utc};
@@ -59,39 +59,24 @@ fn extract_user_from_auth(
     state: &ServerState,
     headers: &axum::http::HeaderMap,
 ) -> Result<(Uuid, String), (StatusCode, Json<FederationErrorResponse>)> {
-    let auth_header = headers
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(FederationErrorResponse {
-                    error: "Missing authorization".to_string(),
-                    code: "UNAUTHORIZED".to_string(),
-                }),
-            )
-        })?;
-
-    let token = if auth_header.starts_with("Bearer ") {
-        &auth_header[7..]
-    } else {
-        auth_header
+    let unauthorized = |error: &str| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(FederationErrorResponse {
+                error: error.to_string(),
+                code: "UNAUTHORIZED".to_string(),
+            }),
+        )
     };
 
-    state
+    let user_id = state
         .auth_state
-        .session_manager
-        .validate_token(token)
-        .and_then(|(_, user_id)| Uuid::try_parse(&user_id).ok().map(|uuid| (uuid, user_id)))
-        .ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(FederationErrorResponse {
-                    error: "Invalid session or user ID format".to_string(),
-                    code: "UNAUTHORIZED".to_string(),
-                }),
-            )
-        })
+        .authenticate(headers)
+        .map_err(|e| unauthorized(e.message()))?;
+    // Parse user_id as UUID - the system should store UUIDs
+    Uuid::try_parse(&user_id)
+        .map(|uuid| (uuid, user_id))
+        .map_err(|_| unauthorized("Invalid session or user ID format"))
 }
 
 /// Helper to get federation store using shared pool
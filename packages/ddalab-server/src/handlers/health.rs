@@ -1,8 +1,14 @@
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    Json,
+};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+use crate::maintenance::MaintenanceWindow;
 use crate::state::ServerState;
+use crate::storage::Announcement;
 
 /// Health check response
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,6 +27,12 @@ pub struct ServerInfoResponse {
     pub institution: String,
     pub features: ServerFeatures,
     pub encryption: String,
+    /// Active maintenance window, for clients to display
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<MaintenanceWindow>,
+    /// Current announcements, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub announcements: Vec<Announcement>,
 }
 
 /// Server features
@@ -29,6 +41,8 @@ pub struct ServerFeatures {
     pub server_side_analysis: bool,
     pub sharing: bool,
     pub encryption: bool,
+    /// Shares can be downloaded through the broker
+    pub relay: bool,
 }
 
 /// Health check endpoint
@@ -57,11 +71,25 @@ pub async fn server_info(
             server_side_analysis: state.config.enable_server_side_analysis,
             sharing: true,
             encryption: state.config.enable_encryption,
+            relay: state.relay.is_some(),
         },
         encryption: if state.config.enable_encryption {
             "aes256gcm".to_string()
         } else {
             "none".to_string()
         },
+        maintenance: state.maintenance.current(),
+        announcements: state.announcements.current(),
     })
 }
+
+/// Prometheus metrics endpoint
+pub async fn prometheus_metrics(
+    State(state): State<Arc<ServerState>>,
+) -> ([(header::HeaderName, &'static str); 1], String) {
+    let stats = state.job_queue.stats().await;
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&stats),
+    )
+}
@@ -1,16 +1,29 @@
 use crate::jobs::{
-    DDAJob, DDAParameters, FileSource, JobStatusResponse, QueueStats, SubmitJobResponse,
+    apply_preset, check_submission, thumbnail_path, write_thumbnail, DDAJob, DDAParameters, FileSource, JobPriority,
+    JobProgressEvent, JobStatus, JobStatusResponse, JobWindowEvent, PipelineStatusResponse, QueueStats, QuotaStatus,
+    ResourceRequirements, StoredUpload, SubmitJobResponse, UploadJobOptions,
 };
+use crate::crypto::read_file;
+use crate::handlers::datasets::dataset_access;
+use crate::handlers::egress::{record_egress, require_admin, EgressErrorResponse};
+use crate::handlers::listing::{Listing, ListingQuery, Page};
 use crate::state::ServerState;
+use crate::storage::{
+    DatasetPermission, EgressEntry, EgressKind, PostgresPresetStore, PostgresTeamStore, PresetStore, StorageError,
+    TeamPreset, TeamStore, UserPreset,
+};
+use crate::transfer::{throttled_body, TransferDecision};
 use axum::{
-    extract::{Multipart, Path, Query, State},
+    extract::{ConnectInfo, Multipart, Path, Query, State},
     http::{header, StatusCode},
     response::sse::{Event, KeepAlive, Sse},
+    response::{IntoResponse, Response},
     Json,
 };
 use futures::stream::Stream;
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tracing::{error, info, warn};
@@ -18,27 +31,139 @@ use uuid::Uuid;
 
 /// Extract authenticated user ID from request headers.
 /// Returns the user email from the session, or "anonymous" if auth is not required.
-fn extract_user_id(state: &ServerState, headers: &axum::http::HeaderMap) -> String {
-    let token = headers
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .map(|v| {
-            if v.starts_with("Bearer ") {
-                &v[7..]
-            } else {
-                v
+pub(super) fn extract_user_id(state: &ServerState, headers: &axum::http::HeaderMap) -> String {
+    state
+        .auth_state
+        .authenticate(headers)
+        .unwrap_or_else(|_| "anonymous".to_string())
+}
+
+/// Refuse new jobs while a maintenance window is active
+pub(super) fn reject_during_maintenance(state: &ServerState) -> Result<(), (StatusCode, String)> {
+    match state.maintenance.rejection_message() {
+        Some(message) => Err((StatusCode::SERVICE_UNAVAILABLE, message)),
+        None => Ok(()),
+    }
+}
+
+/// The parameter presets of the submitter's teams
+async fn team_presets_for(
+    state: &ServerState,
+    user_id: &str,
+) -> Result<Vec<TeamPreset>, (StatusCode, String)> {
+    // Sessions carry the user's email; anonymous submitters belong to no team
+    let user_uuid = match Uuid::try_parse(user_id) {
+        Ok(user_uuid) => user_uuid,
+        Err(_) => match state.user_store.get_user_by_email(user_id).await {
+            Ok(user) => user.id,
+            Err(_) => return Ok(Vec::new()),
+        },
+    };
+    PostgresTeamStore::new(state.db_pool.clone())
+        .list_user_presets(user_uuid)
+        .await
+        .map_err(|e| {
+            error!("Failed to load team presets: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load team presets".to_string(),
+            )
+        })
+}
+
+/// One of the submitter's own presets
+async fn personal_preset(
+    state: &ServerState,
+    user_id: &str,
+    preset_id: Uuid,
+) -> Result<UserPreset, (StatusCode, String)> {
+    match PostgresPresetStore::new(state.db_pool.clone())
+        .get_preset(preset_id)
+        .await
+    {
+        Ok(preset) if preset.user_id == user_id => Ok(preset),
+        Ok(_) | Err(StorageError::NotFound(_)) => Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Preset {} is not available to you", preset_id),
+        )),
+        Err(e) => {
+            error!("Failed to load preset {}: {}", preset_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to load preset".to_string(),
+            ))
+        }
+    }
+}
+
+/// A job's parameters from its preset and the fields it sets itself
+///
+/// `preset_id` may name one of the submitter's own presets or one of their
+/// teams' presets. Team presets stay binding: locked fields must keep the
+/// preset's values, and a team that requires a preset is not satisfied by
+/// a personal one.
+async fn resolve_parameters(
+    state: &ServerState,
+    user_id: &str,
+    preset_id: Option<Uuid>,
+    submitted: &serde_json::Map<String, serde_json::Value>,
+) -> Result<DDAParameters, (StatusCode, String)> {
+    let team_presets = team_presets_for(state, user_id).await?;
+    let mut team_preset_id = None;
+    let preset_parameters = match preset_id {
+        Some(id) => match team_presets.iter().find(|preset| preset.id == id) {
+            Some(preset) => {
+                team_preset_id = Some(id);
+                Some(preset.parameters.clone())
             }
-        });
+            None => Some(personal_preset(state, user_id, id).await?.parameters),
+        },
+        None => None,
+    };
+    let parameters = apply_preset(preset_parameters.as_ref(), submitted)
+        .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+
+    check_preprocessing(&parameters)?;
+    check_submission(&team_presets, team_preset_id, &parameters)
+        .map_err(|message| (StatusCode::UNPROCESSABLE_ENTITY, message))?;
+    Ok(parameters)
+}
+
+/// Refuse preprocessing the binary could not apply
+fn check_preprocessing(parameters: &DDAParameters) -> Result<(), (StatusCode, String)> {
+    parameters
+        .preprocessing
+        .validate()
+        .map_err(|message| (StatusCode::BAD_REQUEST, message))
+}
+
+/// Refuse a job that would take the submitter past their quota
+pub(super) async fn enforce_user_quota(
+    state: &ServerState,
+    user_id: &str,
+    incoming_bytes: u64,
+) -> Result<(), (StatusCode, String)> {
+    state
+        .job_queue
+        .check_quota(user_id, incoming_bytes)
+        .await
+        .map_err(|message| (StatusCode::TOO_MANY_REQUESTS, message))
+}
 
-    match token {
-        Some(t) => state
-            .auth_state
-            .session_manager
-            .validate_token(t)
-            .map(|(_, user_id)| user_id)
-            .unwrap_or_else(|| "anonymous".to_string()),
-        None => "anonymous".to_string(),
+/// Refuse dependencies on jobs the queue does not know
+async fn check_dependencies(
+    state: &ServerState,
+    depends_on: &[Uuid],
+) -> Result<(), (StatusCode, String)> {
+    for job_id in depends_on {
+        if state.job_queue.get_job(*job_id).await.is_none() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Unknown dependency job {}", job_id),
+            ));
+        }
     }
+    Ok(())
 }
 
 /// Query params for listing jobs
@@ -46,8 +171,8 @@ fn extract_user_id(state: &ServerState, headers: &axum::http::HeaderMap) -> Stri
 pub struct ListJobsQuery {
     /// Filter by user ID (admin can see all, users see their own)
     pub user_id: Option<String>,
-    /// Filter by status
-    pub status: Option<String>,
+    /// Only jobs created by this recurring schedule
+    pub schedule_id: Option<Uuid>,
 }
 
 /// Request to submit job for server-side file
@@ -55,8 +180,27 @@ pub struct ListJobsQuery {
 pub struct SubmitServerFileRequest {
     /// Path to file on server (relative to server_files_directory)
     pub server_path: String,
-    /// DDA parameters
-    pub parameters: DDAParameters,
+    /// DDA parameter fields, applied over those of the preset
+    #[serde(default)]
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+    /// Personal or team parameter preset the job runs with
+    #[serde(default)]
+    pub preset_id: Option<Uuid>,
+    /// Scheduling class; `normal` when omitted
+    #[serde(default)]
+    pub priority: JobPriority,
+    /// Jobs that must complete before this one starts
+    #[serde(default)]
+    pub depends_on: Vec<Uuid>,
+    /// Channels and run time, used to pick the job's worker pool
+    #[serde(default)]
+    pub requirements: ResourceRequirements,
+}
+
+/// Request to move a pending job to another priority class
+#[derive(Debug, Deserialize)]
+pub struct SetJobPriorityRequest {
+    pub priority: JobPriority,
 }
 
 /// Response for file upload
@@ -73,6 +217,7 @@ pub async fn submit_server_file_job(
     headers: axum::http::HeaderMap,
     Json(request): Json<SubmitServerFileRequest>,
 ) -> Result<Json<SubmitJobResponse>, (StatusCode, String)> {
+    reject_during_maintenance(&state)?;
     let user_id = extract_user_id(&state, &headers);
     // Validate server-side file access is enabled
     let server_files_dir = state.config.server_files_directory.as_ref().ok_or_else(|| {
@@ -140,20 +285,49 @@ pub async fn submit_server_file_job(
         return Err((StatusCode::NOT_FOUND, "File not found".to_string()));
     }
 
+    // Files of datasets the submitter cannot see are reported as missing
+    let relative_path = canonical_path
+        .strip_prefix(&canonical_base)
+        .unwrap_or(&canonical_path);
+    match dataset_access(&state, &user_id).await?.permission(relative_path) {
+        Some(DatasetPermission::Submit) => {}
+        Some(DatasetPermission::Read) => {
+            warn!(
+                "{} may read but not analyze {}",
+                user_id,
+                relative_path.display()
+            );
+            return Err((
+                StatusCode::FORBIDDEN,
+                "You may view this dataset but not submit jobs on it".to_string(),
+            ));
+        }
+        None => return Err((StatusCode::NOT_FOUND, "File not found".to_string())),
+    }
+
     // Extract filename for display
     let filename = canonical_path
         .file_name()
         .map(|n| n.to_string_lossy().to_string())
         .unwrap_or_else(|| "unknown".to_string());
 
+    let parameters =
+        resolve_parameters(&state, &user_id, request.preset_id, &request.parameters).await?;
+    check_dependencies(&state, &request.depends_on).await?;
+    enforce_user_quota(&state, &user_id, 0).await?;
+
     // Create job
     let job = DDAJob::new(
         user_id,
         FileSource::ServerPath(canonical_path),
         filename,
-        request.parameters,
+        parameters,
         false, // Don't delete server-side files
-    );
+    )
+    .with_preset(request.preset_id)
+    .with_priority(request.priority)
+    .with_dependencies(request.depends_on)
+    .with_requirements(request.requirements);
 
     let job_id = job.id;
 
@@ -170,7 +344,7 @@ pub async fn submit_server_file_job(
 
     Ok(Json(SubmitJobResponse {
         job_id,
-        status: crate::jobs::JobStatus::Pending,
+        status: JobStatus::Pending,
         message: "Job submitted successfully".to_string(),
     }))
 }
@@ -181,11 +355,10 @@ pub async fn upload_and_submit_job(
     headers: axum::http::HeaderMap,
     mut multipart: Multipart,
 ) -> Result<Json<SubmitJobResponse>, (StatusCode, String)> {
+    reject_during_maintenance(&state)?;
     let user_id = extract_user_id(&state, &headers);
-    let mut uploaded_file: Option<(PathBuf, String)> = None;
-    let mut parameters: Option<DDAParameters> = None;
-    let mut delete_after = true;
-    let mut persist_upload = false;
+    let mut uploaded_file: Option<(StoredUpload, String)> = None;
+    let mut options = UploadJobOptions::default();
 
     // Process multipart form
     while let Some(field) = multipart.next_field().await.map_err(|e| {
@@ -220,37 +393,30 @@ pub async fn upload_and_submit_job(
                         ),
                     ));
                 }
+                enforce_user_quota(&state, &user_id, data.len() as u64).await?;
 
-                // Create upload directory if needed
-                tokio::fs::create_dir_all(&state.config.upload_directory)
+                let size = data.len();
+                let stored = state
+                    .job_queue
+                    .blobs()
+                    .store_bytes(&filename, data, state.config.storage_encryption.as_ref())
                     .await
                     .map_err(|e| {
-                        error!("Failed to create upload directory: {}", e);
+                        error!("Failed to save uploaded file: {}", e);
                         (
                             StatusCode::INTERNAL_SERVER_ERROR,
-                            "Upload failed".to_string(),
+                            "Failed to save file".to_string(),
                         )
                     })?;
 
-                // Save file with unique name
-                let upload_id = Uuid::new_v4();
-                let ext = std::path::Path::new(&filename)
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .unwrap_or("edf");
-                let saved_filename = format!("{}_{}.{}", upload_id, sanitize_filename(&filename), ext);
-                let file_path = state.config.upload_directory.join(&saved_filename);
-
-                tokio::fs::write(&file_path, &data).await.map_err(|e| {
-                    error!("Failed to save uploaded file: {}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Failed to save file".to_string(),
-                    )
-                })?;
-
-                info!("File uploaded: {} ({} bytes)", file_path.display(), data.len());
-                uploaded_file = Some((file_path, filename));
+                info!(
+                    "File uploaded: {} ({} bytes{})",
+                    stored.path.display(),
+                    size,
+                    if stored.deduplicated { ", deduplicated" } else { "" }
+                );
+                state.metrics.observe_upload(size as u64);
+                uploaded_file = Some((stored, filename));
             }
             "parameters" => {
                 let text = field.text().await.map_err(|e| {
@@ -259,20 +425,63 @@ pub async fn upload_and_submit_job(
                         format!("Failed to read parameters: {}", e),
                     )
                 })?;
-                parameters = Some(serde_json::from_str(&text).map_err(|e| {
+                options.parameters = serde_json::from_str(&text).map_err(|e| {
                     (
                         StatusCode::BAD_REQUEST,
                         format!("Invalid parameters JSON: {}", e),
                     )
-                })?);
+                })?;
             }
             "delete_after" => {
                 let text = field.text().await.unwrap_or_default();
-                delete_after = text.to_lowercase() == "true";
+                options.delete_after = text.to_lowercase() == "true";
             }
             "persist_upload" => {
                 let text = field.text().await.unwrap_or_default();
-                persist_upload = text.to_lowercase() == "true";
+                options.persist_upload = text.to_lowercase() == "true";
+            }
+            "preset_id" => {
+                let text = field.text().await.unwrap_or_default();
+                options.preset_id = Some(Uuid::try_parse(text.trim()).map_err(|e| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid preset_id: {}", e),
+                    )
+                })?);
+            }
+            "priority" => {
+                let text = field.text().await.unwrap_or_default();
+                options.priority = serde_json::from_value(serde_json::Value::String(text.trim().to_string()))
+                    .map_err(|_| {
+                        (
+                            StatusCode::BAD_REQUEST,
+                            format!("Invalid priority '{}': expected interactive, normal or batch", text.trim()),
+                        )
+                    })?;
+            }
+            "requirements" => {
+                let text = field.text().await.unwrap_or_default();
+                options.requirements = serde_json::from_str(&text).map_err(|e| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid requirements: {}", e),
+                    )
+                })?;
+            }
+            "depends_on" => {
+                let text = field.text().await.unwrap_or_default();
+                options.depends_on = text
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|id| !id.is_empty())
+                    .map(Uuid::try_parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| {
+                        (
+                            StatusCode::BAD_REQUEST,
+                            format!("Invalid depends_on: {}", e),
+                        )
+                    })?;
             }
             _ => {
                 // Ignore unknown fields
@@ -281,17 +490,36 @@ pub async fn upload_and_submit_job(
     }
 
     // Validate we have required data
-    let (file_path, filename) = uploaded_file.ok_or_else(|| {
+    let (stored, filename) = uploaded_file.ok_or_else(|| {
         (StatusCode::BAD_REQUEST, "No file provided".to_string())
     })?;
 
-    let params = parameters.unwrap_or_default();
+    submit_upload(&state, user_id, stored, filename, options).await
+}
+
+/// Submit the job for an upload in the store; the job's reference to the
+/// upload is dropped if the job is refused
+pub(super) async fn submit_upload(
+    state: &ServerState,
+    user_id: String,
+    stored: StoredUpload,
+    filename: String,
+    options: UploadJobOptions,
+) -> Result<Json<SubmitJobResponse>, (StatusCode, String)> {
+    let blobs = state.job_queue.blobs();
+    let parameters = match check_upload_job(state, &user_id, &options).await {
+        Ok(parameters) => parameters,
+        Err(rejection) => {
+            blobs.discard(&stored.path).await;
+            return Err(rejection);
+        }
+    };
 
     // Determine file source type
-    let file_source = if persist_upload {
-        FileSource::UploadedPersistent(file_path)
+    let file_source = if options.persist_upload {
+        FileSource::UploadedPersistent(stored.path.clone())
     } else {
-        FileSource::UploadedTemp(file_path)
+        FileSource::UploadedTemp(stored.path.clone())
     };
 
     // Create job
@@ -299,30 +527,49 @@ pub async fn upload_and_submit_job(
         user_id,
         file_source,
         filename,
-        params,
-        delete_after && !persist_upload,
-    );
+        parameters,
+        options.delete_after && !options.persist_upload,
+    )
+    .with_preset(options.preset_id)
+    .with_priority(options.priority)
+    .with_dependencies(options.depends_on)
+    .with_requirements(options.requirements)
+    .with_file_hash(stored.hash);
 
     let job_id = job.id;
 
     // Submit to queue
-    state.job_queue.submit(job).await.map_err(|e| {
+    if let Err(e) = state.job_queue.submit(job).await {
         error!("Failed to submit job: {}", e);
-        (
+        blobs.discard(&stored.path).await;
+        return Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Failed to submit job: {}", e),
-        )
-    })?;
+        ));
+    }
 
     info!("Job {} submitted with uploaded file", job_id);
 
     Ok(Json(SubmitJobResponse {
         job_id,
-        status: crate::jobs::JobStatus::Pending,
+        status: JobStatus::Pending,
         message: "Job submitted successfully".to_string(),
     }))
 }
 
+/// Check an upload's job against presets and its dependencies, returning
+/// the parameters it runs with
+pub(super) async fn check_upload_job(
+    state: &ServerState,
+    user_id: &str,
+    options: &UploadJobOptions,
+) -> Result<DDAParameters, (StatusCode, String)> {
+    let parameters =
+        resolve_parameters(state, user_id, options.preset_id, &options.parameters).await?;
+    check_dependencies(state, &options.depends_on).await?;
+    Ok(parameters)
+}
+
 /// Get job status
 pub async fn get_job_status(
     State(state): State<Arc<ServerState>>,
@@ -335,19 +582,63 @@ pub async fn get_job_status(
     Ok(Json(JobStatusResponse::from(&job)))
 }
 
-/// List jobs
+/// Get the status of every job in a job's dependency chain
+pub async fn get_job_pipeline(
+    State(state): State<Arc<ServerState>>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<PipelineStatusResponse>, (StatusCode, String)> {
+    let jobs = state.job_queue.pipeline(job_id).await.ok_or_else(|| {
+        (StatusCode::NOT_FOUND, "Job not found".to_string())
+    })?;
+
+    Ok(Json(PipelineStatusResponse::new(jobs)))
+}
+
+/// Fields of `JobStatusResponse` that job listings can sort by and select
+const JOB_LISTING: Listing = Listing {
+    fields: &[
+        "id",
+        "file_name",
+        "status",
+        "priority",
+        "progress",
+        "message",
+        "output_path",
+        "error",
+        "submitted_at",
+        "started_at",
+        "completed_at",
+        "schedule_id",
+        "preset_id",
+        "environment",
+        "depends_on",
+        "pool",
+    ],
+    key: "id",
+    default_sort: "-submitted_at",
+    status_field: Some("status"),
+    date_field: Some("submitted_at"),
+    name_field: Some("file_name"),
+};
+
+/// List jobs, newest first unless `sort` says otherwise
 pub async fn list_jobs(
     State(state): State<Arc<ServerState>>,
     Query(query): Query<ListJobsQuery>,
-) -> Result<Json<Vec<JobStatusResponse>>, (StatusCode, String)> {
+    Query(listing): Query<ListingQuery>,
+) -> Result<Page, (StatusCode, String)> {
     let jobs = if let Some(user_id) = query.user_id {
         state.job_queue.get_user_jobs(&user_id).await
     } else {
         state.job_queue.get_all_jobs().await
     };
 
-    let responses: Vec<JobStatusResponse> = jobs.iter().map(JobStatusResponse::from).collect();
-    Ok(Json(responses))
+    let responses: Vec<JobStatusResponse> = jobs
+        .iter()
+        .filter(|job| query.schedule_id.is_none() || job.schedule_id == query.schedule_id)
+        .map(JobStatusResponse::from)
+        .collect();
+    listing.apply(&responses, &JOB_LISTING)
 }
 
 /// Cancel a job
@@ -376,6 +667,53 @@ pub async fn cancel_job(
     }
 }
 
+/// Move a pending job to another priority class (admin only)
+pub async fn set_job_priority(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+    Path(job_id): Path<Uuid>,
+    Json(request): Json<SetJobPriorityRequest>,
+) -> Result<Json<JobStatusResponse>, (StatusCode, Json<EgressErrorResponse>)> {
+    let admin = require_admin(&state, &headers).await?;
+    let error = |status: StatusCode, message: &str, code: &str| {
+        (
+            status,
+            Json(EgressErrorResponse {
+                error: message.to_string(),
+                code: code.to_string(),
+            }),
+        )
+    };
+
+    if !state.job_queue.set_priority(job_id, request.priority).await {
+        return Err(match state.job_queue.get_job(job_id).await {
+            Some(job) => error(
+                StatusCode::CONFLICT,
+                &format!("Job is {}; only pending jobs can be reprioritized", job.status),
+                "JOB_NOT_PENDING",
+            ),
+            None => error(StatusCode::NOT_FOUND, "Job not found", "NOT_FOUND"),
+        });
+    }
+    info!("{} set job {} to {} priority", admin, job_id, request.priority);
+
+    let job = state
+        .job_queue
+        .get_job(job_id)
+        .await
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, "Job not found", "NOT_FOUND"))?;
+    Ok(Json(JobStatusResponse::from(&job)))
+}
+
+/// The caller's job and storage usage next to their quota
+pub async fn get_my_usage(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+) -> Json<QuotaStatus> {
+    let user_id = extract_user_id(&state, &headers);
+    Json(state.job_queue.quota_status(&user_id).await)
+}
+
 /// Get queue statistics
 pub async fn get_queue_stats(
     State(state): State<Arc<ServerState>>,
@@ -384,13 +722,20 @@ pub async fn get_queue_stats(
 }
 
 /// Download job results
+///
+/// The server's transfer policy applies: the response is paced to the
+/// bandwidth cap, and results above the deferral size are refused with
+/// `503` and `Retry-After` until the off-peak window opens.
 pub async fn download_job_results(
     State(state): State<Arc<ServerState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: axum::http::HeaderMap,
     Path(job_id): Path<Uuid>,
-) -> Result<(StatusCode, Vec<u8>), (StatusCode, String)> {
+) -> Result<Response, (StatusCode, String)> {
     let job = state.job_queue.get_job(job_id).await.ok_or_else(|| {
         (StatusCode::NOT_FOUND, "Job not found".to_string())
     })?;
+    let source_dataset = job.input_path();
 
     let output_path = job.output_path.ok_or_else(|| {
         (
@@ -399,7 +744,35 @@ pub async fn download_job_results(
         )
     })?;
 
-    let data = tokio::fs::read(&output_path).await.map_err(|e| {
+    let size = tokio::fs::metadata(&output_path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let now = chrono::Utc::now();
+    let max_bytes_per_second = match state.config.transfer_policy.decide(size, now) {
+        TransferDecision::Allow {
+            max_bytes_per_second,
+        } => max_bytes_per_second,
+        TransferDecision::Defer { until } => {
+            let retry_after = (until - now).num_seconds().max(1);
+            info!(
+                "Deferring {} byte download of job {} until {}",
+                size, job_id, until
+            );
+            return Ok((
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                format!(
+                    "Results of {} bytes are only sent off-peak; retry after {}",
+                    size,
+                    until.to_rfc3339()
+                ),
+            )
+                .into_response());
+        }
+    };
+
+    let data = read_stored(&state, output_path).await.map_err(|e| {
         error!("Failed to read job output: {}", e);
         (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -407,7 +780,82 @@ pub async fn download_job_results(
         )
     })?;
 
-    Ok((StatusCode::OK, data))
+    // Record egress with lineage back to the analyzed input file
+    let user_id = extract_user_id(&state, &headers);
+    let entry = EgressEntry::new(
+        EgressKind::JobResultDownload,
+        &user_id,
+        &job_id.to_string(),
+        data.len(),
+    )
+    .ip_address(&addr.ip().to_string())
+    .source_dataset(&source_dataset.to_string_lossy());
+    record_egress(&state, entry);
+
+    Ok((StatusCode::OK, throttled_body(data, max_bytes_per_second)).into_response())
+}
+
+/// Read a result or upload, decrypting it if it is encrypted at rest
+async fn read_stored(state: &ServerState, path: PathBuf) -> std::io::Result<Vec<u8>> {
+    let key = state.config.storage_encryption.clone();
+    tokio::task::spawn_blocking(move || read_file(key.as_ref(), &path))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// Heatmap thumbnail of a completed job's primary Q matrix
+///
+/// Rendered when the job completes; results from before thumbnails existed
+/// are rendered on first request.
+pub async fn get_job_thumbnail(
+    State(state): State<Arc<ServerState>>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Response, (StatusCode, String)> {
+    let job = state.job_queue.get_job(job_id).await.ok_or_else(|| {
+        (StatusCode::NOT_FOUND, "Job not found".to_string())
+    })?;
+
+    let output_path = job.output_path.ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            "Job has no output (not completed or failed)".to_string(),
+        )
+    })?;
+
+    let path = thumbnail_path(&output_path);
+    let png = match read_stored(&state, path).await {
+        Ok(png) => png,
+        Err(_) => {
+            let key = state.config.storage_encryption.clone();
+            let path = tokio::task::spawn_blocking(move || write_thumbnail(&output_path, key.as_ref()))
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+                .map_err(|e| {
+                    warn!("Failed to render thumbnail for job {}: {}", job_id, e);
+                    (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "Results cannot be rendered as a heatmap".to_string(),
+                    )
+                })?;
+            read_stored(&state, path).await.map_err(|e| {
+                error!("Failed to read thumbnail: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to read thumbnail".to_string(),
+                )
+            })?
+        }
+    };
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, "private, max-age=3600"),
+        ],
+        png,
+    )
+        .into_response())
 }
 
 /// SSE endpoint for job progress updates
@@ -437,6 +885,94 @@ pub async fn job_progress_stream(
     Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
+/// SSE stream of a job's Q values window by window, so clients can draw the
+/// heatmap while the job runs
+///
+/// Sends the rows reported so far, then each new row as a `window` event,
+/// and a final `done` event with the job's status once it finishes.
+pub async fn job_result_stream(
+    State(state): State<Arc<ServerState>>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    use tokio::sync::broadcast::error::RecvError;
+
+    // Subscribe before looking the job up so its end cannot slip past
+    let mut progress = state.job_queue.subscribe();
+    let (buffered, mut windows) = state.job_queue.subscribe_windows(job_id);
+    let job = state.job_queue.get_job(job_id).await.ok_or_else(|| {
+        (StatusCode::NOT_FOUND, "Job not found".to_string())
+    })?;
+    let finished = move |job: &DDAJob| {
+        matches!(
+            job.status,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+        )
+        .then(|| JobProgressEvent {
+            job_id,
+            status: job.status,
+            progress: job.progress,
+            message: job.message.clone(),
+        })
+    };
+
+    let window_event = |event: &JobWindowEvent| {
+        let data = serde_json::to_string(event).unwrap_or_default();
+        Ok(Event::default().data(data).event("window"))
+    };
+    let done_event = |event: &JobProgressEvent| {
+        let data = serde_json::to_string(event).unwrap_or_default();
+        Ok(Event::default().data(data).event("done"))
+    };
+
+    let stream = async_stream::stream! {
+        for row in buffered {
+            yield window_event(&JobWindowEvent { job_id, row });
+        }
+        if let Some(event) = finished(&job) {
+            yield done_event(&event);
+            return;
+        }
+        loop {
+            // Rows first, so none are left behind when the job ends
+            tokio::select! {
+                biased;
+                event = windows.recv() => match event {
+                    Ok(event) if event.job_id == job_id => yield window_event(&event),
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("Result stream of job {} lagged, missed {} rows", job_id, n);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                event = progress.recv() => match event {
+                    Ok(event)
+                        if event.job_id == job_id
+                            && matches!(
+                                event.status,
+                                JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+                            ) =>
+                    {
+                        yield done_event(&event);
+                        break;
+                    }
+                    Ok(_) => {}
+                    // The job may have ended among the missed updates
+                    Err(RecvError::Lagged(_)) => {
+                        let ended = state.job_queue.get_job(job_id).await;
+                        if let Some(event) = ended.as_ref().and_then(finished) {
+                            yield done_event(&event);
+                            break;
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
 /// List available server-side files
 #[derive(Debug, Serialize)]
 pub struct ServerFileInfo {
@@ -446,10 +982,23 @@ pub struct ServerFileInfo {
     pub is_directory: bool,
 }
 
+/// Fields of `ServerFileInfo` that file listings can sort by and select;
+/// directories come first, then entries by name
+const FILE_LISTING: Listing = Listing {
+    fields: &["path", "name", "size", "is_directory"],
+    key: "path",
+    default_sort: "-is_directory",
+    status_field: None,
+    date_field: None,
+    name_field: Some("name"),
+};
+
 pub async fn list_server_files(
     State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
     Query(query): Query<ListServerFilesQuery>,
-) -> Result<Json<Vec<ServerFileInfo>>, (StatusCode, String)> {
+    Query(listing): Query<ListingQuery>,
+) -> Result<Page, (StatusCode, String)> {
     let server_files_dir = state.config.server_files_directory.as_ref().ok_or_else(|| {
         (
             StatusCode::BAD_REQUEST,
@@ -501,6 +1050,15 @@ pub async fn list_server_files(
         return Err((StatusCode::FORBIDDEN, "Access denied".to_string()));
     }
 
+    // Directories of datasets the user cannot see are reported as missing
+    let access = dataset_access(&state, &extract_user_id(&state, &headers)).await?;
+    let relative_target = canonical_target
+        .strip_prefix(&canonical_base)
+        .unwrap_or(&canonical_target);
+    if !access.visible(relative_target) {
+        return Err((StatusCode::NOT_FOUND, "Directory not found".to_string()));
+    }
+
     let mut entries = Vec::new();
     let mut read_dir = tokio::fs::read_dir(&canonical_target).await.map_err(|e| {
         (
@@ -526,6 +1084,9 @@ pub async fn list_server_files(
             .unwrap_or(&full_path)
             .to_string_lossy()
             .to_string();
+        if !access.visible(std::path::Path::new(&relative_path)) {
+            continue;
+        }
 
         entries.push(ServerFileInfo {
             path: relative_path,
@@ -535,16 +1096,7 @@ pub async fn list_server_files(
         });
     }
 
-    // Sort: directories first, then by name
-    entries.sort_by(|a, b| {
-        match (a.is_directory, b.is_directory) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
-        }
-    });
-
-    Ok(Json(entries))
+    listing.apply(&entries, &FILE_LISTING)
 }
 
 #[derive(Debug, Deserialize)]
@@ -552,11 +1104,3 @@ pub struct ListServerFilesQuery {
     pub path: Option<String>,
 }
 
-/// Sanitize filename for safe storage
-fn sanitize_filename(filename: &str) -> String {
-    filename
-        .chars()
-        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_' || *c == '.')
-        .take(100)
-        .collect()
-}
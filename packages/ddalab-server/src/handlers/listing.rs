@@ -0,0 +1,428 @@
+//! Pagination, sorting, filtering and field selection for listing endpoints
+//!
+//! Listings accept `?limit=&cursor=&sort=&fields=` next to their own filters.
+//! `sort` names a field of the listed items, prefixed with `-` for descending
+//! order, and `fields` is a comma-separated list of the fields to return
+//! (`?fields=id,status,submitted_at`). Listings that have a status, a date or
+//! a name also accept `status` (comma-separated), `since`/`until` (RFC 3339)
+//! and `prefix`.
+//!
+//! Every listing answers with the same envelope:
+//! `{"items": [...], "total": 42, "next_cursor": "..."}`. `total` counts the
+//! items that pass the filters, also sent as `X-Total-Count`. Passing
+//! `next_cursor` back as `cursor` continues after the last item of the page,
+//! so items added or removed meanwhile do not shift the pages.
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+/// Page size when the client does not ask for one
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page a client can ask for
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// What a listing's items look like
+#[derive(Debug, Clone, Copy)]
+pub struct Listing {
+    /// Serialized fields that can be sorted by and selected
+    pub fields: &'static [&'static str],
+    /// Field that identifies an item; it breaks sort ties and anchors cursors
+    pub key: &'static str,
+    /// Order when the client does not pass `sort`
+    pub default_sort: &'static str,
+    /// Field matched by `status`
+    pub status_field: Option<&'static str>,
+    /// Timestamp field bounded by `since` and `until`
+    pub date_field: Option<&'static str>,
+    /// Field matched by `prefix`
+    pub name_field: Option<&'static str>,
+}
+
+/// Query parameters shared by listing endpoints
+#[derive(Debug, Default, Deserialize)]
+pub struct ListingQuery {
+    /// Page size, at most [`MAX_PAGE_SIZE`]
+    pub limit: Option<usize>,
+    /// Items to skip; prefer `cursor`, which survives concurrent changes
+    #[serde(default)]
+    pub offset: usize,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    /// Field to sort by, `-field` for descending
+    pub sort: Option<String>,
+    /// Comma-separated fields to keep in each item
+    pub fields: Option<String>,
+    /// Comma-separated statuses to keep
+    pub status: Option<String>,
+    /// Keep items dated at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Keep items dated before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Keep items whose name starts with this
+    pub prefix: Option<String>,
+}
+
+/// One page of a listing
+#[derive(Debug, Serialize)]
+pub struct Page {
+    pub items: Vec<Value>,
+    pub total: usize,
+    pub next_cursor: Option<String>,
+}
+
+impl IntoResponse for Page {
+    fn into_response(self) -> Response {
+        ([("x-total-count", self.total.to_string())], Json(self)).into_response()
+    }
+}
+
+fn bad_request(message: String) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, message)
+}
+
+/// Where a page ends: the sort it was taken in and the last item's position
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    value: Value,
+    key: Value,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(text.trim()).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+impl ListingQuery {
+    /// Filter, sort, page and trim `items` as described by `listing`
+    pub fn apply<T: Serialize>(
+        &self,
+        items: &[T],
+        listing: &Listing,
+    ) -> Result<Page, (StatusCode, String)> {
+        let known = |field: &str| -> Result<(), (StatusCode, String)> {
+            if listing.fields.contains(&field) {
+                Ok(())
+            } else {
+                Err(bad_request(format!(
+                    "Unknown field '{}'; expected one of: {}",
+                    field,
+                    listing.fields.join(", ")
+                )))
+            }
+        };
+        let supported = |filter: &str, field: Option<&'static str>| {
+            field.ok_or_else(|| bad_request(format!("This listing cannot filter by {}", filter)))
+        };
+
+        let sort = self
+            .sort
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .unwrap_or(listing.default_sort);
+        let (sort_field, descending) = match sort.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (sort, false),
+        };
+        known(sort_field)?;
+        let selected = match &self.fields {
+            Some(list) => {
+                let selected: Vec<&str> = list
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|f| !f.is_empty())
+                    .collect();
+                for field in &selected {
+                    known(field)?;
+                }
+                Some(selected)
+            }
+            None => None,
+        };
+        let cursor = match self.cursor.as_deref().filter(|c| !c.trim().is_empty()) {
+            Some(text) => {
+                if self.offset > 0 {
+                    return Err(bad_request(
+                        "Pass either cursor or offset, not both".to_string(),
+                    ));
+                }
+                let cursor = Cursor::decode(text)
+                    .ok_or_else(|| bad_request("Invalid cursor".to_string()))?;
+                if cursor.sort != sort {
+                    return Err(bad_request(
+                        "Cursor belongs to a listing with a different sort".to_string(),
+                    ));
+                }
+                Some(cursor)
+            }
+            None => None,
+        };
+
+        let statuses: Option<Vec<String>> = match &self.status {
+            Some(list) => {
+                supported("status", listing.status_field)?;
+                Some(
+                    list.split(',')
+                        .map(|s| s.trim().to_ascii_lowercase())
+                        .filter(|s| !s.is_empty())
+                        .collect(),
+                )
+            }
+            None => None,
+        };
+        if self.since.is_some() || self.until.is_some() {
+            supported("date", listing.date_field)?;
+        }
+        if self.prefix.is_some() {
+            supported("name prefix", listing.name_field)?;
+        }
+
+        let mut values = items
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<Value>, _>>()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        values.retain(|item| {
+            let status_matches = match (&statuses, listing.status_field) {
+                (Some(statuses), Some(field)) => item[field]
+                    .as_str()
+                    .is_some_and(|status| statuses.iter().any(|s| s.eq_ignore_ascii_case(status))),
+                _ => true,
+            };
+            let date_matches = match listing.date_field {
+                Some(field) if self.since.is_some() || self.until.is_some() => {
+                    match item[field].as_str().map(DateTime::parse_from_rfc3339) {
+                        Some(Ok(date)) => {
+                            self.since.is_none_or(|since| date >= since)
+                                && self.until.is_none_or(|until| date < until)
+                        }
+                        _ => false,
+                    }
+                }
+                _ => true,
+            };
+            let name_matches = match (&self.prefix, listing.name_field) {
+                (Some(prefix), Some(field)) => item[field]
+                    .as_str()
+                    .is_some_and(|name| name.starts_with(prefix.as_str())),
+                _ => true,
+            };
+            status_matches && date_matches && name_matches
+        });
+
+        // Ties follow the key in ascending order whatever the sort direction
+        let order = |a_value: &Value, a_key: &Value, b_value: &Value, b_key: &Value| {
+            let ordering = compare(a_value, b_value);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+            .then_with(|| compare(a_key, b_key))
+        };
+        values.sort_by(|a, b| {
+            order(
+                &a[sort_field],
+                &a[listing.key],
+                &b[sort_field],
+                &b[listing.key],
+            )
+        });
+
+        let total = values.len();
+        let limit = self
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let start = match &cursor {
+            Some(cursor) => values.partition_point(|item| {
+                order(
+                    &item[sort_field],
+                    &item[listing.key],
+                    &cursor.value,
+                    &cursor.key,
+                ) != Ordering::Greater
+            }),
+            None => self.offset.min(total),
+        };
+        let end = (start + limit).min(total);
+        let next_cursor = (end < total && end > start).then(|| {
+            let last = &values[end - 1];
+            Cursor {
+                sort: sort.to_string(),
+                value: last[sort_field].clone(),
+                key: last[listing.key].clone(),
+            }
+            .encode()
+        });
+
+        let items = values
+            .drain(start..end)
+            .map(|value| match (&selected, value) {
+                (Some(selected), Value::Object(mut object)) => Value::Object(
+                    selected
+                        .iter()
+                        .filter_map(|f| object.remove(*f).map(|v| (f.to_string(), v)))
+                        .collect::<Map<String, Value>>(),
+                ),
+                (_, value) => value,
+            })
+            .collect();
+
+        Ok(Page {
+            items,
+            total,
+            next_cursor,
+        })
+    }
+}
+
+/// Order two field values: missing first, then numbers, timestamps and text
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Less,
+        (_, Value::Null) => Ordering::Greater,
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::String(a), Value::String(b)) => {
+            // RFC 3339 text does not sort by time when fractions differ in length
+            match (
+                chrono::DateTime::parse_from_rfc3339(a),
+                chrono::DateTime::parse_from_rfc3339(b),
+            ) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b),
+            }
+        }
+        (a, b) => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const LISTING: Listing = Listing {
+        fields: &["name", "size", "at", "state"],
+        key: "name",
+        default_sort: "name",
+        status_field: Some("state"),
+        date_field: Some("at"),
+        name_field: Some("name"),
+    };
+
+    fn items() -> Vec<Value> {
+        vec![
+            json!({"name": "b", "size": 10, "at": "2024-01-01T00:00:01Z", "state": "done"}),
+            json!({"name": "a", "size": 2, "at": "2024-01-01T00:00:01.5Z", "state": "queued"}),
+            json!({"name": "c", "size": null, "at": "2024-01-01T00:00:00Z", "state": "done"}),
+        ]
+    }
+
+    fn query(q: &str) -> ListingQuery {
+        let uri = format!("/?{}", q).parse().unwrap();
+        axum::extract::Query::try_from_uri(&uri).unwrap().0
+    }
+
+    fn names(page: &Page) -> Vec<&str> {
+        page.items
+            .iter()
+            .map(|item| item["name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_listing_sorts_and_pages() {
+        let page = query("sort=-at").apply(&items(), &LISTING).unwrap();
+        assert_eq!(names(&page), ["a", "b", "c"]);
+
+        let page = query("sort=size&limit=1&offset=1")
+            .apply(&items(), &LISTING)
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(names(&page), ["a"]);
+
+        let page = query("").apply(&items(), &LISTING).unwrap();
+        assert_eq!(names(&page), ["a", "b", "c"]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn test_listing_selects_fields() {
+        let page = query("fields=name,%20size")
+            .apply(&items(), &LISTING)
+            .unwrap();
+        assert_eq!(page.items[0], json!({"name": "a", "size": 2}));
+
+        let (status, message) = query("fields=name,owner")
+            .apply(&items(), &LISTING)
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("'owner'"));
+        assert!(query("sort=-owner").apply(&items(), &LISTING).is_err());
+    }
+
+    #[test]
+    fn test_cursor_continues_after_last_item() {
+        let first = query("sort=-at&limit=2").apply(&items(), &LISTING).unwrap();
+        assert_eq!(names(&first), ["a", "b"]);
+        let cursor = first.next_cursor.unwrap();
+
+        // An item ahead of the cursor appearing does not shift the next page
+        let mut grown = items();
+        grown.push(json!({"name": "d", "at": "2024-01-02T00:00:00Z", "state": "done"}));
+        let second = query(&format!("sort=-at&limit=2&cursor={}", cursor))
+            .apply(&grown, &LISTING)
+            .unwrap();
+        assert_eq!(names(&second), ["c"]);
+        assert_eq!(second.total, 4);
+        assert_eq!(second.next_cursor, None);
+
+        let mismatched = query(&format!("sort=at&cursor={}", cursor)).apply(&items(), &LISTING);
+        assert!(mismatched.is_err());
+        assert!(query("cursor=bogus").apply(&items(), &LISTING).is_err());
+    }
+
+    #[test]
+    fn test_filters() {
+        let page = query("status=DONE").apply(&items(), &LISTING).unwrap();
+        assert_eq!(names(&page), ["b", "c"]);
+        assert_eq!(page.total, 2);
+
+        let page = query("since=2024-01-01T00:00:01Z&until=2024-01-01T00:00:01.2Z")
+            .apply(&items(), &LISTING)
+            .unwrap();
+        assert_eq!(names(&page), ["b"]);
+
+        let page = query("prefix=c").apply(&items(), &LISTING).unwrap();
+        assert_eq!(names(&page), ["c"]);
+
+        let unfiltered = Listing {
+            status_field: None,
+            ..LISTING
+        };
+        assert!(query("status=done").apply(&items(), &unfiltered).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::handlers::egress::require_admin;
+use crate::maintenance::MaintenanceWindow;
+use crate::state::ServerState;
+
+/// Request to enter or leave maintenance mode
+#[derive(Debug, Deserialize)]
+pub struct SetMaintenanceRequest {
+    pub enabled: bool,
+    /// Message shown to users (required when enabling)
+    pub message: Option<String>,
+    /// Expected end of the downtime
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+/// Maintenance status, including jobs still draining
+#[derive(Debug, Serialize)]
+pub struct MaintenanceStatusResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<MaintenanceWindow>,
+    pub running_jobs: usize,
+    pub pending_jobs: usize,
+}
+
+/// Error response
+#[derive(Debug, Serialize)]
+pub struct MaintenanceErrorResponse {
+    pub error: String,
+    pub code: String,
+}
+
+type MaintenanceError = (StatusCode, Json<MaintenanceErrorResponse>);
+
+fn maintenance_error(status: StatusCode, error: &str, code: &str) -> MaintenanceError {
+    (
+        status,
+        Json(MaintenanceErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+async fn require_maintenance_admin(
+    state: &ServerState,
+    headers: &axum::http::HeaderMap,
+) -> Result<String, MaintenanceError> {
+    require_admin(state, headers)
+        .await
+        .map_err(|(status, Json(e))| maintenance_error(status, &e.error, &e.code))
+}
+
+async fn status_response(state: &ServerState) -> MaintenanceStatusResponse {
+    let stats = state.job_queue.stats().await;
+    let window = state.maintenance.current();
+    MaintenanceStatusResponse {
+        active: window.is_some(),
+        window,
+        running_jobs: stats.running,
+        pending_jobs: stats.pending,
+    }
+}
+
+/// Current maintenance status (admin only)
+pub async fn get_maintenance(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<MaintenanceStatusResponse>, MaintenanceError> {
+    require_maintenance_admin(&state, &headers).await?;
+    Ok(Json(status_response(&state).await))
+}
+
+/// Enter or leave maintenance mode (admin only)
+///
+/// Jobs already accepted keep running; poll this endpoint until
+/// `running_jobs` and `pending_jobs` reach zero before taking the server down.
+pub async fn set_maintenance(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<SetMaintenanceRequest>,
+) -> Result<Json<MaintenanceStatusResponse>, MaintenanceError> {
+    let admin = require_maintenance_admin(&state, &headers).await?;
+
+    if request.enabled {
+        let message = request
+            .message
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .ok_or_else(|| {
+                maintenance_error(
+                    StatusCode::BAD_REQUEST,
+                    "A maintenance message is required",
+                    "INVALID_INPUT",
+                )
+            })?;
+        if request.ends_at.is_some_and(|end| end <= Utc::now()) {
+            return Err(maintenance_error(
+                StatusCode::BAD_REQUEST,
+                "'ends_at' must be in the future",
+                "INVALID_INPUT",
+            ));
+        }
+        state.maintenance.enable(message, request.ends_at);
+        info!("Maintenance mode enabled by {}", admin);
+    } else if state.maintenance.disable() {
+        info!("Maintenance mode disabled by {}", admin);
+    }
+
+    Ok(Json(status_response(&state).await))
+}
@@ -0,0 +1,546 @@
+//! Second-factor endpoints: passkey registration and assertion, recovery codes
+//!
+//! A session starts at [`MfaLevel::Password`]. Completing a passkey assertion
+//! or redeeming a recovery code raises it to [`MfaLevel::MultiFactor`], which
+//! the sensitive routes require for users flagged with `mfa_required`. Once a
+//! user has a passkey, managing their second factors needs an MFA session so
+//! a stolen password alone cannot enroll another authenticator.
+
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::auth::webauthn::{self, Ceremony, WebAuthnConfig};
+use crate::auth::{
+    bearer_token, find_recovery_code, generate_recovery_codes, hash_recovery_code, MfaLevel,
+};
+use crate::state::ServerState;
+use crate::storage::{MfaStore, PostgresMfaStore, User, WebAuthnCredential};
+
+const MAX_PASSKEY_NAME_LENGTH: usize = 255;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn mfa_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn internal_error(e: impl std::fmt::Display) -> ApiError {
+    warn!("Second-factor storage error: {}", e);
+    mfa_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error",
+        "INTERNAL_ERROR",
+    )
+}
+
+fn get_store(state: &ServerState) -> PostgresMfaStore {
+    PostgresMfaStore::new(state.db_pool.clone())
+}
+
+/// Second-factor state of the caller
+#[derive(Debug, Serialize)]
+pub struct MfaStatusResponse {
+    /// Sensitive routes need a second factor
+    pub mfa_required: bool,
+    pub mfa_level: MfaLevel,
+    pub passkeys_enabled: bool,
+    pub passkeys: Vec<WebAuthnCredential>,
+    pub recovery_codes_remaining: usize,
+}
+
+/// A `PublicKeyCredential` serialized with base64url fields
+#[derive(Debug, Deserialize)]
+pub struct PublicKeyCredentialJson {
+    pub id: String,
+    pub response: AuthenticatorResponseJson,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthenticatorResponseJson {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    /// Registration only
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: Option<String>,
+    /// Assertion only
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: Option<String>,
+    /// Assertion only
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterPasskeyRequest {
+    /// Label shown in the passkey list, e.g. "Work laptop"
+    pub name: Option<String>,
+    pub credential: PublicKeyCredentialJson,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegisterPasskeyResponse {
+    pub passkey: WebAuthnCredential,
+    /// Issued with the first passkey; shown only once
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthenticatePasskeyRequest {
+    pub credential: PublicKeyCredentialJson,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecoverRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MfaVerifiedResponse {
+    pub mfa_level: MfaLevel,
+    /// Recovery codes left after this one (recovery only)
+    pub recovery_codes_remaining: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Resolve the session token and user behind a request
+async fn session_user(
+    state: &ServerState,
+    headers: &HeaderMap,
+) -> Result<(String, User), ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| mfa_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    let user = state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| mfa_error(StatusCode::UNAUTHORIZED, "Invalid session", "UNAUTHORIZED"))?;
+    let token = bearer_token(headers).unwrap_or_default();
+    Ok((token.to_string(), user))
+}
+
+fn session_level(state: &ServerState, token: &str) -> MfaLevel {
+    state
+        .auth_state
+        .session_manager
+        .mfa_status(token)
+        .map(|(_, level)| level)
+        .unwrap_or(MfaLevel::Password)
+}
+
+fn require_multi_factor(state: &ServerState, token: &str) -> Result<(), ApiError> {
+    if session_level(state, token) < MfaLevel::MultiFactor {
+        return Err(mfa_error(
+            StatusCode::FORBIDDEN,
+            "Verify with a passkey or recovery code first",
+            "MFA_REQUIRED",
+        ));
+    }
+    Ok(())
+}
+
+fn webauthn_config(state: &ServerState) -> Result<&WebAuthnConfig, ApiError> {
+    state.config.webauthn.as_ref().ok_or_else(|| {
+        mfa_error(
+            StatusCode::NOT_FOUND,
+            "Passkeys are not enabled on this server",
+            "PASSKEYS_DISABLED",
+        )
+    })
+}
+
+fn decode_field(value: Option<&str>, field: &str) -> Result<Vec<u8>, ApiError> {
+    value.and_then(webauthn::decode).ok_or_else(|| {
+        mfa_error(
+            StatusCode::BAD_REQUEST,
+            &format!("Missing or invalid '{}'", field),
+            "INVALID_INPUT",
+        )
+    })
+}
+
+fn reject_ceremony(e: webauthn::WebAuthnError) -> ApiError {
+    mfa_error(StatusCode::UNAUTHORIZED, &e.to_string(), "MFA_FAILED")
+}
+
+/// Reject callers whose IP failed too many second-factor attempts
+fn check_rate_limit(state: &ServerState, addr: &SocketAddr) -> Result<(), ApiError> {
+    if state.auth_state.rate_limiter.is_rate_limited(addr.ip()) {
+        return Err(mfa_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many failed attempts. Please try again later.",
+            "RATE_LIMITED",
+        ));
+    }
+    Ok(())
+}
+
+/// Second-factor status of the calling user
+pub async fn mfa_status(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<MfaStatusResponse>, ApiError> {
+    let (token, user) = session_user(&state, &headers).await?;
+    let store = get_store(&state);
+    let passkeys = store
+        .list_credentials(user.id)
+        .await
+        .map_err(internal_error)?;
+    let recovery_codes_remaining = store
+        .unused_recovery_codes(user.id)
+        .await
+        .map_err(internal_error)?
+        .len();
+
+    Ok(Json(MfaStatusResponse {
+        mfa_required: user.mfa_required,
+        mfa_level: session_level(&state, &token),
+        passkeys_enabled: state.config.webauthn.is_some(),
+        passkeys,
+        recovery_codes_remaining,
+    }))
+}
+
+/// Start registering a passkey
+pub async fn passkey_registration_options(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, ApiError> {
+    let config = webauthn_config(&state)?;
+    let (token, user) = session_user(&state, &headers).await?;
+    let existing = get_store(&state)
+        .list_credentials(user.id)
+        .await
+        .map_err(internal_error)?;
+    if !existing.is_empty() {
+        require_multi_factor(&state, &token)?;
+    }
+
+    let challenge = state
+        .auth_state
+        .webauthn_challenges
+        .issue(&user.email, Ceremony::Registration);
+    let exclude: Vec<Vec<u8>> = existing.into_iter().map(|c| c.credential_id).collect();
+    Ok(Json(webauthn::creation_options(
+        config,
+        user.id.as_bytes(),
+        &user.email,
+        &user.display_name,
+        &challenge,
+        &exclude,
+    )))
+}
+
+/// Finish registering a passkey
+pub async fn register_passkey(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<RegisterPasskeyRequest>,
+) -> Result<Json<RegisterPasskeyResponse>, ApiError> {
+    let config = webauthn_config(&state)?;
+    let (token, user) = session_user(&state, &headers).await?;
+    let store = get_store(&state);
+    let first_passkey = store
+        .list_credentials(user.id)
+        .await
+        .map_err(internal_error)?
+        .is_empty();
+    if !first_passkey {
+        require_multi_factor(&state, &token)?;
+    }
+
+    let name = request
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Passkey".to_string());
+    if name.len() > MAX_PASSKEY_NAME_LENGTH {
+        return Err(mfa_error(
+            StatusCode::BAD_REQUEST,
+            "Passkey name too long",
+            "INVALID_INPUT",
+        ));
+    }
+
+    let challenge = state
+        .auth_state
+        .webauthn_challenges
+        .take(&user.email, Ceremony::Registration)
+        .ok_or_else(|| {
+            mfa_error(
+                StatusCode::BAD_REQUEST,
+                "No registration in progress",
+                "NO_CHALLENGE",
+            )
+        })?;
+    let response = &request.credential.response;
+    let client_data = decode_field(Some(&response.client_data_json), "clientDataJSON")?;
+    let attestation = decode_field(response.attestation_object.as_deref(), "attestationObject")?;
+    let key = webauthn::verify_registration(config, &challenge, &client_data, &attestation)
+        .map_err(reject_ceremony)?;
+
+    let passkey = store
+        .add_credential(
+            user.id,
+            &name,
+            &key.credential_id,
+            &key.public_key,
+            key.sign_count,
+        )
+        .await
+        .map_err(|e| match e {
+            crate::storage::StorageError::AccessDenied(message) => {
+                mfa_error(StatusCode::CONFLICT, &message, "DUPLICATE_PASSKEY")
+            }
+            e => internal_error(e),
+        })?;
+    info!("User {} registered passkey '{}'", user.email, passkey.name);
+
+    // Without recovery codes a lost first passkey would lock the user out
+    let recovery_codes = if first_passkey
+        && store
+            .unused_recovery_codes(user.id)
+            .await
+            .map_err(internal_error)?
+            .is_empty()
+    {
+        Some(issue_recovery_codes(&store, user.id).await?)
+    } else {
+        None
+    };
+
+    Ok(Json(RegisterPasskeyResponse {
+        passkey,
+        recovery_codes,
+    }))
+}
+
+/// Start a passkey assertion
+pub async fn passkey_authentication_options(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, ApiError> {
+    let config = webauthn_config(&state)?;
+    let (_, user) = session_user(&state, &headers).await?;
+    let allow: Vec<Vec<u8>> = get_store(&state)
+        .list_credentials(user.id)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|c| c.credential_id)
+        .collect();
+    if allow.is_empty() {
+        return Err(mfa_error(
+            StatusCode::BAD_REQUEST,
+            "No passkey registered",
+            "NO_PASSKEY",
+        ));
+    }
+
+    let challenge = state
+        .auth_state
+        .webauthn_challenges
+        .issue(&user.email, Ceremony::Authentication);
+    Ok(Json(webauthn::request_options(config, &challenge, &allow)))
+}
+
+/// Finish a passkey assertion and raise the session to multi-factor
+pub async fn authenticate_passkey(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<AuthenticatePasskeyRequest>,
+) -> Result<Json<MfaVerifiedResponse>, ApiError> {
+    let config = webauthn_config(&state)?;
+    check_rate_limit(&state, &addr)?;
+    let (token, user) = session_user(&state, &headers).await?;
+    let challenge = state
+        .auth_state
+        .webauthn_challenges
+        .take(&user.email, Ceremony::Authentication)
+        .ok_or_else(|| {
+            mfa_error(
+                StatusCode::BAD_REQUEST,
+                "No assertion in progress",
+                "NO_CHALLENGE",
+            )
+        })?;
+
+    let credential_id = decode_field(Some(&request.credential.id), "id")?;
+    let store = get_store(&state);
+    let passkey = store
+        .list_credentials(user.id)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .find(|c| c.credential_id == credential_id)
+        .ok_or_else(|| mfa_error(StatusCode::UNAUTHORIZED, "Unknown passkey", "MFA_FAILED"))?;
+
+    let response = &request.credential.response;
+    let client_data = decode_field(Some(&response.client_data_json), "clientDataJSON")?;
+    let authenticator_data =
+        decode_field(response.authenticator_data.as_deref(), "authenticatorData")?;
+    let signature = decode_field(response.signature.as_deref(), "signature")?;
+    let sign_count = webauthn::verify_assertion(
+        config,
+        &challenge,
+        &passkey.public_key,
+        passkey.sign_count as u32,
+        &client_data,
+        &authenticator_data,
+        &signature,
+    )
+    .map_err(|e| {
+        warn!("Passkey assertion for {} failed: {}", user.email, e);
+        state.auth_state.rate_limiter.record_failure(addr.ip());
+        reject_ceremony(e)
+    })?;
+
+    store
+        .record_credential_use(passkey.id, sign_count)
+        .await
+        .map_err(internal_error)?;
+    state
+        .auth_state
+        .session_manager
+        .elevate_to_multi_factor(&token);
+    info!(
+        "User {} verified with passkey '{}'",
+        user.email, passkey.name
+    );
+
+    Ok(Json(MfaVerifiedResponse {
+        mfa_level: MfaLevel::MultiFactor,
+        recovery_codes_remaining: None,
+    }))
+}
+
+/// Remove one of the caller's passkeys
+pub async fn delete_passkey(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(passkey_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let (token, user) = session_user(&state, &headers).await?;
+    require_multi_factor(&state, &token)?;
+    get_store(&state)
+        .delete_credential(user.id, passkey_id)
+        .await
+        .map_err(|e| match e {
+            crate::storage::StorageError::NotFound(_) => {
+                mfa_error(StatusCode::NOT_FOUND, "Passkey not found", "NOT_FOUND")
+            }
+            e => internal_error(e),
+        })?;
+    info!("User {} removed passkey {}", user.email, passkey_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Replace the caller's recovery codes with a new batch
+pub async fn regenerate_recovery_codes(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    let (token, user) = session_user(&state, &headers).await?;
+    require_multi_factor(&state, &token)?;
+    let recovery_codes = issue_recovery_codes(&get_store(&state), user.id).await?;
+    info!("User {} regenerated recovery codes", user.email);
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Redeem a recovery code in place of a passkey
+pub async fn recover_with_code(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<RecoverRequest>,
+) -> Result<Json<MfaVerifiedResponse>, ApiError> {
+    check_rate_limit(&state, &addr)?;
+    let (token, user) = session_user(&state, &headers).await?;
+    let store = get_store(&state);
+    let codes = store
+        .unused_recovery_codes(user.id)
+        .await
+        .map_err(internal_error)?;
+    let hashes: Vec<String> = codes.iter().map(|c| c.code_hash.clone()).collect();
+
+    // Argon2 is deliberately slow; keep it off the async workers
+    let code = request.code;
+    let matched = tokio::task::spawn_blocking(move || find_recovery_code(&code, &hashes))
+        .await
+        .map_err(internal_error)?;
+    let consumed = match matched {
+        Some(index) => store
+            .consume_recovery_code(codes[index].id)
+            .await
+            .map_err(internal_error)?,
+        None => false,
+    };
+    if !consumed {
+        warn!("Invalid recovery code for {}", user.email);
+        state.auth_state.rate_limiter.record_failure(addr.ip());
+        return Err(mfa_error(
+            StatusCode::UNAUTHORIZED,
+            "Invalid recovery code",
+            "MFA_FAILED",
+        ));
+    }
+
+    state
+        .auth_state
+        .session_manager
+        .elevate_to_multi_factor(&token);
+    warn!(
+        "User {} used a recovery code ({} left)",
+        user.email,
+        codes.len() - 1
+    );
+
+    Ok(Json(MfaVerifiedResponse {
+        mfa_level: MfaLevel::MultiFactor,
+        recovery_codes_remaining: Some(codes.len() - 1),
+    }))
+}
+
+/// Generate, store and return a fresh batch of recovery codes
+async fn issue_recovery_codes(
+    store: &PostgresMfaStore,
+    user_id: Uuid,
+) -> Result<Vec<String>, ApiError> {
+    let (codes, hashes) = tokio::task::spawn_blocking(|| {
+        let codes = generate_recovery_codes();
+        let hashes = codes
+            .iter()
+            .map(|code| hash_recovery_code(code))
+            .collect::<Result<Vec<_>, _>>();
+        (codes, hashes)
+    })
+    .await
+    .map_err(internal_error)?;
+    let hashes = hashes.map_err(internal_error)?;
+    store
+        .replace_recovery_codes(user_id, &hashes)
+        .await
+        .map_err(internal_error)?;
+    Ok(codes)
+}
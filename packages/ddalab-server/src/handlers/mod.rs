@@ -1,14 +1,58 @@
 pub mod access_control;
+mod annotations;
+mod announcements;
+mod audit;
 mod auth;
+mod datasets;
+mod egress;
+mod executor;
 mod federation;
 mod health;
 mod jobs;
+mod listing;
+mod maintenance;
+mod mfa;
+mod notifications;
+mod organizations;
+mod presence;
+mod presets;
+mod relay;
+mod retention;
+mod schedules;
+mod search;
+mod share_keys;
 mod shares;
 mod teams;
+mod tokens;
+mod uploads;
+mod users;
+mod webhooks;
 
+pub use annotations::*;
+pub use announcements::*;
+pub use audit::*;
 pub use auth::*;
+pub use datasets::*;
+pub use egress::*;
+pub use executor::*;
 pub use federation::*;
 pub use health::*;
 pub use jobs::*;
+pub use listing::*;
+pub use maintenance::*;
+pub use mfa::*;
+pub use notifications::*;
+pub use organizations::*;
+pub use presence::*;
+pub use presets::*;
+pub use relay::*;
+pub use retention::*;
+pub use schedules::*;
+pub use search::*;
+pub use share_keys::*;
 pub use shares::*;
 pub use teams::*;
+pub use tokens::*;
+pub use uploads::*;
+pub use users::*;
+pub use webhooks::*;
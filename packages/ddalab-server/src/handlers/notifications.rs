@@ -0,0 +1,87 @@
+//! Notification preference endpoints
+//!
+//! Users choose which emails they receive: job completions, job failures
+//! and results shared with them.
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use super::auth::ErrorResponse;
+use crate::state::ServerState;
+use crate::storage::{NotificationPreferences, User};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn notification_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+/// The caller's preferences and whether the server sends email at all
+#[derive(Debug, Serialize)]
+pub struct NotificationSettingsResponse {
+    pub email_enabled: bool,
+    pub preferences: NotificationPreferences,
+}
+
+async fn caller(state: &ServerState, headers: &HeaderMap) -> Result<User, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| notification_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| notification_error(StatusCode::UNAUTHORIZED, "Unknown user", "UNAUTHORIZED"))
+}
+
+/// The caller's notification preferences
+pub async fn get_my_notifications(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<NotificationSettingsResponse>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    Ok(Json(NotificationSettingsResponse {
+        email_enabled: state.notifier.is_some(),
+        preferences: user.notifications,
+    }))
+}
+
+/// Replace the caller's notification preferences
+pub async fn set_my_notifications(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(preferences): Json<NotificationPreferences>,
+) -> Result<Json<NotificationSettingsResponse>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    state
+        .user_store
+        .set_notification_preferences(user.id, preferences)
+        .await
+        .map_err(|e| {
+            warn!("Failed to save notification preferences: {}", e);
+            notification_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal error",
+                "INTERNAL_ERROR",
+            )
+        })?;
+    info!("{} updated their notification preferences", user.email);
+
+    Ok(Json(NotificationSettingsResponse {
+        email_enabled: state.notifier.is_some(),
+        preferences,
+    }))
+}
@@ -0,0 +1,457 @@
+//! Organization endpoints
+//!
+//! Server administrators create organizations; from then on each one is run
+//! by its own owners and admins, who manage members and create teams in it.
+//! Members see only their own organizations, and removing someone from an
+//! organization also removes them from its teams.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::state::ServerState;
+use crate::storage::{
+    AccessPolicy, AccessPolicyType, Organization, OrganizationMember, OrganizationRole,
+    OrganizationStore, OrganizationSummary, PostgresOrganizationStore, PostgresTeamStore,
+    TeamStore, TeamSummary, User,
+};
+
+const MAX_NAME_LENGTH: usize = 256;
+const MAX_DESCRIPTION_LENGTH: usize = 1024;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn org_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn internal_error(e: impl std::fmt::Display) -> ApiError {
+    warn!("Organization storage error: {}", e);
+    org_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error",
+        "INTERNAL_ERROR",
+    )
+}
+
+fn get_store(state: &ServerState) -> PostgresOrganizationStore {
+    PostgresOrganizationStore::new(state.db_pool.clone())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrganizationRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// First owner; the creating administrator when omitted
+    pub owner_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddOrganizationMemberRequest {
+    pub user_id: Uuid,
+    #[serde(default)]
+    pub role: OrganizationRole,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrganizationResponse {
+    pub organization: Organization,
+    pub members: Vec<OrganizationMember>,
+}
+
+/// Resolve the calling user from the session
+async fn caller(state: &ServerState, headers: &HeaderMap) -> Result<User, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| org_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| org_error(StatusCode::UNAUTHORIZED, "Invalid session", "UNAUTHORIZED"))
+}
+
+/// The caller's role in the organization; server administrators act as owners
+///
+/// Non-members get a 404 so organizations stay invisible to outsiders.
+async fn caller_role(
+    store: &PostgresOrganizationStore,
+    organization_id: Uuid,
+    caller: &User,
+) -> Result<OrganizationRole, ApiError> {
+    if caller.is_admin {
+        store
+            .get_organization(organization_id)
+            .await
+            .map_err(|_| org_error(StatusCode::NOT_FOUND, "Organization not found", "NOT_FOUND"))?;
+        return Ok(OrganizationRole::Owner);
+    }
+    store
+        .member_role(organization_id, caller.id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| org_error(StatusCode::NOT_FOUND, "Organization not found", "NOT_FOUND"))
+}
+
+fn require_manager(role: OrganizationRole) -> Result<(), ApiError> {
+    if role.can_manage() {
+        return Ok(());
+    }
+    Err(org_error(
+        StatusCode::FORBIDDEN,
+        "Not an organization admin",
+        "FORBIDDEN",
+    ))
+}
+
+/// Confine a new share to the owner's organization
+///
+/// Shares naming an organization, directly or through an organization's
+/// team, are checked against the owner's memberships. Shares that name none
+/// are confined to the owner's organization when they belong to exactly one;
+/// owners in several organizations must pick one.
+pub(crate) async fn scope_share_policy(
+    state: &ServerState,
+    owner_email: &str,
+    policy: &mut AccessPolicy,
+) -> Result<(), ApiError> {
+    let owner = state
+        .user_store
+        .get_user_by_email(owner_email)
+        .await
+        .map_err(|_| org_error(StatusCode::UNAUTHORIZED, "Invalid session", "UNAUTHORIZED"))?;
+    let memberships: Vec<String> = get_store(state)
+        .list_user_organizations(owner.id)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|org| org.id.to_string())
+        .collect();
+
+    let implied = match &policy.policy_type {
+        AccessPolicyType::Organization { organization_id } => Some(organization_id.clone()),
+        AccessPolicyType::Team { team_id } => match Uuid::try_parse(team_id) {
+            Ok(team_id) => PostgresTeamStore::new(state.db_pool.clone())
+                .get_team(team_id)
+                .await
+                .ok()
+                .and_then(|team| team.organization_id)
+                .map(|id| id.to_string()),
+            Err(_) => None,
+        },
+        _ => None,
+    };
+
+    let organization_id = match (policy.organization_id.take(), implied) {
+        (Some(explicit), Some(implied)) if explicit != implied => {
+            return Err(org_error(
+                StatusCode::BAD_REQUEST,
+                "Share audience lies outside the share's organization",
+                "ORGANIZATION_MISMATCH",
+            ));
+        }
+        (Some(id), _) | (None, Some(id)) => Some(id),
+        (None, None) => match memberships.as_slice() {
+            [] => None,
+            [only] => Some(only.clone()),
+            _ => {
+                return Err(org_error(
+                    StatusCode::BAD_REQUEST,
+                    "Choose which of your organizations the share belongs to",
+                    "ORGANIZATION_REQUIRED",
+                ));
+            }
+        },
+    };
+
+    if let Some(id) = &organization_id {
+        if !memberships.contains(id) {
+            return Err(org_error(
+                StatusCode::FORBIDDEN,
+                "Not a member of the share's organization",
+                "FORBIDDEN",
+            ));
+        }
+    }
+    policy.organization_id = organization_id;
+    Ok(())
+}
+
+/// Create an organization (server administrators only)
+pub async fn create_organization(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateOrganizationRequest>,
+) -> Result<Json<Organization>, ApiError> {
+    let caller = caller(&state, &headers).await?;
+    if !caller.is_admin {
+        return Err(org_error(
+            StatusCode::FORBIDDEN,
+            "Administrator access required",
+            "FORBIDDEN",
+        ));
+    }
+
+    let name = request.name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(org_error(
+            StatusCode::BAD_REQUEST,
+            "Organization name must be 1 to 256 characters",
+            "INVALID_INPUT",
+        ));
+    }
+    if request
+        .description
+        .as_ref()
+        .is_some_and(|desc| desc.len() > MAX_DESCRIPTION_LENGTH)
+    {
+        return Err(org_error(
+            StatusCode::BAD_REQUEST,
+            "Description too long",
+            "INVALID_INPUT",
+        ));
+    }
+
+    let now = Utc::now();
+    let organization = Organization {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        description: request.description,
+        created_by: caller.id,
+        created_at: now,
+        updated_at: now,
+    };
+    let store = get_store(&state);
+    store
+        .create_organization(&organization)
+        .await
+        .map_err(internal_error)?;
+    store
+        .add_organization_member(&OrganizationMember {
+            organization_id: organization.id,
+            user_id: request.owner_id.unwrap_or(caller.id),
+            role: OrganizationRole::Owner,
+            added_at: now,
+            added_by: Some(caller.id),
+        })
+        .await
+        .map_err(internal_error)?;
+    info!(
+        "{} created organization '{}'",
+        caller.email, organization.name
+    );
+
+    Ok(Json(organization))
+}
+
+/// List organizations: all of them for administrators, otherwise the caller's
+pub async fn list_organizations(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<OrganizationSummary>>, ApiError> {
+    let caller = caller(&state, &headers).await?;
+    let store = get_store(&state);
+    let organizations = if caller.is_admin {
+        store.list_organizations().await
+    } else {
+        store.list_user_organizations(caller.id).await
+    }
+    .map_err(internal_error)?;
+    Ok(Json(organizations))
+}
+
+/// Get an organization with its members (members only)
+pub async fn get_organization(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(organization_id): Path<Uuid>,
+) -> Result<Json<OrganizationResponse>, ApiError> {
+    let caller = caller(&state, &headers).await?;
+    let store = get_store(&state);
+    caller_role(&store, organization_id, &caller).await?;
+
+    let organization = store
+        .get_organization(organization_id)
+        .await
+        .map_err(internal_error)?;
+    let members = store
+        .get_organization_members(organization_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(OrganizationResponse {
+        organization,
+        members,
+    }))
+}
+
+/// Delete an organization with its teams (owners only)
+pub async fn delete_organization(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(organization_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let caller = caller(&state, &headers).await?;
+    let store = get_store(&state);
+    if caller_role(&store, organization_id, &caller).await? != OrganizationRole::Owner {
+        return Err(org_error(
+            StatusCode::FORBIDDEN,
+            "Not an organization owner",
+            "FORBIDDEN",
+        ));
+    }
+
+    store
+        .delete_organization(organization_id)
+        .await
+        .map_err(internal_error)?;
+    info!("{} deleted organization {}", caller.email, organization_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List an organization's teams (members only)
+pub async fn list_organization_teams(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(organization_id): Path<Uuid>,
+) -> Result<Json<Vec<TeamSummary>>, ApiError> {
+    let caller = caller(&state, &headers).await?;
+    let store = get_store(&state);
+    caller_role(&store, organization_id, &caller).await?;
+
+    let teams = store
+        .list_organization_teams(organization_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(teams))
+}
+
+/// Add a member or change their role (organization admins)
+///
+/// Only owners may grant ownership or change another owner's role.
+pub async fn add_organization_member(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(organization_id): Path<Uuid>,
+    Json(request): Json<AddOrganizationMemberRequest>,
+) -> Result<StatusCode, ApiError> {
+    let caller = caller(&state, &headers).await?;
+    let store = get_store(&state);
+    let role = caller_role(&store, organization_id, &caller).await?;
+    require_manager(role)?;
+
+    let current = store
+        .member_role(organization_id, request.user_id)
+        .await
+        .map_err(internal_error)?;
+    let touches_owner =
+        request.role == OrganizationRole::Owner || current == Some(OrganizationRole::Owner);
+    if touches_owner && role != OrganizationRole::Owner {
+        return Err(org_error(
+            StatusCode::FORBIDDEN,
+            "Only owners can grant or change ownership",
+            "FORBIDDEN",
+        ));
+    }
+    if current == Some(OrganizationRole::Owner)
+        && request.role != OrganizationRole::Owner
+        && owner_count(&store, organization_id).await? == 1
+    {
+        return Err(org_error(
+            StatusCode::CONFLICT,
+            "An organization needs at least one owner",
+            "LAST_OWNER",
+        ));
+    }
+
+    store
+        .add_organization_member(&OrganizationMember {
+            organization_id,
+            user_id: request.user_id,
+            role: request.role,
+            added_at: Utc::now(),
+            added_by: Some(caller.id),
+        })
+        .await
+        .map_err(internal_error)?;
+
+    Ok(if current.is_some() {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    })
+}
+
+/// Remove a member from an organization and its teams
+///
+/// Admins may remove members and other admins; anyone may leave.
+pub async fn remove_organization_member(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path((organization_id, member_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    let caller = caller(&state, &headers).await?;
+    let store = get_store(&state);
+    let role = caller_role(&store, organization_id, &caller).await?;
+    if member_id != caller.id {
+        require_manager(role)?;
+    }
+
+    let not_found = || org_error(StatusCode::NOT_FOUND, "Member not found", "NOT_FOUND");
+    let current = store
+        .member_role(organization_id, member_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(not_found)?;
+    if current == OrganizationRole::Owner {
+        if role != OrganizationRole::Owner {
+            return Err(org_error(
+                StatusCode::FORBIDDEN,
+                "Only owners can remove an owner",
+                "FORBIDDEN",
+            ));
+        }
+        if owner_count(&store, organization_id).await? == 1 {
+            return Err(org_error(
+                StatusCode::CONFLICT,
+                "An organization needs at least one owner",
+                "LAST_OWNER",
+            ));
+        }
+    }
+
+    store
+        .remove_organization_member(organization_id, member_id)
+        .await
+        .map_err(|_| not_found())?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn owner_count(
+    store: &PostgresOrganizationStore,
+    organization_id: Uuid,
+) -> Result<usize, ApiError> {
+    Ok(store
+        .get_organization_members(organization_id)
+        .await
+        .map_err(internal_error)?
+        .iter()
+        .filter(|member| member.role == OrganizationRole::Owner)
+        .count())
+}
@@ -0,0 +1,101 @@
+//! Presence endpoints
+//!
+//! `GET /api/presence` lists the caller's teammates across all of their
+//! teams and `GET /api/teams/{team_id}/presence` the members of one team,
+//! each with whether they are online and the endpoint their client
+//! advertised. The sync WebSocket's `subscribe_presence` message pushes the
+//! same roster and every change to it.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::error;
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::state::ServerState;
+use crate::storage::{PostgresTeamStore, StorageError, TeamStore, User};
+use crate::sync::{roster, team_members, teammates, PresenceEntry};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn presence_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn storage_error(e: StorageError) -> ApiError {
+    error!("Failed to load presence: {}", e);
+    presence_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error",
+        "INTERNAL_ERROR",
+    )
+}
+
+/// Users and whether they are online, online users first
+#[derive(Debug, Serialize)]
+pub struct PresenceResponse {
+    pub members: Vec<PresenceEntry>,
+}
+
+async fn caller(state: &ServerState, headers: &HeaderMap) -> Result<User, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| presence_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| presence_error(StatusCode::UNAUTHORIZED, "Unknown user", "UNAUTHORIZED"))
+}
+
+/// Presence of everyone sharing a team with the caller
+pub async fn get_my_presence(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<PresenceResponse>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let users = teammates(state.user_store.as_ref(), &state.db_pool, user.id)
+        .await
+        .map_err(storage_error)?;
+    Ok(Json(PresenceResponse {
+        members: roster(&state.registry, &users),
+    }))
+}
+
+/// Presence of a team's members; only for members of the team
+pub async fn get_team_presence(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(team_id): Path<Uuid>,
+) -> Result<Json<PresenceResponse>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let is_member = PostgresTeamStore::new(state.db_pool.clone())
+        .is_team_member(team_id, user.id)
+        .await
+        .map_err(storage_error)?;
+    if !is_member {
+        return Err(presence_error(
+            StatusCode::FORBIDDEN,
+            "Not a member of this team",
+            "FORBIDDEN",
+        ));
+    }
+    let users = team_members(state.user_store.as_ref(), &state.db_pool, team_id)
+        .await
+        .map_err(storage_error)?;
+    Ok(Json(PresenceResponse {
+        members: roster(&state.registry, &users),
+    }))
+}
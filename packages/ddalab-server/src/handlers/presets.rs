@@ -0,0 +1,244 @@
+//! Parameter preset endpoints
+//!
+//! Users keep named job parameters of their own, e.g. the window, scales
+//! and preprocessing they analyze every recording with, and submit jobs
+//! by `preset_id` alone. Team presets are published through the team
+//! endpoints; the listing here shows both so clients offer one choice.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::jobs::normalize_preset;
+use crate::state::ServerState;
+use crate::storage::{
+    PostgresPresetStore, PostgresTeamStore, PresetStore, StorageError, TeamPreset, TeamStore, User,
+    UserPreset,
+};
+
+/// Longest preset name accepted
+const MAX_NAME_LENGTH: usize = 256;
+
+/// Longest preset description accepted
+const MAX_DESCRIPTION_LENGTH: usize = 4096;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn preset_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn storage_error(e: StorageError) -> ApiError {
+    match e {
+        StorageError::NotFound(_) => {
+            preset_error(StatusCode::NOT_FOUND, "Preset not found", "NOT_FOUND")
+        }
+        StorageError::DuplicateName(name) => preset_error(
+            StatusCode::CONFLICT,
+            &format!("You already have a preset named '{}'", name),
+            "DUPLICATE_NAME",
+        ),
+        e => {
+            error!("Preset storage failed: {}", e);
+            preset_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal error",
+                "INTERNAL_ERROR",
+            )
+        }
+    }
+}
+
+/// Create or replace a personal preset
+#[derive(Debug, Deserialize)]
+pub struct SavePresetRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Job parameter fields the preset sets
+    pub parameters: serde_json::Value,
+}
+
+/// A preset the caller can submit jobs with
+#[derive(Debug, Serialize)]
+#[serde(tag = "scope", rename_all = "lowercase")]
+pub enum AvailablePreset {
+    User(UserPreset),
+    Team(TeamPreset),
+}
+
+async fn caller(state: &ServerState, headers: &HeaderMap) -> Result<User, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| preset_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| preset_error(StatusCode::UNAUTHORIZED, "Unknown user", "UNAUTHORIZED"))
+}
+
+/// Check a request and normalize its parameters
+fn validate(request: &SavePresetRequest) -> Result<serde_json::Value, ApiError> {
+    if request.name.trim().is_empty() || request.name.len() > MAX_NAME_LENGTH {
+        return Err(preset_error(
+            StatusCode::BAD_REQUEST,
+            "Preset name must be 1 to 256 characters",
+            "INVALID_INPUT",
+        ));
+    }
+    if request
+        .description
+        .as_ref()
+        .is_some_and(|desc| desc.len() > MAX_DESCRIPTION_LENGTH)
+    {
+        return Err(preset_error(
+            StatusCode::BAD_REQUEST,
+            "Description too long",
+            "INVALID_INPUT",
+        ));
+    }
+    normalize_preset(&request.parameters, &[])
+        .map_err(|message| preset_error(StatusCode::BAD_REQUEST, &message, "INVALID_INPUT"))
+}
+
+/// The caller's own presets followed by those of their teams
+pub async fn list_presets(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<AvailablePreset>>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let own = PostgresPresetStore::new(state.db_pool.clone())
+        .list_presets(&user.email)
+        .await
+        .map_err(storage_error)?;
+    let team = PostgresTeamStore::new(state.db_pool.clone())
+        .list_user_presets(user.id)
+        .await
+        .map_err(storage_error)?;
+
+    Ok(Json(
+        own.into_iter()
+            .map(AvailablePreset::User)
+            .chain(team.into_iter().map(AvailablePreset::Team))
+            .collect(),
+    ))
+}
+
+/// Save a personal preset
+pub async fn create_preset(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<SavePresetRequest>,
+) -> Result<(StatusCode, Json<UserPreset>), ApiError> {
+    let user = caller(&state, &headers).await?;
+    let parameters = validate(&request)?;
+
+    let now = chrono::Utc::now();
+    let preset = UserPreset {
+        id: Uuid::new_v4(),
+        user_id: user.email.clone(),
+        name: request.name.trim().to_string(),
+        description: request.description,
+        parameters,
+        created_at: now,
+        updated_at: now,
+    };
+    PostgresPresetStore::new(state.db_pool.clone())
+        .create_preset(&preset)
+        .await
+        .map_err(storage_error)?;
+    info!(
+        "{} saved preset '{}' ({})",
+        user.email, preset.name, preset.id
+    );
+
+    Ok((StatusCode::CREATED, Json(preset)))
+}
+
+/// One of the caller's presets
+pub async fn get_preset(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(preset_id): Path<Uuid>,
+) -> Result<Json<UserPreset>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let preset = PostgresPresetStore::new(state.db_pool.clone())
+        .get_preset(preset_id)
+        .await
+        .map_err(storage_error)?;
+    // Other users' presets are reported as missing rather than forbidden
+    if preset.user_id != user.email {
+        return Err(preset_error(
+            StatusCode::NOT_FOUND,
+            "Preset not found",
+            "NOT_FOUND",
+        ));
+    }
+    Ok(Json(preset))
+}
+
+/// Replace one of the caller's presets
+pub async fn update_preset(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(preset_id): Path<Uuid>,
+    Json(request): Json<SavePresetRequest>,
+) -> Result<Json<UserPreset>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let parameters = validate(&request)?;
+
+    let store = PostgresPresetStore::new(state.db_pool.clone());
+    let existing = store.get_preset(preset_id).await.map_err(storage_error)?;
+    if existing.user_id != user.email {
+        return Err(preset_error(
+            StatusCode::NOT_FOUND,
+            "Preset not found",
+            "NOT_FOUND",
+        ));
+    }
+    let preset = UserPreset {
+        name: request.name.trim().to_string(),
+        description: request.description,
+        parameters,
+        updated_at: chrono::Utc::now(),
+        ..existing
+    };
+    store.update_preset(&preset).await.map_err(storage_error)?;
+    info!(
+        "{} updated preset '{}' ({})",
+        user.email, preset.name, preset.id
+    );
+
+    Ok(Json(preset))
+}
+
+/// Delete one of the caller's presets
+pub async fn delete_preset(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(preset_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = caller(&state, &headers).await?;
+    PostgresPresetStore::new(state.db_pool.clone())
+        .delete_preset(&user.email, preset_id)
+        .await
+        .map_err(storage_error)?;
+    info!("{} deleted preset {}", user.email, preset_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
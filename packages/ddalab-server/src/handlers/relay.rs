@@ -0,0 +1,492 @@
+//! Relayed share downloads
+//!
+//! When a share's owner is online but not reachable directly, the recipient
+//! asks `GET /api/shares/{token}/relay`. The broker pushes a `relay_request`
+//! to the owner's sync connection and holds the download open; the owner's
+//! client streams the result with `PUT /api/relay/{relay_id}`, or refuses
+//! with `DELETE /api/relay/{relay_id}`, and the bytes go straight through to
+//! the recipient without being stored.
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::StreamExt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::handlers::egress::record_egress;
+use crate::relay::{RelayError, RelayHub, RelayOffer, RELAY_OFFER_TIMEOUT};
+use crate::state::ServerState;
+use crate::storage::{EgressEntry, EgressKind, ShareMetadata, SharedResultStore};
+use crate::sync::{Mailbox, SyncMessage};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn relay_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+impl From<RelayError> for ApiError {
+    fn from(e: RelayError) -> Self {
+        let (status, code) = match e {
+            RelayError::Busy => (StatusCode::SERVICE_UNAVAILABLE, "RELAY_BUSY"),
+            RelayError::QuotaExceeded => (StatusCode::TOO_MANY_REQUESTS, "RELAY_QUOTA_EXCEEDED"),
+            RelayError::NotFound => (StatusCode::NOT_FOUND, "RELAY_NOT_FOUND"),
+            RelayError::Forbidden => (StatusCode::FORBIDDEN, "ACCESS_DENIED"),
+            RelayError::Timeout => (StatusCode::GATEWAY_TIMEOUT, "RELAY_TIMEOUT"),
+            RelayError::Declined => (StatusCode::BAD_GATEWAY, "RELAY_DECLINED"),
+            RelayError::TooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, "RELAY_TOO_LARGE"),
+            RelayError::RequesterGone => (StatusCode::GONE, "RELAY_REQUESTER_GONE"),
+        };
+        relay_error(status, &e.to_string(), code)
+    }
+}
+
+fn hub(state: &ServerState) -> Result<&RelayHub, ApiError> {
+    state.relay.as_ref().ok_or_else(|| {
+        relay_error(
+            StatusCode::NOT_FOUND,
+            "Relayed downloads are not enabled",
+            "RELAY_DISABLED",
+        )
+    })
+}
+
+/// The caller's user id
+fn caller(state: &ServerState, headers: &HeaderMap) -> Result<String, ApiError> {
+    state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| relay_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))
+}
+
+/// Reserve a relay of share `token` for `requester` and ask the share's
+/// owner to upload it
+async fn request_relay(
+    hub: &RelayHub,
+    shares: &dyn SharedResultStore,
+    mailbox: &Mailbox,
+    token: &str,
+    requester: &str,
+) -> Result<(ShareMetadata, RelayOffer), ApiError> {
+    let metadata = shares
+        .get_shared_result(token)
+        .await
+        .map_err(|e| relay_error(StatusCode::NOT_FOUND, &e.to_string(), "SHARE_NOT_FOUND"))?;
+    match shares.check_access(token, &requester.to_string()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err(relay_error(
+                StatusCode::FORBIDDEN,
+                "Access denied",
+                "ACCESS_DENIED",
+            ))
+        }
+        Err(e) => {
+            return Err(relay_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &e.to_string(),
+                "ACCESS_CHECK_ERROR",
+            ))
+        }
+    }
+
+    let offer = hub.open(&metadata.owner_user_id, requester)?;
+    let request = SyncMessage::RelayRequest {
+        relay_id: offer.id,
+        token: token.to_string(),
+        content_id: metadata.content_id.clone(),
+        requester_id: requester.to_string(),
+        max_bytes: offer.limit,
+    };
+    if !mailbox.send_now(&metadata.owner_user_id, request).await {
+        return Err(relay_error(
+            StatusCode::CONFLICT,
+            "The share's owner is offline",
+            "OWNER_OFFLINE",
+        ));
+    }
+    Ok((metadata, offer))
+}
+
+/// Download a shared result through the broker
+///
+/// Waits up to [`RELAY_OFFER_TIMEOUT`] for the owner to start uploading,
+/// then streams the upload as the response body.
+pub async fn download_via_relay(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(token): Path<String>,
+) -> Result<Response, ApiError> {
+    let hub = hub(&state)?;
+    let requester = caller(&state, &headers)?;
+    let (metadata, offer) = request_relay(
+        hub,
+        state.share_store.as_ref(),
+        &state.mailbox,
+        &token,
+        &requester,
+    )
+    .await?;
+
+    let download = offer.wait(RELAY_OFFER_TIMEOUT).await?;
+    info!(
+        "Relaying {} from {} to {}",
+        token, metadata.owner_user_id, requester
+    );
+
+    let size = download.size;
+    let ip = addr.ip().to_string();
+    let finished = state.clone();
+    let stream = download.into_stream(move |bytes| {
+        finished.metrics.observe_relay(bytes);
+        let entry = EgressEntry::new(
+            EgressKind::RelayDownload,
+            &requester,
+            &token,
+            bytes as usize,
+        )
+        .ip_address(&ip)
+        .source_dataset(&metadata.content_id);
+        record_egress(&finished, entry);
+    });
+
+    let mut response = Body::from_stream(stream).into_response();
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/octet-stream"),
+    );
+    if let Some(size) = size {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, header::HeaderValue::from(size));
+    }
+    Ok(response)
+}
+
+/// Upload the result for relay `relay_id`; only the share's owner may
+pub async fn upload_relay(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(relay_id): Path<Uuid>,
+    body: Body,
+) -> Result<StatusCode, ApiError> {
+    let hub = hub(&state)?;
+    let owner = caller(&state, &headers)?;
+    let size = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+
+    forward_upload(hub, relay_id, &owner, size, body).await
+}
+
+/// Stream `body`, announced as `size` bytes if known, to the requester of
+/// relay `relay_id`
+async fn forward_upload(
+    hub: &RelayHub,
+    relay_id: Uuid,
+    owner: &str,
+    size: Option<u64>,
+    body: Body,
+) -> Result<StatusCode, ApiError> {
+    let mut sender = hub.accept(relay_id, owner, size)?;
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = chunks.next().await {
+        match chunk {
+            Ok(bytes) => sender.send(bytes).await?,
+            Err(e) => {
+                warn!("Relay {} upload failed: {}", relay_id, e);
+                sender.abort("The owner's upload failed").await;
+                return Err(relay_error(
+                    StatusCode::BAD_REQUEST,
+                    "Upload failed",
+                    "UPLOAD_FAILED",
+                ));
+            }
+        }
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Refuse relay `relay_id`
+pub async fn decline_relay(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(relay_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let hub = hub(&state)?;
+    let owner = caller(&state, &headers)?;
+    hub.decline(relay_id, &owner)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relay::RelayConfig;
+    use crate::storage::{
+        AccessPolicy, OfflineMessage, OfflineMessageStore, ShareToken, ShareableContentType,
+        StorageError, StorageResult, UserId,
+    };
+    use crate::sync::{OfflineQueuePolicy, CONNECTION_BUFFER};
+    use async_trait::async_trait;
+    use chrono::{DateTime, Utc};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    /// One share, readable by `allowed` only
+    struct OneShare {
+        allowed: &'static str,
+    }
+
+    #[async_trait]
+    impl SharedResultStore for OneShare {
+        async fn publish_result(
+            &self,
+            _share_token: &str,
+            _metadata: ShareMetadata,
+            _content_data: Option<serde_json::Value>,
+        ) -> StorageResult<()> {
+            unimplemented!()
+        }
+
+        async fn get_shared_result(&self, share_token: &str) -> StorageResult<ShareMetadata> {
+            if share_token != "share" {
+                return Err(StorageError::ShareNotFound(share_token.to_string()));
+            }
+            Ok(ShareMetadata {
+                owner_user_id: "alice".to_string(),
+                content_type: ShareableContentType::DdaResult,
+                content_id: "result-1".to_string(),
+                title: "Result".to_string(),
+                description: None,
+                created_at: Utc::now(),
+                access_policy: AccessPolicy::public_default("lab".to_string()),
+                classification: Default::default(),
+                download_count: 0,
+                last_accessed_at: None,
+                sealed: None,
+            })
+        }
+
+        async fn get_share_content(
+            &self,
+            _share_token: &str,
+        ) -> StorageResult<Option<serde_json::Value>> {
+            unimplemented!()
+        }
+
+        async fn check_access(
+            &self,
+            _share_token: &str,
+            requester_id: &UserId,
+        ) -> StorageResult<bool> {
+            Ok(requester_id == self.allowed)
+        }
+
+        async fn revoke_share(&self, _share_token: &str) -> StorageResult<()> {
+            unimplemented!()
+        }
+
+        async fn list_user_shares(&self, _user_id: &UserId) -> StorageResult<Vec<ShareToken>> {
+            unimplemented!()
+        }
+
+        async fn list_user_share_metadata(
+            &self,
+            _user_id: &UserId,
+        ) -> StorageResult<Vec<(ShareToken, ShareMetadata)>> {
+            unimplemented!()
+        }
+
+        async fn search_user_shares(
+            &self,
+            _user_id: &UserId,
+            _terms: &[String],
+            _limit: i64,
+        ) -> StorageResult<Vec<(ShareToken, ShareMetadata)>> {
+            unimplemented!()
+        }
+
+        async fn list_shares_by_type(
+            &self,
+            _user_id: &UserId,
+            _content_type: ShareableContentType,
+            _limit: u32,
+        ) -> StorageResult<Vec<ShareToken>> {
+            unimplemented!()
+        }
+    }
+
+    /// Relay requests are never queued, so nothing is ever pending
+    struct NoQueue;
+
+    #[async_trait]
+    impl OfflineMessageStore for NoQueue {
+        async fn enqueue(
+            &self,
+            _user_id: &str,
+            _message: &serde_json::Value,
+            _expires_at: DateTime<Utc>,
+            _limit: usize,
+        ) -> StorageResult<u64> {
+            unimplemented!()
+        }
+
+        async fn pending(&self, _user_id: &str) -> StorageResult<Vec<OfflineMessage>> {
+            Ok(Vec::new())
+        }
+
+        async fn remove(&self, _user_id: &str, _ids: &[i64]) -> StorageResult<()> {
+            Ok(())
+        }
+
+        async fn purge_expired(&self) -> StorageResult<u64> {
+            Ok(0)
+        }
+    }
+
+    fn hub() -> RelayHub {
+        RelayHub::new(RelayConfig {
+            max_bytes: 10,
+            max_concurrent: 4,
+            daily_bytes_per_user: None,
+        })
+    }
+
+    fn mailbox() -> Mailbox {
+        Mailbox::new(Arc::new(NoQueue), OfflineQueuePolicy::default())
+    }
+
+    /// Connect the share's owner, returning their inbox
+    async fn owner_online(mailbox: &Mailbox) -> mpsc::Receiver<SyncMessage> {
+        let (connection, inbox) = mpsc::channel(CONNECTION_BUFFER);
+        assert!(mailbox.attach("alice", connection).await.is_empty());
+        inbox
+    }
+
+    fn code(error: &ApiError) -> (StatusCode, &str) {
+        (error.0, error.1.code.as_str())
+    }
+
+    #[tokio::test]
+    async fn test_relay_access_denied() {
+        let (hub, mailbox) = (hub(), mailbox());
+        let mut inbox = owner_online(&mailbox).await;
+        let shares = OneShare { allowed: "bob" };
+
+        let error = request_relay(&hub, &shares, &mailbox, "share", "mallory")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(code(&error), (StatusCode::FORBIDDEN, "ACCESS_DENIED"));
+        let error = request_relay(&hub, &shares, &mailbox, "missing", "bob")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(code(&error), (StatusCode::NOT_FOUND, "SHARE_NOT_FOUND"));
+        // The owner is never asked
+        assert!(inbox.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_relay_owner_offline() {
+        let (hub, mailbox) = (hub(), mailbox());
+        let shares = OneShare { allowed: "bob" };
+
+        let error = request_relay(&hub, &shares, &mailbox, "share", "bob")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(code(&error), (StatusCode::CONFLICT, "OWNER_OFFLINE"));
+    }
+
+    #[tokio::test]
+    async fn test_relay_declined() {
+        let (hub, mailbox) = (hub(), mailbox());
+        let mut inbox = owner_online(&mailbox).await;
+        let shares = OneShare { allowed: "bob" };
+
+        let (_, offer) = request_relay(&hub, &shares, &mailbox, "share", "bob")
+            .await
+            .unwrap();
+        let Some(SyncMessage::RelayRequest {
+            relay_id,
+            token,
+            requester_id,
+            max_bytes,
+            ..
+        }) = inbox.recv().await
+        else {
+            panic!("the owner was not asked to upload");
+        };
+        assert_eq!(relay_id, offer.id);
+        assert_eq!(token, "share");
+        assert_eq!(requester_id, "bob");
+        assert_eq!(max_bytes, 10);
+
+        // Only the owner may decline
+        assert_eq!(
+            hub.decline(relay_id, "mallory").err(),
+            Some(RelayError::Forbidden)
+        );
+        hub.decline(relay_id, "alice").unwrap();
+        let error: ApiError = offer
+            .wait(Duration::from_secs(5))
+            .await
+            .err()
+            .unwrap()
+            .into();
+        assert_eq!(code(&error), (StatusCode::BAD_GATEWAY, "RELAY_DECLINED"));
+    }
+
+    #[tokio::test]
+    async fn test_relay_rejects_oversized_upload() {
+        let (hub, mailbox) = (hub(), mailbox());
+        let _inbox = owner_online(&mailbox).await;
+        let shares = OneShare { allowed: "bob" };
+
+        // Announced too large, so refused before any bytes are read
+        let (_, offer) = request_relay(&hub, &shares, &mailbox, "share", "bob")
+            .await
+            .unwrap();
+        let error = forward_upload(&hub, offer.id, "alice", Some(11), Body::from(vec![0; 11]))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            code(&error),
+            (StatusCode::PAYLOAD_TOO_LARGE, "RELAY_TOO_LARGE")
+        );
+        assert_eq!(
+            offer.wait(Duration::from_secs(5)).await.err(),
+            Some(RelayError::TooLarge(10))
+        );
+
+        // Unannounced, so cut off once the limit is passed
+        let (_, offer) = request_relay(&hub, &shares, &mailbox, "share", "bob")
+            .await
+            .unwrap();
+        let error = forward_upload(&hub, offer.id, "alice", None, Body::from(vec![0; 11]))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            code(&error),
+            (StatusCode::PAYLOAD_TOO_LARGE, "RELAY_TOO_LARGE")
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Retention policy endpoints
+//!
+//! Team admins override the server-wide retention for their team's jobs;
+//! users see which of their results are expired and about to be deleted,
+//! and administrators read the log of purged files.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::retention::{effective_policy, expiring_jobs, ExpiringJob, RetentionPolicy};
+use crate::state::ServerState;
+use crate::storage::{
+    PostgresRetentionStore, PostgresTeamStore, PurgeRecord, RetentionStore, StorageError,
+    TeamRetention, TeamStore, User,
+};
+
+/// Longest retention a team may set, about a century
+const MAX_RETAIN_DAYS: u32 = 36_500;
+const MAX_GRACE_DAYS: u32 = 365;
+const DEFAULT_PURGE_LIMIT: i64 = 100;
+const MAX_PURGE_LIMIT: i64 = 1000;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn retention_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn internal_error(e: impl std::fmt::Display) -> ApiError {
+    warn!("Retention storage error: {}", e);
+    retention_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error",
+        "INTERNAL_ERROR",
+    )
+}
+
+fn get_store(state: &ServerState) -> PostgresRetentionStore {
+    PostgresRetentionStore::new(state.db_pool.clone())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetTeamRetentionRequest {
+    pub retain_days: u32,
+    /// Defaults to the server-wide grace period
+    pub grace_days: Option<u32>,
+}
+
+/// A team's retention: its override, if any, and the policy in effect
+#[derive(Debug, Serialize)]
+pub struct TeamRetentionResponse {
+    pub team_id: Uuid,
+    #[serde(rename = "override")]
+    pub team_override: Option<TeamRetention>,
+    /// Absent when files are kept forever
+    pub policy: Option<RetentionPolicy>,
+}
+
+/// The caller's retention and their results in the grace period
+#[derive(Debug, Serialize)]
+pub struct MyRetentionResponse {
+    pub policy: Option<RetentionPolicy>,
+    pub expiring: Vec<ExpiringJob>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PurgeQuery {
+    pub limit: Option<i64>,
+}
+
+async fn caller(state: &ServerState, headers: &HeaderMap) -> Result<User, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| retention_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| retention_error(StatusCode::UNAUTHORIZED, "Unknown user", "UNAUTHORIZED"))
+}
+
+/// Require a team admin or server admin; members may also read
+async fn require_team_access(
+    state: &ServerState,
+    user: &User,
+    team_id: Uuid,
+    write: bool,
+) -> Result<(), ApiError> {
+    if user.is_admin {
+        return Ok(());
+    }
+    let teams = PostgresTeamStore::new(state.db_pool.clone());
+    let allowed = if write {
+        teams.is_team_admin(team_id, user.id).await
+    } else {
+        teams.is_team_member(team_id, user.id).await
+    };
+    if allowed.unwrap_or(false) {
+        Ok(())
+    } else if write {
+        Err(retention_error(
+            StatusCode::FORBIDDEN,
+            "Not a team admin",
+            "FORBIDDEN",
+        ))
+    } else {
+        Err(retention_error(
+            StatusCode::FORBIDDEN,
+            "Not a team member",
+            "FORBIDDEN",
+        ))
+    }
+}
+
+fn team_response(
+    state: &ServerState,
+    team_id: Uuid,
+    team_override: Option<TeamRetention>,
+) -> TeamRetentionResponse {
+    let policy = match &team_override {
+        Some(team) => Some(RetentionPolicy::from(team)),
+        None => state.config.retention,
+    };
+    TeamRetentionResponse {
+        team_id,
+        team_override,
+        policy,
+    }
+}
+
+/// A team's retention policy
+pub async fn get_team_retention(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(team_id): Path<Uuid>,
+) -> Result<Json<TeamRetentionResponse>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    require_team_access(&state, &user, team_id, false).await?;
+
+    let team_override = get_store(&state)
+        .get_team_retention(team_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(team_response(&state, team_id, team_override)))
+}
+
+/// Override the server-wide retention for a team (team admins)
+pub async fn set_team_retention(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(team_id): Path<Uuid>,
+    Json(request): Json<SetTeamRetentionRequest>,
+) -> Result<Json<TeamRetentionResponse>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    require_team_access(&state, &user, team_id, true).await?;
+
+    let grace_days = request.grace_days.unwrap_or(
+        state
+            .config
+            .retention
+            .map(|policy| policy.grace_days)
+            .unwrap_or(crate::retention::DEFAULT_GRACE_DAYS),
+    );
+    if request.retain_days == 0 || request.retain_days > MAX_RETAIN_DAYS {
+        return Err(retention_error(
+            StatusCode::BAD_REQUEST,
+            "retain_days must be between 1 and 36500",
+            "INVALID_INPUT",
+        ));
+    }
+    if grace_days > MAX_GRACE_DAYS {
+        return Err(retention_error(
+            StatusCode::BAD_REQUEST,
+            "grace_days must be at most 365",
+            "INVALID_INPUT",
+        ));
+    }
+
+    let policy = TeamRetention {
+        team_id,
+        retain_days: request.retain_days as i32,
+        grace_days: grace_days as i32,
+        updated_by: user.email.clone(),
+        updated_at: Utc::now(),
+    };
+    get_store(&state)
+        .set_team_retention(&policy)
+        .await
+        .map_err(internal_error)?;
+    info!(
+        "{} set retention of team {} to {} days ({} days grace)",
+        user.email, team_id, policy.retain_days, policy.grace_days
+    );
+
+    Ok(Json(team_response(&state, team_id, Some(policy))))
+}
+
+/// Remove a team's override so the server-wide retention applies
+pub async fn delete_team_retention(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(team_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = caller(&state, &headers).await?;
+    require_team_access(&state, &user, team_id, true).await?;
+
+    match get_store(&state).delete_team_retention(team_id).await {
+        Ok(()) => {
+            info!("{} removed retention of team {}", user.email, team_id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Err(StorageError::NotFound(_)) => Err(retention_error(
+            StatusCode::NOT_FOUND,
+            "Team has no retention policy",
+            "NOT_FOUND",
+        )),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+/// The caller's retention and their results that are about to be deleted
+pub async fn get_my_retention(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<MyRetentionResponse>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let teams = get_store(&state)
+        .retention_for_user(&user.email)
+        .await
+        .map_err(internal_error)?;
+    let policy = effective_policy(state.config.retention, &teams);
+    let expiring = match policy {
+        Some(policy) => expiring_jobs(
+            &state.job_queue.get_user_jobs(&user.email).await,
+            policy,
+            Utc::now(),
+        ),
+        None => Vec::new(),
+    };
+    Ok(Json(MyRetentionResponse { policy, expiring }))
+}
+
+/// Files retention archived and deleted, newest first (administrators)
+pub async fn list_retention_purges(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Query(query): Query<PurgeQuery>,
+) -> Result<Json<Vec<PurgeRecord>>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    if !user.is_admin {
+        warn!("Non-admin user {} requested the purge log", user.email);
+        return Err(retention_error(
+            StatusCode::FORBIDDEN,
+            "Administrator access required",
+            "FORBIDDEN",
+        ));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PURGE_LIMIT)
+        .clamp(1, MAX_PURGE_LIMIT);
+    let purges = get_store(&state)
+        .list_purges(limit)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(purges))
+}
@@ -0,0 +1,199 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::handlers::egress::require_admin;
+use crate::jobs::DDAParameters;
+use crate::scheduler::{
+    AnalysisSchedule, CronSchedule, ParameterPreset, ScheduleRun, ScheduleTarget,
+};
+use crate::state::ServerState;
+use crate::storage::{PostgresTeamStore, TeamStore};
+
+/// Maximum length of schedule and preset names
+const MAX_NAME_LENGTH: usize = 256;
+
+/// Request to create a recurring analysis
+#[derive(Debug, Deserialize)]
+pub struct CreateScheduleRequest {
+    pub name: String,
+    /// Team notified of every run
+    pub team_id: Uuid,
+    /// Five-field cron expression (UTC), e.g. `"0 2 * * *"`
+    pub cron: String,
+    pub preset_name: String,
+    pub parameters: DDAParameters,
+    pub target: ScheduleTarget,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Error response
+#[derive(Debug, Serialize)]
+pub struct ScheduleErrorResponse {
+    pub error: String,
+    pub code: String,
+}
+
+type ScheduleError = (StatusCode, Json<ScheduleErrorResponse>);
+
+fn schedule_error(status: StatusCode, error: &str, code: &str) -> ScheduleError {
+    (
+        status,
+        Json(ScheduleErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+async fn require_schedule_admin(
+    state: &ServerState,
+    headers: &axum::http::HeaderMap,
+) -> Result<String, ScheduleError> {
+    require_admin(state, headers)
+        .await
+        .map_err(|(status, Json(e))| schedule_error(status, &e.error, &e.code))
+}
+
+fn validate_name(name: &str, field: &str) -> Result<String, ScheduleError> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(schedule_error(
+            StatusCode::BAD_REQUEST,
+            &format!("'{}' must be 1-{} characters", field, MAX_NAME_LENGTH),
+            "INVALID_INPUT",
+        ));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_target(target: &ScheduleTarget) -> Result<(), ScheduleError> {
+    let valid = match target {
+        ScheduleTarget::Files { paths } => !paths.is_empty(),
+        ScheduleTarget::NewestMatching { pattern, count } => {
+            !pattern.trim().is_empty() && *count > 0
+        }
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(schedule_error(
+            StatusCode::BAD_REQUEST,
+            "Schedule target must name at least one file or a pattern with count > 0",
+            "INVALID_INPUT",
+        ))
+    }
+}
+
+/// List recurring analyses (admin only)
+pub async fn list_schedules(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<AnalysisSchedule>>, ScheduleError> {
+    require_schedule_admin(&state, &headers).await?;
+    Ok(Json(state.scheduler.list()))
+}
+
+/// Create a recurring analysis (admin only)
+///
+/// Jobs are submitted as the creating admin and tagged with the schedule id.
+pub async fn create_schedule(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<CreateScheduleRequest>,
+) -> Result<(StatusCode, Json<AnalysisSchedule>), ScheduleError> {
+    let admin = require_schedule_admin(&state, &headers).await?;
+
+    if state.config.server_files_directory.is_none() {
+        return Err(schedule_error(
+            StatusCode::BAD_REQUEST,
+            "Server-side file access is not configured",
+            "NOT_CONFIGURED",
+        ));
+    }
+    let name = validate_name(&request.name, "name")?;
+    let preset_name = validate_name(&request.preset_name, "preset_name")?;
+    validate_target(&request.target)?;
+    request
+        .parameters
+        .preprocessing
+        .validate()
+        .map_err(|e| schedule_error(StatusCode::BAD_REQUEST, &e, "INVALID_INPUT"))?;
+    let cron: CronSchedule = request
+        .cron
+        .parse()
+        .map_err(|e: String| schedule_error(StatusCode::BAD_REQUEST, &e, "INVALID_CRON"))?;
+    if cron.next_after(Utc::now()).is_none() {
+        return Err(schedule_error(
+            StatusCode::BAD_REQUEST,
+            "Cron expression never fires",
+            "INVALID_CRON",
+        ));
+    }
+    PostgresTeamStore::new(state.db_pool.clone())
+        .get_team(request.team_id)
+        .await
+        .map_err(|_| schedule_error(StatusCode::NOT_FOUND, "Team not found", "NOT_FOUND"))?;
+
+    let mut schedule = AnalysisSchedule::new(
+        name,
+        request.team_id,
+        admin.clone(),
+        cron,
+        ParameterPreset {
+            name: preset_name,
+            parameters: request.parameters,
+        },
+        request.target,
+    );
+    schedule.enabled = request.enabled;
+    state.scheduler.insert(schedule.clone());
+    info!(
+        "Schedule '{}' ({}) created by {}",
+        schedule.name, schedule.cron, admin
+    );
+
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+/// Delete a recurring analysis (admin only); jobs it already submitted are kept
+pub async fn delete_schedule(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<StatusCode, ScheduleError> {
+    let admin = require_schedule_admin(&state, &headers).await?;
+    let removed = state
+        .scheduler
+        .remove(schedule_id)
+        .ok_or_else(|| schedule_error(StatusCode::NOT_FOUND, "Schedule not found", "NOT_FOUND"))?;
+    info!("Schedule '{}' deleted by {}", removed.name, admin);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Run a schedule immediately, outside its cron timing (admin only)
+pub async fn run_schedule_now(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<Json<ScheduleRun>, ScheduleError> {
+    require_schedule_admin(&state, &headers).await?;
+    let schedule = state
+        .scheduler
+        .get(schedule_id)
+        .ok_or_else(|| schedule_error(StatusCode::NOT_FOUND, "Schedule not found", "NOT_FOUND"))?;
+    let run = state.scheduler.run_now(&state, &schedule, Utc::now()).await;
+    Ok(Json(run))
+}
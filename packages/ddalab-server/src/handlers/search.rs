@@ -0,0 +1,301 @@
+//! Search across the caller's jobs and shares
+//!
+//! A query is split into lowercase words, dropping filler such as "the" or
+//! "from", and each word matches as a prefix of a word in an item. Shares are
+//! found through the full-text index on their title and description; jobs
+//! live in the queue and are matched in memory against their file name,
+//! channels, messages and parameters. Both are ranked the same way, by the
+//! share of query words they match, weighted towards names and titles, so
+//! "seizure patient17 march" ranks the job on `patient17_seizure.edf`
+//! submitted in March above other seizure recordings.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::warn;
+
+use super::auth::ErrorResponse;
+use super::listing::{Listing, ListingQuery, Page};
+use crate::jobs::DDAJob;
+use crate::state::ServerState;
+use crate::storage::ShareMetadata;
+
+/// Longest query accepted
+const MAX_QUERY_LENGTH: usize = 256;
+
+/// Most words of a query that are searched for
+const MAX_TERMS: usize = 16;
+
+/// Most shares fetched from the index before ranking
+const SHARE_CANDIDATES: i64 = 500;
+
+/// Words too common to narrow a search
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "at", "by", "for", "from", "in", "my", "of", "on", "or", "that", "the",
+    "this", "to", "with",
+];
+
+/// Weight of a match in a name or title; other text counts 1
+const NAME_WEIGHT: f64 = 2.0;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn search_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    /// `job` or `share`; both when omitted
+    pub kind: Option<SearchKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchKind {
+    Job,
+    Share,
+}
+
+/// One search result
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub kind: SearchKind,
+    /// Job ID or share token
+    pub id: String,
+    /// File name of a job, title of a share
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Between 0 and [`NAME_WEIGHT`]; higher is better
+    pub score: f64,
+}
+
+/// Fields of `SearchHit` that search results can sort by and select
+const SEARCH_LISTING: Listing = Listing {
+    fields: &["kind", "id", "title", "description", "created_at", "score"],
+    key: "id",
+    default_sort: "-score",
+    status_field: None,
+    date_field: Some("created_at"),
+    name_field: Some("title"),
+};
+
+/// Lowercase words of `text`, split at anything not a letter or digit
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// The words of a query worth searching for
+pub fn search_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in words(query) {
+        if !STOPWORDS.contains(&word.as_str()) && !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms.truncate(MAX_TERMS);
+    terms
+}
+
+/// Score `fields`, pairs of text and weight, against `terms`
+///
+/// Each term counts with the weight of the best field it prefixes a word
+/// of; the sum is divided by the number of terms.
+fn score(terms: &[String], fields: &[(&str, f64)]) -> f64 {
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let fields: Vec<(Vec<String>, f64)> = fields
+        .iter()
+        .map(|(text, weight)| (words(text).collect(), *weight))
+        .collect();
+    let total: f64 = terms
+        .iter()
+        .map(|term| {
+            fields
+                .iter()
+                .filter(|(words, _)| words.iter().any(|word| word.starts_with(term.as_str())))
+                .map(|(_, weight)| *weight)
+                .fold(0.0, f64::max)
+        })
+        .sum();
+    total / terms.len() as f64
+}
+
+fn job_hit(terms: &[String], job: &DDAJob) -> Option<SearchHit> {
+    let parameters = &job.parameters;
+    let mut channels = parameters.channels.join(" ");
+    for (a, b) in parameters.ct_pairs.iter().chain(&parameters.cd_pairs) {
+        channels.push(' ');
+        channels.push_str(a);
+        channels.push(' ');
+        channels.push_str(b);
+    }
+    let details = format!(
+        "{} {} {} window {} delta {} embedding {} svd {}",
+        job.status,
+        job.message.as_deref().unwrap_or_default(),
+        job.error.as_deref().unwrap_or_default(),
+        parameters.time_window,
+        parameters.delta,
+        parameters.embedding_dim,
+        parameters.svd_dimensions,
+    );
+    // Month and year, so "march 2024" finds jobs by when they ran
+    let date = job.submitted_at.format("%B %Y").to_string();
+
+    let score = score(
+        terms,
+        &[
+            (&job.original_filename, NAME_WEIGHT),
+            (&channels, 1.0),
+            (&details, 1.0),
+            (&date, 1.0),
+        ],
+    );
+    (score > 0.0).then(|| SearchHit {
+        kind: SearchKind::Job,
+        id: job.id.to_string(),
+        title: job.original_filename.clone(),
+        description: job.message.clone(),
+        created_at: job.submitted_at,
+        score,
+    })
+}
+
+fn share_hit(terms: &[String], token: String, metadata: ShareMetadata) -> Option<SearchHit> {
+    let date = metadata.created_at.format("%B %Y").to_string();
+    let score = score(
+        terms,
+        &[
+            (&metadata.title, NAME_WEIGHT),
+            (metadata.description.as_deref().unwrap_or_default(), 1.0),
+            (&date, 1.0),
+        ],
+    );
+    (score > 0.0).then_some(SearchHit {
+        kind: SearchKind::Share,
+        id: token,
+        title: metadata.title,
+        description: metadata.description,
+        created_at: metadata.created_at,
+        score,
+    })
+}
+
+/// Search the caller's jobs and shares, best matches first
+pub async fn search(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Query(query): Query<SearchQuery>,
+    Query(listing): Query<ListingQuery>,
+) -> Result<Page, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(&headers)
+        .map_err(|e| search_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+
+    if query.q.len() > MAX_QUERY_LENGTH {
+        return Err(search_error(
+            StatusCode::BAD_REQUEST,
+            "Query too long",
+            "INVALID_INPUT",
+        ));
+    }
+    let terms = search_terms(&query.q);
+    if terms.is_empty() {
+        return Err(search_error(
+            StatusCode::BAD_REQUEST,
+            "Query has no words to search for",
+            "INVALID_INPUT",
+        ));
+    }
+
+    let mut hits = Vec::new();
+    if query.kind != Some(SearchKind::Share) {
+        hits.extend(
+            state
+                .job_queue
+                .get_user_jobs(&email)
+                .await
+                .iter()
+                .filter_map(|job| job_hit(&terms, job)),
+        );
+    }
+    if query.kind != Some(SearchKind::Job) {
+        let shares = state
+            .share_store
+            .search_user_shares(&email, &terms, SHARE_CANDIDATES)
+            .await
+            .map_err(|e| {
+                warn!("Share search failed: {}", e);
+                search_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal error",
+                    "INTERNAL_ERROR",
+                )
+            })?;
+        hits.extend(
+            shares
+                .into_iter()
+                .filter_map(|(token, metadata)| share_hit(&terms, token, metadata)),
+        );
+    }
+
+    listing
+        .apply(&hits, &SEARCH_LISTING)
+        .map_err(|(status, error)| search_error(status, &error, "INVALID_INPUT"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::{DDAParameters, FileSource};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_search_terms_drop_filler() {
+        assert_eq!(
+            search_terms("That seizure analysis on Patient-17 from March, patient"),
+            ["seizure", "analysis", "patient", "17", "march"]
+        );
+        assert!(search_terms("the of, from!").is_empty());
+    }
+
+    #[test]
+    fn test_jobs_rank_by_matched_terms() {
+        let mut job = DDAJob::new(
+            "user@example.com".to_string(),
+            FileSource::ServerPath("/data/p17.edf".into()),
+            "patient17_seizure.edf".to_string(),
+            DDAParameters {
+                channels: vec!["Fp1".to_string(), "Cz".to_string()],
+                ..Default::default()
+            },
+            false,
+        );
+        job.submitted_at = Utc.with_ymd_and_hms(2024, 3, 14, 9, 0, 0).unwrap();
+
+        let hit = job_hit(&search_terms("seizure patient17 march"), &job).unwrap();
+        assert!((hit.score - 5.0 / 3.0).abs() < 1e-9);
+        let channel = job_hit(&search_terms("cz"), &job).unwrap();
+        assert!(hit.score > channel.score);
+        assert!(job_hit(&search_terms("sleep"), &job).is_none());
+    }
+}
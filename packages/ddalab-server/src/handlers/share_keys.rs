@@ -0,0 +1,113 @@
+//! Share key endpoints
+//!
+//! Clients publish the X25519 public key shares are sealed for with
+//! `PUT /api/users/me/share-key` and look up recipients' keys with
+//! `GET /api/share-keys?users=<id>,<id>` before sealing a share. Private
+//! keys never reach the server.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::auth::ErrorResponse;
+use crate::crypto::{decode_public_key, MAX_SEALED_RECIPIENTS};
+use crate::state::ServerState;
+use crate::storage::{PostgresShareKeyStore, ShareKey, ShareKeyStore, StorageError};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn share_key_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn storage_error(e: StorageError) -> ApiError {
+    error!("Share key storage failed: {}", e);
+    share_key_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error",
+        "INTERNAL_ERROR",
+    )
+}
+
+/// Publish a share key
+#[derive(Debug, Deserialize)]
+pub struct SetShareKeyRequest {
+    /// Base64-encoded X25519 public key (32 bytes)
+    pub public_key: String,
+}
+
+/// Users whose share keys to look up
+#[derive(Debug, Deserialize)]
+pub struct ShareKeysQuery {
+    /// Comma-separated user ids
+    pub users: String,
+}
+
+/// The caller's user id
+fn caller(state: &ServerState, headers: &HeaderMap) -> Result<String, ApiError> {
+    state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| share_key_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))
+}
+
+/// Publish or replace the caller's share key
+///
+/// Shares sealed for the previous key can only be opened with its private
+/// half, so clients should keep it.
+pub async fn set_my_share_key(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<SetShareKeyRequest>,
+) -> Result<Json<ShareKey>, ApiError> {
+    let user_id = caller(&state, &headers)?;
+    decode_public_key(&request.public_key)
+        .map_err(|e| share_key_error(StatusCode::BAD_REQUEST, &e.to_string(), "INVALID_INPUT"))?;
+
+    let key = PostgresShareKeyStore::new(state.db_pool.clone())
+        .set_share_key(&user_id, &request.public_key)
+        .await
+        .map_err(storage_error)?;
+    info!("{} published a share key", user_id);
+    Ok(Json(key))
+}
+
+/// Share keys of the requested users; users without one are left out
+pub async fn get_share_keys(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Query(query): Query<ShareKeysQuery>,
+) -> Result<Json<Vec<ShareKey>>, ApiError> {
+    caller(&state, &headers)?;
+    let user_ids: Vec<String> = query
+        .users
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect();
+    if user_ids.len() > MAX_SEALED_RECIPIENTS {
+        return Err(share_key_error(
+            StatusCode::BAD_REQUEST,
+            "Too many users",
+            "INVALID_INPUT",
+        ));
+    }
+
+    let keys = PostgresShareKeyStore::new(state.db_pool.clone())
+        .get_share_keys(&user_ids)
+        .await
+        .map_err(storage_error)?;
+    Ok(Json(keys))
+}
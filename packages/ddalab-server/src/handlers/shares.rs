@@ -1,13 +1,22 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::{header, StatusCode},
+    extract::{ConnectInfo, Path, Query, State},
+    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+use crate::crypto::SealedPayload;
+use crate::handlers::egress::record_egress;
+use crate::handlers::listing::{Listing, ListingQuery, Page};
+use crate::handlers::organizations::scope_share_policy;
+use crate::notifications::notify_share;
 use crate::state::ServerState;
-use crate::storage::{AccessPolicy, ShareMetadata, ShareableContentType, SharedResultInfo};
+use crate::storage::{
+    AccessPolicy, EgressEntry, EgressKind, ShareMetadata, ShareableContentType, SharedResultInfo,
+};
+use crate::sync::notify_share_recipients;
 
 /// Maximum lengths for input validation
 const MAX_TOKEN_LENGTH: usize = 128;
@@ -26,19 +35,10 @@ pub struct CreateShareRequest {
     pub description: Option<String>,
     pub access_policy: AccessPolicy,
     pub owner_user_id: String,
-}
-
-/// Pagination query parameters
-#[derive(Debug, Deserialize)]
-pub struct PaginationQuery {
-    #[serde(default = "default_limit")]
-    pub limit: usize,
+    /// Title, description and content sealed for the recipients; `title`
+    /// must then be empty and `description` absent
     #[serde(default)]
-    pub offset: usize,
-}
-
-fn default_limit() -> usize {
-    100
+    pub sealed: Option<SealedPayload>,
 }
 
 /// Validate input lengths to prevent DoS
@@ -72,12 +72,33 @@ fn validate_create_request(req: &CreateShareRequest) -> Result<(), ShareErrorRes
     Ok(())
 }
 
-/// Share list response
+/// One entry of a user's share listing
 #[derive(Debug, Serialize)]
-pub struct ShareListResponse {
-    pub shares: Vec<String>,
+pub struct ShareListItem {
+    pub share_token: String,
+    #[serde(flatten)]
+    pub metadata: ShareMetadata,
 }
 
+/// Fields of `ShareListItem` that share listings can sort by and select
+const SHARE_LISTING: Listing = Listing {
+    fields: &[
+        "share_token",
+        "content_type",
+        "content_id",
+        "title",
+        "description",
+        "created_at",
+        "access_policy",
+        "classification",
+    ],
+    key: "share_token",
+    default_sort: "-created_at",
+    status_field: None,
+    date_field: Some("created_at"),
+    name_field: Some("title"),
+};
+
 /// Error response
 #[derive(Debug, Serialize)]
 pub struct ShareErrorResponse {
@@ -106,6 +127,19 @@ pub async fn create_share(
         ));
     }
 
+    let mut access_policy = request.access_policy;
+    scope_share_policy(&state, &caller_user_id, &mut access_policy)
+        .await
+        .map_err(|(status, Json(e))| {
+            (
+                status,
+                Json(ShareErrorResponse {
+                    error: e.error,
+                    code: e.code,
+                }),
+            )
+        })?;
+
     let metadata = ShareMetadata {
         owner_user_id: request.owner_user_id,
         content_type: request.content_type,
@@ -113,15 +147,25 @@ pub async fn create_share(
         title: request.title,
         description: request.description,
         created_at: chrono::Utc::now(),
-        access_policy: request.access_policy,
+        access_policy,
         classification: Default::default(),
         download_count: 0,
         last_accessed_at: None,
+        sealed: request.sealed,
     };
+    metadata.validate_sealed().map_err(|error| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ShareErrorResponse {
+                error,
+                code: "INVALID_INPUT".to_string(),
+            }),
+        )
+    })?;
 
     state
         .share_store
-        .publish_result(&request.token, metadata, None)
+        .publish_result(&request.token, metadata.clone(), None)
         .await
         .map_err(|e| {
             (
@@ -133,6 +177,23 @@ pub async fn create_share(
             )
         })?;
 
+    tokio::spawn(notify_share_recipients(
+        state.mailbox.clone(),
+        state.user_store.clone(),
+        state.db_pool.clone(),
+        request.token.clone(),
+        metadata.clone(),
+    ));
+    if let Some(notifier) = state.notifier.clone() {
+        tokio::spawn(notify_share(
+            notifier,
+            state.user_store.clone(),
+            state.db_pool.clone(),
+            request.token,
+            metadata,
+        ));
+    }
+
     Ok(StatusCode::CREATED)
 }
 
@@ -141,44 +202,22 @@ fn extract_user_from_auth(
     state: &ServerState,
     headers: &axum::http::HeaderMap,
 ) -> Result<String, (StatusCode, Json<ShareErrorResponse>)> {
-    let auth_header = headers
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(ShareErrorResponse {
-                    error: "Missing authorization".to_string(),
-                    code: "UNAUTHORIZED".to_string(),
-                }),
-            )
-        })?;
-
-    let token = if auth_header.starts_with("Bearer ") {
-        &auth_header[7..]
-    } else {
-        auth_header
-    };
-
-    state
-        .auth_state
-        .session_manager
-        .validate_token(token)
-        .map(|(_, user_id)| user_id)
-        .ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(ShareErrorResponse {
-                    error: "Invalid session".to_string(),
-                    code: "UNAUTHORIZED".to_string(),
-                }),
-            )
-        })
+    state.auth_state.authenticate(headers).map_err(|e| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ShareErrorResponse {
+                error: e.message().to_string(),
+                code: "UNAUTHORIZED".to_string(),
+            }),
+        )
+    })
 }
 
 /// Get share info by token
 pub async fn get_share(
     State(state): State<Arc<ServerState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: axum::http::HeaderMap,
     Path(token): Path<String>,
 ) -> Result<Json<SharedResultInfo>, (StatusCode, Json<ShareErrorResponse>)> {
     let metadata = state
@@ -207,11 +246,45 @@ pub async fn get_share(
         String::new()
     };
 
-    Ok(Json(SharedResultInfo {
+    let transfer_policy = state
+        .config
+        .transfer_policy
+        .with_overrides(&metadata.access_policy.transfer.unwrap_or_default());
+    let info = SharedResultInfo {
         metadata,
         download_url,
         owner_online,
-    }))
+        transfer_policy,
+    };
+
+    // Record egress; lineage comes from the shared result's source file when known
+    let requester = extract_user_from_auth(&state, &headers).unwrap_or_else(|_| "anonymous".to_string());
+    let source_dataset = state
+        .share_store
+        .get_share_content(&token)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|content| {
+            content
+                .get("file_path")
+                .and_then(|p| p.as_str())
+                .map(|p| p.to_string())
+        })
+        .unwrap_or_else(|| info.metadata.content_id.clone());
+    // Only sealed results travel through this endpoint; the rest come from
+    // the owner's client, or through the relay, which records what it streams
+    let bytes = info
+        .metadata
+        .sealed
+        .as_ref()
+        .map_or(0, |sealed| sealed.ciphertext.len());
+    let entry = EgressEntry::new(EgressKind::ShareAccess, &requester, &token, bytes)
+        .ip_address(&addr.ip().to_string())
+        .source_dataset(&source_dataset);
+    record_egress(&state, entry);
+
+    Ok(Json(info))
 }
 
 /// Revoke a share
@@ -281,8 +354,8 @@ pub async fn list_user_shares(
     State(state): State<Arc<ServerState>>,
     headers: axum::http::HeaderMap,
     Path(user_id): Path<String>,
-    Query(pagination): Query<PaginationQuery>,
-) -> Result<Json<ShareListResponse>, (StatusCode, Json<ShareErrorResponse>)> {
+    Query(listing): Query<ListingQuery>,
+) -> Result<Page, (StatusCode, Json<ShareErrorResponse>)> {
     // Validate user_id length
     if user_id.len() > MAX_USER_ID_LENGTH {
         return Err((
@@ -306,13 +379,9 @@ pub async fn list_user_shares(
         ));
     }
 
-    // Enforce pagination limits
-    let limit = pagination.limit.min(1000); // Max 1000 per request
-    let offset = pagination.offset;
-
-    let all_shares = state
+    let shares: Vec<ShareListItem> = state
         .share_store
-        .list_user_shares(&user_id)
+        .list_user_share_metadata(&user_id)
         .await
         .map_err(|e| {
             (
@@ -322,14 +391,21 @@ pub async fn list_user_shares(
                     code: "LIST_ERROR".to_string(),
                 }),
             )
-        })?;
-
-    // Apply pagination
-    let shares: Vec<String> = all_shares
+        })?
         .into_iter()
-        .skip(offset)
-        .take(limit)
+        .map(|(share_token, metadata)| ShareListItem {
+            share_token,
+            metadata,
+        })
         .collect();
 
-    Ok(Json(ShareListResponse { shares }))
+    listing.apply(&shares, &SHARE_LISTING).map_err(|(status, error)| {
+        (
+            status,
+            Json(ShareErrorResponse {
+                error,
+                code: "INVALID_INPUT".to_string(),
+            }),
+        )
+    })
 }
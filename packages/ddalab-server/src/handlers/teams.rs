@@ -1,14 +1,18 @@
 use axum::{
     extract::{Path, State},
-    http::{header, StatusCode},
+    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::jobs::normalize_preset;
 use crate::state::ServerState;
-use crate::storage::{PostgresTeamStore, Team, TeamMember, TeamRole, TeamStore, TeamSummary};
+use crate::storage::{
+    OrganizationStore, PostgresOrganizationStore, PostgresTeamStore, Team, TeamMember, TeamPreset,
+    TeamRole, TeamStore, TeamSummary,
+};
 
 /// Maximum lengths for input validation
 const MAX_NAME_LENGTH: usize = 256;
@@ -25,6 +29,8 @@ pub struct CreateTeamRequest {
     pub name: String,
     pub description: Option<String>,
     pub institution_id: Uuid,
+    /// Organization to create the team in (organization admins only)
+    pub organization_id: Option<Uuid>,
 }
 
 /// Update team request
@@ -48,6 +54,21 @@ pub struct UpdateMemberRoleRequest {
     pub role: TeamRole,
 }
 
+/// Create or replace a parameter preset
+#[derive(Debug, Deserialize)]
+pub struct SaveTeamPresetRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Job parameter fields the preset sets
+    pub parameters: serde_json::Value,
+    /// Fields members must submit unchanged
+    #[serde(default)]
+    pub locked_fields: Vec<String>,
+    /// Members must submit every job under a required preset
+    #[serde(default)]
+    pub required: bool,
+}
+
 /// Team response with members
 #[derive(Debug, Serialize)]
 pub struct TeamResponse {
@@ -67,44 +88,24 @@ fn extract_user_from_auth(
     state: &ServerState,
     headers: &axum::http::HeaderMap,
 ) -> Result<(Uuid, String), (StatusCode, Json<TeamErrorResponse>)> {
-    let auth_header = headers
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(TeamErrorResponse {
-                    error: "Missing authorization".to_string(),
-                    code: "UNAUTHORIZED".to_string(),
-                }),
-            )
-        })?;
-
-    let token = if auth_header.starts_with("Bearer ") {
-        &auth_header[7..]
-    } else {
-        auth_header
+    let unauthorized = |error: &str| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(TeamErrorResponse {
+                error: error.to_string(),
+                code: "UNAUTHORIZED".to_string(),
+            }),
+        )
     };
 
-    state
+    let user_id = state
         .auth_state
-        .session_manager
-        .validate_token(token)
-        .and_then(|(_, user_id)| {
-            // Parse user_id as UUID - the system should store UUIDs
-            Uuid::try_parse(&user_id)
-                .ok()
-                .map(|uuid| (uuid, user_id))
-        })
-        .ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(TeamErrorResponse {
-                    error: "Invalid session or user ID format".to_string(),
-                    code: "UNAUTHORIZED".to_string(),
-                }),
-            )
-        })
+        .authenticate(headers)
+        .map_err(|e| unauthorized(e.message()))?;
+    // Parse user_id as UUID - the system should store UUIDs
+    Uuid::try_parse(&user_id)
+        .map(|uuid| (uuid, user_id))
+        .map_err(|_| unauthorized("Invalid session or user ID format"))
 }
 
 /// Create a new team
@@ -137,9 +138,26 @@ pub async fn create_team(
 
     let (user_uuid, _) = extract_user_from_auth(&state, &headers)?;
 
+    if let Some(organization_id) = request.organization_id {
+        let role = PostgresOrganizationStore::new(state.db_pool.clone())
+            .member_role(organization_id, user_uuid)
+            .await
+            .unwrap_or(None);
+        if !role.is_some_and(|role| role.can_manage()) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(TeamErrorResponse {
+                    error: "Not an organization admin".to_string(),
+                    code: "FORBIDDEN".to_string(),
+                }),
+            ));
+        }
+    }
+
     let team = Team {
         id: Uuid::new_v4(),
         institution_id: request.institution_id,
+        organization_id: request.organization_id,
         name: request.name,
         description: request.description,
         created_by: user_uuid,
@@ -305,6 +323,36 @@ pub async fn add_team_member(
         ));
     }
 
+    // Teams of an organization only take that organization's members
+    let organization_id = store
+        .get_team(team_id)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(TeamErrorResponse {
+                    error: e.to_string(),
+                    code: "TEAM_NOT_FOUND".to_string(),
+                }),
+            )
+        })?
+        .organization_id;
+    if let Some(organization_id) = organization_id {
+        let role = PostgresOrganizationStore::new(state.db_pool.clone())
+            .member_role(organization_id, request.user_id)
+            .await
+            .unwrap_or(None);
+        if role.is_none() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(TeamErrorResponse {
+                    error: "User is not a member of the team's organization".to_string(),
+                    code: "NOT_IN_ORGANIZATION".to_string(),
+                }),
+            ));
+        }
+    }
+
     let member = TeamMember {
         team_id,
         user_id: request.user_id,
@@ -359,3 +407,156 @@ pub async fn remove_team_member(
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+/// Reject callers who are not admins of the team
+async fn require_team_admin(
+    store: &PostgresTeamStore,
+    team_id: Uuid,
+    user_uuid: Uuid,
+) -> Result<(), (StatusCode, Json<TeamErrorResponse>)> {
+    if store.is_team_admin(team_id, user_uuid).await.unwrap_or(false) {
+        return Ok(());
+    }
+    Err((
+        StatusCode::FORBIDDEN,
+        Json(TeamErrorResponse {
+            error: "Not a team admin".to_string(),
+            code: "FORBIDDEN".to_string(),
+        }),
+    ))
+}
+
+/// Publish a parameter preset, replacing the team's preset of the same name
+pub async fn save_team_preset(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+    Path(team_id): Path<Uuid>,
+    Json(request): Json<SaveTeamPresetRequest>,
+) -> Result<Json<TeamPreset>, (StatusCode, Json<TeamErrorResponse>)> {
+    let invalid = |error: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(TeamErrorResponse {
+                error,
+                code: "INVALID_INPUT".to_string(),
+            }),
+        )
+    };
+    if request.name.trim().is_empty() || request.name.len() > MAX_NAME_LENGTH {
+        return Err(invalid("Preset name must be 1 to 256 characters".to_string()));
+    }
+    if request
+        .description
+        .as_ref()
+        .is_some_and(|desc| desc.len() > MAX_DESCRIPTION_LENGTH)
+    {
+        return Err(invalid("Description too long".to_string()));
+    }
+    let parameters = normalize_preset(&request.parameters, &request.locked_fields).map_err(invalid)?;
+
+    let (user_uuid, _) = extract_user_from_auth(&state, &headers)?;
+    let store = get_store(&state);
+    require_team_admin(&store, team_id, user_uuid).await?;
+
+    let now = chrono::Utc::now();
+    let preset = TeamPreset {
+        id: Uuid::new_v4(),
+        team_id,
+        name: request.name,
+        description: request.description,
+        parameters,
+        locked_fields: request.locked_fields,
+        required: request.required,
+        created_by: user_uuid,
+        created_at: now,
+        updated_at: now,
+    };
+    store.save_team_preset(&preset).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(TeamErrorResponse {
+                error: e.to_string(),
+                code: "SAVE_PRESET_ERROR".to_string(),
+            }),
+        )
+    })?;
+
+    Ok(Json(preset))
+}
+
+/// List a team's parameter presets (members only)
+pub async fn list_team_presets(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+    Path(team_id): Path<Uuid>,
+) -> Result<Json<Vec<TeamPreset>>, (StatusCode, Json<TeamErrorResponse>)> {
+    let (user_uuid, _) = extract_user_from_auth(&state, &headers)?;
+    let store = get_store(&state);
+
+    if !store.is_team_member(team_id, user_uuid).await.unwrap_or(false) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(TeamErrorResponse {
+                error: "Not a team member".to_string(),
+                code: "FORBIDDEN".to_string(),
+            }),
+        ));
+    }
+
+    let presets = store.list_team_presets(team_id).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(TeamErrorResponse {
+                error: e.to_string(),
+                code: "LIST_ERROR".to_string(),
+            }),
+        )
+    })?;
+
+    Ok(Json(presets))
+}
+
+/// Presets of every team the current user belongs to, fetched by clients
+/// when they connect
+pub async fn list_my_presets(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<TeamPreset>>, (StatusCode, Json<TeamErrorResponse>)> {
+    let (user_uuid, _) = extract_user_from_auth(&state, &headers)?;
+    let store = get_store(&state);
+
+    let presets = store.list_user_presets(user_uuid).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(TeamErrorResponse {
+                error: e.to_string(),
+                code: "LIST_ERROR".to_string(),
+            }),
+        )
+    })?;
+
+    Ok(Json(presets))
+}
+
+/// Delete a team parameter preset
+pub async fn delete_team_preset(
+    State(state): State<Arc<ServerState>>,
+    headers: axum::http::HeaderMap,
+    Path((team_id, preset_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<TeamErrorResponse>)> {
+    let (user_uuid, _) = extract_user_from_auth(&state, &headers)?;
+    let store = get_store(&state);
+    require_team_admin(&store, team_id, user_uuid).await?;
+
+    store.delete_team_preset(team_id, preset_id).await.map_err(|e| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(TeamErrorResponse {
+                error: e.to_string(),
+                code: "PRESET_NOT_FOUND".to_string(),
+            }),
+        )
+    })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,275 @@
+//! Personal access token endpoints
+//!
+//! Tokens are created from a session, never from another token, so a leaked
+//! token cannot mint more. The token value is returned once on creation.
+//! Administrators may issue tokens for other user accounts. There are no
+//! separate service-account principals: a CI pipeline gets a token of a
+//! regular user account set aside for it.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::auth::{
+    bearer_token, generate_api_token, hash_api_token, ApiTokenGrant, ApiTokenScope, MfaLevel,
+};
+use crate::state::ServerState;
+use crate::storage::{ApiToken, ApiTokenStore, CreateApiToken, PostgresApiTokenStore, User};
+
+const MAX_TOKEN_NAME_LENGTH: usize = 255;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn token_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn internal_error(e: impl std::fmt::Display) -> ApiError {
+    warn!("Access token storage error: {}", e);
+    token_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error",
+        "INTERNAL_ERROR",
+    )
+}
+
+fn get_store(state: &ServerState) -> PostgresApiTokenStore {
+    PostgresApiTokenStore::new(state.db_pool.clone())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTokenRequest {
+    /// Label shown in the token list, e.g. "nightly CI"
+    pub name: String,
+    pub scope: ApiTokenScope,
+    /// Lifetime in days; the token does not expire when omitted
+    pub expires_in_days: Option<u32>,
+    /// Account to issue the token for (administrators only)
+    pub user_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateTokenResponse {
+    /// Shown only once
+    pub token: String,
+    pub api_token: ApiToken,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListTokensQuery {
+    /// Another account's tokens (administrators only)
+    pub user_id: Option<String>,
+}
+
+/// The grant an issued token confers, unless it has expired
+pub fn api_token_grant(token: &ApiToken) -> Option<ApiTokenGrant> {
+    let grant = ApiTokenGrant {
+        token_id: token.id,
+        user_id: token.user_id.clone(),
+        scope: token.scope.parse().ok()?,
+        expires_at: token.expires_at,
+        mfa_required: token.mfa_required,
+        mfa_level: if token.mfa_verified {
+            MfaLevel::MultiFactor
+        } else {
+            MfaLevel::Password
+        },
+    };
+    (!grant.is_expired()).then_some(grant)
+}
+
+/// Start accepting every stored token that has not expired
+pub async fn load_api_tokens(state: &ServerState) -> Result<usize, crate::storage::StorageError> {
+    let tokens = get_store(state).active_tokens().await?;
+    let mut loaded = 0;
+    for token in &tokens {
+        if let Some(grant) = api_token_grant(token) {
+            state
+                .auth_state
+                .session_manager
+                .register_api_token(token.token_hash.clone(), grant);
+            loaded += 1;
+        }
+    }
+    Ok(loaded)
+}
+
+/// Resolve the caller, refusing requests made with an access token
+async fn session_caller(
+    state: &ServerState,
+    headers: &HeaderMap,
+) -> Result<(String, User), ApiError> {
+    let token = bearer_token(headers).unwrap_or_default();
+    if state
+        .auth_state
+        .session_manager
+        .api_token_scope(token)
+        .is_some()
+    {
+        return Err(token_error(
+            StatusCode::FORBIDDEN,
+            "Access tokens are managed from a login session",
+            "SESSION_REQUIRED",
+        ));
+    }
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| token_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    let user = state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| token_error(StatusCode::UNAUTHORIZED, "Invalid session", "UNAUTHORIZED"))?;
+    Ok((token.to_string(), user))
+}
+
+/// The account a request targets: the caller, or anyone for administrators
+async fn target_user(
+    state: &ServerState,
+    caller: &User,
+    user_id: Option<String>,
+) -> Result<User, ApiError> {
+    match user_id {
+        Some(email) if email != caller.email => {
+            if !caller.is_admin {
+                return Err(token_error(
+                    StatusCode::FORBIDDEN,
+                    "Administrator access required",
+                    "FORBIDDEN",
+                ));
+            }
+            state
+                .user_store
+                .get_user_by_email(&email)
+                .await
+                .map_err(|_| token_error(StatusCode::NOT_FOUND, "User not found", "NOT_FOUND"))
+        }
+        _ => Ok(caller.clone()),
+    }
+}
+
+/// Issue a new access token
+pub async fn create_api_token(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateTokenRequest>,
+) -> Result<Json<CreateTokenResponse>, ApiError> {
+    let (session_token, caller) = session_caller(&state, &headers).await?;
+    let owner = target_user(&state, &caller, request.user_id).await?;
+
+    let name = request.name.trim();
+    if name.is_empty() || name.len() > MAX_TOKEN_NAME_LENGTH {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "Token name must be 1-255 characters",
+            "INVALID_NAME",
+        ));
+    }
+    if request.scope == ApiTokenScope::Admin && !owner.is_admin {
+        return Err(token_error(
+            StatusCode::FORBIDDEN,
+            "Only administrators can hold admin tokens",
+            "FORBIDDEN",
+        ));
+    }
+    if !owner.is_active {
+        return Err(token_error(
+            StatusCode::BAD_REQUEST,
+            "Account is disabled",
+            "ACCOUNT_DISABLED",
+        ));
+    }
+
+    let session_level = state
+        .auth_state
+        .session_manager
+        .mfa_status(&session_token)
+        .map(|(_, level)| level)
+        .unwrap_or(MfaLevel::Password);
+    let token = generate_api_token();
+    let token_hash = hash_api_token(&token);
+    let api_token = get_store(&state)
+        .create_token(CreateApiToken {
+            user_id: &owner.email,
+            name,
+            scope: request.scope.as_str(),
+            token_hash: &token_hash,
+            mfa_required: owner.mfa_required,
+            mfa_verified: session_level == MfaLevel::MultiFactor,
+            created_by: &caller.email,
+            expires_at: request
+                .expires_in_days
+                .map(|days| Utc::now() + Duration::days(i64::from(days))),
+        })
+        .await
+        .map_err(internal_error)?;
+
+    if let Some(grant) = api_token_grant(&api_token) {
+        state
+            .auth_state
+            .session_manager
+            .register_api_token(token_hash, grant);
+    }
+    info!(
+        "{} issued {} token '{}' for {}",
+        caller.email, api_token.scope, api_token.name, owner.email
+    );
+
+    Ok(Json(CreateTokenResponse { token, api_token }))
+}
+
+/// List access tokens
+pub async fn list_api_tokens(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Query(query): Query<ListTokensQuery>,
+) -> Result<Json<Vec<ApiToken>>, ApiError> {
+    let (_, caller) = session_caller(&state, &headers).await?;
+    let owner = target_user(&state, &caller, query.user_id).await?;
+
+    let tokens = get_store(&state)
+        .list_tokens(&owner.email)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(tokens))
+}
+
+/// Revoke an access token
+pub async fn revoke_api_token(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(token_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let (_, caller) = session_caller(&state, &headers).await?;
+    let store = get_store(&state);
+
+    let not_found = || token_error(StatusCode::NOT_FOUND, "Token not found", "NOT_FOUND");
+    let token = store.get_token(token_id).await.map_err(|_| not_found())?;
+    if token.user_id != caller.email && !caller.is_admin {
+        return Err(not_found());
+    }
+
+    store.delete_token(token_id).await.map_err(internal_error)?;
+    state.auth_state.session_manager.revoke_api_token(token_id);
+    info!(
+        "{} revoked token '{}' of {}",
+        caller.email, token.name, token.user_id
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
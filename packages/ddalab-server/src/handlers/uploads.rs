@@ -0,0 +1,247 @@
+//! Resumable upload endpoints
+//!
+//! `POST /api/uploads` starts an upload with its size and job settings,
+//! `PATCH /api/uploads/{id}` appends a chunk at the `Upload-Offset` it
+//! names, `GET /api/uploads/{id}` (or `HEAD`) reports the offset to resume
+//! from, and `POST /api/uploads/{id}/finalize` submits the job once every
+//! byte has arrived.
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::jobs::{
+    check_upload_job, enforce_user_quota, extract_user_id, reject_during_maintenance,
+    submit_upload,
+};
+use crate::jobs::{SubmitJobResponse, UploadError, UploadJobOptions, UploadProgress};
+use crate::state::ServerState;
+
+/// Bytes of an upload received so far, on every upload response
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+
+/// Declared size of an upload
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+
+/// Longest file name accepted
+const MAX_FILENAME_LENGTH: usize = 255;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateUploadRequest {
+    pub filename: String,
+    /// Total size in bytes
+    pub size: u64,
+    /// Job to submit once the upload is complete
+    #[serde(flatten)]
+    pub job: UploadJobOptions,
+}
+
+fn upload_error(e: UploadError) -> (StatusCode, String) {
+    let status = match &e {
+        UploadError::NotFound => StatusCode::NOT_FOUND,
+        UploadError::OffsetMismatch { .. } | UploadError::Incomplete { .. } => StatusCode::CONFLICT,
+        UploadError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        UploadError::Interrupted(_) => StatusCode::BAD_REQUEST,
+        UploadError::Io(e) => {
+            error!("Resumable upload failed: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Upload failed".to_string(),
+            );
+        }
+    };
+    (status, e.to_string())
+}
+
+fn progress_response(status: StatusCode, progress: UploadProgress) -> Response {
+    (
+        status,
+        [
+            (UPLOAD_OFFSET, HeaderValue::from(progress.offset)),
+            (UPLOAD_LENGTH, HeaderValue::from(progress.size)),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-store")),
+        ],
+        Json(progress),
+    )
+        .into_response()
+}
+
+/// Start a resumable upload
+pub async fn create_upload(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateUploadRequest>,
+) -> Result<Response, (StatusCode, String)> {
+    reject_during_maintenance(&state)?;
+    let user_id = extract_user_id(&state, &headers);
+
+    let filename = request.filename.trim();
+    if filename.is_empty() || filename.len() > MAX_FILENAME_LENGTH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "File name must be 1 to 255 characters".to_string(),
+        ));
+    }
+    if request.size == 0 {
+        return Err((StatusCode::BAD_REQUEST, "Upload is empty".to_string()));
+    }
+    if request.size > state.config.max_upload_size {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "File too large. Maximum size: {} bytes",
+                state.config.max_upload_size
+            ),
+        ));
+    }
+    // Refuse the job now rather than after gigabytes have been sent
+    enforce_user_quota(&state, &user_id, request.size).await?;
+    check_upload_job(&state, &user_id, &request.job).await?;
+
+    let pruned = state.uploads.prune_expired().await;
+    if pruned > 0 {
+        info!("Removed {} abandoned uploads", pruned);
+    }
+    let progress = state
+        .uploads
+        .create(&user_id, filename, request.size, request.job)
+        .await
+        .map_err(upload_error)?;
+
+    let mut response = progress_response(StatusCode::CREATED, progress.clone());
+    if let Ok(location) = HeaderValue::from_str(&format!("/api/uploads/{}", progress.upload_id)) {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    Ok(response)
+}
+
+/// Report how much of an upload has arrived
+pub async fn get_upload(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+) -> Result<Response, (StatusCode, String)> {
+    let user_id = extract_user_id(&state, &headers);
+    let session = state
+        .uploads
+        .get(upload_id, &user_id)
+        .await
+        .map_err(upload_error)?;
+    let progress = state
+        .uploads
+        .progress(&session)
+        .await
+        .map_err(upload_error)?;
+    Ok(progress_response(StatusCode::OK, progress))
+}
+
+/// Append the request body at the `Upload-Offset` it names
+pub async fn patch_upload(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+    body: Body,
+) -> Result<Response, (StatusCode, String)> {
+    let user_id = extract_user_id(&state, &headers);
+    let offset = headers
+        .get(&UPLOAD_OFFSET)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                "Upload-Offset header required".to_string(),
+            )
+        })?;
+
+    let offset = state
+        .uploads
+        .append(upload_id, &user_id, offset, body.into_data_stream())
+        .await
+        .map_err(upload_error)?;
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(UPLOAD_OFFSET, HeaderValue::from(offset))],
+    )
+        .into_response())
+}
+
+/// Submit the job of a complete upload
+pub async fn finalize_upload(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+) -> Result<Json<SubmitJobResponse>, (StatusCode, String)> {
+    reject_during_maintenance(&state)?;
+    let user_id = extract_user_id(&state, &headers);
+    let session = state
+        .uploads
+        .get(upload_id, &user_id)
+        .await
+        .map_err(upload_error)?;
+    enforce_user_quota(&state, &user_id, session.size).await?;
+
+    let blobs = state.job_queue.blobs();
+    let store_failed = |e: std::io::Error| {
+        error!("Failed to store upload {}: {}", upload_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to store upload".to_string(),
+        )
+    };
+    let staged = blobs.staging_path().await.map_err(store_failed)?;
+    let session = state
+        .uploads
+        .finish(upload_id, &user_id, &staged)
+        .await
+        .map_err(upload_error)?;
+    // Chunks are appended in the clear; the finished file is hashed, then
+    // encrypted once
+    let stored = match blobs
+        .store_file(
+            &session.filename,
+            &staged,
+            state.config.storage_encryption.as_ref(),
+        )
+        .await
+    {
+        Ok(stored) => stored,
+        Err(e) => {
+            tokio::fs::remove_file(&staged).await.ok();
+            return Err(store_failed(e));
+        }
+    };
+    info!(
+        "File uploaded: {} ({} bytes, resumable{})",
+        stored.path.display(),
+        session.size,
+        if stored.deduplicated { ", deduplicated" } else { "" }
+    );
+    state.metrics.observe_upload(session.size);
+
+    submit_upload(&state, user_id, stored, session.filename, session.job).await
+}
+
+/// Abandon an upload and discard its bytes
+pub async fn delete_upload(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = extract_user_id(&state, &headers);
+    state
+        .uploads
+        .abort(upload_id, &user_id)
+        .await
+        .map_err(upload_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,293 @@
+//! Administrator user management
+//!
+//! The REST counterpart of the `user` CLI commands, so institutions can run
+//! the server without shell access to the host: list users with their job
+//! and disk usage, suspend and reactivate them, force a password change at
+//! the next login, and see or end active sessions.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use super::tokens::load_api_tokens;
+use crate::auth::SessionSummary;
+use crate::jobs::UsageSummary;
+use crate::state::ServerState;
+use crate::storage::{StorageError, User};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn user_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn storage_error(e: StorageError) -> ApiError {
+    match e {
+        StorageError::UserNotFound(_) | StorageError::NotFound(_) => {
+            user_error(StatusCode::NOT_FOUND, "User not found", "NOT_FOUND")
+        }
+        e => {
+            error!("User storage failed: {}", e);
+            user_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal error",
+                "INTERNAL_ERROR",
+            )
+        }
+    }
+}
+
+/// A user with their sessions and usage
+#[derive(Debug, Serialize)]
+pub struct AdminUserResponse {
+    pub id: Uuid,
+    pub email: String,
+    pub display_name: String,
+    pub is_admin: bool,
+    pub is_active: bool,
+    pub institution_id: Option<Uuid>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_login: Option<chrono::DateTime<chrono::Utc>>,
+    pub password_reset_required: bool,
+    pub mfa_required: bool,
+    /// Sessions that can still be used or refreshed
+    pub active_sessions: usize,
+    pub usage: UsageSummary,
+}
+
+/// Whose sessions to list
+#[derive(Debug, Deserialize)]
+pub struct ListSessionsQuery {
+    /// Only this user's sessions, by email
+    #[serde(default)]
+    pub user: Option<String>,
+}
+
+fn user_response(state: &ServerState, user: User, usage: UsageSummary) -> AdminUserResponse {
+    let active_sessions = state
+        .auth_state
+        .session_manager
+        .list_sessions(Some(&user.email))
+        .len();
+    AdminUserResponse {
+        id: user.id,
+        email: user.email,
+        display_name: user.display_name,
+        is_admin: user.is_admin,
+        is_active: user.is_active,
+        institution_id: user.institution_id,
+        created_at: user.created_at,
+        last_login: user.last_login,
+        password_reset_required: user.password_reset_required,
+        mfa_required: user.mfa_required,
+        active_sessions,
+        usage,
+    }
+}
+
+async fn describe_user(state: &ServerState, user_id: Uuid) -> Result<AdminUserResponse, ApiError> {
+    let user = state
+        .user_store
+        .get_user(user_id)
+        .await
+        .map_err(storage_error)?;
+    let usage = state
+        .job_queue
+        .usage_by_user()
+        .await
+        .remove(&user.email)
+        .unwrap_or_default();
+    Ok(user_response(state, user, usage))
+}
+
+async fn require_admin(state: &ServerState, headers: &HeaderMap) -> Result<User, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| user_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    let user = state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| user_error(StatusCode::UNAUTHORIZED, "Unknown user", "UNAUTHORIZED"))?;
+    if !user.is_admin {
+        warn!("Non-admin user {} requested user management", email);
+        return Err(user_error(
+            StatusCode::FORBIDDEN,
+            "Administrator access required",
+            "FORBIDDEN",
+        ));
+    }
+    Ok(user)
+}
+
+/// Every user with their sessions and usage (admin only)
+pub async fn list_users(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<AdminUserResponse>>, ApiError> {
+    require_admin(&state, &headers).await?;
+    let users = state.user_store.list_users().await.map_err(storage_error)?;
+    let mut usage = state.job_queue.usage_by_user().await;
+
+    Ok(Json(
+        users
+            .into_iter()
+            .map(|user| {
+                let summary = usage.remove(&user.email).unwrap_or_default();
+                user_response(&state, user, summary)
+            })
+            .collect(),
+    ))
+}
+
+/// One user with their sessions and usage (admin only)
+pub async fn get_user(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<AdminUserResponse>, ApiError> {
+    require_admin(&state, &headers).await?;
+    Ok(Json(describe_user(&state, user_id).await?))
+}
+
+/// Suspend a user, ending their sessions and access tokens (admin only)
+pub async fn suspend_user(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<AdminUserResponse>, ApiError> {
+    let admin = require_admin(&state, &headers).await?;
+    if admin.id == user_id {
+        return Err(user_error(
+            StatusCode::BAD_REQUEST,
+            "You cannot suspend yourself",
+            "INVALID_INPUT",
+        ));
+    }
+    let user = state
+        .user_store
+        .get_user(user_id)
+        .await
+        .map_err(storage_error)?;
+    state
+        .user_store
+        .set_user_active(user_id, false)
+        .await
+        .map_err(storage_error)?;
+    let sessions = &state.auth_state.session_manager;
+    sessions.revoke_user_sessions(&user.email);
+    sessions.revoke_user_api_tokens(&user.email);
+    info!("{} suspended {}", admin.email, user.email);
+
+    Ok(Json(describe_user(&state, user_id).await?))
+}
+
+/// Reactivate a suspended user and their access tokens (admin only)
+pub async fn activate_user(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<AdminUserResponse>, ApiError> {
+    let admin = require_admin(&state, &headers).await?;
+    let user = state
+        .user_store
+        .get_user(user_id)
+        .await
+        .map_err(storage_error)?;
+    state
+        .user_store
+        .set_user_active(user_id, true)
+        .await
+        .map_err(storage_error)?;
+    load_api_tokens(&state).await.map_err(storage_error)?;
+    info!("{} activated {}", admin.email, user.email);
+
+    Ok(Json(describe_user(&state, user_id).await?))
+}
+
+/// Make a user choose a new password, ending their sessions (admin only)
+pub async fn force_password_reset(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<AdminUserResponse>, ApiError> {
+    let admin = require_admin(&state, &headers).await?;
+    let user = state
+        .user_store
+        .get_user(user_id)
+        .await
+        .map_err(storage_error)?;
+    state
+        .user_store
+        .set_password_reset_required(user_id, true)
+        .await
+        .map_err(storage_error)?;
+    state
+        .auth_state
+        .session_manager
+        .revoke_user_sessions(&user.email);
+    info!(
+        "{} required a password reset of {}",
+        admin.email, user.email
+    );
+
+    Ok(Json(describe_user(&state, user_id).await?))
+}
+
+/// Active sessions, newest first (admin only)
+pub async fn list_sessions(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Query(query): Query<ListSessionsQuery>,
+) -> Result<Json<Vec<SessionSummary>>, ApiError> {
+    require_admin(&state, &headers).await?;
+    Ok(Json(
+        state
+            .auth_state
+            .session_manager
+            .list_sessions(query.user.as_deref()),
+    ))
+}
+
+/// End a session (admin only)
+pub async fn revoke_session(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(session_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let admin = require_admin(&state, &headers).await?;
+    let user_id = state
+        .auth_state
+        .session_manager
+        .revoke_session_by_id(session_id)
+        .ok_or_else(|| user_error(StatusCode::NOT_FOUND, "Session not found", "NOT_FOUND"))?;
+    info!(
+        "{} ended session {} of {}",
+        admin.email, session_id, user_id
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Job and disk usage of everyone with jobs on the server, by user (admin only)
+pub async fn usage_report(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<BTreeMap<String, UsageSummary>>, ApiError> {
+    require_admin(&state, &headers).await?;
+    Ok(Json(state.job_queue.usage_by_user().await))
+}
@@ -0,0 +1,221 @@
+//! Webhook endpoints
+//!
+//! Users register URLs to be told when their jobs are submitted, start,
+//! complete or fail; team admins can register webhooks that fire for the
+//! jobs of every team member. The signing secret is returned once, on
+//! creation.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::auth::ErrorResponse;
+use crate::state::ServerState;
+use crate::storage::{
+    CreateWebhook, PostgresTeamStore, PostgresWebhookStore, TeamStore, User, Webhook,
+    WebhookDelivery, WebhookStore,
+};
+use crate::webhooks::WebhookEvent;
+
+const MAX_URL_LENGTH: usize = 2048;
+const DEFAULT_DELIVERY_LIMIT: i64 = 50;
+const MAX_DELIVERY_LIMIT: i64 = 500;
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+fn webhook_error(status: StatusCode, error: &str, code: &str) -> ApiError {
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code: code.to_string(),
+        }),
+    )
+}
+
+fn internal_error(e: impl std::fmt::Display) -> ApiError {
+    warn!("Webhook storage error: {}", e);
+    webhook_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal error",
+        "INTERNAL_ERROR",
+    )
+}
+
+fn get_store(state: &ServerState) -> PostgresWebhookStore {
+    PostgresWebhookStore::new(state.db_pool.clone())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Event names to deliver; all events when omitted
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Fire for every member's jobs (team admins only)
+    pub team_id: Option<Uuid>,
+}
+
+/// A new webhook with its signing secret
+#[derive(Debug, Serialize)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryQuery {
+    pub limit: Option<i64>,
+}
+
+async fn caller(state: &ServerState, headers: &HeaderMap) -> Result<User, ApiError> {
+    let email = state
+        .auth_state
+        .authenticate(headers)
+        .map_err(|e| webhook_error(StatusCode::UNAUTHORIZED, e.message(), "UNAUTHORIZED"))?;
+    state
+        .user_store
+        .get_user_by_email(&email)
+        .await
+        .map_err(|_| webhook_error(StatusCode::UNAUTHORIZED, "Unknown user", "UNAUTHORIZED"))
+}
+
+/// Look up a webhook the caller owns; admins may manage any webhook
+async fn owned_webhook(
+    state: &ServerState,
+    user: &User,
+    webhook_id: Uuid,
+) -> Result<Webhook, ApiError> {
+    let not_found = || webhook_error(StatusCode::NOT_FOUND, "Webhook not found", "NOT_FOUND");
+    let webhook = get_store(state)
+        .get_webhook(webhook_id)
+        .await
+        .map_err(|_| not_found())?;
+    if webhook.user_id != user.email && !user.is_admin {
+        return Err(not_found());
+    }
+    Ok(webhook)
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Register a webhook
+pub async fn create_webhook(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<Json<CreateWebhookResponse>, ApiError> {
+    let user = caller(&state, &headers).await?;
+
+    let url = request.url.trim();
+    if url.len() > MAX_URL_LENGTH || !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err(webhook_error(
+            StatusCode::BAD_REQUEST,
+            "URL must be an http(s) URL of at most 2048 characters",
+            "INVALID_INPUT",
+        ));
+    }
+
+    let mut events = Vec::new();
+    for name in &request.events {
+        let event = name
+            .parse::<WebhookEvent>()
+            .map_err(|e| webhook_error(StatusCode::BAD_REQUEST, &e, "INVALID_INPUT"))?;
+        if !events.contains(&event.as_str().to_string()) {
+            events.push(event.as_str().to_string());
+        }
+    }
+
+    if let Some(team_id) = request.team_id {
+        let is_team_admin = PostgresTeamStore::new(state.db_pool.clone())
+            .is_team_admin(team_id, user.id)
+            .await
+            .unwrap_or(false);
+        if !is_team_admin {
+            return Err(webhook_error(
+                StatusCode::FORBIDDEN,
+                "Not a team admin",
+                "FORBIDDEN",
+            ));
+        }
+    }
+
+    let secret = generate_secret();
+    let webhook = get_store(&state)
+        .create_webhook(CreateWebhook {
+            user_id: &user.email,
+            team_id: request.team_id,
+            url,
+            secret: &secret,
+            events: &events,
+        })
+        .await
+        .map_err(internal_error)?;
+    info!("{} registered webhook {}", user.email, webhook.id);
+
+    Ok(Json(CreateWebhookResponse { webhook, secret }))
+}
+
+/// List the caller's webhooks
+pub async fn list_webhooks(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Webhook>>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    let webhooks = get_store(&state)
+        .list_webhooks(&user.email)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(webhooks))
+}
+
+/// Remove a webhook
+pub async fn delete_webhook(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user = caller(&state, &headers).await?;
+    owned_webhook(&state, &user, webhook_id).await?;
+
+    get_store(&state)
+        .delete_webhook(webhook_id)
+        .await
+        .map_err(internal_error)?;
+    info!("{} removed webhook {}", user.email, webhook_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Recent delivery attempts of a webhook, newest first
+pub async fn list_webhook_deliveries(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(webhook_id): Path<Uuid>,
+    Query(query): Query<DeliveryQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, ApiError> {
+    let user = caller(&state, &headers).await?;
+    owned_webhook(&state, &user, webhook_id).await?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_DELIVERY_LIMIT)
+        .clamp(1, MAX_DELIVERY_LIMIT);
+    let deliveries = get_store(&state)
+        .list_deliveries(webhook_id, limit)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(deliveries))
+}
@@ -0,0 +1,322 @@
+//! Content-addressed uploads
+//!
+//! The same large recording is often uploaded again and again by different
+//! members of a team. Uploads are therefore stored once per content, under
+//! the SHA-256 of their bytes, the hash annotations are keyed by:
+//! `<upload dir>/blobs/<sha256>.<extension>`. Every job whose input is a
+//! blob holds a reference to it, and the last job to let go takes the blob
+//! out of the store. References live with the jobs in memory, so blobs left
+//! from before a restart are picked up again by new uploads of the same
+//! contents, or swept by retention like other orphaned files.
+
+use axum::body::Bytes;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::crypto::{write_file, StorageKey};
+
+/// Directory under the upload directory holding blobs
+const BLOB_DIRECTORY: &str = "blobs";
+
+/// Directory under the blob directory holding blobs whose last reference
+/// was released, until the releasing caller deletes them
+const RELEASED_DIRECTORY: &str = ".released";
+
+/// SHA-256 of a file's contents, in lowercase hex
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// An upload in the store, with one reference held for its job
+#[derive(Debug, Clone)]
+pub struct StoredUpload {
+    pub path: PathBuf,
+    /// SHA-256 of the contents, in hex
+    pub hash: String,
+    /// Whether the copy of an earlier upload was reused
+    pub deduplicated: bool,
+}
+
+/// Uploads on disk, one copy per content
+pub struct UploadBlobs {
+    directory: PathBuf,
+    /// References jobs hold, by blob path; the lock also serializes blobs
+    /// entering and leaving the store
+    references: Mutex<HashMap<PathBuf, usize>>,
+}
+
+impl UploadBlobs {
+    pub fn new(upload_directory: &Path) -> Self {
+        Self {
+            directory: upload_directory.join(BLOB_DIRECTORY),
+            references: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Directory the blobs are stored in
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Whether `path` is a blob, rather than an upload stored before
+    /// deduplication
+    pub fn contains(&self, path: &Path) -> bool {
+        path.parent() == Some(self.directory.as_path())
+    }
+
+    /// References jobs hold to the blob at `path`
+    pub async fn references(&self, path: &Path) -> usize {
+        self.references.lock().await.get(path).copied().unwrap_or(0)
+    }
+
+    fn blob_path(&self, hash: &str, filename: &str) -> PathBuf {
+        // The DDA binary tells formats apart by extension
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .filter(|e| e.len() <= 16 && e.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("edf");
+        self.directory.join(format!("{}.{}", hash, extension))
+    }
+
+    /// A fresh path in the blob directory to write an upload to before it
+    /// is stored with [`UploadBlobs::store_file`]
+    pub async fn staging_path(&self) -> io::Result<PathBuf> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        Ok(self.directory.join(format!(".{}.partial", Uuid::new_v4())))
+    }
+
+    /// Take a reference to the blob at `path`, if it is stored
+    async fn reuse(&self, path: &Path) -> bool {
+        let mut references = self.references.lock().await;
+        if !path.exists() {
+            return false;
+        }
+        *references.entry(path.to_path_buf()).or_default() += 1;
+        // Recently used again, so the orphan sweep leaves it alone
+        if let Ok(file) = std::fs::File::options().append(true).open(path) {
+            file.set_modified(SystemTime::now()).ok();
+        }
+        true
+    }
+
+    /// Move a staged file into the store as `path` and take a reference;
+    /// returns whether an identical upload got there first
+    async fn adopt(&self, staged: &Path, path: &Path) -> io::Result<bool> {
+        let mut references = self.references.lock().await;
+        let deduplicated = path.exists();
+        if deduplicated {
+            tokio::fs::remove_file(staged).await.ok();
+        } else {
+            tokio::fs::rename(staged, path).await?;
+        }
+        *references.entry(path.to_path_buf()).or_default() += 1;
+        Ok(deduplicated)
+    }
+
+    /// Store uploaded bytes, encrypted with `key` if given, unless the same
+    /// contents are stored already
+    pub async fn store_bytes(
+        &self,
+        filename: &str,
+        data: Bytes,
+        key: Option<&StorageKey>,
+    ) -> io::Result<StoredUpload> {
+        let hash = {
+            let data = data.clone();
+            tokio::task::spawn_blocking(move || hex::encode(Sha256::digest(&data)))
+                .await
+                .map_err(io::Error::other)?
+        };
+        let path = self.blob_path(&hash, filename);
+        if self.reuse(&path).await {
+            return Ok(StoredUpload {
+                path,
+                hash,
+                deduplicated: true,
+            });
+        }
+
+        let staged = self.staging_path().await?;
+        let (key, target) = (key.cloned(), staged.clone());
+        let written = tokio::task::spawn_blocking(move || write_file(key.as_ref(), &target, &data))
+            .await
+            .map_err(io::Error::other)
+            .and_then(|result| result);
+        if let Err(e) = written {
+            tokio::fs::remove_file(&staged).await.ok();
+            return Err(e);
+        }
+        let deduplicated = self.adopt(&staged, &path).await?;
+        Ok(StoredUpload {
+            path,
+            hash,
+            deduplicated,
+        })
+    }
+
+    /// Store the plaintext file at `staged`, encrypted with `key` if given;
+    /// the file is moved into the store, or removed when the same contents
+    /// are stored already
+    pub async fn store_file(
+        &self,
+        filename: &str,
+        staged: &Path,
+        key: Option<&StorageKey>,
+    ) -> io::Result<StoredUpload> {
+        let hash = {
+            let staged = staged.to_path_buf();
+            tokio::task::spawn_blocking(move || hash_file(&staged))
+                .await
+                .map_err(io::Error::other)??
+        };
+        let path = self.blob_path(&hash, filename);
+        if self.reuse(&path).await {
+            tokio::fs::remove_file(staged).await.ok();
+            return Ok(StoredUpload {
+                path,
+                hash,
+                deduplicated: true,
+            });
+        }
+
+        if let Some(key) = key.cloned() {
+            let target = staged.to_path_buf();
+            tokio::task::spawn_blocking(move || key.encrypt_in_place(&target))
+                .await
+                .map_err(io::Error::other)??;
+        }
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let deduplicated = self.adopt(staged, &path).await?;
+        Ok(StoredUpload {
+            path,
+            hash,
+            deduplicated,
+        })
+    }
+
+    /// Drop one reference to the upload at `path`
+    ///
+    /// Returns the file to delete once nothing refers to it any more. A
+    /// blob is first moved out of the store, so that a new upload of the
+    /// same contents cannot pick it up while it is being deleted. Uploads
+    /// stored before deduplication are returned as they are.
+    pub async fn release(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+        if !self.contains(path) {
+            return Ok(Some(path.to_path_buf()));
+        }
+        let mut references = self.references.lock().await;
+        if let Some(count) = references.get_mut(path) {
+            *count = count.saturating_sub(1);
+            if *count > 0 {
+                return Ok(None);
+            }
+        }
+        references.remove(path);
+
+        let released = self.directory.join(RELEASED_DIRECTORY);
+        tokio::fs::create_dir_all(&released).await?;
+        let released = released.join(path.file_name().unwrap_or_default());
+        match tokio::fs::rename(path, &released).await {
+            Ok(()) => Ok(Some(released)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Put a released blob back at `path` for a job that still needs it
+    pub async fn restore(&self, released: &Path, path: &Path) -> io::Result<()> {
+        let mut references = self.references.lock().await;
+        if path.exists() {
+            // Uploaded again in the meantime
+            tokio::fs::remove_file(released).await?;
+        } else {
+            tokio::fs::rename(released, path).await?;
+        }
+        *references.entry(path.to_path_buf()).or_default() += 1;
+        Ok(())
+    }
+
+    /// Drop one reference to the upload at `path`, deleting it with the last
+    pub async fn discard(&self, path: &Path) {
+        match self.release(path).await {
+            Ok(Some(file)) => match tokio::fs::remove_file(&file).await {
+                Ok(()) => info!("Deleted upload {:?}", path),
+                Err(e) => warn!("Failed to delete upload {:?}: {}", path, e),
+            },
+            Ok(None) => {}
+            Err(e) => warn!("Failed to release upload {:?}: {}", path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_identical_uploads_share_one_blob_until_released() {
+        let dir = std::env::temp_dir().join(format!("ddalab-blobs-{}", Uuid::new_v4()));
+        let blobs = UploadBlobs::new(&dir);
+
+        let first = blobs
+            .store_bytes("a.edf", Bytes::from_static(b"recording"), None)
+            .await
+            .unwrap();
+        assert!(!first.deduplicated);
+        assert_eq!(first.hash, hex::encode(Sha256::digest(b"recording")));
+
+        let staged = blobs.staging_path().await.unwrap();
+        std::fs::write(&staged, b"recording").unwrap();
+        let second = blobs.store_file("b.edf", &staged, None).await.unwrap();
+        assert!(second.deduplicated);
+        assert_eq!(second.path, first.path);
+        assert!(!staged.exists());
+        assert_eq!(blobs.references(&first.path).await, 2);
+
+        let other = blobs
+            .store_bytes("a.edf", Bytes::from_static(b"other"), None)
+            .await
+            .unwrap();
+        assert_ne!(other.path, first.path);
+
+        assert_eq!(blobs.release(&first.path).await.unwrap(), None);
+        assert!(first.path.exists());
+        let released = blobs.release(&first.path).await.unwrap().unwrap();
+        assert!(!first.path.exists());
+        assert_eq!(std::fs::read(&released).unwrap(), b"recording");
+
+        // Kept after all, e.g. when archiving failed
+        blobs.restore(&released, &first.path).await.unwrap();
+        assert_eq!(blobs.references(&first.path).await, 1);
+        blobs.discard(&first.path).await;
+        assert!(!first.path.exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_uploads_from_before_deduplication_are_released_as_they_are() {
+        let dir = std::env::temp_dir().join(format!("ddalab-blobs-{}", Uuid::new_v4()));
+        let blobs = UploadBlobs::new(&dir);
+        let legacy = dir.join("1234_recording.edf");
+        assert!(!blobs.contains(&legacy));
+        assert_eq!(blobs.release(&legacy).await.unwrap(), Some(legacy));
+    }
+}
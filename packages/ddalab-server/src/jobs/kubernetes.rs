@@ -0,0 +1,356 @@
+//! Kubernetes Jobs for remote runs
+//!
+//! With `DDA_EXECUTOR=kubernetes`, every attempt becomes a Job in
+//! `KUBERNETES_NAMESPACE` whose single pod runs `ddalab-server worker-node
+//! --run <id>` from `KUBERNETES_WORKER_IMAGE`. The pod claims that one run
+//! from the server at `EXECUTOR_SERVER_URL`, authenticating with the
+//! executor token from the `KUBERNETES_TOKEN_SECRET` secret, and exits once
+//! it is done. The server talks to the API as its pod's service account,
+//! which needs permission to create and delete Jobs in the namespace.
+
+use rustls::pki_types::{pem::PemObject, CertificateDer};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::types::DDAJob;
+
+/// Where a pod's service account credentials are mounted
+const SERVICE_ACCOUNT_DIRECTORY: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// How long finished Jobs are kept for their pods' logs
+const FINISHED_JOB_TTL_SECONDS: u32 = 600;
+
+const API_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Kubernetes settings for remote runs
+#[derive(Debug, Clone)]
+pub struct KubernetesConfig {
+    /// API server, in-cluster unless `KUBERNETES_API_URL` is set
+    pub api_url: String,
+    pub namespace: String,
+    /// Image with `ddalab-server` and the DDA binary
+    pub image: String,
+    /// Path of `ddalab-server` in the image (`KUBERNETES_WORKER_COMMAND`)
+    pub command: String,
+    /// URL pods reach this server at (`EXECUTOR_SERVER_URL`)
+    pub server_url: String,
+    /// Secret whose `token` key holds the executor token
+    pub token_secret: String,
+    /// CPU and memory requested for each pod, e.g. "2" and "4Gi"
+    pub cpu: Option<String>,
+    pub memory: Option<String>,
+    /// Service account token, read for every request since it is rotated
+    pub token_path: PathBuf,
+    /// CA of the API server's certificate, from `KUBERNETES_CA_PATH` or the
+    /// service account; the system roots when unset
+    pub ca: Option<rustls::RootCertStore>,
+}
+
+impl KubernetesConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let required = |name: &str| {
+            var(name).ok_or_else(|| format!("{} must be set with DDA_EXECUTOR=kubernetes", name))
+        };
+        let service_account = PathBuf::from(SERVICE_ACCOUNT_DIRECTORY);
+
+        let api_url = match (
+            var("KUBERNETES_API_URL"),
+            var("KUBERNETES_SERVICE_HOST"),
+            var("KUBERNETES_SERVICE_PORT"),
+        ) {
+            (Some(url), _, _) => url.trim_end_matches('/').to_string(),
+            (None, Some(host), port) if host.contains(':') => {
+                format!("https://[{}]:{}", host, port.as_deref().unwrap_or("443"))
+            }
+            (None, Some(host), port) => {
+                format!("https://{}:{}", host, port.as_deref().unwrap_or("443"))
+            }
+            (None, None, _) => {
+                return Err("KUBERNETES_API_URL must be set outside a cluster".to_string())
+            }
+        };
+        let namespace = var("KUBERNETES_NAMESPACE")
+            .or_else(|| {
+                std::fs::read_to_string(service_account.join("namespace"))
+                    .ok()
+                    .map(|namespace| namespace.trim().to_string())
+            })
+            .unwrap_or_else(|| "default".to_string());
+        let ca = match var("KUBERNETES_CA_PATH")
+            .map(PathBuf::from)
+            .or_else(|| Some(service_account.join("ca.crt")).filter(|path| path.exists()))
+        {
+            Some(path) => Some(load_ca(&path)?),
+            None => None,
+        };
+
+        Ok(Self {
+            api_url,
+            namespace,
+            image: required("KUBERNETES_WORKER_IMAGE")?,
+            command: var("KUBERNETES_WORKER_COMMAND")
+                .unwrap_or_else(|| "/app/ddalab-server".to_string()),
+            server_url: required("EXECUTOR_SERVER_URL")?,
+            token_secret: var("KUBERNETES_TOKEN_SECRET")
+                .unwrap_or_else(|| "ddalab-executor".to_string()),
+            cpu: var("KUBERNETES_WORKER_CPU"),
+            memory: var("KUBERNETES_WORKER_MEMORY"),
+            token_path: var("KUBERNETES_TOKEN_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| service_account.join("token")),
+            ca,
+        })
+    }
+}
+
+fn load_ca(path: &Path) -> Result<rustls::RootCertStore, String> {
+    let invalid = |e: &dyn std::fmt::Display| format!("Invalid CA {}: {}", path.display(), e);
+    let mut roots = rustls::RootCertStore::empty();
+    for certificate in CertificateDer::pem_file_iter(path).map_err(|e| invalid(&e))? {
+        roots
+            .add(certificate.map_err(|e| invalid(&e))?)
+            .map_err(|e| invalid(&e))?;
+    }
+    Ok(roots)
+}
+
+/// Creates and deletes the Jobs of remote runs
+#[derive(Debug)]
+pub struct KubernetesLauncher {
+    config: KubernetesConfig,
+    agent: ureq::Agent,
+}
+
+impl KubernetesLauncher {
+    pub fn new(config: KubernetesConfig) -> Self {
+        let mut agent = ureq::AgentBuilder::new().timeout(API_TIMEOUT);
+        if let Some(roots) = &config.ca {
+            let tls = rustls::ClientConfig::builder_with_provider(Arc::new(
+                rustls::crypto::ring::default_provider(),
+            ))
+            .with_safe_default_protocol_versions()
+            .expect("ring supports the default protocol versions")
+            .with_root_certificates(roots.clone())
+            .with_no_client_auth();
+            agent = agent.tls_config(Arc::new(tls));
+        }
+        Self {
+            config,
+            agent: agent.build(),
+        }
+    }
+
+    fn job_name(run_id: Uuid) -> String {
+        format!("ddalab-run-{}", run_id.simple())
+    }
+
+    fn jobs_url(&self) -> String {
+        format!(
+            "{}/apis/batch/v1/namespaces/{}/jobs",
+            self.config.api_url, self.config.namespace
+        )
+    }
+
+    fn bearer(&self) -> Result<String, String> {
+        std::fs::read_to_string(&self.config.token_path)
+            .map(|token| format!("Bearer {}", token.trim()))
+            .map_err(|e| {
+                format!(
+                    "Failed to read service account token {}: {}",
+                    self.config.token_path.display(),
+                    e
+                )
+            })
+    }
+
+    /// The Job running `run_id` of `job`
+    pub fn manifest(&self, run_id: Uuid, job: &DDAJob) -> Value {
+        let labels = json!({
+            "app.kubernetes.io/name": "ddalab-worker",
+            "ddalab.io/job": job.id.to_string(),
+            "ddalab.io/run": run_id.to_string(),
+        });
+        let mut requests = serde_json::Map::new();
+        if let Some(cpu) = &self.config.cpu {
+            requests.insert("cpu".to_string(), json!(cpu));
+        }
+        if let Some(memory) = &self.config.memory {
+            requests.insert("memory".to_string(), json!(memory));
+        }
+
+        json!({
+            "apiVersion": "batch/v1",
+            "kind": "Job",
+            "metadata": {
+                "name": Self::job_name(run_id),
+                "labels": labels,
+            },
+            "spec": {
+                // The server retries failed attempts itself
+                "backoffLimit": 0,
+                "ttlSecondsAfterFinished": FINISHED_JOB_TTL_SECONDS,
+                "template": {
+                    "metadata": { "labels": labels },
+                    "spec": {
+                        "restartPolicy": "Never",
+                        "containers": [{
+                            "name": "dda",
+                            "image": self.config.image,
+                            "command": [self.config.command],
+                            "args": [
+                                "worker-node",
+                                "--server", self.config.server_url,
+                                "--run", run_id.to_string(),
+                            ],
+                            "env": [{
+                                "name": "EXECUTOR_TOKEN",
+                                "valueFrom": {
+                                    "secretKeyRef": {
+                                        "name": self.config.token_secret,
+                                        "key": "token",
+                                    },
+                                },
+                            }],
+                            "resources": { "requests": requests },
+                        }],
+                    },
+                },
+            },
+        })
+    }
+
+    /// Start the Job for `run_id`
+    pub async fn create_job(&self, run_id: Uuid, job: &DDAJob) -> Result<(), String> {
+        let request = self
+            .agent
+            .post(&self.jobs_url())
+            .set("Authorization", &self.bearer()?);
+        let manifest = self.manifest(run_id, job).to_string();
+        tokio::task::spawn_blocking(move || {
+            request
+                .set("Content-Type", "application/json")
+                .send_string(&manifest)
+                .map_err(api_error)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        info!(
+            "Created Kubernetes Job {} for job {}",
+            Self::job_name(run_id),
+            job.id
+        );
+        Ok(())
+    }
+
+    /// Delete the Job for `run_id` with its pod, stopping it if it still runs
+    pub async fn delete_job(&self, run_id: Uuid) {
+        let name = Self::job_name(run_id);
+        let request = match self.bearer() {
+            Ok(bearer) => self
+                .agent
+                .delete(&format!("{}/{}", self.jobs_url(), name))
+                .set("Authorization", &bearer),
+            Err(e) => {
+                warn!("Failed to delete Kubernetes Job {}: {}", name, e);
+                return;
+            }
+        };
+        let body = json!({ "propagationPolicy": "Background" }).to_string();
+        let deleted = tokio::task::spawn_blocking(move || {
+            match request
+                .set("Content-Type", "application/json")
+                .send_string(&body)
+            {
+                Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+                Err(e) => Err(api_error(e)),
+            }
+        });
+        match deleted.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Failed to delete Kubernetes Job {}: {}", name, e),
+            Err(e) => warn!("Failed to delete Kubernetes Job {}: {}", name, e),
+        }
+    }
+}
+
+/// The API's reason for refusing a request
+fn api_error(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(status, response) => {
+            let message = response
+                .into_string()
+                .ok()
+                .and_then(|body| serde_json::from_str::<Value>(&body).ok())
+                .and_then(|body| body["message"].as_str().map(str::to_string));
+            match message {
+                Some(message) => format!("HTTP {}: {}", status, message),
+                None => format!("HTTP {}", status),
+            }
+        }
+        e => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::{DDAParameters, FileSource};
+
+    #[test]
+    fn test_manifest_runs_a_worker_node_for_one_run() {
+        let launcher = KubernetesLauncher::new(KubernetesConfig {
+            api_url: "https://10.0.0.1:443".to_string(),
+            namespace: "ddalab".to_string(),
+            image: "registry.example.org/ddalab-worker:1".to_string(),
+            command: "/app/ddalab-server".to_string(),
+            server_url: "http://ddalab-server.ddalab.svc:8080".to_string(),
+            token_secret: "ddalab-executor".to_string(),
+            cpu: Some("2".to_string()),
+            memory: None,
+            token_path: PathBuf::from("/nonexistent/token"),
+            ca: None,
+        });
+        let job = DDAJob::new(
+            "user".to_string(),
+            FileSource::ServerPath("/data/a.edf".into()),
+            "a.edf".to_string(),
+            DDAParameters::default(),
+            false,
+        );
+        let run_id = Uuid::new_v4();
+
+        let manifest = launcher.manifest(run_id, &job);
+        assert_eq!(
+            manifest["metadata"]["name"],
+            format!("ddalab-run-{}", run_id.simple())
+        );
+        assert_eq!(manifest["spec"]["backoffLimit"], 0);
+        let container = &manifest["spec"]["template"]["spec"]["containers"][0];
+        assert_eq!(
+            container["args"],
+            json!([
+                "worker-node",
+                "--server",
+                "http://ddalab-server.ddalab.svc:8080",
+                "--run",
+                run_id.to_string()
+            ])
+        );
+        assert_eq!(
+            container["env"][0]["valueFrom"]["secretKeyRef"]["name"],
+            "ddalab-executor"
+        );
+        assert_eq!(container["resources"]["requests"], json!({ "cpu": "2" }));
+        assert!(launcher.jobs_url().ends_with("/namespaces/ddalab/jobs"));
+    }
+}
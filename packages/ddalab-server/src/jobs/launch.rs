@@ -0,0 +1,327 @@
+//! How the DDA binary is launched
+//!
+//! By default the binary is spawned directly, which handles paths with
+//! spaces and UNC shares on every platform. Installs that need an
+//! interpreter or environment set up first can route the call through a
+//! shell instead (`DDA_LAUNCH_SHELL`); arguments are then quoted for that
+//! shell. On Windows the process is placed in a job object so cancelling a
+//! job also ends any helpers it started.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Direct spawn or via a shell command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaunchStrategy {
+    Direct,
+    Shell { shell: PathBuf },
+}
+
+/// Quoting rules of the shell a command line is handed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellFlavor {
+    /// `sh`, `bash`, `zsh`, ...
+    Posix,
+    /// `cmd.exe`
+    Cmd,
+}
+
+impl ShellFlavor {
+    pub fn of(shell: &Path) -> Self {
+        // Split on both separators so Windows paths are recognized everywhere
+        let path = shell.to_string_lossy().to_ascii_lowercase();
+        let name = path.rsplit(['/', '\\']).next().unwrap_or_default();
+        if name == "cmd" || name == "cmd.exe" {
+            ShellFlavor::Cmd
+        } else {
+            ShellFlavor::Posix
+        }
+    }
+}
+
+impl LaunchStrategy {
+    /// From `DDA_LAUNCH_SHELL`: unset or empty spawns directly, `default`
+    /// uses the platform shell (`sh` or `cmd.exe`), anything else is a shell path
+    pub fn from_env() -> Self {
+        match std::env::var("DDA_LAUNCH_SHELL") {
+            Ok(value) if value.trim().is_empty() => LaunchStrategy::Direct,
+            Ok(value) if value.trim() == "default" => LaunchStrategy::Shell {
+                shell: default_shell(),
+            },
+            Ok(value) => LaunchStrategy::Shell {
+                shell: PathBuf::from(value.trim()),
+            },
+            Err(_) => LaunchStrategy::Direct,
+        }
+    }
+
+    /// Command running `program` with `args`
+    pub fn command(&self, program: &Path, args: &[OsString]) -> Command {
+        let program = normalize_program_path(program);
+        match self {
+            LaunchStrategy::Direct => {
+                let mut cmd = Command::new(program);
+                cmd.args(args);
+                cmd
+            }
+            LaunchStrategy::Shell { shell } => {
+                let flavor = ShellFlavor::of(shell);
+                let line = command_line(flavor, &program, args);
+                let mut cmd = Command::new(shell);
+                match flavor {
+                    ShellFlavor::Posix => {
+                        cmd.arg("-c").arg(line);
+                    }
+                    ShellFlavor::Cmd => {
+                        // `/S` keeps cmd from stripping quotes it did not expect
+                        #[cfg(windows)]
+                        cmd.raw_arg(format!("/D /S /C \"{}\"", line));
+                        #[cfg(not(windows))]
+                        cmd.args(["/D", "/S", "/C", &line]);
+                    }
+                }
+                cmd
+            }
+        }
+    }
+}
+
+fn default_shell() -> PathBuf {
+    if cfg!(windows) {
+        std::env::var_os("COMSPEC")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("cmd.exe"))
+    } else {
+        PathBuf::from("/bin/sh")
+    }
+}
+
+/// Strip the `\\?\` verbatim prefix that `canonicalize` adds on Windows;
+/// many binaries and shells reject it, notably for UNC shares
+pub fn normalize_program_path(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    if let Some(unc) = text.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", unc))
+    } else if let Some(local) = text.strip_prefix(r"\\?\") {
+        PathBuf::from(local)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Full command line for `flavor`, every word quoted
+pub fn command_line(flavor: ShellFlavor, program: &Path, args: &[OsString]) -> String {
+    std::iter::once(program.as_os_str())
+        .chain(args.iter().map(OsString::as_os_str))
+        .map(|word| quote_arg(flavor, &word.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn quote_arg(flavor: ShellFlavor, arg: &str) -> String {
+    match flavor {
+        ShellFlavor::Posix => format!("'{}'", arg.replace('\'', r"'\''")),
+        ShellFlavor::Cmd => quote_windows_arg(arg),
+    }
+}
+
+/// Quote for `CommandLineToArgvW`: backslashes are literal unless they
+/// precede a quote, so runs before a quote (or the closing quote) double.
+/// cmd still expands `%NAME%` of defined variables inside quotes; there is
+/// no escape for that, so prefer direct spawn for such paths.
+fn quote_windows_arg(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+                quoted.push(c);
+            }
+        }
+    }
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+/// The DDA process and everything it starts: its process group on Unix
+/// (see `process_group(0)` in the worker), a job object on Windows
+pub struct ProcessTree {
+    #[cfg(unix)]
+    pid: Option<u32>,
+    #[cfg(windows)]
+    job: Option<ProcessJob>,
+}
+
+impl ProcessTree {
+    pub fn attach(child: &tokio::process::Child) -> Self {
+        Self {
+            #[cfg(unix)]
+            pid: child.id(),
+            #[cfg(windows)]
+            job: ProcessJob::attach(child),
+        }
+    }
+
+    /// Ask every process in the tree to exit (SIGTERM). Windows has no
+    /// equivalent for console-less processes, so the tree is killed outright.
+    pub fn terminate(&self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.pid {
+            // SAFETY: signalling a process group we created; a stale id only yields ESRCH
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGTERM);
+            }
+        }
+        #[cfg(windows)]
+        self.kill();
+    }
+
+    /// Kill every process in the tree; the child itself is reaped by the caller
+    pub fn kill(&self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.pid {
+            // SAFETY: signalling a process group we created; a stale id only yields ESRCH
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+        #[cfg(windows)]
+        if let Some(job) = &self.job {
+            job.kill();
+        }
+    }
+}
+
+/// Windows job object that ends the DDA process tree when killed or dropped
+#[cfg(windows)]
+struct ProcessJob {
+    handle: windows_sys::Win32::Foundation::HANDLE,
+}
+
+// SAFETY: a job handle may be used from any thread
+#[cfg(windows)]
+unsafe impl Send for ProcessJob {}
+#[cfg(windows)]
+unsafe impl Sync for ProcessJob {}
+
+#[cfg(windows)]
+impl ProcessJob {
+    /// Put `child` in a new kill-on-close job; `None` if Windows refuses
+    fn attach(child: &tokio::process::Child) -> Option<Self> {
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+            SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+            JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        };
+
+        let process = child.raw_handle()?;
+        // SAFETY: plain Win32 calls on a job we own and a live child handle
+        unsafe {
+            let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if handle.is_null() {
+                return None;
+            }
+            let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+            limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            let configured = SetInformationJobObject(
+                handle,
+                JobObjectExtendedLimitInformation,
+                &limits as *const _ as *const std::ffi::c_void,
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            );
+            if configured == 0 || AssignProcessToJobObject(handle, process as _) == 0 {
+                CloseHandle(handle);
+                return None;
+            }
+            Some(Self { handle })
+        }
+    }
+
+    /// Terminate every process in the job
+    fn kill(&self) {
+        // SAFETY: the handle stays valid until drop
+        unsafe {
+            windows_sys::Win32::System::JobObjects::TerminateJobObject(self.handle, 1);
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ProcessJob {
+    fn drop(&mut self) {
+        // SAFETY: closing our own handle; kill-on-close ends any survivors
+        unsafe {
+            windows_sys::Win32::Foundation::CloseHandle(self.handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quotes_arguments_per_shell() {
+        let args = [
+            OsString::from("-i"),
+            OsString::from("/data/EEG sessions/it's.edf"),
+        ];
+        assert_eq!(
+            command_line(ShellFlavor::Posix, Path::new("/opt/dda/dda"), &args),
+            r#"'/opt/dda/dda' '-i' '/data/EEG sessions/it'\''s.edf'"#
+        );
+
+        assert_eq!(
+            quote_arg(ShellFlavor::Cmd, r"C:\Program Files\DDA\"),
+            r#""C:\Program Files\DDA\\""#
+        );
+        assert_eq!(quote_arg(ShellFlavor::Cmd, r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(
+            ShellFlavor::of(Path::new(r"C:\Windows\System32\cmd.exe")),
+            ShellFlavor::Cmd
+        );
+    }
+
+    #[test]
+    fn test_strips_verbatim_prefixes() {
+        assert_eq!(
+            normalize_program_path(Path::new(r"\\?\UNC\lab-nas\tools\dda.exe")),
+            PathBuf::from(r"\\lab-nas\tools\dda.exe")
+        );
+        assert_eq!(
+            normalize_program_path(Path::new(r"\\?\C:\DDA\dda.exe")),
+            PathBuf::from(r"C:\DDA\dda.exe")
+        );
+        assert_eq!(
+            normalize_program_path(Path::new("/usr/bin/dda")),
+            PathBuf::from("/usr/bin/dda")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shell_launch_preserves_arguments() {
+        let strategy = LaunchStrategy::Shell {
+            shell: PathBuf::from("/bin/sh"),
+        };
+        let args = ["%s|", "a b", "it's $HOME"].map(OsString::from);
+        let output = strategy
+            .command(Path::new("printf"), &args)
+            .output()
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "a b|it's $HOME|");
+    }
+}
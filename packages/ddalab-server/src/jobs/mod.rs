@@ -1,10 +1,45 @@
+mod blobs;
+mod kubernetes;
+mod launch;
+mod node;
+mod policy;
+mod pools;
+mod presets;
 mod queue;
+mod quota;
+mod remote;
+mod resources;
+mod thumbnail;
 mod types;
+mod uploads;
+mod workdir;
 mod worker;
 
+pub use blobs::{hash_file, StoredUpload, UploadBlobs};
+pub use kubernetes::{KubernetesConfig, KubernetesLauncher};
+pub use launch::LaunchStrategy;
+pub use node::WorkerNode;
+pub use policy::RunPolicy;
+pub use pools::{parse_worker_pools, pool_for, PoolStats, ResourceRequirements, WorkerPool};
+pub use presets::{apply_preset, check_submission, normalize_preset};
 pub use queue::{JobQueue, JobQueueConfig, QueueStats};
+pub use quota::{QuotaStatus, UsageSummary, UserQuota, UserUsage};
+pub use remote::{
+    ClaimRequest, OutputLine, RemoteConfig, RemoteExecutor, RunAssignment, RunError, RunOutcome,
+    RunReport,
+};
+pub use resources::{ResourceEstimate, ResourceLimits};
+pub use thumbnail::{thumbnail_path, write_thumbnail};
 pub use types::{
-    DDAJob, DDAParameters, FileSource, JobProgressEvent, JobStatus, JobStatusResponse,
-    SubmitJobRequest, SubmitJobResponse,
+    DDAJob, DDAParameters, FileSource, JobPriority, JobProgressEvent, JobStatus, JobStatusResponse,
+    JobWindowEvent, PipelineStatusResponse, PreprocessingOptions, SubmitJobRequest,
+    SubmitJobResponse, WindowRow,
+};
+pub use uploads::{
+    ResumableUploads, UploadError, UploadJobOptions, UploadProgress, UploadSession, UPLOAD_EXPIRY,
+};
+pub use workdir::{capture_environment, WorkDir, WorkDirPolicy};
+pub use worker::{
+    run_dda_analysis, AnalysisSettings, Attempt, AttemptError, Executor, LocalExecutor,
+    OutputStream,
 };
-pub use worker::run_dda_analysis;
@@ -0,0 +1,296 @@
+//! Worker node
+//!
+//! `ddalab-server worker-node --server <url>` executes DDA runs for a
+//! server with `DDA_EXECUTOR=workers`, one at a time, until it is stopped.
+//! With `--run <id>`, as started by a Kubernetes Job, it executes that one
+//! run and exits. It authenticates with the server's `EXECUTOR_TOKEN` and
+//! runs the binary as the server would (`DDA_BINARY_PATH`,
+//! `DDA_LAUNCH_SHELL`, `DDA_WORK_DIR`, ...).
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::policy::RunPolicy;
+use super::remote::{
+    ClaimRequest, OutputLine, RunAssignment, RunOutcome, RunReport, CLAIM_WAIT, HEARTBEAT_INTERVAL,
+};
+use super::workdir::{WorkDir, WorkDirPolicy};
+use super::worker::{Attempt, AttemptError, Executor, LocalExecutor};
+
+/// How often output is sent while the binary prints
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pause after the server could not be reached
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// A connection to the server handing out runs
+#[derive(Clone)]
+pub struct WorkerNode {
+    server: String,
+    token: String,
+    name: String,
+    agent: ureq::Agent,
+}
+
+impl WorkerNode {
+    /// Node for the server at `server`, named after the host unless `name`
+    /// is given; the token comes from `EXECUTOR_TOKEN`
+    pub fn from_env(server: &str, name: Option<String>) -> Result<Self> {
+        dotenvy::dotenv().ok();
+        let token = std::env::var("EXECUTOR_TOKEN")
+            .map(|token| token.trim().to_string())
+            .ok()
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| anyhow!("EXECUTOR_TOKEN must be set"))?;
+        let name = name.unwrap_or_else(|| {
+            hostname::get()
+                .map(|host| host.to_string_lossy().into_owned())
+                .unwrap_or_else(|_| "worker".to_string())
+        });
+        Ok(Self {
+            server: server.trim_end_matches('/').to_string(),
+            token,
+            name,
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(10))
+                // Claims are held open until a run is offered
+                .timeout_read(CLAIM_WAIT + Duration::from_secs(30))
+                .build(),
+        })
+    }
+
+    /// Execute runs until the process is stopped
+    pub async fn serve(&self) -> Result<()> {
+        info!("Worker node {} serving {}", self.name, self.server);
+        loop {
+            match self.claim(None).await {
+                Ok(Some(assignment)) => self.execute(assignment).await,
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("Failed to claim a run: {:#}", e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    /// Execute the run `run_id` and return
+    pub async fn run_once(&self, run_id: Uuid) -> Result<()> {
+        match self.claim(Some(run_id)).await? {
+            Some(assignment) => {
+                self.execute(assignment).await;
+                Ok(())
+            }
+            None => bail!("Run {} is not available", run_id),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/executor{}", self.server, path)
+    }
+
+    /// Send a request with the executor token, off the runtime; a refusal
+    /// comes back as a [`ureq::Error::Status`]
+    async fn call<T: Send + 'static>(
+        &self,
+        method: &str,
+        path: &str,
+        send: impl FnOnce(ureq::Request) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let request = self
+            .agent
+            .request(method, &self.url(path))
+            .set("Authorization", &format!("Bearer {}", self.token));
+        tokio::task::spawn_blocking(move || send(request)).await?
+    }
+
+    async fn post_json<B: serde::Serialize>(&self, path: &str, body: &B) -> Result<ureq::Response> {
+        let body = serde_json::to_string(body)?;
+        self.call("POST", path, move |request| {
+            Ok(request
+                .set("Content-Type", "application/json")
+                .send_string(&body)?)
+        })
+        .await
+    }
+
+    async fn claim(&self, run_id: Option<Uuid>) -> Result<Option<RunAssignment>> {
+        let claim = ClaimRequest {
+            node: self.name.clone(),
+            run_id,
+        };
+        let response = self.post_json("/claim", &claim).await?;
+        if response.status() == 204 {
+            return Ok(None);
+        }
+        let body = response.into_string()?;
+        Ok(Some(serde_json::from_str(&body).context("Invalid run")?))
+    }
+
+    /// Execute a claimed run and report how it ended
+    async fn execute(&self, assignment: RunAssignment) {
+        let run_id = assignment.run_id;
+        info!(
+            "Running job {} attempt {} (run {})",
+            assignment.job.id, assignment.attempt, run_id
+        );
+        let outcome = match WorkDirPolicy::from_env()
+            .create(assignment.job.id, assignment.attempt)
+            .await
+        {
+            Ok(work_dir) => {
+                let outcome = self.run_in(&assignment, &work_dir).await;
+                work_dir
+                    .finish(!matches!(outcome, Err(AttemptError::Failed(_))))
+                    .await;
+                outcome
+            }
+            Err(e) => Err(AttemptError::Failed(e)),
+        };
+
+        let error = match outcome {
+            Ok(()) => None,
+            Err(AttemptError::Cancelled) => {
+                info!("Run {} was withdrawn", run_id);
+                return;
+            }
+            Err(AttemptError::TimedOut) => Some("DDA timed out on the worker node".to_string()),
+            Err(AttemptError::Failed(e)) => Some(format!("{:#}", e)),
+        };
+        let finished = self
+            .post_json(
+                &format!("/runs/{}/finish", run_id),
+                &RunOutcome {
+                    error: error.clone(),
+                },
+            )
+            .await;
+        match (finished, error) {
+            (Err(e), _) => warn!("Failed to report run {}: {:#}", run_id, e),
+            (Ok(_), None) => info!("Run {} completed", run_id),
+            (Ok(_), Some(error)) => warn!("Run {} failed: {}", run_id, error),
+        }
+    }
+
+    /// Download the input, run the binary and upload the result
+    async fn run_in(
+        &self,
+        assignment: &RunAssignment,
+        work_dir: &WorkDir,
+    ) -> std::result::Result<(), AttemptError> {
+        let run_id = assignment.run_id;
+        let input = work_dir
+            .path()
+            .join(format!("input.{}", assignment.input_extension));
+        let output_path = work_dir.path().join("result.json");
+        self.download_input(run_id, &input)
+            .await
+            .map_err(|e| AttemptError::Failed(e.context("Failed to download the input")))?;
+
+        let cancel = CancellationToken::new();
+        let done = CancellationToken::new();
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let reporter = tokio::spawn(self.clone().report_output(
+            run_id,
+            lines.clone(),
+            cancel.clone(),
+            done.clone(),
+        ));
+
+        // The server enforces the timeout and withdraws the run
+        let policy = RunPolicy {
+            timeout: None,
+            ..assignment.policy
+        };
+        let attempt = Attempt {
+            job: &assignment.job,
+            number: assignment.attempt,
+            input: &input,
+            output: &output_path,
+            work_dir,
+            cancel: &cancel,
+            policy: &policy,
+        };
+        let outcome = LocalExecutor::from_env()
+            .run(&attempt, &mut |stream, line| {
+                lines
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(OutputLine { stream, line });
+            })
+            .await;
+        done.cancel();
+        let _ = reporter.await;
+        // Never leave a plaintext copy behind, even in a kept work directory
+        tokio::fs::remove_file(&input).await.ok();
+        outcome?;
+
+        self.upload_result(run_id, &output_path)
+            .await
+            .map_err(|e| AttemptError::Failed(e.context("Failed to upload the result")))
+    }
+
+    async fn download_input(&self, run_id: Uuid, path: &Path) -> Result<()> {
+        let path = path.to_path_buf();
+        self.call("GET", &format!("/runs/{}/input", run_id), move |request| {
+            let response = request.call()?;
+            let mut file = std::fs::File::create(&path)?;
+            std::io::copy(&mut response.into_reader(), &mut file)?;
+            Ok(())
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn upload_result(&self, run_id: Uuid, path: &Path) -> Result<()> {
+        let result = tokio::fs::read(path).await?;
+        self.call("PUT", &format!("/runs/{}/result", run_id), move |request| {
+            Ok(request.send_bytes(&result)?)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Send output as it comes, and a heartbeat when there is none, until
+    /// `done`; cancels the run once the server has withdrawn it
+    async fn report_output(
+        self,
+        run_id: Uuid,
+        lines: Arc<Mutex<Vec<OutputLine>>>,
+        cancel: CancellationToken,
+        done: CancellationToken,
+    ) {
+        let path = format!("/runs/{}/output", run_id);
+        let mut last_report = Instant::now();
+        loop {
+            let finished = tokio::select! {
+                _ = tokio::time::sleep(REPORT_INTERVAL) => false,
+                _ = done.cancelled() => true,
+            };
+            let report = RunReport {
+                lines: std::mem::take(&mut *lines.lock().unwrap_or_else(PoisonError::into_inner)),
+            };
+            if report.lines.is_empty() && !finished && last_report.elapsed() < HEARTBEAT_INTERVAL {
+                continue;
+            }
+            match self.post_json(&path, &report).await {
+                Ok(_) => last_report = Instant::now(),
+                Err(e) => match e.downcast_ref::<ureq::Error>() {
+                    Some(ureq::Error::Status(404, _)) => {
+                        cancel.cancel();
+                        return;
+                    }
+                    _ => warn!("Failed to report output of run {}: {:#}", run_id, e),
+                },
+            }
+            if finished {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Timeout and retry policy for DDA executions
+//!
+//! Invocations reading from network filesystems can hang indefinitely. With
+//! a timeout set, an attempt that runs too long is asked to stop (SIGTERM),
+//! given `kill_grace` to exit, then killed, and retried up to `max_retries`
+//! times with exponential backoff.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunPolicy {
+    /// Per-attempt wall-clock limit; unlimited when unset
+    #[serde(default, with = "optional_secs")]
+    pub timeout: Option<Duration>,
+    /// Extra attempts after a timeout or failed exit
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further one
+    #[serde(default = "default_backoff", with = "secs")]
+    pub backoff: Duration,
+    /// Time between the stop request and the hard kill
+    #[serde(default = "default_kill_grace", with = "secs")]
+    pub kill_grace: Duration,
+}
+
+fn default_backoff() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_kill_grace() -> Duration {
+    Duration::from_secs(5)
+}
+
+impl Default for RunPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            max_retries: 0,
+            backoff: default_backoff(),
+            kill_grace: default_kill_grace(),
+        }
+    }
+}
+
+impl RunPolicy {
+    /// Total attempts allowed
+    pub fn max_attempts(&self) -> u32 {
+        self.max_retries.saturating_add(1)
+    }
+
+    /// Delay before attempt `attempt` (1-based; the first attempt has none)
+    pub fn backoff_before(&self, attempt: u32) -> Duration {
+        if attempt <= 1 {
+            return Duration::ZERO;
+        }
+        let factor = 1u32.checked_shl(attempt - 2).unwrap_or(u32::MAX);
+        self.backoff.saturating_mul(factor)
+    }
+}
+
+mod secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(value.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom)
+    }
+}
+
+mod optional_secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&value.as_secs_f64()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?
+            .map(|secs| Duration::try_from_secs_f64(secs).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_serializes_as_seconds() {
+        let policy = RunPolicy {
+            timeout: Some(Duration::from_secs(600)),
+            max_retries: 3,
+            backoff: Duration::from_secs(2),
+            kill_grace: Duration::from_millis(500),
+        };
+        assert_eq!(policy.max_attempts(), 4);
+        assert_eq!(policy.backoff_before(1), Duration::ZERO);
+        assert_eq!(policy.backoff_before(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff_before(4), Duration::from_secs(8));
+
+        let json = serde_json::to_value(policy).unwrap();
+        assert_eq!(json["timeout"], 600.0);
+        assert_eq!(json["kill_grace"], 0.5);
+        assert_eq!(serde_json::from_value::<RunPolicy>(json).unwrap(), policy);
+
+        let defaults: RunPolicy = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults, RunPolicy::default());
+    }
+}
@@ -0,0 +1,170 @@
+//! Worker pools for jobs of different sizes
+//!
+//! Jobs declare the channels they analyze and how long they expect to run.
+//! With `WORKER_POOLS` set, every job is assigned on submission to the first
+//! pool whose limits it fits, and each pool runs at most its own number of
+//! jobs at once. A long many-channel run then waits for a slot in the large
+//! pool instead of taking the slots small interactive jobs need. Jobs that
+//! fit no pool, or leave a bound undeclared, go to the last pool.
+//!
+//! `WORKER_POOLS` lists pools as `name:slots[:channels=N][:seconds=N]`,
+//! smallest first, e.g. `small:4:channels=32:seconds=600,large:1`.
+
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Resources a job declares on submission
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceRequirements {
+    /// Channels analyzed; taken from the parameters when they list channels
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u32>,
+    /// Expected run time in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_seconds: Option<u64>,
+}
+
+/// A class of jobs with its own concurrency limit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorkerPool {
+    pub name: String,
+    /// Jobs of this pool that may run at once
+    pub max_concurrent: usize,
+    /// Most channels a job may declare; unbounded when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_channels: Option<u32>,
+    /// Longest run time a job may declare; unbounded when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_estimated_seconds: Option<u64>,
+}
+
+impl WorkerPool {
+    /// Whether a job with `requirements` fits this pool's bounds
+    ///
+    /// An undeclared value only fits a pool that does not bound it.
+    pub fn accepts(&self, requirements: &ResourceRequirements) -> bool {
+        let within = |bound: Option<u64>, value: Option<u64>| match (bound, value) {
+            (None, _) => true,
+            (Some(max), Some(value)) => value <= max,
+            (Some(_), None) => false,
+        };
+        within(
+            self.max_channels.map(u64::from),
+            requirements.channels.map(u64::from),
+        ) && within(self.max_estimated_seconds, requirements.estimated_seconds)
+    }
+}
+
+impl FromStr for WorkerPool {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':').map(str::trim);
+        let name = parts
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| format!("Worker pool '{}' needs a name", s.trim()))?;
+        let max_concurrent = parts
+            .next()
+            .and_then(|slots| slots.parse::<usize>().ok())
+            .filter(|slots| *slots > 0)
+            .ok_or_else(|| format!("Worker pool '{}' needs a positive slot count", name))?;
+
+        let mut pool = WorkerPool {
+            name: name.to_string(),
+            max_concurrent,
+            max_channels: None,
+            max_estimated_seconds: None,
+        };
+        for bound in parts {
+            let invalid = || format!("Invalid bound '{}' for worker pool '{}'", bound, name);
+            let (key, value) = bound.split_once('=').ok_or_else(invalid)?;
+            match key.trim() {
+                "channels" => {
+                    pool.max_channels = Some(value.trim().parse().map_err(|_| invalid())?)
+                }
+                "seconds" => {
+                    pool.max_estimated_seconds = Some(value.trim().parse().map_err(|_| invalid())?)
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(pool)
+    }
+}
+
+/// Parse a comma-separated list of worker pools
+pub fn parse_worker_pools(s: &str) -> Result<Vec<WorkerPool>, String> {
+    let pools: Vec<WorkerPool> = s
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()?;
+    for (index, pool) in pools.iter().enumerate() {
+        if pools[..index].iter().any(|other| other.name == pool.name) {
+            return Err(format!("Worker pool '{}' is listed twice", pool.name));
+        }
+    }
+    Ok(pools)
+}
+
+/// The pool a job with `requirements` runs in: the first that accepts it,
+/// or the last one
+pub fn pool_for<'a>(
+    pools: &'a [WorkerPool],
+    requirements: &ResourceRequirements,
+) -> Option<&'a WorkerPool> {
+    pools
+        .iter()
+        .find(|pool| pool.accepts(requirements))
+        .or(pools.last())
+}
+
+/// Occupancy of one worker pool
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStats {
+    #[serde(flatten)]
+    pub pool: WorkerPool,
+    pub running: usize,
+    pub pending: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pools_parse_and_assign_in_order() {
+        let pools = parse_worker_pools("small:4:channels=32:seconds=600, large:1").unwrap();
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools[0].max_channels, Some(32));
+        assert_eq!(pools[0].max_estimated_seconds, Some(600));
+        assert_eq!(pools[1].max_concurrent, 1);
+
+        let small = ResourceRequirements {
+            channels: Some(8),
+            estimated_seconds: Some(60),
+        };
+        let wide = ResourceRequirements {
+            channels: Some(128),
+            ..small
+        };
+        let undeclared = ResourceRequirements {
+            channels: Some(8),
+            estimated_seconds: None,
+        };
+        assert_eq!(pool_for(&pools, &small).unwrap().name, "small");
+        assert_eq!(pool_for(&pools, &wide).unwrap().name, "large");
+        assert_eq!(pool_for(&pools, &undeclared).unwrap().name, "large");
+        assert!(pool_for(&[], &small).is_none());
+    }
+
+    #[test]
+    fn test_invalid_pools_are_rejected() {
+        assert!(parse_worker_pools("small").is_err());
+        assert!(parse_worker_pools("small:0").is_err());
+        assert!(parse_worker_pools("small:2:memory=5").is_err());
+        assert!(parse_worker_pools("a:1,a:2").is_err());
+        assert!(parse_worker_pools("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+//! Parameter presets at job submission
+//!
+//! Presets are published per team so a multi-site study analyzes every
+//! recording the same way, or kept by a user for their own analyses. A job
+//! submitted with a `preset_id` takes the preset's parameters and changes
+//! only the fields it sends itself. The checks here make team presets'
+//! locked fields and required presets binding regardless of what a client
+//! sends.
+
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::jobs::DDAParameters;
+use crate::storage::TeamPreset;
+
+/// Check preset parameters and normalize them to the types jobs serialize
+///
+/// Every key must be a job parameter field and every locked field must have
+/// a value in the preset. Numbers are re-encoded through [`DDAParameters`]
+/// so that e.g. `1` and `1.0` compare equal at submission.
+pub fn normalize_preset(parameters: &Value, locked_fields: &[String]) -> Result<Value, String> {
+    let preset = parameters
+        .as_object()
+        .ok_or_else(|| "Preset parameters must be a JSON object".to_string())?;
+    let Value::Object(mut merged) = parameters_to_value(&DDAParameters::default()) else {
+        unreachable!("DDAParameters serializes to an object");
+    };
+    for (field, value) in preset {
+        if !merged.contains_key(field) {
+            return Err(format!("Unknown parameter '{}' in preset", field));
+        }
+        merged.insert(field.clone(), value.clone());
+    }
+    for field in locked_fields {
+        if !preset.contains_key(field) {
+            return Err(format!(
+                "Locked field '{}' has no value in the preset",
+                field
+            ));
+        }
+    }
+
+    let typed: DDAParameters = serde_json::from_value(Value::Object(merged))
+        .map_err(|e| format!("Invalid preset parameters: {}", e))?;
+    let Value::Object(typed) = parameters_to_value(&typed) else {
+        unreachable!("DDAParameters serializes to an object");
+    };
+    Ok(Value::Object(
+        typed
+            .into_iter()
+            .filter(|(field, _)| preset.contains_key(field))
+            .collect::<Map<_, _>>(),
+    ))
+}
+
+/// Job parameters from a preset and the fields a submission sets itself
+///
+/// Submitted fields win over the preset's, and objects such as
+/// `preprocessing` are merged field by field; whatever neither sets keeps
+/// its default.
+pub fn apply_preset(
+    preset: Option<&Value>,
+    submitted: &Map<String, Value>,
+) -> Result<DDAParameters, String> {
+    let Value::Object(mut merged) = parameters_to_value(&DDAParameters::default()) else {
+        unreachable!("DDAParameters serializes to an object");
+    };
+    for layer in preset
+        .and_then(Value::as_object)
+        .into_iter()
+        .chain([submitted])
+    {
+        for (field, value) in layer {
+            // Unknown fields are ignored, as they were before presets
+            if let Some(current) = merged.get_mut(field) {
+                merge_value(current, value);
+            }
+        }
+    }
+    serde_json::from_value(Value::Object(merged)).map_err(|e| format!("Invalid parameters: {}", e))
+}
+
+fn merge_value(target: &mut Value, value: &Value) {
+    if let (Value::Object(target), Value::Object(value)) = (&mut *target, value) {
+        for (field, value) in value {
+            match target.get_mut(field) {
+                Some(current) => merge_value(current, value),
+                None => {
+                    target.insert(field.clone(), value.clone());
+                }
+            }
+        }
+    } else {
+        *target = value.clone();
+    }
+}
+
+/// Enforce the presets of the submitter's teams on a job's parameters
+///
+/// With `preset_id`, that preset must belong to one of the teams and the
+/// job must match it on every locked field. Without one, the submission is
+/// refused if any of the teams requires its members to use a preset.
+pub fn check_submission(
+    presets: &[TeamPreset],
+    preset_id: Option<Uuid>,
+    parameters: &DDAParameters,
+) -> Result<(), String> {
+    let Some(preset_id) = preset_id else {
+        return match presets.iter().find(|preset| preset.required) {
+            Some(preset) => Err(format!(
+                "Your team requires a parameter preset, e.g. '{}' ({})",
+                preset.name, preset.id
+            )),
+            None => Ok(()),
+        };
+    };
+
+    let preset = presets
+        .iter()
+        .find(|preset| preset.id == preset_id)
+        .ok_or_else(|| format!("Preset {} is not available to you", preset_id))?;
+    let submitted = parameters_to_value(parameters);
+    let changed: Vec<&str> = preset
+        .locked_fields
+        .iter()
+        .filter(|field| submitted.get(field.as_str()) != preset.parameters.get(field.as_str()))
+        .map(String::as_str)
+        .collect();
+    if changed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Preset '{}' locks {}; submit the preset's values",
+            preset.name,
+            changed.join(", ")
+        ))
+    }
+}
+
+fn parameters_to_value(parameters: &DDAParameters) -> Value {
+    serde_json::to_value(parameters).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn preset(parameters: Value, locked: &[&str], required: bool) -> TeamPreset {
+        let locked_fields: Vec<String> = locked.iter().map(|f| f.to_string()).collect();
+        TeamPreset {
+            id: Uuid::new_v4(),
+            team_id: Uuid::new_v4(),
+            name: "study".to_string(),
+            description: None,
+            parameters: normalize_preset(&parameters, &locked_fields).unwrap(),
+            locked_fields,
+            required,
+            created_by: Uuid::new_v4(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_preset() {
+        let normalized =
+            normalize_preset(&json!({"time_window": 2, "embedding_dim": 4}), &[]).unwrap();
+        assert_eq!(normalized, json!({"time_window": 2.0, "embedding_dim": 4}));
+
+        assert!(normalize_preset(&json!({"detrend": true}), &[]).is_err());
+        assert!(normalize_preset(&json!({"delta": "x"}), &[]).is_err());
+        assert!(normalize_preset(&json!({}), &["delta".to_string()]).is_err());
+        assert!(normalize_preset(&json!([1]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_apply_preset() {
+        let preset = json!({
+            "time_window": 2,
+            "delta": 0.5,
+            "preprocessing": {"highpass": 0.5, "notch": 60}
+        });
+        let submitted = json!({
+            "channels": ["Fp1", "Fp2"],
+            "delta": 0.25,
+            "preprocessing": {"notch": 50}
+        });
+        let parameters = apply_preset(Some(&preset), submitted.as_object().unwrap()).unwrap();
+        assert_eq!(parameters.channels, ["Fp1", "Fp2"]);
+        assert_eq!(parameters.time_window, 2.0);
+        assert_eq!(parameters.delta, 0.25);
+        assert_eq!(
+            parameters.embedding_dim,
+            DDAParameters::default().embedding_dim
+        );
+        assert_eq!(parameters.preprocessing.highpass, Some(0.5));
+        assert_eq!(parameters.preprocessing.notch, Some(50.0));
+
+        let parameters = apply_preset(None, &Map::new()).unwrap();
+        assert_eq!(parameters.time_window, DDAParameters::default().time_window);
+        assert!(apply_preset(None, json!({"scales": [1]}).as_object().unwrap()).is_ok());
+        assert!(apply_preset(None, json!({"delta": "x"}).as_object().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_locked_fields_and_required_presets() {
+        let locked = preset(
+            json!({"time_window": 2, "delta": 0.5}),
+            &["time_window"],
+            true,
+        );
+        let presets = vec![locked.clone()];
+        let mut parameters = DDAParameters {
+            time_window: 2.0,
+            ..Default::default()
+        };
+
+        // Unlocked fields may differ from the preset
+        assert!(check_submission(&presets, Some(locked.id), &parameters).is_ok());
+
+        parameters.time_window = 4.0;
+        let error = check_submission(&presets, Some(locked.id), &parameters).unwrap_err();
+        assert!(error.contains("time_window"), "{}", error);
+
+        assert!(check_submission(&presets, None, &parameters).is_err());
+        assert!(check_submission(&presets, Some(Uuid::new_v4()), &parameters).is_err());
+        assert!(check_submission(&[], None, &parameters).is_ok());
+    }
+}
@@ -1,13 +1,42 @@
-use super::types::{DDAJob, JobProgressEvent, JobStatus};
-use super::worker::run_dda_analysis;
-use anyhow::Result;
+use super::blobs::UploadBlobs;
+use super::policy::RunPolicy;
+use super::pools::{pool_for, PoolStats, WorkerPool};
+use super::quota::{QuotaStatus, UsageSummary, UserQuota, UserUsage};
+use super::resources::{ResourceEstimate, ResourceLimits};
+use super::thumbnail::write_thumbnail;
+use super::types::{
+    DDAJob, FileSource, JobPriority, JobProgressEvent, JobStatus, JobWindowEvent, WindowRow,
+};
+use super::workdir::{capture_environment, WorkDirPolicy};
+use super::worker::{run_dda_analysis, AnalysisSettings, Executor, LocalExecutor};
+use crate::crypto::StorageKey;
+use anyhow::{bail, Result};
 use chrono::Utc;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc, RwLock, Semaphore};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::{broadcast, Notify, RwLock, Semaphore};
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+/// Capacity of the window row channel; rows arrive far more often than
+/// progress updates
+const WINDOW_CHANNEL_CAPACITY: usize = 4096;
+
+/// Most Q values kept per running job for streams that join late (32 MiB);
+/// later rows are only sent live
+const MAX_BUFFERED_VALUES: usize = 4 << 20;
+
+/// Window rows a running job has reported so far
+#[derive(Default)]
+struct PartialResult {
+    rows: Vec<WindowRow>,
+    values: usize,
+}
+
+type PartialResults = Arc<Mutex<HashMap<Uuid, PartialResult>>>;
+
 /// Configuration for the job queue
 #[derive(Debug, Clone)]
 pub struct JobQueueConfig {
@@ -15,6 +44,23 @@ pub struct JobQueueConfig {
     pub max_concurrent_jobs: usize,
     /// Channel capacity for progress notifications
     pub notification_capacity: usize,
+    /// Timeout and retry policy applied to every job
+    pub run_policy: RunPolicy,
+    /// Limits applied to each user's jobs
+    pub user_quota: UserQuota,
+    /// Memory and CPUs the running jobs may hold together
+    pub resource_limits: ResourceLimits,
+    /// Classes of jobs with their own concurrency limits; when set, they
+    /// replace `max_concurrent_jobs`
+    pub worker_pools: Vec<WorkerPool>,
+    /// Key uploads and results are encrypted with at rest, if any
+    pub storage_encryption: Option<StorageKey>,
+    /// Directory uploads are stored in, under their content hash
+    pub upload_directory: PathBuf,
+    /// Directory job results are written to
+    pub output_directory: PathBuf,
+    /// Where DDA attempts run
+    pub executor: Arc<dyn Executor>,
 }
 
 impl Default for JobQueueConfig {
@@ -22,22 +68,73 @@ impl Default for JobQueueConfig {
         Self {
             max_concurrent_jobs: 2,
             notification_capacity: 1000,
+            run_policy: RunPolicy::default(),
+            user_quota: UserQuota::default(),
+            resource_limits: ResourceLimits::default(),
+            worker_pools: Vec::new(),
+            storage_encryption: None,
+            upload_directory: PathBuf::from("/tmp/ddalab-uploads"),
+            output_directory: PathBuf::from("/tmp/ddalab-jobs"),
+            executor: Arc::new(LocalExecutor::default()),
         }
     }
 }
 
+impl JobQueueConfig {
+    /// Jobs that may run at once across every pool
+    pub fn total_slots(&self) -> usize {
+        if self.worker_pools.is_empty() {
+            self.max_concurrent_jobs
+        } else {
+            self.worker_pools
+                .iter()
+                .map(|pool| pool.max_concurrent)
+                .sum()
+        }
+    }
+
+    /// Whether a job in `pool` may start while `running` of the pool's jobs run
+    fn pool_has_room(&self, pool: Option<&str>, running: usize) -> bool {
+        self.worker_pools
+            .iter()
+            .find(|candidate| Some(candidate.name.as_str()) == pool)
+            .is_none_or(|pool| running < pool.max_concurrent)
+    }
+}
+
 /// Async job queue with configurable parallelism
+///
+/// Pending jobs start in priority order: every interactive job before any
+/// normal one, and normal before batch. Within a class jobs start in the
+/// order they were submitted, except that the user with the fewest running
+/// jobs goes first and users at their running limit wait their turn. The
+/// next job also waits until the running jobs leave room for its estimated
+/// memory and CPUs, so it is not overtaken by smaller jobs behind it.
+///
+/// With worker pools configured, each job runs in the pool it was assigned
+/// on submission, and jobs whose pool is full are passed over until one of
+/// the pool's jobs finishes.
+///
+/// A job that lists other jobs in `depends_on` stays pending until all of
+/// them have completed. When one of them fails or is cancelled instead, the
+/// job and everything downstream of it fail or are cancelled with it.
 pub struct JobQueue {
     /// All jobs indexed by ID
     jobs: Arc<RwLock<HashMap<Uuid, DDAJob>>>,
     /// Semaphore to limit concurrent jobs
     semaphore: Arc<Semaphore>,
-    /// Channel to submit new jobs
-    submit_tx: mpsc::Sender<DDAJob>,
+    /// Wakes the dispatcher when a job becomes pending
+    pending: Arc<Notify>,
     /// Broadcast channel for progress updates
     progress_tx: broadcast::Sender<JobProgressEvent>,
-    /// Set of jobs that should be cancelled
-    cancel_requests: Arc<RwLock<std::collections::HashSet<Uuid>>>,
+    /// Broadcast channel for window rows of running jobs
+    window_tx: broadcast::Sender<JobWindowEvent>,
+    /// Window rows of running jobs, replayed to streams that join late
+    partial_results: PartialResults,
+    /// Cancellation tokens for running jobs
+    cancel_tokens: Arc<RwLock<HashMap<Uuid, CancellationToken>>>,
+    /// Uploaded inputs, released as their jobs end
+    blobs: Arc<UploadBlobs>,
     /// Configuration
     config: JobQueueConfig,
 }
@@ -45,68 +142,78 @@ pub struct JobQueue {
 impl JobQueue {
     /// Create a new job queue with the given configuration
     pub fn new(config: JobQueueConfig) -> Self {
-        let (submit_tx, submit_rx) = mpsc::channel::<DDAJob>(100);
         let (progress_tx, _) = broadcast::channel(config.notification_capacity);
+        let (window_tx, _) = broadcast::channel(WINDOW_CHANNEL_CAPACITY);
 
         let queue = Self {
             jobs: Arc::new(RwLock::new(HashMap::new())),
-            semaphore: Arc::new(Semaphore::new(config.max_concurrent_jobs)),
-            submit_tx,
+            semaphore: Arc::new(Semaphore::new(config.total_slots())),
+            pending: Arc::new(Notify::new()),
             progress_tx,
-            cancel_requests: Arc::new(RwLock::new(std::collections::HashSet::new())),
+            window_tx,
+            partial_results: Arc::new(Mutex::new(HashMap::new())),
+            cancel_tokens: Arc::new(RwLock::new(HashMap::new())),
+            blobs: Arc::new(UploadBlobs::new(&config.upload_directory)),
             config,
         };
 
         // Start the dispatcher task
-        queue.start_dispatcher(submit_rx);
+        queue.start_dispatcher();
 
         queue
     }
 
-    /// Start the dispatcher that processes incoming jobs
-    fn start_dispatcher(&self, mut submit_rx: mpsc::Receiver<DDAJob>) {
+    /// Start the dispatcher that hands free slots to the most urgent jobs
+    fn start_dispatcher(&self) {
         let jobs = self.jobs.clone();
         let semaphore = self.semaphore.clone();
+        let pending = self.pending.clone();
         let progress_tx = self.progress_tx.clone();
-        let cancel_requests = self.cancel_requests.clone();
+        let window_tx = self.window_tx.clone();
+        let partial_results = self.partial_results.clone();
+        let cancel_tokens = self.cancel_tokens.clone();
+        let blobs = self.blobs.clone();
+        let run_policy = self.config.run_policy;
+        let config = self.config.clone();
 
         tokio::spawn(async move {
-            while let Some(job) = submit_rx.recv().await {
+            loop {
+                // Wait for a free slot before choosing, so a job submitted
+                // while the queue is full still competes for the next slot
+                let permit = match semaphore.clone().acquire_owned().await {
+                    Ok(p) => p,
+                    Err(e) => {
+                        warn!("Job dispatcher stopped: {}", e);
+                        return;
+                    }
+                };
+                let (job, cancel_token) = loop {
+                    if let Some(started) = start_next_job(&jobs, &cancel_tokens, &config).await {
+                        break started;
+                    }
+                    pending.notified().await;
+                };
                 let job_id = job.id;
-                info!("Job {} received by dispatcher", job_id);
-
-                // Store job in pending state
-                {
-                    let mut jobs_guard = jobs.write().await;
-                    jobs_guard.insert(job_id, job.clone());
-                }
+                info!(
+                    "Job {} ({} priority) acquired semaphore, starting execution",
+                    job_id, job.priority
+                );
 
                 // Clone references for the task
                 let jobs_clone = jobs.clone();
-                let semaphore_clone = semaphore.clone();
+                let pending_clone = pending.clone();
                 let progress_tx_clone = progress_tx.clone();
-                let cancel_requests_clone = cancel_requests.clone();
+                let window_tx_clone = window_tx.clone();
+                let partial_results_clone = partial_results.clone();
+                let cancel_tokens_clone = cancel_tokens.clone();
+                let blobs_clone = blobs.clone();
+                let storage_key = config.storage_encryption.clone();
+                let executor = config.executor.clone();
+                let output_directory = config.output_directory.clone();
 
                 // Spawn task to process this job
                 tokio::spawn(async move {
-                    // Acquire semaphore permit (blocks if at capacity)
-                    let _permit = match semaphore_clone.acquire().await {
-                        Ok(p) => p,
-                        Err(e) => {
-                            log::warn!("Job {} failed to acquire semaphore: {}", job_id, e);
-                            return;
-                        }
-                    };
-                    info!("Job {} acquired semaphore, starting execution", job_id);
-
-                    // Update status to running
-                    {
-                        let mut jobs_guard = jobs_clone.write().await;
-                        if let Some(job) = jobs_guard.get_mut(&job_id) {
-                            job.status = JobStatus::Running;
-                            job.started_at = Some(Utc::now());
-                        }
-                    }
+                    let _permit = permit;
 
                     // Send running notification
                     let _ = progress_tx_clone.send(JobProgressEvent {
@@ -116,32 +223,24 @@ impl JobQueue {
                         message: Some("Starting DDA analysis...".to_string()),
                     });
 
-                    // Get a copy of the job for execution
-                    let job_copy = {
-                        let jobs_guard = jobs_clone.read().await;
-                        jobs_guard.get(&job_id).cloned()
-                    };
-
-                    if let Some(job) = job_copy {
-                        // Run the analysis with progress callback
-                        let jobs_for_callback = jobs_clone.clone();
-                        let progress_tx_for_callback = progress_tx_clone.clone();
-                        let cancel_requests_for_callback = cancel_requests_clone.clone();
-
-                        let result = run_dda_analysis(&job, |progress, message| {
-                            // Check for cancellation
-                            let should_cancel = {
-                                let cancel_guard = cancel_requests_for_callback.blocking_read();
-                                cancel_guard.contains(&job_id)
-                            };
-
-                            if should_cancel {
-                                return false; // Signal cancellation
-                            }
+                    // Run the analysis with progress callback
+                    let jobs_for_callback = jobs_clone.clone();
+                    let progress_tx_for_callback = progress_tx_clone.clone();
 
-                            // Update progress in job
-                            {
-                                let mut jobs_guard = jobs_for_callback.blocking_write();
+                    let settings = AnalysisSettings {
+                        executor: executor.as_ref(),
+                        policy: &run_policy,
+                        storage_key: storage_key.as_ref(),
+                        output_directory: &output_directory,
+                    };
+                    let result = run_dda_analysis(
+                        &job,
+                        &settings,
+                        &cancel_token,
+                        |progress, message| {
+                            // Update progress in job (best effort; the callback runs on
+                            // the runtime and must not block on the lock)
+                            if let Ok(mut jobs_guard) = jobs_for_callback.try_write() {
                                 if let Some(job) = jobs_guard.get_mut(&job_id) {
                                     job.progress = progress;
                                     job.message = message.clone();
@@ -155,67 +254,104 @@ impl JobQueue {
                                 progress,
                                 message,
                             });
+                        },
+                        |row| {
+                            record_window(&partial_results_clone, &window_tx_clone, job_id, row);
+                        },
+                    )
+                    .await;
 
-                            true // Continue execution
+                    // Render the preview before the job is reported complete
+                    if let Ok(output_path) = &result {
+                        let output_path = output_path.clone();
+                        let storage_key = storage_key.clone();
+                        match tokio::task::spawn_blocking(move || {
+                            write_thumbnail(&output_path, storage_key.as_ref())
                         })
-                        .await;
+                        .await
+                        {
+                            Ok(Ok(_)) => {}
+                            Ok(Err(e)) => warn!("Job {} thumbnail failed: {}", job_id, e),
+                            Err(e) => warn!("Job {} thumbnail task failed: {}", job_id, e),
+                        }
+                    }
 
-                        // Update final status
-                        let mut jobs_guard = jobs_clone.write().await;
-                        if let Some(job) = jobs_guard.get_mut(&job_id) {
-                            job.completed_at = Some(Utc::now());
+                    // Update final status
+                    let mut jobs_guard = jobs_clone.write().await;
+                    let mut released = Vec::new();
+                    if let Some(job) = jobs_guard.get_mut(&job_id) {
+                        job.completed_at = Some(Utc::now());
 
-                            match result {
-                                Ok(output_path) => {
-                                    job.status = JobStatus::Completed;
-                                    job.progress = 100;
-                                    job.output_path = Some(output_path);
-                                    job.message = Some("Analysis complete".to_string());
-                                    info!("Job {} completed successfully", job_id);
+                        match result {
+                            Ok(output_path) => {
+                                job.status = JobStatus::Completed;
+                                job.progress = 100;
+                                job.output_path = Some(output_path);
+                                job.message = Some("Analysis complete".to_string());
+                                info!("Job {} completed successfully", job_id);
+
+                                let _ = progress_tx_clone.send(JobProgressEvent {
+                                    job_id,
+                                    status: JobStatus::Completed,
+                                    progress: 100,
+                                    message: Some("Analysis complete".to_string()),
+                                });
+                            }
+                            Err(e) => {
+                                let error_msg = e.to_string();
+                                if error_msg.contains("cancelled") {
+                                    job.status = JobStatus::Cancelled;
+                                    job.message = Some("Job cancelled by user".to_string());
+                                    info!("Job {} cancelled", job_id);
 
                                     let _ = progress_tx_clone.send(JobProgressEvent {
                                         job_id,
-                                        status: JobStatus::Completed,
-                                        progress: 100,
-                                        message: Some("Analysis complete".to_string()),
+                                        status: JobStatus::Cancelled,
+                                        progress: job.progress,
+                                        message: Some("Job cancelled by user".to_string()),
+                                    });
+                                } else {
+                                    job.status = JobStatus::Failed;
+                                    job.error = Some(error_msg.clone());
+                                    job.message = Some(format!("Failed: {}", error_msg));
+                                    error!("Job {} failed: {}", job_id, error_msg);
+
+                                    let _ = progress_tx_clone.send(JobProgressEvent {
+                                        job_id,
+                                        status: JobStatus::Failed,
+                                        progress: job.progress,
+                                        message: Some(format!("Failed: {}", error_msg)),
                                     });
-                                }
-                                Err(e) => {
-                                    let error_msg = e.to_string();
-                                    if error_msg.contains("cancelled") {
-                                        job.status = JobStatus::Cancelled;
-                                        job.message = Some("Job cancelled by user".to_string());
-                                        info!("Job {} cancelled", job_id);
-
-                                        let _ = progress_tx_clone.send(JobProgressEvent {
-                                            job_id,
-                                            status: JobStatus::Cancelled,
-                                            progress: job.progress,
-                                            message: Some("Job cancelled by user".to_string()),
-                                        });
-                                    } else {
-                                        job.status = JobStatus::Failed;
-                                        job.error = Some(error_msg.clone());
-                                        job.message = Some(format!("Failed: {}", error_msg));
-                                        error!("Job {} failed: {}", job_id, error_msg);
-
-                                        let _ = progress_tx_clone.send(JobProgressEvent {
-                                            job_id,
-                                            status: JobStatus::Failed,
-                                            progress: job.progress,
-                                            message: Some(format!("Failed: {}", error_msg)),
-                                        });
-                                    }
                                 }
                             }
                         }
 
-                        // Clean up cancel request if any
-                        {
-                            let mut cancel_guard = cancel_requests_clone.write().await;
-                            cancel_guard.remove(&job_id);
+                        released.extend(released_input(job));
+
+                        // Settle the jobs waiting on this one if it did not complete
+                        if job.status != JobStatus::Completed {
+                            released.extend(settle_dependents(
+                                &mut jobs_guard,
+                                job_id,
+                                &progress_tx_clone,
+                            ));
                         }
                     }
+                    // A dependent or a job held back by its user's running
+                    // limit may be able to start now
+                    pending_clone.notify_one();
+
+                    // Drop the job's cancellation token, streamed rows and
+                    // temporary input
+                    drop(jobs_guard);
+                    for path in released {
+                        blobs_clone.discard(&path).await;
+                    }
+                    partial_results_clone
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .remove(&job_id);
+                    cancel_tokens_clone.write().await.remove(&job_id);
 
                     // Permit is released when _permit goes out of scope
                 });
@@ -224,25 +360,72 @@ impl JobQueue {
     }
 
     /// Submit a new job to the queue
-    pub async fn submit(&self, job: DDAJob) -> Result<Uuid> {
+    ///
+    /// Fails when the job depends on a job the queue does not know. A job
+    /// whose dependency has already failed or been cancelled is settled
+    /// the same way straight away.
+    pub async fn submit(&self, mut job: DDAJob) -> Result<Uuid> {
+        if job.resources.is_none() {
+            let estimated = job.clone();
+            job.resources =
+                tokio::task::spawn_blocking(move || ResourceEstimate::for_job(&estimated))
+                    .await
+                    .ok();
+        }
+        job.pool =
+            pool_for(&self.config.worker_pools, &job.requirements).map(|pool| pool.name.clone());
         let job_id = job.id;
+        let priority = job.priority;
 
-        // Store job immediately
-        {
-            let mut jobs = self.jobs.write().await;
-            jobs.insert(job_id, job.clone());
+        let mut jobs = self.jobs.write().await;
+        if let Some(unknown) = job.depends_on.iter().find(|id| !jobs.contains_key(id)) {
+            bail!("Unknown dependency job {}", unknown);
         }
+        let settled: Vec<Uuid> = job
+            .depends_on
+            .iter()
+            .copied()
+            .filter(|id| matches!(jobs[id].status, JobStatus::Failed | JobStatus::Cancelled))
+            .collect();
+        jobs.insert(job_id, job);
+        let mut released = Vec::new();
+        for dependency in settled {
+            released.extend(settle_dependents(&mut jobs, dependency, &self.progress_tx));
+        }
+        drop(jobs);
+        for path in released {
+            self.blobs.discard(&path).await;
+        }
+        self.pending.notify_one();
 
-        // Send to dispatcher
-        self.submit_tx
-            .send(job)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to submit job: {}", e))?;
-
-        info!("Job {} submitted to queue", job_id);
+        let _ = self.progress_tx.send(JobProgressEvent {
+            job_id,
+            status: JobStatus::Pending,
+            progress: 0,
+            message: Some("Queued".to_string()),
+        });
+        info!("Job {} submitted to queue ({} priority)", job_id, priority);
         Ok(job_id)
     }
 
+    /// Move a pending job to another priority class
+    ///
+    /// Returns false when the job does not exist or has already started.
+    pub async fn set_priority(&self, job_id: Uuid, priority: JobPriority) -> bool {
+        let mut jobs = self.jobs.write().await;
+        match jobs.get_mut(&job_id) {
+            Some(job) if job.status == JobStatus::Pending => {
+                info!(
+                    "Job {} priority changed from {} to {}",
+                    job_id, job.priority, priority
+                );
+                job.priority = priority;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Cancel a job
     pub async fn cancel(&self, job_id: Uuid) -> Result<bool> {
         let mut jobs = self.jobs.write().await;
@@ -263,13 +446,18 @@ impl JobQueue {
                     });
 
                     info!("Job {} cancelled (was pending)", job_id);
+                    let mut released: Vec<PathBuf> = released_input(job).into_iter().collect();
+                    released.extend(settle_dependents(&mut jobs, job_id, &self.progress_tx));
+                    drop(jobs);
+                    for path in released {
+                        self.blobs.discard(&path).await;
+                    }
                     Ok(true)
                 }
                 JobStatus::Running => {
-                    // Request cancellation - will be picked up by progress callback
-                    {
-                        let mut cancel_guard = self.cancel_requests.write().await;
-                        cancel_guard.insert(job_id);
+                    // Signal the worker, which kills the DDA process and cleans up
+                    if let Some(token) = self.cancel_tokens.read().await.get(&job_id) {
+                        token.cancel();
                     }
                     info!("Job {} cancel requested (was running)", job_id);
                     Ok(true)
@@ -284,12 +472,136 @@ impl JobQueue {
         }
     }
 
+    /// Uploaded inputs held by the jobs
+    pub fn blobs(&self) -> &Arc<UploadBlobs> {
+        &self.blobs
+    }
+
     /// Get job status
     pub async fn get_job(&self, job_id: Uuid) -> Option<DDAJob> {
         let jobs = self.jobs.read().await;
         jobs.get(&job_id).cloned()
     }
 
+    /// Forget a finished job's result after retention deleted its files
+    pub async fn clear_output(&self, job_id: Uuid) {
+        let mut jobs = self.jobs.write().await;
+        if let Some(job) = jobs.get_mut(&job_id) {
+            if job.output_path.take().is_some() {
+                job.message = Some("Results deleted by retention policy".to_string());
+            }
+        }
+    }
+
+    /// Every job connected to `job_id` through `depends_on`, upstream and
+    /// downstream, including the job itself
+    pub async fn pipeline(&self, job_id: Uuid) -> Option<Vec<DDAJob>> {
+        let jobs = self.jobs.read().await;
+        if !jobs.contains_key(&job_id) {
+            return None;
+        }
+
+        let mut seen = HashSet::from([job_id]);
+        let mut frontier = vec![job_id];
+        while let Some(id) = frontier.pop() {
+            let upstream = jobs[&id].depends_on.iter().copied();
+            let downstream = jobs
+                .values()
+                .filter(|job| job.depends_on.contains(&id))
+                .map(|job| job.id);
+            for next in upstream.chain(downstream).collect::<Vec<_>>() {
+                if jobs.contains_key(&next) && seen.insert(next) {
+                    frontier.push(next);
+                }
+            }
+        }
+        Some(seen.iter().map(|id| jobs[id].clone()).collect())
+    }
+
+    /// A user's running and pending jobs and the bytes their uploads and
+    /// results take up
+    pub async fn usage(&self, user_id: &str) -> UserUsage {
+        let mut usage = UserUsage::default();
+        let mut paths: HashSet<PathBuf> = HashSet::new();
+        for job in self.jobs.read().await.values() {
+            if job.user_id != user_id {
+                continue;
+            }
+            match job.status {
+                JobStatus::Running => usage.running += 1,
+                JobStatus::Pending => usage.queued += 1,
+                _ => {}
+            }
+            if let FileSource::UploadedTemp(path) | FileSource::UploadedPersistent(path) =
+                &job.file_source
+            {
+                paths.insert(path.clone());
+            }
+            if let Some(path) = &job.output_path {
+                paths.insert(path.clone());
+            }
+        }
+
+        for path in paths {
+            // Temporary uploads are gone once their job has run
+            if let Ok(metadata) = tokio::fs::metadata(&path).await {
+                usage.disk_bytes += metadata.len();
+            }
+        }
+        usage
+    }
+
+    /// Every user's jobs and disk use, by user
+    pub async fn usage_by_user(&self) -> BTreeMap<String, UsageSummary> {
+        let mut summaries: BTreeMap<String, UsageSummary> = BTreeMap::new();
+        let mut paths: HashMap<PathBuf, String> = HashMap::new();
+        for job in self.jobs.read().await.values() {
+            let summary = summaries.entry(job.user_id.clone()).or_default();
+            summary.total_jobs += 1;
+            match job.status {
+                JobStatus::Running => summary.usage.running += 1,
+                JobStatus::Pending => summary.usage.queued += 1,
+                JobStatus::Completed => summary.completed += 1,
+                JobStatus::Failed => summary.failed += 1,
+                JobStatus::Cancelled => summary.cancelled += 1,
+            }
+            if let FileSource::UploadedTemp(path) | FileSource::UploadedPersistent(path) =
+                &job.file_source
+            {
+                paths.insert(path.clone(), job.user_id.clone());
+            }
+            if let Some(path) = &job.output_path {
+                paths.insert(path.clone(), job.user_id.clone());
+            }
+        }
+
+        for (path, user_id) in paths {
+            if let Ok(metadata) = tokio::fs::metadata(&path).await {
+                if let Some(summary) = summaries.get_mut(&user_id) {
+                    summary.usage.disk_bytes += metadata.len();
+                }
+            }
+        }
+        summaries
+    }
+
+    /// A user's usage next to the configured limits
+    pub async fn quota_status(&self, user_id: &str) -> QuotaStatus {
+        QuotaStatus {
+            user_id: user_id.to_string(),
+            quota: self.config.user_quota,
+            usage: self.usage(user_id).await,
+        }
+    }
+
+    /// Whether a user may queue one more job that stores `incoming_bytes`
+    pub async fn check_quota(&self, user_id: &str, incoming_bytes: u64) -> Result<(), String> {
+        let usage = self.usage(user_id).await;
+        self.config
+            .user_quota
+            .check_submission(&usage, incoming_bytes)
+    }
+
     /// Get all jobs for a user
     pub async fn get_user_jobs(&self, user_id: &str) -> Vec<DDAJob> {
         let jobs = self.jobs.read().await;
@@ -310,6 +622,23 @@ impl JobQueue {
         self.progress_tx.subscribe()
     }
 
+    /// Subscribe to the window rows of running jobs, along with the rows a
+    /// job has already reported; together they miss and repeat nothing
+    pub fn subscribe_windows(
+        &self,
+        job_id: Uuid,
+    ) -> (Vec<WindowRow>, broadcast::Receiver<JobWindowEvent>) {
+        let partial = self
+            .partial_results
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let rows = partial
+            .get(&job_id)
+            .map(|result| result.rows.clone())
+            .unwrap_or_default();
+        (rows, self.window_tx.subscribe())
+    }
+
     /// Get queue statistics
     pub async fn stats(&self) -> QueueStats {
         let jobs = self.jobs.read().await;
@@ -317,7 +646,10 @@ impl JobQueue {
 
         for job in jobs.values() {
             match job.status {
-                JobStatus::Pending => stats.pending += 1,
+                JobStatus::Pending => {
+                    stats.pending += 1;
+                    *stats.pending_by_priority.entry(job.priority).or_default() += 1;
+                }
                 JobStatus::Running => stats.running += 1,
                 JobStatus::Completed => stats.completed += 1,
                 JobStatus::Failed => stats.failed += 1,
@@ -325,23 +657,208 @@ impl JobQueue {
             }
         }
 
-        stats.max_concurrent = self.config.max_concurrent_jobs;
-        stats.available_slots = self.semaphore.available_permits();
+        stats.max_concurrent = self.config.total_slots();
+        // The dispatcher holds a permit while it waits for work, so count
+        // slots from the running jobs rather than the semaphore
+        stats.available_slots = stats.max_concurrent.saturating_sub(stats.running);
+        stats.pools = self
+            .config
+            .worker_pools
+            .iter()
+            .map(|pool| {
+                let count = |status| {
+                    jobs.values()
+                        .filter(|job| job.status == status)
+                        .filter(|job| job.pool.as_deref() == Some(pool.name.as_str()))
+                        .count()
+                };
+                PoolStats {
+                    pool: pool.clone(),
+                    running: count(JobStatus::Running),
+                    pending: count(JobStatus::Pending),
+                }
+            })
+            .collect();
+        stats.run_policy = self.config.run_policy;
+        stats.user_quota = self.config.user_quota;
+        stats.resource_limits = self.config.resource_limits;
+        stats.resources_reserved = ResourceEstimate::total(
+            jobs.values()
+                .filter(|job| job.status == JobStatus::Running)
+                .filter_map(|job| job.resources.as_ref()),
+        );
 
         stats
     }
 }
 
+/// Mark the most urgent pending job running and register its cancel token
+///
+/// The token is registered under the jobs lock so a concurrent cancel never
+/// sees a running job without one.
+async fn start_next_job(
+    jobs: &RwLock<HashMap<Uuid, DDAJob>>,
+    cancel_tokens: &RwLock<HashMap<Uuid, CancellationToken>>,
+    config: &JobQueueConfig,
+) -> Option<(DDAJob, CancellationToken)> {
+    let mut jobs_guard = jobs.write().await;
+    let job_id = next_pending(&jobs_guard, config)?;
+    let job = jobs_guard.get_mut(&job_id)?;
+    job.status = JobStatus::Running;
+    job.started_at = Some(Utc::now());
+    job.environment = Some(capture_environment(&WorkDirPolicy::from_env()));
+
+    let cancel_token = CancellationToken::new();
+    cancel_tokens
+        .write()
+        .await
+        .insert(job_id, cancel_token.clone());
+    Some((job.clone(), cancel_token))
+}
+
+/// The pending job to start next among the jobs whose dependencies have all
+/// completed and whose user and worker pool are below their running limits:
+/// highest priority, then the user with the fewest running jobs, then oldest
+///
+/// Returns `None` while that job does not fit in the resources the running
+/// jobs leave free.
+fn next_pending(jobs: &HashMap<Uuid, DDAJob>, config: &JobQueueConfig) -> Option<Uuid> {
+    let mut running: HashMap<&str, usize> = HashMap::new();
+    let mut running_in_pool: HashMap<&str, usize> = HashMap::new();
+    let mut reserved = Vec::new();
+    for job in jobs.values().filter(|job| job.status == JobStatus::Running) {
+        *running.entry(job.user_id.as_str()).or_default() += 1;
+        if let Some(pool) = &job.pool {
+            *running_in_pool.entry(pool.as_str()).or_default() += 1;
+        }
+        reserved.extend(job.resources.as_ref());
+    }
+    let running_for = |job: &DDAJob| running.get(job.user_id.as_str()).copied().unwrap_or(0);
+    let pool_has_room = |job: &DDAJob| {
+        let pool = job.pool.as_deref();
+        let running = pool.and_then(|pool| running_in_pool.get(pool)).copied();
+        config.pool_has_room(pool, running.unwrap_or(0))
+    };
+
+    jobs.values()
+        .filter(|job| job.status == JobStatus::Pending)
+        .filter(|job| config.user_quota.may_start(running_for(job)))
+        .filter(|job| pool_has_room(job))
+        .filter(|job| {
+            job.depends_on.iter().all(|id| {
+                jobs.get(id)
+                    .is_some_and(|dependency| dependency.status == JobStatus::Completed)
+            })
+        })
+        .min_by_key(|job| (job.priority, running_for(job), job.submitted_at, job.id))
+        .filter(|job| {
+            config.resource_limits.fits(
+                &ResourceEstimate::total(reserved.iter().copied()),
+                &job.resources.unwrap_or_default(),
+            )
+        })
+        .map(|job| job.id)
+}
+
+/// Keep a running job's window row for late streams and broadcast it
+///
+/// Both happen under the lock so `subscribe_windows` sees each row exactly
+/// once, either buffered or live.
+fn record_window(
+    partial_results: &PartialResults,
+    window_tx: &broadcast::Sender<JobWindowEvent>,
+    job_id: Uuid,
+    row: WindowRow,
+) {
+    let mut partial = partial_results
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let result = partial.entry(job_id).or_default();
+    if result.values + row.values.len() <= MAX_BUFFERED_VALUES {
+        result.values += row.values.len();
+        result.rows.push(row.clone());
+    }
+    let _ = window_tx.send(JobWindowEvent { job_id, row });
+}
+
+/// The upload a job lets go of when it ends: its input, if temporary
+fn released_input(job: &DDAJob) -> Option<PathBuf> {
+    match &job.file_source {
+        FileSource::UploadedTemp(path) if job.delete_input_after => Some(path.clone()),
+        _ => None,
+    }
+}
+
+/// Fail or cancel the pending jobs downstream of a job that failed or was
+/// cancelled, following the chain to its end; returns the uploads the
+/// settled jobs let go of
+fn settle_dependents(
+    jobs: &mut HashMap<Uuid, DDAJob>,
+    job_id: Uuid,
+    progress_tx: &broadcast::Sender<JobProgressEvent>,
+) -> Vec<PathBuf> {
+    let mut released = Vec::new();
+    let mut settled = vec![job_id];
+    while let Some(upstream_id) = settled.pop() {
+        let Some(upstream_status) = jobs.get(&upstream_id).map(|job| job.status) else {
+            continue;
+        };
+        let (status, message) = match upstream_status {
+            JobStatus::Failed => (
+                JobStatus::Failed,
+                format!("Dependency {} failed", upstream_id),
+            ),
+            JobStatus::Cancelled => (
+                JobStatus::Cancelled,
+                format!("Dependency {} was cancelled", upstream_id),
+            ),
+            _ => continue,
+        };
+
+        for job in jobs
+            .values_mut()
+            .filter(|job| job.status == JobStatus::Pending && job.depends_on.contains(&upstream_id))
+        {
+            job.status = status;
+            job.completed_at = Some(Utc::now());
+            job.message = Some(message.clone());
+            if status == JobStatus::Failed {
+                job.error = Some(message.clone());
+            }
+            info!("Job {} {}: {}", job.id, status, message);
+
+            let _ = progress_tx.send(JobProgressEvent {
+                job_id: job.id,
+                status,
+                progress: 0,
+                message: Some(message.clone()),
+            });
+            released.extend(released_input(job));
+            settled.push(job.id);
+        }
+    }
+    released
+}
+
 /// Queue statistics
 #[derive(Debug, Default, Clone, serde::Serialize)]
 pub struct QueueStats {
     pub pending: usize,
+    pub pending_by_priority: BTreeMap<JobPriority, usize>,
     pub running: usize,
     pub completed: usize,
     pub failed: usize,
     pub cancelled: usize,
     pub max_concurrent: usize,
     pub available_slots: usize,
+    pub run_policy: RunPolicy,
+    pub user_quota: UserQuota,
+    pub resource_limits: ResourceLimits,
+    /// Estimated resources held by the running jobs
+    pub resources_reserved: ResourceEstimate,
+    /// Running and pending jobs per worker pool, when pools are configured
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pools: Vec<PoolStats>,
 }
 
 #[cfg(test)]
@@ -355,6 +872,7 @@ mod tests {
         let config = JobQueueConfig {
             max_concurrent_jobs: 2,
             notification_capacity: 100,
+            ..Default::default()
         };
         let queue = JobQueue::new(config);
 
@@ -388,4 +906,209 @@ mod tests {
         let retrieved = queue.get_job(job_id).await;
         assert!(retrieved.is_some());
     }
+
+    #[test]
+    fn test_next_pending_prefers_priority_then_age() {
+        let job = |priority, seconds_ago| {
+            let mut job = DDAJob::new(
+                "test_user".to_string(),
+                FileSource::ServerPath(PathBuf::from("/test/file.edf")),
+                "test.edf".to_string(),
+                DDAParameters::default(),
+                false,
+            )
+            .with_priority(priority);
+            job.submitted_at -= chrono::Duration::seconds(seconds_ago);
+            job
+        };
+        let old_batch = job(JobPriority::Batch, 60);
+        let old_normal = job(JobPriority::Normal, 30);
+        let new_normal = job(JobPriority::Normal, 10);
+        let mut interactive = job(JobPriority::Interactive, 0);
+        interactive.status = JobStatus::Running;
+
+        let mut jobs: HashMap<Uuid, DDAJob> = [&old_batch, &old_normal, &new_normal, &interactive]
+            .into_iter()
+            .map(|job| (job.id, job.clone()))
+            .collect();
+        assert_eq!(
+            next_pending(&jobs, &JobQueueConfig::default()),
+            Some(old_normal.id)
+        );
+
+        jobs.get_mut(&old_normal.id).unwrap().status = JobStatus::Cancelled;
+        assert_eq!(
+            next_pending(&jobs, &JobQueueConfig::default()),
+            Some(new_normal.id)
+        );
+
+        jobs.get_mut(&old_batch.id).unwrap().priority = JobPriority::Interactive;
+        assert_eq!(
+            next_pending(&jobs, &JobQueueConfig::default()),
+            Some(old_batch.id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dependents_wait_and_settle_with_their_parents() {
+        let queue = JobQueue::new(JobQueueConfig::default());
+        let job = || {
+            DDAJob::new(
+                "test_user".to_string(),
+                FileSource::ServerPath(PathBuf::from("/test/file.edf")),
+                "test.edf".to_string(),
+                DDAParameters::default(),
+                false,
+            )
+        };
+        let mut parent = job();
+        parent.status = JobStatus::Running;
+        let child = job().with_dependencies(vec![parent.id]);
+        let grandchild = job().with_dependencies(vec![child.id]);
+        let (parent_id, child_id, grandchild_id) = (parent.id, child.id, grandchild.id);
+
+        let mut jobs: HashMap<Uuid, DDAJob> = [&parent, &child, &grandchild]
+            .into_iter()
+            .map(|job| (job.id, job.clone()))
+            .collect();
+        assert_eq!(next_pending(&jobs, &JobQueueConfig::default()), None);
+        jobs.get_mut(&parent_id).unwrap().status = JobStatus::Completed;
+        assert_eq!(
+            next_pending(&jobs, &JobQueueConfig::default()),
+            Some(child_id)
+        );
+
+        jobs.get_mut(&child_id).unwrap().status = JobStatus::Failed;
+        settle_dependents(&mut jobs, child_id, &queue.progress_tx);
+        assert_eq!(jobs[&grandchild_id].status, JobStatus::Failed);
+        assert!(jobs[&grandchild_id]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains(&child_id.to_string()));
+
+        assert!(queue
+            .submit(job().with_dependencies(vec![Uuid::new_v4()]))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_next_pending_shares_slots_between_users() {
+        let job = |user: &str, seconds_ago| {
+            let mut job = DDAJob::new(
+                user.to_string(),
+                FileSource::ServerPath(PathBuf::from("/test/file.edf")),
+                "test.edf".to_string(),
+                DDAParameters::default(),
+                false,
+            );
+            job.submitted_at -= chrono::Duration::seconds(seconds_ago);
+            job
+        };
+        let mut busy_running = job("busy", 90);
+        busy_running.status = JobStatus::Running;
+        let busy_waiting = job("busy", 60);
+        let quiet_waiting = job("quiet", 30);
+
+        let mut jobs: HashMap<Uuid, DDAJob> = [&busy_running, &busy_waiting, &quiet_waiting]
+            .into_iter()
+            .map(|job| (job.id, job.clone()))
+            .collect();
+        assert_eq!(
+            next_pending(&jobs, &JobQueueConfig::default()),
+            Some(quiet_waiting.id)
+        );
+
+        jobs.remove(&quiet_waiting.id);
+        let limited = JobQueueConfig {
+            user_quota: UserQuota {
+                max_running: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(next_pending(&jobs, &limited), None);
+        assert_eq!(
+            next_pending(&jobs, &JobQueueConfig::default()),
+            Some(busy_waiting.id)
+        );
+    }
+
+    #[test]
+    fn test_full_pool_does_not_hold_back_other_pools() {
+        let config = JobQueueConfig {
+            worker_pools: crate::jobs::parse_worker_pools("small:2:channels=16,large:1").unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(config.total_slots(), 3);
+        let job = |pool: &str, seconds_ago| {
+            let mut job = DDAJob::new(
+                "test_user".to_string(),
+                FileSource::ServerPath(PathBuf::from("/test/file.edf")),
+                "test.edf".to_string(),
+                DDAParameters::default(),
+                false,
+            );
+            job.submitted_at -= chrono::Duration::seconds(seconds_ago);
+            job.pool = Some(pool.to_string());
+            job
+        };
+        let mut large_running = job("large", 90);
+        large_running.status = JobStatus::Running;
+        let large_waiting = job("large", 60);
+        let small_waiting = job("small", 30);
+
+        let mut jobs: HashMap<Uuid, DDAJob> = [&large_running, &large_waiting, &small_waiting]
+            .into_iter()
+            .map(|job| (job.id, job.clone()))
+            .collect();
+        // The older large job waits for its pool; the small one starts
+        assert_eq!(next_pending(&jobs, &config), Some(small_waiting.id));
+
+        jobs.remove(&small_waiting.id);
+        assert_eq!(next_pending(&jobs, &config), None);
+        jobs.get_mut(&large_running.id).unwrap().status = JobStatus::Completed;
+        assert_eq!(next_pending(&jobs, &config), Some(large_waiting.id));
+    }
+
+    #[test]
+    fn test_next_pending_waits_for_memory() {
+        let job = |memory_bytes, seconds_ago| {
+            let mut job = DDAJob::new(
+                "test_user".to_string(),
+                FileSource::ServerPath(PathBuf::from("/test/file.edf")),
+                "test.edf".to_string(),
+                DDAParameters::default(),
+                false,
+            );
+            job.submitted_at -= chrono::Duration::seconds(seconds_ago);
+            job.resources = Some(ResourceEstimate {
+                memory_bytes,
+                cpus: 1,
+            });
+            job
+        };
+        let mut running = job(600, 90);
+        running.status = JobStatus::Running;
+        let large = job(600, 60);
+        let small = job(100, 30);
+        let config = JobQueueConfig {
+            resource_limits: ResourceLimits {
+                memory_bytes: Some(1000),
+                cpus: None,
+            },
+            ..Default::default()
+        };
+
+        let mut jobs: HashMap<Uuid, DDAJob> = [&running, &large, &small]
+            .into_iter()
+            .map(|job| (job.id, job.clone()))
+            .collect();
+        // The older large job holds its place instead of letting the small one by
+        assert_eq!(next_pending(&jobs, &config), None);
+
+        jobs.get_mut(&running.id).unwrap().status = JobStatus::Completed;
+        assert_eq!(next_pending(&jobs, &config), Some(large.id));
+    }
 }
@@ -0,0 +1,112 @@
+//! Per-user limits on queued work and stored data
+//!
+//! Every limit is optional. `max_running` caps how many of a user's jobs run
+//! at once, so one user's backlog cannot hold every slot; `max_queued` caps
+//! the jobs a user can have waiting, and `max_disk_bytes` caps the space
+//! taken by their uploads and results.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct UserQuota {
+    /// Jobs of one user that may run at the same time
+    pub max_running: Option<usize>,
+    /// Pending jobs one user may have
+    pub max_queued: Option<usize>,
+    /// Bytes of uploads and results one user may keep on the server
+    pub max_disk_bytes: Option<u64>,
+}
+
+/// What a user currently holds against their quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct UserUsage {
+    pub running: usize,
+    pub queued: usize,
+    pub disk_bytes: u64,
+}
+
+/// A user's jobs by outcome next to their usage, for administrators
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct UsageSummary {
+    #[serde(flatten)]
+    pub usage: UserUsage,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    /// Every job of the user the server still holds
+    pub total_jobs: usize,
+}
+
+/// A user's usage next to their limits
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaStatus {
+    pub user_id: String,
+    pub quota: UserQuota,
+    pub usage: UserUsage,
+}
+
+impl UserQuota {
+    /// Whether a user with `usage` may queue one more job that stores
+    /// `incoming_bytes` of new data
+    pub fn check_submission(&self, usage: &UserUsage, incoming_bytes: u64) -> Result<(), String> {
+        if let Some(max) = self.max_queued {
+            if usage.queued >= max {
+                return Err(format!(
+                    "Queue quota reached: {} of {} jobs already pending",
+                    usage.queued, max
+                ));
+            }
+        }
+        if let Some(max) = self.max_disk_bytes {
+            let needed = usage.disk_bytes.saturating_add(incoming_bytes);
+            if needed > max {
+                return Err(format!(
+                    "Disk quota exceeded: {} bytes in use, {} more requested, limit {}",
+                    usage.disk_bytes, incoming_bytes, max
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether another of a user's jobs may start while `running` of them run
+    pub fn may_start(&self, running: usize) -> bool {
+        self.max_running.is_none_or(|max| running < max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_submission_enforces_queue_and_disk_limits() {
+        let quota = UserQuota {
+            max_running: Some(1),
+            max_queued: Some(2),
+            max_disk_bytes: Some(1000),
+        };
+        let usage = UserUsage {
+            running: 1,
+            queued: 1,
+            disk_bytes: 600,
+        };
+        assert!(quota.check_submission(&usage, 400).is_ok());
+        assert!(quota
+            .check_submission(&usage, 401)
+            .unwrap_err()
+            .contains("Disk quota"));
+
+        let full = UserUsage { queued: 2, ..usage };
+        assert!(quota
+            .check_submission(&full, 0)
+            .unwrap_err()
+            .contains("Queue quota"));
+
+        assert!(!quota.may_start(1));
+        assert!(UserQuota::default().may_start(100));
+        assert!(UserQuota::default()
+            .check_submission(&full, u64::MAX)
+            .is_ok());
+    }
+}
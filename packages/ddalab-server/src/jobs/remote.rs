@@ -0,0 +1,515 @@
+//! DDA attempts on other machines
+//!
+//! With `DDA_EXECUTOR=workers`, attempts are offered to worker nodes:
+//! `ddalab-server worker-node` processes on other hosts that claim runs
+//! from `/api/executor`, download the input, run the binary, and send back
+//! its output as it goes and the result at the end. With
+//! `DDA_EXECUTOR=kubernetes`, every attempt starts a Kubernetes Job whose
+//! pod is a worker node for that one run. Either way the queue, retries and
+//! timeouts stay on the server; a node that stops reporting fails its run.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Notify};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::kubernetes::{KubernetesConfig, KubernetesLauncher};
+use super::policy::RunPolicy;
+use super::types::DDAJob;
+use super::worker::{Attempt, AttemptError, Executor, OutputStream};
+use crate::auth::constant_time_eq;
+
+/// How often a worker node reports on a run, even without new output
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Silence after which the node running a run is presumed lost
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Longest a claim waits for a run to be offered
+pub const CLAIM_WAIT: Duration = Duration::from_secs(25);
+
+/// Shortest accepted executor token
+const MIN_TOKEN_LENGTH: usize = 32;
+
+/// Remote execution settings
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    /// Secret worker nodes authenticate with (`EXECUTOR_TOKEN`)
+    pub token: String,
+    /// Start a Kubernetes Job for every attempt instead of waiting for
+    /// standing worker nodes
+    pub kubernetes: Option<KubernetesConfig>,
+}
+
+impl RemoteConfig {
+    /// None unless `DDA_EXECUTOR` is `workers` or `kubernetes`
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let executor = std::env::var("DDA_EXECUTOR")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let kubernetes = match executor.as_str() {
+            "" | "local" => return Ok(None),
+            "workers" => None,
+            "kubernetes" => Some(KubernetesConfig::from_env()?),
+            other => {
+                return Err(format!(
+                    "Unknown DDA_EXECUTOR '{}': expected local, workers or kubernetes",
+                    other
+                ))
+            }
+        };
+        let token = std::env::var("EXECUTOR_TOKEN")
+            .map(|token| token.trim().to_string())
+            .unwrap_or_default();
+        if token.len() < MIN_TOKEN_LENGTH {
+            return Err(format!(
+                "EXECUTOR_TOKEN of at least {} characters must be set with DDA_EXECUTOR={}",
+                MIN_TOKEN_LENGTH, executor
+            ));
+        }
+        Ok(Some(Self { token, kubernetes }))
+    }
+}
+
+/// A claim for a run to execute
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClaimRequest {
+    /// Name of the worker node, for logs
+    pub node: String,
+    /// The run a Kubernetes pod was started for; any run when unset
+    #[serde(default)]
+    pub run_id: Option<Uuid>,
+}
+
+/// A run as handed to the worker node that claimed it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunAssignment {
+    pub run_id: Uuid,
+    /// Attempt of the job, from 1
+    pub attempt: u32,
+    pub job: DDAJob,
+    /// Extension of the input, which the binary tells formats apart by
+    pub input_extension: String,
+    /// The server enforces the timeout; nodes use the kill grace
+    pub policy: RunPolicy,
+}
+
+/// A line the binary printed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+/// Output since a node's last report; an empty report is a heartbeat
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunReport {
+    #[serde(default)]
+    pub lines: Vec<OutputLine>,
+}
+
+/// How a run ended on its node; the result is uploaded first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunOutcome {
+    /// Why the run failed; succeeded when unset
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Errors of the worker node protocol
+#[derive(Debug, thiserror::Error)]
+pub enum RunError {
+    /// Finished, cancelled or timed out; the node should stop
+    #[error("Run not found")]
+    NotFound,
+    #[error("Run has not been claimed")]
+    NotClaimed,
+}
+
+enum RunEvent {
+    Output(OutputStream, String),
+    Finished(Option<String>),
+}
+
+struct RemoteRun {
+    assignment: RunAssignment,
+    input: PathBuf,
+    output: PathBuf,
+    /// Only the Kubernetes pod started for it may claim the run
+    dedicated: bool,
+    offered_at: Instant,
+    node: Option<String>,
+    last_seen: Instant,
+    events: mpsc::UnboundedSender<RunEvent>,
+}
+
+/// Hands attempts to worker nodes or Kubernetes Jobs
+pub struct RemoteExecutor {
+    token: String,
+    kubernetes: Option<KubernetesLauncher>,
+    /// Runs offered or in progress, by run ID
+    runs: Mutex<HashMap<Uuid, RemoteRun>>,
+    /// Woken when a run is offered
+    offered: Notify,
+}
+
+impl std::fmt::Debug for RemoteExecutor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteExecutor")
+            .field("kubernetes", &self.kubernetes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RemoteExecutor {
+    pub fn new(config: &RemoteConfig) -> Self {
+        Self {
+            token: config.token.clone(),
+            kubernetes: config.kubernetes.clone().map(KubernetesLauncher::new),
+            runs: Mutex::new(HashMap::new()),
+            offered: Notify::new(),
+        }
+    }
+
+    /// Whether `token` is the executor token
+    pub fn authorize(&self, token: &str) -> bool {
+        constant_time_eq(token.as_bytes(), self.token.as_bytes())
+    }
+
+    fn runs(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, RemoteRun>> {
+        self.runs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Claim the oldest run offered, or `run_id`, waiting up to
+    /// [`CLAIM_WAIT`] for one
+    pub async fn claim(&self, node: &str, run_id: Option<Uuid>) -> Option<RunAssignment> {
+        let deadline = tokio::time::Instant::now() + CLAIM_WAIT;
+        loop {
+            // Registered before looking, so no offer in between is missed
+            let offered = self.offered.notified();
+            if let Some(assignment) = self.take(node, run_id) {
+                return Some(assignment);
+            }
+            if tokio::time::timeout_at(deadline, offered).await.is_err() {
+                return None;
+            }
+        }
+    }
+
+    fn take(&self, node: &str, run_id: Option<Uuid>) -> Option<RunAssignment> {
+        let mut runs = self.runs();
+        let run = match run_id {
+            Some(run_id) => runs.get_mut(&run_id).filter(|run| run.node.is_none()),
+            None => runs
+                .values_mut()
+                .filter(|run| run.node.is_none() && !run.dedicated)
+                .min_by_key(|run| run.offered_at),
+        }?;
+        run.node = Some(node.to_string());
+        run.last_seen = Instant::now();
+        info!(
+            "Worker node {} claimed run {} of job {}",
+            node, run.assignment.run_id, run.assignment.job.id
+        );
+        Some(run.assignment.clone())
+    }
+
+    /// Look up a claimed run, noting that its node is alive
+    fn with_run<T>(&self, run_id: Uuid, f: impl FnOnce(&RemoteRun) -> T) -> Result<T, RunError> {
+        let mut runs = self.runs();
+        let run = runs.get_mut(&run_id).ok_or(RunError::NotFound)?;
+        if run.node.is_none() {
+            return Err(RunError::NotClaimed);
+        }
+        run.last_seen = Instant::now();
+        Ok(f(run))
+    }
+
+    /// The plaintext input of a claimed run
+    pub fn input_path(&self, run_id: Uuid) -> Result<PathBuf, RunError> {
+        self.with_run(run_id, |run| run.input.clone())
+    }
+
+    /// Where the result of a claimed run is written
+    pub fn result_path(&self, run_id: Uuid) -> Result<PathBuf, RunError> {
+        self.with_run(run_id, |run| run.output.clone())
+    }
+
+    /// Pass on output of a claimed run
+    pub fn report(&self, run_id: Uuid, report: RunReport) -> Result<(), RunError> {
+        self.with_run(run_id, |run| {
+            for OutputLine { stream, line } in report.lines {
+                let _ = run.events.send(RunEvent::Output(stream, line));
+            }
+        })
+    }
+
+    /// End a claimed run
+    pub fn finish(&self, run_id: Uuid, outcome: RunOutcome) -> Result<(), RunError> {
+        self.with_run(run_id, |run| {
+            let _ = run.events.send(RunEvent::Finished(outcome.error));
+        })
+    }
+
+    /// Runs offered but not yet claimed, and runs in progress
+    pub fn counts(&self) -> (usize, usize) {
+        let runs = self.runs();
+        let claimed = runs.values().filter(|run| run.node.is_some()).count();
+        (runs.len() - claimed, claimed)
+    }
+
+    /// The node of a claimed run that has not reported for too long
+    fn lost_node(&self, run_id: Uuid) -> Option<String> {
+        let runs = self.runs();
+        let run = runs.get(&run_id)?;
+        run.node
+            .clone()
+            .filter(|_| run.last_seen.elapsed() > HEARTBEAT_TIMEOUT)
+    }
+
+    /// Wait for the run to end, passing on its output
+    async fn follow(
+        &self,
+        run_id: Uuid,
+        attempt: &Attempt<'_>,
+        events: &mut mpsc::UnboundedReceiver<RunEvent>,
+        output: &mut (dyn FnMut(OutputStream, String) + Send),
+    ) -> Result<(), AttemptError> {
+        let deadline = async {
+            match attempt.policy.timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(deadline);
+        let mut check = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Some(RunEvent::Output(stream, line)) => output(stream, line),
+                    Some(RunEvent::Finished(None)) if attempt.output.exists() => return Ok(()),
+                    Some(RunEvent::Finished(None)) => {
+                        return Err(AttemptError::Failed(anyhow::anyhow!(
+                            "Worker node finished without sending the result"
+                        )));
+                    }
+                    Some(RunEvent::Finished(Some(error))) => {
+                        return Err(AttemptError::Failed(anyhow::anyhow!(error)));
+                    }
+                    None => {
+                        return Err(AttemptError::Failed(anyhow::anyhow!("Run {} vanished", run_id)));
+                    }
+                },
+                _ = attempt.cancel.cancelled() => return Err(AttemptError::Cancelled),
+                _ = &mut deadline => return Err(AttemptError::TimedOut),
+                _ = check.tick() => {
+                    if let Some(node) = self.lost_node(run_id) {
+                        return Err(AttemptError::Failed(anyhow::anyhow!(
+                            "Worker node {} stopped responding",
+                            node
+                        )));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Executor for RemoteExecutor {
+    async fn run(
+        &self,
+        attempt: &Attempt<'_>,
+        output: &mut (dyn FnMut(OutputStream, String) + Send),
+    ) -> Result<(), AttemptError> {
+        let run_id = Uuid::new_v4();
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let now = Instant::now();
+        self.runs().insert(
+            run_id,
+            RemoteRun {
+                assignment: RunAssignment {
+                    run_id,
+                    attempt: attempt.number,
+                    job: attempt.job.clone(),
+                    input_extension: input_extension(attempt.input),
+                    policy: *attempt.policy,
+                },
+                input: attempt.input.to_path_buf(),
+                output: attempt.output.to_path_buf(),
+                dedicated: self.kubernetes.is_some(),
+                offered_at: now,
+                node: None,
+                last_seen: now,
+                events: events_tx,
+            },
+        );
+
+        if let Some(kubernetes) = &self.kubernetes {
+            if let Err(e) = kubernetes.create_job(run_id, attempt.job).await {
+                self.runs().remove(&run_id);
+                return Err(AttemptError::Failed(anyhow::anyhow!(
+                    "Failed to create Kubernetes Job: {}",
+                    e
+                )));
+            }
+        }
+        self.offered.notify_waiters();
+        info!(
+            "Job {} attempt {} offered as run {}",
+            attempt.job.id, attempt.number, run_id
+        );
+
+        let outcome = self.follow(run_id, attempt, &mut events, output).await;
+        // Nodes still working on it learn from the next report that it ended
+        self.runs().remove(&run_id);
+        if outcome.is_err() {
+            if let Some(kubernetes) = &self.kubernetes {
+                kubernetes.delete_job(run_id).await;
+            }
+        }
+        if let Err(AttemptError::Failed(e)) = &outcome {
+            warn!("Run {} of job {} failed: {}", run_id, attempt.job.id, e);
+        }
+        outcome
+    }
+}
+
+fn input_extension(input: &Path) -> String {
+    input
+        .extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "edf".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::{DDAParameters, FileSource, WorkDirPolicy};
+    use tokio_util::sync::CancellationToken;
+
+    fn executor() -> RemoteExecutor {
+        RemoteExecutor::new(&RemoteConfig {
+            token: "t".repeat(MIN_TOKEN_LENGTH),
+            kubernetes: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_worker_node_runs_an_offered_attempt() {
+        let dir = std::env::temp_dir().join(format!("ddalab-remote-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.edf");
+        std::fs::write(&input, b"recording").unwrap();
+        let output_path = dir.join("result.json");
+        let job = DDAJob::new(
+            "user".to_string(),
+            FileSource::ServerPath(input.clone()),
+            "input.edf".to_string(),
+            DDAParameters::default(),
+            false,
+        );
+        let work_dir = WorkDirPolicy::default().create(job.id, 1).await.unwrap();
+        let executor = std::sync::Arc::new(executor());
+        assert!(executor.authorize(&"t".repeat(MIN_TOKEN_LENGTH)));
+        assert!(!executor.authorize("t"));
+
+        // A node that claims the run, reports a line and sends the result
+        let node = {
+            let executor = executor.clone();
+            tokio::spawn(async move {
+                let assignment = executor.claim("node-1", None).await.unwrap();
+                assert_eq!(assignment.input_extension, "edf");
+                let input = executor.input_path(assignment.run_id).unwrap();
+                assert_eq!(std::fs::read(input).unwrap(), b"recording");
+                executor
+                    .report(
+                        assignment.run_id,
+                        RunReport {
+                            lines: vec![OutputLine {
+                                stream: OutputStream::Stderr,
+                                line: "Progress: 50%".to_string(),
+                            }],
+                        },
+                    )
+                    .unwrap();
+                let result = executor.result_path(assignment.run_id).unwrap();
+                std::fs::write(result, b"{}").unwrap();
+                executor
+                    .finish(assignment.run_id, RunOutcome::default())
+                    .unwrap();
+                assignment.run_id
+            })
+        };
+
+        let cancel = CancellationToken::new();
+        let policy = RunPolicy::default();
+        let attempt = Attempt {
+            job: &job,
+            number: 1,
+            input: &input,
+            output: &output_path,
+            work_dir: &work_dir,
+            cancel: &cancel,
+            policy: &policy,
+        };
+        let mut lines = Vec::new();
+        executor
+            .run(&attempt, &mut |stream, line| lines.push((stream, line)))
+            .await
+            .unwrap();
+        let run_id = node.await.unwrap();
+
+        assert_eq!(
+            lines,
+            vec![(OutputStream::Stderr, "Progress: 50%".to_string())]
+        );
+        assert_eq!(std::fs::read(&output_path).unwrap(), b"{}");
+        // Finished runs are gone, which tells a late node to stop
+        assert!(matches!(
+            executor.report(run_id, RunReport::default()),
+            Err(RunError::NotFound)
+        ));
+        assert_eq!(executor.counts(), (0, 0));
+
+        work_dir.finish(true).await;
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_withdraws_the_run() {
+        let executor = executor();
+        let dir = std::env::temp_dir().join(format!("ddalab-remote-{}", Uuid::new_v4()));
+        let job = DDAJob::new(
+            "user".to_string(),
+            FileSource::ServerPath(dir.join("input.edf")),
+            "input.edf".to_string(),
+            DDAParameters::default(),
+            false,
+        );
+        let work_dir = WorkDirPolicy::default().create(job.id, 1).await.unwrap();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let policy = RunPolicy::default();
+        let (input, output_path) = (dir.join("input.edf"), dir.join("result.json"));
+        let attempt = Attempt {
+            job: &job,
+            number: 1,
+            input: &input,
+            output: &output_path,
+            work_dir: &work_dir,
+            cancel: &cancel,
+            policy: &policy,
+        };
+        let outcome = executor.run(&attempt, &mut |_, _| {}).await;
+        assert!(matches!(outcome, Err(AttemptError::Cancelled)));
+        assert_eq!(executor.counts(), (0, 0));
+        work_dir.finish(true).await;
+    }
+}
@@ -0,0 +1,284 @@
+//! Memory and CPU footprint of jobs
+//!
+//! A job's footprint is estimated once, when it is submitted, from the input
+//! file: the EDF/BDF header gives the number of samples the selected
+//! channels and time range cover, and other formats fall back to the file
+//! size. The queue starts a job only while the running jobs' estimates plus
+//! its own stay within the configured limits, so a few very long recordings
+//! cannot exhaust the host's memory between them.
+
+use super::types::DDAJob;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+/// Memory every DDA process needs regardless of input size
+const BASE_MEMORY_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Working copies of the signal held during an analysis
+const SAMPLE_COPIES: u64 = 3;
+
+/// Bytes per sample assumed for inputs whose header is not read
+const UNKNOWN_FORMAT_BYTES_PER_SAMPLE: u64 = 2;
+
+/// Fraction of host memory jobs may use when no limit is configured
+const HOST_MEMORY_SHARE: f64 = 0.8;
+
+/// Estimated resources one job holds while it runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceEstimate {
+    pub memory_bytes: u64,
+    pub cpus: u32,
+}
+
+impl ResourceEstimate {
+    fn add(self, other: ResourceEstimate) -> ResourceEstimate {
+        ResourceEstimate {
+            memory_bytes: self.memory_bytes.saturating_add(other.memory_bytes),
+            cpus: self.cpus.saturating_add(other.cpus),
+        }
+    }
+
+    /// Sum the estimates of several jobs
+    pub fn total<'a>(estimates: impl IntoIterator<Item = &'a ResourceEstimate>) -> Self {
+        estimates
+            .into_iter()
+            .fold(ResourceEstimate::default(), |sum, estimate| {
+                sum.add(*estimate)
+            })
+    }
+
+    /// Estimate the footprint of a job from its input file
+    ///
+    /// Reads the file header, so call it off the async runtime.
+    pub fn for_job(job: &DDAJob) -> Self {
+        let path = job.input_path();
+        let samples = match read_edf_header(&path) {
+            Some(header) => header.selected_samples(job),
+            None => std::fs::metadata(&path)
+                .map(|m| m.len() / UNKNOWN_FORMAT_BYTES_PER_SAMPLE)
+                .unwrap_or(0),
+        };
+        let downsample = u64::from(job.parameters.downsample.max(1));
+        ResourceEstimate {
+            memory_bytes: BASE_MEMORY_BYTES
+                + samples.saturating_mul(8 * SAMPLE_COPIES) / downsample,
+            cpus: 1,
+        }
+    }
+}
+
+/// Upper bounds on what running jobs may hold together
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// Unlimited when unset
+    pub memory_bytes: Option<u64>,
+    /// Unlimited when unset
+    pub cpus: Option<u32>,
+}
+
+impl ResourceLimits {
+    /// Limits derived from the host: a share of its memory and all its CPUs
+    pub fn from_host() -> Self {
+        Self {
+            memory_bytes: host_memory_bytes()
+                .map(|total| (total as f64 * HOST_MEMORY_SHARE) as u64),
+            cpus: std::thread::available_parallelism()
+                .ok()
+                .map(|n| n.get() as u32),
+        }
+    }
+
+    /// Whether a job with `estimate` may start while `reserved` is held
+    ///
+    /// A job larger than the limits on its own still runs once nothing else
+    /// does, rather than waiting forever.
+    pub fn fits(&self, reserved: &ResourceEstimate, estimate: &ResourceEstimate) -> bool {
+        if *reserved == ResourceEstimate::default() {
+            return true;
+        }
+        let needed = reserved.add(*estimate);
+        self.memory_bytes
+            .is_none_or(|max| needed.memory_bytes <= max)
+            && self.cpus.is_none_or(|max| needed.cpus <= max)
+    }
+}
+
+/// Total physical memory, where the platform reports it
+fn host_memory_bytes() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemTotal:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// The parts of an EDF/BDF header that size a recording
+#[derive(Debug, PartialEq)]
+struct EdfHeader {
+    labels: Vec<String>,
+    records: u64,
+    record_seconds: f64,
+    samples_per_record: Vec<u64>,
+}
+
+impl EdfHeader {
+    /// Samples of the job's channels within its time range
+    fn selected_samples(&self, job: &DDAJob) -> u64 {
+        let channels = &job.parameters.channels;
+        let per_record: u64 = self
+            .labels
+            .iter()
+            .zip(&self.samples_per_record)
+            .filter(|(label, _)| {
+                channels.is_empty() || channels.iter().any(|c| c.trim() == label.trim())
+            })
+            .map(|(_, samples)| samples)
+            .sum();
+
+        let duration = self.records as f64 * self.record_seconds;
+        let start = job
+            .parameters
+            .start_time
+            .unwrap_or(0.0)
+            .clamp(0.0, duration);
+        let end = job
+            .parameters
+            .end_time
+            .unwrap_or(duration)
+            .clamp(start, duration);
+        let records = if duration > 0.0 {
+            (self.records as f64 * (end - start) / duration).ceil() as u64
+        } else {
+            self.records
+        };
+        per_record.saturating_mul(records)
+    }
+}
+
+/// Read the fixed and per-signal header of an EDF or BDF file
+fn read_edf_header(path: &Path) -> Option<EdfHeader> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut fixed = [0u8; 256];
+    file.read_exact(&mut fixed).ok()?;
+    // EDF starts with "0" padded to eight bytes, BDF with 0xFF "BIOSEMI"
+    if &fixed[0..8] != b"0       " && &fixed[0..8] != b"\xffBIOSEMI" {
+        return None;
+    }
+    let field = |bytes: &[u8]| String::from_utf8_lossy(bytes).trim().to_string();
+    let records: u64 = field(&fixed[236..244]).parse().ok()?;
+    let record_seconds: f64 = field(&fixed[244..252]).parse().ok()?;
+    let signals: usize = field(&fixed[252..256]).parse().ok()?;
+
+    let mut per_signal = vec![0u8; signals * 256];
+    file.read_exact(&mut per_signal).ok()?;
+    let labels = (0..signals)
+        .map(|i| field(&per_signal[i * 16..(i + 1) * 16]))
+        .collect();
+    // Samples per record follow the label, transducer, dimension, four
+    // range and prefiltering fields: 216 bytes per signal in
+    let offset = signals * 216;
+    let samples_per_record = (0..signals)
+        .map(|i| {
+            field(&per_signal[offset + i * 8..offset + (i + 1) * 8])
+                .parse()
+                .ok()
+        })
+        .collect::<Option<Vec<u64>>>()?;
+
+    Some(EdfHeader {
+        labels,
+        records,
+        record_seconds,
+        samples_per_record,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::types::{DDAParameters, FileSource};
+    use std::io::Write;
+
+    fn edf_header(labels: &[&str], records: u64, samples: u64) -> Vec<u8> {
+        let pad = |text: &str, width: usize| format!("{:<width$}", text, width = width);
+        let mut header = String::new();
+        header += &pad("0", 8);
+        header += &pad("", 80 + 80 + 8 + 8);
+        header += &pad(&(256 * (labels.len() + 1)).to_string(), 8);
+        header += &pad("", 44);
+        header += &pad(&records.to_string(), 8);
+        header += &pad("1", 8);
+        header += &pad(&labels.len().to_string(), 4);
+        for label in labels {
+            header += &pad(label, 16);
+        }
+        header += &pad("", labels.len() * (80 + 8 + 8 + 8 + 8 + 8 + 80));
+        for _ in labels {
+            header += &pad(&samples.to_string(), 8);
+        }
+        header += &pad("", labels.len() * 32);
+        header.into_bytes()
+    }
+
+    #[test]
+    fn test_estimate_counts_selected_channels_and_time() {
+        let dir = std::env::temp_dir().join(format!("ddalab-resources-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("recording.edf");
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(&edf_header(&["Fp1", "Fp2", "Cz", "O1"], 3600, 256))
+            .unwrap();
+
+        let header = read_edf_header(&path).unwrap();
+        assert_eq!(header.labels, ["Fp1", "Fp2", "Cz", "O1"]);
+        assert_eq!(header.records, 3600);
+
+        let job = |parameters| {
+            DDAJob::new(
+                "test_user".to_string(),
+                FileSource::ServerPath(path.clone()),
+                "recording.edf".to_string(),
+                parameters,
+                false,
+            )
+        };
+        let all = ResourceEstimate::for_job(&job(DDAParameters::default()));
+        assert_eq!(
+            all.memory_bytes,
+            BASE_MEMORY_BYTES + 4 * 256 * 3600 * 8 * SAMPLE_COPIES
+        );
+
+        let subset = ResourceEstimate::for_job(&job(DDAParameters {
+            channels: vec!["Cz".to_string()],
+            end_time: Some(1800.0),
+            ..Default::default()
+        }));
+        assert_eq!(
+            subset.memory_bytes,
+            BASE_MEMORY_BYTES + 256 * 1800 * 8 * SAMPLE_COPIES
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_limits_admit_oversized_job_only_when_idle() {
+        let limits = ResourceLimits {
+            memory_bytes: Some(1000),
+            cpus: Some(4),
+        };
+        let small = ResourceEstimate {
+            memory_bytes: 400,
+            cpus: 1,
+        };
+        let huge = ResourceEstimate {
+            memory_bytes: 5000,
+            cpus: 1,
+        };
+        assert!(limits.fits(&small, &small));
+        assert!(!limits.fits(&ResourceEstimate::total([&small, &small]), &small));
+        assert!(!limits.fits(&small, &huge));
+        assert!(limits.fits(&ResourceEstimate::default(), &huge));
+    }
+}
@@ -0,0 +1,210 @@
+//! Heatmap thumbnails of completed jobs
+//!
+//! A small PNG of the primary Q matrix is rendered next to each result
+//! (`<job>.png` beside `<job>.json`) so job lists can show a preview without
+//! downloading the full result. Rows are channels, columns are windows
+//! averaged into at most `MAX_WIDTH` bins, and colors span the 2nd to 98th
+//! percentile so a few extreme values do not wash out the image.
+
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::crypto::{read_file, write_file, StorageKey};
+
+/// Largest thumbnail width in pixels
+const MAX_WIDTH: usize = 256;
+/// Largest thumbnail height in pixels
+const MAX_HEIGHT: usize = 128;
+/// Pixel height of a channel when there are few of them
+const ROW_HEIGHT: usize = 8;
+/// Color of windows without a finite value
+const MISSING: [u8; 3] = [48, 48, 48];
+
+/// Viridis anchor colors, evenly spaced from 0 to 1
+const VIRIDIS: [[u8; 3]; 5] = [
+    [68, 1, 84],
+    [59, 82, 139],
+    [33, 145, 140],
+    [94, 201, 98],
+    [253, 231, 37],
+];
+
+/// Where the thumbnail of a result file lives
+pub fn thumbnail_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension("png")
+}
+
+/// Render the thumbnail of `output_path` and write it beside the result,
+/// encrypted like the result when a storage key is given
+pub fn write_thumbnail(output_path: &Path, key: Option<&StorageKey>) -> Result<PathBuf> {
+    let data =
+        read_file(key, output_path).with_context(|| format!("Failed to read {:?}", output_path))?;
+    let result: serde_json::Value = serde_json::from_slice(&data)?;
+    let matrix = primary_q_matrix(&result).ok_or_else(|| anyhow!("Result has no Q matrix"))?;
+    let png = render_png(&matrix)?;
+
+    let path = thumbnail_path(output_path);
+    write_file(key, &path, &png).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(path)
+}
+
+/// The top-level `q_matrix`, or that of the first variant when it is empty
+fn primary_q_matrix(result: &serde_json::Value) -> Option<Vec<Vec<f64>>> {
+    let parse = |value: &serde_json::Value| -> Option<Vec<Vec<f64>>> {
+        let rows = value
+            .as_array()?
+            .iter()
+            .map(|row| {
+                row.as_array()
+                    .map(|values| {
+                        values
+                            .iter()
+                            .map(|v| v.as_f64().unwrap_or(f64::NAN))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<Vec<f64>>>();
+        rows.iter().any(|row| !row.is_empty()).then_some(rows)
+    };
+
+    parse(&result["q_matrix"]).or_else(|| {
+        result["variant_results"]
+            .as_array()?
+            .iter()
+            .find_map(|variant| parse(&variant["q_matrix"]))
+    })
+}
+
+/// Encode `matrix` (rows × windows) as an RGB heatmap PNG
+pub fn render_png(matrix: &[Vec<f64>]) -> Result<Vec<u8>> {
+    let rows = matrix.len();
+    let columns = matrix.iter().map(Vec::len).max().unwrap_or(0);
+    if rows == 0 || columns == 0 {
+        return Err(anyhow!("Cannot render an empty matrix"));
+    }
+
+    let width = columns.min(MAX_WIDTH);
+    let height = (rows * ROW_HEIGHT).min(MAX_HEIGHT);
+    let binned = bin_columns(matrix, width);
+    let (low, high) = color_range(&binned);
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        let row = &binned[y * rows / height];
+        for value in row {
+            let color = if value.is_finite() {
+                let t = if high > low {
+                    (value - low) / (high - low)
+                } else {
+                    0.5
+                };
+                colormap(t)
+            } else {
+                MISSING
+            };
+            pixels.extend_from_slice(&color);
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(png)
+}
+
+/// Average each row into `width` bins, ignoring non-finite values
+fn bin_columns(matrix: &[Vec<f64>], width: usize) -> Vec<Vec<f64>> {
+    matrix
+        .iter()
+        .map(|row| {
+            (0..width)
+                .map(|bin| {
+                    let start = bin * row.len() / width;
+                    let end = ((bin + 1) * row.len() / width)
+                        .max(start + 1)
+                        .min(row.len());
+                    let finite = row
+                        .get(start..end)
+                        .unwrap_or_default()
+                        .iter()
+                        .filter(|v| v.is_finite());
+                    let (sum, count) = finite.fold((0.0, 0usize), |(s, c), v| (s + v, c + 1));
+                    if count > 0 {
+                        sum / count as f64
+                    } else {
+                        f64::NAN
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// 2nd and 98th percentile of the finite values
+fn color_range(matrix: &[Vec<f64>]) -> (f64, f64) {
+    let mut values: Vec<f64> = matrix
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|v| v.is_finite())
+        .collect();
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    values.sort_by(f64::total_cmp);
+    let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+    (at(0.02), at(0.98))
+}
+
+fn colormap(t: f64) -> [u8; 3] {
+    let scaled = t.clamp(0.0, 1.0) * (VIRIDIS.len() - 1) as f64;
+    let index = (scaled.floor() as usize).min(VIRIDIS.len() - 2);
+    let frac = scaled - index as f64;
+    let (a, b) = (VIRIDIS[index], VIRIDIS[index + 1]);
+    std::array::from_fn(|i| (a[i] as f64 + (b[i] as f64 - a[i] as f64) * frac).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renders_primary_matrix_as_png() {
+        let dir = std::env::temp_dir().join(format!("ddalab-thumb-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("job.json");
+        let q: Vec<Vec<f64>> = (0..3)
+            .map(|c| (0..1000).map(|w| (c * w) as f64).collect())
+            .collect();
+        let mut row = q[2].clone();
+        row[10] = f64::NAN;
+        std::fs::write(
+            &output,
+            serde_json::json!({ "q_matrix": [], "variant_results": [{ "q_matrix": [q[0], q[1], row] }] })
+                .to_string(),
+        )
+        .unwrap();
+
+        let path = write_thumbnail(&output, None).unwrap();
+        assert_eq!(path, dir.join("job.png"));
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(
+            (info.width, info.height),
+            (MAX_WIDTH as u32, 3 * ROW_HEIGHT as u32)
+        );
+
+        assert!(write_thumbnail(&dir.join("missing.json"), None).is_err());
+        assert_eq!(colormap(0.0), VIRIDIS[0]);
+        assert_eq!(colormap(1.0), VIRIDIS[4]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
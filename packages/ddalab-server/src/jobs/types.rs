@@ -1,5 +1,8 @@
+use super::pools::ResourceRequirements;
+use super::resources::ResourceEstimate;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use uuid::Uuid;
 
@@ -31,6 +34,31 @@ impl std::fmt::Display for JobStatus {
     }
 }
 
+/// Scheduling class of a job; pending jobs start in this order
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPriority {
+    /// Quick analyses someone is waiting on
+    Interactive,
+    /// Ordinary submissions
+    #[default]
+    Normal,
+    /// Bulk and scheduled work that can wait
+    Batch,
+}
+
+impl std::fmt::Display for JobPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobPriority::Interactive => write!(f, "interactive"),
+            JobPriority::Normal => write!(f, "normal"),
+            JobPriority::Batch => write!(f, "batch"),
+        }
+    }
+}
+
 /// Source of input file for DDA job
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -67,12 +95,61 @@ pub struct DDAParameters {
     pub start_time: Option<f64>,
     /// Optional end time in seconds
     pub end_time: Option<f64>,
+    /// Filtering applied to the recording before DDA runs
+    #[serde(default)]
+    pub preprocessing: PreprocessingOptions,
 }
 
 fn default_downsample() -> u32 {
     1
 }
 
+/// Filters the DDA binary applies to the recording before the analysis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PreprocessingOptions {
+    /// High-pass cutoff in Hz
+    #[serde(default)]
+    pub highpass: Option<f64>,
+    /// Low-pass cutoff in Hz
+    #[serde(default)]
+    pub lowpass: Option<f64>,
+    /// Line-noise frequency to notch out in Hz, usually 50 or 60
+    #[serde(default)]
+    pub notch: Option<f64>,
+    /// Remove each channel's linear trend
+    #[serde(default)]
+    pub detrend: bool,
+}
+
+impl PreprocessingOptions {
+    /// Check that cutoffs are positive and leave a passband
+    ///
+    /// Cutoffs are checked against the Nyquist frequency by the binary,
+    /// which knows the recording's sampling rate.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("highpass", self.highpass),
+            ("lowpass", self.lowpass),
+            ("notch", self.notch),
+        ] {
+            if let Some(hz) = value {
+                if !(hz.is_finite() && hz > 0.0) {
+                    return Err(format!("{} must be a positive frequency in Hz", name));
+                }
+            }
+        }
+        if let (Some(highpass), Some(lowpass)) = (self.highpass, self.lowpass) {
+            if highpass >= lowpass {
+                return Err(format!(
+                    "highpass ({} Hz) must be below lowpass ({} Hz)",
+                    highpass, lowpass
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Default for DDAParameters {
     fn default() -> Self {
         Self {
@@ -86,6 +163,7 @@ impl Default for DDAParameters {
             downsample: 1,
             start_time: None,
             end_time: None,
+            preprocessing: PreprocessingOptions::default(),
         }
     }
 }
@@ -105,6 +183,9 @@ pub struct DDAJob {
     pub parameters: DDAParameters,
     /// Current status
     pub status: JobStatus,
+    /// Scheduling class
+    #[serde(default)]
+    pub priority: JobPriority,
     /// Progress percentage (0-100)
     pub progress: u8,
     /// Status message
@@ -121,6 +202,30 @@ pub struct DDAJob {
     pub completed_at: Option<DateTime<Utc>>,
     /// Whether to delete input file after processing
     pub delete_input_after: bool,
+    /// Recurring schedule that created this job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_id: Option<Uuid>,
+    /// Team parameter preset the job was submitted under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset_id: Option<Uuid>,
+    /// Environment the DDA execution saw, recorded when the job starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<BTreeMap<String, String>>,
+    /// Jobs that must complete before this one starts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<Uuid>,
+    /// Memory and CPUs the job is expected to hold, estimated on submission
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceEstimate>,
+    /// Channels and run time the submitter declared
+    #[serde(default)]
+    pub requirements: ResourceRequirements,
+    /// Worker pool the job was assigned to on submission
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    /// SHA-256 of an uploaded input, in hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_hash: Option<String>,
 }
 
 impl DDAJob {
@@ -138,6 +243,7 @@ impl DDAJob {
             original_filename,
             parameters,
             status: JobStatus::Pending,
+            priority: JobPriority::Normal,
             progress: 0,
             message: None,
             output_path: None,
@@ -146,9 +252,57 @@ impl DDAJob {
             started_at: None,
             completed_at: None,
             delete_input_after,
+            schedule_id: None,
+            preset_id: None,
+            environment: None,
+            depends_on: Vec::new(),
+            resources: None,
+            requirements: ResourceRequirements::default(),
+            pool: None,
+            file_hash: None,
         }
     }
 
+    /// Tag the job with the schedule that created it
+    pub fn with_schedule(mut self, schedule_id: Uuid) -> Self {
+        self.schedule_id = Some(schedule_id);
+        self
+    }
+
+    /// Queue the job in another priority class
+    pub fn with_priority(mut self, priority: JobPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Hold the job until the given jobs have completed
+    pub fn with_dependencies(mut self, depends_on: Vec<Uuid>) -> Self {
+        self.depends_on = depends_on;
+        self
+    }
+
+    /// Declare the job's requirements; the channel count defaults to the
+    /// channels its parameters list
+    pub fn with_requirements(mut self, mut requirements: ResourceRequirements) -> Self {
+        if requirements.channels.is_none() && !self.parameters.channels.is_empty() {
+            requirements.channels = Some(self.parameters.channels.len() as u32);
+        }
+        self.requirements = requirements;
+        self
+    }
+
+    /// Tag the job with the team preset it was submitted under
+    pub fn with_preset(mut self, preset_id: Option<Uuid>) -> Self {
+        self.preset_id = preset_id;
+        self
+    }
+
+    /// Record the content hash of the job's uploaded input
+    pub fn with_file_hash(mut self, file_hash: String) -> Self {
+        self.file_hash = Some(file_hash);
+        self
+    }
+
     /// Get the input file path
     pub fn input_path(&self) -> PathBuf {
         match &self.file_source {
@@ -174,6 +328,9 @@ pub struct SubmitJobRequest {
     /// Whether to store in persistent working directory
     #[serde(default)]
     pub persist_upload: bool,
+    /// Jobs that must complete before this one starts
+    #[serde(default)]
+    pub depends_on: Vec<Uuid>,
 }
 
 /// Response after submitting a job
@@ -188,7 +345,9 @@ pub struct SubmitJobResponse {
 #[derive(Debug, Clone, Serialize)]
 pub struct JobStatusResponse {
     pub id: Uuid,
+    pub file_name: String,
     pub status: JobStatus,
+    pub priority: JobPriority,
     pub progress: u8,
     pub message: Option<String>,
     pub output_path: Option<String>,
@@ -196,24 +355,103 @@ pub struct JobStatusResponse {
     pub submitted_at: DateTime<Utc>,
     pub started_at: Option<DateTime<Utc>>,
     pub completed_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    /// SHA-256 of the uploaded input, the hash its annotations are kept under
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_hash: Option<String>,
 }
 
 impl From<&DDAJob> for JobStatusResponse {
     fn from(job: &DDAJob) -> Self {
         Self {
             id: job.id,
+            file_name: job.original_filename.clone(),
             status: job.status,
+            priority: job.priority,
             progress: job.progress,
             message: job.message.clone(),
-            output_path: job.output_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            output_path: job
+                .output_path
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string()),
             error: job.error.clone(),
             submitted_at: job.submitted_at,
             started_at: job.started_at,
             completed_at: job.completed_at,
+            schedule_id: job.schedule_id,
+            preset_id: job.preset_id,
+            environment: job.environment.clone(),
+            depends_on: job.depends_on.clone(),
+            pool: job.pool.clone(),
+            file_hash: job.file_hash.clone(),
+        }
+    }
+}
+
+/// Status of every job connected to one job through `depends_on`
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineStatusResponse {
+    /// Overall status: failed or cancelled as soon as any job is, completed
+    /// once every job is, otherwise running or pending
+    pub status: JobStatus,
+    /// Completed jobs out of `jobs.len()`
+    pub completed: usize,
+    /// Jobs in submission order
+    pub jobs: Vec<JobStatusResponse>,
+}
+
+impl PipelineStatusResponse {
+    pub fn new(mut jobs: Vec<DDAJob>) -> Self {
+        jobs.sort_by_key(|job| (job.submitted_at, job.id));
+        let count = |status| jobs.iter().filter(|job| job.status == status).count();
+        let completed = count(JobStatus::Completed);
+        let status = if count(JobStatus::Failed) > 0 {
+            JobStatus::Failed
+        } else if count(JobStatus::Cancelled) > 0 {
+            JobStatus::Cancelled
+        } else if completed == jobs.len() {
+            JobStatus::Completed
+        } else if count(JobStatus::Running) > 0 || completed > 0 {
+            JobStatus::Running
+        } else {
+            JobStatus::Pending
+        };
+        Self {
+            status,
+            completed,
+            jobs: jobs.iter().map(JobStatusResponse::from).collect(),
         }
     }
 }
 
+/// One analysis window's Q values, reported while a job runs
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowRow {
+    /// Variant the values belong to, e.g. `ST`
+    pub variant: String,
+    /// Zero-based window index
+    pub window: usize,
+    /// One value per channel (or pair) of the variant; `null` when missing
+    pub values: Vec<f64>,
+}
+
+/// A window row of a running job, for result streams
+#[derive(Debug, Clone, Serialize)]
+pub struct JobWindowEvent {
+    pub job_id: Uuid,
+    #[serde(flatten)]
+    pub row: WindowRow,
+}
+
 /// Progress update event for WebSocket notifications
 #[derive(Debug, Clone, Serialize)]
 pub struct JobProgressEvent {
@@ -0,0 +1,402 @@
+//! Resumable chunked uploads
+//!
+//! Multi-gigabyte recordings sent over hospital Wi-Fi rarely make it in one
+//! request. A resumable upload is created with its final size and the job
+//! it should run; its bytes are then appended in chunks that each state the
+//! offset they start at, as in the tus protocol. After a dropped connection
+//! the client asks for the current offset and carries on from there instead
+//! of starting over. Partial files and their job settings are kept under
+//! `<upload dir>/.partial`, so uploads also survive a server restart. Once
+//! every byte has arrived the upload is finished into a regular uploaded
+//! file and its job submitted. Uploads that receive no chunk for
+//! [`UPLOAD_EXPIRY`] are removed.
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{JobPriority, ResourceRequirements};
+
+/// How long an upload may go without a chunk before it is removed
+pub const UPLOAD_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Directory under the upload directory holding partial uploads
+const PARTIAL_DIRECTORY: &str = ".partial";
+
+/// The job an upload is submitted as once complete
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadJobOptions {
+    /// Job parameter fields, applied over those of the preset
+    #[serde(default)]
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+    /// Personal or team parameter preset the job runs with
+    #[serde(default)]
+    pub preset_id: Option<Uuid>,
+    #[serde(default)]
+    pub priority: JobPriority,
+    /// Jobs that must complete before this one starts
+    #[serde(default)]
+    pub depends_on: Vec<Uuid>,
+    #[serde(default)]
+    pub requirements: ResourceRequirements,
+    /// Delete the uploaded file once the job has run
+    #[serde(default = "default_delete_after")]
+    pub delete_after: bool,
+    /// Keep the uploaded file for later jobs
+    #[serde(default)]
+    pub persist_upload: bool,
+}
+
+fn default_delete_after() -> bool {
+    true
+}
+
+impl Default for UploadJobOptions {
+    fn default() -> Self {
+        Self {
+            parameters: serde_json::Map::new(),
+            preset_id: None,
+            priority: JobPriority::default(),
+            depends_on: Vec::new(),
+            requirements: ResourceRequirements::default(),
+            delete_after: default_delete_after(),
+            persist_upload: false,
+        }
+    }
+}
+
+/// A resumable upload as stored next to its partial file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadSession {
+    pub id: Uuid,
+    pub user_id: String,
+    pub filename: String,
+    /// Total size in bytes, declared on creation
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+    pub job: UploadJobOptions,
+}
+
+/// Progress of a resumable upload
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadProgress {
+    pub upload_id: Uuid,
+    pub filename: String,
+    pub size: u64,
+    /// Bytes received so far; the next chunk starts here
+    pub offset: u64,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    #[error("Upload not found")]
+    NotFound,
+    #[error("Upload is at offset {expected}, not {given}")]
+    OffsetMismatch { expected: u64, given: u64 },
+    #[error("Chunk runs past the declared size of {0} bytes")]
+    TooLarge(u64),
+    #[error("Upload has {received} of {size} bytes")]
+    Incomplete { received: u64, size: u64 },
+    #[error("Upload interrupted: {0}")]
+    Interrupted(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Partial uploads on disk
+pub struct ResumableUploads {
+    directory: PathBuf,
+    /// Serializes chunks, finishing and aborting per upload
+    locks: Mutex<HashMap<Uuid, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl ResumableUploads {
+    pub fn new(upload_directory: &Path) -> Self {
+        Self {
+            directory: upload_directory.join(PARTIAL_DIRECTORY),
+            locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn data_path(&self, id: Uuid) -> PathBuf {
+        self.directory.join(format!("{}.part", id))
+    }
+
+    fn session_path(&self, id: Uuid) -> PathBuf {
+        self.directory.join(format!("{}.json", id))
+    }
+
+    fn lock(&self, id: Uuid) -> Arc<tokio::sync::Mutex<()>> {
+        self.locks.lock().entry(id).or_default().clone()
+    }
+
+    /// Start an upload of `size` bytes
+    pub async fn create(
+        &self,
+        user_id: &str,
+        filename: &str,
+        size: u64,
+        job: UploadJobOptions,
+    ) -> Result<UploadProgress, UploadError> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let session = UploadSession {
+            id: Uuid::new_v4(),
+            user_id: user_id.to_string(),
+            filename: filename.to_string(),
+            size,
+            created_at: Utc::now(),
+            job,
+        };
+        tokio::fs::File::create(self.data_path(session.id)).await?;
+        let json = serde_json::to_vec(&session).map_err(std::io::Error::other)?;
+        tokio::fs::write(self.session_path(session.id), json).await?;
+        info!(
+            "Resumable upload {} of {} ({} bytes) started by {}",
+            session.id, session.filename, size, user_id
+        );
+        self.progress(&session).await
+    }
+
+    /// Load an upload; another user's uploads are not found
+    pub async fn get(&self, id: Uuid, user_id: &str) -> Result<UploadSession, UploadError> {
+        let json = match tokio::fs::read(self.session_path(id)).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(UploadError::NotFound)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let session: UploadSession =
+            serde_json::from_slice(&json).map_err(std::io::Error::other)?;
+        if session.user_id != user_id {
+            return Err(UploadError::NotFound);
+        }
+        Ok(session)
+    }
+
+    /// Bytes received and expiry of an upload
+    pub async fn progress(&self, session: &UploadSession) -> Result<UploadProgress, UploadError> {
+        let metadata = tokio::fs::metadata(self.data_path(session.id)).await?;
+        let last_chunk = metadata
+            .modified()
+            .map(DateTime::<Utc>::from)
+            .unwrap_or(session.created_at);
+        Ok(UploadProgress {
+            upload_id: session.id,
+            filename: session.filename.clone(),
+            size: session.size,
+            offset: metadata.len(),
+            expires_at: last_chunk + UPLOAD_EXPIRY,
+        })
+    }
+
+    /// Append a chunk starting at `offset`; returns the new offset
+    ///
+    /// Bytes written before the chunk's stream fails are kept, so the
+    /// client resumes after them.
+    pub async fn append<S, B, E>(
+        &self,
+        id: Uuid,
+        user_id: &str,
+        offset: u64,
+        mut chunk: S,
+    ) -> Result<u64, UploadError>
+    where
+        S: Stream<Item = Result<B, E>> + Unpin,
+        B: AsRef<[u8]>,
+        E: std::fmt::Display,
+    {
+        let lock = self.lock(id);
+        let _guard = lock.lock().await;
+        let session = self.get(id, user_id).await?;
+        let mut received = tokio::fs::metadata(self.data_path(id)).await?.len();
+        if offset != received {
+            return Err(UploadError::OffsetMismatch {
+                expected: received,
+                given: offset,
+            });
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(self.data_path(id))
+            .await?;
+        let mut outcome = Ok(());
+        while let Some(bytes) = chunk.next().await {
+            let bytes = match bytes {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    outcome = Err(UploadError::Interrupted(e.to_string()));
+                    break;
+                }
+            };
+            let bytes = bytes.as_ref();
+            if received + bytes.len() as u64 > session.size {
+                outcome = Err(UploadError::TooLarge(session.size));
+                break;
+            }
+            file.write_all(bytes).await?;
+            received += bytes.len() as u64;
+        }
+        file.flush().await?;
+        outcome.map(|_| received)
+    }
+
+    /// Move a complete upload to `destination` and forget it
+    pub async fn finish(
+        &self,
+        id: Uuid,
+        user_id: &str,
+        destination: &Path,
+    ) -> Result<UploadSession, UploadError> {
+        let lock = self.lock(id);
+        let _guard = lock.lock().await;
+        let session = self.get(id, user_id).await?;
+        let received = tokio::fs::metadata(self.data_path(id)).await?.len();
+        if received != session.size {
+            return Err(UploadError::Incomplete {
+                received,
+                size: session.size,
+            });
+        }
+        tokio::fs::rename(self.data_path(id), destination).await?;
+        tokio::fs::remove_file(self.session_path(id)).await.ok();
+        self.locks.lock().remove(&id);
+        Ok(session)
+    }
+
+    /// Discard an upload and its bytes
+    pub async fn abort(&self, id: Uuid, user_id: &str) -> Result<(), UploadError> {
+        let lock = self.lock(id);
+        let _guard = lock.lock().await;
+        self.get(id, user_id).await?;
+        self.remove(id).await;
+        Ok(())
+    }
+
+    async fn remove(&self, id: Uuid) {
+        tokio::fs::remove_file(self.data_path(id)).await.ok();
+        tokio::fs::remove_file(self.session_path(id)).await.ok();
+        self.locks.lock().remove(&id);
+    }
+
+    /// Remove uploads that have gone [`UPLOAD_EXPIRY`] without a chunk;
+    /// returns how many were removed
+    pub async fn prune_expired(&self) -> usize {
+        let Ok(mut entries) = tokio::fs::read_dir(&self.directory).await else {
+            return 0;
+        };
+        let mut removed = 0;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(id) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| Uuid::try_parse(s).ok())
+            else {
+                continue;
+            };
+            let idle = tokio::fs::metadata(self.data_path(id))
+                .await
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+            // A session without its data file is left over from a crash
+            if idle.is_none_or(|idle| idle > UPLOAD_EXPIRY) {
+                warn!("Removing abandoned upload {}", id);
+                self.remove(id).await;
+                removed += 1;
+            }
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    fn chunk(bytes: &'static [u8]) -> impl Stream<Item = Result<&'static [u8], String>> + Unpin {
+        stream::iter(vec![Ok(bytes)])
+    }
+
+    #[tokio::test]
+    async fn test_upload_resumes_at_offset() {
+        let dir = std::env::temp_dir().join(format!("ddalab-uploads-{}", Uuid::new_v4()));
+        let uploads = ResumableUploads::new(&dir);
+        let created = uploads
+            .create("a@example.com", "rec.edf", 6, UploadJobOptions::default())
+            .await
+            .unwrap();
+        let id = created.upload_id;
+        assert_eq!(created.offset, 0);
+
+        assert_eq!(
+            uploads
+                .append(id, "a@example.com", 0, chunk(b"abc"))
+                .await
+                .unwrap(),
+            3
+        );
+        // A repeated chunk after a lost response is refused, not duplicated
+        assert!(matches!(
+            uploads.append(id, "a@example.com", 0, chunk(b"abc")).await,
+            Err(UploadError::OffsetMismatch { expected: 3, .. })
+        ));
+        assert!(matches!(
+            uploads.append(id, "b@example.com", 3, chunk(b"def")).await,
+            Err(UploadError::NotFound)
+        ));
+        assert!(matches!(
+            uploads
+                .finish(id, "a@example.com", &dir.join("rec.edf"))
+                .await,
+            Err(UploadError::Incomplete {
+                received: 3,
+                size: 6
+            })
+        ));
+
+        // An interrupted chunk keeps what arrived
+        let broken = stream::iter(vec![Ok(&b"de"[..]), Err("reset".to_string())]);
+        assert!(matches!(
+            uploads.append(id, "a@example.com", 3, broken).await,
+            Err(UploadError::Interrupted(_))
+        ));
+        let session = uploads.get(id, "a@example.com").await.unwrap();
+        assert_eq!(uploads.progress(&session).await.unwrap().offset, 5);
+        assert!(matches!(
+            uploads.append(id, "a@example.com", 5, chunk(b"fg")).await,
+            Err(UploadError::TooLarge(6))
+        ));
+        uploads
+            .append(id, "a@example.com", 5, chunk(b"f"))
+            .await
+            .unwrap();
+
+        let destination = dir.join("rec.edf");
+        uploads
+            .finish(id, "a@example.com", &destination)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), b"abcdef");
+        assert!(matches!(
+            uploads.get(id, "a@example.com").await,
+            Err(UploadError::NotFound)
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
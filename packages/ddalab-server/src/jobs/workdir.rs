@@ -0,0 +1,179 @@
+//! Isolated working directories for DDA executions
+//!
+//! Concurrent jobs on one machine used to share the process working
+//! directory and system temp dir, so scratch files written by the binary
+//! could collide. Every attempt now runs in `<root>/<job id>/attempt-<n>`,
+//! which is also its `TMPDIR`/`TMP`/`TEMP`, and the directory is removed
+//! afterwards unless the attempt failed and `keep_on_error` is set.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Variables recorded besides every `DDA_*` one
+const CAPTURED_VARIABLES: &[&str] = &[
+    "PATH",
+    "LD_LIBRARY_PATH",
+    "DYLD_LIBRARY_PATH",
+    "LANG",
+    "LC_ALL",
+    "LC_NUMERIC",
+    "OMP_NUM_THREADS",
+    "MKL_NUM_THREADS",
+    "OPENBLAS_NUM_THREADS",
+];
+
+/// Variables pointing the child at its work directory
+const TEMP_VARIABLES: &[&str] = &["TMPDIR", "TMP", "TEMP"];
+
+/// Where work directories are created and when they are kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkDirPolicy {
+    pub root: PathBuf,
+    /// Keep the directory of a failed attempt for inspection
+    pub keep_on_error: bool,
+}
+
+impl Default for WorkDirPolicy {
+    fn default() -> Self {
+        Self {
+            root: std::env::temp_dir().join("ddalab-work"),
+            keep_on_error: false,
+        }
+    }
+}
+
+impl WorkDirPolicy {
+    /// From `DDA_WORK_ROOT` and `DDA_KEEP_WORK_DIR_ON_ERROR`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            root: std::env::var("DDA_WORK_ROOT")
+                .ok()
+                .filter(|root| !root.trim().is_empty())
+                .map(PathBuf::from)
+                .unwrap_or(defaults.root),
+            keep_on_error: std::env::var("DDA_KEEP_WORK_DIR_ON_ERROR")
+                .map(|v| v.to_lowercase() == "true")
+                .unwrap_or(defaults.keep_on_error),
+        }
+    }
+
+    /// Directory of attempt `attempt` of `job_id`
+    pub fn path_for(&self, job_id: Uuid, attempt: u32) -> PathBuf {
+        self.root
+            .join(job_id.to_string())
+            .join(format!("attempt-{}", attempt))
+    }
+
+    /// Create a fresh directory for an attempt, clearing leftovers of a
+    /// previous server run that used the same job id and attempt
+    pub async fn create(&self, job_id: Uuid, attempt: u32) -> Result<WorkDir> {
+        let path = self.path_for(job_id, attempt);
+        if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            tokio::fs::remove_dir_all(&path).await.ok();
+        }
+        tokio::fs::create_dir_all(&path)
+            .await
+            .with_context(|| format!("Failed to create work directory {:?}", path))?;
+        Ok(WorkDir {
+            path,
+            keep_on_error: self.keep_on_error,
+        })
+    }
+}
+
+/// Working directory of one attempt
+#[derive(Debug)]
+pub struct WorkDir {
+    path: PathBuf,
+    keep_on_error: bool,
+}
+
+impl WorkDir {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Run `cmd` inside this directory with the temp variables pointing at it
+    pub fn apply(&self, cmd: &mut Command) {
+        cmd.current_dir(&self.path);
+        for name in TEMP_VARIABLES {
+            cmd.env(name, &self.path);
+        }
+    }
+
+    /// Remove the directory; a failed attempt's directory is kept when the
+    /// policy asks for it, and its path returned
+    pub async fn finish(self, succeeded: bool) -> Option<PathBuf> {
+        if !succeeded && self.keep_on_error {
+            info!("Keeping work directory {:?} of failed attempt", self.path);
+            return Some(self.path);
+        }
+        if let Err(e) = tokio::fs::remove_dir_all(&self.path).await {
+            error!("Failed to remove work directory {:?}: {}", self.path, e);
+        }
+        // Drop the per-job parent once its last attempt is gone
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::remove_dir(parent).await.ok();
+        }
+        None
+    }
+}
+
+/// Environment a DDA execution sees, for the job record: every `DDA_*`
+/// variable, the library and locale settings that change numerical results,
+/// and the temp directory the attempt is given
+pub fn capture_environment(policy: &WorkDirPolicy) -> BTreeMap<String, String> {
+    let mut captured: BTreeMap<String, String> = std::env::vars()
+        .filter(|(name, _)| name.starts_with("DDA_") || CAPTURED_VARIABLES.contains(&name.as_str()))
+        .collect();
+    captured.insert(
+        "TMPDIR".to_string(),
+        policy
+            .root
+            .join("<job>/attempt-<n>")
+            .to_string_lossy()
+            .into_owned(),
+    );
+    captured
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_work_dirs_are_isolated_and_cleaned_up() {
+        let root = std::env::temp_dir().join(format!("ddalab-work-{}", Uuid::new_v4()));
+        let policy = WorkDirPolicy {
+            root: root.clone(),
+            keep_on_error: true,
+        };
+        let job_id = Uuid::new_v4();
+
+        let first = policy.create(job_id, 1).await.unwrap();
+        let second = policy.create(job_id, 2).await.unwrap();
+        assert_ne!(first.path(), second.path());
+        std::fs::write(first.path().join("scratch.txt"), "x").unwrap();
+
+        assert_eq!(second.finish(true).await, None);
+        let kept = first.finish(false).await.unwrap();
+        assert!(kept.join("scratch.txt").exists());
+
+        // A recreated attempt starts empty
+        let again = policy.create(job_id, 1).await.unwrap();
+        assert!(!again.path().join("scratch.txt").exists());
+        assert_eq!(again.finish(true).await, None);
+        assert!(!root.join(job_id.to_string()).exists());
+
+        let environment = capture_environment(&policy);
+        assert!(environment["TMPDIR"].starts_with(root.to_str().unwrap()));
+        assert!(!environment.contains_key("DATABASE_URL"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
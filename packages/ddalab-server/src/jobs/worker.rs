@@ -1,93 +1,387 @@
-use super::types::DDAJob;
-use anyhow::{anyhow, Result};
-use std::path::PathBuf;
+use super::launch::{LaunchStrategy, ProcessTree};
+use super::policy::RunPolicy;
+use super::types::{DDAJob, WindowRow};
+use super::workdir::{WorkDir, WorkDirPolicy};
+use crate::crypto::{is_encrypted, StorageKey};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
-use tracing::{debug, error, info};
+use tokio::process::Child;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+/// Why a single DDA attempt ended without a result
+#[derive(Debug)]
+pub enum AttemptError {
+    Cancelled,
+    TimedOut,
+    Failed(anyhow::Error),
+}
 
-/// Run DDA analysis for a job
+/// Stream of the DDA binary a line was printed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    /// Window rows
+    Stdout,
+    /// Progress and status messages
+    Stderr,
+}
+
+/// One run of the DDA binary for a job
+pub struct Attempt<'a> {
+    pub job: &'a DDAJob,
+    /// 1-based
+    pub number: u32,
+    /// Plaintext input on this host
+    pub input: &'a Path,
+    /// Where the result is expected on this host
+    pub output: &'a Path,
+    pub work_dir: &'a WorkDir,
+    pub cancel: &'a CancellationToken,
+    pub policy: &'a RunPolicy,
+}
+
+/// Where DDA attempts run
 ///
-/// The `progress_callback` is called with (progress_percent, message) and should return
-/// `true` to continue or `false` to cancel.
-pub async fn run_dda_analysis<F>(job: &DDAJob, mut progress_callback: F) -> Result<PathBuf>
-where
-    F: FnMut(u8, Option<String>) -> bool,
-{
-    // Get DDA binary path from environment or use default
-    let dda_binary = std::env::var("DDA_BINARY_PATH")
-        .ok()
-        .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("dda")); // Assume in PATH
+/// [`LocalExecutor`] spawns the binary on this host; the remote executor
+/// hands attempts to worker nodes or Kubernetes Jobs.
+#[async_trait]
+pub trait Executor: std::fmt::Debug + Send + Sync {
+    /// Run `attempt`, passing every line the binary prints to `output`,
+    /// until its result is at `attempt.output`, it is cancelled, or it
+    /// exceeds `attempt.policy.timeout`
+    async fn run(
+        &self,
+        attempt: &Attempt<'_>,
+        output: &mut (dyn FnMut(OutputStream, String) + Send),
+    ) -> std::result::Result<(), AttemptError>;
+}
+
+/// Spawns the DDA binary on this host
+#[derive(Debug, Clone)]
+pub struct LocalExecutor {
+    binary: PathBuf,
+}
 
-    if !dda_binary.exists() && dda_binary.to_string_lossy() != "dda" {
-        return Err(anyhow!("DDA binary not found at {:?}", dda_binary));
+impl LocalExecutor {
+    pub fn new(binary: PathBuf) -> Self {
+        Self { binary }
     }
 
-    let input_path = job.input_path();
+    /// The binary at `DDA_BINARY_PATH`, or `dda` on the `PATH`
+    pub fn from_env() -> Self {
+        std::env::var_os("DDA_BINARY_PATH")
+            .map(|path| Self::new(PathBuf::from(path)))
+            .unwrap_or_default()
+    }
+}
+
+impl Default for LocalExecutor {
+    /// `dda` on the `PATH`
+    fn default() -> Self {
+        Self::new(PathBuf::from("dda"))
+    }
+}
+
+#[async_trait]
+impl Executor for LocalExecutor {
+    async fn run(
+        &self,
+        attempt: &Attempt<'_>,
+        output: &mut (dyn FnMut(OutputStream, String) + Send),
+    ) -> std::result::Result<(), AttemptError> {
+        let dda_binary = &self.binary;
+        if !dda_binary.exists() && dda_binary.to_string_lossy() != "dda" {
+            return Err(AttemptError::Failed(anyhow!(
+                "DDA binary not found at {:?}",
+                dda_binary
+            )));
+        }
+
+        // Direct spawn unless DDA_LAUNCH_SHELL routes the call through a shell
+        let strategy = LaunchStrategy::from_env();
+        let args = dda_arguments(attempt.job, attempt.input.to_path_buf(), attempt.output);
+        run_process(attempt, &strategy, dda_binary, &args, output).await
+    }
+}
+
+/// Where and how a job's attempts run
+#[derive(Clone, Copy)]
+pub struct AnalysisSettings<'a> {
+    pub executor: &'a dyn Executor,
+    pub policy: &'a RunPolicy,
+    /// Key encrypted inputs are decrypted with and results encrypted with
+    pub storage_key: Option<&'a StorageKey>,
+    /// Directory results are written to
+    pub output_directory: &'a Path,
+}
+
+/// Run DDA analysis for a job
+///
+/// The `progress_callback` is called with (progress_percent, message), and the
+/// `window_callback` with each window row the binary prints on stdout as it
+/// goes (see [`parse_window_row`]). When `cancel` is triggered the attempt is stopped and the partial output removed.
+/// Attempts that exceed the policy's timeout or exit unsuccessfully are retried
+/// as the policy allows. With a storage key, an encrypted input is decrypted
+/// into each attempt's work directory only while the binary runs, and the
+/// result is encrypted before it is reported.
+pub async fn run_dda_analysis<F, W>(
+    job: &DDAJob,
+    settings: &AnalysisSettings<'_>,
+    cancel: &CancellationToken,
+    mut progress_callback: F,
+    mut window_callback: W,
+) -> Result<PathBuf>
+where
+    F: FnMut(u8, Option<String>) + Send,
+    W: FnMut(WindowRow) + Send,
+{
+    let AnalysisSettings {
+        executor,
+        policy,
+        storage_key,
+        output_directory,
+    } = *settings;
+
+    // Absolute, since the binary runs inside its work directory
+    let input_path = std::path::absolute(job.input_path())?;
     if !input_path.exists() {
         return Err(anyhow!("Input file not found: {:?}", input_path));
     }
+    let encrypted_input = match storage_key {
+        Some(key) if is_encrypted(&input_path)? => Some(key),
+        _ => None,
+    };
+
+    tokio::fs::create_dir_all(output_directory).await?;
+
+    let output_path = std::path::absolute(output_directory.join(format!("{}.json", job.id)))?;
+
+    let work_dirs = WorkDirPolicy::from_env();
+    let max_attempts = policy.max_attempts();
+    let mut attempt = 1;
+    loop {
+        let work_dir = work_dirs.create(job.id, attempt).await?;
+        let attempt_input = match encrypted_input {
+            Some(key) => match decrypt_input(key, &input_path, &work_dir).await {
+                Ok(path) => path,
+                Err(e) => {
+                    work_dir.finish(true).await;
+                    return Err(e);
+                }
+            },
+            None => input_path.clone(),
+        };
+        let outcome = {
+            let mut last_progress: u8 = 0;
+            let mut output = |stream: OutputStream, line: String| match stream {
+                OutputStream::Stdout => {
+                    if let Some(row) = parse_window_row(&line) {
+                        window_callback(row);
+                    }
+                }
+                OutputStream::Stderr => {
+                    debug!("DDA output: {}", line);
+
+                    // Parse progress from DDA output
+                    // Expecting format like: "Progress: 45%" or "[45%]" or "45/100"
+                    if let Some(progress) = parse_progress(&line) {
+                        last_progress = progress;
+                        progress_callback(progress, Some(line));
+                    } else if line.contains("Processing") || line.contains("Analyzing") {
+                        // Status messages
+                        progress_callback(last_progress, Some(line));
+                    }
+                }
+            };
+            let current = Attempt {
+                job,
+                number: attempt,
+                input: &attempt_input,
+                output: &output_path,
+                work_dir: &work_dir,
+                cancel,
+                policy,
+            };
+            executor.run(&current, &mut output).await
+        };
+        // Never leave a decrypted copy behind, even in a kept work directory
+        if encrypted_input.is_some() {
+            tokio::fs::remove_file(&attempt_input).await.ok();
+        }
+        let failed = matches!(
+            outcome,
+            Err(AttemptError::TimedOut | AttemptError::Failed(_))
+        );
+        let kept_work_dir = work_dir.finish(!failed).await;
+        let error = match outcome {
+            Ok(()) => break,
+            Err(AttemptError::Cancelled) => {
+                remove_partial_output(job, &output_path).await;
+                return Err(anyhow!("Job cancelled"));
+            }
+            Err(AttemptError::TimedOut) => anyhow!(
+                "DDA timed out after {}s",
+                policy.timeout.unwrap_or_default().as_secs_f64()
+            ),
+            Err(AttemptError::Failed(error)) => error,
+        };
+        let error = match kept_work_dir {
+            Some(dir) => error.context(format!("Work directory kept at {:?}", dir)),
+            None => error,
+        };
+        remove_partial_output(job, &output_path).await;
 
-    // Create output directory
-    let output_dir = std::env::var("DDA_OUTPUT_DIR")
-        .ok()
-        .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("/tmp/ddalab-jobs"));
-
-    tokio::fs::create_dir_all(&output_dir).await?;
+        if attempt >= max_attempts {
+            if max_attempts > 1 {
+                return Err(error.context(format!("Giving up after {} attempts", attempt)));
+            }
+            return Err(error);
+        }
+        attempt += 1;
+        let delay = policy.backoff_before(attempt);
+        warn!(
+            "Job {} attempt {} failed ({}), retrying in {:?}",
+            job.id,
+            attempt - 1,
+            error,
+            delay
+        );
+        progress_callback(
+            0,
+            Some(format!(
+                "Retrying ({}/{}) after: {}",
+                attempt, max_attempts, error
+            )),
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = cancel.cancelled() => {
+                return Err(anyhow!("Job cancelled"));
+            }
+        }
+    }
 
-    let output_path = output_dir.join(format!("{}.json", job.id));
+    if let Some(key) = storage_key {
+        let (key, path) = (key.clone(), output_path.clone());
+        let encrypted = tokio::task::spawn_blocking(move || key.encrypt_in_place(&path))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result.map_err(anyhow::Error::from));
+        if let Err(e) = encrypted {
+            remove_partial_output(job, &output_path).await;
+            return Err(e.context("Failed to encrypt the result"));
+        }
+    }
 
-    // Build DDA command
-    let mut cmd = Command::new(&dda_binary);
+    info!("Job {} completed, results at {:?}", job.id, output_path);
 
-    // Input file
-    cmd.arg("-i").arg(input_path);
+    Ok(output_path)
+}
 
-    // Output file
-    cmd.arg("-o").arg(&output_path);
+/// Command-line arguments for the DDA binary
+pub(super) fn dda_arguments(
+    job: &DDAJob,
+    input_path: PathBuf,
+    output_path: &Path,
+) -> Vec<OsString> {
+    let mut args: Vec<OsString> = Vec::new();
+    let mut arg = |flag: &str, value: OsString| {
+        args.push(flag.into());
+        args.push(value);
+    };
+
+    // Input and output files
+    arg("-i", input_path.into_os_string());
+    arg("-o", output_path.as_os_str().to_os_string());
 
     // Channels
     if !job.parameters.channels.is_empty() {
-        cmd.arg("-c").arg(job.parameters.channels.join(","));
+        arg("-c", job.parameters.channels.join(",").into());
     }
 
     // CT pairs
     for (c1, c2) in &job.parameters.ct_pairs {
-        cmd.arg("--ct").arg(format!("{},{}", c1, c2));
+        arg("--ct", format!("{},{}", c1, c2).into());
     }
 
     // CD pairs
     for (c1, c2) in &job.parameters.cd_pairs {
-        cmd.arg("--cd").arg(format!("{},{}", c1, c2));
+        arg("--cd", format!("{},{}", c1, c2).into());
     }
 
     // Parameters
-    cmd.arg("-w").arg(job.parameters.time_window.to_string());
-    cmd.arg("-d").arg(job.parameters.delta.to_string());
-    cmd.arg("-m").arg(job.parameters.embedding_dim.to_string());
-    cmd.arg("-s").arg(job.parameters.svd_dimensions.to_string());
+    arg("-w", job.parameters.time_window.to_string().into());
+    arg("-d", job.parameters.delta.to_string().into());
+    arg("-m", job.parameters.embedding_dim.to_string().into());
+    arg("-s", job.parameters.svd_dimensions.to_string().into());
 
     if job.parameters.downsample > 1 {
-        cmd.arg("--downsample")
-            .arg(job.parameters.downsample.to_string());
+        arg("--downsample", job.parameters.downsample.to_string().into());
+    }
+
+    // Filters run on the recording before the analysis
+    let preprocessing = &job.parameters.preprocessing;
+    if let Some(cutoff) = preprocessing.highpass {
+        arg("--highpass", cutoff.to_string().into());
+    }
+    if let Some(cutoff) = preprocessing.lowpass {
+        arg("--lowpass", cutoff.to_string().into());
+    }
+    if let Some(frequency) = preprocessing.notch {
+        arg("--notch", frequency.to_string().into());
     }
 
     if let Some(start) = job.parameters.start_time {
-        cmd.arg("--start").arg(start.to_string());
+        arg("--start", start.to_string().into());
     }
 
     if let Some(end) = job.parameters.end_time {
-        cmd.arg("--end").arg(end.to_string());
+        arg("--end", end.to_string().into());
+    }
+
+    if preprocessing.detrend {
+        args.push("--detrend".into());
     }
 
     // Enable progress output
-    cmd.arg("--progress");
+    args.push("--progress".into());
+    args
+}
+
+/// Run the DDA binary once, passing on its output until it exits
+async fn run_process(
+    attempt: &Attempt<'_>,
+    strategy: &LaunchStrategy,
+    dda_binary: &Path,
+    args: &[OsString],
+    output: &mut (dyn FnMut(OutputStream, String) + Send),
+) -> std::result::Result<(), AttemptError> {
+    let Attempt {
+        job,
+        output: output_path,
+        work_dir,
+        cancel,
+        policy,
+        ..
+    } = *attempt;
+    let mut cmd = strategy.command(dda_binary, args);
+    work_dir.apply(&mut cmd);
 
     // Configure stdio
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
+    cmd.kill_on_drop(true);
+
+    // Run in its own process group so cancellation also reaches any helpers it spawns
+    #[cfg(unix)]
+    cmd.process_group(0);
 
     info!(
         "Starting DDA analysis for job {}: {:?}",
@@ -96,66 +390,151 @@ where
     );
 
     // Start process
-    let mut child = cmd.spawn().map_err(|e| anyhow!("Failed to spawn DDA: {}", e))?;
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| AttemptError::Failed(anyhow!("Failed to spawn DDA: {}", e)))?;
+    let tree = ProcessTree::attach(&child);
+
+    let deadline = async {
+        match policy.timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(deadline);
 
     // Read progress from stderr (DDA typically outputs progress to stderr)
-    let stderr = child.stderr.take().ok_or_else(|| anyhow!("No stderr"))?;
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| AttemptError::Failed(anyhow!("No stderr")))?;
     let mut stderr_reader = BufReader::new(stderr).lines();
-
-    // Process output lines for progress
-    let mut last_progress: u8 = 0;
-    while let Ok(Some(line)) = stderr_reader.next_line().await {
-        debug!("DDA output: {}", line);
-
-        // Parse progress from DDA output
-        // Expecting format like: "Progress: 45%" or "[45%]" or "45/100"
-        if let Some(progress) = parse_progress(&line) {
-            last_progress = progress;
-            if !progress_callback(progress, Some(line.clone())) {
-                // Cancelled - kill process
-                info!("Job {} cancelled, killing DDA process", job.id);
-                let _ = child.kill().await;
-                return Err(anyhow!("Job cancelled"));
+    // Window rows arrive on stdout
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| AttemptError::Failed(anyhow!("No stdout")))?;
+    let mut stdout_reader = BufReader::new(stdout).lines();
+    let (mut stderr_open, mut stdout_open) = (true, true);
+
+    // Pass on output lines until both streams close
+    while stderr_open || stdout_open {
+        let line = tokio::select! {
+            line = stderr_reader.next_line(), if stderr_open => line,
+            line = stdout_reader.next_line(), if stdout_open => {
+                match line {
+                    Ok(Some(line)) => output(OutputStream::Stdout, line),
+                    _ => stdout_open = false,
+                }
+                continue;
             }
-        } else if line.contains("Processing") || line.contains("Analyzing") {
-            // Status messages
-            if !progress_callback(last_progress, Some(line.clone())) {
-                let _ = child.kill().await;
-                return Err(anyhow!("Job cancelled"));
+            _ = cancel.cancelled() => {
+                info!("Job {} cancelled, killing DDA process group", job.id);
+                stop_process(job, &mut child, &tree, Duration::ZERO).await;
+                return Err(AttemptError::Cancelled);
             }
-        }
+            _ = &mut deadline => {
+                warn!("Job {} timed out, stopping DDA process group", job.id);
+                stop_process(job, &mut child, &tree, policy.kill_grace).await;
+                return Err(AttemptError::TimedOut);
+            }
+        };
+        let Ok(Some(line)) = line else {
+            stderr_open = false;
+            continue;
+        };
+        output(OutputStream::Stderr, line);
     }
 
     // Wait for process to complete
-    let status = child.wait().await?;
+    let status = tokio::select! {
+        status = child.wait() => status.map_err(|e| AttemptError::Failed(e.into()))?,
+        _ = cancel.cancelled() => {
+            info!("Job {} cancelled, killing DDA process group", job.id);
+            stop_process(job, &mut child, &tree, Duration::ZERO).await;
+            return Err(AttemptError::Cancelled);
+        }
+        _ = &mut deadline => {
+            warn!("Job {} timed out, stopping DDA process group", job.id);
+            stop_process(job, &mut child, &tree, policy.kill_grace).await;
+            return Err(AttemptError::TimedOut);
+        }
+    };
 
     if !status.success() {
         let exit_code = status.code().unwrap_or(-1);
-        return Err(anyhow!("DDA exited with code {}", exit_code));
+        return Err(AttemptError::Failed(anyhow!(
+            "DDA exited with code {}",
+            exit_code
+        )));
     }
 
     // Verify output file exists
     if !output_path.exists() {
-        return Err(anyhow!("DDA completed but output file not found"));
+        return Err(AttemptError::Failed(anyhow!(
+            "DDA completed but output file not found"
+        )));
     }
+    Ok(())
+}
 
-    // Clean up input file if requested
-    if job.delete_input_after {
-        match &job.file_source {
-            super::types::FileSource::UploadedTemp(p) => {
-                if let Err(e) = tokio::fs::remove_file(p).await {
-                    error!("Failed to delete temp file {:?}: {}", p, e);
-                } else {
-                    info!("Deleted temp input file for job {}", job.id);
-                }
-            }
-            _ => {} // Don't delete server-side or persistent files
+/// Stop the DDA process tree, asking it to exit first when `grace` is non-zero
+async fn stop_process(job: &DDAJob, child: &mut Child, tree: &ProcessTree, grace: Duration) {
+    if !grace.is_zero() {
+        tree.terminate();
+        if tokio::time::timeout(grace, child.wait()).await.is_ok() {
+            // Reap any helpers that outlived the main process
+            tree.kill();
+            return;
         }
     }
+    tree.kill();
+    if let Err(e) = child.kill().await {
+        warn!("Failed to kill DDA process for job {}: {}", job.id, e);
+    }
+}
 
-    info!("Job {} completed, results at {:?}", job.id, output_path);
+/// Decrypt an encrypted input into the attempt's work directory, keeping
+/// its extension so the binary recognizes the format
+async fn decrypt_input(key: &StorageKey, input_path: &Path, work_dir: &WorkDir) -> Result<PathBuf> {
+    let extension = input_path
+        .extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "edf".to_string());
+    let destination = work_dir.path().join(format!("input.{}", extension));
+    let (key, source, target) = (key.clone(), input_path.to_path_buf(), destination.clone());
+    tokio::task::spawn_blocking(move || key.decrypt_to(&source, &target))
+        .await?
+        .with_context(|| format!("Failed to decrypt input {:?}", input_path))?;
+    Ok(destination)
+}
 
-    Ok(output_path)
+/// Remove whatever a stopped or failed attempt wrote
+async fn remove_partial_output(job: &DDAJob, output_path: &Path) {
+    match tokio::fs::remove_file(output_path).await {
+        Ok(()) => info!("Removed partial output for job {}", job.id),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => error!("Failed to remove partial output {:?}: {}", output_path, e),
+    }
+}
+
+/// Parse a window row from a DDA stdout line
+///
+/// Rows look like `Window: <variant> <window> <value>...`, one Q value per
+/// channel (or pair) in the order of the final result, with `NaN` for
+/// windows without a value.
+fn parse_window_row(line: &str) -> Option<WindowRow> {
+    let mut fields = line.trim().strip_prefix("Window:")?.split_whitespace();
+    let variant = fields.next()?.to_string();
+    let window = fields.next()?.parse().ok()?;
+    let values = fields
+        .map(|value| value.parse::<f64>().ok())
+        .collect::<Option<Vec<f64>>>()?;
+    Some(WindowRow {
+        variant,
+        window,
+        values,
+    })
 }
 
 /// Parse progress percentage from DDA output line
@@ -195,7 +574,11 @@ fn parse_progress(line: &str) -> Option<u8> {
         let after = line[slash_idx + 1..].trim();
 
         // Get last number before slash
-        let num_str: String = before.chars().rev().take_while(|c| c.is_ascii_digit()).collect();
+        let num_str: String = before
+            .chars()
+            .rev()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
         let num_str: String = num_str.chars().rev().collect();
 
         // Get first number after slash
@@ -215,14 +598,192 @@ fn parse_progress(line: &str) -> Option<u8> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_preprocessing_flags() {
+        use super::super::types::{DDAParameters, FileSource, PreprocessingOptions};
+
+        let job = DDAJob::new(
+            "user".to_string(),
+            FileSource::ServerPath("/data/a.edf".into()),
+            "a.edf".to_string(),
+            DDAParameters {
+                preprocessing: PreprocessingOptions {
+                    highpass: Some(0.5),
+                    lowpass: None,
+                    notch: Some(60.0),
+                    detrend: true,
+                },
+                ..Default::default()
+            },
+            false,
+        );
+        let args: Vec<String> = dda_arguments(&job, "/data/a.edf".into(), Path::new("/out.json"))
+            .into_iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        let joined = args.join(" ");
+        assert!(joined.contains("--highpass 0.5 --notch 60"));
+        assert!(joined.contains("--detrend --progress"));
+        assert!(!joined.contains("--lowpass"));
+    }
+
     #[test]
     fn test_parse_progress() {
         assert_eq!(parse_progress("Progress: 45%"), Some(45));
         assert_eq!(parse_progress("Progress: 100%"), Some(100));
+        // Status lines emitted by `ddalab run --progress`
+        assert_eq!(
+            parse_progress("Progress: 37% | Single Timeseries | window 3/8"),
+            Some(37)
+        );
         assert_eq!(parse_progress("[45%]"), Some(45));
         assert_eq!(parse_progress("(45%)"), Some(45));
         assert_eq!(parse_progress("Processing 45/100 channels"), Some(45));
         assert_eq!(parse_progress("50 / 100"), Some(50));
         assert_eq!(parse_progress("No progress here"), None);
     }
+
+    #[test]
+    fn test_parse_window_row() {
+        let row = parse_window_row("Window: ST 3 0.25 -1e-3 NaN").unwrap();
+        assert_eq!(row.variant, "ST");
+        assert_eq!(row.window, 3);
+        assert_eq!(&row.values[..2], &[0.25, -0.001]);
+        assert!(row.values[2].is_nan());
+        assert!(parse_window_row("Window: ST three 0.25").is_none());
+        assert!(parse_window_row("Window: ST 3 0.25 x").is_none());
+        assert!(parse_window_row("Progress: 45%").is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_kills_process_and_removes_partial_output() {
+        use super::super::types::{DDAParameters, FileSource};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ddalab-cancel-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Stand-in binary: writes a partial result, reports progress, then hangs
+        let binary = dir.join("fake-dda.sh");
+        std::fs::write(
+            &binary,
+            "#!/bin/sh\nwhile [ \"$1\" != \"-o\" ]; do shift; done\necho partial > \"$2\"\necho 'Progress: 10%' >&2\nsleep 30\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let input = dir.join("input.edf");
+        std::fs::write(&input, b"").unwrap();
+
+        let job = DDAJob::new(
+            "test_user".to_string(),
+            FileSource::ServerPath(input),
+            "input.edf".to_string(),
+            DDAParameters::default(),
+            false,
+        );
+        let output_path = dir.join(format!("{}.json", job.id));
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        let started = std::time::Instant::now();
+        let executor = LocalExecutor::new(binary);
+        let settings = AnalysisSettings {
+            executor: &executor,
+            policy: &RunPolicy::default(),
+            storage_key: None,
+            output_directory: &dir,
+        };
+        let result = run_dda_analysis(
+            &job,
+            &settings,
+            &cancel,
+            move |progress, _| {
+                if progress >= 10 {
+                    trigger.cancel();
+                }
+            },
+            |_| {},
+        )
+        .await;
+
+        assert!(result.unwrap_err().to_string().contains("cancelled"));
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert!(!output_path.exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hung_attempts_time_out_and_retry() {
+        use super::super::types::{DDAParameters, FileSource};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("ddalab-timeout-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Stand-in binary: records the attempt, writes a partial result, then hangs
+        let attempts = dir.join("attempts");
+        let binary = dir.join("fake-dda.sh");
+        std::fs::write(
+            &binary,
+            format!(
+                "#!/bin/sh\necho run >> '{}'\nwhile [ \"$1\" != \"-o\" ]; do shift; done\necho partial > \"$2\"\nsleep 30\n",
+                attempts.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let input = dir.join("input.edf");
+        std::fs::write(&input, b"").unwrap();
+
+        let job = DDAJob::new(
+            "test_user".to_string(),
+            FileSource::ServerPath(input),
+            "input.edf".to_string(),
+            DDAParameters::default(),
+            false,
+        );
+        let output_path = dir.join(format!("{}.json", job.id));
+        let policy = RunPolicy {
+            timeout: Some(Duration::from_millis(300)),
+            max_retries: 1,
+            backoff: Duration::from_millis(50),
+            kill_grace: Duration::from_millis(200),
+        };
+
+        let mut messages = Vec::new();
+        let started = std::time::Instant::now();
+        let executor = LocalExecutor::new(binary);
+        let settings = AnalysisSettings {
+            executor: &executor,
+            policy: &policy,
+            storage_key: None,
+            output_directory: &dir,
+        };
+        let result = run_dda_analysis(
+            &job,
+            &settings,
+            &CancellationToken::new(),
+            |_, message| {
+                messages.extend(message);
+            },
+            |_| {},
+        )
+        .await;
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(error.contains("after 2 attempts"), "{}", error);
+        assert!(error.contains("timed out"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(
+            std::fs::read_to_string(&attempts).unwrap().lines().count(),
+            2
+        );
+        assert!(messages.iter().any(|m| m.starts_with("Retrying (2/2)")));
+        assert!(!output_path.exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -1,15 +1,30 @@
+pub mod announcements;
 pub mod auth;
 pub mod cli;
 pub mod config;
 pub mod crypto;
 pub mod handlers;
 pub mod jobs;
+pub mod maintenance;
+pub mod metrics;
 pub mod middleware;
+pub mod notifications;
+pub mod relay;
+pub mod retention;
+pub mod scheduler;
 pub mod state;
 pub mod storage;
 pub mod sync;
+pub mod tls;
+pub mod transfer;
+pub mod webhooks;
 
+pub use announcements::{AnnouncementBoard, AnnouncementNotice};
 pub use config::ServerConfig;
 pub use jobs::{JobQueue, JobQueueConfig};
+pub use maintenance::{MaintenanceMode, MaintenanceWindow};
+pub use metrics::ServerMetrics;
 pub use middleware::{audit_middleware, AuditMiddlewareState};
+pub use scheduler::{AnalysisSchedule, CronSchedule, Scheduler};
 pub use state::ServerState;
+pub use transfer::{OffPeakWindow, TransferPolicy};
@@ -1,25 +1,68 @@
 use axum::{
-    http::{header, HeaderValue, Method},
     middleware,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use clap::Parser;
 use ddalab_server::{
     audit_middleware,
-    auth::auth_middleware,
+    auth::{api_token_scope_middleware, auth_middleware, require_mfa_middleware},
+    middleware::{compression_layer, origin_policy_middleware, OriginPolicies},
     cli::{Cli, Commands},
     config::ServerConfig,
+    tls::TlsConfig,
     handlers::{
-        add_team_member, cancel_job, create_share, create_team, delete_team, download_job_results,
-        get_job_status, get_queue_stats, get_share, get_team, health_check, job_progress_stream,
-        key_exchange, list_institution_teams, list_jobs, list_my_teams, list_server_files,
-        list_user_shares, login, logout, remove_team_member, revoke_share, server_info,
-        submit_server_file_job, upload_and_submit_job, validate_session,
+        add_organization_member, add_team_member, authenticate_passkey, cancel_job,
+        create_announcement, delete_announcement, list_all_announcements, list_announcements,
+        load_announcements, mark_announcement_read, create_schedule, create_share,
+        create_api_token, create_organization, create_team, delete_organization, delete_passkey,
+        delete_schedule, delete_team, delete_team_preset, download_job_results,
+        egress_report, export_audit_log,
+        claim_run, finish_run, get_run_input, report_run_output, upload_run_result,
+        delete_team_retention, get_my_retention, get_team_retention,
+        get_my_notifications, set_my_notifications,
+        get_my_presence, get_team_presence,
+        get_share_keys, set_my_share_key,
+        decline_relay, download_via_relay, upload_relay,
+        create_preset, delete_preset, get_preset, list_presets, update_preset,
+        activate_user, force_password_reset, get_user, list_sessions, list_users, revoke_session,
+        suspend_user, usage_report,
+        create_annotation, delete_annotation, get_annotation, list_annotations, save_annotation,
+        create_dataset, delete_dataset, delete_dataset_grant, list_datasets, save_dataset_grant,
+        update_dataset,
+        list_retention_purges, set_team_retention,
+        get_job_status, get_maintenance, get_queue_stats, get_share, get_team, health_check, prometheus_metrics,
+        get_job_pipeline, get_job_thumbnail, get_my_usage, get_organization,
+        job_progress_stream, job_result_stream,
+        key_exchange, list_api_tokens, list_institution_teams, list_jobs, list_my_presets, list_my_teams,
+        list_organization_teams, list_organizations, list_schedules, list_server_files, search,
+        list_team_presets,
+        list_user_shares, load_api_tokens, login, logout, mfa_status, passkey_authentication_options,
+        passkey_registration_options, recover_with_code, refresh_session, regenerate_recovery_codes,
+        register_passkey, remove_organization_member, remove_team_member, revoke_api_token, revoke_share, run_schedule_now,
+        save_team_preset, server_info,
+        set_job_priority,
+        set_maintenance, submit_server_file_job, upload_and_submit_job, validate_session,
+        create_upload, delete_upload, finalize_upload, get_upload, patch_upload,
+        create_webhook, delete_webhook, list_webhook_deliveries, list_webhooks,
     },
+    jobs::WorkerNode,
     state::ServerState,
-    storage::{AuditStore, PostgresAuditStore, PostgresShareStore, PostgresUserStore, UserStore},
-    sync::{handle_websocket, hash_psk, BrokerDiscovery},
+    storage::{
+        AuditStore, PostgresAnnouncementStore, PostgresApiTokenStore, PostgresAuditStore,
+        PostgresAnnotationStore, PostgresDatasetStore, PostgresEgressStore,
+        PostgresMfaStore, PostgresOfflineMessageStore, PostgresPresetStore, PostgresRetentionStore,
+        PostgresShareKeyStore,
+        PostgresShareStore, PostgresWebhookStore,
+        PostgresUserStore, UserStore,
+    },
+    sync::{
+        handle_federation_link, handle_websocket, hash_psk, spawn_federation_links,
+        spawn_offline_message_purger, BrokerDiscovery,
+    },
+    notifications::spawn_job_notifier,
+    retention::{spawn_audit_pruner, spawn_retention_sweeper, RetentionConfig},
+    webhooks::spawn_webhook_dispatcher,
     AuditMiddlewareState,
 };
 use sqlx::postgres::PgPoolOptions;
@@ -27,7 +70,6 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time;
-use tower_http::cors::CorsLayer;
 use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::trace::TraceLayer;
 use tracing::{info, warn};
@@ -49,6 +91,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Parse CLI arguments
     let cli = Cli::parse();
 
+    // Worker nodes need neither the server configuration nor the database
+    if let Some(Commands::WorkerNode { server, run, name }) = &cli.command {
+        let node = WorkerNode::from_env(server, name.clone())?;
+        return match run {
+            Some(run_id) => node.run_once(*run_id).await,
+            None => node.serve().await,
+        }
+        .map_err(|e| e.into());
+    }
+
     // Load configuration
     let config = ServerConfig::from_env()?;
 
@@ -68,6 +120,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let share_store = PostgresShareStore::new(pool.clone());
     share_store.initialize().await?;
 
+    let egress_store = PostgresEgressStore::new(pool.clone());
+    egress_store.initialize().await?;
+
+    let mfa_store = PostgresMfaStore::new(pool.clone());
+    mfa_store.initialize().await?;
+
+    let api_token_store = PostgresApiTokenStore::new(pool.clone());
+    api_token_store.initialize().await?;
+
+    let announcement_store = PostgresAnnouncementStore::new(pool.clone());
+    announcement_store.initialize().await?;
+
+    let webhook_store = PostgresWebhookStore::new(pool.clone());
+    webhook_store.initialize().await?;
+
+    let retention_store = PostgresRetentionStore::new(pool.clone());
+    retention_store.initialize().await?;
+
+    let preset_store = PostgresPresetStore::new(pool.clone());
+    preset_store.initialize().await?;
+
+    let dataset_store = PostgresDatasetStore::new(pool.clone());
+    dataset_store.initialize().await?;
+
+    let annotation_store = PostgresAnnotationStore::new(pool.clone());
+    annotation_store.initialize().await?;
+
+    let offline_message_store = PostgresOfflineMessageStore::new(pool.clone());
+    offline_message_store.initialize().await?;
+
+    let share_key_store = PostgresShareKeyStore::new(pool.clone());
+    share_key_store.initialize().await?;
+
     // Handle CLI commands
     match cli.command {
         Some(Commands::User(cmd)) => {
@@ -109,6 +194,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             return Ok(());
         }
+        Some(Commands::WorkerNode { .. }) => unreachable!("handled before connecting"),
         Some(Commands::Serve) | None => {
             // Continue to run server
         }
@@ -122,10 +208,87 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("   Institution: {}", config.institution_name);
     info!("   Authentication required: {}", config.require_auth);
     info!("   Encryption enabled: {}", config.enable_encryption);
+    match &config.webauthn {
+        Some(webauthn) => info!("   Passkeys: {} ({})", webauthn.rp_id, webauthn.origins.join(", ")),
+        None => info!("   Passkeys: disabled (set WEBAUTHN_RP_ID to enable)"),
+    }
+    match &config.smtp {
+        Some(smtp) => info!("   Email notifications: {}:{} as {}", smtp.host, smtp.port, smtp.from),
+        None => info!("   Email notifications: disabled (set SMTP_HOST to enable)"),
+    }
     info!("   mDNS discovery: {}", config.enable_mdns);
-    info!("   Max concurrent jobs: {}", config.max_concurrent_jobs);
+    match &config.remote_execution {
+        Some(remote) => match &remote.kubernetes {
+            Some(kubernetes) => info!(
+                "   DDA executor: Kubernetes Jobs in {} ({})",
+                kubernetes.namespace, kubernetes.image
+            ),
+            None => info!("   DDA executor: worker nodes"),
+        },
+        None => info!("   DDA executor: local"),
+    }
+    if config.worker_pools.is_empty() {
+        info!("   Max concurrent jobs: {}", config.max_concurrent_jobs);
+    }
+    for pool in &config.worker_pools {
+        info!(
+            "   Worker pool {}: {} slots, channels <= {:?}, seconds <= {:?}",
+            pool.name, pool.max_concurrent, pool.max_channels, pool.max_estimated_seconds
+        );
+    }
+    info!(
+        "   Job resource limits: memory {:?} bytes, {:?} CPUs",
+        config.resource_limits.memory_bytes, config.resource_limits.cpus
+    );
     info!("   Job output directory: {:?}", config.job_output_directory);
     info!("   Upload directory: {:?}", config.upload_directory);
+    match &config.storage_encryption {
+        Some(_) => info!("   Encryption at rest: enabled"),
+        None => info!("   Encryption at rest: disabled (set STORAGE_ENCRYPTION_KEY to enable)"),
+    }
+    match &config.tls {
+        Some(TlsConfig::Files { cert_path, .. }) => {
+            info!("   TLS: {}", cert_path.display())
+        }
+        Some(TlsConfig::Acme(acme)) => info!("   TLS: ACME for {}", acme.domains.join(", ")),
+        None => info!("   TLS: disabled (set TLS_CERT_PATH and TLS_KEY_PATH, or ACME_DOMAINS)"),
+    }
+    match &config.retention {
+        Some(policy) => info!(
+            "   Retention: {} days, {} days grace",
+            policy.retain_days, policy.grace_days
+        ),
+        None => info!("   Retention: keep forever unless a team sets a policy"),
+    }
+    match config.audit_retention_days {
+        Some(days) => info!("   Audit log retention: {} days", days),
+        None => info!("   Audit log retention: keep forever"),
+    }
+    match &config.federation {
+        Some(federation) => info!(
+            "   Federation: namespace {}, {} peers",
+            federation.namespace,
+            federation.peers.len()
+        ),
+        None => info!("   Federation: disabled (set FEDERATION_NAMESPACE to enable)"),
+    }
+    match &config.relay {
+        Some(relay) => info!(
+            "   Share relay: up to {} bytes per transfer, {} at once{}",
+            relay.max_bytes,
+            relay.max_concurrent,
+            relay
+                .daily_bytes_per_user
+                .map(|bytes| format!(", {} bytes per user per day", bytes))
+                .unwrap_or_default()
+        ),
+        None => info!("   Share relay: disabled (set RELAY_ENABLED to enable)"),
+    }
+    info!(
+        "   Offline message queue: {} per user for {} hours",
+        config.offline_queue.limit,
+        config.offline_queue.ttl.as_secs() / 3600
+    );
     info!("✅ Database connected and schema initialized");
 
     // Create server state
@@ -135,6 +298,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Arc::new(user_store),
         pool.clone(),
     ));
+    let api_tokens = load_api_tokens(&state).await?;
+    info!("   Access tokens: {} active", api_tokens);
+    let announcements = load_announcements(&state).await?;
+    info!("   Announcements: {} unexpired", announcements);
+    spawn_webhook_dispatcher(state.job_queue.clone(), Arc::new(webhook_store));
+    if let Some(notifier) = state.notifier.clone() {
+        spawn_job_notifier(state.job_queue.clone(), state.user_store.clone(), notifier);
+    }
+    spawn_retention_sweeper(
+        state.job_queue.clone(),
+        Arc::new(retention_store),
+        RetentionConfig {
+            default_policy: config.retention,
+            archive_hook: config.retention_archive.clone(),
+            directories: vec![
+                config.job_output_directory.clone(),
+                config.upload_directory.clone(),
+                state.job_queue.blobs().directory().to_path_buf(),
+            ],
+        },
+    );
+    if let Some(days) = config.audit_retention_days {
+        spawn_audit_pruner(Arc::new(PostgresAuditStore::new(pool.clone())), days);
+    }
+    spawn_offline_message_purger(Arc::new(offline_message_store));
+    state.metrics.track_jobs(state.job_queue.clone());
 
     // Create audit middleware state
     let audit_middleware_state = AuditMiddlewareState {
@@ -188,6 +377,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         });
     }
 
+    // Spawn background task to submit due recurring analyses
+    {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(30));
+            loop {
+                interval.tick().await;
+                state.scheduler.run_due(&state).await;
+            }
+        });
+    }
+
     // Create WebSocket sync state with authentication config
     let password_hash = hash_psk(&config.broker_password);
     let sync_state = ddalab_server::sync::websocket::SyncState {
@@ -202,28 +403,108 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             None
         },
         require_auth: config.require_auth,
+        maintenance: state.maintenance.clone(),
+        announcements: state.announcements.clone(),
+        metrics: state.metrics.clone(),
+        mailbox: state.mailbox.clone(),
+        user_store: state.user_store.clone(),
+        db_pool: pool.clone(),
+        federation: state.federation.clone(),
+        transfer_policy: config.transfer_policy,
     };
 
     // Build router
     let public_routes = Router::new()
         .route("/health", get(health_check))
         .route("/info", get(server_info))
+        .route("/metrics", get(prometheus_metrics))
         .route("/auth/login", post(login))
+        .route("/auth/refresh", post(refresh_session))
         .route("/auth/key-exchange", post(key_exchange));
 
     let protected_routes = Router::new()
         .route("/auth/logout", post(logout))
         .route("/auth/session", get(validate_session))
+        // Second factor
+        .route("/auth/mfa", get(mfa_status))
+        .route(
+            "/auth/mfa/passkeys/register/options",
+            post(passkey_registration_options),
+        )
+        .route("/auth/mfa/passkeys/register", post(register_passkey))
+        .route(
+            "/auth/mfa/passkeys/authenticate/options",
+            post(passkey_authentication_options),
+        )
+        .route("/auth/mfa/passkeys/authenticate", post(authenticate_passkey))
+        .route("/auth/mfa/passkeys/{passkey_id}", delete(delete_passkey))
+        .route("/auth/mfa/recovery-codes", post(regenerate_recovery_codes))
+        .route("/auth/mfa/recover", post(recover_with_code))
+        // Announcements
+        .route("/api/announcements", get(list_announcements))
+        .route(
+            "/api/announcements/{announcement_id}/read",
+            post(mark_announcement_read),
+        )
+        // Job lifecycle webhooks
+        .route("/api/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/api/webhooks/{webhook_id}", delete(delete_webhook))
+        .route(
+            "/api/webhooks/{webhook_id}/deliveries",
+            get(list_webhook_deliveries),
+        )
+        // Personal access tokens
+        .route("/api/tokens", get(list_api_tokens).post(create_api_token))
+        .route("/api/tokens/{token_id}", delete(revoke_api_token))
         .route("/api/shares", post(create_share))
-        .route("/api/shares/{token}", get(get_share))
         .route("/api/shares/{token}", delete(revoke_share))
         .route("/api/shares/user/{user_id}", get(list_user_shares))
+        .route("/api/share-keys", get(get_share_keys))
+        .route("/api/users/me/share-key", put(set_my_share_key))
+        // Organization management routes
+        .route(
+            "/api/organizations",
+            get(list_organizations).post(create_organization),
+        )
+        .route(
+            "/api/organizations/{organization_id}",
+            get(get_organization).delete(delete_organization),
+        )
+        .route(
+            "/api/organizations/{organization_id}/members",
+            post(add_organization_member),
+        )
+        .route(
+            "/api/organizations/{organization_id}/members/{member_id}",
+            delete(remove_organization_member),
+        )
+        .route(
+            "/api/organizations/{organization_id}/teams",
+            get(list_organization_teams),
+        )
         // Team management routes
         .route("/api/teams", post(create_team))
         .route("/api/teams/me", get(list_my_teams))
+        .route("/api/teams/me/presets", get(list_my_presets))
+        .route("/api/teams/{team_id}/presence", get(get_team_presence))
+        .route("/api/presence", get(get_my_presence))
         .route("/api/teams/{team_id}", get(get_team))
         .route("/api/teams/{team_id}", delete(delete_team))
         .route("/api/teams/{team_id}/members", post(add_team_member))
+        .route(
+            "/api/teams/{team_id}/presets",
+            get(list_team_presets).put(save_team_preset),
+        )
+        .route(
+            "/api/teams/{team_id}/presets/{preset_id}",
+            delete(delete_team_preset),
+        )
+        .route(
+            "/api/teams/{team_id}/retention",
+            get(get_team_retention)
+                .put(set_team_retention)
+                .delete(delete_team_retention),
+        )
         .route(
             "/api/teams/{team_id}/members/{member_id}",
             delete(remove_team_member),
@@ -232,43 +513,134 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "/api/teams/institution/{institution_id}",
             get(list_institution_teams),
         )
+        // Annotation sync
+        .route("/api/annotations", get(list_annotations).post(create_annotation))
+        .route(
+            "/api/annotations/{annotation_id}",
+            get(get_annotation).put(save_annotation).delete(delete_annotation),
+        )
+        // Personal parameter presets
+        .route("/api/presets", get(list_presets).post(create_preset))
+        .route(
+            "/api/presets/{preset_id}",
+            get(get_preset).put(update_preset).delete(delete_preset),
+        )
         // Job management routes
         .route("/api/jobs", get(list_jobs))
         .route("/api/jobs/submit", post(submit_server_file_job))
         // Note: /api/jobs/upload is in upload_routes with larger body limit
         .route("/api/jobs/stats", get(get_queue_stats))
+        .route("/api/users/me/usage", get(get_my_usage))
+        .route("/api/users/me/retention", get(get_my_retention))
+        .route(
+            "/api/users/me/notifications",
+            get(get_my_notifications).put(set_my_notifications),
+        )
+        .route("/api/search", get(search))
         .route("/api/jobs/progress", get(job_progress_stream))
         .route("/api/jobs/{job_id}", get(get_job_status))
         .route("/api/jobs/{job_id}/cancel", post(cancel_job))
-        .route("/api/jobs/{job_id}/download", get(download_job_results))
+        .route("/api/jobs/{job_id}/pipeline", get(get_job_pipeline))
+        .route("/api/jobs/{job_id}/thumbnail", get(get_job_thumbnail))
+        .route("/api/jobs/{job_id}/results/stream", get(job_result_stream))
         .route("/api/files", get(list_server_files))
         .layer(middleware::from_fn_with_state(
             state.auth_state.clone(),
             auth_middleware,
         ));
 
+    // Admin and data egress routes: users flagged to require MFA need a
+    // session that completed a second factor (the last layer runs first)
+    let sensitive_routes = Router::new()
+        .route("/api/shares/{token}", get(get_share))
+        .route("/api/shares/{token}/relay", get(download_via_relay))
+        .route("/api/jobs/{job_id}/download", get(download_job_results))
+        // User management
+        .route("/api/admin/users", get(list_users))
+        .route("/api/admin/users/{user_id}", get(get_user))
+        .route("/api/admin/users/{user_id}/suspend", post(suspend_user))
+        .route("/api/admin/users/{user_id}/activate", post(activate_user))
+        .route(
+            "/api/admin/users/{user_id}/reset-password",
+            post(force_password_reset),
+        )
+        .route("/api/admin/sessions", get(list_sessions))
+        .route("/api/admin/sessions/{session_id}", delete(revoke_session))
+        .route("/api/admin/usage", get(usage_report))
+        // Compliance reporting
+        .route("/api/admin/egress", get(egress_report))
+        .route("/api/audit/export", get(export_audit_log))
+        .route("/api/admin/retention/purges", get(list_retention_purges))
+        // Scheduled downtime
+        .route(
+            "/api/admin/maintenance",
+            get(get_maintenance).post(set_maintenance),
+        )
+        // Announcements
+        .route(
+            "/api/admin/announcements",
+            get(list_all_announcements).post(create_announcement),
+        )
+        .route(
+            "/api/admin/announcements/{announcement_id}",
+            delete(delete_announcement),
+        )
+        // Recurring analyses
+        .route(
+            "/api/admin/schedules",
+            get(list_schedules).post(create_schedule),
+        )
+        .route("/api/admin/schedules/{schedule_id}", delete(delete_schedule))
+        .route(
+            "/api/admin/schedules/{schedule_id}/run",
+            post(run_schedule_now),
+        )
+        // Queue priority overrides
+        .route("/api/admin/jobs/{job_id}/priority", post(set_job_priority))
+        // Dataset access control
+        .route(
+            "/api/admin/datasets",
+            get(list_datasets).post(create_dataset),
+        )
+        .route(
+            "/api/admin/datasets/{dataset_id}",
+            put(update_dataset).delete(delete_dataset),
+        )
+        .route(
+            "/api/admin/datasets/{dataset_id}/grants",
+            put(save_dataset_grant),
+        )
+        .route(
+            "/api/admin/datasets/{dataset_id}/grants/{grant_id}",
+            delete(delete_dataset_grant),
+        )
+        .layer(middleware::from_fn_with_state(
+            state.auth_state.clone(),
+            require_mfa_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.auth_state.clone(),
+            auth_middleware,
+        ));
+
     let ws_routes = Router::new()
         .route("/ws", get(handle_websocket))
-        .with_state(sync_state);
-
-    // CORS configuration - configurable via CORS_ORIGINS env var
-    let cors_origins: Vec<HeaderValue> = config
-        .cors_origins
-        .iter()
-        .filter_map(|origin| origin.parse::<HeaderValue>().ok())
-        .collect();
-    info!("   CORS origins: {:?}", config.cors_origins);
-    let cors = CorsLayer::new()
-        .allow_origin(cors_origins)
-        .allow_methods([
-            Method::GET,
-            Method::POST,
-            Method::PUT,
-            Method::DELETE,
-            Method::OPTIONS,
-        ])
-        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION, header::ACCEPT])
-        .allow_credentials(true);
+        .route("/federation", get(handle_federation_link))
+        .with_state(sync_state.clone());
+    spawn_federation_links(sync_state);
+
+    // CORS and embedding rules - configurable via CORS_ORIGINS and ORIGIN_POLICIES
+    for policy in &config.origin_policies {
+        info!(
+            "   Origin {}: {:?}{}{}",
+            policy.origin,
+            policy.scope,
+            if policy.embed { ", embeddable" } else { "" },
+            if policy.credentials { "" } else { ", credentialless" }
+        );
+    }
+    let origin_policies = OriginPolicies::new(config.origin_policies.clone());
+    let cors = origin_policies.cors_layer();
 
     // SECURITY: Limit request body size to prevent DoS
     const MAX_API_BODY_SIZE: usize = 1024 * 1024; // 1MB for regular API requests
@@ -277,6 +649,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create upload route with larger body limit (separate from other routes)
     let upload_routes = Router::new()
         .route("/api/jobs/upload", post(upload_and_submit_job))
+        // Resumable uploads; chunks go through the same limit
+        .route("/api/uploads", post(create_upload))
+        .route(
+            "/api/uploads/{upload_id}",
+            get(get_upload).patch(patch_upload).delete(delete_upload),
+        )
+        .route("/api/uploads/{upload_id}/finalize", post(finalize_upload))
         .layer(RequestBodyLimitLayer::new(max_upload_size))
         .layer(middleware::from_fn_with_state(
             state.auth_state.clone(),
@@ -284,25 +663,85 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         ))
         .with_state(state.clone());
 
-    let app = Router::new()
+    // Worker nodes authenticate with the executor token, and send results
+    // as large as uploads
+    let executor_routes = Router::new()
+        .route("/api/executor/claim", post(claim_run))
+        .route("/api/executor/runs/{run_id}/input", get(get_run_input))
+        .route("/api/executor/runs/{run_id}/output", post(report_run_output))
+        .route("/api/executor/runs/{run_id}/result", put(upload_run_result))
+        .route("/api/executor/runs/{run_id}/finish", post(finish_run))
+        .layer(RequestBodyLimitLayer::new(max_upload_size))
+        .with_state(state.clone());
+
+    // Owners' relayed uploads may be as large as the relay allows
+    let relay_body_limit = config
+        .relay
+        .as_ref()
+        .map_or(max_upload_size, |relay| relay.max_bytes as usize);
+    let relay_routes = Router::new()
+        .route(
+            "/api/relay/{relay_id}",
+            put(upload_relay).delete(decline_relay),
+        )
+        .layer(RequestBodyLimitLayer::new(relay_body_limit))
+        .layer(middleware::from_fn_with_state(
+            state.auth_state.clone(),
+            auth_middleware,
+        ))
+        .with_state(state.clone());
+
+    info!(
+        "   Compression: {} (min {} bytes)",
+        if config.enable_compression { "gzip, br" } else { "disabled" },
+        config.compression_min_size
+    );
+    let compression =
+        compression_layer(config.enable_compression, config.compression_min_size);
+
+    // The API limit wraps only the regular routes; around the upload routes
+    // it would cap uploads at 1MB as well
+    let api_routes = Router::new()
         .merge(public_routes)
-        .merge(upload_routes) // Upload routes first with larger limit
         .merge(protected_routes)
+        .merge(sensitive_routes)
         .merge(ws_routes)
+        .layer(RequestBodyLimitLayer::new(MAX_API_BODY_SIZE));
+
+    let app = Router::new()
+        .merge(upload_routes) // Upload routes first with larger limit
+        .merge(executor_routes)
+        .merge(relay_routes)
+        .merge(api_routes)
+        // Access tokens stay within their scope on every route, whether or
+        // not authentication is required
+        .layer(middleware::from_fn_with_state(
+            state.auth_state.clone(),
+            api_token_scope_middleware,
+        ))
         .layer(middleware::from_fn_with_state(
             audit_middleware_state,
             audit_middleware,
         ))
-        .layer(RequestBodyLimitLayer::new(MAX_API_BODY_SIZE))
+        .layer(compression)
         .layer(TraceLayer::new_for_http())
         .layer(cors)
+        .layer(middleware::from_fn_with_state(
+            origin_policies,
+            origin_policy_middleware,
+        ))
         .with_state(state.clone());
 
     // Start server
     let addr: SocketAddr = config.bind_address().parse()?;
-    info!("🎧 Listening on http://{}", addr);
-    info!("📡 WebSocket endpoint: ws://{}/ws", addr);
-    info!("🔑 Health endpoint: http://{}/health", addr);
+    let tls = match &config.tls {
+        Some(tls) => Some(ddalab_server::tls::rustls_config(tls, addr).await?),
+        None => None,
+    };
+    let (http, ws) = if tls.is_some() { ("https", "wss") } else { ("http", "ws") };
+    info!("🎧 Listening on {}://{}", http, addr);
+    info!("📡 WebSocket endpoint: {}://{}/ws", ws, addr);
+    info!("🔑 Health endpoint: {}://{}/health", http, addr);
 
     // Initialize mDNS discovery
     let mut discovery: Option<BrokerDiscovery> = None;
@@ -315,6 +754,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     &config.institution_name,
                     &password_hash,
                     VERSION,
+                    tls.is_some(),
                 ) {
                     Ok(_) => {
                         info!("🔍 mDNS discovery announcement started");
@@ -336,8 +776,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Run server
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls {
+        Some(tls) => axum_server::bind_rustls(addr, tls).serve(app).await?,
+        None => {
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            axum::serve(listener, app).await?;
+        }
+    }
 
     // Clean up mDNS announcement on shutdown
     if let Some(disc) = discovery {
@@ -0,0 +1,139 @@
+//! Server maintenance mode
+//!
+//! While a maintenance window is active, new job submissions are rejected,
+//! jobs already accepted by the queue run to completion, and connected sync
+//! clients are notified over their WebSocket.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+/// Announced maintenance window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    /// Message shown to users (e.g. the reason for the downtime)
+    pub message: String,
+    pub started_at: DateTime<Utc>,
+    /// Expected end of the downtime, if announced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+/// Shared maintenance state; cheap to clone
+#[derive(Clone)]
+pub struct MaintenanceMode {
+    window: Arc<RwLock<Option<MaintenanceWindow>>>,
+    /// Every change is broadcast; `None` means maintenance ended
+    notices: broadcast::Sender<Option<MaintenanceWindow>>,
+}
+
+impl Default for MaintenanceMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MaintenanceMode {
+    pub fn new() -> Self {
+        let (notices, _) = broadcast::channel(16);
+        Self {
+            window: Arc::new(RwLock::new(None)),
+            notices,
+        }
+    }
+
+    /// Current maintenance window, if maintenance is active
+    pub fn current(&self) -> Option<MaintenanceWindow> {
+        self.window.read().unwrap().clone()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.window.read().unwrap().is_some()
+    }
+
+    /// Enter maintenance (or update the active window) and notify subscribers
+    pub fn enable(&self, message: String, ends_at: Option<DateTime<Utc>>) -> MaintenanceWindow {
+        let window = {
+            let mut guard = self.window.write().unwrap();
+            let started_at = guard
+                .as_ref()
+                .map(|w| w.started_at)
+                .unwrap_or_else(Utc::now);
+            let window = MaintenanceWindow {
+                message,
+                started_at,
+                ends_at,
+            };
+            *guard = Some(window.clone());
+            window
+        };
+        let _ = self.notices.send(Some(window.clone()));
+        window
+    }
+
+    /// Leave maintenance; returns false if it was not active
+    pub fn disable(&self) -> bool {
+        let was_active = self.window.write().unwrap().take().is_some();
+        if was_active {
+            let _ = self.notices.send(None);
+        }
+        was_active
+    }
+
+    /// Subscribe to maintenance changes
+    pub fn subscribe(&self) -> broadcast::Receiver<Option<MaintenanceWindow>> {
+        self.notices.subscribe()
+    }
+
+    /// User-facing reason to reject a new job, if maintenance is active
+    pub fn rejection_message(&self) -> Option<String> {
+        self.current().map(|window| {
+            let until = window
+                .ends_at
+                .map(|end| format!(" until {}", end.format("%Y-%m-%d %H:%M UTC")))
+                .unwrap_or_default();
+            format!(
+                "Server is in scheduled maintenance{}: {}. New jobs are not accepted.",
+                until, window.message
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_enable_and_disable_broadcast_notices() {
+        let mode = MaintenanceMode::new();
+        let mut notices = mode.subscribe();
+        assert!(!mode.is_active());
+        assert!(mode.rejection_message().is_none());
+
+        let ends_at = Utc.with_ymd_and_hms(2026, 3, 1, 18, 0, 0).unwrap();
+        let window = mode.enable("Storage upgrade".to_string(), Some(ends_at));
+        assert_eq!(notices.try_recv().unwrap(), Some(window));
+        assert_eq!(
+            mode.rejection_message().unwrap(),
+            "Server is in scheduled maintenance until 2026-03-01 18:00 UTC: Storage upgrade. \
+             New jobs are not accepted."
+        );
+
+        assert!(mode.disable());
+        assert_eq!(notices.try_recv().unwrap(), None);
+        assert!(!mode.disable());
+        assert!(notices.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_updating_window_keeps_start_time() {
+        let mode = MaintenanceMode::new();
+        let first = mode.enable("Upgrade".to_string(), None);
+        let second = mode.enable("Upgrade (extended)".to_string(), None);
+        assert_eq!(first.started_at, second.started_at);
+        assert_eq!(mode.current().unwrap().message, "Upgrade (extended)");
+    }
+}
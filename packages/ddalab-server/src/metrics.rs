@@ -0,0 +1,385 @@
+//! Prometheus metrics
+//!
+//! Counters and histograms are updated where the events happen; queue
+//! gauges are read from the job queue when `/metrics` is scraped. The
+//! output follows the Prometheus text exposition format.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::jobs::{JobPriority, JobQueue, JobStatus, QueueStats};
+
+/// Upper bounds of the job duration buckets, in seconds
+const DURATION_BUCKETS: [f64; 11] = [
+    1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0, 7200.0,
+];
+
+/// Upper bounds of the upload size buckets, in bytes
+const UPLOAD_BUCKETS: [f64; 7] = [1e6, 1e7, 1e8, 5e8, 1e9, 5e9, 1e10];
+
+/// Why a request failed to authenticate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFailure {
+    /// No credentials were sent
+    MissingCredentials,
+    /// Unknown, expired or revoked session or access token
+    InvalidSession,
+    /// Wrong email or password at login
+    InvalidPassword,
+    /// Rejected because of too many recent failures
+    RateLimited,
+}
+
+impl AuthFailure {
+    const ALL: [AuthFailure; 4] = [
+        AuthFailure::MissingCredentials,
+        AuthFailure::InvalidSession,
+        AuthFailure::InvalidPassword,
+        AuthFailure::RateLimited,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthFailure::MissingCredentials => "missing_credentials",
+            AuthFailure::InvalidSession => "invalid_session",
+            AuthFailure::InvalidPassword => "invalid_password",
+            AuthFailure::RateLimited => "rate_limited",
+        }
+    }
+}
+
+/// Cumulative histogram with fixed buckets
+#[derive(Debug)]
+struct Histogram {
+    bounds: &'static [f64],
+    data: Mutex<HistogramData>,
+}
+
+#[derive(Debug, Default)]
+struct HistogramData {
+    /// Observations per bucket, not cumulative; the last one is `+Inf`
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            data: Mutex::new(HistogramData {
+                counts: vec![0; bounds.len() + 1],
+                sum: 0.0,
+            }),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|&bound| value <= bound)
+            .unwrap_or(self.bounds.len());
+        let mut data = self.data.lock().unwrap();
+        data.counts[bucket] += 1;
+        data.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let data = self.data.lock().unwrap();
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&data.counts) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, cumulative
+            );
+        }
+        cumulative += data.counts[self.bounds.len()];
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, separator, cumulative
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, data.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, cumulative);
+    }
+}
+
+struct Inner {
+    completed_durations: Histogram,
+    failed_durations: Histogram,
+    upload_sizes: Histogram,
+    auth_failures: [AtomicU64; AuthFailure::ALL.len()],
+    websocket_connections: AtomicU64,
+    relayed_transfers: AtomicU64,
+    relayed_bytes: AtomicU64,
+}
+
+/// Server-wide metrics; cheap to clone
+#[derive(Clone)]
+pub struct ServerMetrics {
+    inner: Arc<Inner>,
+}
+
+impl Default for ServerMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerMetrics {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                completed_durations: Histogram::new(&DURATION_BUCKETS),
+                failed_durations: Histogram::new(&DURATION_BUCKETS),
+                upload_sizes: Histogram::new(&UPLOAD_BUCKETS),
+                auth_failures: Default::default(),
+                websocket_connections: AtomicU64::new(0),
+                relayed_transfers: AtomicU64::new(0),
+                relayed_bytes: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Record how long a finished job ran
+    pub fn observe_job_duration(&self, status: JobStatus, seconds: f64) {
+        match status {
+            JobStatus::Completed => self.inner.completed_durations.observe(seconds),
+            JobStatus::Failed => self.inner.failed_durations.observe(seconds),
+            _ => {}
+        }
+    }
+
+    /// Record the size of an uploaded file
+    pub fn observe_upload(&self, bytes: u64) {
+        self.inner.upload_sizes.observe(bytes as f64);
+    }
+
+    /// Count a share download relayed through the broker
+    pub fn observe_relay(&self, bytes: u64) {
+        self.inner.relayed_transfers.fetch_add(1, Ordering::Relaxed);
+        self.inner.relayed_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Count a failed authentication
+    pub fn auth_failure(&self, reason: AuthFailure) {
+        let index = AuthFailure::ALL
+            .iter()
+            .position(|r| *r == reason)
+            .expect("every reason is listed");
+        self.inner.auth_failures[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count an open WebSocket connection until the guard is dropped
+    pub fn websocket_connected(&self) -> ConnectionGuard {
+        self.inner
+            .websocket_connections
+            .fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard {
+            metrics: self.clone(),
+        }
+    }
+
+    /// Record the run time of every job that finishes on `queue`
+    pub fn track_jobs(&self, queue: Arc<JobQueue>) {
+        let metrics = self.clone();
+        let mut events = queue.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let progress = match events.recv().await {
+                    Ok(progress) => progress,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                if !matches!(progress.status, JobStatus::Completed | JobStatus::Failed) {
+                    continue;
+                }
+                let Some(job) = queue.get_job(progress.job_id).await else {
+                    continue;
+                };
+                if let (Some(started), Some(completed)) = (job.started_at, job.completed_at) {
+                    let seconds = (completed - started).num_milliseconds().max(0) as f64 / 1000.0;
+                    metrics.observe_job_duration(progress.status, seconds);
+                }
+            }
+        });
+    }
+
+    /// Render every metric in the Prometheus text format
+    pub fn render(&self, queue: &QueueStats) -> String {
+        let mut out = String::new();
+
+        gauge_header(
+            &mut out,
+            "ddalab_queue_pending_jobs",
+            "Jobs waiting in the queue",
+        );
+        for priority in [
+            JobPriority::Interactive,
+            JobPriority::Normal,
+            JobPriority::Batch,
+        ] {
+            let count = queue
+                .pending_by_priority
+                .get(&priority)
+                .copied()
+                .unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "ddalab_queue_pending_jobs{{priority=\"{}\"}} {}",
+                priority, count
+            );
+        }
+        gauge(
+            &mut out,
+            "ddalab_running_jobs",
+            "Jobs currently running",
+            queue.running,
+        );
+        gauge(
+            &mut out,
+            "ddalab_job_slots",
+            "Jobs that may run at once",
+            queue.max_concurrent,
+        );
+        gauge(
+            &mut out,
+            "ddalab_available_job_slots",
+            "Job slots currently free",
+            queue.available_slots,
+        );
+
+        let name = "ddalab_job_duration_seconds";
+        let _ = writeln!(out, "# HELP {} Run time of finished jobs", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        self.inner
+            .completed_durations
+            .render(&mut out, name, "status=\"completed\"");
+        self.inner
+            .failed_durations
+            .render(&mut out, name, "status=\"failed\"");
+
+        let name = "ddalab_upload_size_bytes";
+        let _ = writeln!(out, "# HELP {} Size of uploaded files", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        self.inner.upload_sizes.render(&mut out, name, "");
+
+        let name = "ddalab_auth_failures_total";
+        let _ = writeln!(out, "# HELP {} Failed authentication attempts", name);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        for (reason, count) in AuthFailure::ALL.iter().zip(&self.inner.auth_failures) {
+            let _ = writeln!(
+                out,
+                "{}{{reason=\"{}\"}} {}",
+                name,
+                reason.as_str(),
+                count.load(Ordering::Relaxed)
+            );
+        }
+
+        gauge(
+            &mut out,
+            "ddalab_websocket_connections",
+            "Open sync WebSocket connections",
+            self.inner.websocket_connections.load(Ordering::Relaxed),
+        );
+
+        for (name, help, value) in [
+            (
+                "ddalab_relayed_transfers_total",
+                "Share downloads relayed through the broker",
+                &self.inner.relayed_transfers,
+            ),
+            (
+                "ddalab_relayed_bytes_total",
+                "Bytes of share downloads relayed through the broker",
+                &self.inner.relayed_bytes,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        out
+    }
+}
+
+/// Keeps a WebSocket connection counted while alive
+pub struct ConnectionGuard {
+    metrics: ServerMetrics,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.metrics
+            .inner
+            .websocket_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn gauge_header(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    gauge_header(out, name, help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobQueueConfig;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[1.0, 10.0]);
+        for value in [0.5, 3.0, 7.0, 50.0] {
+            histogram.observe(value);
+        }
+        let mut out = String::new();
+        histogram.render(&mut out, "h", "status=\"completed\"");
+        assert_eq!(
+            out,
+            "h_bucket{status=\"completed\",le=\"1\"} 1\n\
+             h_bucket{status=\"completed\",le=\"10\"} 3\n\
+             h_bucket{status=\"completed\",le=\"+Inf\"} 4\n\
+             h_sum{status=\"completed\"} 60.5\n\
+             h_count{status=\"completed\"} 4\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_counters_and_connection_gauge() {
+        let metrics = ServerMetrics::new();
+        metrics.auth_failure(AuthFailure::InvalidPassword);
+        metrics.auth_failure(AuthFailure::InvalidPassword);
+        let first = metrics.websocket_connected();
+        let second = metrics.websocket_connected();
+        drop(first);
+        metrics.observe_relay(2048);
+
+        let queue = JobQueue::new(JobQueueConfig::default());
+        let out = metrics.render(&queue.stats().await);
+        assert!(out.contains("ddalab_auth_failures_total{reason=\"invalid_password\"} 2\n"));
+        assert!(out.contains("ddalab_auth_failures_total{reason=\"rate_limited\"} 0\n"));
+        assert!(out.contains("ddalab_websocket_connections 1\n"));
+        assert!(out.contains("ddalab_relayed_bytes_total 2048\n"));
+        assert!(out.contains("ddalab_queue_pending_jobs{priority=\"batch\"} 0\n"));
+        assert!(out.contains("ddalab_running_jobs 0\n"));
+        drop(second);
+    }
+}
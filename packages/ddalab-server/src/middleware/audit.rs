@@ -9,7 +9,7 @@ use std::sync::Arc;
 use tracing::error;
 use uuid::Uuid;
 
-use crate::auth::SessionManager;
+use crate::auth::{bearer_token, SessionManager};
 use crate::storage::{AuditAction, AuditEntryBuilder, AuditStore};
 
 /// State for the audit middleware
@@ -42,6 +42,17 @@ fn route_to_action(method: &str, path: &str) -> Option<AuditAction> {
         }
         ("DELETE", p) if p.starts_with("/api/shares/") => Some(AuditAction::ShareRevoked),
 
+        // User management (admin)
+        ("POST", p) if p.starts_with("/api/admin/users/") && p.ends_with("/suspend") => {
+            Some(AuditAction::UserSuspended)
+        }
+        ("POST", p) if p.starts_with("/api/admin/users/") && p.ends_with("/activate") => {
+            Some(AuditAction::UserActivated)
+        }
+        ("POST", p) if p.starts_with("/api/admin/users/") && p.ends_with("/reset-password") => {
+            Some(AuditAction::UserPasswordReset)
+        }
+
         // Default - log as generic API request for protected routes
         (_, p) if p.starts_with("/api/") => Some(AuditAction::ApiRequest),
 
@@ -63,6 +74,10 @@ fn extract_resource(path: &str) -> (Option<String>, Option<String>) {
         ["api", "shares", token, ..] if !token.contains("user") => {
             (Some("share".to_string()), Some(token.to_string()))
         }
+        // /api/admin/users/{user_id}
+        ["api", "admin", "users", user_id, ..] => {
+            (Some("user".to_string()), Some(user_id.to_string()))
+        }
         _ => (None, None),
     }
 }
@@ -148,15 +163,8 @@ fn extract_user_from_request(
     request: &Request,
     session_manager: &SessionManager,
 ) -> (Option<Uuid>, Option<String>) {
-    let auth_header = request
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok());
-
-    let token = match auth_header {
-        Some(h) if h.starts_with("Bearer ") => &h[7..],
-        Some(h) => h,
-        None => return (None, None),
+    let Some(token) = bearer_token(request.headers()) else {
+        return (None, None);
     };
 
     // Validate token and get user info
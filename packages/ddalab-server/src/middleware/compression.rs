@@ -0,0 +1,66 @@
+use tower_http::compression::{
+    predicate::{And, DefaultPredicate, Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+/// Response compression for clients sending `Accept-Encoding: gzip` or `br`.
+///
+/// Bodies under `min_size` bytes are sent as-is. The default predicate also
+/// skips images, gRPC and `text/event-stream`, so the SSE job progress
+/// stream is never buffered by the encoder.
+pub fn compression_layer(
+    enabled: bool,
+    min_size: u16,
+) -> CompressionLayer<And<DefaultPredicate, SizeAbove>> {
+    CompressionLayer::new()
+        .gzip(enabled)
+        .br(enabled)
+        .compress_when(DefaultPredicate::new().and(SizeAbove::new(min_size)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header, Request},
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    fn app(enabled: bool) -> Router {
+        Router::new()
+            .route("/small", get(|| async { "ok" }))
+            .route("/large", get(|| async { "q".repeat(4096) }))
+            .layer(compression_layer(enabled, 1024))
+    }
+
+    async fn encoding(app: Router, path: &str, accept: &str) -> Option<String> {
+        let request = Request::builder()
+            .uri(path)
+            .header(header::ACCEPT_ENCODING, accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_negotiates_encoding_above_threshold() {
+        assert_eq!(
+            encoding(app(true), "/large", "gzip").await.as_deref(),
+            Some("gzip")
+        );
+        assert_eq!(
+            encoding(app(true), "/large", "br, gzip").await.as_deref(),
+            Some("br")
+        );
+        assert_eq!(encoding(app(true), "/small", "gzip").await, None);
+        assert_eq!(encoding(app(true), "/large", "identity").await, None);
+        assert_eq!(encoding(app(false), "/large", "gzip").await, None);
+    }
+}
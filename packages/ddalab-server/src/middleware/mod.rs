@@ -1,3 +1,9 @@
 mod audit;
+mod compression;
+mod origin_policy;
 
 pub use audit::{audit_middleware, AuditMiddlewareState};
+pub use compression::compression_layer;
+pub use origin_policy::{
+    origin_policy_middleware, parse_origin_policies, OriginPolicies, OriginPolicy, OriginScope,
+};
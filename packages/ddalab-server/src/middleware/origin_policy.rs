@@ -0,0 +1,267 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::str::FromStr;
+use std::sync::Arc;
+use tower_http::cors::{AllowCredentials, AllowOrigin, CorsLayer};
+
+/// What a cross-origin caller may do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OriginScope {
+    /// Every method the API supports
+    #[default]
+    Full,
+    /// GET and HEAD only, e.g. for dashboards embedding the result viewer
+    ReadOnly,
+}
+
+/// CORS and embedding rules for one browser origin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginPolicy {
+    pub origin: String,
+    pub scope: OriginScope,
+    /// May show the viewer in a frame (listed in `frame-ancestors`)
+    pub embed: bool,
+    /// Browsers may attach credentials; when false only an explicit
+    /// `Authorization` token authenticates requests from this origin
+    pub credentials: bool,
+}
+
+impl OriginPolicy {
+    /// Unrestricted API access with credentials, as for `CORS_ORIGINS`
+    pub fn full(origin: impl Into<String>) -> Self {
+        Self {
+            origin: origin.into(),
+            scope: OriginScope::Full,
+            embed: false,
+            credentials: true,
+        }
+    }
+}
+
+impl FromStr for OriginPolicy {
+    type Err = String;
+
+    /// Parse `"https://dash.example.org read-only embed credentialless"`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let origin = parts
+            .next()
+            .ok_or_else(|| "Empty origin policy".to_string())?;
+        if origin.parse::<HeaderValue>().is_err() || !origin.contains("://") {
+            return Err(format!("Invalid origin '{}'", origin));
+        }
+        let mut policy = Self::full(origin);
+        for option in parts {
+            match option {
+                "full" => policy.scope = OriginScope::Full,
+                "read-only" => policy.scope = OriginScope::ReadOnly,
+                "embed" => policy.embed = true,
+                "credentialless" => policy.credentials = false,
+                _ => {
+                    return Err(format!(
+                        "Unknown option '{}' for origin '{}', expected full, read-only, embed or credentialless",
+                        option, origin
+                    ))
+                }
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/// Parse a comma-separated list of [`OriginPolicy`] entries
+pub fn parse_origin_policies(s: &str) -> Result<Vec<OriginPolicy>, String> {
+    s.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Every configured origin policy, shared by the CORS layer and middleware
+#[derive(Debug, Clone)]
+pub struct OriginPolicies {
+    policies: Arc<Vec<OriginPolicy>>,
+    frame_ancestors: HeaderValue,
+}
+
+impl OriginPolicies {
+    pub fn new(policies: Vec<OriginPolicy>) -> Self {
+        let mut directive = "frame-ancestors 'self'".to_string();
+        for policy in policies.iter().filter(|policy| policy.embed) {
+            directive.push(' ');
+            directive.push_str(&policy.origin);
+        }
+        Self {
+            policies: Arc::new(policies),
+            // Origins were validated as header values when parsed
+            frame_ancestors: HeaderValue::from_str(&directive)
+                .unwrap_or(HeaderValue::from_static("frame-ancestors 'self'")),
+        }
+    }
+
+    pub fn get(&self, origin: &HeaderValue) -> Option<&OriginPolicy> {
+        self.policies
+            .iter()
+            .find(|policy| policy.origin.as_bytes() == origin.as_bytes())
+    }
+
+    /// CORS layer answering each origin according to its policy
+    pub fn cors_layer(&self) -> CorsLayer {
+        let origins = self.clone();
+        let credentials = self.clone();
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::predicate(move |origin, _| {
+                origins.get(origin).is_some()
+            }))
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::DELETE,
+                Method::OPTIONS,
+            ])
+            .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION, header::ACCEPT])
+            .allow_credentials(AllowCredentials::predicate(move |origin, _| {
+                credentials
+                    .get(origin)
+                    .is_some_and(|policy| policy.credentials)
+            }))
+    }
+}
+
+/// Enforce read-only origins and send the `frame-ancestors` policy
+///
+/// Browsers still send simple cross-origin POSTs without a preflight, so the
+/// scope of an origin is checked here rather than left to the CORS headers.
+/// Must run outside the CORS layer to see preflight requests.
+pub async fn origin_policy_middleware(
+    State(policies): State<OriginPolicies>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let read_only = request
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|origin| policies.get(origin))
+        .is_some_and(|policy| policy.scope == OriginScope::ReadOnly);
+    if read_only {
+        let method = if request.method() == Method::OPTIONS {
+            request
+                .headers()
+                .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+                .and_then(|method| Method::from_bytes(method.as_bytes()).ok())
+        } else {
+            Some(request.method().clone())
+        };
+        if let Some(method) = method.filter(|method| !method.is_safe()) {
+            return (
+                StatusCode::FORBIDDEN,
+                format!("Origin is limited to read-only access, {} refused", method),
+            )
+                .into_response();
+        }
+    }
+
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .entry(header::CONTENT_SECURITY_POLICY)
+        .or_insert_with(|| policies.frame_ancestors.clone());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let policies = OriginPolicies::new(
+            parse_origin_policies(
+                "https://app.example.org, https://dash.example.org read-only embed credentialless",
+            )
+            .unwrap(),
+        );
+        Router::new()
+            .route(
+                "/api/jobs",
+                get(|| async { "jobs" }).post(|| async { "ok" }),
+            )
+            .layer(policies.cors_layer())
+            .layer(middleware::from_fn_with_state(
+                policies,
+                origin_policy_middleware,
+            ))
+    }
+
+    async fn send(method: Method, origin: &str) -> Response {
+        let mut request = Request::builder()
+            .method(method.clone())
+            .uri("/api/jobs")
+            .header(header::ORIGIN, origin);
+        if method == Method::OPTIONS {
+            request = request.header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST");
+        }
+        app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parses_policies() {
+        let policies =
+            parse_origin_policies(" https://a.org ,https://b.org embed read-only,").unwrap();
+        assert_eq!(policies[0], OriginPolicy::full("https://a.org"));
+        assert_eq!(policies[1].scope, OriginScope::ReadOnly);
+        assert!(policies[1].embed && policies[1].credentials);
+        assert!("https://a.org write".parse::<OriginPolicy>().is_err());
+        assert!("a.org".parse::<OriginPolicy>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_applies_per_origin_rules() {
+        let full = send(Method::POST, "https://app.example.org").await;
+        assert_eq!(full.status(), StatusCode::OK);
+        assert_eq!(
+            full.headers()[header::ACCESS_CONTROL_ALLOW_CREDENTIALS],
+            "true"
+        );
+        assert_eq!(
+            full.headers()[header::CONTENT_SECURITY_POLICY],
+            "frame-ancestors 'self' https://dash.example.org"
+        );
+
+        let read = send(Method::GET, "https://dash.example.org").await;
+        assert_eq!(read.status(), StatusCode::OK);
+        assert_eq!(
+            read.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://dash.example.org"
+        );
+        assert!(!read
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+        assert_eq!(
+            send(Method::POST, "https://dash.example.org")
+                .await
+                .status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            send(Method::OPTIONS, "https://dash.example.org")
+                .await
+                .status(),
+            StatusCode::FORBIDDEN
+        );
+
+        let unknown = send(Method::GET, "https://other.org").await;
+        assert!(!unknown
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+}
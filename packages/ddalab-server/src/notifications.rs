@@ -0,0 +1,424 @@
+//! Email notifications
+//!
+//! With SMTP configured, users are emailed when their jobs complete or fail
+//! and when someone shares a result with them, by name or through one of
+//! their teams. Each kind of email can be turned off in the user's
+//! notification preferences.
+
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::jobs::{DDAJob, JobQueue, JobStatus};
+use crate::storage::{
+    AccessPolicyType, NotificationPreferences, PostgresTeamStore, ShareMetadata, TeamStore, User,
+    UserStore,
+};
+
+/// How long one SMTP conversation may take
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the connection to the SMTP server is secured (`SMTP_TLS`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection with STARTTLS, usually on port 587
+    StartTls,
+    /// Implicit TLS, usually on port 465
+    Tls,
+    /// No encryption, for a relay on localhost
+    None,
+}
+
+impl std::str::FromStr for SmtpSecurity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            "none" => Ok(SmtpSecurity::None),
+            other => Err(format!(
+                "SMTP_TLS must be starttls, tls or none, not '{}'",
+                other
+            )),
+        }
+    }
+}
+
+/// SMTP server settings, from `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME`,
+/// `SMTP_PASSWORD`, `SMTP_FROM` and `SMTP_TLS`
+#[derive(Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender address, e.g. `DDALAB <ddalab@example.edu>`
+    pub from: String,
+    pub security: SmtpSecurity,
+}
+
+impl std::fmt::Debug for SmtpConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SmtpConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("from", &self.from)
+            .field("security", &self.security)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SmtpConfig {
+    /// None unless `SMTP_HOST` is set; the port follows the security mode
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let Some(host) = var("SMTP_HOST") else {
+            return Ok(None);
+        };
+        let security = var("SMTP_TLS")
+            .map(|v| v.parse())
+            .transpose()?
+            .unwrap_or(SmtpSecurity::StartTls);
+        let port = match var("SMTP_PORT") {
+            Some(v) => v
+                .parse()
+                .map_err(|_| format!("SMTP_PORT '{}' is not a port", v))?,
+            None => match security {
+                SmtpSecurity::StartTls => 587,
+                SmtpSecurity::Tls => 465,
+                SmtpSecurity::None => 25,
+            },
+        };
+        let from = var("SMTP_FROM").ok_or("SMTP_FROM is required when SMTP_HOST is set")?;
+        from.parse::<Mailbox>()
+            .map_err(|e| format!("SMTP_FROM '{}' is not an address: {}", from, e))?;
+
+        Ok(Some(Self {
+            host,
+            port,
+            username: var("SMTP_USERNAME"),
+            password: var("SMTP_PASSWORD"),
+            from,
+            security,
+        }))
+    }
+}
+
+/// Subject and plain-text body of one email
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Notification {
+    /// The email for a finished job, if its owner wants one
+    pub fn for_job(
+        job: &DDAJob,
+        preferences: &NotificationPreferences,
+        institution_name: &str,
+    ) -> Option<Self> {
+        match job.status {
+            JobStatus::Completed if preferences.job_completed => Some(Self {
+                subject: format!("DDA job completed: {}", job.original_filename),
+                body: format!(
+                    "Your DDA analysis of {} on {} has completed.\n\n\
+                     Job ID: {}\n\n\
+                     Open DDALAB to view and download the results.\n",
+                    job.original_filename, institution_name, job.id
+                ),
+            }),
+            JobStatus::Failed if preferences.job_failed => Some(Self {
+                subject: format!("DDA job failed: {}", job.original_filename),
+                body: format!(
+                    "Your DDA analysis of {} on {} has failed.\n\n\
+                     Job ID: {}\n\
+                     Error: {}\n",
+                    job.original_filename,
+                    institution_name,
+                    job.id,
+                    job.error.as_deref().unwrap_or("unknown error")
+                ),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The email telling a user a result was shared with them; encrypted
+    /// shares have no title the server can read
+    pub fn for_share(
+        owner: &str,
+        token: &str,
+        title: &str,
+        description: Option<&str>,
+        institution_name: &str,
+    ) -> Self {
+        let shared = if title.is_empty() {
+            "an encrypted result".to_string()
+        } else {
+            format!("\"{}\"", title)
+        };
+        let mut body = format!(
+            "{} shared {} with you on {}.\n\n",
+            owner, shared, institution_name
+        );
+        if let Some(description) = description.filter(|d| !d.trim().is_empty()) {
+            body.push_str(description.trim());
+            body.push_str("\n\n");
+        }
+        body.push_str(&format!(
+            "Open it in DDALAB with the share token {}.\n",
+            token
+        ));
+        Self {
+            subject: format!("{} shared {} with you", owner, shared),
+            body,
+        }
+    }
+}
+
+/// Sends notification emails through the configured SMTP server
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    institution_name: String,
+}
+
+impl EmailNotifier {
+    pub fn new(config: &SmtpConfig, institution_name: &str) -> Result<Self, String> {
+        let builder = match config.security {
+            SmtpSecurity::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                    .map_err(|e| e.to_string())?
+            }
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|e| e.to_string())?,
+            SmtpSecurity::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+            }
+        };
+        let mut builder = builder.port(config.port).timeout(Some(SEND_TIMEOUT));
+        if let Some(username) = &config.username {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                config.password.clone().unwrap_or_default(),
+            ));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from.parse().map_err(|e| format!("{}", e))?,
+            institution_name: institution_name.to_string(),
+        })
+    }
+
+    pub fn institution_name(&self) -> &str {
+        &self.institution_name
+    }
+
+    /// Send one email, logging rather than returning failures
+    pub async fn send(&self, to: &str, notification: &Notification) {
+        let to: Mailbox = match to.parse() {
+            Ok(to) => to,
+            Err(e) => {
+                warn!("Not emailing '{}': {}", to, e);
+                return;
+            }
+        };
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.clone())
+            .subject(notification.subject.as_str())
+            .header(ContentType::TEXT_PLAIN)
+            .body(notification.body.clone());
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Failed to build email to {}: {}", to, e);
+                return;
+            }
+        };
+        if let Err(e) = self.transport.send(message).await {
+            warn!("Failed to email {}: {}", to, e);
+        }
+    }
+}
+
+/// Start emailing job owners when their jobs complete or fail
+pub fn spawn_job_notifier(
+    queue: Arc<JobQueue>,
+    user_store: Arc<dyn UserStore>,
+    notifier: Arc<EmailNotifier>,
+) {
+    let mut events = queue.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let progress = match events.recv().await {
+                Ok(progress) => progress,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Email notifier missed {} job events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if !matches!(progress.status, JobStatus::Completed | JobStatus::Failed) {
+                continue;
+            }
+            let Some(job) = queue.get_job(progress.job_id).await else {
+                continue;
+            };
+            // Anonymous and federated job owners have no account to email
+            let Ok(user) = user_store.get_user_by_email(&job.user_id).await else {
+                continue;
+            };
+            if !user.is_active {
+                continue;
+            }
+            let Some(notification) =
+                Notification::for_job(&job, &user.notifications, notifier.institution_name())
+            else {
+                continue;
+            };
+            let notifier = notifier.clone();
+            tokio::spawn(async move { notifier.send(&user.email, &notification).await });
+        }
+    });
+}
+
+/// Users a share names, directly or through a team
+///
+/// Public, institution and organization shares reach too many people to
+/// notify and have none.
+pub async fn share_recipients(
+    user_store: &dyn UserStore,
+    pool: PgPool,
+    share: &ShareMetadata,
+) -> Vec<User> {
+    match &share.access_policy.policy_type {
+        AccessPolicyType::Users { user_ids } => {
+            let mut users = Vec::new();
+            for user_id in user_ids {
+                if let Ok(user) = user_store.get_user_by_email(user_id).await {
+                    users.push(user);
+                }
+            }
+            users
+        }
+        AccessPolicyType::Team { team_id } => {
+            let Ok(team_id) = team_id.parse::<Uuid>() else {
+                return Vec::new();
+            };
+            let members = match PostgresTeamStore::new(pool).get_team_members(team_id).await {
+                Ok(members) => members,
+                Err(e) => {
+                    warn!("Failed to load members of team {}: {}", team_id, e);
+                    return Vec::new();
+                }
+            };
+            let mut users = Vec::new();
+            for member in members {
+                if let Ok(user) = user_store.get_user(member.user_id).await {
+                    users.push(user);
+                }
+            }
+            users
+        }
+        AccessPolicyType::Public
+        | AccessPolicyType::Institution
+        | AccessPolicyType::Organization { .. } => Vec::new(),
+    }
+}
+
+/// Email everyone a new share names, directly or through a team, except
+/// its owner
+pub async fn notify_share(
+    notifier: Arc<EmailNotifier>,
+    user_store: Arc<dyn UserStore>,
+    pool: PgPool,
+    token: String,
+    share: ShareMetadata,
+) {
+    let recipients = share_recipients(user_store.as_ref(), pool, &share).await;
+    let owner = share.owner_user_id;
+    let notification = Notification::for_share(
+        &owner,
+        &token,
+        &share.title,
+        share.description.as_deref(),
+        notifier.institution_name(),
+    );
+    let mut sent = 0;
+    for user in recipients
+        .iter()
+        .filter(|u| u.is_active && u.notifications.shares && u.email != owner)
+    {
+        notifier.send(&user.email, &notification).await;
+        sent += 1;
+    }
+    if sent > 0 {
+        info!("Emailed {} users about share {}", sent, token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::{DDAParameters, FileSource};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_job_notifications_follow_preferences() {
+        let mut job = DDAJob::new(
+            "alice@example.edu".to_string(),
+            FileSource::ServerPath(PathBuf::from("/data/eeg.edf")),
+            "eeg.edf".to_string(),
+            DDAParameters::default(),
+            false,
+        );
+        let all = NotificationPreferences::default();
+        assert_eq!(Notification::for_job(&job, &all, "Lab"), None);
+
+        job.status = JobStatus::Completed;
+        let email = Notification::for_job(&job, &all, "Lab").unwrap();
+        assert_eq!(email.subject, "DDA job completed: eeg.edf");
+        assert!(email.body.contains(&job.id.to_string()));
+
+        job.status = JobStatus::Failed;
+        job.error = Some("DDA exited with status 1".to_string());
+        let email = Notification::for_job(&job, &all, "Lab").unwrap();
+        assert!(email.body.contains("DDA exited with status 1"));
+
+        let failures_only = NotificationPreferences {
+            job_completed: false,
+            ..all
+        };
+        assert!(Notification::for_job(&job, &failures_only, "Lab").is_some());
+        job.status = JobStatus::Completed;
+        assert_eq!(Notification::for_job(&job, &failures_only, "Lab"), None);
+    }
+
+    #[test]
+    fn test_share_notification_and_smtp_security() {
+        let email = Notification::for_share("bob@example.edu", "abc123", "Run 4", None, "Lab");
+        assert_eq!(email.subject, "bob@example.edu shared \"Run 4\" with you");
+        assert!(email.body.contains("abc123"));
+        let sealed = Notification::for_share("bob@example.edu", "abc123", "", None, "Lab");
+        assert_eq!(sealed.subject, "bob@example.edu shared an encrypted result with you");
+
+        assert_eq!("STARTTLS".parse(), Ok(SmtpSecurity::StartTls));
+        assert_eq!("none".parse(), Ok(SmtpSecurity::None));
+        assert!("ssl".parse::<SmtpSecurity>().is_err());
+    }
+}
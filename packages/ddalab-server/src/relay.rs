@@ -0,0 +1,457 @@
+//! Relayed share downloads
+//!
+//! Peer-to-peer downloads need the requester to reach the owner's client,
+//! which fails whenever the owner is behind NAT. With relaying enabled the
+//! requester can download through the broker instead: the broker asks the
+//! owner's client over its sync connection to upload the result, and
+//! streams the upload straight on to the requester without storing it.
+//! Transfers are capped in size, in number at once and in bytes per user
+//! per day, and every relayed byte is counted.
+
+use axum::body::Bytes;
+use chrono::{NaiveDate, Utc};
+use futures_util::Stream;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use crate::storage::UserId;
+
+/// Largest relayed result unless `RELAY_MAX_BYTES` says otherwise (1 GiB)
+pub const DEFAULT_RELAY_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Relayed transfers at once unless `RELAY_MAX_CONCURRENT` says otherwise
+pub const DEFAULT_RELAY_MAX_CONCURRENT: usize = 8;
+
+/// How long the owner's client has to start uploading
+pub const RELAY_OFFER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Chunks buffered between the owner's upload and the requester's download
+const RELAY_BUFFER: usize = 16;
+
+type Chunk = Result<Bytes, std::io::Error>;
+
+/// Limits on relayed downloads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayConfig {
+    /// Largest result relayed in one transfer
+    pub max_bytes: u64,
+    /// Transfers relayed at once
+    pub max_concurrent: usize,
+    /// Bytes each user may download through the relay per day (UTC);
+    /// unlimited when unset
+    pub daily_bytes_per_user: Option<u64>,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_RELAY_MAX_BYTES,
+            max_concurrent: DEFAULT_RELAY_MAX_CONCURRENT,
+            daily_bytes_per_user: None,
+        }
+    }
+}
+
+impl RelayConfig {
+    /// Read `RELAY_ENABLED`, `RELAY_MAX_BYTES`, `RELAY_MAX_CONCURRENT` and
+    /// `RELAY_DAILY_BYTES_PER_USER`; `None` unless relaying is enabled
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let enabled = std::env::var("RELAY_ENABLED")
+            .map(|v| v.trim().eq_ignore_ascii_case("true") || v.trim() == "1")
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+
+        let positive = |name: &str| -> Result<Option<u64>, String> {
+            std::env::var(name)
+                .ok()
+                .map(|v| match v.trim().parse::<u64>() {
+                    Ok(n) if n > 0 => Ok(n),
+                    _ => Err(format!("{} must be a positive number", name)),
+                })
+                .transpose()
+        };
+        let defaults = Self::default();
+        Ok(Some(Self {
+            max_bytes: positive("RELAY_MAX_BYTES")?.unwrap_or(defaults.max_bytes),
+            max_concurrent: positive("RELAY_MAX_CONCURRENT")?
+                .map(|n| n as usize)
+                .unwrap_or(defaults.max_concurrent),
+            daily_bytes_per_user: positive("RELAY_DAILY_BYTES_PER_USER")?,
+        }))
+    }
+}
+
+/// Why a relayed transfer did not happen or broke off
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RelayError {
+    #[error("Too many relayed transfers in progress")]
+    Busy,
+    #[error("Daily relay allowance used up")]
+    QuotaExceeded,
+    #[error("Relay not found or already started")]
+    NotFound,
+    #[error("Relay is for another user's share")]
+    Forbidden,
+    #[error("Owner did not start the upload in time")]
+    Timeout,
+    #[error("Owner declined the transfer")]
+    Declined,
+    #[error("Result exceeds the relay limit of {0} bytes")]
+    TooLarge(u64),
+    #[error("Requester disconnected")]
+    RequesterGone,
+}
+
+/// The owner's upload as handed to the waiting requester
+struct Upload {
+    chunks: mpsc::Receiver<Chunk>,
+    size: Option<u64>,
+}
+
+struct PendingRelay {
+    owner: UserId,
+    limit: u64,
+    started: oneshot::Sender<Result<Upload, RelayError>>,
+}
+
+struct Inner {
+    config: RelayConfig,
+    pending: Mutex<HashMap<Uuid, PendingRelay>>,
+    active: AtomicUsize,
+    /// Bytes relayed to each user on the given day
+    usage: Mutex<HashMap<UserId, (NaiveDate, u64)>>,
+}
+
+/// Pairs owners' uploads with requesters' downloads; cheap to clone
+#[derive(Clone)]
+pub struct RelayHub {
+    inner: Arc<Inner>,
+}
+
+impl RelayHub {
+    pub fn new(config: RelayConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                pending: Mutex::new(HashMap::new()),
+                active: AtomicUsize::new(0),
+                usage: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &RelayConfig {
+        &self.inner.config
+    }
+
+    /// Bytes `user_id` may still download through the relay today
+    pub fn remaining_today(&self, user_id: &str) -> Option<u64> {
+        let limit = self.inner.config.daily_bytes_per_user?;
+        let today = Utc::now().date_naive();
+        let used = match self.inner.usage.lock().unwrap().get(user_id) {
+            Some((day, used)) if *day == today => *used,
+            _ => 0,
+        };
+        Some(limit.saturating_sub(used))
+    }
+
+    fn charge(&self, user_id: &str, bytes: u64) {
+        let today = Utc::now().date_naive();
+        let mut usage = self.inner.usage.lock().unwrap();
+        // Yesterday's totals are of no further use
+        usage.retain(|_, (day, _)| *day == today);
+        usage.entry(user_id.to_string()).or_insert((today, 0)).1 += bytes;
+    }
+
+    /// Reserve a transfer of one of `owner`'s results to `requester`
+    pub fn open(&self, owner: &str, requester: &str) -> Result<RelayOffer, RelayError> {
+        let remaining = self.remaining_today(requester);
+        if remaining == Some(0) {
+            return Err(RelayError::QuotaExceeded);
+        }
+        let max_concurrent = self.inner.config.max_concurrent;
+        self.inner
+            .active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < max_concurrent).then_some(active + 1)
+            })
+            .map_err(|_| RelayError::Busy)?;
+        let slot = RelaySlot {
+            hub: self.clone(),
+            requester: requester.to_string(),
+            bytes: 0,
+            on_finish: None,
+        };
+
+        let id = Uuid::new_v4();
+        let limit = remaining.map_or(self.inner.config.max_bytes, |r| {
+            r.min(self.inner.config.max_bytes)
+        });
+        let (started, upload) = oneshot::channel();
+        self.inner.pending.lock().unwrap().insert(
+            id,
+            PendingRelay {
+                owner: owner.to_string(),
+                limit,
+                started,
+            },
+        );
+        Ok(RelayOffer {
+            id,
+            limit,
+            upload,
+            slot,
+        })
+    }
+
+    fn take_pending(&self, id: Uuid, owner: &str) -> Result<PendingRelay, RelayError> {
+        let mut pending = self.inner.pending.lock().unwrap();
+        match pending.get(&id) {
+            None => Err(RelayError::NotFound),
+            Some(relay) if relay.owner != owner => Err(RelayError::Forbidden),
+            Some(_) => Ok(pending.remove(&id).expect("checked above")),
+        }
+    }
+
+    /// Start the owner's upload of `size` bytes, if known, for relay `id`
+    pub fn accept(
+        &self,
+        id: Uuid,
+        owner: &str,
+        size: Option<u64>,
+    ) -> Result<RelaySender, RelayError> {
+        let relay = self.take_pending(id, owner)?;
+        if size.is_some_and(|size| size > relay.limit) {
+            let _ = relay.started.send(Err(RelayError::TooLarge(relay.limit)));
+            return Err(RelayError::TooLarge(relay.limit));
+        }
+        let (sender, chunks) = mpsc::channel(RELAY_BUFFER);
+        relay
+            .started
+            .send(Ok(Upload { chunks, size }))
+            .map_err(|_| RelayError::RequesterGone)?;
+        Ok(RelaySender {
+            chunks: sender,
+            limit: relay.limit,
+            sent: 0,
+        })
+    }
+
+    /// Refuse relay `id`
+    pub fn decline(&self, id: Uuid, owner: &str) -> Result<(), RelayError> {
+        let relay = self.take_pending(id, owner)?;
+        let _ = relay.started.send(Err(RelayError::Declined));
+        Ok(())
+    }
+}
+
+/// A reserved transfer waiting for the owner's upload
+pub struct RelayOffer {
+    pub id: Uuid,
+    /// Most bytes the owner may upload
+    pub limit: u64,
+    upload: oneshot::Receiver<Result<Upload, RelayError>>,
+    slot: RelaySlot,
+}
+
+impl RelayOffer {
+    /// Wait up to `timeout` for the owner to start uploading
+    pub async fn wait(self, timeout: Duration) -> Result<RelayDownload, RelayError> {
+        let hub = self.slot.hub.clone();
+        match tokio::time::timeout(timeout, self.upload).await {
+            Ok(Ok(Ok(upload))) => Ok(RelayDownload {
+                size: upload.size,
+                chunks: upload.chunks,
+                slot: self.slot,
+            }),
+            Ok(Ok(Err(e))) => Err(e),
+            Ok(Err(_)) => Err(RelayError::Declined),
+            Err(_) => {
+                hub.inner.pending.lock().unwrap().remove(&self.id);
+                Err(RelayError::Timeout)
+            }
+        }
+    }
+}
+
+/// The requester's side of a started transfer
+pub struct RelayDownload {
+    /// Size the owner announced, if any
+    pub size: Option<u64>,
+    chunks: mpsc::Receiver<Chunk>,
+    slot: RelaySlot,
+}
+
+impl RelayDownload {
+    /// The relayed bytes; `on_finish` gets how many were forwarded once the
+    /// transfer ends or the requester goes away
+    pub fn into_stream(
+        self,
+        on_finish: impl FnOnce(u64) + Send + 'static,
+    ) -> impl Stream<Item = Chunk> + Send + 'static {
+        let mut slot = self.slot;
+        slot.on_finish = Some(Box::new(on_finish));
+        let mut chunks = self.chunks;
+        async_stream::stream! {
+            while let Some(chunk) = chunks.recv().await {
+                let failed = chunk.is_err();
+                if let Ok(bytes) = &chunk {
+                    slot.forwarded(bytes.len());
+                }
+                yield chunk;
+                if failed {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Holds a transfer's place among the concurrent ones and charges its
+/// bytes to the requester when dropped
+struct RelaySlot {
+    hub: RelayHub,
+    requester: UserId,
+    bytes: u64,
+    on_finish: Option<Box<dyn FnOnce(u64) + Send>>,
+}
+
+impl RelaySlot {
+    fn forwarded(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+    }
+}
+
+impl Drop for RelaySlot {
+    fn drop(&mut self) {
+        self.hub.inner.active.fetch_sub(1, Ordering::AcqRel);
+        if self.bytes > 0 {
+            self.hub.charge(&self.requester, self.bytes);
+        }
+        if let Some(on_finish) = self.on_finish.take() {
+            on_finish(self.bytes);
+        }
+    }
+}
+
+/// The owner's side of a started transfer
+pub struct RelaySender {
+    chunks: mpsc::Sender<Chunk>,
+    limit: u64,
+    sent: u64,
+}
+
+impl RelaySender {
+    /// Forward the next chunk of the upload
+    pub async fn send(&mut self, bytes: Bytes) -> Result<(), RelayError> {
+        self.sent += bytes.len() as u64;
+        if self.sent > self.limit {
+            let error = RelayError::TooLarge(self.limit);
+            self.abort(&error.to_string()).await;
+            return Err(error);
+        }
+        self.chunks
+            .send(Ok(bytes))
+            .await
+            .map_err(|_| RelayError::RequesterGone)
+    }
+
+    /// Break off the requester's download
+    pub async fn abort(&self, reason: &str) {
+        let _ = self
+            .chunks
+            .send(Err(std::io::Error::other(reason.to_string())))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn hub(max_concurrent: usize, daily_bytes_per_user: Option<u64>) -> RelayHub {
+        RelayHub::new(RelayConfig {
+            max_bytes: 10,
+            max_concurrent,
+            daily_bytes_per_user,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_relay_streams_and_charges_requester() {
+        let hub = hub(1, Some(12));
+        let offer = hub.open("alice", "bob").unwrap();
+        let id = offer.id;
+        assert_eq!(hub.open("alice", "carol").err(), Some(RelayError::Busy));
+        assert_eq!(
+            hub.accept(id, "mallory", None).err(),
+            Some(RelayError::Forbidden)
+        );
+
+        let owner = {
+            let hub = hub.clone();
+            tokio::spawn(async move {
+                let mut sender = hub.accept(id, "alice", Some(8)).unwrap();
+                sender.send(Bytes::from_static(b"abcd")).await.unwrap();
+                sender.send(Bytes::from_static(b"efgh")).await.unwrap();
+            })
+        };
+        let download = offer.wait(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(download.size, Some(8));
+        let (finished, relayed) = oneshot::channel();
+        let body: Vec<u8> = download
+            .into_stream(move |bytes| {
+                let _ = finished.send(bytes);
+            })
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        owner.await.unwrap();
+
+        assert_eq!(body, b"abcdefgh");
+        assert_eq!(relayed.await.unwrap(), 8);
+        assert_eq!(hub.remaining_today("bob"), Some(4));
+        // The slot is free again, but only 4 bytes remain for Bob today
+        assert_eq!(hub.open("alice", "bob").unwrap().limit, 4);
+    }
+
+    #[tokio::test]
+    async fn test_relay_limits() {
+        let hub = hub(2, None);
+
+        let offer = hub.open("alice", "bob").unwrap();
+        assert_eq!(
+            hub.accept(offer.id, "alice", Some(11)).err(),
+            Some(RelayError::TooLarge(10))
+        );
+        assert_eq!(
+            offer.wait(Duration::from_secs(5)).await.err(),
+            Some(RelayError::TooLarge(10))
+        );
+
+        let offer = hub.open("alice", "bob").unwrap();
+        hub.decline(offer.id, "alice").unwrap();
+        assert_eq!(
+            offer.wait(Duration::from_secs(5)).await.err(),
+            Some(RelayError::Declined)
+        );
+
+        let offer = hub.open("alice", "bob").unwrap();
+        let id = offer.id;
+        assert_eq!(
+            offer.wait(Duration::from_millis(10)).await.err(),
+            Some(RelayError::Timeout)
+        );
+        assert_eq!(
+            hub.accept(id, "alice", None).err(),
+            Some(RelayError::NotFound)
+        );
+    }
+}
@@ -0,0 +1,498 @@
+//! Retention of job results and uploads
+//!
+//! `RETENTION_DAYS` sets how long a finished job keeps its result, thumbnail
+//! and persistent upload; teams can override it, and a user in several teams
+//! gets the longest of their policies. Files past their retention are
+//! expired but stay for `RETENTION_GRACE_DAYS` more, during which owners see
+//! them under `/api/users/me/retention`, before the sweep deletes them.
+//!
+//! With `RETENTION_ARCHIVE_COMMAND` set, every file is first handed to that
+//! command, e.g. `aws s3 cp {path} s3://ddalab-archive/{job_id}/{name}`, and
+//! kept if the command fails. Every file the sweep touches is written to the
+//! purge log. Files in the output and upload directories no known job refers
+//! to are swept by modification time under the longest configured policy,
+//! and only when a server-wide policy is set.
+//!
+//! The audit log has a retention of its own, `AUDIT_RETENTION_DAYS`, with no
+//! grace period; entries are kept forever when it is unset.
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::jobs::{thumbnail_path, DDAJob, FileSource, JobQueue, JobStatus};
+use crate::storage::{AuditStore, PurgeRecord, PurgedKind, RetentionStore, TeamRetention};
+
+/// How often expired files are looked for
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long the archive command may take per file
+const ARCHIVE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Grace period when `RETENTION_GRACE_DAYS` is not set
+pub const DEFAULT_GRACE_DAYS: u32 = 7;
+
+/// How long finished jobs keep their files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RetentionPolicy {
+    pub retain_days: u32,
+    pub grace_days: u32,
+}
+
+impl RetentionPolicy {
+    /// When files of a job that finished at `finished` expire
+    pub fn expires_at(&self, finished: DateTime<Utc>) -> DateTime<Utc> {
+        finished + ChronoDuration::days(i64::from(self.retain_days))
+    }
+
+    /// When files of a job that finished at `finished` are deleted
+    pub fn delete_at(&self, finished: DateTime<Utc>) -> DateTime<Utc> {
+        self.expires_at(finished) + ChronoDuration::days(i64::from(self.grace_days))
+    }
+
+    /// The policy that keeps files longest
+    pub fn longest(policies: impl IntoIterator<Item = RetentionPolicy>) -> Option<Self> {
+        policies
+            .into_iter()
+            .max_by_key(|policy| u64::from(policy.retain_days) + u64::from(policy.grace_days))
+    }
+}
+
+impl From<&TeamRetention> for RetentionPolicy {
+    fn from(team: &TeamRetention) -> Self {
+        Self {
+            retain_days: team.retain_days.max(0) as u32,
+            grace_days: team.grace_days.max(0) as u32,
+        }
+    }
+}
+
+/// The policy for a user: their teams' longest, or the server-wide one
+pub fn effective_policy(
+    default: Option<RetentionPolicy>,
+    teams: &[TeamRetention],
+) -> Option<RetentionPolicy> {
+    if teams.is_empty() {
+        default
+    } else {
+        RetentionPolicy::longest(teams.iter().map(RetentionPolicy::from))
+    }
+}
+
+/// Command that copies a file somewhere safe before it is deleted
+///
+/// Arguments are split on whitespace and run without a shell; `{path}`,
+/// `{name}`, `{job_id}` and `{user}` are replaced in each argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveHook {
+    program: String,
+    args: Vec<String>,
+}
+
+impl FromStr for ArchiveHook {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace().map(str::to_string);
+        let program = words
+            .next()
+            .ok_or_else(|| "Archive command is empty".to_string())?;
+        Ok(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+}
+
+impl ArchiveHook {
+    fn arguments(&self, path: &Path, job_id: Option<Uuid>, user: Option<&str>) -> Vec<String> {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let job_id = job_id.map(|id| id.to_string()).unwrap_or_default();
+        self.args
+            .iter()
+            .map(|arg| {
+                arg.replace("{path}", &path.to_string_lossy())
+                    .replace("{name}", &name)
+                    .replace("{job_id}", &job_id)
+                    .replace("{user}", user.unwrap_or_default())
+            })
+            .collect()
+    }
+
+    async fn archive(
+        &self,
+        path: &Path,
+        job_id: Option<Uuid>,
+        user: Option<&str>,
+    ) -> Result<(), String> {
+        let mut command = tokio::process::Command::new(&self.program);
+        command
+            .args(self.arguments(path, job_id, user))
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true);
+        match tokio::time::timeout(ARCHIVE_TIMEOUT, command.status()).await {
+            Ok(Ok(status)) if status.success() => Ok(()),
+            Ok(Ok(status)) => Err(format!("Archive command exited with {}", status)),
+            Ok(Err(e)) => Err(format!("Archive command failed to start: {}", e)),
+            Err(_) => Err("Archive command timed out".to_string()),
+        }
+    }
+}
+
+/// Server-wide retention settings
+#[derive(Debug, Clone, Default)]
+pub struct RetentionConfig {
+    /// Applies to users in no team with a policy; files are kept when unset
+    pub default_policy: Option<RetentionPolicy>,
+    pub archive_hook: Option<ArchiveHook>,
+    /// Output and upload directories swept for files no job refers to
+    pub directories: Vec<PathBuf>,
+}
+
+/// A file that is past its retention but not yet deleted
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiringJob {
+    pub job_id: Uuid,
+    pub file_name: String,
+    pub expired_at: DateTime<Utc>,
+    pub delete_at: DateTime<Utc>,
+}
+
+/// Files of `job` the sweep deletes, with their kinds
+fn job_files(job: &DDAJob) -> Vec<(PathBuf, PurgedKind)> {
+    let mut files = Vec::new();
+    if let Some(output) = &job.output_path {
+        files.push((thumbnail_path(output), PurgedKind::Output));
+        files.push((output.clone(), PurgedKind::Output));
+    }
+    if let FileSource::UploadedPersistent(path) = &job.file_source {
+        files.push((path.clone(), PurgedKind::Upload));
+    }
+    files
+}
+
+fn is_finished(job: &DDAJob) -> bool {
+    matches!(
+        job.status,
+        JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+    )
+}
+
+/// The caller's finished jobs whose files are in their grace period
+pub fn expiring_jobs(
+    jobs: &[DDAJob],
+    policy: RetentionPolicy,
+    now: DateTime<Utc>,
+) -> Vec<ExpiringJob> {
+    let mut expiring: Vec<ExpiringJob> = jobs
+        .iter()
+        .filter(|job| is_finished(job) && !job_files(job).is_empty())
+        .filter_map(|job| {
+            let finished = job.completed_at?;
+            let expired_at = policy.expires_at(finished);
+            let delete_at = policy.delete_at(finished);
+            (expired_at <= now && now < delete_at).then(|| ExpiringJob {
+                job_id: job.id,
+                file_name: job.original_filename.clone(),
+                expired_at,
+                delete_at,
+            })
+        })
+        .collect();
+    expiring.sort_by_key(|job| job.delete_at);
+    expiring
+}
+
+/// Start deleting expired files every hour
+pub fn spawn_retention_sweeper(
+    queue: Arc<JobQueue>,
+    store: Arc<dyn RetentionStore>,
+    config: RetentionConfig,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let purged = sweep(&queue, store.as_ref(), &config).await;
+            if purged > 0 {
+                info!("Retention sweep deleted {} files", purged);
+            }
+        }
+    });
+}
+
+/// Start deleting audit log entries older than `retain_days` every hour
+pub fn spawn_audit_pruner(store: Arc<dyn AuditStore>, retain_days: u32) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let cutoff = Utc::now() - ChronoDuration::days(i64::from(retain_days));
+            match store.prune_before(cutoff).await {
+                Ok(0) => {}
+                Ok(pruned) => info!(
+                    "Pruned {} audit log entries older than {} days",
+                    pruned, retain_days
+                ),
+                Err(e) => warn!("Failed to prune the audit log: {}", e),
+            }
+        }
+    });
+}
+
+/// Archive and delete every file past its grace period; returns how many
+/// files were deleted
+async fn sweep(queue: &JobQueue, store: &dyn RetentionStore, config: &RetentionConfig) -> usize {
+    let now = Utc::now();
+    let jobs = queue.get_all_jobs().await;
+    let mut policies: HashMap<String, Option<RetentionPolicy>> = HashMap::new();
+    let mut deleted = 0;
+
+    // Files a job still needs, which neither it nor the orphan sweep may take
+    let mut protected: HashSet<PathBuf> = HashSet::new();
+    let mut due: Vec<&DDAJob> = Vec::new();
+    for job in &jobs {
+        let policy = match policies.get(&job.user_id) {
+            Some(policy) => *policy,
+            None => {
+                let teams = match store.retention_for_user(&job.user_id).await {
+                    Ok(teams) => teams,
+                    Err(e) => {
+                        warn!("Failed to load retention for {}: {}", job.user_id, e);
+                        return deleted;
+                    }
+                };
+                let policy = effective_policy(config.default_policy, &teams);
+                policies.insert(job.user_id.clone(), policy);
+                policy
+            }
+        };
+        let is_due = is_finished(job)
+            && job
+                .completed_at
+                .zip(policy)
+                .is_some_and(|(finished, policy)| now >= policy.delete_at(finished));
+        if is_due {
+            due.push(job);
+        } else {
+            protected.extend(job_files(job).into_iter().map(|(path, _)| path));
+            protected.insert(job.input_path());
+        }
+    }
+
+    for job in due {
+        let mut kept = false;
+        for (path, kind) in job_files(job) {
+            if protected.contains(&path) || !path.exists() {
+                continue;
+            }
+            if kind == PurgedKind::Upload && queue.blobs().contains(&path) {
+                // Shared with other jobs' uploads; the last one purges it
+                let released = match queue.blobs().release(&path).await {
+                    Ok(Some(released)) => released,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        warn!("Retention kept {}: {}", path.display(), e);
+                        kept = true;
+                        continue;
+                    }
+                };
+                match purge(store, config, &released, kind, Some(job.id), Some(&job.user_id)).await {
+                    true => deleted += 1,
+                    false => {
+                        if let Err(e) = queue.blobs().restore(&released, &path).await {
+                            warn!("Failed to restore {}: {}", path.display(), e);
+                        }
+                        kept = true;
+                    }
+                }
+                continue;
+            }
+            match purge(store, config, &path, kind, Some(job.id), Some(&job.user_id)).await {
+                true => deleted += 1,
+                false => kept = true,
+            }
+        }
+        if !kept {
+            queue.clear_output(job.id).await;
+        }
+    }
+
+    // Without a server-wide policy nobody agreed to lose unowned files
+    let Some(default) = config.default_policy else {
+        return deleted;
+    };
+    let teams = store.list_team_retention().await.unwrap_or_default();
+    let policy = RetentionPolicy::longest(
+        std::iter::once(default).chain(teams.iter().map(RetentionPolicy::from)),
+    )
+    .unwrap_or(default);
+    for directory in &config.directories {
+        let Ok(mut entries) = tokio::fs::read_dir(directory).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if !metadata.is_file() || protected.contains(&path) {
+                continue;
+            }
+            let Some(modified) = metadata.modified().ok().map(DateTime::<Utc>::from) else {
+                continue;
+            };
+            let known = jobs.iter().any(|job| {
+                job.input_path() == path || job_files(job).iter().any(|(file, _)| *file == path)
+            });
+            if !known
+                && now >= policy.delete_at(modified)
+                && purge(store, config, &path, PurgedKind::Orphan, None, None).await
+            {
+                deleted += 1;
+            }
+        }
+    }
+
+    deleted
+}
+
+/// Archive one file if a hook is set, delete it and log the outcome;
+/// returns whether the file was deleted
+async fn purge(
+    store: &dyn RetentionStore,
+    config: &RetentionConfig,
+    path: &Path,
+    kind: PurgedKind,
+    job_id: Option<Uuid>,
+    user_id: Option<&str>,
+) -> bool {
+    let bytes = tokio::fs::metadata(path)
+        .await
+        .map(|m| m.len() as i64)
+        .unwrap_or(0);
+    let mut record = PurgeRecord {
+        id: Uuid::new_v4(),
+        path: path.to_string_lossy().to_string(),
+        kind,
+        job_id,
+        user_id: user_id.map(str::to_string),
+        bytes,
+        archived: false,
+        error: None,
+        deleted: false,
+        purged_at: Utc::now(),
+    };
+
+    let archived = match &config.archive_hook {
+        Some(hook) => hook.archive(path, job_id, user_id).await.map(|_| true),
+        None => Ok(false),
+    };
+    match archived {
+        Ok(archived) => {
+            record.archived = archived;
+            match tokio::fs::remove_file(path).await {
+                Ok(()) => record.deleted = true,
+                Err(e) => record.error = Some(format!("Delete failed: {}", e)),
+            }
+        }
+        Err(e) => record.error = Some(e),
+    }
+
+    if let Some(error) = &record.error {
+        warn!("Retention kept {}: {}", record.path, error);
+    }
+    if let Err(e) = store.record_purge(&record).await {
+        warn!("Failed to log purge of {}: {}", record.path, e);
+    }
+    record.deleted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::DDAParameters;
+
+    fn team(retain_days: i32, grace_days: i32) -> TeamRetention {
+        TeamRetention {
+            team_id: Uuid::new_v4(),
+            retain_days,
+            grace_days,
+            updated_by: "admin@example.com".to_string(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_team_policies_override_the_default() {
+        let default = Some(RetentionPolicy {
+            retain_days: 30,
+            grace_days: 7,
+        });
+        assert_eq!(effective_policy(default, &[]), default);
+        assert_eq!(
+            effective_policy(default, &[team(10, 1), team(90, 0)]),
+            Some(RetentionPolicy {
+                retain_days: 90,
+                grace_days: 0
+            })
+        );
+        assert_eq!(effective_policy(None, &[]), None);
+    }
+
+    #[test]
+    fn test_expiring_jobs_are_in_their_grace_period() {
+        let policy = RetentionPolicy {
+            retain_days: 30,
+            grace_days: 7,
+        };
+        let now = Utc::now();
+        let finished = |days_ago| {
+            let mut job = DDAJob::new(
+                "user@example.com".to_string(),
+                FileSource::UploadedPersistent("/uploads/a.edf".into()),
+                "a.edf".to_string(),
+                DDAParameters::default(),
+                false,
+            );
+            job.status = JobStatus::Completed;
+            job.completed_at = Some(now - ChronoDuration::days(days_ago));
+            job
+        };
+        let jobs = [finished(10), finished(33), finished(40)];
+        let expiring = expiring_jobs(&jobs, policy, now);
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].job_id, jobs[1].id);
+        assert_eq!(
+            expiring[0].delete_at,
+            jobs[1].completed_at.unwrap() + ChronoDuration::days(37)
+        );
+    }
+
+    #[test]
+    fn test_archive_hook_fills_placeholders() {
+        let hook: ArchiveHook = "aws s3 cp {path} s3://archive/{user}/{job_id}/{name}"
+            .parse()
+            .unwrap();
+        let job_id = Uuid::nil();
+        assert_eq!(
+            hook.arguments(Path::new("/out/r.json"), Some(job_id), Some("a@b.org")),
+            [
+                "s3",
+                "cp",
+                "/out/r.json",
+                &format!("s3://archive/a@b.org/{}/r.json", job_id)
+            ]
+        );
+        assert!("  ".parse::<ArchiveHook>().is_err());
+    }
+}
@@ -0,0 +1,252 @@
+//! Minimal five-field cron expressions, evaluated in UTC
+//!
+//! Supports `*`, single values, ranges (`1-5`), steps (`*/15`, `0-30/10`),
+//! comma-separated lists and the `@hourly`, `@daily`, `@weekly` and
+//! `@monthly` shorthands. As in classic cron, when both day-of-month and
+//! day-of-week are restricted a day matches if either field does.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How far ahead `next_after` searches before giving up (e.g. `0 0 30 2 *`)
+const SEARCH_HORIZON_DAYS: i64 = 5 * 366;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSchedule {
+    /// First matching minute strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = after
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(after)
+            + Duration::minutes(1);
+        let limit = after + Duration::days(SEARCH_HORIZON_DAYS);
+
+        while t < limit {
+            if !has_bit(self.months, t.month()) {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = start_of_day(NaiveDate::from_ymd_opt(year, month, 1)?);
+                continue;
+            }
+            if !self.day_matches(t) {
+                t = start_of_day(t.date_naive().succ_opt()?);
+                continue;
+            }
+            if !has_bit(self.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if !has_bit(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+                continue;
+            }
+            return Some(t);
+        }
+        None
+    }
+
+    fn day_matches(&self, t: DateTime<Utc>) -> bool {
+        let dom = has_bit(self.days_of_month, t.day());
+        let dow = has_bit(self.days_of_week, t.weekday().num_days_from_sunday());
+        if self.dom_restricted && self.dow_restricted {
+            dom || dow
+        } else {
+            dom && dow
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = s.trim();
+        let expanded = match expression {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Cron expression '{}' must have 5 fields (minute hour day month weekday)",
+                expression
+            ));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7, "weekday")?;
+        // 7 is an alias for Sunday
+        if has_bit(days_of_week, 7) {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            expression: expression.to_string(),
+            minutes: parse_field(fields[0], 0, 59, "minute")?,
+            hours: parse_field(fields[1], 0, 23, "hour")?,
+            days_of_month: parse_field(fields[2], 1, 31, "day of month")?,
+            months: parse_field(fields[3], 1, 12, "month")?,
+            days_of_week,
+            dom_restricted: fields[2] != "*",
+            dow_restricted: fields[4] != "*",
+        })
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> Self {
+        schedule.expression
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid cron {} field '{}'", name, field);
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().map_err(|_| invalid())?;
+                if step == 0 {
+                    return Err(invalid());
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (
+                a.parse::<u32>().map_err(|_| invalid())?,
+                b.parse::<u32>().map_err(|_| invalid())?,
+            )
+        } else {
+            let value = range.parse::<u32>().map_err(|_| invalid())?;
+            // `5/15` means "from 5 to the end in steps of 15"
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!(
+                "Cron {} field '{}' is outside {}-{}",
+                name, field, min, max
+            ));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn has_bit(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn test_next_after_common_expressions() {
+        let nightly: CronSchedule = "30 2 * * *".parse().unwrap();
+        assert_eq!(
+            nightly.next_after(at(2024, 3, 10, 2, 30)),
+            Some(at(2024, 3, 11, 2, 30))
+        );
+        assert_eq!(
+            nightly.next_after(at(2024, 12, 31, 23, 0)),
+            Some(at(2025, 1, 1, 2, 30))
+        );
+
+        let quarter_hour: CronSchedule = "*/15 9-17 * * 1-5".parse().unwrap();
+        // Saturday evening -> Monday 09:00
+        assert_eq!(
+            quarter_hour.next_after(at(2024, 3, 9, 18, 0)),
+            Some(at(2024, 3, 11, 9, 0))
+        );
+        assert_eq!(
+            quarter_hour.next_after(at(2024, 3, 11, 9, 7)),
+            Some(at(2024, 3, 11, 9, 15))
+        );
+
+        let weekly: CronSchedule = "@weekly".parse().unwrap();
+        assert_eq!(
+            weekly.next_after(at(2024, 3, 11, 0, 0)),
+            Some(at(2024, 3, 17, 0, 0))
+        );
+        assert_eq!(weekly.to_string(), "@weekly");
+    }
+
+    #[test]
+    fn test_day_of_month_or_weekday() {
+        // The 1st of the month or any Sunday
+        let schedule: CronSchedule = "0 6 1 * 0".parse().unwrap();
+        assert_eq!(
+            schedule.next_after(at(2024, 3, 2, 0, 0)),
+            Some(at(2024, 3, 3, 6, 0))
+        );
+        assert_eq!(
+            schedule.next_after(at(2024, 3, 31, 7, 0)),
+            Some(at(2024, 4, 1, 6, 0))
+        );
+
+        let never: CronSchedule = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(never.next_after(at(2024, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_rejects_malformed_expressions() {
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(bad.parse::<CronSchedule>().is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,442 @@
+//! Recurring analyses on server-side datasets
+//!
+//! A schedule pairs a named parameter preset with a set of server files (or
+//! the newest files matching a glob) and a cron expression. When a schedule
+//! comes due its jobs are submitted to the regular job queue, tagged with the
+//! schedule id, and members of the owning team are notified over their sync
+//! WebSocket, or when they next connect. Schedules are held in memory, like the job queue itself.
+
+mod cron;
+
+pub use cron::CronSchedule;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::jobs::{DDAJob, DDAParameters, FileSource, JobPriority};
+use crate::state::ServerState;
+use crate::storage::{PostgresTeamStore, TeamStore};
+use crate::sync::SyncMessage;
+
+/// Named DDA parameter set run by a schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterPreset {
+    pub name: String,
+    pub parameters: DDAParameters,
+}
+
+/// Files a schedule analyzes, relative to the server files directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduleTarget {
+    /// A fixed list of files
+    Files { paths: Vec<String> },
+    /// The `count` most recently modified files matching a glob pattern
+    NewestMatching {
+        pattern: String,
+        #[serde(default = "default_newest_count")]
+        count: usize,
+    },
+}
+
+fn default_newest_count() -> usize {
+    1
+}
+
+/// Outcome of one scheduled run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleRun {
+    pub ran_at: DateTime<Utc>,
+    pub job_ids: Vec<Uuid>,
+    /// Files submitted, relative to the server files directory
+    pub files: Vec<String>,
+    /// Why the run submitted nothing (or only part of its files)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A recurring analysis owned by a team
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisSchedule {
+    pub id: Uuid,
+    pub name: String,
+    /// Team notified of every run
+    pub team_id: Uuid,
+    /// User the scheduled jobs are submitted as
+    pub created_by: String,
+    pub cron: CronSchedule,
+    pub preset: ParameterPreset,
+    pub target: ScheduleTarget,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub next_run_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<ScheduleRun>,
+}
+
+impl AnalysisSchedule {
+    pub fn new(
+        name: String,
+        team_id: Uuid,
+        created_by: String,
+        cron: CronSchedule,
+        preset: ParameterPreset,
+        target: ScheduleTarget,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            team_id,
+            created_by,
+            next_run_at: cron.next_after(now),
+            cron,
+            preset,
+            target,
+            enabled: true,
+            created_at: now,
+            last_run: None,
+        }
+    }
+}
+
+/// Shared schedule registry; cheap to clone
+#[derive(Clone)]
+pub struct Scheduler {
+    schedules: Arc<RwLock<HashMap<Uuid, AnalysisSchedule>>>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            schedules: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn insert(&self, schedule: AnalysisSchedule) {
+        self.schedules
+            .write()
+            .unwrap()
+            .insert(schedule.id, schedule);
+    }
+
+    pub fn remove(&self, id: Uuid) -> Option<AnalysisSchedule> {
+        self.schedules.write().unwrap().remove(&id)
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<AnalysisSchedule> {
+        self.schedules.read().unwrap().get(&id).cloned()
+    }
+
+    /// All schedules, ordered by name
+    pub fn list(&self) -> Vec<AnalysisSchedule> {
+        let mut schedules = self
+            .schedules
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        schedules.sort_by(|a, b| a.name.cmp(&b.name));
+        schedules
+    }
+
+    /// Enabled schedules whose next run is at or before `now`
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<AnalysisSchedule> {
+        self.schedules
+            .read()
+            .unwrap()
+            .values()
+            .filter(|s| s.enabled && s.next_run_at.is_some_and(|next| next <= now))
+            .cloned()
+            .collect()
+    }
+
+    /// Store the outcome of a run and advance the schedule past `now`
+    fn record_run(&self, id: Uuid, run: ScheduleRun, now: DateTime<Utc>) {
+        if let Some(schedule) = self.schedules.write().unwrap().get_mut(&id) {
+            schedule.next_run_at = schedule.cron.next_after(now);
+            schedule.last_run = Some(run);
+        }
+    }
+
+    /// Run every due schedule; called periodically from the server's main loop
+    pub async fn run_due(&self, state: &ServerState) {
+        let now = Utc::now();
+        for schedule in self.due(now) {
+            self.run_now(state, &schedule, now).await;
+        }
+    }
+
+    /// Submit the jobs of `schedule` immediately and notify its team
+    pub async fn run_now(
+        &self,
+        state: &ServerState,
+        schedule: &AnalysisSchedule,
+        now: DateTime<Utc>,
+    ) -> ScheduleRun {
+        let run = submit_schedule_jobs(state, schedule, now).await;
+        match &run.error {
+            Some(error) => warn!(
+                "Schedule '{}' submitted {} job(s): {}",
+                schedule.name,
+                run.job_ids.len(),
+                error
+            ),
+            None => info!(
+                "Schedule '{}' submitted {} job(s)",
+                schedule.name,
+                run.job_ids.len()
+            ),
+        }
+        self.record_run(schedule.id, run.clone(), now);
+
+        let notice = SyncMessage::ScheduledRunNotice {
+            schedule_id: schedule.id,
+            schedule_name: schedule.name.clone(),
+            team_id: schedule.team_id,
+            run: run.clone(),
+        };
+        for recipient in team_recipients(state, schedule.team_id).await {
+            state.mailbox.deliver(&recipient, notice.clone()).await;
+        }
+        run
+    }
+}
+
+async fn submit_schedule_jobs(
+    state: &ServerState,
+    schedule: &AnalysisSchedule,
+    now: DateTime<Utc>,
+) -> ScheduleRun {
+    let mut run = ScheduleRun {
+        ran_at: now,
+        job_ids: Vec::new(),
+        files: Vec::new(),
+        error: None,
+    };
+    if let Some(message) = state.maintenance.rejection_message() {
+        run.error = Some(format!("Skipped: {}", message));
+        return run;
+    }
+    let Some(base) = state.config.server_files_directory.as_ref() else {
+        run.error = Some("Server-side file access is not configured".to_string());
+        return run;
+    };
+    let files = match resolve_target(base, &schedule.target) {
+        Ok(files) if files.is_empty() => {
+            run.error = Some("No files matched the schedule target".to_string());
+            return run;
+        }
+        Ok(files) => files,
+        Err(error) => {
+            run.error = Some(error);
+            return run;
+        }
+    };
+
+    for (relative, path) in files {
+        let filename = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let job = DDAJob::new(
+            schedule.created_by.clone(),
+            FileSource::ServerPath(path),
+            filename,
+            schedule.preset.parameters.clone(),
+            false,
+        )
+        .with_schedule(schedule.id)
+        .with_priority(JobPriority::Batch);
+        match state.job_queue.submit(job).await {
+            Ok(job_id) => {
+                run.job_ids.push(job_id);
+                run.files.push(relative);
+            }
+            Err(e) => {
+                run.error = Some(format!("Failed to submit job for {}: {}", relative, e));
+                break;
+            }
+        }
+    }
+    run
+}
+
+/// Emails of the owning team's members; empty if the team can't be read
+async fn team_recipients(state: &ServerState, team_id: Uuid) -> Vec<String> {
+    let members = match PostgresTeamStore::new(state.db_pool.clone())
+        .get_team_members(team_id)
+        .await
+    {
+        Ok(members) => members,
+        Err(e) => {
+            warn!("Could not load members of team {}: {}", team_id, e);
+            return Vec::new();
+        }
+    };
+    let mut recipients = Vec::with_capacity(members.len());
+    for member in members {
+        if let Ok(user) = state.user_store.get_user(member.user_id).await {
+            recipients.push(user.email);
+        }
+    }
+    recipients
+}
+
+/// Resolve a target to `(relative path, canonical path)` pairs inside `base`
+pub fn resolve_target(
+    base: &Path,
+    target: &ScheduleTarget,
+) -> Result<Vec<(String, PathBuf)>, String> {
+    let canonical_base = base
+        .canonicalize()
+        .map_err(|e| format!("Server files directory invalid: {}", e))?;
+    match target {
+        ScheduleTarget::Files { paths } => paths
+            .iter()
+            .map(|relative| {
+                check_relative(relative)?;
+                let path = canonical_base
+                    .join(relative)
+                    .canonicalize()
+                    .map_err(|e| format!("File not found: {}: {}", relative, e))?;
+                if !path.starts_with(&canonical_base) || !path.is_file() {
+                    return Err(format!("File not found: {}", relative));
+                }
+                Ok((relative.clone(), path))
+            })
+            .collect(),
+        ScheduleTarget::NewestMatching { pattern, count } => {
+            check_relative(pattern)?;
+            let full_pattern = canonical_base.join(pattern);
+            let entries = glob::glob(&full_pattern.to_string_lossy())
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+            let mut matches = entries
+                .filter_map(Result::ok)
+                .filter_map(|path| path.canonicalize().ok())
+                .filter(|path| path.starts_with(&canonical_base) && path.is_file())
+                .filter_map(|path| {
+                    let modified = path.metadata().and_then(|m| m.modified()).ok()?;
+                    Some((modified, path))
+                })
+                .collect::<Vec<_>>();
+            matches.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+            Ok(matches
+                .into_iter()
+                .take(*count)
+                .map(|(_, path)| {
+                    let relative = path
+                        .strip_prefix(&canonical_base)
+                        .unwrap_or(&path)
+                        .to_string_lossy()
+                        .to_string();
+                    (relative, path)
+                })
+                .collect())
+        }
+    }
+}
+
+fn check_relative(path: &str) -> Result<(), String> {
+    if Path::new(path).is_absolute() {
+        return Err("Absolute paths are not allowed".to_string());
+    }
+    if path.contains("..") {
+        return Err("Path traversal sequences are not allowed".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn schedule(cron: &str) -> AnalysisSchedule {
+        AnalysisSchedule::new(
+            "nightly QC".to_string(),
+            Uuid::new_v4(),
+            "admin@example.org".to_string(),
+            cron.parse().unwrap(),
+            ParameterPreset {
+                name: "default".to_string(),
+                parameters: DDAParameters::default(),
+            },
+            ScheduleTarget::Files {
+                paths: vec!["a.edf".to_string()],
+            },
+        )
+    }
+
+    #[test]
+    fn test_due_and_record_run_advance_schedule() {
+        let scheduler = Scheduler::new();
+        let mut s = schedule("0 3 * * *");
+        let next = s.next_run_at.unwrap();
+        scheduler.insert(s.clone());
+        assert!(scheduler
+            .due(next - chrono::Duration::minutes(1))
+            .is_empty());
+        assert_eq!(scheduler.due(next).len(), 1);
+
+        scheduler.record_run(
+            s.id,
+            ScheduleRun {
+                ran_at: next,
+                job_ids: vec![],
+                files: vec![],
+                error: None,
+            },
+            next,
+        );
+        let updated = scheduler.get(s.id).unwrap();
+        assert_eq!(updated.next_run_at, Some(next + chrono::Duration::days(1)));
+        assert!(updated.last_run.is_some());
+
+        s.enabled = false;
+        scheduler.insert(s);
+        assert!(scheduler.due(next + chrono::Duration::days(2)).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_newest_matching_files() {
+        let dir = std::env::temp_dir().join(format!("ddalab-schedule-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("night")).unwrap();
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for (i, name) in ["rec1.edf", "rec2.edf", "rec3.edf", "notes.txt"]
+            .iter()
+            .enumerate()
+        {
+            let file = std::fs::File::create(dir.join("night").join(name)).unwrap();
+            file.set_modified(epoch + Duration::from_secs(i as u64 * 60))
+                .unwrap();
+        }
+
+        let target = ScheduleTarget::NewestMatching {
+            pattern: "night/*.edf".to_string(),
+            count: 2,
+        };
+        let files = resolve_target(&dir, &target).unwrap();
+        let names = files.iter().map(|(r, _)| r.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["night/rec3.edf", "night/rec2.edf"]);
+
+        let escape = ScheduleTarget::Files {
+            paths: vec!["../etc/passwd".to_string()],
+        };
+        assert!(resolve_target(&dir, &escape).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
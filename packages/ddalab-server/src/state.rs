@@ -1,21 +1,45 @@
 use sqlx::PgPool;
 use std::sync::Arc;
 use std::time::Instant;
+use tracing::warn;
 
+use crate::announcements::AnnouncementBoard;
 use crate::auth::{AuthState, SessionManager};
 use crate::config::ServerConfig;
-use crate::jobs::{JobQueue, JobQueueConfig};
-use crate::storage::{SharedResultStore, UserStore};
-use crate::sync::UserRegistry;
+use crate::jobs::{
+    Executor, JobQueue, JobQueueConfig, LocalExecutor, RemoteExecutor, ResumableUploads,
+};
+use crate::maintenance::MaintenanceMode;
+use crate::metrics::ServerMetrics;
+use crate::notifications::EmailNotifier;
+use crate::relay::RelayHub;
+use crate::scheduler::Scheduler;
+use crate::storage::{PostgresOfflineMessageStore, SharedResultStore, UserStore};
+use crate::sync::{Federation, Mailbox, UserRegistry};
 
 /// Main server state shared across all handlers
 pub struct ServerState {
     pub config: ServerConfig,
     pub registry: UserRegistry,
+    /// Notices for users, queued while they are offline
+    pub mailbox: Mailbox,
+    /// Links to other institutions' brokers
+    pub federation: Option<Federation>,
+    /// Present when share downloads may go through the broker
+    pub relay: Option<RelayHub>,
     pub share_store: Arc<dyn SharedResultStore>,
     pub user_store: Arc<dyn UserStore>,
     pub auth_state: Arc<AuthState>,
     pub job_queue: Arc<JobQueue>,
+    pub uploads: Arc<ResumableUploads>,
+    /// Present when DDA runs on worker nodes or Kubernetes Jobs
+    pub remote_executor: Option<Arc<RemoteExecutor>>,
+    /// Present when SMTP is configured
+    pub notifier: Option<Arc<EmailNotifier>>,
+    pub maintenance: MaintenanceMode,
+    pub announcements: AnnouncementBoard,
+    pub metrics: ServerMetrics,
+    pub scheduler: Scheduler,
     pub start_time: Instant,
     pub db_pool: PgPool,
 }
@@ -27,27 +51,80 @@ impl ServerState {
         user_store: Arc<dyn UserStore>,
         db_pool: PgPool,
     ) -> Self {
-        let session_manager = SessionManager::new(config.session_timeout_seconds);
-        let auth_state = Arc::new(AuthState::new(
-            session_manager,
-            &config.broker_password,
-            config.require_auth,
-        ));
+        let session_manager = SessionManager::new(config.session_timeout_seconds)
+            .with_absolute_timeout(config.session_absolute_timeout_seconds);
+        let metrics = ServerMetrics::new();
+        let auth_state = Arc::new(
+            AuthState::new(
+                session_manager,
+                &config.broker_password,
+                config.require_auth,
+            )
+            .with_metrics(metrics.clone()),
+        );
+
+        let remote_executor = config
+            .remote_execution
+            .as_ref()
+            .map(|remote| Arc::new(RemoteExecutor::new(remote)));
+        let executor: Arc<dyn Executor> = match &remote_executor {
+            Some(remote) => remote.clone(),
+            None => Arc::new(
+                config
+                    .dda_binary_path
+                    .clone()
+                    .map(LocalExecutor::new)
+                    .unwrap_or_default(),
+            ),
+        };
 
         // Initialize job queue with config
         let job_queue_config = JobQueueConfig {
             max_concurrent_jobs: config.max_concurrent_jobs,
             notification_capacity: 1000,
+            run_policy: config.run_policy,
+            user_quota: config.user_quota,
+            resource_limits: config.resource_limits,
+            worker_pools: config.worker_pools.clone(),
+            storage_encryption: config.storage_encryption.clone(),
+            upload_directory: config.upload_directory.clone(),
+            output_directory: config.job_output_directory.clone(),
+            executor,
         };
         let job_queue = Arc::new(JobQueue::new(job_queue_config));
+        let uploads = Arc::new(ResumableUploads::new(&config.upload_directory));
+        let notifier = config.smtp.as_ref().and_then(|smtp| {
+            EmailNotifier::new(smtp, &config.institution_name)
+                .map_err(|e| warn!("Email notifications disabled: {}", e))
+                .ok()
+                .map(Arc::new)
+        });
+
+        let federation = config.federation.clone().map(Federation::new);
+        let mailbox = Mailbox::new(
+            Arc::new(PostgresOfflineMessageStore::new(db_pool.clone())),
+            config.offline_queue,
+        )
+        .with_federation(federation.clone());
+        let relay = config.relay.clone().map(RelayHub::new);
 
         Self {
             config,
             registry: UserRegistry::new(),
+            mailbox,
+            federation,
+            relay,
             share_store,
             user_store,
             auth_state,
             job_queue,
+            uploads,
+            remote_executor,
+            notifier,
+            maintenance: MaintenanceMode::new(),
+            announcements: AnnouncementBoard::new(),
+            metrics,
+            scheduler: Scheduler::new(),
             start_time: Instant::now(),
             db_pool,
         }
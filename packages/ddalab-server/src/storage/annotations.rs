@@ -0,0 +1,273 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::traits::{StorageError, StorageResult};
+use super::types::UserId;
+
+/// An annotation of a recording, shared through the server
+///
+/// Recordings are identified by the SHA-256 of their contents, so copies of
+/// a file at different paths on each desktop share their annotations.
+#[derive(Debug, Clone, Serialize)]
+pub struct Annotation {
+    /// Chosen by the client that created it, so offline edits sync as-is
+    pub id: String,
+    pub file_hash: String,
+    pub owner: UserId,
+    /// Team whose members see and edit the annotation; private when unset
+    pub team_id: Option<Uuid>,
+    pub label: String,
+    pub notes: String,
+    pub channel_name: Option<String>,
+    pub start_seconds: f64,
+    pub end_seconds: Option<f64>,
+    /// Client fields the server does not interpret, e.g. color
+    pub payload: serde_json::Value,
+    /// Increases with every change to any annotation; clients sync from it
+    pub revision: i64,
+    /// Deleted annotations are kept so the deletion syncs too
+    pub deleted: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Annotation store trait
+#[async_trait]
+pub trait AnnotationStore: Send + Sync {
+    /// Create or replace an annotation under the next revision
+    ///
+    /// With `expected_revision`, an existing annotation is only replaced
+    /// while it is still at that revision. The owner and creation time of
+    /// an existing annotation are kept.
+    async fn save_annotation(
+        &self,
+        annotation: &Annotation,
+        expected_revision: Option<i64>,
+    ) -> StorageResult<Annotation>;
+
+    /// Look up one annotation, deleted or not
+    async fn get_annotation(&self, id: &str) -> StorageResult<Annotation>;
+
+    /// Annotations a user can see, their own and their teams', changed
+    /// after revision `since`, oldest change first
+    async fn changed_annotations(
+        &self,
+        user_id: &str,
+        file_hash: Option<&str>,
+        since: i64,
+        include_deleted: bool,
+        limit: i64,
+    ) -> StorageResult<Vec<Annotation>>;
+
+    /// Mark an annotation deleted under the next revision
+    async fn delete_annotation(
+        &self,
+        id: &str,
+        expected_revision: Option<i64>,
+    ) -> StorageResult<Annotation>;
+}
+
+/// PostgreSQL implementation of AnnotationStore
+pub struct PostgresAnnotationStore {
+    pool: PgPool,
+}
+
+impl PostgresAnnotationStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for annotations
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query("CREATE SEQUENCE IF NOT EXISTS annotation_revisions")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS annotations (
+                id VARCHAR(128) PRIMARY KEY,
+                file_hash CHAR(64) NOT NULL,
+                owner VARCHAR(255) NOT NULL,
+                team_id UUID REFERENCES teams(id) ON DELETE SET NULL,
+                label VARCHAR(256) NOT NULL,
+                notes TEXT NOT NULL DEFAULT '',
+                channel_name VARCHAR(256),
+                start_seconds DOUBLE PRECISION NOT NULL,
+                end_seconds DOUBLE PRECISION,
+                payload JSONB NOT NULL DEFAULT '{}',
+                revision BIGINT NOT NULL,
+                deleted BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_annotations_file ON annotations(file_hash, revision)",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_annotations_revision ON annotations(revision)")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// A conditional write that found the annotation at another revision
+fn conflict(current: &Annotation) -> StorageError {
+    StorageError::Conflict(format!("Annotation is at revision {}", current.revision))
+}
+
+fn annotation_from_row(row: &sqlx::postgres::PgRow) -> Annotation {
+    Annotation {
+        id: row.get("id"),
+        file_hash: row.get("file_hash"),
+        owner: row.get("owner"),
+        team_id: row.get("team_id"),
+        label: row.get("label"),
+        notes: row.get("notes"),
+        channel_name: row.get("channel_name"),
+        start_seconds: row.get("start_seconds"),
+        end_seconds: row.get("end_seconds"),
+        payload: row.get("payload"),
+        revision: row.get("revision"),
+        deleted: row.get("deleted"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+#[async_trait]
+impl AnnotationStore for PostgresAnnotationStore {
+    async fn save_annotation(
+        &self,
+        annotation: &Annotation,
+        expected_revision: Option<i64>,
+    ) -> StorageResult<Annotation> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO annotations
+                (id, file_hash, owner, team_id, label, notes, channel_name,
+                 start_seconds, end_seconds, payload, revision, deleted, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10,
+                    nextval('annotation_revisions'), FALSE, $11, $11)
+            ON CONFLICT (id) DO UPDATE SET
+                file_hash = EXCLUDED.file_hash,
+                team_id = EXCLUDED.team_id,
+                label = EXCLUDED.label,
+                notes = EXCLUDED.notes,
+                channel_name = EXCLUDED.channel_name,
+                start_seconds = EXCLUDED.start_seconds,
+                end_seconds = EXCLUDED.end_seconds,
+                payload = EXCLUDED.payload,
+                revision = EXCLUDED.revision,
+                deleted = FALSE,
+                updated_at = EXCLUDED.updated_at
+            WHERE $12::BIGINT IS NULL OR annotations.revision = $12
+            RETURNING *
+            "#,
+        )
+        .bind(&annotation.id)
+        .bind(&annotation.file_hash)
+        .bind(&annotation.owner)
+        .bind(annotation.team_id)
+        .bind(&annotation.label)
+        .bind(&annotation.notes)
+        .bind(&annotation.channel_name)
+        .bind(annotation.start_seconds)
+        .bind(annotation.end_seconds)
+        .bind(&annotation.payload)
+        .bind(annotation.updated_at)
+        .bind(expected_revision)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(annotation_from_row(&row)),
+            None => Err(conflict(&self.get_annotation(&annotation.id).await?)),
+        }
+    }
+
+    async fn get_annotation(&self, id: &str) -> StorageResult<Annotation> {
+        let row = sqlx::query("SELECT * FROM annotations WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| StorageError::NotFound("Annotation not found".to_string()))?;
+
+        Ok(annotation_from_row(&row))
+    }
+
+    async fn changed_annotations(
+        &self,
+        user_id: &str,
+        file_hash: Option<&str>,
+        since: i64,
+        include_deleted: bool,
+        limit: i64,
+    ) -> StorageResult<Vec<Annotation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT a.* FROM annotations a
+            WHERE a.revision > $2
+              AND ($3::TEXT IS NULL OR a.file_hash = $3)
+              AND ($4 OR NOT a.deleted)
+              AND (a.owner = $1 OR a.team_id IN (
+                    SELECT tm.team_id FROM team_members tm
+                    JOIN users u ON u.id = tm.user_id
+                    WHERE u.email = $1
+              ))
+            ORDER BY a.revision
+            LIMIT $5
+            "#,
+        )
+        .bind(user_id)
+        .bind(since)
+        .bind(file_hash)
+        .bind(include_deleted)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(annotation_from_row).collect())
+    }
+
+    async fn delete_annotation(
+        &self,
+        id: &str,
+        expected_revision: Option<i64>,
+    ) -> StorageResult<Annotation> {
+        let row = sqlx::query(
+            r#"
+            UPDATE annotations
+            SET deleted = TRUE, revision = nextval('annotation_revisions'), updated_at = NOW()
+            WHERE id = $1 AND NOT deleted AND ($2::BIGINT IS NULL OR revision = $2)
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(expected_revision)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Ok(annotation_from_row(&row)),
+            None => match self.get_annotation(id).await? {
+                current if current.deleted => {
+                    Err(StorageError::NotFound("Annotation not found".to_string()))
+                }
+                current => Err(conflict(&current)),
+            },
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::traits::{StorageError, StorageResult};
+use super::types::UserId;
+
+/// How prominently clients show an announcement
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnouncementSeverity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+impl AnnouncementSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnnouncementSeverity::Info => "info",
+            AnnouncementSeverity::Warning => "warning",
+            AnnouncementSeverity::Critical => "critical",
+        }
+    }
+
+    fn from_str_lossy(s: &str) -> Self {
+        match s {
+            "warning" => AnnouncementSeverity::Warning,
+            "critical" => AnnouncementSeverity::Critical,
+            _ => AnnouncementSeverity::Info,
+        }
+    }
+}
+
+/// A message from the administrators to every user of the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Announcement {
+    pub id: Uuid,
+    pub title: String,
+    pub message: String,
+    pub severity: AnnouncementSeverity,
+    /// Shown from this time on
+    pub starts_at: DateTime<Utc>,
+    /// Hidden from this time on; shown until withdrawn when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<DateTime<Utc>>,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Announcement {
+    /// Whether the announcement is shown at `now`
+    pub fn is_current(&self, now: DateTime<Utc>) -> bool {
+        self.starts_at <= now && self.ends_at.is_none_or(|end| now < end)
+    }
+}
+
+/// Fields of an announcement to be created
+#[derive(Debug)]
+pub struct CreateAnnouncement<'a> {
+    pub title: &'a str,
+    pub message: &'a str,
+    pub severity: AnnouncementSeverity,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub created_by: &'a str,
+}
+
+/// Announcement store trait
+#[async_trait]
+pub trait AnnouncementStore: Send + Sync {
+    /// Record a new announcement
+    async fn create_announcement(
+        &self,
+        announcement: CreateAnnouncement<'_>,
+    ) -> StorageResult<Announcement>;
+
+    /// Every announcement, newest first
+    async fn list_announcements(&self) -> StorageResult<Vec<Announcement>>;
+
+    /// Announcements that are current or scheduled, oldest first
+    async fn unexpired_announcements(&self) -> StorageResult<Vec<Announcement>>;
+
+    /// Withdraw an announcement
+    async fn delete_announcement(&self, id: Uuid) -> StorageResult<()>;
+
+    /// Record that a user has read an announcement
+    async fn mark_read(&self, id: Uuid, user_id: &str) -> StorageResult<()>;
+
+    /// IDs of the announcements a user has read
+    async fn read_announcements(&self, user_id: &str) -> StorageResult<Vec<Uuid>>;
+}
+
+/// PostgreSQL implementation of AnnouncementStore
+pub struct PostgresAnnouncementStore {
+    pool: PgPool,
+}
+
+impl PostgresAnnouncementStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for announcements
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS announcements (
+                id UUID PRIMARY KEY,
+                title VARCHAR(255) NOT NULL,
+                message TEXT NOT NULL,
+                severity VARCHAR(16) NOT NULL DEFAULT 'info',
+                starts_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                ends_at TIMESTAMPTZ,
+                created_by VARCHAR(255) NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS announcement_reads (
+                announcement_id UUID NOT NULL REFERENCES announcements(id) ON DELETE CASCADE,
+                user_id VARCHAR(255) NOT NULL,
+                read_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                PRIMARY KEY (announcement_id, user_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_announcement_reads_user ON announcement_reads(user_id)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn announcement_from_row(row: &sqlx::postgres::PgRow) -> Announcement {
+    Announcement {
+        id: row.get("id"),
+        title: row.get("title"),
+        message: row.get("message"),
+        severity: AnnouncementSeverity::from_str_lossy(row.get("severity")),
+        starts_at: row.get("starts_at"),
+        ends_at: row.get("ends_at"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[async_trait]
+impl AnnouncementStore for PostgresAnnouncementStore {
+    async fn create_announcement(
+        &self,
+        announcement: CreateAnnouncement<'_>,
+    ) -> StorageResult<Announcement> {
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO announcements (id, title, message, severity, starts_at, ends_at, created_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(id)
+        .bind(announcement.title)
+        .bind(announcement.message)
+        .bind(announcement.severity.as_str())
+        .bind(announcement.starts_at)
+        .bind(announcement.ends_at)
+        .bind(announcement.created_by)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Announcement {
+            id,
+            title: announcement.title.to_string(),
+            message: announcement.message.to_string(),
+            severity: announcement.severity,
+            starts_at: announcement.starts_at,
+            ends_at: announcement.ends_at,
+            created_by: announcement.created_by.to_string(),
+            created_at: now,
+        })
+    }
+
+    async fn list_announcements(&self) -> StorageResult<Vec<Announcement>> {
+        let rows = sqlx::query("SELECT * FROM announcements ORDER BY created_at DESC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(announcement_from_row).collect())
+    }
+
+    async fn unexpired_announcements(&self) -> StorageResult<Vec<Announcement>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM announcements
+            WHERE ends_at IS NULL OR ends_at > NOW()
+            ORDER BY starts_at ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(announcement_from_row).collect())
+    }
+
+    async fn delete_announcement(&self, id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query("DELETE FROM announcements WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Announcement {}", id)));
+        }
+
+        Ok(())
+    }
+
+    async fn mark_read(&self, id: Uuid, user_id: &str) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO announcement_reads (announcement_id, user_id)
+            VALUES ($1, $2)
+            ON CONFLICT (announcement_id, user_id) DO NOTHING
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn read_announcements(&self, user_id: &str) -> StorageResult<Vec<Uuid>> {
+        let rows = sqlx::query("SELECT announcement_id FROM announcement_reads WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(|row| row.get("announcement_id")).collect())
+    }
+}
@@ -0,0 +1,208 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::traits::{StorageError, StorageResult};
+use super::types::UserId;
+
+/// A personal access token; the token itself is only known to its holder
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub name: String,
+    /// `read`, `submit` or `admin`
+    pub scope: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    /// Copied from the creating session's second-factor state
+    #[serde(skip)]
+    pub mfa_required: bool,
+    #[serde(skip)]
+    pub mfa_verified: bool,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Fields of a token to be created
+#[derive(Debug)]
+pub struct CreateApiToken<'a> {
+    pub user_id: &'a str,
+    pub name: &'a str,
+    pub scope: &'a str,
+    pub token_hash: &'a str,
+    pub mfa_required: bool,
+    pub mfa_verified: bool,
+    pub created_by: &'a str,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Access token store trait
+#[async_trait]
+pub trait ApiTokenStore: Send + Sync {
+    /// Record a new token
+    async fn create_token(&self, token: CreateApiToken<'_>) -> StorageResult<ApiToken>;
+
+    /// List a user's tokens
+    async fn list_tokens(&self, user_id: &str) -> StorageResult<Vec<ApiToken>>;
+
+    /// All tokens that have not expired, except those of suspended users
+    async fn active_tokens(&self) -> StorageResult<Vec<ApiToken>>;
+
+    /// Look up one token
+    async fn get_token(&self, id: Uuid) -> StorageResult<ApiToken>;
+
+    /// Remove a token
+    async fn delete_token(&self, id: Uuid) -> StorageResult<()>;
+}
+
+/// PostgreSQL implementation of ApiTokenStore
+pub struct PostgresApiTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresApiTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for access tokens
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_tokens (
+                id UUID PRIMARY KEY,
+                user_id VARCHAR(255) NOT NULL,
+                name VARCHAR(255) NOT NULL,
+                scope VARCHAR(16) NOT NULL,
+                token_hash VARCHAR(64) NOT NULL UNIQUE,
+                mfa_required BOOLEAN NOT NULL DEFAULT FALSE,
+                mfa_verified BOOLEAN NOT NULL DEFAULT FALSE,
+                created_by VARCHAR(255) NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                expires_at TIMESTAMPTZ
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_api_tokens_user ON api_tokens(user_id)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn token_from_row(row: &sqlx::postgres::PgRow) -> ApiToken {
+    ApiToken {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        name: row.get("name"),
+        scope: row.get("scope"),
+        token_hash: row.get("token_hash"),
+        mfa_required: row.get("mfa_required"),
+        mfa_verified: row.get("mfa_verified"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        expires_at: row.get("expires_at"),
+    }
+}
+
+#[async_trait]
+impl ApiTokenStore for PostgresApiTokenStore {
+    async fn create_token(&self, token: CreateApiToken<'_>) -> StorageResult<ApiToken> {
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO api_tokens (id, user_id, name, scope, token_hash, mfa_required, mfa_verified, created_by, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(id)
+        .bind(token.user_id)
+        .bind(token.name)
+        .bind(token.scope)
+        .bind(token.token_hash)
+        .bind(token.mfa_required)
+        .bind(token.mfa_verified)
+        .bind(token.created_by)
+        .bind(now)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(ApiToken {
+            id,
+            user_id: token.user_id.to_string(),
+            name: token.name.to_string(),
+            scope: token.scope.to_string(),
+            token_hash: token.token_hash.to_string(),
+            mfa_required: token.mfa_required,
+            mfa_verified: token.mfa_verified,
+            created_by: token.created_by.to_string(),
+            created_at: now,
+            expires_at: token.expires_at,
+        })
+    }
+
+    async fn list_tokens(&self, user_id: &str) -> StorageResult<Vec<ApiToken>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM api_tokens WHERE user_id = $1 ORDER BY created_at ASC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(token_from_row).collect())
+    }
+
+    async fn active_tokens(&self) -> StorageResult<Vec<ApiToken>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM api_tokens
+            WHERE (expires_at IS NULL OR expires_at > NOW())
+              AND user_id NOT IN (SELECT email FROM users WHERE NOT is_active)
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(token_from_row).collect())
+    }
+
+    async fn get_token(&self, id: Uuid) -> StorageResult<ApiToken> {
+        let row = sqlx::query("SELECT * FROM api_tokens WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| StorageError::NotFound(format!("Access token {}", id)))?;
+
+        Ok(token_from_row(&row))
+    }
+
+    async fn delete_token(&self, id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query("DELETE FROM api_tokens WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Access token {}", id)));
+        }
+
+        Ok(())
+    }
+}
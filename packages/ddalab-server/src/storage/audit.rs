@@ -1,7 +1,8 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Row};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
 use uuid::Uuid;
 
 use super::traits::{StorageError, StorageResult};
@@ -224,6 +225,7 @@ impl AuditEntryBuilder {
 #[derive(Debug, Default)]
 pub struct AuditQuery {
     pub user_id: Option<Uuid>,
+    pub user_email: Option<String>,
     pub action: Option<AuditAction>,
     pub resource_type: Option<String>,
     pub from_date: Option<DateTime<Utc>>,
@@ -233,6 +235,14 @@ pub struct AuditQuery {
     pub offset: Option<i64>,
 }
 
+/// One page of an export
+#[derive(Debug)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    /// Timestamp and ID of the page's last row; None after the last page
+    pub next: Option<(DateTime<Utc>, Uuid)>,
+}
+
 /// Audit store trait
 #[async_trait]
 pub trait AuditStore: Send + Sync {
@@ -250,6 +260,19 @@ pub trait AuditStore: Send + Sync {
 
     /// Count audit entries matching criteria
     async fn count(&self, query: AuditQuery) -> StorageResult<i64>;
+
+    /// Up to `limit` entries matching criteria, oldest first, starting
+    /// after the previous page's `next`; the query's limit and offset are
+    /// ignored
+    async fn export_page(
+        &self,
+        query: &AuditQuery,
+        after: Option<(DateTime<Utc>, Uuid)>,
+        limit: i64,
+    ) -> StorageResult<AuditPage>;
+
+    /// Delete entries older than `cutoff`, returning how many went
+    async fn prune_before(&self, cutoff: DateTime<Utc>) -> StorageResult<u64>;
 }
 
 /// PostgreSQL implementation of AuditStore
@@ -325,6 +348,57 @@ impl PostgresAuditStore {
     }
 }
 
+const AUDIT_COLUMNS: &str = "id, timestamp, user_id, user_email, action, resource_type, \
+    resource_id, ip_address, user_agent, http_method, http_path, http_status, details, success";
+
+/// Append `AND` clauses for the query's filters
+fn push_filters(sql: &mut QueryBuilder<'_, Postgres>, query: &AuditQuery) {
+    if let Some(user_id) = query.user_id {
+        sql.push(" AND user_id = ").push_bind(user_id);
+    }
+    if let Some(email) = &query.user_email {
+        sql.push(" AND user_email = ").push_bind(email.clone());
+    }
+    if let Some(action) = &query.action {
+        sql.push(" AND action = ").push_bind(action.as_str());
+    }
+    if let Some(resource_type) = &query.resource_type {
+        sql.push(" AND resource_type = ").push_bind(resource_type.clone());
+    }
+    if let Some(from) = query.from_date {
+        sql.push(" AND timestamp >= ").push_bind(from);
+    }
+    if let Some(to) = query.to_date {
+        sql.push(" AND timestamp <= ").push_bind(to);
+    }
+    if let Some(success) = query.success_only {
+        sql.push(" AND success = ").push_bind(success);
+    }
+}
+
+/// Entry from a row, skipping actions this version does not know
+fn entry_from_row(row: &PgRow) -> Option<AuditEntry> {
+    let action_str: String = row.get("action");
+    let action = AuditAction::from_str(&action_str)?;
+
+    Some(AuditEntry {
+        id: row.get("id"),
+        timestamp: row.get("timestamp"),
+        user_id: row.get("user_id"),
+        user_email: row.get("user_email"),
+        action,
+        resource_type: row.get("resource_type"),
+        resource_id: row.get("resource_id"),
+        ip_address: row.get("ip_address"),
+        user_agent: row.get("user_agent"),
+        http_method: row.get("http_method"),
+        http_path: row.get("http_path"),
+        http_status: row.get("http_status"),
+        details: row.get("details"),
+        success: row.get("success"),
+    })
+}
+
 #[async_trait]
 impl AuditStore for PostgresAuditStore {
     async fn log(&self, entry: AuditEntry) -> StorageResult<()> {
@@ -358,83 +432,16 @@ impl AuditStore for PostgresAuditStore {
     }
 
     async fn query(&self, query: AuditQuery) -> StorageResult<Vec<AuditEntry>> {
-        let mut sql = String::from(
-            r#"
-            SELECT id, timestamp, user_id, user_email, action, resource_type, resource_id,
-                   ip_address, user_agent, http_method, http_path, http_status, details, success
-            FROM audit_logs
-            WHERE 1=1
-            "#,
-        );
-
-        let mut params: Vec<String> = Vec::new();
-        let mut param_idx = 1;
-
-        if query.user_id.is_some() {
-            sql.push_str(&format!(" AND user_id = ${}", param_idx));
-            param_idx += 1;
-        }
+        let mut sql = QueryBuilder::new(format!("SELECT {} FROM audit_logs WHERE 1=1", AUDIT_COLUMNS));
+        push_filters(&mut sql, &query);
+        sql.push(" ORDER BY timestamp DESC LIMIT ")
+            .push_bind(query.limit.unwrap_or(100))
+            .push(" OFFSET ")
+            .push_bind(query.offset.unwrap_or(0));
 
-        if query.action.is_some() {
-            sql.push_str(&format!(" AND action = ${}", param_idx));
-            param_idx += 1;
-        }
+        let rows = sql.build().fetch_all(&self.pool).await?;
 
-        if query.resource_type.is_some() {
-            sql.push_str(&format!(" AND resource_type = ${}", param_idx));
-            param_idx += 1;
-        }
-
-        if query.from_date.is_some() {
-            sql.push_str(&format!(" AND timestamp >= ${}", param_idx));
-            param_idx += 1;
-        }
-
-        if query.to_date.is_some() {
-            sql.push_str(&format!(" AND timestamp <= ${}", param_idx));
-            param_idx += 1;
-        }
-
-        if let Some(success) = query.success_only {
-            sql.push_str(&format!(" AND success = ${}", param_idx));
-            param_idx += 1;
-        }
-
-        sql.push_str(" ORDER BY timestamp DESC");
-
-        let limit = query.limit.unwrap_or(100);
-        let offset = query.offset.unwrap_or(0);
-        sql.push_str(&format!(" LIMIT {} OFFSET {}", limit, offset));
-
-        // Build dynamic query - for now, use simpler approach
-        let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
-            .await?;
-
-        Ok(rows
-            .into_iter()
-            .filter_map(|row| {
-                let action_str: String = row.get("action");
-                let action = AuditAction::from_str(&action_str)?;
-
-                Some(AuditEntry {
-                    id: row.get("id"),
-                    timestamp: row.get("timestamp"),
-                    user_id: row.get("user_id"),
-                    user_email: row.get("user_email"),
-                    action,
-                    resource_type: row.get("resource_type"),
-                    resource_id: row.get("resource_id"),
-                    ip_address: row.get("ip_address"),
-                    user_agent: row.get("user_agent"),
-                    http_method: row.get("http_method"),
-                    http_path: row.get("http_path"),
-                    http_status: row.get("http_status"),
-                    details: row.get("details"),
-                    success: row.get("success"),
-                })
-            })
-            .collect())
+        Ok(rows.iter().filter_map(entry_from_row).collect())
     }
 
     async fn recent(&self, limit: i64) -> StorageResult<Vec<AuditEntry>> {
@@ -451,30 +458,7 @@ impl AuditStore for PostgresAuditStore {
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows
-            .into_iter()
-            .filter_map(|row| {
-                let action_str: String = row.get("action");
-                let action = AuditAction::from_str(&action_str)?;
-
-                Some(AuditEntry {
-                    id: row.get("id"),
-                    timestamp: row.get("timestamp"),
-                    user_id: row.get("user_id"),
-                    user_email: row.get("user_email"),
-                    action,
-                    resource_type: row.get("resource_type"),
-                    resource_id: row.get("resource_id"),
-                    ip_address: row.get("ip_address"),
-                    user_agent: row.get("user_agent"),
-                    http_method: row.get("http_method"),
-                    http_path: row.get("http_path"),
-                    http_status: row.get("http_status"),
-                    details: row.get("details"),
-                    success: row.get("success"),
-                })
-            })
-            .collect())
+        Ok(rows.iter().filter_map(entry_from_row).collect())
     }
 
     async fn for_user(&self, user_id: Uuid, limit: i64) -> StorageResult<Vec<AuditEntry>> {
@@ -493,42 +477,53 @@ impl AuditStore for PostgresAuditStore {
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows
-            .into_iter()
-            .filter_map(|row| {
-                let action_str: String = row.get("action");
-                let action = AuditAction::from_str(&action_str)?;
-
-                Some(AuditEntry {
-                    id: row.get("id"),
-                    timestamp: row.get("timestamp"),
-                    user_id: row.get("user_id"),
-                    user_email: row.get("user_email"),
-                    action,
-                    resource_type: row.get("resource_type"),
-                    resource_id: row.get("resource_id"),
-                    ip_address: row.get("ip_address"),
-                    user_agent: row.get("user_agent"),
-                    http_method: row.get("http_method"),
-                    http_path: row.get("http_path"),
-                    http_status: row.get("http_status"),
-                    details: row.get("details"),
-                    success: row.get("success"),
-                })
-            })
-            .collect())
+        Ok(rows.iter().filter_map(entry_from_row).collect())
     }
 
     async fn count(&self, query: AuditQuery) -> StorageResult<i64> {
-        // Simplified count - just count all for now
-        let row = sqlx::query(
-            r#"
-            SELECT COUNT(*) as count FROM audit_logs
-            "#,
-        )
-        .fetch_one(&self.pool)
-        .await?;
+        let mut sql = QueryBuilder::new("SELECT COUNT(*) as count FROM audit_logs WHERE 1=1");
+        push_filters(&mut sql, &query);
+        let row = sql.build().fetch_one(&self.pool).await?;
 
         Ok(row.get("count"))
     }
+
+    async fn export_page(
+        &self,
+        query: &AuditQuery,
+        after: Option<(DateTime<Utc>, Uuid)>,
+        limit: i64,
+    ) -> StorageResult<AuditPage> {
+        let mut sql = QueryBuilder::new(format!("SELECT {} FROM audit_logs WHERE 1=1", AUDIT_COLUMNS));
+        push_filters(&mut sql, query);
+        if let Some((timestamp, id)) = after {
+            sql.push(" AND (timestamp, id) > (")
+                .push_bind(timestamp)
+                .push(", ")
+                .push_bind(id)
+                .push(")");
+        }
+        sql.push(" ORDER BY timestamp, id LIMIT ").push_bind(limit);
+
+        let rows = sql.build().fetch_all(&self.pool).await?;
+
+        // Rows of unknown actions are dropped, so the cursor follows the rows
+        let next = match rows.last() {
+            Some(row) if rows.len() as i64 == limit => Some((row.get("timestamp"), row.get("id"))),
+            _ => None,
+        };
+        Ok(AuditPage {
+            entries: rows.iter().filter_map(entry_from_row).collect(),
+            next,
+        })
+    }
+
+    async fn prune_before(&self, cutoff: DateTime<Utc>) -> StorageResult<u64> {
+        let result = sqlx::query("DELETE FROM audit_logs WHERE timestamp < $1")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
 }
@@ -0,0 +1,344 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::traits::{StorageError, StorageResult};
+use super::types::UserId;
+
+/// What a grant lets its holder do with a dataset's files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetPermission {
+    /// See the files in listings
+    Read,
+    /// See the files and submit jobs on them
+    Submit,
+}
+
+impl DatasetPermission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Submit => "submit",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "submit" => Self::Submit,
+            _ => Self::Read,
+        }
+    }
+}
+
+/// Who a grant is for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum Grantee {
+    /// A user, by email
+    User(UserId),
+    /// Every member of a team
+    Team(Uuid),
+}
+
+impl Grantee {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::User(_) => "user",
+            Self::Team(_) => "team",
+        }
+    }
+
+    fn id(&self) -> String {
+        match self {
+            Self::User(email) => email.clone(),
+            Self::Team(team_id) => team_id.to_string(),
+        }
+    }
+
+    fn from_parts(kind: &str, id: String) -> Self {
+        match (kind, Uuid::try_parse(&id)) {
+            ("team", Ok(team_id)) => Self::Team(team_id),
+            _ => Self::User(id),
+        }
+    }
+}
+
+/// A directory of the server files that only its owner, the holders of its
+/// grants and administrators can use
+#[derive(Debug, Clone, Serialize)]
+pub struct Dataset {
+    pub id: Uuid,
+    /// Directory relative to the server files directory, e.g. `studies/sleep`
+    pub path: String,
+    pub owner: UserId,
+    pub description: Option<String>,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Access to a dataset for a user or team
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetGrant {
+    pub id: Uuid,
+    pub dataset_id: Uuid,
+    pub grantee: Grantee,
+    pub permission: DatasetPermission,
+    pub granted_by: UserId,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Dataset access control store trait
+#[async_trait]
+pub trait DatasetStore: Send + Sync {
+    /// Register a dataset; each directory can be registered once
+    async fn create_dataset(&self, dataset: &Dataset) -> StorageResult<()>;
+
+    /// Change a dataset's owner and description
+    async fn update_dataset(&self, dataset: &Dataset) -> StorageResult<()>;
+
+    /// Look up one dataset
+    async fn get_dataset(&self, id: Uuid) -> StorageResult<Dataset>;
+
+    /// Every dataset by path
+    async fn list_datasets(&self) -> StorageResult<Vec<Dataset>>;
+
+    /// Remove a dataset and its grants, opening its files up again
+    async fn delete_dataset(&self, id: Uuid) -> StorageResult<()>;
+
+    /// Grant access, replacing the grantee's previous permission
+    async fn save_grant(&self, grant: &DatasetGrant) -> StorageResult<DatasetGrant>;
+
+    /// A dataset's grants
+    async fn list_grants(&self, dataset_id: Uuid) -> StorageResult<Vec<DatasetGrant>>;
+
+    /// Withdraw a grant
+    async fn delete_grant(&self, dataset_id: Uuid, grant_id: Uuid) -> StorageResult<()>;
+
+    /// Grants held by a user directly or through their teams
+    async fn grants_for_user(&self, user_id: &str) -> StorageResult<Vec<DatasetGrant>>;
+}
+
+/// PostgreSQL implementation of DatasetStore
+pub struct PostgresDatasetStore {
+    pool: PgPool,
+}
+
+impl PostgresDatasetStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for datasets and their grants
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS datasets (
+                id UUID PRIMARY KEY,
+                path TEXT NOT NULL UNIQUE,
+                owner VARCHAR(255) NOT NULL,
+                description TEXT,
+                created_by VARCHAR(255) NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS dataset_grants (
+                id UUID PRIMARY KEY,
+                dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+                grantee_kind VARCHAR(10) NOT NULL,
+                grantee_id VARCHAR(255) NOT NULL,
+                permission VARCHAR(10) NOT NULL,
+                granted_by VARCHAR(255) NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                UNIQUE (dataset_id, grantee_kind, grantee_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_dataset_grants_grantee ON dataset_grants(grantee_kind, grantee_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn dataset_from_row(row: &sqlx::postgres::PgRow) -> Dataset {
+    Dataset {
+        id: row.get("id"),
+        path: row.get("path"),
+        owner: row.get("owner"),
+        description: row.get("description"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn grant_from_row(row: &sqlx::postgres::PgRow) -> DatasetGrant {
+    let kind: String = row.get("grantee_kind");
+    let permission: String = row.get("permission");
+    DatasetGrant {
+        id: row.get("id"),
+        dataset_id: row.get("dataset_id"),
+        grantee: Grantee::from_parts(&kind, row.get("grantee_id")),
+        permission: DatasetPermission::parse(&permission),
+        granted_by: row.get("granted_by"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[async_trait]
+impl DatasetStore for PostgresDatasetStore {
+    async fn create_dataset(&self, dataset: &Dataset) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO datasets (id, path, owner, description, created_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(dataset.id)
+        .bind(&dataset.path)
+        .bind(&dataset.owner)
+        .bind(&dataset.description)
+        .bind(&dataset.created_by)
+        .bind(dataset.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_err) if db_err.is_unique_violation() => {
+                StorageError::DuplicateName(dataset.path.clone())
+            }
+            _ => StorageError::Database(e),
+        })?;
+
+        Ok(())
+    }
+
+    async fn update_dataset(&self, dataset: &Dataset) -> StorageResult<()> {
+        let result = sqlx::query("UPDATE datasets SET owner = $2, description = $3 WHERE id = $1")
+            .bind(dataset.id)
+            .bind(&dataset.owner)
+            .bind(&dataset.description)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound("Dataset not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn get_dataset(&self, id: Uuid) -> StorageResult<Dataset> {
+        let row = sqlx::query("SELECT * FROM datasets WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| StorageError::NotFound("Dataset not found".to_string()))?;
+
+        Ok(dataset_from_row(&row))
+    }
+
+    async fn list_datasets(&self) -> StorageResult<Vec<Dataset>> {
+        let rows = sqlx::query("SELECT * FROM datasets ORDER BY path")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(dataset_from_row).collect())
+    }
+
+    async fn delete_dataset(&self, id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query("DELETE FROM datasets WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound("Dataset not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn save_grant(&self, grant: &DatasetGrant) -> StorageResult<DatasetGrant> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO dataset_grants
+                (id, dataset_id, grantee_kind, grantee_id, permission, granted_by, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (dataset_id, grantee_kind, grantee_id) DO UPDATE SET
+                permission = EXCLUDED.permission,
+                granted_by = EXCLUDED.granted_by,
+                created_at = EXCLUDED.created_at
+            RETURNING *
+            "#,
+        )
+        .bind(grant.id)
+        .bind(grant.dataset_id)
+        .bind(grant.grantee.kind())
+        .bind(grant.grantee.id())
+        .bind(grant.permission.as_str())
+        .bind(&grant.granted_by)
+        .bind(grant.created_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(grant_from_row(&row))
+    }
+
+    async fn list_grants(&self, dataset_id: Uuid) -> StorageResult<Vec<DatasetGrant>> {
+        let rows = sqlx::query(
+            "SELECT * FROM dataset_grants WHERE dataset_id = $1 ORDER BY grantee_kind, grantee_id",
+        )
+        .bind(dataset_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(grant_from_row).collect())
+    }
+
+    async fn delete_grant(&self, dataset_id: Uuid, grant_id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query("DELETE FROM dataset_grants WHERE id = $1 AND dataset_id = $2")
+            .bind(grant_id)
+            .bind(dataset_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound("Grant not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn grants_for_user(&self, user_id: &str) -> StorageResult<Vec<DatasetGrant>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT g.* FROM dataset_grants g
+            WHERE (g.grantee_kind = 'user' AND g.grantee_id = $1)
+               OR (g.grantee_kind = 'team' AND g.grantee_id IN (
+                    SELECT tm.team_id::text FROM team_members tm
+                    JOIN users u ON u.id = tm.user_id
+                    WHERE u.email = $1
+               ))
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(grant_from_row).collect())
+    }
+}
@@ -0,0 +1,374 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::traits::StorageResult;
+
+/// Kind of resource that left the server
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EgressKind {
+    /// Job result file downloaded by a client
+    JobResultDownload,
+    /// Share metadata/content served to a recipient
+    ShareAccess,
+    /// Shared result relayed from its owner's client to a recipient
+    RelayDownload,
+}
+
+impl EgressKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::JobResultDownload => "job_result_download",
+            Self::ShareAccess => "share_access",
+            Self::RelayDownload => "relay_download",
+        }
+    }
+}
+
+impl std::str::FromStr for EgressKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "job_result_download" => Ok(Self::JobResultDownload),
+            "share_access" => Ok(Self::ShareAccess),
+            "relay_download" => Ok(Self::RelayDownload),
+            other => Err(format!("Unknown egress kind '{}'", other)),
+        }
+    }
+}
+
+/// One record in the egress ledger
+#[derive(Debug, Clone, Serialize)]
+pub struct EgressEntry {
+    pub id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub kind: EgressKind,
+    /// User who received the data (email, or "anonymous")
+    pub user_id: String,
+    pub ip_address: Option<String>,
+    /// Job ID or share token
+    pub resource_id: String,
+    /// Source file the egressed data derives from (dataset lineage)
+    pub source_dataset: Option<String>,
+    pub bytes: i64,
+}
+
+impl EgressEntry {
+    pub fn new(kind: EgressKind, user_id: &str, resource_id: &str, bytes: usize) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            kind,
+            user_id: user_id.to_string(),
+            ip_address: None,
+            resource_id: resource_id.to_string(),
+            source_dataset: None,
+            bytes: bytes as i64,
+        }
+    }
+
+    pub fn ip_address(mut self, ip: &str) -> Self {
+        self.ip_address = Some(ip.to_string());
+        self
+    }
+
+    pub fn source_dataset(mut self, dataset: &str) -> Self {
+        self.source_dataset = Some(dataset.to_string());
+        self
+    }
+}
+
+/// Egress aggregated for a single dataset over a reporting window
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetEgressSummary {
+    /// Source dataset (None for egress without known lineage)
+    pub dataset: Option<String>,
+    pub event_count: i64,
+    pub total_bytes: i64,
+    pub unique_users: i64,
+    pub unique_ips: i64,
+    pub first_at: DateTime<Utc>,
+    pub last_at: DateTime<Utc>,
+}
+
+/// Egress ledger store trait
+#[async_trait]
+pub trait EgressStore: Send + Sync {
+    /// Append an entry to the ledger
+    async fn record(&self, entry: EgressEntry) -> StorageResult<()>;
+
+    /// Aggregate egress per dataset for entries within [from, to]
+    async fn report_by_dataset(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> StorageResult<Vec<DatasetEgressSummary>>;
+
+    /// List raw ledger entries for a dataset within [from, to]
+    async fn entries_for_dataset(
+        &self,
+        dataset: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> StorageResult<Vec<EgressEntry>>;
+}
+
+/// PostgreSQL implementation of EgressStore
+pub struct PostgresEgressStore {
+    pool: PgPool,
+}
+
+impl PostgresEgressStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for the egress ledger
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS egress_ledger (
+                id UUID PRIMARY KEY,
+                timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                kind VARCHAR(50) NOT NULL,
+                user_id VARCHAR(255) NOT NULL,
+                ip_address VARCHAR(45),
+                resource_id VARCHAR(255) NOT NULL,
+                source_dataset TEXT,
+                bytes BIGINT NOT NULL DEFAULT 0
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_egress_timestamp ON egress_ledger(timestamp DESC)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_egress_dataset ON egress_ledger(source_dataset, timestamp)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EgressStore for PostgresEgressStore {
+    async fn record(&self, entry: EgressEntry) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO egress_ledger (
+                id, timestamp, kind, user_id, ip_address, resource_id, source_dataset, bytes
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(entry.id)
+        .bind(entry.timestamp)
+        .bind(entry.kind.as_str())
+        .bind(&entry.user_id)
+        .bind(&entry.ip_address)
+        .bind(&entry.resource_id)
+        .bind(&entry.source_dataset)
+        .bind(entry.bytes)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn report_by_dataset(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> StorageResult<Vec<DatasetEgressSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT source_dataset,
+                   COUNT(*) AS event_count,
+                   COALESCE(SUM(bytes), 0)::BIGINT AS total_bytes,
+                   COUNT(DISTINCT user_id) AS unique_users,
+                   COUNT(DISTINCT ip_address) AS unique_ips,
+                   MIN(timestamp) AS first_at,
+                   MAX(timestamp) AS last_at
+            FROM egress_ledger
+            WHERE timestamp >= $1 AND timestamp <= $2
+            GROUP BY source_dataset
+            ORDER BY total_bytes DESC
+            "#,
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DatasetEgressSummary {
+                dataset: row.get("source_dataset"),
+                event_count: row.get("event_count"),
+                total_bytes: row.get("total_bytes"),
+                unique_users: row.get("unique_users"),
+                unique_ips: row.get("unique_ips"),
+                first_at: row.get("first_at"),
+                last_at: row.get("last_at"),
+            })
+            .collect())
+    }
+
+    async fn entries_for_dataset(
+        &self,
+        dataset: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> StorageResult<Vec<EgressEntry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, timestamp, kind, user_id, ip_address, resource_id, source_dataset, bytes
+            FROM egress_ledger
+            WHERE source_dataset = $1 AND timestamp >= $2 AND timestamp <= $3
+            ORDER BY timestamp DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(dataset)
+        .bind(from)
+        .bind(to)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let kind_str: String = row.get("kind");
+                let kind = kind_str.parse().ok()?;
+
+                Some(EgressEntry {
+                    id: row.get("id"),
+                    timestamp: row.get("timestamp"),
+                    kind,
+                    user_id: row.get("user_id"),
+                    ip_address: row.get("ip_address"),
+                    resource_id: row.get("resource_id"),
+                    source_dataset: row.get("source_dataset"),
+                    bytes: row.get("bytes"),
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_egress_kind_parsing() {
+        for kind in [
+            EgressKind::JobResultDownload,
+            EgressKind::ShareAccess,
+            EgressKind::RelayDownload,
+        ] {
+            assert_eq!(kind.as_str().parse::<EgressKind>(), Ok(kind));
+        }
+        assert!("download".parse::<EgressKind>().is_err());
+    }
+
+    /// Runs against the database in `TEST_DATABASE_URL`, when set
+    #[tokio::test]
+    async fn test_report_groups_by_dataset() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let store = PostgresEgressStore::new(PgPool::connect(&url).await.unwrap());
+        store.initialize().await.unwrap();
+
+        // A window of its own keeps other rows out of the report
+        let offset = Uuid::new_v4().as_u128() % 1_000_000_000;
+        let from = Utc.timestamp_opt(0, 0).unwrap() + Duration::seconds(offset as i64 * 60);
+        let dataset_a = format!("a-{}.edf", offset);
+        let dataset_b = format!("b-{}.edf", offset);
+        let entries = [
+            (
+                EgressKind::JobResultDownload,
+                "alice",
+                "1.1.1.1",
+                Some(&dataset_a),
+                100,
+            ),
+            (
+                EgressKind::ShareAccess,
+                "bob",
+                "2.2.2.2",
+                Some(&dataset_a),
+                50,
+            ),
+            (
+                EgressKind::RelayDownload,
+                "alice",
+                "1.1.1.1",
+                Some(&dataset_a),
+                25,
+            ),
+            (
+                EgressKind::JobResultDownload,
+                "carol",
+                "3.3.3.3",
+                Some(&dataset_b),
+                500,
+            ),
+            (EgressKind::ShareAccess, "bob", "2.2.2.2", None, 7),
+        ];
+        for (i, (kind, user, ip, dataset, bytes)) in entries.into_iter().enumerate() {
+            let mut entry = EgressEntry::new(kind, user, "resource", bytes).ip_address(ip);
+            if let Some(dataset) = dataset {
+                entry = entry.source_dataset(dataset);
+            }
+            entry.timestamp = from + Duration::seconds(i as i64);
+            store.record(entry).await.unwrap();
+        }
+
+        let report = store
+            .report_by_dataset(from, from + Duration::seconds(30))
+            .await
+            .unwrap();
+        let datasets: Vec<_> = report.iter().map(|s| s.dataset.clone()).collect();
+        assert_eq!(
+            datasets,
+            vec![Some(dataset_b.clone()), Some(dataset_a.clone()), None]
+        );
+
+        let a = &report[1];
+        assert_eq!(a.event_count, 3);
+        assert_eq!(a.total_bytes, 175);
+        assert_eq!(a.unique_users, 2);
+        assert_eq!(a.unique_ips, 2);
+        assert_eq!(a.first_at, from);
+        assert_eq!(a.last_at, from + Duration::seconds(2));
+
+        let detail = store
+            .entries_for_dataset(&dataset_a, from, from + Duration::seconds(30), 10)
+            .await
+            .unwrap();
+        assert_eq!(detail.len(), 3);
+        assert_eq!(detail[0].kind, EgressKind::RelayDownload);
+    }
+}
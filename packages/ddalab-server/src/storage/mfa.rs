@@ -0,0 +1,318 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::traits::{StorageError, StorageResult};
+
+/// A passkey registered as a user's second factor
+#[derive(Debug, Clone, Serialize)]
+pub struct WebAuthnCredential {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    #[serde(skip)]
+    pub credential_id: Vec<u8>,
+    #[serde(skip)]
+    pub public_key: Vec<u8>,
+    #[serde(skip)]
+    pub sign_count: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// An unused recovery code
+#[derive(Debug, Clone)]
+pub struct RecoveryCode {
+    pub id: Uuid,
+    pub code_hash: String,
+}
+
+/// Second-factor store trait
+#[async_trait]
+pub trait MfaStore: Send + Sync {
+    /// Register a passkey for a user
+    async fn add_credential(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        credential_id: &[u8],
+        public_key: &[u8],
+        sign_count: u32,
+    ) -> StorageResult<WebAuthnCredential>;
+
+    /// List a user's passkeys
+    async fn list_credentials(&self, user_id: Uuid) -> StorageResult<Vec<WebAuthnCredential>>;
+
+    /// Record a successful assertion
+    async fn record_credential_use(&self, id: Uuid, sign_count: u32) -> StorageResult<()>;
+
+    /// Remove one of a user's passkeys
+    async fn delete_credential(&self, user_id: Uuid, id: Uuid) -> StorageResult<()>;
+
+    /// Replace all of a user's recovery codes with a new batch of hashes
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String],
+    ) -> StorageResult<()>;
+
+    /// A user's recovery codes that have not been used
+    async fn unused_recovery_codes(&self, user_id: Uuid) -> StorageResult<Vec<RecoveryCode>>;
+
+    /// Mark a recovery code used; false if it already was
+    async fn consume_recovery_code(&self, id: Uuid) -> StorageResult<bool>;
+
+    /// Remove all passkeys and recovery codes of a user
+    async fn reset_user(&self, user_id: Uuid) -> StorageResult<()>;
+}
+
+/// PostgreSQL implementation of MfaStore
+pub struct PostgresMfaStore {
+    pool: PgPool,
+}
+
+impl PostgresMfaStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for passkeys and recovery codes
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webauthn_credentials (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                credential_id BYTEA NOT NULL UNIQUE,
+                public_key BYTEA NOT NULL,
+                sign_count BIGINT NOT NULL DEFAULT 0,
+                name VARCHAR(255) NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                last_used_at TIMESTAMPTZ
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_webauthn_credentials_user ON webauthn_credentials(user_id)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_recovery_codes (
+                id UUID PRIMARY KEY,
+                user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                code_hash VARCHAR(255) NOT NULL,
+                used_at TIMESTAMPTZ
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_user_recovery_codes_user ON user_recovery_codes(user_id)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MfaStore for PostgresMfaStore {
+    async fn add_credential(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        credential_id: &[u8],
+        public_key: &[u8],
+        sign_count: u32,
+    ) -> StorageResult<WebAuthnCredential> {
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO webauthn_credentials (id, user_id, credential_id, public_key, sign_count, name, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(credential_id)
+        .bind(public_key)
+        .bind(sign_count as i64)
+        .bind(name)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if let Some(db_err) = e.as_database_error() {
+                if db_err.is_unique_violation() {
+                    return StorageError::AccessDenied("Passkey is already registered".to_string());
+                }
+            }
+            StorageError::Database(e)
+        })?;
+
+        Ok(WebAuthnCredential {
+            id,
+            user_id,
+            credential_id: credential_id.to_vec(),
+            public_key: public_key.to_vec(),
+            sign_count: sign_count as i64,
+            name: name.to_string(),
+            created_at: now,
+            last_used_at: None,
+        })
+    }
+
+    async fn list_credentials(&self, user_id: Uuid) -> StorageResult<Vec<WebAuthnCredential>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, credential_id, public_key, sign_count, name, created_at, last_used_at
+            FROM webauthn_credentials
+            WHERE user_id = $1
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| WebAuthnCredential {
+                id: row.get("id"),
+                user_id: row.get("user_id"),
+                credential_id: row.get("credential_id"),
+                public_key: row.get("public_key"),
+                sign_count: row.get("sign_count"),
+                name: row.get("name"),
+                created_at: row.get("created_at"),
+                last_used_at: row.get("last_used_at"),
+            })
+            .collect())
+    }
+
+    async fn record_credential_use(&self, id: Uuid, sign_count: u32) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE webauthn_credentials SET sign_count = $2, last_used_at = NOW() WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(sign_count as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_credential(&self, user_id: Uuid, id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM webauthn_credentials WHERE id = $1 AND user_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Passkey {}", id)));
+        }
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        code_hashes: &[String],
+    ) -> StorageResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for code_hash in code_hashes {
+            sqlx::query(
+                r#"
+                INSERT INTO user_recovery_codes (id, user_id, code_hash) VALUES ($1, $2, $3)
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(user_id)
+            .bind(code_hash)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn unused_recovery_codes(&self, user_id: Uuid) -> StorageResult<Vec<RecoveryCode>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, code_hash FROM user_recovery_codes WHERE user_id = $1 AND used_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| RecoveryCode {
+                id: row.get("id"),
+                code_hash: row.get("code_hash"),
+            })
+            .collect())
+    }
+
+    async fn consume_recovery_code(&self, id: Uuid) -> StorageResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE user_recovery_codes SET used_at = NOW() WHERE id = $1 AND used_at IS NULL
+            "#,
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn reset_user(&self, user_id: Uuid) -> StorageResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM webauthn_credentials WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
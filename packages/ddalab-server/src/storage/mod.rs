@@ -1,17 +1,46 @@
+mod annotations;
+mod announcements;
+mod api_tokens;
 mod audit;
 mod content_types;
+mod datasets;
+mod egress;
 mod federation;
+mod mfa;
+mod offline_messages;
+mod organizations;
 mod postgres;
+mod presets;
+mod retention;
+mod share_keys;
 mod teams;
 mod traits;
 mod types;
 mod users;
+mod webhooks;
 
-pub use audit::{AuditAction, AuditEntry, AuditEntryBuilder, AuditStore, PostgresAuditStore};
+pub use annotations::{Annotation, AnnotationStore, PostgresAnnotationStore};
+pub use announcements::{
+    Announcement, AnnouncementSeverity, AnnouncementStore, CreateAnnouncement,
+    PostgresAnnouncementStore,
+};
+pub use api_tokens::{ApiToken, ApiTokenStore, CreateApiToken, PostgresApiTokenStore};
+pub use audit::{AuditAction, AuditEntry, AuditEntryBuilder, AuditPage, AuditQuery, AuditStore, PostgresAuditStore};
 pub use content_types::*;
+pub use datasets::{
+    Dataset, DatasetGrant, DatasetPermission, DatasetStore, Grantee, PostgresDatasetStore,
+};
+pub use egress::{DatasetEgressSummary, EgressEntry, EgressKind, EgressStore, PostgresEgressStore};
 pub use federation::PostgresFederationStore;
+pub use mfa::{MfaStore, PostgresMfaStore, RecoveryCode, WebAuthnCredential};
+pub use offline_messages::{OfflineMessage, OfflineMessageStore, PostgresOfflineMessageStore};
+pub use organizations::PostgresOrganizationStore;
 pub use postgres::{PostgresSessionStore, PostgresShareStore, PostgresStorage};
+pub use presets::{PostgresPresetStore, PresetStore, UserPreset};
+pub use retention::{PostgresRetentionStore, PurgeRecord, PurgedKind, RetentionStore, TeamRetention};
+pub use share_keys::{PostgresShareKeyStore, ShareKey, ShareKeyStore};
 pub use teams::PostgresTeamStore;
-pub use traits::{AuditLogStore, FederationStore, InstitutionStore, SessionStore, SharedResultStore, StorageError, StorageResult, TeamStore};
+pub use traits::{AuditLogStore, FederationStore, InstitutionStore, OrganizationStore, SessionStore, SharedResultStore, StorageError, StorageResult, TeamStore};
 pub use types::*;
-pub use users::{CreateUser, PostgresUserStore, User, UserStore};
+pub use users::{CreateUser, NotificationPreferences, PostgresUserStore, User, UserStore};
+pub use webhooks::{CreateWebhook, PostgresWebhookStore, Webhook, WebhookDelivery, WebhookStore};
@@ -0,0 +1,173 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Row};
+
+use super::traits::StorageResult;
+use super::types::UserId;
+
+/// A message waiting for its recipient to connect
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfflineMessage {
+    /// Increases in the order messages were queued
+    pub id: i64,
+    pub user_id: UserId,
+    /// The message as sent over the sync WebSocket
+    pub message: serde_json::Value,
+    pub queued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Offline message store trait
+#[async_trait]
+pub trait OfflineMessageStore: Send + Sync {
+    /// Queue a message for a user, dropping their oldest messages beyond
+    /// `limit`; returns how many were dropped
+    async fn enqueue(
+        &self,
+        user_id: &str,
+        message: &serde_json::Value,
+        expires_at: DateTime<Utc>,
+        limit: usize,
+    ) -> StorageResult<u64>;
+
+    /// Unexpired messages queued for a user, oldest first
+    async fn pending(&self, user_id: &str) -> StorageResult<Vec<OfflineMessage>>;
+
+    /// Remove a user's delivered messages
+    async fn remove(&self, user_id: &str, ids: &[i64]) -> StorageResult<()>;
+
+    /// Delete expired messages; returns how many were deleted
+    async fn purge_expired(&self) -> StorageResult<u64>;
+}
+
+/// PostgreSQL implementation of OfflineMessageStore
+pub struct PostgresOfflineMessageStore {
+    pool: PgPool,
+}
+
+impl PostgresOfflineMessageStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for offline messages
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS offline_messages (
+                id BIGSERIAL PRIMARY KEY,
+                user_id VARCHAR(255) NOT NULL,
+                message JSONB NOT NULL,
+                queued_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                expires_at TIMESTAMPTZ NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_offline_messages_user ON offline_messages(user_id, id)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_offline_messages_expires ON offline_messages(expires_at)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OfflineMessageStore for PostgresOfflineMessageStore {
+    async fn enqueue(
+        &self,
+        user_id: &str,
+        message: &serde_json::Value,
+        expires_at: DateTime<Utc>,
+        limit: usize,
+    ) -> StorageResult<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO offline_messages (user_id, message, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(user_id)
+        .bind(message)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        let dropped = sqlx::query(
+            r#"
+            DELETE FROM offline_messages
+            WHERE user_id = $1 AND id NOT IN (
+                SELECT id FROM offline_messages
+                WHERE user_id = $1
+                ORDER BY id DESC
+                LIMIT $2
+            )
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit as i64)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok(dropped)
+    }
+
+    async fn pending(&self, user_id: &str) -> StorageResult<Vec<OfflineMessage>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, message, queued_at, expires_at
+            FROM offline_messages
+            WHERE user_id = $1 AND expires_at > NOW()
+            ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| OfflineMessage {
+                id: row.get("id"),
+                user_id: row.get("user_id"),
+                message: row.get("message"),
+                queued_at: row.get("queued_at"),
+                expires_at: row.get("expires_at"),
+            })
+            .collect())
+    }
+
+    async fn remove(&self, user_id: &str, ids: &[i64]) -> StorageResult<()> {
+        sqlx::query("DELETE FROM offline_messages WHERE user_id = $1 AND id = ANY($2)")
+            .bind(user_id)
+            .bind(ids)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> StorageResult<u64> {
+        let result = sqlx::query("DELETE FROM offline_messages WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,260 @@
+use async_trait::async_trait;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::storage::traits::{OrganizationStore, StorageError, StorageResult};
+use crate::storage::types::{
+    Organization, OrganizationMember, OrganizationRole, OrganizationSummary, TeamSummary,
+};
+
+pub struct PostgresOrganizationStore {
+    pool: PgPool,
+}
+
+impl PostgresOrganizationStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn summary_from_row(row: sqlx::postgres::PgRow) -> OrganizationSummary {
+    OrganizationSummary {
+        id: row.get("id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        member_count: row.get("member_count"),
+        team_count: row.get("team_count"),
+    }
+}
+
+#[async_trait]
+impl OrganizationStore for PostgresOrganizationStore {
+    async fn create_organization(&self, organization: &Organization) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO organizations (id, name, description, created_by, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(organization.id)
+        .bind(&organization.name)
+        .bind(&organization.description)
+        .bind(organization.created_by)
+        .bind(organization.created_at)
+        .bind(organization.updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_organization(&self, organization_id: Uuid) -> StorageResult<Organization> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, name, description, created_by, created_at, updated_at
+            FROM organizations WHERE id = $1
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(StorageError::OrganizationNotFound(organization_id))?;
+
+        Ok(Organization {
+            id: row.get("id"),
+            name: row.get("name"),
+            description: row.get("description"),
+            created_by: row.get("created_by"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        })
+    }
+
+    async fn delete_organization(&self, organization_id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query("DELETE FROM organizations WHERE id = $1")
+            .bind(organization_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::OrganizationNotFound(organization_id));
+        }
+
+        Ok(())
+    }
+
+    async fn list_organizations(&self) -> StorageResult<Vec<OrganizationSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT o.id, o.name, o.description,
+                   COUNT(DISTINCT om.user_id) as member_count,
+                   COUNT(DISTINCT t.id) as team_count
+            FROM organizations o
+            LEFT JOIN organization_members om ON om.organization_id = o.id
+            LEFT JOIN teams t ON t.organization_id = o.id
+            GROUP BY o.id
+            ORDER BY o.name
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(summary_from_row).collect())
+    }
+
+    async fn list_user_organizations(
+        &self,
+        user_id: Uuid,
+    ) -> StorageResult<Vec<OrganizationSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT o.id, o.name, o.description,
+                   COUNT(DISTINCT om2.user_id) as member_count,
+                   COUNT(DISTINCT t.id) as team_count
+            FROM organizations o
+            INNER JOIN organization_members om ON om.organization_id = o.id AND om.user_id = $1
+            LEFT JOIN organization_members om2 ON om2.organization_id = o.id
+            LEFT JOIN teams t ON t.organization_id = o.id
+            GROUP BY o.id
+            ORDER BY o.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(summary_from_row).collect())
+    }
+
+    async fn add_organization_member(&self, member: &OrganizationMember) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO organization_members (organization_id, user_id, role, added_at, added_by)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (organization_id, user_id) DO UPDATE SET role = EXCLUDED.role
+            "#,
+        )
+        .bind(member.organization_id)
+        .bind(member.user_id)
+        .bind(member.role.as_str())
+        .bind(member.added_at)
+        .bind(member.added_by)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove_organization_member(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> StorageResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM team_members
+            WHERE user_id = $2
+              AND team_id IN (SELECT id FROM teams WHERE organization_id = $1)
+            "#,
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query(
+            "DELETE FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(
+                "Organization member not found".to_string(),
+            ));
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_organization_members(
+        &self,
+        organization_id: Uuid,
+    ) -> StorageResult<Vec<OrganizationMember>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT organization_id, user_id, role, added_at, added_by
+            FROM organization_members WHERE organization_id = $1
+            ORDER BY added_at
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| OrganizationMember {
+                organization_id: row.get("organization_id"),
+                user_id: row.get("user_id"),
+                role: OrganizationRole::from_str_lossy(row.get("role")),
+                added_at: row.get("added_at"),
+                added_by: row.get("added_by"),
+            })
+            .collect())
+    }
+
+    async fn member_role(
+        &self,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> StorageResult<Option<OrganizationRole>> {
+        let row = sqlx::query(
+            "SELECT role FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| OrganizationRole::from_str_lossy(row.get("role"))))
+    }
+
+    async fn list_organization_teams(
+        &self,
+        organization_id: Uuid,
+    ) -> StorageResult<Vec<TeamSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.name, t.description,
+                   COUNT(DISTINCT tm.user_id) as member_count,
+                   COUNT(DISTINCT sr.share_token) as share_count
+            FROM teams t
+            LEFT JOIN team_members tm ON tm.team_id = t.id
+            LEFT JOIN shared_results sr ON sr.access_policy->>'team_id' = t.id::text
+            WHERE t.organization_id = $1
+            GROUP BY t.id
+            ORDER BY t.name
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TeamSummary {
+                id: row.get("id"),
+                name: row.get("name"),
+                description: row.get("description"),
+                member_count: row.get("member_count"),
+                share_count: row.get("share_count"),
+            })
+            .collect())
+    }
+}
@@ -36,6 +36,11 @@ impl PostgresShareStore {
         .execute(&self.pool)
         .await?;
 
+        // Sealed payloads of end-to-end encrypted shares
+        let _ = sqlx::query("ALTER TABLE shared_results ADD COLUMN IF NOT EXISTS sealed JSONB")
+            .execute(&self.pool)
+            .await;
+
         // Create index
         sqlx::query(
             r#"
@@ -46,8 +51,59 @@ impl PostgresShareStore {
         .execute(&self.pool)
         .await?;
 
+        // Full-text index for search; queries must repeat this expression
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_shared_results_search
+                ON shared_results
+                USING GIN (to_tsvector('simple', title || ' ' || COALESCE(description, '')))
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
         Ok(())
     }
+
+    /// Whether the user with this email belongs to the organization
+    async fn is_organization_member(&self, organization_id: &str, requester_id: &UserId) -> bool {
+        let row = sqlx::query(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM organization_members om
+                JOIN users u ON u.id = om.user_id
+                WHERE om.organization_id = $1::uuid AND u.email = $2
+            ) as is_member
+            "#,
+        )
+        .bind(organization_id)
+        .bind(requester_id)
+        .fetch_one(&self.pool)
+        .await;
+        row.map(|r| r.get::<bool, _>("is_member")).unwrap_or(false)
+    }
+}
+
+fn share_metadata_from_row(row: &sqlx::postgres::PgRow) -> StorageResult<ShareMetadata> {
+    let access_policy: AccessPolicy = serde_json::from_value(row.get("access_policy"))?;
+    let sealed = row
+        .get::<Option<serde_json::Value>, _>("sealed")
+        .map(serde_json::from_value)
+        .transpose()?;
+
+    Ok(ShareMetadata {
+        owner_user_id: row.get("owner_user_id"),
+        content_type: Default::default(), // Default until migration adds column
+        content_id: row.get("result_id"), // Maps from result_id column until migration
+        title: row.get("title"),
+        description: row.get("description"),
+        created_at: row.get("created_at"),
+        access_policy,
+        classification: Default::default(),
+        download_count: 0,
+        last_accessed_at: None,
+        sealed,
+    })
 }
 
 #[async_trait]
@@ -59,6 +115,7 @@ impl SharedResultStore for PostgresShareStore {
         content_data: Option<serde_json::Value>,
     ) -> StorageResult<()> {
         let access_policy_json = serde_json::to_value(&metadata.access_policy)?;
+        let sealed_json = metadata.sealed.as_ref().map(serde_json::to_value).transpose()?;
         let content_type_str = serde_json::to_string(&metadata.content_type)?
             .trim_matches('"')
             .to_string();
@@ -67,13 +124,14 @@ impl SharedResultStore for PostgresShareStore {
             r#"
             INSERT INTO shared_results
                 (share_token, owner_user_id, content_type, result_id, title, description,
-                 access_policy, created_at, content_data)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                 access_policy, created_at, content_data, sealed)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
             ON CONFLICT (share_token) DO UPDATE SET
                 title = EXCLUDED.title,
                 description = EXCLUDED.description,
                 access_policy = EXCLUDED.access_policy,
-                content_data = EXCLUDED.content_data
+                content_data = EXCLUDED.content_data,
+                sealed = EXCLUDED.sealed
             "#,
         )
         .bind(share_token)
@@ -85,6 +143,7 @@ impl SharedResultStore for PostgresShareStore {
         .bind(access_policy_json)
         .bind(metadata.created_at)
         .bind(&content_data)
+        .bind(sealed_json)
         .execute(&self.pool)
         .await?;
 
@@ -94,7 +153,7 @@ impl SharedResultStore for PostgresShareStore {
     async fn get_shared_result(&self, share_token: &str) -> StorageResult<ShareMetadata> {
         let row = sqlx::query(
             r#"
-            SELECT owner_user_id, result_id, title, description, access_policy, created_at
+            SELECT owner_user_id, result_id, title, description, access_policy, created_at, sealed
             FROM shared_results
             WHERE share_token = $1 AND revoked_at IS NULL
             "#,
@@ -104,20 +163,7 @@ impl SharedResultStore for PostgresShareStore {
         .await?
         .ok_or_else(|| StorageError::ShareNotFound(share_token.to_string()))?;
 
-        let access_policy: AccessPolicy = serde_json::from_value(row.get("access_policy"))?;
-
-        Ok(ShareMetadata {
-            owner_user_id: row.get("owner_user_id"),
-            content_type: Default::default(), // Default until migration adds column
-            content_id: row.get("result_id"), // Maps from result_id column until migration
-            title: row.get("title"),
-            description: row.get("description"),
-            created_at: row.get("created_at"),
-            access_policy,
-            classification: Default::default(),
-            download_count: 0,
-            last_accessed_at: None,
-        })
+        share_metadata_from_row(&row)
     }
 
     async fn check_access(
@@ -127,6 +173,13 @@ impl SharedResultStore for PostgresShareStore {
     ) -> StorageResult<bool> {
         let metadata = self.get_shared_result(share_token).await?;
 
+        // Shares confined to an organization are invisible to everyone outside it
+        if let Some(organization_id) = &metadata.access_policy.organization_id {
+            if !self.is_organization_member(organization_id, requester_id).await {
+                return Ok(false);
+            }
+        }
+
         let has_access = match &metadata.access_policy.policy_type {
             AccessPolicyType::Public => true,
             AccessPolicyType::Institution => {
@@ -162,6 +215,9 @@ impl SharedResultStore for PostgresShareStore {
                 row.map(|r| r.get::<bool, _>("is_member")).unwrap_or(false)
             }
             AccessPolicyType::Users { user_ids } => user_ids.contains(requester_id),
+            AccessPolicyType::Organization { organization_id } => {
+                self.is_organization_member(organization_id, requester_id).await
+            }
         };
 
         Ok(has_access)
@@ -202,6 +258,64 @@ impl SharedResultStore for PostgresShareStore {
         Ok(rows.into_iter().map(|row| row.get("share_token")).collect())
     }
 
+    async fn list_user_share_metadata(
+        &self,
+        user_id: &UserId,
+    ) -> StorageResult<Vec<(ShareToken, ShareMetadata)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT share_token, owner_user_id, result_id, title, description, access_policy, created_at, sealed
+            FROM shared_results
+            WHERE owner_user_id = $1 AND revoked_at IS NULL
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| Ok((row.get("share_token"), share_metadata_from_row(row)?)))
+            .collect()
+    }
+
+    async fn search_user_shares(
+        &self,
+        user_id: &UserId,
+        terms: &[String],
+        limit: i64,
+    ) -> StorageResult<Vec<(ShareToken, ShareMetadata)>> {
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        // Any term, each as a prefix; terms are alphanumeric so need no quoting
+        let query = terms
+            .iter()
+            .map(|term| format!("{}:*", term))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let rows = sqlx::query(
+            r#"
+            SELECT share_token, owner_user_id, result_id, title, description, access_policy, created_at, sealed
+            FROM shared_results
+            WHERE owner_user_id = $1 AND revoked_at IS NULL
+              AND to_tsvector('simple', title || ' ' || COALESCE(description, ''))
+                  @@ to_tsquery('simple', $2)
+            ORDER BY created_at DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(query)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| Ok((row.get("share_token"), share_metadata_from_row(row)?)))
+            .collect()
+    }
+
     async fn get_share_content(&self, share_token: &str) -> StorageResult<Option<serde_json::Value>> {
         let row = sqlx::query(
             r#"
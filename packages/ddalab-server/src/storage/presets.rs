@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::traits::{StorageError, StorageResult};
+use super::types::UserId;
+
+/// Named job parameters a user keeps for themselves
+///
+/// Team presets, which can also lock fields and be required, live with the
+/// team in `team_presets`.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserPreset {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub name: String,
+    pub description: Option<String>,
+    /// Job parameter fields the preset sets
+    pub parameters: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Personal preset store trait
+#[async_trait]
+pub trait PresetStore: Send + Sync {
+    /// Record a new preset; names are unique per user
+    async fn create_preset(&self, preset: &UserPreset) -> StorageResult<()>;
+
+    /// Replace a preset's name, description and parameters
+    async fn update_preset(&self, preset: &UserPreset) -> StorageResult<()>;
+
+    /// Look up one preset
+    async fn get_preset(&self, id: Uuid) -> StorageResult<UserPreset>;
+
+    /// A user's presets by name
+    async fn list_presets(&self, user_id: &str) -> StorageResult<Vec<UserPreset>>;
+
+    /// Remove one of a user's presets
+    async fn delete_preset(&self, user_id: &str, id: Uuid) -> StorageResult<()>;
+}
+
+/// PostgreSQL implementation of PresetStore
+pub struct PostgresPresetStore {
+    pool: PgPool,
+}
+
+impl PostgresPresetStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for personal presets
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_presets (
+                id UUID PRIMARY KEY,
+                user_id VARCHAR(255) NOT NULL,
+                name VARCHAR(256) NOT NULL,
+                description TEXT,
+                parameters JSONB NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                UNIQUE (user_id, name)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn preset_from_row(row: &sqlx::postgres::PgRow) -> UserPreset {
+    UserPreset {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        parameters: row.get("parameters"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn duplicate_name(e: sqlx::Error, name: &str) -> StorageError {
+    if let Some(db_err) = e.as_database_error() {
+        if db_err.is_unique_violation() {
+            return StorageError::DuplicateName(name.to_string());
+        }
+    }
+    StorageError::Database(e)
+}
+
+#[async_trait]
+impl PresetStore for PostgresPresetStore {
+    async fn create_preset(&self, preset: &UserPreset) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_presets
+                (id, user_id, name, description, parameters, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(preset.id)
+        .bind(&preset.user_id)
+        .bind(&preset.name)
+        .bind(&preset.description)
+        .bind(&preset.parameters)
+        .bind(preset.created_at)
+        .bind(preset.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| duplicate_name(e, &preset.name))?;
+
+        Ok(())
+    }
+
+    async fn update_preset(&self, preset: &UserPreset) -> StorageResult<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE user_presets
+            SET name = $3, description = $4, parameters = $5, updated_at = $6
+            WHERE id = $1 AND user_id = $2
+            "#,
+        )
+        .bind(preset.id)
+        .bind(&preset.user_id)
+        .bind(&preset.name)
+        .bind(&preset.description)
+        .bind(&preset.parameters)
+        .bind(preset.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| duplicate_name(e, &preset.name))?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound("Preset not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn get_preset(&self, id: Uuid) -> StorageResult<UserPreset> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, user_id, name, description, parameters, created_at, updated_at
+            FROM user_presets WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| StorageError::NotFound("Preset not found".to_string()))?;
+
+        Ok(preset_from_row(&row))
+    }
+
+    async fn list_presets(&self, user_id: &str) -> StorageResult<Vec<UserPreset>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, user_id, name, description, parameters, created_at, updated_at
+            FROM user_presets WHERE user_id = $1
+            ORDER BY name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(preset_from_row).collect())
+    }
+
+    async fn delete_preset(&self, user_id: &str, id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query("DELETE FROM user_presets WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound("Preset not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
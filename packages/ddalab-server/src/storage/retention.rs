@@ -0,0 +1,286 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::traits::{StorageError, StorageResult};
+use super::types::UserId;
+
+/// A team's override of the server-wide retention policy
+#[derive(Debug, Clone, Serialize)]
+pub struct TeamRetention {
+    pub team_id: Uuid,
+    /// Days finished jobs keep their results and uploads
+    pub retain_days: i32,
+    /// Days expired files stay before they are deleted
+    pub grace_days: i32,
+    pub updated_by: UserId,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Which file of a job was purged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PurgedKind {
+    /// Result file or its thumbnail
+    Output,
+    /// Uploaded input kept for the job
+    Upload,
+    /// File in the output or upload directory no known job refers to
+    Orphan,
+}
+
+impl PurgedKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Output => "output",
+            Self::Upload => "upload",
+            Self::Orphan => "orphan",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "output" => Self::Output,
+            "upload" => Self::Upload,
+            _ => Self::Orphan,
+        }
+    }
+}
+
+/// One file the retention sweep archived and deleted, or tried to
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgeRecord {
+    pub id: Uuid,
+    pub path: String,
+    pub kind: PurgedKind,
+    pub job_id: Option<Uuid>,
+    /// Owner of the job, when known
+    pub user_id: Option<UserId>,
+    pub bytes: i64,
+    /// Whether the archive hook copied the file first
+    pub archived: bool,
+    /// Why the file was kept, if archiving or deleting failed
+    pub error: Option<String>,
+    pub deleted: bool,
+    pub purged_at: DateTime<Utc>,
+}
+
+/// Retention policy store trait
+#[async_trait]
+pub trait RetentionStore: Send + Sync {
+    /// Set or replace a team's retention policy
+    async fn set_team_retention(&self, policy: &TeamRetention) -> StorageResult<()>;
+
+    /// A team's retention policy, if it has one
+    async fn get_team_retention(&self, team_id: Uuid) -> StorageResult<Option<TeamRetention>>;
+
+    /// Remove a team's policy so the server-wide one applies again
+    async fn delete_team_retention(&self, team_id: Uuid) -> StorageResult<()>;
+
+    /// Every team policy
+    async fn list_team_retention(&self) -> StorageResult<Vec<TeamRetention>>;
+
+    /// Policies of the teams a user belongs to
+    async fn retention_for_user(&self, user_id: &str) -> StorageResult<Vec<TeamRetention>>;
+
+    /// Add a file to the purge log
+    async fn record_purge(&self, record: &PurgeRecord) -> StorageResult<()>;
+
+    /// Most recent purge log entries, newest first
+    async fn list_purges(&self, limit: i64) -> StorageResult<Vec<PurgeRecord>>;
+}
+
+/// PostgreSQL implementation of RetentionStore
+pub struct PostgresRetentionStore {
+    pool: PgPool,
+}
+
+impl PostgresRetentionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for retention policies and the purge log
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS team_retention (
+                team_id UUID PRIMARY KEY REFERENCES teams(id) ON DELETE CASCADE,
+                retain_days INTEGER NOT NULL,
+                grace_days INTEGER NOT NULL,
+                updated_by VARCHAR(255) NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS retention_purges (
+                id UUID PRIMARY KEY,
+                path TEXT NOT NULL,
+                kind VARCHAR(20) NOT NULL,
+                job_id UUID,
+                user_id VARCHAR(255),
+                bytes BIGINT NOT NULL DEFAULT 0,
+                archived BOOLEAN NOT NULL,
+                error TEXT,
+                deleted BOOLEAN NOT NULL,
+                purged_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_retention_purges_at ON retention_purges(purged_at DESC)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn team_retention_from_row(row: &sqlx::postgres::PgRow) -> TeamRetention {
+    TeamRetention {
+        team_id: row.get("team_id"),
+        retain_days: row.get("retain_days"),
+        grace_days: row.get("grace_days"),
+        updated_by: row.get("updated_by"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+#[async_trait]
+impl RetentionStore for PostgresRetentionStore {
+    async fn set_team_retention(&self, policy: &TeamRetention) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO team_retention (team_id, retain_days, grace_days, updated_by, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (team_id) DO UPDATE SET
+                retain_days = EXCLUDED.retain_days,
+                grace_days = EXCLUDED.grace_days,
+                updated_by = EXCLUDED.updated_by,
+                updated_at = EXCLUDED.updated_at
+            "#,
+        )
+        .bind(policy.team_id)
+        .bind(policy.retain_days)
+        .bind(policy.grace_days)
+        .bind(&policy.updated_by)
+        .bind(policy.updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_team_retention(&self, team_id: Uuid) -> StorageResult<Option<TeamRetention>> {
+        let row = sqlx::query("SELECT * FROM team_retention WHERE team_id = $1")
+            .bind(team_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(team_retention_from_row))
+    }
+
+    async fn delete_team_retention(&self, team_id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query("DELETE FROM team_retention WHERE team_id = $1")
+            .bind(team_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!(
+                "No retention policy for team {}",
+                team_id
+            )));
+        }
+        Ok(())
+    }
+
+    async fn list_team_retention(&self) -> StorageResult<Vec<TeamRetention>> {
+        let rows = sqlx::query("SELECT * FROM team_retention")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(team_retention_from_row).collect())
+    }
+
+    async fn retention_for_user(&self, user_id: &str) -> StorageResult<Vec<TeamRetention>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.* FROM team_retention r
+            JOIN team_members tm ON tm.team_id = r.team_id
+            JOIN users u ON u.id = tm.user_id
+            WHERE u.email = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(team_retention_from_row).collect())
+    }
+
+    async fn record_purge(&self, record: &PurgeRecord) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO retention_purges (id, path, kind, job_id, user_id, bytes, archived, error, deleted, purged_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(record.id)
+        .bind(&record.path)
+        .bind(record.kind.as_str())
+        .bind(record.job_id)
+        .bind(&record.user_id)
+        .bind(record.bytes)
+        .bind(record.archived)
+        .bind(&record.error)
+        .bind(record.deleted)
+        .bind(record.purged_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list_purges(&self, limit: i64) -> StorageResult<Vec<PurgeRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM retention_purges
+            ORDER BY purged_at DESC
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| PurgeRecord {
+                id: row.get("id"),
+                path: row.get("path"),
+                kind: PurgedKind::parse(row.get("kind")),
+                job_id: row.get("job_id"),
+                user_id: row.get("user_id"),
+                bytes: row.get("bytes"),
+                archived: row.get("archived"),
+                error: row.get("error"),
+                deleted: row.get("deleted"),
+                purged_at: row.get("purged_at"),
+            })
+            .collect())
+    }
+}
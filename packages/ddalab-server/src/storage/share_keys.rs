@@ -0,0 +1,104 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+
+use super::traits::StorageResult;
+use super::types::UserId;
+
+/// The public key a user's client publishes so others can seal shares for
+/// them; the private half never leaves the client
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareKey {
+    pub user_id: UserId,
+    /// Base64-encoded X25519 public key (32 bytes)
+    pub public_key: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Share key store trait
+#[async_trait]
+pub trait ShareKeyStore: Send + Sync {
+    /// Publish or replace a user's share key
+    async fn set_share_key(&self, user_id: &str, public_key: &str) -> StorageResult<ShareKey>;
+
+    /// The share keys of those `user_ids` who published one
+    async fn get_share_keys(&self, user_ids: &[String]) -> StorageResult<Vec<ShareKey>>;
+}
+
+/// PostgreSQL implementation of ShareKeyStore
+pub struct PostgresShareKeyStore {
+    pool: PgPool,
+}
+
+impl PostgresShareKeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for share keys
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_share_keys (
+                user_id VARCHAR(255) PRIMARY KEY,
+                public_key TEXT NOT NULL,
+                updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ShareKeyStore for PostgresShareKeyStore {
+    async fn set_share_key(&self, user_id: &str, public_key: &str) -> StorageResult<ShareKey> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO user_share_keys (user_id, public_key, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (user_id) DO UPDATE SET
+                public_key = EXCLUDED.public_key,
+                updated_at = EXCLUDED.updated_at
+            RETURNING updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(public_key)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(ShareKey {
+            user_id: user_id.to_string(),
+            public_key: public_key.to_string(),
+            updated_at: row.get("updated_at"),
+        })
+    }
+
+    async fn get_share_keys(&self, user_ids: &[String]) -> StorageResult<Vec<ShareKey>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT user_id, public_key, updated_at
+            FROM user_share_keys
+            WHERE user_id = ANY($1)
+            ORDER BY user_id
+            "#,
+        )
+        .bind(user_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ShareKey {
+                user_id: row.get("user_id"),
+                public_key: row.get("public_key"),
+                updated_at: row.get("updated_at"),
+            })
+            .collect())
+    }
+}
@@ -3,7 +3,7 @@ use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
 use crate::storage::traits::{StorageError, StorageResult, TeamStore};
-use crate::storage::types::{Team, TeamMember, TeamRole, TeamSummary};
+use crate::storage::types::{Team, TeamMember, TeamPreset, TeamRole, TeamSummary};
 
 pub struct PostgresTeamStore {
     pool: PgPool,
@@ -20,12 +20,13 @@ impl TeamStore for PostgresTeamStore {
     async fn create_team(&self, team: &Team) -> StorageResult<()> {
         sqlx::query(
             r#"
-            INSERT INTO teams (id, institution_id, name, description, created_by, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            INSERT INTO teams (id, institution_id, organization_id, name, description, created_by, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             "#,
         )
         .bind(team.id)
         .bind(team.institution_id)
+        .bind(team.organization_id)
         .bind(&team.name)
         .bind(&team.description)
         .bind(team.created_by)
@@ -40,7 +41,7 @@ impl TeamStore for PostgresTeamStore {
     async fn get_team(&self, team_id: Uuid) -> StorageResult<Team> {
         let row = sqlx::query(
             r#"
-            SELECT id, institution_id, name, description, created_by, created_at, updated_at
+            SELECT id, institution_id, organization_id, name, description, created_by, created_at, updated_at
             FROM teams WHERE id = $1
             "#,
         )
@@ -52,6 +53,7 @@ impl TeamStore for PostgresTeamStore {
         Ok(Team {
             id: row.get("id"),
             institution_id: row.get("institution_id"),
+            organization_id: row.get("organization_id"),
             name: row.get("name"),
             description: row.get("description"),
             created_by: row.get("created_by"),
@@ -254,6 +256,97 @@ impl TeamStore for PostgresTeamStore {
 
         Ok(row.is_some())
     }
+
+    async fn save_team_preset(&self, preset: &TeamPreset) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO team_presets
+                (id, team_id, name, description, parameters, locked_fields, required, created_by, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (team_id, name) DO UPDATE SET
+                description = EXCLUDED.description,
+                parameters = EXCLUDED.parameters,
+                locked_fields = EXCLUDED.locked_fields,
+                required = EXCLUDED.required
+            "#,
+        )
+        .bind(preset.id)
+        .bind(preset.team_id)
+        .bind(&preset.name)
+        .bind(&preset.description)
+        .bind(&preset.parameters)
+        .bind(&preset.locked_fields)
+        .bind(preset.required)
+        .bind(preset.created_by)
+        .bind(preset.created_at)
+        .bind(preset.updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list_team_presets(&self, team_id: Uuid) -> StorageResult<Vec<TeamPreset>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, team_id, name, description, parameters, locked_fields, required,
+                   created_by, created_at, updated_at
+            FROM team_presets WHERE team_id = $1
+            ORDER BY name
+            "#,
+        )
+        .bind(team_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(preset_from_row).collect())
+    }
+
+    async fn list_user_presets(&self, user_id: Uuid) -> StorageResult<Vec<TeamPreset>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT p.id, p.team_id, p.name, p.description, p.parameters, p.locked_fields,
+                   p.required, p.created_by, p.created_at, p.updated_at
+            FROM team_presets p
+            INNER JOIN team_members tm ON tm.team_id = p.team_id AND tm.user_id = $1
+            ORDER BY p.team_id, p.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(preset_from_row).collect())
+    }
+
+    async fn delete_team_preset(&self, team_id: Uuid, preset_id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query("DELETE FROM team_presets WHERE team_id = $1 AND id = $2")
+            .bind(team_id)
+            .bind(preset_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound("Team preset not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+fn preset_from_row(row: &sqlx::postgres::PgRow) -> TeamPreset {
+    TeamPreset {
+        id: row.get("id"),
+        team_id: row.get("team_id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        parameters: row.get("parameters"),
+        locked_fields: row.get("locked_fields"),
+        required: row.get("required"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
 }
 
 #[cfg(test)]
@@ -2,8 +2,9 @@ use async_trait::async_trait;
 use uuid::Uuid;
 use crate::storage::types::{
     AuditLogEntry, FederatedInstitutionSummary, FederationInvite, FederationTrust,
-    InstitutionConfig, ShareMetadata, ShareToken, ShareableContentType, Team, TeamMember,
-    TeamRole, TeamSummary, TrustLevel, UserId, UserSession,
+    InstitutionConfig, Organization, OrganizationMember, OrganizationRole,
+    OrganizationSummary, ShareMetadata, ShareToken, ShareableContentType, Team, TeamMember,
+    TeamPreset, TeamRole, TeamSummary, TrustLevel, UserId, UserSession,
 };
 
 /// Result type for storage operations
@@ -33,9 +34,18 @@ pub enum StorageError {
     #[error("Team not found: {0}")]
     TeamNotFound(Uuid),
 
+    #[error("Organization not found: {0}")]
+    OrganizationNotFound(Uuid),
+
     #[error("Email already exists: {0}")]
     DuplicateEmail(String),
 
+    #[error("Name already in use: {0}")]
+    DuplicateName(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("User is suspended")]
     UserSuspended,
 
@@ -94,6 +104,21 @@ pub trait SharedResultStore: Send + Sync {
     /// List all shares owned by a user
     async fn list_user_shares(&self, user_id: &UserId) -> StorageResult<Vec<ShareToken>>;
 
+    /// List a user's active shares with their metadata, newest first
+    async fn list_user_share_metadata(
+        &self,
+        user_id: &UserId,
+    ) -> StorageResult<Vec<(ShareToken, ShareMetadata)>>;
+
+    /// A user's active shares whose title or description contains any of
+    /// `terms` as a word prefix, newest first
+    async fn search_user_shares(
+        &self,
+        user_id: &UserId,
+        terms: &[String],
+        limit: i64,
+    ) -> StorageResult<Vec<(ShareToken, ShareMetadata)>>;
+
     /// List shares by content type for a user
     async fn list_shares_by_type(
         &self,
@@ -222,6 +247,55 @@ pub trait TeamStore: Send + Sync {
 
     /// Check if user is team admin
     async fn is_team_admin(&self, team_id: Uuid, user_id: Uuid) -> StorageResult<bool>;
+
+    /// Create or replace a team parameter preset
+    async fn save_team_preset(&self, preset: &TeamPreset) -> StorageResult<()>;
+
+    /// List a team's parameter presets
+    async fn list_team_presets(&self, team_id: Uuid) -> StorageResult<Vec<TeamPreset>>;
+
+    /// List the parameter presets of every team a user belongs to
+    async fn list_user_presets(&self, user_id: Uuid) -> StorageResult<Vec<TeamPreset>>;
+
+    /// Delete a team parameter preset
+    async fn delete_team_preset(&self, team_id: Uuid, preset_id: Uuid) -> StorageResult<()>;
+}
+
+/// Storage trait for organizations and their members
+///
+/// Organizations sit above teams: a team belongs to at most one organization
+/// and its members must belong to that organization too.
+#[async_trait]
+pub trait OrganizationStore: Send + Sync {
+    /// Create a new organization
+    async fn create_organization(&self, organization: &Organization) -> StorageResult<()>;
+
+    /// Get organization by ID
+    async fn get_organization(&self, organization_id: Uuid) -> StorageResult<Organization>;
+
+    /// Delete an organization with its teams
+    async fn delete_organization(&self, organization_id: Uuid) -> StorageResult<()>;
+
+    /// List every organization on the server
+    async fn list_organizations(&self) -> StorageResult<Vec<OrganizationSummary>>;
+
+    /// List organizations a user belongs to
+    async fn list_user_organizations(&self, user_id: Uuid) -> StorageResult<Vec<OrganizationSummary>>;
+
+    /// Add a member, or change the role of an existing one
+    async fn add_organization_member(&self, member: &OrganizationMember) -> StorageResult<()>;
+
+    /// Remove a member from the organization and from all its teams
+    async fn remove_organization_member(&self, organization_id: Uuid, user_id: Uuid) -> StorageResult<()>;
+
+    /// Get organization members
+    async fn get_organization_members(&self, organization_id: Uuid) -> StorageResult<Vec<OrganizationMember>>;
+
+    /// A user's role in the organization, if they are a member
+    async fn member_role(&self, organization_id: Uuid, user_id: Uuid) -> StorageResult<Option<OrganizationRole>>;
+
+    /// List the organization's teams
+    async fn list_organization_teams(&self, organization_id: Uuid) -> StorageResult<Vec<TeamSummary>>;
 }
 
 /// Storage backend for federation between institutions
@@ -2,6 +2,9 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::crypto::SealedPayload;
+use crate::transfer::TransferPolicy;
+
 /// Unique identifier for users
 pub type UserId = String;
 
@@ -94,6 +97,8 @@ pub enum AccessPolicyType {
     Users { user_ids: Vec<UserId> },
     /// All institution members (explicit)
     Institution,
+    /// All members of one organization
+    Organization { organization_id: String },
 }
 
 /// Full access policy with permissions and expiration
@@ -104,6 +109,10 @@ pub struct AccessPolicy {
     pub policy_type: AccessPolicyType,
     /// Institution this share belongs to
     pub institution_id: String,
+    /// Organization the share is confined to; outsiders are denied whatever
+    /// the policy type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization_id: Option<String>,
     /// What they can do
     pub permissions: Vec<Permission>,
     /// When access expires (ISO 8601)
@@ -111,6 +120,9 @@ pub struct AccessPolicy {
     /// Optional download limit
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_downloads: Option<u32>,
+    /// Overrides of the server's bandwidth cap and off-peak deferral for this share
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer: Option<TransferPolicy>,
 }
 
 impl AccessPolicy {
@@ -129,9 +141,11 @@ impl AccessPolicy {
         Self {
             policy_type: AccessPolicyType::Public,
             institution_id,
+            organization_id: None,
             permissions: vec![Permission::View, Permission::Download],
             expires_at: Utc::now() + chrono::Duration::days(30),
             max_downloads: None,
+            transfer: None,
         }
     }
 }
@@ -272,6 +286,27 @@ pub struct ShareMetadata {
     /// Last time this share was accessed
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_accessed_at: Option<DateTime<Utc>>,
+    /// Title, description and content encrypted by the owner's client for
+    /// the recipients; the broker cannot read them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<SealedPayload>,
+}
+
+impl ShareMetadata {
+    /// Check a sealed share is well formed and leaves its title and
+    /// description to the sealed payload
+    pub fn validate_sealed(&self) -> Result<(), String> {
+        let Some(sealed) = &self.sealed else {
+            return Ok(());
+        };
+        if !self.title.is_empty() || self.description.is_some() {
+            return Err(
+                "Encrypted shares keep their title and description in the sealed payload"
+                    .to_string(),
+            );
+        }
+        sealed.validate().map_err(|e| e.to_string())
+    }
 }
 
 /// Information about a shared result including owner availability
@@ -280,6 +315,10 @@ pub struct SharedResultInfo {
     pub metadata: ShareMetadata,
     pub download_url: String,
     pub owner_online: bool,
+    /// Effective transfer policy (server defaults with the share's overrides)
+    /// the owner's client applies when serving the download
+    #[serde(default)]
+    pub transfer_policy: TransferPolicy,
 }
 
 /// User session information stored in database
@@ -331,6 +370,9 @@ pub struct BackupMetadata {
 pub struct Team {
     pub id: Uuid,
     pub institution_id: Uuid,
+    /// Organization the team belongs to, if any
+    #[serde(default)]
+    pub organization_id: Option<Uuid>,
     pub name: String,
     pub description: Option<String>,
     pub created_by: Uuid,
@@ -372,11 +414,98 @@ pub struct TeamSummary {
     pub share_count: i64,
 }
 
+/// Parameter preset published by a team admin
+///
+/// `parameters` holds a subset of the job parameter fields. Fields listed in
+/// `locked_fields` must be submitted with exactly the preset's value, and a
+/// `required` preset makes members submit every job under one of their
+/// teams' required presets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TeamPreset {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub parameters: serde_json::Value,
+    pub locked_fields: Vec<String>,
+    pub required: bool,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Organization hosting teams and users, e.g. one department of a hospital
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Organization member role
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrganizationRole {
+    #[default]
+    Member,
+    /// Manages members and teams
+    Admin,
+    /// Admin who may also grant ownership and delete the organization
+    Owner,
+}
+
+impl OrganizationRole {
+    /// Whether this role may manage members and teams
+    pub fn can_manage(&self) -> bool {
+        *self >= OrganizationRole::Admin
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrganizationRole::Member => "member",
+            OrganizationRole::Admin => "admin",
+            OrganizationRole::Owner => "owner",
+        }
+    }
+
+    pub fn from_str_lossy(s: &str) -> Self {
+        match s {
+            "owner" => OrganizationRole::Owner,
+            "admin" => OrganizationRole::Admin,
+            _ => OrganizationRole::Member,
+        }
+    }
+}
+
+/// Organization membership
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationMember {
+    pub organization_id: Uuid,
+    pub user_id: Uuid,
+    pub role: OrganizationRole,
+    pub added_at: DateTime<Utc>,
+    pub added_by: Option<Uuid>,
+}
+
+/// Organization with member and team counts for listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub member_count: i64,
+    pub team_count: i64,
+}
+
 /// Trust level between federated institutions
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TrustLevel {
     /// Full bidirectional access to non-PHI content
+    #[default]
     Full,
     /// Read-only access (can view but not download)
     ReadOnly,
@@ -384,12 +513,6 @@ pub enum TrustLevel {
     Revoked,
 }
 
-impl Default for TrustLevel {
-    fn default() -> Self {
-        Self::Full
-    }
-}
-
 /// Federation invite for establishing trust
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FederationInvite {
@@ -1,5 +1,7 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
@@ -17,6 +19,41 @@ pub struct User {
     pub institution_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub last_login: Option<DateTime<Utc>>,
+    /// User must choose a new password after their next login
+    pub password_reset_required: bool,
+    /// Sensitive routes need a second factor in this user's sessions
+    pub mfa_required: bool,
+    /// Which emails the user wants
+    pub notifications: NotificationPreferences,
+}
+
+/// Emails a user receives when SMTP is configured; all on by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationPreferences {
+    pub job_completed: bool,
+    pub job_failed: bool,
+    /// A result shared with the user by name or through one of their teams
+    pub shares: bool,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            job_completed: true,
+            job_failed: true,
+            shares: true,
+        }
+    }
+}
+
+impl NotificationPreferences {
+    fn from_row(row: &PgRow) -> Self {
+        Self {
+            job_completed: row.get("notify_job_completed"),
+            job_failed: row.get("notify_job_failed"),
+            shares: row.get("notify_shares"),
+        }
+    }
 }
 
 /// User creation request
@@ -27,6 +64,7 @@ pub struct CreateUser {
     pub password_hash: String,
     pub is_admin: bool,
     pub institution_id: Option<Uuid>,
+    pub password_reset_required: bool,
 }
 
 /// User store trait
@@ -44,9 +82,22 @@ pub trait UserStore: Send + Sync {
     /// List all users
     async fn list_users(&self) -> StorageResult<Vec<User>>;
 
-    /// Update user's password
+    /// Update user's password (clears any pending forced reset)
     async fn update_password(&self, id: Uuid, password_hash: &str) -> StorageResult<()>;
 
+    /// Require the user to change their password after the next login
+    async fn set_password_reset_required(&self, id: Uuid, required: bool) -> StorageResult<()>;
+
+    /// Require a second factor for sensitive routes, from the next login on
+    async fn set_mfa_required(&self, id: Uuid, required: bool) -> StorageResult<()>;
+
+    /// Choose which notification emails the user receives
+    async fn set_notification_preferences(
+        &self,
+        id: Uuid,
+        preferences: NotificationPreferences,
+    ) -> StorageResult<()>;
+
     /// Update user's active status
     async fn set_user_active(&self, id: Uuid, is_active: bool) -> StorageResult<()>;
 
@@ -119,6 +170,27 @@ impl PostgresUserStore {
             .execute(&self.pool)
             .await;
 
+        let _ = sqlx::query(
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS password_reset_required BOOLEAN NOT NULL DEFAULT FALSE",
+        )
+        .execute(&self.pool)
+        .await;
+
+        let _ = sqlx::query(
+            "ALTER TABLE users ADD COLUMN IF NOT EXISTS mfa_required BOOLEAN NOT NULL DEFAULT FALSE",
+        )
+        .execute(&self.pool)
+        .await;
+
+        for column in ["notify_job_completed", "notify_job_failed", "notify_shares"] {
+            let _ = sqlx::query(&format!(
+                "ALTER TABLE users ADD COLUMN IF NOT EXISTS {} BOOLEAN NOT NULL DEFAULT TRUE",
+                column
+            ))
+            .execute(&self.pool)
+            .await;
+        }
+
         Ok(())
     }
 }
@@ -131,8 +203,8 @@ impl UserStore for PostgresUserStore {
 
         sqlx::query(
             r#"
-            INSERT INTO users (id, email, display_name, password_hash, is_admin, is_active, institution_id, created_at)
-            VALUES ($1, $2, $3, $4, $5, TRUE, $6, $7)
+            INSERT INTO users (id, email, display_name, password_hash, is_admin, is_active, institution_id, created_at, password_reset_required)
+            VALUES ($1, $2, $3, $4, $5, TRUE, $6, $7, $8)
             "#,
         )
         .bind(id)
@@ -142,6 +214,7 @@ impl UserStore for PostgresUserStore {
         .bind(user.is_admin)
         .bind(user.institution_id)
         .bind(now)
+        .bind(user.password_reset_required)
         .execute(&self.pool)
         .await
         .map_err(|e| {
@@ -163,13 +236,18 @@ impl UserStore for PostgresUserStore {
             institution_id: user.institution_id,
             created_at: now,
             last_login: None,
+            password_reset_required: user.password_reset_required,
+            mfa_required: false,
+            notifications: NotificationPreferences::default(),
         })
     }
 
     async fn get_user(&self, id: Uuid) -> StorageResult<User> {
         let row = sqlx::query(
             r#"
-            SELECT id, email, display_name, password_hash, is_admin, is_active, institution_id, created_at, last_login
+            SELECT id, email, display_name, password_hash, is_admin, is_active, institution_id, created_at, last_login,
+                   password_reset_required, mfa_required,
+                   notify_job_completed, notify_job_failed, notify_shares
             FROM users
             WHERE id = $1
             "#,
@@ -189,13 +267,18 @@ impl UserStore for PostgresUserStore {
             institution_id: row.get("institution_id"),
             created_at: row.get("created_at"),
             last_login: row.get("last_login"),
+            password_reset_required: row.get("password_reset_required"),
+            mfa_required: row.get("mfa_required"),
+            notifications: NotificationPreferences::from_row(&row),
         })
     }
 
     async fn get_user_by_email(&self, email: &str) -> StorageResult<User> {
         let row = sqlx::query(
             r#"
-            SELECT id, email, display_name, password_hash, is_admin, is_active, institution_id, created_at, last_login
+            SELECT id, email, display_name, password_hash, is_admin, is_active, institution_id, created_at, last_login,
+                   password_reset_required, mfa_required,
+                   notify_job_completed, notify_job_failed, notify_shares
             FROM users
             WHERE email = $1
             "#,
@@ -215,13 +298,18 @@ impl UserStore for PostgresUserStore {
             institution_id: row.get("institution_id"),
             created_at: row.get("created_at"),
             last_login: row.get("last_login"),
+            password_reset_required: row.get("password_reset_required"),
+            mfa_required: row.get("mfa_required"),
+            notifications: NotificationPreferences::from_row(&row),
         })
     }
 
     async fn list_users(&self) -> StorageResult<Vec<User>> {
         let rows = sqlx::query(
             r#"
-            SELECT id, email, display_name, password_hash, is_admin, is_active, institution_id, created_at, last_login
+            SELECT id, email, display_name, password_hash, is_admin, is_active, institution_id, created_at, last_login,
+                   password_reset_required, mfa_required,
+                   notify_job_completed, notify_job_failed, notify_shares
             FROM users
             ORDER BY created_at ASC
             "#,
@@ -241,6 +329,9 @@ impl UserStore for PostgresUserStore {
                 institution_id: row.get("institution_id"),
                 created_at: row.get("created_at"),
                 last_login: row.get("last_login"),
+                password_reset_required: row.get("password_reset_required"),
+                mfa_required: row.get("mfa_required"),
+                notifications: NotificationPreferences::from_row(&row),
             })
             .collect())
     }
@@ -248,7 +339,7 @@ impl UserStore for PostgresUserStore {
     async fn update_password(&self, id: Uuid, password_hash: &str) -> StorageResult<()> {
         let result = sqlx::query(
             r#"
-            UPDATE users SET password_hash = $2 WHERE id = $1
+            UPDATE users SET password_hash = $2, password_reset_required = FALSE WHERE id = $1
             "#,
         )
         .bind(id)
@@ -281,6 +372,68 @@ impl UserStore for PostgresUserStore {
         Ok(())
     }
 
+    async fn set_password_reset_required(&self, id: Uuid, required: bool) -> StorageResult<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET password_reset_required = $2 WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(required)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::UserNotFound(id.to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn set_mfa_required(&self, id: Uuid, required: bool) -> StorageResult<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET mfa_required = $2 WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(required)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::UserNotFound(id.to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn set_notification_preferences(
+        &self,
+        id: Uuid,
+        preferences: NotificationPreferences,
+    ) -> StorageResult<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET notify_job_completed = $2, notify_job_failed = $3, notify_shares = $4
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(preferences.job_completed)
+        .bind(preferences.job_failed)
+        .bind(preferences.shares)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::UserNotFound(id.to_string()));
+        }
+
+        Ok(())
+    }
+
     async fn set_user_admin(&self, id: Uuid, is_admin: bool) -> StorageResult<()> {
         let result = sqlx::query(
             r#"
@@ -0,0 +1,301 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::traits::{StorageError, StorageResult};
+use super::types::UserId;
+
+/// A subscription to job lifecycle events
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    /// Owner; a personal webhook fires for the owner's jobs
+    pub user_id: UserId,
+    /// A team webhook fires for the jobs of every team member
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team_id: Option<Uuid>,
+    pub url: String,
+    /// HMAC key for payload signatures; only returned on creation
+    #[serde(skip)]
+    pub secret: String,
+    /// Event names subscribed to; all events when empty
+    pub events: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Fields of a webhook to be created
+#[derive(Debug)]
+pub struct CreateWebhook<'a> {
+    pub user_id: &'a str,
+    pub team_id: Option<Uuid>,
+    pub url: &'a str,
+    pub secret: &'a str,
+    pub events: &'a [String],
+}
+
+/// One attempt to deliver an event to a webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: String,
+    pub job_id: Uuid,
+    /// 1 for the first attempt
+    pub attempt: i32,
+    /// HTTP status of the response, if one arrived
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub delivered: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Webhook store trait
+#[async_trait]
+pub trait WebhookStore: Send + Sync {
+    /// Record a new webhook
+    async fn create_webhook(&self, webhook: CreateWebhook<'_>) -> StorageResult<Webhook>;
+
+    /// List the webhooks a user owns
+    async fn list_webhooks(&self, user_id: &str) -> StorageResult<Vec<Webhook>>;
+
+    /// Look up one webhook
+    async fn get_webhook(&self, id: Uuid) -> StorageResult<Webhook>;
+
+    /// Remove a webhook and its delivery log
+    async fn delete_webhook(&self, id: Uuid) -> StorageResult<()>;
+
+    /// Webhooks that fire for a user's jobs: their own and their teams'
+    async fn subscriptions_for(&self, user_id: &str) -> StorageResult<Vec<Webhook>>;
+
+    /// Log a delivery attempt
+    async fn record_delivery(&self, delivery: &WebhookDelivery) -> StorageResult<()>;
+
+    /// Most recent delivery attempts of a webhook, newest first
+    async fn list_deliveries(
+        &self,
+        webhook_id: Uuid,
+        limit: i64,
+    ) -> StorageResult<Vec<WebhookDelivery>>;
+}
+
+/// PostgreSQL implementation of WebhookStore
+pub struct PostgresWebhookStore {
+    pool: PgPool,
+}
+
+impl PostgresWebhookStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Initialize database schema for webhooks
+    pub async fn initialize(&self) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webhooks (
+                id UUID PRIMARY KEY,
+                user_id VARCHAR(255) NOT NULL,
+                team_id UUID,
+                url TEXT NOT NULL,
+                secret VARCHAR(64) NOT NULL,
+                events TEXT[] NOT NULL DEFAULT '{}',
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                id UUID PRIMARY KEY,
+                webhook_id UUID NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+                event VARCHAR(32) NOT NULL,
+                job_id UUID NOT NULL,
+                attempt INTEGER NOT NULL,
+                status_code INTEGER,
+                error TEXT,
+                delivered BOOLEAN NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_webhooks_user ON webhooks(user_id)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook
+                ON webhook_deliveries(webhook_id, created_at DESC)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn webhook_from_row(row: &sqlx::postgres::PgRow) -> Webhook {
+    Webhook {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        team_id: row.get("team_id"),
+        url: row.get("url"),
+        secret: row.get("secret"),
+        events: row.get("events"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[async_trait]
+impl WebhookStore for PostgresWebhookStore {
+    async fn create_webhook(&self, webhook: CreateWebhook<'_>) -> StorageResult<Webhook> {
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO webhooks (id, user_id, team_id, url, secret, events, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(id)
+        .bind(webhook.user_id)
+        .bind(webhook.team_id)
+        .bind(webhook.url)
+        .bind(webhook.secret)
+        .bind(webhook.events)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Webhook {
+            id,
+            user_id: webhook.user_id.to_string(),
+            team_id: webhook.team_id,
+            url: webhook.url.to_string(),
+            secret: webhook.secret.to_string(),
+            events: webhook.events.to_vec(),
+            created_at: now,
+        })
+    }
+
+    async fn list_webhooks(&self, user_id: &str) -> StorageResult<Vec<Webhook>> {
+        let rows = sqlx::query("SELECT * FROM webhooks WHERE user_id = $1 ORDER BY created_at ASC")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(webhook_from_row).collect())
+    }
+
+    async fn get_webhook(&self, id: Uuid) -> StorageResult<Webhook> {
+        let row = sqlx::query("SELECT * FROM webhooks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| StorageError::NotFound(format!("Webhook {}", id)))?;
+
+        Ok(webhook_from_row(&row))
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> StorageResult<()> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Webhook {}", id)));
+        }
+
+        Ok(())
+    }
+
+    async fn subscriptions_for(&self, user_id: &str) -> StorageResult<Vec<Webhook>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT w.* FROM webhooks w
+            WHERE (w.team_id IS NULL AND w.user_id = $1)
+               OR w.team_id IN (
+                    SELECT tm.team_id FROM team_members tm
+                    JOIN users u ON u.id = tm.user_id
+                    WHERE u.email = $1
+               )
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(webhook_from_row).collect())
+    }
+
+    async fn record_delivery(&self, delivery: &WebhookDelivery) -> StorageResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (id, webhook_id, event, job_id, attempt, status_code, error, delivered, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(delivery.id)
+        .bind(delivery.webhook_id)
+        .bind(&delivery.event)
+        .bind(delivery.job_id)
+        .bind(delivery.attempt)
+        .bind(delivery.status_code)
+        .bind(&delivery.error)
+        .bind(delivery.delivered)
+        .bind(delivery.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list_deliveries(
+        &self,
+        webhook_id: Uuid,
+        limit: i64,
+    ) -> StorageResult<Vec<WebhookDelivery>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM webhook_deliveries
+            WHERE webhook_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| WebhookDelivery {
+                id: row.get("id"),
+                webhook_id: row.get("webhook_id"),
+                event: row.get("event"),
+                job_id: row.get("job_id"),
+                attempt: row.get("attempt"),
+                status_code: row.get("status_code"),
+                error: row.get("error"),
+                delivered: row.get("delivered"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+}
@@ -30,6 +30,7 @@ impl BrokerDiscovery {
         institution: &str,
         auth_hash: &str,
         server_version: &str,
+        tls: bool,
     ) -> Result<()> {
         let hostname_base = hostname::get()
             .ok()
@@ -53,8 +54,8 @@ impl BrokerDiscovery {
         // SECURITY: Don't expose password hash via mDNS - authentication happens
         // during WebSocket handshake with password sent over encrypted channel
         let _ = auth_hash; // Parameter kept for API compatibility but not broadcast
-        // Client expects "tls" property - we use "false" since we use app-layer encryption
-        properties.insert("tls".to_string(), "false".to_string());
+        // Without built-in TLS, traffic relies on app-layer encryption
+        properties.insert("tls".to_string(), tls.to_string());
         properties.insert("encryption".to_string(), "aes256gcm".to_string());
 
         // Get local IP address - prefer IPv4 over IPv6, skip link-local
@@ -0,0 +1,668 @@
+//! Broker-to-broker federation
+//!
+//! Each institution runs its own broker. Brokers that federate peer over
+//! WebSocket links to `/federation`, authenticated with a secret shared by
+//! each pair of brokers, and qualify the ids they pass across with their
+//! namespace: share `abc` of the broker `ucsd` is `ucsd:abc` everywhere
+//! else, and its user `alice@ucsd.edu` is `ucsd:alice@ucsd.edu`.
+//!
+//! A broker forwards requests for shares in a peer's namespace to that
+//! peer, and relays notices for the peer's users over the link, where they
+//! are delivered like local ones. The owning broker only lets remote users
+//! see shares that name them, and never shares classified as PHI.
+
+use futures_util::{SinkExt, Stream, StreamExt};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::auth::constant_time_eq;
+use crate::storage::{AccessPolicyType, DataClassification, ShareToken, UserId};
+use crate::sync::types::SyncMessage;
+use crate::sync::websocket::{share_info, SyncState};
+
+/// Shortest secret accepted for a link
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// Messages held for a peer while its link is down
+const LINK_BACKLOG: usize = 1024;
+
+/// How long a peer may take to answer a share lookup
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause before dialing a peer again
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// A broker this one federates with
+#[derive(Clone)]
+pub struct FederationPeer {
+    pub namespace: String,
+    /// `wss://` URL of the peer's `/federation` endpoint; the peer dials in
+    /// when unset
+    pub url: Option<String>,
+    /// Shared by both ends of the link
+    pub secret: String,
+}
+
+impl std::fmt::Debug for FederationPeer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FederationPeer")
+            .field("namespace", &self.namespace)
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+/// This broker's namespace and peers, from `FEDERATION_NAMESPACE` and
+/// `FEDERATION_PEERS`
+#[derive(Debug, Clone)]
+pub struct FederationConfig {
+    pub namespace: String,
+    pub peers: Vec<FederationPeer>,
+}
+
+impl FederationConfig {
+    /// None unless `FEDERATION_NAMESPACE` is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(namespace) = std::env::var("FEDERATION_NAMESPACE")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+        else {
+            return Ok(None);
+        };
+        validate_namespace(&namespace)?;
+        let peers = parse_federation_peers(&std::env::var("FEDERATION_PEERS").unwrap_or_default())?;
+        if peers.iter().any(|peer| peer.namespace == namespace) {
+            return Err(format!(
+                "FEDERATION_PEERS lists this broker's own namespace '{}'",
+                namespace
+            ));
+        }
+        Ok(Some(Self { namespace, peers }))
+    }
+}
+
+fn validate_namespace(namespace: &str) -> Result<(), String> {
+    let valid = !namespace.is_empty()
+        && namespace.len() <= 32
+        && namespace
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Federation namespace '{}' must be up to 32 lowercase letters, digits or dashes",
+            namespace
+        ))
+    }
+}
+
+/// Parse `FEDERATION_PEERS`: comma-separated `<namespace> [<url>] <secret>`
+/// entries, e.g. "mit wss://ddalab.mit.edu/federation 5f2c...,ucla 9b1e..."
+pub fn parse_federation_peers(s: &str) -> Result<Vec<FederationPeer>, String> {
+    let mut peers: Vec<FederationPeer> = Vec::new();
+    for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let fields: Vec<&str> = entry.split_whitespace().collect();
+        let (namespace, url, secret) = match fields.as_slice() {
+            [namespace, secret] => (*namespace, None, *secret),
+            [namespace, url, secret] => (*namespace, Some(*url), *secret),
+            _ => {
+                return Err(format!(
+                    "Invalid federation peer '{}', expected '<namespace> [<url>] <secret>'",
+                    entry
+                ))
+            }
+        };
+        validate_namespace(namespace)?;
+        if let Some(url) = url {
+            if !url.starts_with("ws://") && !url.starts_with("wss://") {
+                return Err(format!(
+                    "URL of federation peer '{}' must start with wss:// or ws://",
+                    namespace
+                ));
+            }
+        }
+        if secret.len() < MIN_SECRET_LENGTH {
+            return Err(format!(
+                "Secret of federation peer '{}' must be at least {} characters",
+                namespace, MIN_SECRET_LENGTH
+            ));
+        }
+        if peers.iter().any(|peer| peer.namespace == namespace) {
+            return Err(format!("Federation peer '{}' is listed twice", namespace));
+        }
+        peers.push(FederationPeer {
+            namespace: namespace.to_string(),
+            url: url.map(str::to_string),
+            secret: secret.to_string(),
+        });
+    }
+    Ok(peers)
+}
+
+/// Messages exchanged between federated brokers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FederationMessage {
+    /// First message of the dialing broker
+    Hello { namespace: String, secret: String },
+
+    /// The link is up
+    Welcome { namespace: String },
+
+    /// Look up a share of the receiving broker for a user of the sender
+    ShareLookup {
+        request_id: Uuid,
+        token: ShareToken,
+        requester_id: UserId,
+    },
+
+    /// Answer to a lookup: `share_info` or `error`, as the sender's own
+    /// clients would get it
+    ShareLookupResult {
+        request_id: Uuid,
+        response: SyncMessage,
+    },
+
+    /// Deliver a notice to a user of the receiving broker
+    Relay {
+        user_id: UserId,
+        message: SyncMessage,
+    },
+
+    /// The link was refused
+    Error { message: String, code: String },
+}
+
+/// Outgoing messages to one peer, kept while its link is down
+struct PeerLink {
+    outgoing: mpsc::Sender<FederationMessage>,
+    /// Taken by the link while it is up
+    backlog: Mutex<Option<mpsc::Receiver<FederationMessage>>>,
+}
+
+/// Links to the peers of this broker; cheap to clone
+#[derive(Clone)]
+pub struct Federation {
+    config: Arc<FederationConfig>,
+    links: Arc<HashMap<String, PeerLink>>,
+    lookups: Arc<Mutex<HashMap<Uuid, oneshot::Sender<SyncMessage>>>>,
+}
+
+impl Federation {
+    pub fn new(config: FederationConfig) -> Self {
+        let links = config
+            .peers
+            .iter()
+            .map(|peer| {
+                let (outgoing, backlog) = mpsc::channel(LINK_BACKLOG);
+                let link = PeerLink {
+                    outgoing,
+                    backlog: Mutex::new(Some(backlog)),
+                };
+                (peer.namespace.clone(), link)
+            })
+            .collect();
+        Self {
+            config: Arc::new(config),
+            links: Arc::new(links),
+            lookups: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// This broker's namespace
+    pub fn namespace(&self) -> &str {
+        &self.config.namespace
+    }
+
+    pub fn peers(&self) -> &[FederationPeer] {
+        &self.config.peers
+    }
+
+    /// `id` as other brokers know it
+    pub fn qualify(&self, id: &str) -> String {
+        format!("{}:{}", self.config.namespace, id)
+    }
+
+    /// The peer namespace and the peer's own id of `id`, if it belongs to
+    /// a peer
+    pub fn remote<'a>(&self, id: &'a str) -> Option<(&'a str, &'a str)> {
+        id.split_once(':')
+            .filter(|(namespace, _)| self.links.contains_key(*namespace))
+    }
+
+    /// `id` without this broker's namespace
+    pub fn local<'a>(&self, id: &'a str) -> &'a str {
+        id.strip_prefix(self.config.namespace.as_str())
+            .and_then(|rest| rest.strip_prefix(':'))
+            .unwrap_or(id)
+    }
+
+    /// Whether the link to `namespace` is up
+    pub fn is_connected(&self, namespace: &str) -> bool {
+        self.links
+            .get(namespace)
+            .is_some_and(|link| link.backlog.lock().is_none())
+    }
+
+    /// Send `message` to the user `user_id` of the peer `namespace`, once
+    /// its link is up
+    pub fn relay(&self, namespace: &str, user_id: &str, message: SyncMessage) {
+        let Some(link) = self.links.get(namespace) else {
+            return;
+        };
+        let relay = FederationMessage::Relay {
+            user_id: user_id.to_string(),
+            message,
+        };
+        if link.outgoing.try_send(relay).is_err() {
+            warn!(
+                "Dropped a notice for {}:{}, its link is backed up",
+                namespace, user_id
+            );
+        }
+    }
+
+    /// Look up the share `token` of the peer `namespace` for `requester_id`
+    pub async fn lookup_share(
+        &self,
+        namespace: &str,
+        token: &str,
+        requester_id: &str,
+    ) -> SyncMessage {
+        let unavailable = || SyncMessage::Error {
+            message: format!("Broker '{}' is not connected", namespace),
+            code: "PEER_UNAVAILABLE".to_string(),
+        };
+        let Some(link) = self
+            .links
+            .get(namespace)
+            .filter(|_| self.is_connected(namespace))
+        else {
+            return unavailable();
+        };
+
+        let request_id = Uuid::new_v4();
+        let (reply, response) = oneshot::channel();
+        self.lookups.lock().insert(request_id, reply);
+        let lookup = FederationMessage::ShareLookup {
+            request_id,
+            token: token.to_string(),
+            requester_id: requester_id.to_string(),
+        };
+        if link.outgoing.try_send(lookup).is_err() {
+            self.lookups.lock().remove(&request_id);
+            return unavailable();
+        }
+        match tokio::time::timeout(LOOKUP_TIMEOUT, response).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => unavailable(),
+            Err(_) => {
+                self.lookups.lock().remove(&request_id);
+                SyncMessage::Error {
+                    message: format!("Broker '{}' did not answer in time", namespace),
+                    code: "PEER_TIMEOUT".to_string(),
+                }
+            }
+        }
+    }
+
+    /// Take the backlog of `namespace` for a new link; None if it is up
+    fn open(&self, namespace: &str) -> Option<mpsc::Receiver<FederationMessage>> {
+        self.links.get(namespace)?.backlog.lock().take()
+    }
+
+    /// Hand the backlog back when a link goes down
+    fn close(&self, namespace: &str, backlog: mpsc::Receiver<FederationMessage>) {
+        if let Some(link) = self.links.get(namespace) {
+            *link.backlog.lock() = Some(backlog);
+        }
+    }
+
+    fn secret_of(&self, namespace: &str) -> Option<&str> {
+        self.config
+            .peers
+            .iter()
+            .find(|peer| peer.namespace == namespace)
+            .map(|peer| peer.secret.as_str())
+    }
+}
+
+/// Answer a peer's lookup of one of this broker's shares
+async fn answer_share_lookup(
+    state: &SyncState,
+    namespace: &str,
+    token: &str,
+    requester_id: &str,
+) -> SyncMessage {
+    let metadata = match state.share_store.get_shared_result(token).await {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return SyncMessage::Error {
+                message: e.to_string(),
+                code: "SHARE_NOT_FOUND".to_string(),
+            }
+        }
+    };
+    let requester = format!("{}:{}", namespace, requester_id);
+    let policy = &metadata.access_policy;
+    let named = matches!(&policy.policy_type, AccessPolicyType::Users { user_ids } if user_ids.contains(&requester));
+    let allowed = named
+        && policy.organization_id.is_none()
+        && !policy.is_expired()
+        && metadata.classification != DataClassification::Phi;
+    if !allowed {
+        return SyncMessage::Error {
+            message: "Access denied".to_string(),
+            code: "ACCESS_DENIED".to_string(),
+        };
+    }
+    info!(
+        "Broker {} looked up share {} for {}",
+        namespace, token, requester
+    );
+    SyncMessage::ShareInfo {
+        info: share_info(state, metadata),
+    }
+}
+
+/// Exchange messages with the peer `namespace` until the link drops
+async fn run_link<S, R>(
+    state: &SyncState,
+    federation: &Federation,
+    namespace: &str,
+    mut sink: S,
+    mut stream: R,
+) where
+    S: futures_util::Sink<String> + Unpin,
+    S::Error: std::fmt::Display,
+    R: Stream<Item = String> + Unpin,
+{
+    let Some(mut backlog) = federation.open(namespace) else {
+        return;
+    };
+    info!("Federation link to {} is up", namespace);
+
+    loop {
+        let text = tokio::select! {
+            outgoing = backlog.recv() => {
+                let Some(outgoing) = outgoing else { break };
+                let Ok(json) = serde_json::to_string(&outgoing) else { continue };
+                if let Err(e) = sink.send(json).await {
+                    warn!("Federation link to {} failed: {}", namespace, e);
+                    break;
+                }
+                continue;
+            }
+            text = stream.next() => match text {
+                Some(text) => text,
+                None => break,
+            },
+        };
+        let message = match serde_json::from_str::<FederationMessage>(&text) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Invalid message from broker {}: {}", namespace, e);
+                continue;
+            }
+        };
+        match message {
+            FederationMessage::ShareLookup {
+                request_id,
+                token,
+                requester_id,
+            } => {
+                let response =
+                    answer_share_lookup(state, namespace, federation.local(&token), &requester_id)
+                        .await;
+                let result = FederationMessage::ShareLookupResult {
+                    request_id,
+                    response,
+                };
+                let Ok(json) = serde_json::to_string(&result) else {
+                    continue;
+                };
+                if let Err(e) = sink.send(json).await {
+                    warn!("Federation link to {} failed: {}", namespace, e);
+                    break;
+                }
+            }
+            FederationMessage::ShareLookupResult {
+                request_id,
+                response,
+            } => {
+                if let Some(reply) = federation.lookups.lock().remove(&request_id) {
+                    let _ = reply.send(response);
+                }
+            }
+            FederationMessage::Relay { user_id, message } => {
+                state
+                    .mailbox
+                    .deliver(federation.local(&user_id), message)
+                    .await;
+            }
+            FederationMessage::Hello { .. }
+            | FederationMessage::Welcome { .. }
+            | FederationMessage::Error { .. } => {
+                warn!("Unexpected handshake message from broker {}", namespace);
+            }
+        }
+    }
+
+    federation.close(namespace, backlog);
+    info!("Federation link to {} is down", namespace);
+}
+
+/// Accept a link from a peer at `/federation`
+pub async fn handle_federation_link(
+    ws: axum::extract::WebSocketUpgrade,
+    axum::extract::State(state): axum::extract::State<SyncState>,
+) -> axum::response::Response {
+    use axum::extract::ws::Message;
+    use axum::response::IntoResponse;
+
+    let Some(federation) = state.federation.clone() else {
+        return axum::http::StatusCode::NOT_FOUND.into_response();
+    };
+    ws.on_upgrade(move |socket| async move {
+        let (sink, stream) = socket.split();
+        let mut sink = sink.with(|text: String| {
+            std::future::ready(Ok::<_, axum::Error>(Message::Text(text.into())))
+        });
+        let mut stream = Box::pin(
+            stream
+                .take_while(|message| {
+                    std::future::ready(matches!(message, Ok(m) if !matches!(m, Message::Close(_))))
+                })
+                .filter_map(|message| async move {
+                    match message {
+                        Ok(Message::Text(text)) => Some(text.to_string()),
+                        _ => None,
+                    }
+                }),
+        );
+
+        let hello = match stream.next().await.map(|text| serde_json::from_str(&text)) {
+            Some(Ok(FederationMessage::Hello { namespace, secret })) => (namespace, secret),
+            _ => return,
+        };
+        let (namespace, secret) = hello;
+        let refusal = match federation.secret_of(&namespace) {
+            Some(expected) if constant_time_eq(expected.as_bytes(), secret.as_bytes()) => {
+                if federation.is_connected(&namespace) {
+                    Some(("Already linked", "LINK_EXISTS"))
+                } else {
+                    None
+                }
+            }
+            _ => {
+                warn!("Refused a federation link claiming to be {}", namespace);
+                Some(("Invalid namespace or secret", "AUTH_FAILED"))
+            }
+        };
+        let reply = match refusal {
+            Some((message, code)) => FederationMessage::Error {
+                message: message.to_string(),
+                code: code.to_string(),
+            },
+            None => FederationMessage::Welcome {
+                namespace: federation.namespace().to_string(),
+            },
+        };
+        let Ok(json) = serde_json::to_string(&reply) else {
+            return;
+        };
+        if sink.send(json).await.is_err() || refusal.is_some() {
+            return;
+        }
+        run_link(&state, &federation, &namespace, sink, stream).await;
+    })
+}
+
+/// Dial the peers with a URL, and again whenever their link drops
+pub fn spawn_federation_links(state: SyncState) {
+    let Some(federation) = state.federation.clone() else {
+        return;
+    };
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    for peer in federation
+        .peers()
+        .iter()
+        .filter(|peer| peer.url.is_some())
+        .cloned()
+    {
+        let (state, federation) = (state.clone(), federation.clone());
+        tokio::spawn(async move {
+            loop {
+                if !federation.is_connected(&peer.namespace) {
+                    if let Err(e) = dial(&state, &federation, &peer).await {
+                        warn!("Federation link to {} failed: {}", peer.namespace, e);
+                    }
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+}
+
+async fn dial(
+    state: &SyncState,
+    federation: &Federation,
+    peer: &FederationPeer,
+) -> anyhow::Result<()> {
+    use tokio_tungstenite::tungstenite::Message;
+
+    let url = peer.url.as_deref().unwrap_or_default();
+    let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+    let (sink, stream) = socket.split();
+    let mut sink = sink.with(|text: String| {
+        std::future::ready(Ok::<_, tokio_tungstenite::tungstenite::Error>(
+            Message::Text(text.into()),
+        ))
+    });
+    let mut stream = Box::pin(
+        stream
+            .take_while(|message| std::future::ready(matches!(message, Ok(m) if !m.is_close())))
+            .filter_map(|message| async move {
+                match message {
+                    Ok(Message::Text(text)) => Some(text.to_string()),
+                    _ => None,
+                }
+            }),
+    );
+
+    let hello = FederationMessage::Hello {
+        namespace: federation.namespace().to_string(),
+        secret: peer.secret.clone(),
+    };
+    sink.send(serde_json::to_string(&hello)?).await?;
+    match stream.next().await.map(|text| serde_json::from_str(&text)) {
+        Some(Ok(FederationMessage::Welcome { namespace })) if namespace == peer.namespace => {}
+        Some(Ok(FederationMessage::Error { message, .. })) => anyhow::bail!("refused: {}", message),
+        _ => anyhow::bail!("unexpected handshake"),
+    }
+    run_link(state, federation, &peer.namespace, sink, stream).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_parse_federation_peers() {
+        let peers = parse_federation_peers(&format!(
+            "mit wss://ddalab.mit.edu/federation {0}, ucla {0}",
+            SECRET
+        ))
+        .unwrap();
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].namespace, "mit");
+        assert_eq!(
+            peers[0].url.as_deref(),
+            Some("wss://ddalab.mit.edu/federation")
+        );
+        assert_eq!(peers[1].url, None);
+        assert!(parse_federation_peers("").unwrap().is_empty());
+
+        assert!(parse_federation_peers("mit short").is_err());
+        assert!(parse_federation_peers(&format!("MIT {}", SECRET)).is_err());
+        assert!(parse_federation_peers(&format!("mit https://mit.edu {}", SECRET)).is_err());
+        assert!(parse_federation_peers(&format!("mit {0},mit {0}", SECRET)).is_err());
+    }
+
+    #[test]
+    fn test_ids_are_routed_by_namespace() {
+        let federation = Federation::new(FederationConfig {
+            namespace: "ucsd".to_string(),
+            peers: parse_federation_peers(&format!("mit {}", SECRET)).unwrap(),
+        });
+        assert_eq!(federation.remote("mit:abc"), Some(("mit", "abc")));
+        assert_eq!(
+            federation.remote("mit:bob@mit.edu"),
+            Some(("mit", "bob@mit.edu"))
+        );
+        assert_eq!(federation.remote("ucla:abc"), None);
+        assert_eq!(federation.remote("abc"), None);
+        assert_eq!(federation.local("ucsd:abc"), "abc");
+        assert_eq!(federation.local("abc"), "abc");
+        assert_eq!(federation.qualify("alice@ucsd.edu"), "ucsd:alice@ucsd.edu");
+    }
+
+    #[tokio::test]
+    async fn test_relays_wait_for_the_link() {
+        let federation = Federation::new(FederationConfig {
+            namespace: "ucsd".to_string(),
+            peers: parse_federation_peers(&format!("mit {}", SECRET)).unwrap(),
+        });
+        assert!(!federation.is_connected("mit"));
+        federation.relay("mit", "bob@mit.edu", SyncMessage::Ack { message_id: None });
+
+        let mut backlog = federation.open("mit").unwrap();
+        assert!(federation.is_connected("mit"));
+        assert!(federation.open("mit").is_none());
+        match backlog.recv().await.unwrap() {
+            FederationMessage::Relay { user_id, .. } => assert_eq!(user_id, "bob@mit.edu"),
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        // Lookups fail fast while a link is down
+        federation.close("mit", backlog);
+        match federation
+            .lookup_share("mit", "abc", "alice@ucsd.edu")
+            .await
+        {
+            SyncMessage::Error { code, .. } => assert_eq!(code, "PEER_UNAVAILABLE"),
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,407 @@
+//! Store-and-forward delivery of notices to users
+//!
+//! Notices meant for particular users, such as a share naming them or a
+//! scheduled run of their team, go straight to their sync WebSocket while
+//! they are connected. Otherwise they are queued in Postgres and replayed,
+//! oldest first, right after the user next registers. Queued messages
+//! expire after `OFFLINE_MESSAGE_TTL_HOURS`, and each user's queue keeps
+//! only the newest `OFFLINE_QUEUE_LIMIT` messages.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::notifications::share_recipients;
+use crate::storage::{AccessPolicyType, OfflineMessageStore, ShareMetadata, UserId, UserStore};
+use crate::sync::federation::Federation;
+use crate::sync::types::SyncMessage;
+
+/// Messages a connection may fall behind by before further ones are queued
+pub const CONNECTION_BUFFER: usize = 64;
+
+/// How often expired messages are deleted
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How long undeliverable messages are kept, and how many per user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OfflineQueuePolicy {
+    pub ttl: Duration,
+    /// Messages kept per user, dropping the oldest; none when 0
+    pub limit: usize,
+}
+
+impl Default for OfflineQueuePolicy {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(7 * 24 * 3600),
+            limit: 100,
+        }
+    }
+}
+
+/// Routes messages to connected users and queues them for the others;
+/// cheap to clone
+#[derive(Clone)]
+pub struct Mailbox {
+    /// Connection of each registered user. Held for reading while a
+    /// message is queued, so that nothing is queued after the user's new
+    /// connection has taken the queue.
+    connections: Arc<RwLock<HashMap<UserId, mpsc::Sender<SyncMessage>>>>,
+    store: Arc<dyn OfflineMessageStore>,
+    policy: OfflineQueuePolicy,
+    /// Relays messages for users of federated brokers
+    federation: Option<Federation>,
+}
+
+impl Mailbox {
+    pub fn new(store: Arc<dyn OfflineMessageStore>, policy: OfflineQueuePolicy) -> Self {
+        Self {
+            connections: Arc::new(RwLock::new(HashMap::new())),
+            store,
+            policy,
+            federation: None,
+        }
+    }
+
+    /// Relay messages for users of `federation`'s peers over its links
+    pub fn with_federation(mut self, federation: Option<Federation>) -> Self {
+        self.federation = federation;
+        self
+    }
+
+    pub fn federation(&self) -> Option<&Federation> {
+        self.federation.as_ref()
+    }
+
+    /// Send `message` to `user_id`, or queue it until they connect
+    pub async fn deliver(&self, user_id: &str, message: SyncMessage) {
+        if let Some(federation) = &self.federation {
+            if let Some((namespace, remote_user)) = federation.remote(user_id) {
+                federation.relay(namespace, remote_user, message);
+                return;
+            }
+        }
+        let connections = self.connections.read().await;
+        let message = match connections.get(user_id) {
+            Some(connection) => match connection.try_send(message) {
+                Ok(()) => return,
+                Err(TrySendError::Full(message)) => {
+                    warn!("Connection of {} is falling behind, queueing", user_id);
+                    message
+                }
+                Err(TrySendError::Closed(message)) => message,
+            },
+            None => message,
+        };
+        self.queue(user_id, &message).await;
+    }
+
+    /// Send `message` to `user_id` if they are connected here; unlike
+    /// [`Mailbox::deliver`] nothing is queued, so only for messages that are
+    /// useless later
+    pub async fn send_now(&self, user_id: &str, message: SyncMessage) -> bool {
+        match self.connections.read().await.get(user_id) {
+            Some(connection) => connection.try_send(message).is_ok(),
+            None => false,
+        }
+    }
+
+    async fn queue(&self, user_id: &str, message: &SyncMessage) {
+        if self.policy.limit == 0 {
+            return;
+        }
+        let message = match serde_json::to_value(message) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Failed to serialize a message for {}: {}", user_id, e);
+                return;
+            }
+        };
+        let expires_at = chrono::Duration::from_std(self.policy.ttl)
+            .ok()
+            .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        match self
+            .store
+            .enqueue(user_id, &message, expires_at, self.policy.limit)
+            .await
+        {
+            Ok(0) => {}
+            Ok(dropped) => warn!(
+                "Dropped the {} oldest queued messages for {}",
+                dropped, user_id
+            ),
+            Err(e) => warn!("Failed to queue a message for {}: {}", user_id, e),
+        }
+    }
+
+    /// Route messages for `user_id` to `connection` from now on, replacing
+    /// any earlier connection
+    ///
+    /// Returns the messages queued while they were away, oldest first, to
+    /// be sent ahead of anything arriving on the connection and then
+    /// [acknowledged](Mailbox::acknowledge).
+    pub async fn attach(
+        &self,
+        user_id: &str,
+        connection: mpsc::Sender<SyncMessage>,
+    ) -> Vec<(i64, SyncMessage)> {
+        self.connections
+            .write()
+            .await
+            .insert(user_id.to_string(), connection);
+
+        let queued = match self.store.pending(user_id).await {
+            Ok(queued) => queued,
+            Err(e) => {
+                warn!("Failed to load queued messages for {}: {}", user_id, e);
+                return Vec::new();
+            }
+        };
+        let mut messages = Vec::with_capacity(queued.len());
+        let mut unreadable = Vec::new();
+        for queued in queued {
+            match serde_json::from_value(queued.message) {
+                Ok(message) => messages.push((queued.id, message)),
+                Err(e) => {
+                    warn!(
+                        "Discarding unreadable queued message {} for {}: {}",
+                        queued.id, user_id, e
+                    );
+                    unreadable.push(queued.id);
+                }
+            }
+        }
+        if !unreadable.is_empty() {
+            self.acknowledge(user_id, &unreadable).await;
+        }
+        messages
+    }
+
+    /// Remove queued messages that were sent to `user_id`
+    pub async fn acknowledge(&self, user_id: &str, ids: &[i64]) {
+        if ids.is_empty() {
+            return;
+        }
+        if let Err(e) = self.store.remove(user_id, ids).await {
+            warn!("Failed to remove delivered messages for {}: {}", user_id, e);
+        }
+    }
+
+    /// Stop routing messages for `user_id` to `connection`, unless they
+    /// have connected again since
+    pub async fn detach(&self, user_id: &str, connection: &mpsc::Sender<SyncMessage>) {
+        let mut connections = self.connections.write().await;
+        if connections
+            .get(user_id)
+            .is_some_and(|current| current.same_channel(connection))
+        {
+            connections.remove(user_id);
+        }
+    }
+}
+
+/// Start deleting expired queued messages every hour
+pub fn spawn_offline_message_purger(store: Arc<dyn OfflineMessageStore>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match store.purge_expired().await {
+                Ok(0) => {}
+                Ok(purged) => info!("Deleted {} expired offline messages", purged),
+                Err(e) => warn!("Failed to delete expired offline messages: {}", e),
+            }
+        }
+    });
+}
+
+/// Tell everyone a new share names, directly or through a team, except its
+/// owner, about it
+pub async fn notify_share_recipients(
+    mailbox: Mailbox,
+    user_store: Arc<dyn UserStore>,
+    pool: PgPool,
+    token: String,
+    share: ShareMetadata,
+) {
+    let notice = |token: String, owner_user_id: String| SyncMessage::ShareNotice {
+        token,
+        owner_user_id,
+        title: share.title.clone(),
+        description: share.description.clone(),
+        shared_at: share.created_at,
+    };
+
+    let local = notice(token.clone(), share.owner_user_id.clone());
+    for user in share_recipients(user_store.as_ref(), pool, &share).await {
+        if user.is_active && user.email != share.owner_user_id {
+            mailbox.deliver(&user.email, local.clone()).await;
+        }
+    }
+
+    // Users of federated brokers are named with their broker's namespace,
+    // and look the share up under this one's
+    if let (Some(federation), AccessPolicyType::Users { user_ids }) =
+        (mailbox.federation(), &share.access_policy.policy_type)
+    {
+        let remote = notice(
+            federation.qualify(&token),
+            federation.qualify(&share.owner_user_id),
+        );
+        for user_id in user_ids {
+            if federation.remote(user_id).is_some() {
+                mailbox.deliver(user_id, remote.clone()).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{OfflineMessage, StorageResult};
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        messages: Mutex<Vec<OfflineMessage>>,
+    }
+
+    #[async_trait]
+    impl OfflineMessageStore for MemoryStore {
+        async fn enqueue(
+            &self,
+            user_id: &str,
+            message: &serde_json::Value,
+            expires_at: DateTime<Utc>,
+            limit: usize,
+        ) -> StorageResult<u64> {
+            let mut messages = self.messages.lock().unwrap();
+            let id = messages.last().map_or(1, |m| m.id + 1);
+            messages.push(OfflineMessage {
+                id,
+                user_id: user_id.to_string(),
+                message: message.clone(),
+                queued_at: Utc::now(),
+                expires_at,
+            });
+            let queued = messages.iter().filter(|m| m.user_id == user_id).count();
+            let mut excess = queued.saturating_sub(limit);
+            let dropped = excess as u64;
+            messages.retain(|m| {
+                let drop = excess > 0 && m.user_id == user_id;
+                if drop {
+                    excess -= 1;
+                }
+                !drop
+            });
+            Ok(dropped)
+        }
+
+        async fn pending(&self, user_id: &str) -> StorageResult<Vec<OfflineMessage>> {
+            let now = Utc::now();
+            Ok(self
+                .messages
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|m| m.user_id == user_id && m.expires_at > now)
+                .cloned()
+                .collect())
+        }
+
+        async fn remove(&self, user_id: &str, ids: &[i64]) -> StorageResult<()> {
+            self.messages
+                .lock()
+                .unwrap()
+                .retain(|m| m.user_id != user_id || !ids.contains(&m.id));
+            Ok(())
+        }
+
+        async fn purge_expired(&self) -> StorageResult<u64> {
+            Ok(0)
+        }
+    }
+
+    fn withdrawn() -> SyncMessage {
+        SyncMessage::AnnouncementWithdrawn { id: Uuid::new_v4() }
+    }
+
+    fn withdrawn_id(message: &SyncMessage) -> Uuid {
+        match message {
+            SyncMessage::AnnouncementWithdrawn { id } => *id,
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_messages_for_offline_users_are_replayed_on_connect() {
+        let mailbox = Mailbox::new(
+            Arc::new(MemoryStore::default()),
+            OfflineQueuePolicy {
+                limit: 2,
+                ..Default::default()
+            },
+        );
+        let sent = [withdrawn(), withdrawn(), withdrawn()];
+        for message in &sent {
+            mailbox.deliver("bob@example.edu", message.clone()).await;
+        }
+
+        let (connection, mut inbox) = mpsc::channel(CONNECTION_BUFFER);
+        let queued = mailbox.attach("bob@example.edu", connection.clone()).await;
+        // Only the newest messages fit the queue
+        let replayed: Vec<Uuid> = queued.iter().map(|(_, m)| withdrawn_id(m)).collect();
+        assert_eq!(
+            replayed,
+            vec![withdrawn_id(&sent[1]), withdrawn_id(&sent[2])]
+        );
+        let ids: Vec<i64> = queued.iter().map(|(id, _)| *id).collect();
+        mailbox.acknowledge("bob@example.edu", &ids).await;
+
+        // Connected users get messages straight away
+        let live = withdrawn();
+        mailbox.deliver("bob@example.edu", live.clone()).await;
+        assert_eq!(
+            withdrawn_id(&inbox.try_recv().unwrap()),
+            withdrawn_id(&live)
+        );
+
+        mailbox.detach("bob@example.edu", &connection).await;
+        let (connection, _inbox) = mpsc::channel(CONNECTION_BUFFER);
+        assert!(mailbox
+            .attach("bob@example.edu", connection)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_detaching_a_replaced_connection_keeps_the_new_one() {
+        let mailbox = Mailbox::new(
+            Arc::new(MemoryStore::default()),
+            OfflineQueuePolicy::default(),
+        );
+        let (old, _old_inbox) = mpsc::channel(CONNECTION_BUFFER);
+        let (new, mut new_inbox) = mpsc::channel(CONNECTION_BUFFER);
+        mailbox.attach("bob@example.edu", old.clone()).await;
+        mailbox.attach("bob@example.edu", new.clone()).await;
+        mailbox.detach("bob@example.edu", &old).await;
+
+        mailbox.deliver("bob@example.edu", withdrawn()).await;
+        assert!(new_inbox.try_recv().is_ok());
+
+        mailbox.detach("bob@example.edu", &new).await;
+        mailbox.deliver("bob@example.edu", withdrawn()).await;
+        assert!(new_inbox.try_recv().is_err());
+        let (again, _inbox) = mpsc::channel(CONNECTION_BUFFER);
+        assert_eq!(mailbox.attach("bob@example.edu", again).await.len(), 1);
+    }
+}
@@ -1,9 +1,21 @@
 mod discovery;
+mod federation;
+mod mailbox;
+mod presence;
 mod registry;
 mod types;
 pub mod websocket;
 
 pub use discovery::{BrokerDiscovery, hash_psk, verify_psk};
-pub use registry::{RegistrationResult, UserRegistry};
+pub use federation::{
+    handle_federation_link, parse_federation_peers, spawn_federation_links, Federation,
+    FederationConfig, FederationMessage, FederationPeer,
+};
+pub use mailbox::{
+    notify_share_recipients, spawn_offline_message_purger, Mailbox, OfflineQueuePolicy,
+    CONNECTION_BUFFER,
+};
+pub use presence::{roster, team_members, teammates, PresenceEntry, PresenceWatch};
+pub use registry::{PresenceChange, RegistrationResult, UserRegistry};
 pub use types::SyncMessage;
 pub use websocket::{handle_websocket, SyncState};
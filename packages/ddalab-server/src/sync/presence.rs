@@ -0,0 +1,204 @@
+//! Team presence
+//!
+//! Users see which of their teammates are online and the endpoints their
+//! clients advertised on registering, so a client knows whether a peer
+//! download can work before attempting it. Presence is only shown to users
+//! sharing a team.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::{BTreeSet, HashSet};
+use tokio::sync::broadcast;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::storage::{PostgresTeamStore, StorageResult, TeamStore, User, UserId, UserStore};
+use crate::sync::registry::{PresenceChange, UserRegistry};
+use crate::sync::types::SyncMessage;
+
+/// A teammate's availability
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresenceEntry {
+    pub user_id: UserId,
+    pub display_name: String,
+    pub online: bool,
+    /// Where the teammate's client serves peer downloads, while online
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connected_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_heartbeat: Option<DateTime<Utc>>,
+}
+
+/// Users sharing at least one team with `user_id`, not including them
+pub async fn teammates(
+    user_store: &dyn UserStore,
+    pool: &PgPool,
+    user_id: Uuid,
+) -> StorageResult<Vec<User>> {
+    let team_store = PostgresTeamStore::new(pool.clone());
+    let mut member_ids = BTreeSet::new();
+    for team in team_store.list_user_teams(user_id).await? {
+        for member in team_store.get_team_members(team.id).await? {
+            member_ids.insert(member.user_id);
+        }
+    }
+    member_ids.remove(&user_id);
+    members(user_store, member_ids).await
+}
+
+/// Members of a team
+pub async fn team_members(
+    user_store: &dyn UserStore,
+    pool: &PgPool,
+    team_id: Uuid,
+) -> StorageResult<Vec<User>> {
+    let member_ids = PostgresTeamStore::new(pool.clone())
+        .get_team_members(team_id)
+        .await?
+        .into_iter()
+        .map(|member| member.user_id)
+        .collect();
+    members(user_store, member_ids).await
+}
+
+async fn members(
+    user_store: &dyn UserStore,
+    member_ids: BTreeSet<Uuid>,
+) -> StorageResult<Vec<User>> {
+    let mut users = Vec::with_capacity(member_ids.len());
+    for member_id in member_ids {
+        match user_store.get_user(member_id).await {
+            Ok(user) => users.push(user),
+            Err(e) => warn!("Skipping team member {} in presence: {}", member_id, e),
+        }
+    }
+    Ok(users)
+}
+
+/// The current presence of `users`, online users first
+pub fn roster(registry: &UserRegistry, users: &[User]) -> Vec<PresenceEntry> {
+    let mut entries: Vec<PresenceEntry> = users
+        .iter()
+        .map(|user| {
+            let connection = registry.get_connection(&user.email);
+            PresenceEntry {
+                user_id: user.email.clone(),
+                display_name: user.display_name.clone(),
+                online: connection.is_some(),
+                endpoint: connection.as_ref().map(|c| c.endpoint.clone()),
+                connected_at: connection.as_ref().map(|c| c.connected_at),
+                last_heartbeat: connection.as_ref().map(|c| c.last_heartbeat),
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        b.online
+            .cmp(&a.online)
+            .then_with(|| a.display_name.cmp(&b.display_name))
+    });
+    entries
+}
+
+/// A connection's subscription to the presence of its user's teammates
+pub struct PresenceWatch {
+    changes: broadcast::Receiver<PresenceChange>,
+    watched: HashSet<UserId>,
+}
+
+impl PresenceWatch {
+    /// Watch `watched` for changes received from `changes`, which should
+    /// be subscribed before the roster is read so no change is missed
+    pub fn new(changes: broadcast::Receiver<PresenceChange>, watched: HashSet<UserId>) -> Self {
+        Self { changes, watched }
+    }
+
+    /// The next change to a watched user's presence
+    pub async fn next(&mut self) -> Option<SyncMessage> {
+        loop {
+            match self.changes.recv().await {
+                Ok(change) if self.watched.contains(&change.user_id) => {
+                    return Some(SyncMessage::PresenceChanged {
+                        online: change.endpoint.is_some(),
+                        user_id: change.user_id,
+                        endpoint: change.endpoint,
+                    });
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Dropped {} presence changes for a slow client", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::NotificationPreferences;
+
+    fn user(email: &str, display_name: &str) -> User {
+        User {
+            id: Uuid::new_v4(),
+            email: email.to_string(),
+            display_name: display_name.to_string(),
+            password_hash: String::new(),
+            is_admin: false,
+            is_active: true,
+            institution_id: None,
+            created_at: Utc::now(),
+            last_login: None,
+            password_reset_required: false,
+            mfa_required: false,
+            notifications: NotificationPreferences::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_roster_and_watch() {
+        let registry = UserRegistry::new();
+        let users = [
+            user("alice@example.org", "Alice"),
+            user("bob@example.org", "Bob"),
+        ];
+        let mut watch = PresenceWatch::new(
+            registry.subscribe(),
+            users.iter().map(|u| u.email.clone()).collect(),
+        );
+        registry.register(
+            "carol@example.org".to_string(),
+            Uuid::new_v4(),
+            "http://10.0.0.3:8765".to_string(),
+        );
+        registry.register(
+            "bob@example.org".to_string(),
+            Uuid::new_v4(),
+            "http://10.0.0.2:8765".to_string(),
+        );
+
+        let entries = roster(&registry, &users);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].user_id, "bob@example.org");
+        assert_eq!(entries[0].endpoint.as_deref(), Some("http://10.0.0.2:8765"));
+        assert!(!entries[1].online);
+        assert!(entries[1].endpoint.is_none());
+
+        // Carol is not watched
+        match watch.next().await {
+            Some(SyncMessage::PresenceChanged {
+                user_id,
+                online,
+                endpoint,
+            }) => {
+                assert_eq!(user_id, "bob@example.org");
+                assert!(online);
+                assert_eq!(endpoint.as_deref(), Some("http://10.0.0.2:8765"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -2,6 +2,7 @@ use chrono::Utc;
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
 use crate::storage::{ConnectionInfo, UserId};
@@ -9,6 +10,9 @@ use crate::storage::{ConnectionInfo, UserId};
 /// Default maximum connections to prevent DoS
 const DEFAULT_MAX_CONNECTIONS: usize = 10_000;
 
+/// Presence changes kept for subscribers that fall behind
+const PRESENCE_BUFFER: usize = 256;
+
 /// Registration result
 #[derive(Debug, Clone, PartialEq)]
 pub enum RegistrationResult {
@@ -20,11 +24,20 @@ pub enum RegistrationResult {
     AtCapacity,
 }
 
+/// A user registered, possibly from a new endpoint, or went offline
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresenceChange {
+    pub user_id: UserId,
+    /// The advertised endpoint; `None` once the user is offline
+    pub endpoint: Option<String>,
+}
+
 /// In-memory registry of connected users
 #[derive(Clone)]
 pub struct UserRegistry {
     connections: Arc<RwLock<HashMap<UserId, ConnectionInfo>>>,
     max_connections: usize,
+    changes: broadcast::Sender<PresenceChange>,
 }
 
 impl UserRegistry {
@@ -34,12 +47,24 @@ impl UserRegistry {
 
     /// Create registry with custom max connections
     pub fn with_capacity(max_connections: usize) -> Self {
+        let (changes, _) = broadcast::channel(PRESENCE_BUFFER);
         Self {
             connections: Arc::new(RwLock::new(HashMap::new())),
             max_connections,
+            changes,
         }
     }
 
+    /// Receive every presence change from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<PresenceChange> {
+        self.changes.subscribe()
+    }
+
+    fn announce(&self, user_id: UserId, endpoint: Option<String>) {
+        // No subscribers is fine
+        let _ = self.changes.send(PresenceChange { user_id, endpoint });
+    }
+
     /// Register a new user connection
     /// Returns RegistrationResult indicating success or failure
     pub fn register(&self, user_id: UserId, session_id: Uuid, endpoint: String) -> RegistrationResult {
@@ -47,7 +72,7 @@ impl UserRegistry {
         let info = ConnectionInfo {
             user_id: user_id.clone(),
             session_id,
-            endpoint,
+            endpoint: endpoint.clone(),
             connected_at: now,
             last_heartbeat: now,
         };
@@ -55,18 +80,19 @@ impl UserRegistry {
         let mut connections = self.connections.write();
 
         // Check if replacing existing connection
-        if connections.contains_key(&user_id) {
-            connections.insert(user_id, info);
-            return RegistrationResult::Replaced;
-        }
-
-        // Check capacity before inserting new connection
-        if connections.len() >= self.max_connections {
+        let result = if connections.contains_key(&user_id) {
+            RegistrationResult::Replaced
+        } else if connections.len() >= self.max_connections {
+            // Check capacity before inserting new connection
             return RegistrationResult::AtCapacity;
-        }
+        } else {
+            RegistrationResult::Ok
+        };
 
-        connections.insert(user_id, info);
-        RegistrationResult::Ok
+        connections.insert(user_id.clone(), info);
+        drop(connections);
+        self.announce(user_id, Some(endpoint));
+        result
     }
 
     /// Update heartbeat timestamp
@@ -81,7 +107,10 @@ impl UserRegistry {
 
     /// Disconnect a user
     pub fn disconnect(&self, user_id: &UserId) {
-        self.connections.write().remove(user_id);
+        let removed = self.connections.write().remove(user_id);
+        if removed.is_some() {
+            self.announce(user_id.clone(), None);
+        }
     }
 
     /// Check if a user is online
@@ -107,15 +136,19 @@ impl UserRegistry {
     /// Remove stale connections (no heartbeat in last N seconds)
     pub fn cleanup_stale(&self, timeout_seconds: i64) -> usize {
         let now = Utc::now();
-        let mut removed = 0;
-        self.connections.write().retain(|_, conn| {
+        let mut removed = Vec::new();
+        self.connections.write().retain(|user_id, conn| {
             let is_stale = (now - conn.last_heartbeat).num_seconds() >= timeout_seconds;
             if is_stale {
-                removed += 1;
+                removed.push(user_id.clone());
             }
             !is_stale
         });
-        removed
+        let count = removed.len();
+        for user_id in removed {
+            self.announce(user_id, None);
+        }
+        count
     }
 }
 
@@ -182,4 +215,32 @@ mod tests {
         registry.disconnect(&"user1".to_string());
         assert_eq!(registry.connection_count(), 1);
     }
+
+    #[test]
+    fn test_presence_changes() {
+        let registry = UserRegistry::new();
+        let mut changes = registry.subscribe();
+        let user_id = "test_user".to_string();
+
+        registry.register(user_id.clone(), Uuid::new_v4(), "http://localhost:8765".to_string());
+        registry.disconnect(&user_id);
+        // Disconnecting an offline user changes nothing
+        registry.disconnect(&user_id);
+
+        assert_eq!(
+            changes.try_recv().unwrap(),
+            PresenceChange {
+                user_id: user_id.clone(),
+                endpoint: Some("http://localhost:8765".to_string()),
+            }
+        );
+        assert_eq!(
+            changes.try_recv().unwrap(),
+            PresenceChange {
+                user_id,
+                endpoint: None,
+            }
+        );
+        assert!(changes.try_recv().is_err());
+    }
 }
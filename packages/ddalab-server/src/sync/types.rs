@@ -1,6 +1,10 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::storage::{ShareMetadata, SharedResultInfo, UserId, ShareToken};
+use crate::maintenance::MaintenanceWindow;
+use crate::scheduler::ScheduleRun;
+use crate::storage::{Announcement, ShareMetadata, SharedResultInfo, UserId, ShareToken};
+use crate::sync::presence::PresenceEntry;
 
 /// Messages exchanged between local instances and the server
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +33,13 @@ pub enum SyncMessage {
         user_id: UserId,
     },
 
+    /// Receive the presence of the user's teammates, then every change to
+    /// it; subscribe again to pick up team membership changes
+    SubscribePresence,
+
+    /// Stop receiving presence changes
+    UnsubscribePresence,
+
     // === Share Management ===
     /// Publish a shareable result
     PublishShare {
@@ -86,10 +97,69 @@ pub enum SyncMessage {
         shares: Vec<ShareToken>,
     },
 
+    /// Response to SubscribePresence
+    PresenceRoster {
+        members: Vec<PresenceEntry>,
+    },
+
     /// Connection established response
     Connected {
         server_version: String,
         institution: String,
         user_id: UserId,
     },
+
+    // === Server Notices ===
+    /// Maintenance mode changed; `window` is `None` once maintenance ends
+    MaintenanceNotice {
+        window: Option<MaintenanceWindow>,
+    },
+
+    /// An administrator published an announcement
+    AnnouncementNotice {
+        announcement: Announcement,
+    },
+
+    /// An announcement was withdrawn and should no longer be shown
+    AnnouncementWithdrawn {
+        id: Uuid,
+    },
+
+    /// A recurring analysis owned by one of the user's teams has run
+    ScheduledRunNotice {
+        schedule_id: Uuid,
+        schedule_name: String,
+        team_id: Uuid,
+        run: ScheduleRun,
+    },
+
+    /// A share names the user, directly or through one of their teams;
+    /// queued while they are offline
+    ShareNotice {
+        token: ShareToken,
+        owner_user_id: UserId,
+        title: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        shared_at: DateTime<Utc>,
+    },
+
+    /// A recipient wants one of the user's shared results relayed through
+    /// the broker: upload it with `PUT /api/relay/{relay_id}`, at most
+    /// `max_bytes`, or decline with `DELETE`
+    RelayRequest {
+        relay_id: Uuid,
+        token: ShareToken,
+        content_id: String,
+        requester_id: UserId,
+        max_bytes: u64,
+    },
+
+    /// A teammate came online, registered a new endpoint or went offline
+    PresenceChanged {
+        user_id: UserId,
+        online: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        endpoint: Option<String>,
+    },
 }
@@ -5,16 +5,25 @@ use axum::{
     },
     response::Response,
 };
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{stream::SplitSink, SinkExt, StreamExt};
+use sqlx::PgPool;
 use std::sync::Arc;
+use tokio::sync::mpsc;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+use crate::announcements::{AnnouncementBoard, AnnouncementNotice};
 use crate::auth::SessionManager;
+use crate::maintenance::MaintenanceMode;
+use crate::metrics::ServerMetrics;
+use crate::sync::federation::Federation;
+use crate::sync::mailbox::{notify_share_recipients, Mailbox, CONNECTION_BUFFER};
+use crate::sync::presence::{roster, teammates, PresenceWatch};
 use crate::sync::registry::UserRegistry;
 use crate::sync::types::SyncMessage;
 use crate::sync::verify_psk;
-use crate::storage::{SharedResultStore, SharedResultInfo};
+use crate::storage::{ShareMetadata, SharedResultStore, SharedResultInfo, UserStore};
+use crate::transfer::TransferPolicy;
 
 /// Shared application state for WebSocket handling
 #[derive(Clone)]
@@ -28,6 +37,21 @@ pub struct SyncState {
     pub password_hash: Option<String>,
     /// Whether authentication is required
     pub require_auth: bool,
+    /// Maintenance state, broadcast to every connected client on change
+    pub maintenance: MaintenanceMode,
+    /// Announcements, pushed to every connected client when published
+    pub announcements: AnnouncementBoard,
+    /// Counts open connections
+    pub metrics: ServerMetrics,
+    /// Notices for particular users, queued while they are offline
+    pub mailbox: Mailbox,
+    /// Resolve the recipients of new shares
+    pub user_store: Arc<dyn UserStore>,
+    pub db_pool: PgPool,
+    /// Links to federated brokers, when `FEDERATION_NAMESPACE` is set
+    pub federation: Option<Federation>,
+    /// Server transfer defaults, reported with share info
+    pub transfer_policy: TransferPolicy,
 }
 
 /// Handle WebSocket upgrade
@@ -42,15 +66,92 @@ pub async fn handle_websocket(
 async fn handle_socket(socket: WebSocket, state: SyncState) {
     let (mut sender, mut receiver) = socket.split();
     let mut current_user_id: Option<String> = None;
+    // User whose mailbox feeds this connection
+    let mut attached: Option<String> = None;
+    let (outbox, mut inbox) = mpsc::channel(CONNECTION_BUFFER);
+    let mut unsent: Option<SyncMessage> = None;
+    let mut presence: Option<PresenceWatch> = None;
+    let mut maintenance_notices = state.maintenance.subscribe();
+    let mut announcement_notices = state.announcements.subscribe();
+    let _connection = state.metrics.websocket_connected();
 
     info!("New WebSocket connection established");
 
-    while let Some(msg) = receiver.next().await {
-        let msg = match msg {
-            Ok(msg) => msg,
-            Err(e) => {
-                error!("WebSocket error: {}", e);
-                break;
+    // Clients connecting mid-maintenance get the notice straight away
+    if let Some(window) = state.maintenance.current() {
+        let notice = SyncMessage::MaintenanceNotice {
+            window: Some(window),
+        };
+        if let Ok(json) = serde_json::to_string(&notice) {
+            let _ = sender.send(Message::Text(json.into())).await;
+        }
+    }
+
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => match msg {
+                Some(Ok(msg)) => msg,
+                Some(Err(e)) => {
+                    error!("WebSocket error: {}", e);
+                    break;
+                }
+                None => break,
+            },
+            notice = maintenance_notices.recv() => {
+                let window = match notice {
+                    Ok(window) => window,
+                    // Only the latest state matters after lagging behind
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                        state.maintenance.current()
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => continue,
+                };
+                if let Ok(json) = serde_json::to_string(&SyncMessage::MaintenanceNotice { window }) {
+                    if let Err(e) = sender.send(Message::Text(json.into())).await {
+                        error!("Failed to send maintenance notice: {}", e);
+                        break;
+                    }
+                }
+                continue;
+            }
+            notice = announcement_notices.recv() => {
+                let message = match notice {
+                    Ok(AnnouncementNotice::Published(announcement)) => {
+                        SyncMessage::AnnouncementNotice { announcement }
+                    }
+                    Ok(AnnouncementNotice::Withdrawn(id)) => SyncMessage::AnnouncementWithdrawn { id },
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Dropped {} announcement notices for a slow client", skipped);
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => continue,
+                };
+                if let Ok(json) = serde_json::to_string(&message) {
+                    if let Err(e) = sender.send(Message::Text(json.into())).await {
+                        error!("Failed to send announcement: {}", e);
+                        break;
+                    }
+                }
+                continue;
+            }
+            Some(notice) = inbox.recv() => {
+                if let Ok(json) = serde_json::to_string(&notice) {
+                    if let Err(e) = sender.send(Message::Text(json.into())).await {
+                        error!("Failed to send notice: {}", e);
+                        unsent = Some(notice);
+                        break;
+                    }
+                }
+                continue;
+            }
+            Some(change) = next_presence_change(&mut presence) => {
+                if let Ok(json) = serde_json::to_string(&change) {
+                    if let Err(e) = sender.send(Message::Text(json.into())).await {
+                        error!("Failed to send presence change: {}", e);
+                        break;
+                    }
+                }
+                continue;
             }
         };
 
@@ -73,7 +174,14 @@ async fn handle_socket(socket: WebSocket, state: SyncState) {
                 };
 
                 // Handle the message
-                let response = handle_sync_message(sync_msg, &state, &mut current_user_id).await;
+                let previous_user_id = current_user_id.clone();
+                let response =
+                    handle_sync_message(sync_msg, &state, &mut current_user_id, &mut presence).await;
+                if current_user_id != previous_user_id {
+                    // Teammates of the previous user are not this user's
+                    presence = None;
+                }
+                let registered = matches!(response, Some(SyncMessage::Connected { .. }));
 
                 // Send response if any
                 if let Some(resp) = response {
@@ -89,6 +197,19 @@ async fn handle_socket(socket: WebSocket, state: SyncState) {
                         }
                     }
                 }
+
+                if attached.is_some() && attached != current_user_id {
+                    if let Some(user_id) = attached.take() {
+                        state.mailbox.detach(&user_id, &outbox).await;
+                    }
+                }
+                if let Some(user_id) = current_user_id.as_ref().filter(|_| registered) {
+                    attached = Some(user_id.clone());
+                    if let Err(e) = open_mailbox(&mut sender, &state.mailbox, user_id, &outbox).await {
+                        error!("Failed to send queued messages: {}", e);
+                        break;
+                    }
+                }
             }
             Message::Close(_) => {
                 info!("WebSocket connection closed by client");
@@ -104,6 +225,18 @@ async fn handle_socket(socket: WebSocket, state: SyncState) {
         }
     }
 
+    // Notices that did not make it out wait for the user's next connection
+    if let Some(user_id) = attached {
+        state.mailbox.detach(&user_id, &outbox).await;
+        inbox.close();
+        if let Some(notice) = unsent {
+            state.mailbox.deliver(&user_id, notice).await;
+        }
+        while let Ok(notice) = inbox.try_recv() {
+            state.mailbox.deliver(&user_id, notice).await;
+        }
+    }
+
     // Clean up user registration on disconnect
     if let Some(user_id) = current_user_id {
         state.registry.disconnect(&user_id);
@@ -113,11 +246,75 @@ async fn handle_socket(socket: WebSocket, state: SyncState) {
     info!("WebSocket connection terminated");
 }
 
+/// Route `user_id`'s notices to this connection, after sending those
+/// queued while they were offline
+async fn open_mailbox(
+    sender: &mut SplitSink<WebSocket, Message>,
+    mailbox: &Mailbox,
+    user_id: &str,
+    outbox: &mpsc::Sender<SyncMessage>,
+) -> Result<(), axum::Error> {
+    let queued = mailbox.attach(user_id, outbox.clone()).await;
+    if !queued.is_empty() {
+        info!("Replaying {} queued messages to {}", queued.len(), user_id);
+    }
+    let mut delivered = Vec::with_capacity(queued.len());
+    let mut result = Ok(());
+    for (id, message) in queued {
+        if let Ok(json) = serde_json::to_string(&message) {
+            if let Err(e) = sender.send(Message::Text(json.into())).await {
+                result = Err(e);
+                break;
+            }
+        }
+        delivered.push(id);
+    }
+    mailbox.acknowledge(user_id, &delivered).await;
+    result
+}
+
+/// The next presence change for a subscribed connection; never resolves
+/// otherwise
+async fn next_presence_change(presence: &mut Option<PresenceWatch>) -> Option<SyncMessage> {
+    match presence {
+        Some(watch) => watch.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// A share with its owner's availability and the transfer policy applying
+/// to it
+pub(crate) fn share_info(state: &SyncState, metadata: ShareMetadata) -> SharedResultInfo {
+    // Get owner connection info
+    let owner_online = state.registry.is_online(&metadata.owner_user_id);
+    let download_url = if owner_online {
+        state
+            .registry
+            .get_connection(&metadata.owner_user_id)
+            .map(|conn| format!("{}/api/results/{}", conn.endpoint, metadata.content_id))
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    let transfer_policy = state
+        .transfer_policy
+        .with_overrides(&metadata.access_policy.transfer.unwrap_or_default());
+
+    SharedResultInfo {
+        metadata,
+        download_url,
+        owner_online,
+        transfer_policy,
+    }
+}
+
 /// Handle a sync message and return optional response
 async fn handle_sync_message(
     msg: SyncMessage,
     state: &SyncState,
     current_user_id: &mut Option<String>,
+    presence: &mut Option<PresenceWatch>,
 ) -> Option<SyncMessage> {
     match msg {
         SyncMessage::RegisterUser { user_id, endpoint, password, session_token } => {
@@ -203,6 +400,43 @@ async fn handle_sync_message(
             Some(SyncMessage::Ack { message_id: None })
         }
 
+        SyncMessage::SubscribePresence => {
+            let Some(user_id) = current_user_id.as_ref() else {
+                return Some(SyncMessage::Error {
+                    message: "Authentication required".to_string(),
+                    code: "AUTH_REQUIRED".to_string(),
+                });
+            };
+
+            // Subscribe first so no change between reading the roster and
+            // watching is missed
+            let changes = state.registry.subscribe();
+            let users = match state.user_store.get_user_by_email(user_id).await {
+                Ok(user) => teammates(state.user_store.as_ref(), &state.db_pool, user.id).await,
+                Err(e) => Err(e),
+            };
+            match users {
+                Ok(users) => {
+                    let members = roster(&state.registry, &users);
+                    let watched = members.iter().map(|m| m.user_id.clone()).collect();
+                    *presence = Some(PresenceWatch::new(changes, watched));
+                    Some(SyncMessage::PresenceRoster { members })
+                }
+                Err(e) => {
+                    error!("Failed to load teammates of {}: {}", user_id, e);
+                    Some(SyncMessage::Error {
+                        message: e.to_string(),
+                        code: "PRESENCE_ERROR".to_string(),
+                    })
+                }
+            }
+        }
+
+        SyncMessage::UnsubscribePresence => {
+            *presence = None;
+            Some(SyncMessage::Ack { message_id: None })
+        }
+
         SyncMessage::PublishShare { token, metadata } => {
             // Require authentication before allowing publish
             if current_user_id.is_none() {
@@ -226,12 +460,28 @@ async fn handle_sync_message(
                 });
             }
 
+            if let Err(message) = metadata.validate_sealed() {
+                return Some(SyncMessage::Error {
+                    message,
+                    code: "INVALID_INPUT".to_string(),
+                });
+            }
+
             info!(
                 "Publishing share: {} by user {}",
                 token, metadata.owner_user_id
             );
-            match state.share_store.publish_result(&token, metadata, None).await {
-                Ok(_) => Some(SyncMessage::Ack { message_id: None }),
+            match state.share_store.publish_result(&token, metadata.clone(), None).await {
+                Ok(_) => {
+                    tokio::spawn(notify_share_recipients(
+                        state.mailbox.clone(),
+                        state.user_store.clone(),
+                        state.db_pool.clone(),
+                        token,
+                        metadata,
+                    ));
+                    Some(SyncMessage::Ack { message_id: None })
+                }
                 Err(e) => {
                     error!("Failed to publish share: {}", e);
                     Some(SyncMessage::Error {
@@ -248,6 +498,29 @@ async fn handle_sync_message(
         } => {
             info!("User {} requesting share: {}", requester_id, token);
 
+            // Shares of federated brokers are looked up there, on behalf of
+            // the signed-in user
+            let token = match &state.federation {
+                Some(federation) => {
+                    if let Some((namespace, remote_token)) = federation.remote(&token) {
+                        let Some(user_id) = current_user_id.as_ref() else {
+                            return Some(SyncMessage::Error {
+                                message: "Authentication required".to_string(),
+                                code: "AUTH_REQUIRED".to_string(),
+                            });
+                        };
+                        let mut response = federation.lookup_share(namespace, remote_token, user_id).await;
+                        if let SyncMessage::ShareInfo { info } = &mut response {
+                            info.metadata.owner_user_id =
+                                format!("{}:{}", namespace, info.metadata.owner_user_id);
+                        }
+                        return Some(response);
+                    }
+                    federation.local(&token).to_string()
+                }
+                None => token,
+            };
+
             // Get share metadata
             let metadata = match state.share_store.get_shared_result(&token).await {
                 Ok(meta) => meta,
@@ -276,24 +549,8 @@ async fn handle_sync_message(
                 _ => {}
             }
 
-            // Get owner connection info
-            let owner_online = state.registry.is_online(&metadata.owner_user_id);
-            let download_url = if owner_online {
-                state
-                    .registry
-                    .get_connection(&metadata.owner_user_id)
-                    .map(|conn| format!("{}/api/results/{}", conn.endpoint, metadata.content_id))
-                    .unwrap_or_default()
-            } else {
-                String::new()
-            };
-
             Some(SyncMessage::ShareInfo {
-                info: SharedResultInfo {
-                    metadata,
-                    download_url,
-                    owner_online,
-                },
+                info: share_info(state, metadata),
             })
         }
 
@@ -404,7 +661,15 @@ async fn handle_sync_message(
         | SyncMessage::Error { .. }
         | SyncMessage::ShareInfo { .. }
         | SyncMessage::ShareList { .. }
-        | SyncMessage::Connected { .. } => {
+        | SyncMessage::Connected { .. }
+        | SyncMessage::PresenceRoster { .. }
+        | SyncMessage::PresenceChanged { .. }
+        | SyncMessage::RelayRequest { .. }
+        | SyncMessage::MaintenanceNotice { .. }
+        | SyncMessage::AnnouncementNotice { .. }
+        | SyncMessage::AnnouncementWithdrawn { .. }
+        | SyncMessage::ScheduledRunNotice { .. }
+        | SyncMessage::ShareNotice { .. } => {
             warn!("Received response message as request, ignoring");
             None
         }
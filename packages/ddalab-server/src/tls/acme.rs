@@ -0,0 +1,642 @@
+//! Certificates from an ACME CA (RFC 8555) with HTTP-01 challenges
+//!
+//! The CA checks control of each domain by fetching
+//! `http://<domain>/.well-known/acme-challenge/<token>`, so a plain HTTP
+//! listener (port 80 by default) answers those requests and redirects every
+//! other request to HTTPS. The account key, the certificate and its private
+//! key are kept in the cache directory across restarts, and the certificate
+//! is renewed well before it expires.
+
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use parking_lot::RwLock;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+
+use super::TlsError;
+
+/// Let's Encrypt's production directory, used unless `ACME_DIRECTORY_URL`
+/// names another CA
+pub const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+
+/// Age at which a certificate is renewed; Let's Encrypt's last 90 days
+const RENEW_AFTER: Duration = Duration::from_secs(60 * 24 * 60 * 60);
+/// How often the certificate's age is checked
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+/// How long one request to the CA may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Checks of a pending authorization or order before giving up
+const MAX_POLLS: usize = 30;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// ACME settings, from `ACME_DOMAINS`, `ACME_EMAIL`, `ACME_DIRECTORY_URL`,
+/// `ACME_CACHE_DIRECTORY` and `ACME_HTTP_PORT`
+#[derive(Debug, Clone)]
+pub struct AcmeConfig {
+    /// Names the certificate covers
+    pub domains: Vec<String>,
+    /// Address the CA may write to about the account, e.g. expiry warnings
+    pub contact: Option<String>,
+    /// Directory URL of the CA
+    pub directory_url: String,
+    /// Where the account key and certificate are kept
+    pub cache_directory: PathBuf,
+    /// Port of the plain HTTP listener answering challenges
+    pub http_port: u16,
+}
+
+impl AcmeConfig {
+    /// None unless `ACME_DOMAINS` is set; the cache defaults to `acme` in
+    /// the data directory
+    pub fn from_env(data_directory: &Path) -> Result<Option<Self>, String> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let Some(domains) = var("ACME_DOMAINS") else {
+            return Ok(None);
+        };
+        let domains: Vec<String> = domains
+            .split(',')
+            .map(|d| d.trim().to_lowercase())
+            .filter(|d| !d.is_empty())
+            .collect();
+        if domains.is_empty() {
+            return Err("ACME_DOMAINS must name at least one domain".to_string());
+        }
+        let http_port = match var("ACME_HTTP_PORT") {
+            Some(v) => v
+                .parse()
+                .map_err(|_| format!("ACME_HTTP_PORT '{}' is not a port", v))?,
+            None => 80,
+        };
+
+        Ok(Some(Self {
+            domains,
+            contact: var("ACME_EMAIL"),
+            directory_url: var("ACME_DIRECTORY_URL")
+                .unwrap_or_else(|| LETS_ENCRYPT_DIRECTORY.to_string()),
+            cache_directory: var("ACME_CACHE_DIRECTORY")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| data_directory.join("acme")),
+            http_port,
+        }))
+    }
+
+    fn cert_path(&self) -> PathBuf {
+        self.cache_directory.join("cert.pem")
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.cache_directory.join("key.pem")
+    }
+
+    fn domains_path(&self) -> PathBuf {
+        self.cache_directory.join("domains")
+    }
+
+    /// Age of the cached certificate, if it covers the configured domains
+    fn certificate_age(&self) -> Option<Duration> {
+        let domains = std::fs::read_to_string(self.domains_path()).ok()?;
+        if domains.trim() != self.domains.join(",") || !self.key_path().exists() {
+            return None;
+        }
+        let issued = std::fs::metadata(self.cert_path()).ok()?.modified().ok()?;
+        Some(SystemTime::now().duration_since(issued).unwrap_or_default())
+    }
+
+    fn store_certificate(&self, cert_pem: &str, key_pem: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.cache_directory)?;
+        write_private(&self.key_path(), key_pem.as_bytes())?;
+        // Written last: its modification time is the certificate's age
+        std::fs::write(self.cert_path(), cert_pem)?;
+        std::fs::write(self.domains_path(), self.domains.join(","))
+    }
+
+    /// The account key, created on first use
+    fn account_key(&self) -> Result<SigningKey, TlsError> {
+        let path = self.cache_directory.join("account.key");
+        if let Ok(encoded) = std::fs::read_to_string(&path) {
+            return hex::decode(encoded.trim())
+                .ok()
+                .and_then(|bytes| SigningKey::from_slice(&bytes).ok())
+                .ok_or_else(|| {
+                    TlsError::Acme(format!("{} is not an ACME account key", path.display()))
+                });
+        }
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        std::fs::create_dir_all(&self.cache_directory)?;
+        write_private(&path, hex::encode(key.to_bytes()).as_bytes())?;
+        Ok(key)
+    }
+}
+
+/// Write a file only its owner can read
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents)
+}
+
+/// Key authorizations of the challenges in progress, by token
+type Challenges = Arc<RwLock<HashMap<String, String>>>;
+
+#[derive(Clone)]
+struct ChallengeState {
+    challenges: Challenges,
+    https_port: u16,
+}
+
+/// Start answering challenges, obtain a certificate unless a current one is
+/// cached, and renew it in the background
+pub(super) async fn start(config: AcmeConfig, addr: SocketAddr) -> Result<RustlsConfig, TlsError> {
+    let challenges = Challenges::default();
+    let listener = tokio::net::TcpListener::bind((addr.ip(), config.http_port)).await?;
+    let app = Router::new()
+        .route("/.well-known/acme-challenge/{token}", get(answer_challenge))
+        .fallback(redirect_to_https)
+        .with_state(ChallengeState {
+            challenges: challenges.clone(),
+            https_port: addr.port(),
+        });
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("ACME challenge listener failed: {}", e);
+        }
+    });
+    info!("🔒 Answering ACME challenges on port {}", config.http_port);
+
+    match config.certificate_age() {
+        Some(age) if age < RENEW_AFTER => {}
+        // An old certificate is still better than none
+        Some(_) => {
+            if let Err(e) = renew(&config, &challenges).await {
+                warn!(
+                    "Failed to renew TLS certificate, serving the old one: {}",
+                    e
+                );
+            }
+        }
+        None => renew(&config, &challenges).await?,
+    }
+
+    let rustls = RustlsConfig::from_pem_file(config.cert_path(), config.key_path()).await?;
+    tokio::spawn(renew_periodically(config, challenges, rustls.clone()));
+    Ok(rustls)
+}
+
+async fn renew_periodically(config: AcmeConfig, challenges: Challenges, rustls: RustlsConfig) {
+    let mut interval = tokio::time::interval(RENEWAL_CHECK_INTERVAL);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        if config
+            .certificate_age()
+            .is_some_and(|age| age < RENEW_AFTER)
+        {
+            continue;
+        }
+        if let Err(e) = renew(&config, &challenges).await {
+            warn!("Failed to renew TLS certificate: {}", e);
+            continue;
+        }
+        if let Err(e) = rustls
+            .reload_from_pem_file(config.cert_path(), config.key_path())
+            .await
+        {
+            warn!("Failed to load the renewed TLS certificate: {}", e);
+        }
+    }
+}
+
+/// Obtain a certificate and store it in the cache
+async fn renew(config: &AcmeConfig, challenges: &Challenges) -> Result<(), TlsError> {
+    info!(
+        "🔒 Requesting a TLS certificate for {} from {}",
+        config.domains.join(", "),
+        config.directory_url
+    );
+    let (config, challenges) = (config.clone(), challenges.clone());
+    tokio::task::spawn_blocking(move || {
+        let (cert_pem, key_pem) = obtain_certificate(&config, &challenges)?;
+        config.store_certificate(&cert_pem, &key_pem)?;
+        Ok::<_, TlsError>(())
+    })
+    .await
+    .map_err(|e| TlsError::Acme(e.to_string()))??;
+    info!("🔒 TLS certificate issued");
+    Ok(())
+}
+
+async fn answer_challenge(
+    State(state): State<ChallengeState>,
+    UrlPath(token): UrlPath<String>,
+) -> Response {
+    let key_authorization = state.challenges.read().get(&token).cloned();
+    match key_authorization {
+        Some(key_authorization) => key_authorization.into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn redirect_to_https(
+    State(state): State<ChallengeState>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    let Some(host) = headers.get(header::HOST).and_then(|h| h.to_str().ok()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    Redirect::permanent(&https_url(host, state.https_port, path)).into_response()
+}
+
+/// The HTTPS URL of `path` on `host`, whatever port the request came to
+fn https_url(host: &str, https_port: u16, path: &str) -> String {
+    let host = match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    };
+    match https_port {
+        443 => format!("https://{}{}", host, path),
+        port => format!("https://{}:{}{}", host, port, path),
+    }
+}
+
+fn acme_error(context: &str, e: impl std::fmt::Display) -> TlsError {
+    TlsError::Acme(format!("{}: {}", context, e))
+}
+
+fn json_body(url: &str, response: ureq::Response) -> Result<Value, TlsError> {
+    let body = response.into_string().map_err(|e| acme_error(url, e))?;
+    serde_json::from_str(&body).map_err(|e| acme_error(url, e))
+}
+
+/// Public account key as a JWK
+fn jwk(key: &SigningKey) -> Value {
+    let point = key.verifying_key().to_encoded_point(false);
+    let coordinate = |c: Option<&p256::FieldBytes>| {
+        URL_SAFE_NO_PAD.encode(c.expect("uncompressed points have both coordinates"))
+    };
+    json!({
+        "crv": "P-256",
+        "kty": "EC",
+        "x": coordinate(point.x()),
+        "y": coordinate(point.y()),
+    })
+}
+
+/// RFC 7638 thumbprint of the account key, which key authorizations end in
+fn thumbprint(key: &SigningKey) -> String {
+    let jwk = jwk(key);
+    // The members in lexicographic order, without whitespace
+    let canonical = format!(
+        r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+        jwk["x"].as_str().unwrap_or_default(),
+        jwk["y"].as_str().unwrap_or_default()
+    );
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+/// Signed requests to the CA on behalf of one account
+struct AcmeClient {
+    agent: ureq::Agent,
+    key: SigningKey,
+    /// Account URL, sent as the key ID once the account exists
+    kid: Option<String>,
+    nonce: Option<String>,
+    new_nonce_url: String,
+}
+
+impl AcmeClient {
+    fn new_nonce(&self) -> Result<String, TlsError> {
+        let response = self
+            .agent
+            .head(&self.new_nonce_url)
+            .call()
+            .map_err(|e| acme_error(&self.new_nonce_url, e))?;
+        response
+            .header("Replay-Nonce")
+            .map(str::to_string)
+            .ok_or_else(|| TlsError::Acme("The CA sent no nonce".to_string()))
+    }
+
+    /// A flattened JWS of `payload` for `url`; without a payload, a
+    /// POST-as-GET
+    fn sign(&self, url: &str, nonce: &str, payload: Option<&Value>) -> String {
+        let mut protected = json!({ "alg": "ES256", "nonce": nonce, "url": url });
+        match &self.kid {
+            Some(kid) => protected["kid"] = json!(kid),
+            None => protected["jwk"] = jwk(&self.key),
+        }
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        let payload = payload
+            .map(|p| URL_SAFE_NO_PAD.encode(p.to_string()))
+            .unwrap_or_default();
+        let signature: Signature = self
+            .key
+            .sign(format!("{}.{}", protected, payload).as_bytes());
+
+        json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+        })
+        .to_string()
+    }
+
+    fn post(&mut self, url: &str, payload: Option<&Value>) -> Result<ureq::Response, TlsError> {
+        // A rejected nonce is retried with the fresh one the error carries
+        for _ in 0..3 {
+            let nonce = match self.nonce.take() {
+                Some(nonce) => nonce,
+                None => self.new_nonce()?,
+            };
+            let body = self.sign(url, &nonce, payload);
+            let response = match self
+                .agent
+                .post(url)
+                .set("Content-Type", "application/jose+json")
+                .send_string(&body)
+            {
+                Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+                Err(e) => return Err(acme_error(url, e)),
+            };
+            self.nonce = response.header("Replay-Nonce").map(str::to_string);
+            if response.status() < 300 {
+                return Ok(response);
+            }
+
+            let problem = json_body(url, response).unwrap_or(Value::Null);
+            if problem["type"] == "urn:ietf:params:acme:error:badNonce" {
+                continue;
+            }
+            let detail = problem["detail"].as_str().unwrap_or("request failed");
+            return Err(acme_error(url, detail));
+        }
+        Err(acme_error(url, "the CA kept rejecting nonces"))
+    }
+
+    /// The JSON response and its `Location`
+    fn post_json(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> Result<(Value, Option<String>), TlsError> {
+        let response = self.post(url, payload)?;
+        let location = response.header("Location").map(str::to_string);
+        Ok((json_body(url, response)?, location))
+    }
+
+    /// Fetch an authorization or order until it is no longer pending
+    fn poll(&mut self, url: &str) -> Result<Value, TlsError> {
+        for _ in 0..MAX_POLLS {
+            let (body, _) = self.post_json(url, None)?;
+            match body["status"].as_str() {
+                Some("pending") | Some("processing") => std::thread::sleep(POLL_INTERVAL),
+                _ => return Ok(body),
+            }
+        }
+        Err(acme_error(url, "still pending"))
+    }
+}
+
+/// Run one order through to a certificate: the PEM chain and its key
+fn obtain_certificate(
+    config: &AcmeConfig,
+    challenges: &Challenges,
+) -> Result<(String, String), TlsError> {
+    let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+    let directory = agent
+        .get(&config.directory_url)
+        .call()
+        .map_err(|e| acme_error(&config.directory_url, e))
+        .and_then(|response| json_body(&config.directory_url, response))?;
+    let endpoint = |name: &str| {
+        directory[name]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| TlsError::Acme(format!("The ACME directory has no {}", name)))
+    };
+
+    let mut client = AcmeClient {
+        agent,
+        key: config.account_key()?,
+        kid: None,
+        nonce: None,
+        new_nonce_url: endpoint("newNonce")?,
+    };
+
+    let mut account = json!({ "termsOfServiceAgreed": true });
+    if let Some(contact) = &config.contact {
+        account["contact"] = json!([format!("mailto:{}", contact)]);
+    }
+    let (_, account_url) = client.post_json(&endpoint("newAccount")?, Some(&account))?;
+    client.kid =
+        Some(account_url.ok_or_else(|| TlsError::Acme("The CA sent no account URL".to_string()))?);
+
+    let identifiers: Vec<Value> = config
+        .domains
+        .iter()
+        .map(|domain| json!({ "type": "dns", "value": domain }))
+        .collect();
+    let (order, order_url) = client.post_json(
+        &endpoint("newOrder")?,
+        Some(&json!({ "identifiers": identifiers })),
+    )?;
+    let order_url =
+        order_url.ok_or_else(|| TlsError::Acme("The CA sent no order URL".to_string()))?;
+
+    let thumbprint = thumbprint(&client.key);
+    for authorization in order["authorizations"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        authorize(&mut client, authorization, &thumbprint, challenges)?;
+    }
+
+    let key = rcgen::KeyPair::generate().map_err(|e| acme_error("Certificate key", e))?;
+    let csr = rcgen::CertificateParams::new(config.domains.clone())
+        .and_then(|params| params.serialize_request(&key))
+        .map_err(|e| acme_error("Certificate request", e))?;
+    let finalize = order["finalize"]
+        .as_str()
+        .ok_or_else(|| TlsError::Acme("The order has no finalize URL".to_string()))?;
+    client.post_json(
+        finalize,
+        Some(&json!({ "csr": URL_SAFE_NO_PAD.encode(csr.der()) })),
+    )?;
+
+    let order = client.poll(&order_url)?;
+    let certificate_url = match (order["status"].as_str(), order["certificate"].as_str()) {
+        (Some("valid"), Some(url)) => url.to_string(),
+        (status, _) => {
+            return Err(acme_error(
+                &order_url,
+                format!("order ended {}", status.unwrap_or("without a status")),
+            ))
+        }
+    };
+    let cert_pem = client
+        .post(&certificate_url, None)?
+        .into_string()
+        .map_err(|e| acme_error(&certificate_url, e))?;
+
+    Ok((cert_pem, key.serialize_pem()))
+}
+
+/// Complete the HTTP-01 challenge of one authorization
+fn authorize(
+    client: &mut AcmeClient,
+    url: &str,
+    thumbprint: &str,
+    challenges: &Challenges,
+) -> Result<(), TlsError> {
+    let (authorization, _) = client.post_json(url, None)?;
+    if authorization["status"] == "valid" {
+        return Ok(());
+    }
+    let domain = authorization["identifier"]["value"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let challenge = authorization["challenges"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|challenge| challenge["type"] == "http-01");
+    let Some((token, challenge_url)) =
+        challenge.and_then(|c| Some((c["token"].as_str()?, c["url"].as_str()?)))
+    else {
+        return Err(TlsError::Acme(format!(
+            "The CA offered no HTTP-01 challenge for {}",
+            domain
+        )));
+    };
+
+    challenges
+        .write()
+        .insert(token.to_string(), format!("{}.{}", token, thumbprint));
+    let result = client
+        .post_json(challenge_url, Some(&json!({})))
+        .and_then(|_| client.poll(url));
+    challenges.write().remove(token);
+
+    let authorization = result?;
+    if authorization["status"] == "valid" {
+        return Ok(());
+    }
+    let detail = authorization["challenges"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find_map(|challenge| challenge["error"]["detail"].as_str())
+        .unwrap_or("no details");
+    Err(TlsError::Acme(format!(
+        "{} was not authorized: {}",
+        domain, detail
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::{signature::Verifier, VerifyingKey};
+
+    #[test]
+    fn test_https_url_keeps_host_and_path() {
+        assert_eq!(
+            https_url("lab.example.org", 443, "/health?x=1"),
+            "https://lab.example.org/health?x=1"
+        );
+        assert_eq!(
+            https_url("lab.example.org:80", 8443, "/"),
+            "https://lab.example.org:8443/"
+        );
+        assert_eq!(https_url("[::1]:80", 443, "/ws"), "https://[::1]/ws");
+        assert_eq!(https_url("[::1]", 443, "/ws"), "https://[::1]/ws");
+    }
+
+    #[test]
+    fn test_signed_requests_verify_with_the_account_key() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let mut client = AcmeClient {
+            agent: ureq::agent(),
+            key: key.clone(),
+            kid: None,
+            nonce: None,
+            new_nonce_url: String::new(),
+        };
+
+        let decode = |value: &Value| -> Value {
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(value.as_str().unwrap()).unwrap())
+                .unwrap()
+        };
+        let jws: Value =
+            serde_json::from_str(&client.sign("https://ca/new-acct", "n1", Some(&json!({}))))
+                .unwrap();
+        let protected = decode(&jws["protected"]);
+        assert_eq!(protected["jwk"], jwk(&key));
+        assert_eq!(protected["nonce"], "n1");
+        assert!(protected.get("kid").is_none());
+
+        let signature = URL_SAFE_NO_PAD
+            .decode(jws["signature"].as_str().unwrap())
+            .unwrap();
+        let signed = format!(
+            "{}.{}",
+            jws["protected"].as_str().unwrap(),
+            jws["payload"].as_str().unwrap()
+        );
+        VerifyingKey::from(&key)
+            .verify(
+                signed.as_bytes(),
+                &Signature::from_slice(&signature).unwrap(),
+            )
+            .unwrap();
+
+        client.kid = Some("https://ca/acct/1".to_string());
+        let jws: Value =
+            serde_json::from_str(&client.sign("https://ca/order", "n2", None)).unwrap();
+        let protected = decode(&jws["protected"]);
+        assert_eq!(protected["kid"], "https://ca/acct/1");
+        assert!(protected.get("jwk").is_none());
+        assert_eq!(jws["payload"], "");
+    }
+
+    #[test]
+    fn test_thumbprint_is_stable_and_key_specific() {
+        let key = SigningKey::random(&mut rand::rngs::OsRng);
+        let other = SigningKey::random(&mut rand::rngs::OsRng);
+        assert_eq!(thumbprint(&key), thumbprint(&key));
+        assert_ne!(thumbprint(&key), thumbprint(&other));
+        // SHA-256, base64url without padding
+        assert_eq!(thumbprint(&key).len(), 43);
+    }
+}
@@ -0,0 +1,125 @@
+//! Built-in HTTPS
+//!
+//! Small labs often have no reverse proxy to terminate TLS, so the server can
+//! serve the REST API and the `/ws` WebSocket over HTTPS itself: with a
+//! certificate and key from disk (`TLS_CERT_PATH`, `TLS_KEY_PATH`), or with a
+//! certificate it obtains and renews through ACME, e.g. from Let's Encrypt
+//! (`ACME_DOMAINS`).
+
+mod acme;
+
+pub use acme::{AcmeConfig, LETS_ENCRYPT_DIRECTORY};
+
+use axum_server::tls_rustls::RustlsConfig;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// How often certificate files on disk are checked for replacement
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Where the served certificate comes from
+#[derive(Debug, Clone)]
+pub enum TlsConfig {
+    /// A PEM certificate chain and private key, reloaded when replaced on
+    /// disk, e.g. by certbot
+    Files {
+        cert_path: PathBuf,
+        key_path: PathBuf,
+    },
+    /// A certificate obtained and renewed through ACME
+    Acme(AcmeConfig),
+}
+
+impl TlsConfig {
+    /// None unless `TLS_CERT_PATH` and `TLS_KEY_PATH`, or `ACME_DOMAINS`,
+    /// are set
+    pub fn from_env(data_directory: &Path) -> Result<Option<Self>, String> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let files = match (var("TLS_CERT_PATH"), var("TLS_KEY_PATH")) {
+            (Some(cert), Some(key)) => Some(TlsConfig::Files {
+                cert_path: PathBuf::from(cert),
+                key_path: PathBuf::from(key),
+            }),
+            (None, None) => None,
+            _ => return Err("TLS_CERT_PATH and TLS_KEY_PATH must be set together".to_string()),
+        };
+        let acme = AcmeConfig::from_env(data_directory)?.map(TlsConfig::Acme);
+
+        match (files, acme) {
+            (Some(_), Some(_)) => Err(
+                "Set either TLS_CERT_PATH and TLS_KEY_PATH or ACME_DOMAINS, not both".to_string(),
+            ),
+            (files, acme) => Ok(files.or(acme)),
+        }
+    }
+}
+
+/// TLS setup errors
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("TLS I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("ACME error: {0}")]
+    Acme(String),
+}
+
+/// The certificate to serve, obtained first when ACME is configured, kept
+/// current in the background
+///
+/// With ACME, this also starts the plain HTTP listener that answers the
+/// CA's challenges and redirects everything else to HTTPS.
+pub async fn rustls_config(tls: &TlsConfig, addr: SocketAddr) -> Result<RustlsConfig, TlsError> {
+    // Only ring is compiled in; an earlier installation wins, which is fine
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    match tls {
+        TlsConfig::Files {
+            cert_path,
+            key_path,
+        } => {
+            let config = RustlsConfig::from_pem_file(cert_path, key_path).await?;
+            info!("🔒 TLS certificate loaded from {}", cert_path.display());
+            tokio::spawn(reload_on_change(
+                config.clone(),
+                cert_path.clone(),
+                key_path.clone(),
+            ));
+            Ok(config)
+        }
+        TlsConfig::Acme(acme) => acme::start(acme.clone(), addr).await,
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reload the certificate whenever either file is replaced
+async fn reload_on_change(config: RustlsConfig, cert_path: PathBuf, key_path: PathBuf) {
+    let mut loaded = (modified(&cert_path), modified(&key_path));
+    let mut interval = tokio::time::interval(RELOAD_CHECK_INTERVAL);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let current = (modified(&cert_path), modified(&key_path));
+        if current == loaded {
+            continue;
+        }
+        match config.reload_from_pem_file(&cert_path, &key_path).await {
+            Ok(()) => {
+                info!("🔒 TLS certificate reloaded from {}", cert_path.display());
+                loaded = current;
+            }
+            // Usually caught between writing the two files; retried next time
+            Err(e) => warn!("Failed to reload TLS certificate: {}", e),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Bandwidth caps and off-peak scheduling for large transfers
+//!
+//! A [`TransferPolicy`] caps the rate of each download connection and can
+//! defer transfers above a size threshold until an off-peak window, so
+//! large results do not saturate clinic networks during working hours. The
+//! server-wide policy comes from configuration; a share may override it in
+//! its access policy, and the override travels with the share info so the
+//! owner's client applies it when serving the result.
+
+use axum::body::{Body, Bytes};
+use chrono::{DateTime, Duration, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tokio::time::{sleep_until, Instant};
+
+/// Chunk size of throttled bodies
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Daily off-peak hours in UTC, `start_hour` inclusive to `end_hour` exclusive.
+///
+/// Windows may wrap midnight, e.g. 20 to 6.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OffPeakWindow {
+    pub start_hour: u32,
+    pub end_hour: u32,
+}
+
+impl OffPeakWindow {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        let hour = at.hour();
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+
+    /// Start of the next off-peak window, or `at` itself if inside one
+    pub fn next_start(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        if self.contains(at) {
+            return at;
+        }
+        let start_today = at
+            .with_hour(self.start_hour)
+            .and_then(|t| t.with_minute(0))
+            .and_then(|t| t.with_second(0))
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(at);
+        if start_today > at {
+            start_today
+        } else {
+            start_today + Duration::days(1)
+        }
+    }
+}
+
+impl FromStr for OffPeakWindow {
+    type Err = String;
+
+    /// Parse `"20-6"` (hours, UTC)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid off-peak window '{}', expected e.g. '20-6'", s);
+        let (start, end) = s.trim().split_once('-').ok_or_else(invalid)?;
+        let start_hour = start.trim().parse::<u32>().map_err(|_| invalid())?;
+        let end_hour = end.trim().parse::<u32>().map_err(|_| invalid())?;
+        if start_hour > 23 || end_hour > 23 || start_hour == end_hour {
+            return Err(invalid());
+        }
+        Ok(Self {
+            start_hour,
+            end_hour,
+        })
+    }
+}
+
+/// Rate cap and deferral rules for one transfer path or share
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferPolicy {
+    /// Per-connection cap; unlimited when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes_per_second: Option<u64>,
+    /// Transfers larger than this wait for `off_peak`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub defer_above_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub off_peak: Option<OffPeakWindow>,
+}
+
+/// What to do with a transfer right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferDecision {
+    /// Send now, capped at the given rate if any
+    Allow { max_bytes_per_second: Option<u64> },
+    /// Retry once the off-peak window opens
+    Defer { until: DateTime<Utc> },
+}
+
+impl TransferPolicy {
+    /// This policy with every field set in `overrides` replaced
+    pub fn with_overrides(&self, overrides: &TransferPolicy) -> Self {
+        Self {
+            max_bytes_per_second: overrides.max_bytes_per_second.or(self.max_bytes_per_second),
+            defer_above_bytes: overrides.defer_above_bytes.or(self.defer_above_bytes),
+            off_peak: overrides.off_peak.or(self.off_peak),
+        }
+    }
+
+    pub fn decide(&self, size_bytes: u64, now: DateTime<Utc>) -> TransferDecision {
+        if let (Some(limit), Some(window)) = (self.defer_above_bytes, self.off_peak) {
+            if size_bytes > limit && !window.contains(now) {
+                return TransferDecision::Defer {
+                    until: window.next_start(now),
+                };
+            }
+        }
+        TransferDecision::Allow {
+            max_bytes_per_second: self.max_bytes_per_second.filter(|&rate| rate > 0),
+        }
+    }
+}
+
+/// Response body for `data`, paced to `max_bytes_per_second` when set
+pub fn throttled_body(data: Vec<u8>, max_bytes_per_second: Option<u64>) -> Body {
+    let Some(rate) = max_bytes_per_second.filter(|&rate| rate > 0) else {
+        return Body::from(data);
+    };
+    let data = Bytes::from(data);
+    // Smaller chunks for low caps so pacing stays smooth
+    let chunk_size = CHUNK_SIZE.min((rate as usize).max(1));
+    let stream = async_stream::stream! {
+        let started = Instant::now();
+        let mut sent = 0usize;
+        while sent < data.len() {
+            let end = (sent + chunk_size).min(data.len());
+            yield Ok::<_, std::io::Error>(data.slice(sent..end));
+            sent = end;
+            let due = std::time::Duration::from_secs_f64(sent as f64 / rate as f64);
+            sleep_until(started + due).await;
+        }
+    };
+    Body::from_stream(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 11, h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_off_peak_window_wraps_midnight() {
+        let window: OffPeakWindow = "20-6".parse().unwrap();
+        assert!(window.contains(at(23, 0)));
+        assert!(window.contains(at(5, 59)));
+        assert!(!window.contains(at(6, 0)));
+        assert_eq!(window.next_start(at(9, 30)), at(20, 0));
+        assert_eq!(window.next_start(at(21, 0)), at(21, 0));
+        assert!("6-6".parse::<OffPeakWindow>().is_err());
+        assert!("25-3".parse::<OffPeakWindow>().is_err());
+    }
+
+    #[test]
+    fn test_large_transfers_deferred_and_overrides_win() {
+        let policy = TransferPolicy {
+            max_bytes_per_second: Some(1_000_000),
+            defer_above_bytes: Some(100 * 1024 * 1024),
+            off_peak: Some("20-6".parse().unwrap()),
+        };
+        assert_eq!(
+            policy.decide(200 * 1024 * 1024, at(10, 0)),
+            TransferDecision::Defer { until: at(20, 0) }
+        );
+        assert_eq!(
+            policy.decide(200 * 1024 * 1024, at(22, 0)),
+            TransferDecision::Allow {
+                max_bytes_per_second: Some(1_000_000)
+            }
+        );
+
+        // A share that may always go out immediately, at a higher rate
+        let share = TransferPolicy {
+            max_bytes_per_second: Some(5_000_000),
+            defer_above_bytes: Some(u64::MAX),
+            off_peak: None,
+        };
+        assert_eq!(
+            policy
+                .with_overrides(&share)
+                .decide(200 * 1024 * 1024, at(10, 0)),
+            TransferDecision::Allow {
+                max_bytes_per_second: Some(5_000_000)
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_throttled_body_paces_chunks() {
+        let started = Instant::now();
+        let body = throttled_body(vec![7u8; 3000], Some(10_000));
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        assert_eq!(bytes.len(), 3000);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(300));
+    }
+}
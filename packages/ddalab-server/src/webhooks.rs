@@ -0,0 +1,314 @@
+//! Job lifecycle webhooks
+//!
+//! The dispatcher follows the job queue's progress events and POSTs a JSON
+//! payload to every webhook subscribed to the job owner's events, whether
+//! the owner's own or one of their teams'. Each payload is signed with the
+//! webhook's secret (`X-DDALAB-Signature: sha256=<hex HMAC>`), failed
+//! deliveries are retried with growing delays, and every attempt is logged.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::jobs::{DDAJob, JobProgressEvent, JobQueue, JobStatus};
+use crate::storage::{Webhook, WebhookDelivery, WebhookStore};
+
+/// Delays before the second and later delivery attempts
+const RETRY_DELAYS: [Duration; 4] = [
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(30 * 60),
+];
+
+/// How long one delivery attempt may take
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A job lifecycle event a webhook can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "job.submitted")]
+    Submitted,
+    #[serde(rename = "job.started")]
+    Started,
+    #[serde(rename = "job.completed")]
+    Completed,
+    #[serde(rename = "job.failed")]
+    Failed,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::Submitted,
+        WebhookEvent::Started,
+        WebhookEvent::Completed,
+        WebhookEvent::Failed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::Submitted => "job.submitted",
+            WebhookEvent::Started => "job.started",
+            WebhookEvent::Completed => "job.completed",
+            WebhookEvent::Failed => "job.failed",
+        }
+    }
+
+    /// Whether a webhook subscribed to `events` wants this event
+    pub fn is_subscribed(&self, events: &[String]) -> bool {
+        events.is_empty() || events.iter().any(|e| e == self.as_str())
+    }
+}
+
+impl std::str::FromStr for WebhookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WebhookEvent::ALL
+            .into_iter()
+            .find(|event| event.as_str() == s)
+            .ok_or_else(|| format!("Unknown webhook event '{}'", s))
+    }
+}
+
+/// Body POSTed to a webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    pub job_id: Uuid,
+    pub user_id: String,
+    pub file_name: String,
+    pub status: JobStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl WebhookPayload {
+    fn new(event: WebhookEvent, job: &DDAJob) -> Self {
+        Self {
+            event,
+            job_id: job.id,
+            user_id: job.user_id.clone(),
+            file_name: job.original_filename.clone(),
+            status: job.status,
+            message: job.error.clone().or_else(|| job.message.clone()),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Hex HMAC-SHA256 of a payload under a webhook's secret
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Turns the queue's progress events into lifecycle events
+///
+/// Running jobs report progress many times; only the first report counts
+/// as the job starting.
+#[derive(Debug, Default)]
+struct LifecycleTracker {
+    started: HashSet<Uuid>,
+}
+
+impl LifecycleTracker {
+    fn observe(&mut self, event: &JobProgressEvent) -> Option<WebhookEvent> {
+        match event.status {
+            JobStatus::Pending => Some(WebhookEvent::Submitted),
+            JobStatus::Running => self
+                .started
+                .insert(event.job_id)
+                .then_some(WebhookEvent::Started),
+            JobStatus::Completed => {
+                self.started.remove(&event.job_id);
+                Some(WebhookEvent::Completed)
+            }
+            JobStatus::Failed => {
+                self.started.remove(&event.job_id);
+                Some(WebhookEvent::Failed)
+            }
+            JobStatus::Cancelled => {
+                self.started.remove(&event.job_id);
+                None
+            }
+        }
+    }
+}
+
+/// Start delivering job lifecycle events to subscribed webhooks
+pub fn spawn_webhook_dispatcher(queue: Arc<JobQueue>, store: Arc<dyn WebhookStore>) {
+    let mut events = queue.subscribe();
+    tokio::spawn(async move {
+        let mut tracker = LifecycleTracker::default();
+        loop {
+            let progress = match events.recv().await {
+                Ok(progress) => progress,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Webhook dispatcher missed {} job events", skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let Some(event) = tracker.observe(&progress) else {
+                continue;
+            };
+            let Some(job) = queue.get_job(progress.job_id).await else {
+                continue;
+            };
+            let webhooks = match store.subscriptions_for(&job.user_id).await {
+                Ok(webhooks) => webhooks,
+                Err(e) => {
+                    warn!("Failed to load webhooks for {}: {}", job.user_id, e);
+                    continue;
+                }
+            };
+            let payload = WebhookPayload::new(event, &job);
+            for webhook in webhooks
+                .into_iter()
+                .filter(|w| event.is_subscribed(&w.events))
+            {
+                tokio::spawn(deliver(store.clone(), webhook, payload.clone()));
+            }
+        }
+    });
+}
+
+/// Deliver one payload, retrying until it is accepted or attempts run out
+async fn deliver(store: Arc<dyn WebhookStore>, webhook: Webhook, payload: WebhookPayload) {
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Failed to serialize webhook payload: {}", e);
+            return;
+        }
+    };
+    let signature = format!("sha256={}", sign_payload(&webhook.secret, &body));
+    let delivery_id = Uuid::new_v4();
+
+    for attempt in 1..=RETRY_DELAYS.len() + 1 {
+        let request = {
+            let url = webhook.url.clone();
+            let body = body.clone();
+            let signature = signature.clone();
+            let event = payload.event.as_str();
+            // Reduced to status and error on the blocking thread, so the
+            // large ureq error does not travel back
+            tokio::task::spawn_blocking(move || {
+                let response = ureq::post(&url)
+                    .timeout(DELIVERY_TIMEOUT)
+                    .set("Content-Type", "application/json")
+                    .set("X-DDALAB-Event", event)
+                    .set("X-DDALAB-Delivery", &delivery_id.to_string())
+                    .set("X-DDALAB-Signature", &signature)
+                    .send_bytes(&body);
+                match response {
+                    Ok(response) => (Some(i32::from(response.status())), None),
+                    Err(ureq::Error::Status(code, _)) => {
+                        (Some(i32::from(code)), Some(format!("HTTP {}", code)))
+                    }
+                    Err(e) => (None, Some(e.to_string())),
+                }
+            })
+        };
+        let (status_code, error) = request
+            .await
+            .unwrap_or_else(|e| (None, Some(e.to_string())));
+        let delivered = error.is_none();
+
+        let record = WebhookDelivery {
+            id: Uuid::new_v4(),
+            webhook_id: webhook.id,
+            event: payload.event.as_str().to_string(),
+            job_id: payload.job_id,
+            attempt: attempt as i32,
+            status_code,
+            error,
+            delivered,
+            created_at: Utc::now(),
+        };
+        if let Err(e) = store.record_delivery(&record).await {
+            warn!("Failed to log webhook delivery: {}", e);
+        }
+
+        if delivered {
+            return;
+        }
+        match RETRY_DELAYS.get(attempt - 1) {
+            Some(delay) => tokio::time::sleep(*delay).await,
+            None => info!(
+                "Giving up on {} for job {} to webhook {} after {} attempts",
+                payload.event.as_str(),
+                payload.job_id,
+                webhook.id,
+                attempt
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(job_id: Uuid, status: JobStatus) -> JobProgressEvent {
+        JobProgressEvent {
+            job_id,
+            status,
+            progress: 0,
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_tracker_reports_each_lifecycle_step_once() {
+        let mut tracker = LifecycleTracker::default();
+        let job = Uuid::new_v4();
+        let observed: Vec<Option<WebhookEvent>> = [
+            JobStatus::Pending,
+            JobStatus::Running,
+            JobStatus::Running,
+            JobStatus::Completed,
+        ]
+        .into_iter()
+        .map(|status| tracker.observe(&progress(job, status)))
+        .collect();
+        assert_eq!(
+            observed,
+            [
+                Some(WebhookEvent::Submitted),
+                Some(WebhookEvent::Started),
+                None,
+                Some(WebhookEvent::Completed),
+            ]
+        );
+        assert!(tracker.started.is_empty());
+        assert_eq!(tracker.observe(&progress(job, JobStatus::Cancelled)), None);
+    }
+
+    #[test]
+    fn test_signature_and_subscriptions() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign_payload("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let completed_only = vec!["job.completed".to_string()];
+        assert!(WebhookEvent::Completed.is_subscribed(&completed_only));
+        assert!(!WebhookEvent::Failed.is_subscribed(&completed_only));
+        assert!(WebhookEvent::Failed.is_subscribed(&[]));
+        assert_eq!("job.started".parse(), Ok(WebhookEvent::Started));
+        assert!("job.deleted".parse::<WebhookEvent>().is_err());
+    }
+}
@@ -0,0 +1,144 @@
+from __future__ import annotations
+
+from dataclasses import dataclass
+from datetime import datetime
+import re
+from typing import Iterable, Optional
+
+from ...domain.models import WaveformAnnotation
+
+_QT_DATETIME_TOKEN = re.compile(
+    r"'(?:[^']|'')*'|yyyy|yy|MMMM|MMM|MM|M|dddd|ddd|dd|d|HH|H|hh|h|mm|m|ss|s|zzz|z|AP|ap|A|a|t"
+)
+_QT_TO_STRFTIME = {
+    "yyyy": "%Y",
+    "yy": "%y",
+    "MMMM": "%B",
+    "MMM": "%b",
+    "MM": "%m",
+    "M": "%m",
+    "dddd": "%A",
+    "ddd": "%a",
+    "dd": "%d",
+    "d": "%d",
+    "HH": "%H",
+    "H": "%H",
+    "mm": "%M",
+    "m": "%M",
+    "ss": "%S",
+    "s": "%S",
+    "AP": "%p",
+    "ap": "%p",
+    "A": "%p",
+    "a": "%p",
+    "t": "%Z",
+}
+
+
+@dataclass(frozen=True)
+class TextExportFormat:
+    """Number and date formatting of a delimited text export."""
+
+    decimal_separator: str = "."
+    # Follows the decimal separator when unset: ";" for "," decimals
+    delimiter: Optional[str] = None
+    # strftime pattern for date columns; ISO 8601 when unset
+    date_format: Optional[str] = None
+
+    def resolved_delimiter(self) -> str:
+        if self.delimiter:
+            return self.delimiter
+        return ";" if self.decimal_separator == "," else ","
+
+    def validate(self) -> None:
+        if self.resolved_delimiter() == self.decimal_separator:
+            raise ValueError(
+                f"Delimiter {self.resolved_delimiter()!r} must differ from the decimal separator."
+            )
+
+    def format_number(self, value: Optional[float]) -> str:
+        if value is None:
+            return ""
+        return f"{float(value):.12g}".replace(".", self.decimal_separator)
+
+    def format_datetime(self, value: datetime) -> str:
+        if self.date_format:
+            return value.strftime(self.date_format)
+        return value.isoformat()
+
+
+def qt_datetime_format_to_strftime(qt_format: str) -> str:
+    """Translate a QLocale date/time format such as ``dd.MM.yy HH:mm``."""
+    twelve_hour = any(marker in qt_format for marker in ("AP", "ap", "A", "a"))
+    parts: list[str] = []
+    position = 0
+    for match in _QT_DATETIME_TOKEN.finditer(qt_format):
+        parts.append(qt_format[position : match.start()].replace("%", "%%"))
+        token = match.group(0)
+        if token.startswith("'"):
+            literal = token[1:-1].replace("''", "'") if len(token) > 2 else "'"
+            parts.append(literal.replace("%", "%%"))
+        elif token in ("hh", "h"):
+            parts.append("%I" if twelve_hour else "%H")
+        elif token in ("zzz", "z"):
+            # strftime has milliseconds only as part of microseconds
+            parts.append("%f")
+        else:
+            parts.append(_QT_TO_STRFTIME[token])
+        position = match.end()
+    parts.append(qt_format[position:].replace("%", "%%"))
+    return "".join(parts)
+
+
+def locale_text_export_format(
+    decimal_point: str, qt_datetime_format: str
+) -> TextExportFormat:
+    return TextExportFormat(
+        decimal_separator=decimal_point or ".",
+        date_format=qt_datetime_format_to_strftime(qt_datetime_format) or None,
+    )
+
+
+def export_annotations_csv(
+    annotations: Iterable[WaveformAnnotation],
+    *,
+    source_file_path: Optional[str],
+    exported_at: datetime,
+    text_format: TextExportFormat = TextExportFormat(),
+) -> str:
+    text_format.validate()
+    delimiter = text_format.resolved_delimiter()
+    exported_at_text = text_format.format_datetime(exported_at)
+    rows = [
+        [
+            "Label",
+            "Channel",
+            "Start (s)",
+            "End (s)",
+            "Notes",
+            "Source File",
+            "Exported At",
+        ]
+    ]
+    for annotation in annotations:
+        rows.append(
+            [
+                annotation.label,
+                annotation.channel_name or "",
+                text_format.format_number(annotation.start_seconds),
+                text_format.format_number(annotation.end_seconds),
+                annotation.notes,
+                source_file_path or "",
+                exported_at_text,
+            ]
+        )
+    return "".join(
+        delimiter.join(_delimited_escape(value, delimiter) for value in row) + "\n"
+        for row in rows
+    )
+
+
+def _delimited_escape(value: str, delimiter: str) -> str:
+    if any(token in value for token in [delimiter, '"', "\n", "\r"]):
+        return '"' + value.replace('"', '""') + '"'
+    return value
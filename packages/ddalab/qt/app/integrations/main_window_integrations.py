@@ -1,13 +1,14 @@
 from __future__ import annotations
 
 from dataclasses import asdict
+from datetime import datetime
 import json
 from pathlib import Path
 from typing import Callable, List, Optional
 import uuid
 import webbrowser
 
-from PySide6.QtCore import QMarginsF, Qt
+from PySide6.QtCore import QLocale, QMarginsF, Qt
 from PySide6.QtGui import QPageLayout, QPageSize, QPainter, QPdfWriter
 from PySide6.QtSvg import QSvgGenerator
 from PySide6.QtWidgets import QFileDialog, QTableWidget, QTableWidgetItem
@@ -18,6 +19,10 @@ from ...domain.models import (
     OpenNeuroDataset,
     WorkflowSessionEntry,
 )
+from .annotation_export_utils import (
+    export_annotations_csv,
+    locale_text_export_format,
+)
 from .dda_export_utils import (
     default_result_base_name,
     export_all_variants_csv,
@@ -994,10 +999,35 @@ class MainWindowIntegrationsMixin:
             self,
             "Export Annotations",
             str(Path.home() / f"{base_name}-annotations.json"),
-            "JSON Files (*.json)",
+            "JSON Files (*.json);;CSV Files (*.csv)",
         )
         if not target_path:
             return
+        if Path(target_path).suffix.lower() == ".csv":
+            # Numbers and dates follow the system locale, e.g. "," decimals
+            # with ";" columns for European spreadsheets
+            locale = QLocale.system()
+            content = export_annotations_csv(
+                annotations,
+                source_file_path=self.state.active_file_path,
+                exported_at=datetime.now().astimezone(),
+                text_format=locale_text_export_format(
+                    locale.decimalPoint(),
+                    locale.dateTimeFormat(QLocale.FormatType.ShortFormat),
+                ),
+            )
+            self._run_background_file_export(
+                target_path=target_path,
+                task=lambda target: target.write_text(content, encoding="utf-8"),
+                pending_message="Exporting annotations…",
+                success_title="Annotations Exported",
+                failure_title="Annotations Export Failed",
+                workflow_action_type="export-annotations",
+                workflow_description=f"Exported annotations to {Path(target_path).name}",
+                workflow_payload={"path": target_path},
+                file_path=self.state.active_file_path,
+            )
+            return
         payload = {
             "activeFilePath": self.state.active_file_path,
             "exportedAtIso": self._now_iso(),
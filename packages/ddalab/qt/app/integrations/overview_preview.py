@@ -0,0 +1,164 @@
+"""Headless overview thumbnails for recordings.
+
+File manager preview providers (a macOS Quick Look extension, a Windows
+thumbnail handler) cannot start the Qt GUI, so the PNG is encoded here without
+Qt. They either call ``render_file_preview_png`` or run
+``ddalab waveform preview --file <path> --output <png>``.
+"""
+
+from __future__ import annotations
+
+from dataclasses import dataclass
+import struct
+from typing import TYPE_CHECKING, List, Optional, Sequence
+import zlib
+
+from ...domain.models import WaveformOverview, WaveformOverviewChannel
+
+if TYPE_CHECKING:
+    from ...backend.contracts import BackendClient
+
+DEFAULT_PREVIEW_WIDTH = 256
+DEFAULT_PREVIEW_HEIGHT = 160
+DEFAULT_PREVIEW_CHANNELS = 8
+# Thumbnails larger than this would only slow the file manager down
+MAX_PREVIEW_SIZE = 2048
+
+_PNG_SIGNATURE = b"\x89PNG\r\n\x1a\n"
+
+Rgb = tuple[int, int, int]
+
+
+@dataclass(frozen=True)
+class PreviewColors:
+    background: Rgb
+    baseline: Rgb
+    trace: Rgb
+
+
+# The light theme's overview colors; previews sit on file manager backgrounds
+LIGHT_PREVIEW_COLORS = PreviewColors(
+    background=(0xF7, 0xFA, 0xFE),
+    baseline=(0xD3, 0xDC, 0xE7),
+    trace=(0x0F, 0x76, 0x6E),
+)
+
+
+def render_overview_png(
+    overview: WaveformOverview,
+    *,
+    width: int = DEFAULT_PREVIEW_WIDTH,
+    height: int = DEFAULT_PREVIEW_HEIGHT,
+    colors: PreviewColors = LIGHT_PREVIEW_COLORS,
+) -> bytes:
+    """Draw each channel's min/max envelope in its own band, like the
+    overview strip of the waveform view."""
+    _validate_size(width, height)
+    canvas = _Canvas(width, height, colors.background)
+    channels = overview.channels
+    band_height = height / max(len(channels), 1)
+    for index, channel in enumerate(channels):
+        top = int(round(index * band_height))
+        bottom = int(round((index + 1) * band_height)) - 1
+        _draw_channel(canvas, channel, top, bottom, colors)
+    return canvas.to_png()
+
+
+def render_file_preview_png(
+    backend: BackendClient,
+    path: str,
+    *,
+    width: int = DEFAULT_PREVIEW_WIDTH,
+    height: int = DEFAULT_PREVIEW_HEIGHT,
+    channel_names: Optional[Sequence[str]] = None,
+    max_channels: int = DEFAULT_PREVIEW_CHANNELS,
+) -> bytes:
+    """Preview of a recording file, from its first `max_channels` channels
+    unless `channel_names` picks them."""
+    _validate_size(width, height)
+    if channel_names:
+        selected = list(channel_names)
+    else:
+        dataset = backend.load_dataset(path)
+        path = dataset.file_path
+        selected = list(dataset.channel_names[: max(1, max_channels)])
+    overview = backend.load_waveform_overview(
+        path,
+        selected,
+        # One bucket per pixel column is all a thumbnail can show
+        max_buckets=width,
+    )
+    return render_overview_png(overview, width=width, height=height)
+
+
+def _validate_size(width: int, height: int) -> None:
+    if not (1 <= width <= MAX_PREVIEW_SIZE and 1 <= height <= MAX_PREVIEW_SIZE):
+        raise ValueError(
+            f"Preview size must be between 1 and {MAX_PREVIEW_SIZE} pixels per side."
+        )
+
+
+def _draw_channel(
+    canvas: "_Canvas",
+    channel: WaveformOverviewChannel,
+    top: int,
+    bottom: int,
+    colors: PreviewColors,
+) -> None:
+    if bottom < top:
+        return
+    canvas.hline((top + bottom) // 2, colors.baseline)
+    bucket_count = min(len(channel.mins), len(channel.maxs))
+    if bucket_count == 0:
+        return
+    range_value = max(channel.max_value - channel.min_value, 1e-6)
+    # Keep a pixel of padding so neighbouring bands stay apart
+    inner_top = top + 1 if bottom - top >= 2 else top
+    inner_bottom = bottom - 1 if bottom - top >= 2 else bottom
+
+    def map_y(value: float) -> int:
+        normalized = max(0.0, min(1.0, (value - channel.min_value) / range_value))
+        return int(round(inner_bottom - normalized * (inner_bottom - inner_top)))
+
+    for x in range(canvas.width):
+        first = x * bucket_count // canvas.width
+        last = max(first + 1, (x + 1) * bucket_count // canvas.width)
+        column_min = min(float(value) for value in channel.mins[first:last])
+        column_max = max(float(value) for value in channel.maxs[first:last])
+        canvas.vline(x, map_y(column_max), map_y(column_min), colors.trace)
+
+
+class _Canvas:
+    def __init__(self, width: int, height: int, background: Rgb) -> None:
+        self.width = width
+        self.height = height
+        self._rows: List[bytearray] = [
+            bytearray(bytes(background) * width) for _ in range(height)
+        ]
+
+    def hline(self, y: int, color: Rgb) -> None:
+        if 0 <= y < self.height:
+            self._rows[y][:] = bytes(color) * self.width
+
+    def vline(self, x: int, y0: int, y1: int, color: Rgb) -> None:
+        offset = x * 3
+        for y in range(max(0, min(y0, y1)), min(self.height - 1, max(y0, y1)) + 1):
+            self._rows[y][offset : offset + 3] = bytes(color)
+
+    def to_png(self) -> bytes:
+        # Filter type 0 before every scanline
+        raw = b"".join(b"\x00" + bytes(row) for row in self._rows)
+        header = struct.pack(">IIBBBBB", self.width, self.height, 8, 2, 0, 0, 0)
+        return b"".join(
+            [
+                _PNG_SIGNATURE,
+                _png_chunk(b"IHDR", header),
+                _png_chunk(b"IDAT", zlib.compress(raw, 9)),
+                _png_chunk(b"IEND", b""),
+            ]
+        )
+
+
+def _png_chunk(kind: bytes, data: bytes) -> bytes:
+    checksum = zlib.crc32(kind + data) & 0xFFFFFFFF
+    return struct.pack(">I", len(data)) + kind + data + struct.pack(">I", checksum)
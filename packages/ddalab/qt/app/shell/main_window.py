@@ -59,6 +59,7 @@ class MainWindow(
             Path.home() / ".ddalab-qt" / "session.json"
         )
         self.state_db.purge_fallback_dda_results()
+        self.state_db.purge_trash()
         self.openneuro = OpenNeuroClient()
         self.state = AppState()
         self._task_executor = ThreadPoolExecutor(
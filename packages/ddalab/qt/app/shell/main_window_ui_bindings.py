@@ -340,6 +340,7 @@ class MainWindowUiBindingsMixin:
         self.settings_update_install_button.clicked.connect(
             self._on_install_update_clicked
         )
+        self.settings_cache_refresh_button.clicked.connect(self._refresh_cache_usage)
 
         self.waveform_reload_timer = QTimer(self)
         self.waveform_reload_timer.setSingleShot(True)
@@ -370,6 +370,24 @@ class MainWindowUiResultsPagesMixin:
         updates_layout.addLayout(updates_actions)
         cards.addWidget(updates_card, 1, 0, 1, 2)
 
+        memory_card, memory_layout = self._build_settings_card(
+            title="Plot Caches",
+            description=(
+                "Rendered plots and DDA matrix tiles share one memory budget, set with DDALAB_CACHE_BUDGET_MB (512 MB by default); the least recently viewed entries are evicted first."
+            ),
+        )
+        self.settings_cache_usage_label = QLabel("")
+        self.settings_cache_usage_label.setWordWrap(True)
+        self.settings_cache_usage_label.setProperty("muted", True)
+        memory_layout.addWidget(self.settings_cache_usage_label)
+        memory_actions = QHBoxLayout()
+        self.settings_cache_refresh_button = QPushButton("Refresh Usage")
+        self.settings_cache_refresh_button.setProperty("secondary", True)
+        memory_actions.addWidget(self.settings_cache_refresh_button)
+        memory_actions.addStretch(1)
+        memory_layout.addLayout(memory_actions)
+        cards.addWidget(memory_card, 2, 0, 1, 2)
+
         layout.addLayout(cards)
         layout.addStretch(1)
         page = self._wrap_scroll_panel(content)
@@ -16,6 +16,7 @@ from PySide6.QtWidgets import (
     QMessageBox,
 )
 
+from ...ui.cache_coordinator import shared_cache_coordinator
 from ...ui.style import apply_theme, normalize_theme_mode
 from ...update_manager import AvailableUpdate, UpdateDownloadProgress
 from ..core.navigation import normalize_navigation
@@ -29,6 +30,14 @@ from .main_window_support_helpers import (
 class MainWindowSupportSessionMixin:
     def _refresh_settings_overview(self) -> None:
         self._refresh_update_ui()
+        self._refresh_cache_usage()
+
+    def _refresh_cache_usage(self) -> None:
+        if not hasattr(self, "settings_cache_usage_label"):
+            return
+        self.settings_cache_usage_label.setText(
+            shared_cache_coordinator().usage_report()
+        )
 
     def _initialize_update_support(self) -> None:
         self._refresh_update_ui()
@@ -13,6 +13,11 @@ from importlib.metadata import PackageNotFoundError, version as package_version
 from pathlib import Path
 from typing import Any, Optional, Sequence
 
+from .app.integrations.overview_preview import (
+    DEFAULT_PREVIEW_HEIGHT,
+    DEFAULT_PREVIEW_WIDTH,
+    render_file_preview_png,
+)
 from .backend.local import LocalBackendClient, _find_cli_command
 from .domain.file_types import resolve_dataset_path, supports_qt_dataset_path
 from .domain.models import DdaReproductionConfig, DdaResult
@@ -138,6 +143,24 @@ def _build_parser() -> argparse.ArgumentParser:
     waveform_overview.add_argument("--max-buckets", type=int, default=1600)
     waveform_overview.set_defaults(handler=_handle_waveform_overview)
 
+    waveform_preview = waveform_subparsers.add_parser(
+        "preview",
+        help="Render a multi-channel overview thumbnail as PNG",
+    )
+    waveform_preview.add_argument("--file", required=True)
+    waveform_preview.add_argument(
+        "--output",
+        required=True,
+        help="PNG path, or - for standard output",
+    )
+    waveform_preview.add_argument("--channels", type=int, nargs="+")
+    waveform_preview.add_argument("--all-channels", action="store_true")
+    waveform_preview.add_argument("--width", type=int, default=DEFAULT_PREVIEW_WIDTH)
+    waveform_preview.add_argument(
+        "--height", type=int, default=DEFAULT_PREVIEW_HEIGHT
+    )
+    waveform_preview.set_defaults(handler=_handle_waveform_preview)
+
     ica_parser = subparsers.add_parser(
         "ica",
         help="Run local ICA analysis through the Python backend",
@@ -421,6 +444,35 @@ def _handle_waveform_overview(args: argparse.Namespace) -> int:
     return 0
 
 
+def _handle_waveform_preview(args: argparse.Namespace) -> int:
+    backend, _runtime_paths = _local_backend()
+    try:
+        dataset = backend.load_dataset(args.file)
+        channel_names = _selected_channel_names(
+            dataset,
+            args.channels,
+            all_channels=args.all_channels,
+        )
+        png = render_file_preview_png(
+            backend,
+            dataset.file_path,
+            width=int(args.width),
+            height=int(args.height),
+            channel_names=channel_names,
+        )
+    finally:
+        backend.close()
+    if args.output == "-":
+        sys.stdout.buffer.write(png)
+        sys.stdout.buffer.flush()
+        return 0
+    output_path = Path(args.output).expanduser()
+    output_path.parent.mkdir(parents=True, exist_ok=True)
+    output_path.write_bytes(png)
+    print(str(output_path))
+    return 0
+
+
 def _handle_ica_run(args: argparse.Namespace) -> int:
     backend, _runtime_paths = _local_backend()
     try:
@@ -634,6 +634,26 @@ class DdaResultSummary:
     is_fallback: bool
 
 
+@dataclass
+class TrashEntry:
+    kind: str
+    id: str
+    file_path: str
+    label: str
+    deleted_at_iso: str
+
+
+@dataclass
+class SearchHit:
+    # "annotation", "dda_result" or "file"
+    kind: str
+    id: str
+    file_path: str
+    title: str
+    detail: str
+    score: int
+
+
 @dataclass
 class IcaComponent:
     component_id: int
@@ -4,6 +4,7 @@ import json
 import sqlite3
 from contextlib import contextmanager
 from dataclasses import asdict
+from datetime import datetime, timedelta, timezone
 from pathlib import Path
 from typing import Iterable, Iterator, List, Optional, Sequence
 
@@ -16,6 +17,8 @@ from ..domain.models import (
     IcaResult,
     NetworkMotifData,
     NotificationEntry,
+    SearchHit,
+    TrashEntry,
     WaveformAnnotation,
     WorkflowActionEntry,
     WorkflowSessionEntry,
@@ -45,13 +48,19 @@ _MIGRATION_DEFINITIONS = {
     "INTEGER NOT NULL DEFAULT 0",
 }
 _MIGRATION_COLUMNS = {
+    "annotations": {"deleted_at"},
     "dda_results": {
         "file_name",
         "engine_label",
         "variant_ids_json",
         "is_fallback",
+        "deleted_at",
     },
 }
+# Deleted annotations and DDA results stay restorable this long
+TRASH_RETENTION_DAYS = 30
+# Ties in search score list annotations first, then analyses, then files
+_SEARCH_KIND_ORDER = {"annotation": 0, "dda_result": 1, "file": 2}
 _TIMESTAMPED_TABLE_ID_COLUMNS = {
     "notifications": "notification_id",
     "workflow_actions": "action_id",
@@ -219,6 +228,8 @@ class StateDatabase:
             "is_fallback",
             "INTEGER NOT NULL DEFAULT 0",
         )
+        self._ensure_column("annotations", "deleted_at", "TEXT")
+        self._ensure_column("dda_results", "deleted_at", "TEXT")
 
     def _ensure_column(
         self, table_name: str, column_name: str, definition: str
@@ -345,6 +356,7 @@ class StateDatabase:
             SELECT payload_json
             FROM annotations
             WHERE file_path = ?
+            AND deleted_at IS NULL
             ORDER BY sort_index ASC
             """,
             (file_path,),
@@ -381,7 +393,8 @@ class StateDatabase:
                         channel_name=excluded.channel_name,
                         start_seconds=excluded.start_seconds,
                         end_seconds=excluded.end_seconds,
-                        payload_json=excluded.payload_json
+                        payload_json=excluded.payload_json,
+                        deleted_at=NULL
                     """,
                     (
                         annotation.id,
@@ -395,30 +408,37 @@ class StateDatabase:
                         self._dumps(asdict(annotation)),
                     ),
                 )
+            # Annotations no longer in the list move to the trash
             if annotation_list:
                 placeholders = self._sql.placeholders(len(annotation_list))
                 self._sql.execute(
                     f"""
-                    DELETE FROM annotations
+                    UPDATE annotations
+                    SET deleted_at = ?
                     WHERE file_path = ?
+                    AND deleted_at IS NULL
                     AND annotation_id NOT IN ({placeholders})
                     """,
-                    [file_path, *[annotation.id for annotation in annotation_list]],
+                    [
+                        self._now_iso(),
+                        file_path,
+                        *[annotation.id for annotation in annotation_list],
+                    ],
                 )
             else:
                 self._sql.execute(
-                    "DELETE FROM annotations WHERE file_path = ?",
-                    (file_path,),
+                    """
+                    UPDATE annotations
+                    SET deleted_at = ?
+                    WHERE file_path = ? AND deleted_at IS NULL
+                    """,
+                    (self._now_iso(), file_path),
                 )
 
     def save_dda_result(self, result: DdaResult) -> None:
         result = result.materialize()
         if result.is_fallback:
-            with self._sql.transaction():
-                self._sql.execute(
-                    "DELETE FROM dda_results WHERE result_id = ?",
-                    (result.id,),
-                )
+            self.delete_dda_result(result.id)
             return
         variant_ids_json = self._dumps([variant.id for variant in result.variants])
         with self._sql.transaction():
@@ -442,7 +462,8 @@ class StateDatabase:
                     engine_label=excluded.engine_label,
                     variant_ids_json=excluded.variant_ids_json,
                     is_fallback=excluded.is_fallback,
-                    payload_json=excluded.payload_json
+                    payload_json=excluded.payload_json,
+                    deleted_at=NULL
                 """,
                 (
                     result.id,
@@ -461,18 +482,246 @@ class StateDatabase:
         with self._sql.transaction():
             self._sql.execute(
                 f"""
-                DELETE FROM dda_results
-                WHERE {_DDA_FALLBACK_SQL}
+                UPDATE dda_results
+                SET deleted_at = ?
+                WHERE deleted_at IS NULL
+                AND {_DDA_FALLBACK_SQL}
+                """,
+                (self._now_iso(),),
+            )
+        return self._sql.total_changes - before_changes
+
+    def delete_dda_result(self, result_id: str) -> bool:
+        with self._sql.transaction():
+            cursor = self._sql.execute(
                 """
+                UPDATE dda_results
+                SET deleted_at = ?
+                WHERE result_id = ? AND deleted_at IS NULL
+                """,
+                (self._now_iso(), result_id),
+            )
+        return cursor.rowcount > 0
+
+    def restore_dda_result(self, result_id: str) -> bool:
+        with self._sql.transaction():
+            cursor = self._sql.execute(
+                """
+                UPDATE dda_results
+                SET deleted_at = NULL
+                WHERE result_id = ? AND deleted_at IS NOT NULL
+                """,
+                (result_id,),
             )
+        return cursor.rowcount > 0
+
+    def restore_annotation(self, annotation_id: str) -> bool:
+        with self._sql.transaction():
+            cursor = self._sql.execute(
+                """
+                UPDATE annotations
+                SET deleted_at = NULL,
+                    sort_index = (
+                        SELECT COALESCE(MAX(live.sort_index), -1) + 1
+                        FROM annotations AS live
+                        WHERE live.file_path = annotations.file_path
+                        AND live.deleted_at IS NULL
+                    )
+                WHERE annotation_id = ? AND deleted_at IS NOT NULL
+                """,
+                (annotation_id,),
+            )
+        return cursor.rowcount > 0
+
+    def list_trash(self, limit: int = 200) -> List[TrashEntry]:
+        rows = self._sql.execute(
+            f"""
+            SELECT
+                'annotation' AS kind,
+                annotation_id AS item_id,
+                file_path,
+                label,
+                deleted_at
+            FROM annotations
+            WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT
+                'dda_result' AS kind,
+                result_id AS item_id,
+                file_path,
+                'DDA result from ' || created_at_iso AS label,
+                deleted_at
+            FROM dda_results
+            WHERE deleted_at IS NOT NULL
+            AND NOT {_DDA_FALLBACK_SQL}
+            ORDER BY deleted_at DESC
+            LIMIT ?
+            """,
+            (limit,),
+        ).fetchall()
+        return [
+            TrashEntry(
+                kind=str(row["kind"]),
+                id=str(row["item_id"]),
+                file_path=str(row["file_path"]),
+                label=str(row["label"]),
+                deleted_at_iso=str(row["deleted_at"]),
+            )
+            for row in rows
+        ]
+
+    def purge_trash(
+        self,
+        older_than_days: Optional[float] = TRASH_RETENTION_DAYS,
+        *,
+        now: Optional[datetime] = None,
+    ) -> int:
+        """Permanently remove trashed rows, all of them when `older_than_days`
+        is None."""
+        if older_than_days is None:
+            cutoff = None
+        else:
+            cutoff = (
+                (now or datetime.now(timezone.utc)) - timedelta(days=older_than_days)
+            ).isoformat()
+        before_changes = self._sql.total_changes
+        with self._sql.transaction():
+            for table_name in ("annotations", "dda_results"):
+                table_sql = self._sql.identifier(
+                    table_name,
+                    allowed=_STATE_TABLES,
+                    kind="table name",
+                )
+                self._sql.execute(
+                    f"""
+                    DELETE FROM {table_sql}
+                    WHERE deleted_at IS NOT NULL
+                    AND (? IS NULL OR deleted_at < ?)
+                    """,
+                    (cutoff, cutoff),
+                )
         return self._sql.total_changes - before_changes
 
+    def search(self, query: str, limit: int = 50) -> List[SearchHit]:
+        """Find annotations, DDA results and open files matching every word
+        of `query`, best matches first. Trashed rows are left out."""
+        terms = [term.casefold() for term in query.split()]
+        if not terms:
+            return []
+        pattern = self._like_pattern(terms[0])
+        hits: List[SearchHit] = []
+        for row in self._sql.execute(
+            """
+            SELECT annotation_id, file_path, label, notes, channel_name, start_seconds
+            FROM annotations
+            WHERE deleted_at IS NULL
+            AND (
+                label LIKE ? ESCAPE '\\'
+                OR notes LIKE ? ESCAPE '\\'
+                OR COALESCE(channel_name, '') LIKE ? ESCAPE '\\'
+                OR file_path LIKE ? ESCAPE '\\'
+            )
+            """,
+            (pattern, pattern, pattern, pattern),
+        ):
+            channel = str(row["channel_name"] or "")
+            file_name = Path(str(row["file_path"])).name
+            detail = f"{file_name} @ {float(row['start_seconds']):.2f}s"
+            if channel:
+                detail = f"{detail} ({channel})"
+            hits.append(
+                SearchHit(
+                    kind="annotation",
+                    id=str(row["annotation_id"]),
+                    file_path=str(row["file_path"]),
+                    title=str(row["label"]),
+                    detail=detail,
+                    score=self._search_score(
+                        terms,
+                        str(row["label"]),
+                        [str(row["notes"]), channel, str(row["file_path"])],
+                    ),
+                )
+            )
+        for row in self._sql.execute(
+            f"""
+            SELECT
+                result_id,
+                file_path,
+                file_name,
+                created_at_iso,
+                engine_label,
+                variant_ids_json,
+                is_fallback
+            FROM dda_results
+            WHERE deleted_at IS NULL
+            AND NOT {_DDA_FALLBACK_SQL}
+            AND (
+                COALESCE(file_name, '') LIKE ? ESCAPE '\\'
+                OR COALESCE(engine_label, '') LIKE ? ESCAPE '\\'
+                OR variant_ids_json LIKE ? ESCAPE '\\'
+                OR file_path LIKE ? ESCAPE '\\'
+            )
+            """,
+            (pattern, pattern, pattern, pattern),
+        ):
+            summary = self._deserialize_dda_result_summary(row)
+            title = summary.file_name or Path(summary.file_path).name
+            hits.append(
+                SearchHit(
+                    kind="dda_result",
+                    id=summary.id,
+                    file_path=summary.file_path,
+                    title=title,
+                    detail=f"{summary.engine_label} · {summary.created_at_iso}",
+                    score=self._search_score(
+                        terms,
+                        title,
+                        [
+                            summary.engine_label,
+                            " ".join(summary.variant_ids),
+                            summary.file_path,
+                        ],
+                    ),
+                )
+            )
+        for row in self._sql.execute(
+            """
+            SELECT path
+            FROM open_files
+            WHERE path LIKE ? ESCAPE '\\'
+            ORDER BY position ASC
+            """,
+            (pattern,),
+        ):
+            path = str(row["path"])
+            hits.append(
+                SearchHit(
+                    kind="file",
+                    id=path,
+                    file_path=path,
+                    title=Path(path).name,
+                    detail=path,
+                    score=self._search_score(terms, Path(path).name, [path]),
+                )
+            )
+        ranked = sorted(
+            (hit for hit in hits if hit.score > 0),
+            key=lambda hit: (
+                -hit.score,
+                _SEARCH_KIND_ORDER[hit.kind],
+                hit.title.casefold(),
+            ),
+        )
+        return ranked[:limit]
+
     def load_dda_history(self, file_path: str, limit: int = 30) -> List[DdaResult]:
         rows = self._sql.execute(
             f"""
             SELECT payload_json
             FROM dda_results
             WHERE file_path = ?
+            AND deleted_at IS NULL
             AND NOT {_DDA_FALLBACK_SQL}
             ORDER BY created_at_iso DESC
             LIMIT ?
@@ -499,6 +748,7 @@ class StateDatabase:
                 is_fallback
             FROM dda_results
             WHERE file_path = ?
+            AND deleted_at IS NULL
             AND NOT {_DDA_FALLBACK_SQL}
             ORDER BY created_at_iso DESC
             LIMIT ?
@@ -513,6 +763,7 @@ class StateDatabase:
             SELECT payload_json
             FROM dda_results
             WHERE result_id = ?
+            AND deleted_at IS NULL
             AND NOT {_DDA_FALLBACK_SQL}
             LIMIT 1
             """,
@@ -935,6 +1186,34 @@ class StateDatabase:
             if isinstance(item, dict)
         ]
 
+    @staticmethod
+    def _like_pattern(term: str) -> str:
+        escaped = term.replace("\\", "\\\\").replace("%", "\\%").replace("_", "\\_")
+        return f"%{escaped}%"
+
+    @staticmethod
+    def _search_score(terms: Sequence[str], title: str, fields: Sequence[str]) -> int:
+        """Rank a hit by where each term matched; 0 when any term is missing."""
+        title_text = title.casefold()
+        field_text = " ".join(fields).casefold()
+        score = 0
+        for term in terms:
+            if title_text == term:
+                score += 100
+            elif title_text.startswith(term):
+                score += 60
+            elif term in title_text:
+                score += 40
+            elif term in field_text:
+                score += 10
+            else:
+                return 0
+        return score
+
+    @staticmethod
+    def _now_iso() -> str:
+        return datetime.now(timezone.utc).isoformat()
+
     @staticmethod
     def _loads(value: str) -> object:
         try:
@@ -0,0 +1,196 @@
+from __future__ import annotations
+
+from dataclasses import dataclass, fields, is_dataclass
+import itertools
+import os
+from threading import RLock
+from typing import Optional, Protocol
+import weakref
+
+from ..app.runtime.perf_logging import perf_logger
+
+_DEFAULT_CACHE_BUDGET_MB = 512
+
+
+class CoordinatedCache(Protocol):
+    name: str
+    # Caches with a lower priority are emptied first
+    priority: int
+
+    @property
+    def size(self) -> int: ...
+
+    @property
+    def usage_bytes(self) -> int: ...
+
+    def oldest_access(self) -> Optional[int]: ...
+
+    def evict_oldest(self) -> int: ...
+
+
+@dataclass(frozen=True)
+class CacheUsage:
+    name: str
+    caches: int
+    entries: int
+    bytes: int
+
+
+class CacheCoordinator:
+    """Holds the render and tile caches of every plot surface to one
+    memory budget, evicting the least recently used entries across them."""
+
+    def __init__(self, budget_bytes: Optional[int]) -> None:
+        self._lock = RLock()
+        self._caches: weakref.WeakSet[CoordinatedCache] = weakref.WeakSet()
+        self._clock = itertools.count(1)
+        self._budget_bytes = budget_bytes
+        self.evictions = 0
+
+    @property
+    def budget_bytes(self) -> Optional[int]:
+        return self._budget_bytes
+
+    def set_budget_bytes(self, budget_bytes: Optional[int]) -> None:
+        with self._lock:
+            self._budget_bytes = budget_bytes
+            self.enforce()
+
+    def register(self, cache: CoordinatedCache) -> None:
+        with self._lock:
+            self._caches.add(cache)
+
+    def tick(self) -> int:
+        return next(self._clock)
+
+    def total_bytes(self) -> int:
+        with self._lock:
+            return sum(cache.usage_bytes for cache in list(self._caches))
+
+    def enforce(self, keep: Optional[CoordinatedCache] = None) -> int:
+        """Evict until usage fits the budget; `keep` retains its newest
+        entry so that a just-rendered view is never thrown away."""
+        budget = self._budget_bytes
+        if budget is None:
+            return 0
+        freed = 0
+        evicted = 0
+        with self._lock:
+            total = self.total_bytes()
+            while total > budget:
+                victim = self._next_victim(keep)
+                if victim is None:
+                    break
+                released = victim.evict_oldest()
+                total -= released
+                freed += released
+                evicted += 1
+            self.evictions += evicted
+        if evicted:
+            perf_logger().log(
+                "cache_budget_evict",
+                entries=evicted,
+                freedBytes=freed,
+                totalBytes=total,
+                budgetBytes=budget,
+            )
+        return freed
+
+    def _next_victim(
+        self, keep: Optional[CoordinatedCache]
+    ) -> Optional[CoordinatedCache]:
+        victim: Optional[CoordinatedCache] = None
+        victim_key: Optional[tuple[int, int]] = None
+        for cache in list(self._caches):
+            if cache.size == 0 or (cache is keep and cache.size <= 1):
+                continue
+            oldest = cache.oldest_access()
+            if oldest is None:
+                continue
+            key = (cache.priority, oldest)
+            if victim_key is None or key < victim_key:
+                victim, victim_key = cache, key
+        return victim
+
+    def usage(self) -> list[CacheUsage]:
+        totals: dict[str, list[int]] = {}
+        with self._lock:
+            for cache in list(self._caches):
+                entry = totals.setdefault(cache.name, [0, 0, 0])
+                entry[0] += 1
+                entry[1] += cache.size
+                entry[2] += cache.usage_bytes
+        return [
+            CacheUsage(name=name, caches=counts[0], entries=counts[1], bytes=counts[2])
+            for name, counts in sorted(totals.items())
+        ]
+
+    def usage_report(self) -> str:
+        lines = [
+            f"{usage.name}: {usage.entries} entries in {usage.caches} caches, "
+            f"{_megabytes(usage.bytes)}"
+            for usage in self.usage()
+        ]
+        budget = (
+            _megabytes(self._budget_bytes)
+            if self._budget_bytes is not None
+            else "unlimited"
+        )
+        lines.append(
+            f"Total: {_megabytes(self.total_bytes())} of {budget}, "
+            f"{self.evictions} evicted"
+        )
+        return "\n".join(lines)
+
+
+def cache_budget_bytes_from_env() -> Optional[int]:
+    """`DDALAB_CACHE_BUDGET_MB`, 512 by default; 0 or less is unlimited."""
+    raw_budget = os.environ.get(
+        "DDALAB_CACHE_BUDGET_MB", str(_DEFAULT_CACHE_BUDGET_MB)
+    ).strip()
+    try:
+        budget_mb = float(raw_budget)
+    except ValueError:
+        budget_mb = _DEFAULT_CACHE_BUDGET_MB
+    if budget_mb <= 0:
+        return None
+    return int(budget_mb * 1024 * 1024)
+
+
+def estimate_nbytes(value: object, _depth: int = 0) -> int:
+    """Rough size of a cached value: its arrays and images, plus a small
+    overhead for everything else."""
+    if _depth > 4 or value is None:
+        return 0
+    nbytes = getattr(value, "nbytes", None)
+    if isinstance(nbytes, int):
+        return nbytes
+    size_in_bytes = getattr(value, "sizeInBytes", None)
+    if callable(size_in_bytes):
+        try:
+            return int(size_in_bytes())
+        except (TypeError, ValueError):
+            return 0
+    if isinstance(value, (bytes, bytearray, str)):
+        return len(value)
+    if is_dataclass(value) and not isinstance(value, type):
+        return sum(
+            estimate_nbytes(getattr(value, field.name), _depth + 1)
+            for field in fields(value)
+        )
+    if isinstance(value, (tuple, list)):
+        return sum(estimate_nbytes(item, _depth + 1) for item in value)
+    if isinstance(value, dict):
+        return sum(estimate_nbytes(item, _depth + 1) for item in value.values())
+    return 64
+
+
+def _megabytes(value: int) -> str:
+    return f"{value / (1024 * 1024):.1f} MB"
+
+
+_SHARED_COORDINATOR = CacheCoordinator(cache_budget_bytes_from_env())
+
+
+def shared_cache_coordinator() -> CacheCoordinator:
+    return _SHARED_COORDINATOR
@@ -67,8 +67,9 @@ MatrixTileKey = tuple[
 
 class MatrixTileCache:
     def __init__(self, capacity: int = 16) -> None:
+        # Tiles outlive the images rendered from them under memory pressure
         self._cache: LruRenderCache[MatrixTileKey, MatrixView] = LruRenderCache(
-            capacity
+            capacity, name="matrix-tiles", priority=1
         )
 
     @property
@@ -77,7 +77,7 @@ class QuickPlotSurfaceBridge(QObject):
         self._render_cache = LruRenderCache[
             MatrixViewRenderKey,
             MatrixRenderArtifacts,
-        ](_RENDER_CACHE_CAPACITY)
+        ](_RENDER_CACHE_CAPACITY, name="matrix-render")
 
     def clear(self) -> None:
         self._title = "DDALAB plot"
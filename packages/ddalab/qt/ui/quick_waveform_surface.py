@@ -61,7 +61,7 @@ class QuickWaveformSurfaceBridge(QObject):
         self._render_cache = LruRenderCache[
             WaveformRenderKey,
             WaveformRenderArtifacts,
-        ](_RENDER_CACHE_CAPACITY)
+        ](_RENDER_CACHE_CAPACITY, name="waveform-render")
 
     def clear(self) -> None:
         self._title = "DDALAB waveform"
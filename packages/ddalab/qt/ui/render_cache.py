@@ -1,32 +1,78 @@
 from __future__ import annotations
 
 from collections import OrderedDict
-from typing import Generic, Hashable, TypeVar
+from typing import Generic, Hashable, Optional, TypeVar
+
+from .cache_coordinator import (
+    CacheCoordinator,
+    estimate_nbytes,
+    shared_cache_coordinator,
+)
 
 K = TypeVar("K", bound=Hashable)
 V = TypeVar("V")
 
 
 class LruRenderCache(Generic[K, V]):
-    def __init__(self, capacity: int = 8) -> None:
+    def __init__(
+        self,
+        capacity: int = 8,
+        *,
+        name: str = "render",
+        priority: int = 0,
+        coordinator: Optional[CacheCoordinator] = None,
+    ) -> None:
         self._capacity = max(1, int(capacity))
-        self._items: OrderedDict[K, V] = OrderedDict()
+        # Value, estimated bytes and last access tick of each entry
+        self._items: OrderedDict[K, tuple[V, int, int]] = OrderedDict()
+        self._bytes = 0
+        self.name = name
+        self.priority = priority
+        self._coordinator = coordinator or shared_cache_coordinator()
+        self._coordinator.register(self)
 
     @property
     def size(self) -> int:
         return len(self._items)
 
+    @property
+    def usage_bytes(self) -> int:
+        return self._bytes
+
     def get(self, key: K) -> V | None:
-        value = self._items.pop(key, None)
-        if value is not None:
-            self._items[key] = value
+        item = self._items.pop(key, None)
+        if item is None:
+            return None
+        value, nbytes, _ = item
+        self._items[key] = (value, nbytes, self._coordinator.tick())
         return value
 
     def put(self, key: K, value: V) -> None:
-        self._items.pop(key, None)
-        self._items[key] = value
+        self._discard(key)
+        nbytes = estimate_nbytes(value)
+        self._items[key] = (value, nbytes, self._coordinator.tick())
+        self._bytes += nbytes
         while len(self._items) > self._capacity:
-            self._items.popitem(last=False)
+            self.evict_oldest()
+        self._coordinator.enforce(keep=self)
+
+    def oldest_access(self) -> Optional[int]:
+        for _, _, last_used in self._items.values():
+            return last_used
+        return None
+
+    def evict_oldest(self) -> int:
+        if not self._items:
+            return 0
+        _, (_, nbytes, _) = self._items.popitem(last=False)
+        self._bytes -= nbytes
+        return nbytes
 
     def clear(self) -> None:
         self._items.clear()
+        self._bytes = 0
+
+    def _discard(self, key: K) -> None:
+        item = self._items.pop(key, None)
+        if item is not None:
+            self._bytes -= item[1]
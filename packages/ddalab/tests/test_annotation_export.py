@@ -0,0 +1,111 @@
+from __future__ import annotations
+
+from datetime import datetime, timezone
+import sys
+import unittest
+from pathlib import Path
+
+# ruff: noqa: E402
+PACKAGE_ROOT = Path(__file__).resolve().parents[1]
+if str(PACKAGE_ROOT) not in sys.path:
+    sys.path.insert(0, str(PACKAGE_ROOT))
+
+from qt.app.integrations.annotation_export_utils import (
+    TextExportFormat,
+    export_annotations_csv,
+    locale_text_export_format,
+    qt_datetime_format_to_strftime,
+)
+from qt.domain.models import WaveformAnnotation
+
+
+EXPORTED_AT = datetime(2026, 3, 4, 15, 6, 7, tzinfo=timezone.utc)
+
+
+def _annotations() -> list[WaveformAnnotation]:
+    return [
+        WaveformAnnotation(
+            id="a1",
+            label="Spike",
+            notes='sharp, "high" amplitude',
+            channel_name="Fp1",
+            start_seconds=12.5,
+            end_seconds=13.25,
+        ),
+        WaveformAnnotation(
+            id="a2",
+            label="Artifact",
+            notes="",
+            channel_name=None,
+            start_seconds=40.0,
+        ),
+    ]
+
+
+class AnnotationExportTests(unittest.TestCase):
+    def test_default_csv_uses_point_decimals_and_iso_dates(self) -> None:
+        content = export_annotations_csv(
+            _annotations(),
+            source_file_path="/data/sub-01.edf",
+            exported_at=EXPORTED_AT,
+        )
+
+        lines = content.splitlines()
+        self.assertEqual(
+            lines[0], "Label,Channel,Start (s),End (s),Notes,Source File,Exported At"
+        )
+        self.assertEqual(
+            lines[1],
+            'Spike,Fp1,12.5,13.25,"sharp, ""high"" amplitude",/data/sub-01.edf,'
+            "2026-03-04T15:06:07+00:00",
+        )
+        self.assertEqual(
+            lines[2], "Artifact,,40,,,/data/sub-01.edf,2026-03-04T15:06:07+00:00"
+        )
+
+    def test_comma_decimals_switch_to_semicolons_and_local_dates(self) -> None:
+        content = export_annotations_csv(
+            _annotations(),
+            source_file_path=None,
+            exported_at=EXPORTED_AT,
+            text_format=TextExportFormat(
+                decimal_separator=",", date_format="%d.%m.%Y %H:%M"
+            ),
+        )
+
+        lines = content.splitlines()
+        self.assertTrue(lines[0].startswith("Label;Channel;Start (s);"))
+        # Commas in text no longer need quoting; quotes still do
+        self.assertEqual(
+            lines[1],
+            'Spike;Fp1;12,5;13,25;"sharp, ""high"" amplitude";;04.03.2026 15:06',
+        )
+
+    def test_delimiter_must_differ_from_decimal_separator(self) -> None:
+        with self.assertRaises(ValueError):
+            export_annotations_csv(
+                _annotations(),
+                source_file_path=None,
+                exported_at=EXPORTED_AT,
+                text_format=TextExportFormat(decimal_separator=",", delimiter=","),
+            )
+
+    def test_qt_locale_formats_translate_to_strftime(self) -> None:
+        self.assertEqual(
+            qt_datetime_format_to_strftime("dd.MM.yy HH:mm"), "%d.%m.%y %H:%M"
+        )
+        self.assertEqual(
+            qt_datetime_format_to_strftime("M/d/yy h:mm AP"), "%m/%d/%y %I:%M %p"
+        )
+        self.assertEqual(
+            qt_datetime_format_to_strftime("yyyy-MM-dd 'at' HH:mm 100%"),
+            "%Y-%m-%d at %H:%M 100%%",
+        )
+
+        text_format = locale_text_export_format(",", "dd.MM.yyyy HH:mm")
+        self.assertEqual(text_format.resolved_delimiter(), ";")
+        self.assertEqual(text_format.format_datetime(EXPORTED_AT), "04.03.2026 15:06")
+
+
+if __name__ == "__main__":
+    unittest.main()
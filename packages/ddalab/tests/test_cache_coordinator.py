@@ -0,0 +1,108 @@
+from __future__ import annotations
+
+import os
+import sys
+import unittest
+from pathlib import Path
+from unittest.mock import patch
+
+# ruff: noqa: E402
+PACKAGE_ROOT = Path(__file__).resolve().parents[1]
+if str(PACKAGE_ROOT) not in sys.path:
+    sys.path.insert(0, str(PACKAGE_ROOT))
+
+from qt.ui.cache_coordinator import (
+    CacheCoordinator,
+    cache_budget_bytes_from_env,
+    estimate_nbytes,
+)
+from qt.ui.render_cache import LruRenderCache
+
+
+class CacheCoordinatorTests(unittest.TestCase):
+    def test_caches_share_one_budget_and_evict_least_recent_first(self) -> None:
+        coordinator = CacheCoordinator(budget_bytes=300)
+        tiles = LruRenderCache[str, bytes](8, name="tiles", coordinator=coordinator)
+        renders = LruRenderCache[str, bytes](8, name="renders", coordinator=coordinator)
+
+        tiles.put("a", b"x" * 100)
+        renders.put("b", b"x" * 100)
+        tiles.put("c", b"x" * 100)
+        self.assertEqual(coordinator.total_bytes(), 300)
+
+        # Touching "a" makes "b", in the other cache, the oldest entry
+        self.assertIsNotNone(tiles.get("a"))
+        renders.put("d", b"x" * 100)
+
+        self.assertIsNone(renders.get("b"))
+        self.assertIsNotNone(renders.get("d"))
+        self.assertEqual(tiles.size, 2)
+        self.assertEqual(coordinator.total_bytes(), 300)
+        self.assertEqual(coordinator.evictions, 1)
+
+    def test_lower_priority_caches_are_emptied_first(self) -> None:
+        coordinator = CacheCoordinator(budget_bytes=200)
+        tiles = LruRenderCache[str, bytes](
+            8, name="tiles", priority=1, coordinator=coordinator
+        )
+        renders = LruRenderCache[str, bytes](8, name="renders", coordinator=coordinator)
+
+        tiles.put("tile", b"x" * 100)
+        renders.put("old", b"x" * 100)
+        renders.put("new", b"x" * 100)
+
+        self.assertIsNotNone(tiles.get("tile"))
+        self.assertIsNone(renders.get("old"))
+        self.assertIsNotNone(renders.get("new"))
+
+    def test_newest_entry_is_kept_even_when_over_budget(self) -> None:
+        coordinator = CacheCoordinator(budget_bytes=50)
+        cache = LruRenderCache[str, bytes](8, coordinator=coordinator)
+
+        cache.put("large", b"x" * 100)
+
+        self.assertEqual(cache.size, 1)
+        cache.put("larger", b"x" * 200)
+        self.assertIsNone(cache.get("large"))
+        self.assertEqual(cache.usage_bytes, 200)
+
+        coordinator.set_budget_bytes(None)
+        cache.put("small", b"x" * 10)
+        self.assertEqual(cache.size, 2)
+
+    def test_usage_report_groups_caches_by_name(self) -> None:
+        coordinator = CacheCoordinator(budget_bytes=4 * 1024 * 1024)
+        first = LruRenderCache[str, bytes](8, name="renders", coordinator=coordinator)
+        second = LruRenderCache[str, bytes](8, name="renders", coordinator=coordinator)
+        first.put("a", b"x" * 1024 * 1024)
+        second.put("b", b"x" * 1024 * 1024)
+
+        [usage] = coordinator.usage()
+        self.assertEqual((usage.name, usage.caches, usage.entries), ("renders", 2, 2))
+        self.assertEqual(usage.bytes, 2 * 1024 * 1024)
+        report = coordinator.usage_report()
+        self.assertIn("renders: 2 entries in 2 caches, 2.0 MB", report)
+        self.assertIn("Total: 2.0 MB of 4.0 MB, 0 evicted", report)
+
+        # Caches of closed plots stop counting
+        del second
+        self.assertEqual(coordinator.total_bytes(), 1024 * 1024)
+
+    def test_estimate_counts_nested_buffers(self) -> None:
+        class Image:
+            def sizeInBytes(self) -> int:
+                return 4096
+
+        self.assertEqual(estimate_nbytes((b"x" * 10, [Image()])), 4106)
+
+    def test_budget_comes_from_environment(self) -> None:
+        with patch.dict(os.environ, {"DDALAB_CACHE_BUDGET_MB": "64"}):
+            self.assertEqual(cache_budget_bytes_from_env(), 64 * 1024 * 1024)
+        with patch.dict(os.environ, {"DDALAB_CACHE_BUDGET_MB": "0"}):
+            self.assertIsNone(cache_budget_bytes_from_env())
+        with patch.dict(os.environ, {"DDALAB_CACHE_BUDGET_MB": "lots"}):
+            self.assertEqual(cache_budget_bytes_from_env(), 512 * 1024 * 1024)
+
+
+if __name__ == "__main__":
+    unittest.main()
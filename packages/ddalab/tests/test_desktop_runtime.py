@@ -2,6 +2,7 @@ from __future__ import annotations
 
 # ruff: noqa: E402
 
+from datetime import datetime, timedelta, timezone
 import os
 from pathlib import Path
 import sys
@@ -53,7 +54,7 @@ from qt.backend.readers.local import (
     _nifti_browser_channel_limit,
     _representative_nifti_indices,
 )
-from qt.domain.models import NotificationEntry
+from qt.domain.models import DdaResult, NotificationEntry, WaveformAnnotation
 from qt.persistence.state_db import StateDatabase
 from qt.runtime_paths import RuntimePaths
 from qt.update_manager import (
@@ -193,6 +194,14 @@ class BackendApiTests(unittest.TestCase):
         with self.assertRaises(SystemExit):
             parser.parse_args(["gui", "--server", "http://127.0.0.1:8000"])
 
+    def test_waveform_preview_command_defaults_to_thumbnail_size(self) -> None:
+        args = _build_parser().parse_args(
+            ["waveform", "preview", "--file", "/data/sub-01.edf", "--output", "-"]
+        )
+        self.assertEqual((args.width, args.height), (256, 160))
+        self.assertEqual(args.output, "-")
+        self.assertEqual(args.handler.__name__, "_handle_waveform_preview")
+
     def test_backend_package_exports_local_clients_only(self) -> None:
         self.assertFalse(hasattr(backend_package, "RemoteBackendClient"))
         self.assertTrue(hasattr(backend_package, "LocalBackendClient"))
@@ -448,6 +457,159 @@ class StateDatabaseSqlSafetyTests(unittest.TestCase):
                 db.close()
 
 
+def _annotation(annotation_id: str, label: str) -> WaveformAnnotation:
+    return WaveformAnnotation(
+        id=annotation_id,
+        label=label,
+        notes="",
+        channel_name=None,
+        start_seconds=1.0,
+    )
+
+
+def _dda_result(result_id: str, *, is_fallback: bool = False) -> DdaResult:
+    return DdaResult(
+        id=result_id,
+        file_path="/data/sub-01.edf",
+        file_name="sub-01.edf",
+        created_at_iso="2026-01-01T00:00:00+00:00",
+        engine_label="dda-rs",
+        diagnostics=[],
+        window_centers_seconds=[],
+        variants=[],
+        is_fallback=is_fallback,
+    )
+
+
+class StateDatabaseTrashTests(unittest.TestCase):
+    def setUp(self) -> None:
+        self._tmpdir = tempfile.TemporaryDirectory()
+        self.db = StateDatabase(Path(self._tmpdir.name) / "state.sqlite3")
+
+    def tearDown(self) -> None:
+        self.db.close()
+        self._tmpdir.cleanup()
+
+    def test_removed_annotations_move_to_trash_and_restore(self) -> None:
+        path = "/data/sub-01.edf"
+        spike, artifact = _annotation("a1", "Spike"), _annotation("a2", "Artifact")
+        self.db.replace_annotations_for_file(path, [spike, artifact])
+        self.db.replace_annotations_for_file(path, [artifact])
+
+        self.assertEqual(self.db.load_annotations_for_file(path), [artifact])
+        [entry] = self.db.list_trash()
+        self.assertEqual(
+            (entry.kind, entry.id, entry.file_path, entry.label),
+            ("annotation", "a1", path, "Spike"),
+        )
+
+        self.assertTrue(self.db.restore_annotation("a1"))
+        self.assertFalse(self.db.restore_annotation("a1"))
+        # Restored annotations go after the ones still in place
+        self.assertEqual(self.db.load_annotations_for_file(path), [artifact, spike])
+        self.assertEqual(self.db.list_trash(), [])
+
+        self.db.replace_annotations_for_file(path, [])
+        self.assertEqual(self.db.load_annotations_for_file(path), [])
+        self.assertEqual(len(self.db.list_trash()), 2)
+
+    def test_deleted_dda_results_are_hidden_until_restored(self) -> None:
+        self.db.save_dda_result(_dda_result("r1"))
+        self.assertTrue(self.db.delete_dda_result("r1"))
+        self.assertFalse(self.db.delete_dda_result("r1"))
+
+        self.assertIsNone(self.db.load_dda_result_by_id("r1"))
+        self.assertEqual(self.db.load_dda_history_summaries("/data/sub-01.edf"), [])
+        [entry] = self.db.list_trash()
+        self.assertEqual((entry.kind, entry.id), ("dda_result", "r1"))
+
+        self.assertTrue(self.db.restore_dda_result("r1"))
+        restored = self.db.load_dda_result_by_id("r1")
+        self.assertIsNotNone(restored)
+        self.assertEqual(restored.id, "r1")
+
+    def test_fallback_results_are_trashed_but_not_listed(self) -> None:
+        self.db.save_dda_result(_dda_result("r1"))
+        # Saving a fallback over a real result keeps the real one restorable
+        self.db.save_dda_result(_dda_result("r1", is_fallback=True))
+        self.assertIsNone(self.db.load_dda_result_by_id("r1"))
+        self.assertEqual([entry.id for entry in self.db.list_trash()], ["r1"])
+
+        self.db._sql.execute(
+            "UPDATE dda_results SET is_fallback = 1, deleted_at = NULL"
+        )
+        self.db._sql.commit()
+        self.assertEqual(self.db.purge_fallback_dda_results(), 1)
+        self.assertEqual(self.db.purge_fallback_dda_results(), 0)
+        self.assertEqual(self.db.list_trash(), [])
+
+    def test_purge_removes_only_expired_trash(self) -> None:
+        path = "/data/sub-01.edf"
+        self.db.replace_annotations_for_file(path, [_annotation("a1", "Spike")])
+        self.db.replace_annotations_for_file(path, [])
+        self.db.save_dda_result(_dda_result("r1"))
+        self.db.delete_dda_result("r1")
+
+        self.assertEqual(self.db.purge_trash(), 0)
+        later = datetime.now(timezone.utc) + timedelta(days=31)
+        self.assertEqual(self.db.purge_trash(now=later), 2)
+        self.assertEqual(self.db.list_trash(), [])
+        self.assertFalse(self.db.restore_annotation("a1"))
+
+        self.db.replace_annotations_for_file(path, [_annotation("a2", "Artifact")])
+        self.db.replace_annotations_for_file(path, [])
+        self.assertEqual(self.db.purge_trash(None), 1)
+
+
+class StateDatabaseSearchTests(unittest.TestCase):
+    def setUp(self) -> None:
+        self._tmpdir = tempfile.TemporaryDirectory()
+        self.db = StateDatabase(Path(self._tmpdir.name) / "state.sqlite3")
+
+    def tearDown(self) -> None:
+        self.db.close()
+        self._tmpdir.cleanup()
+
+    def test_search_returns_typed_hits_ranked_by_match(self) -> None:
+        path = "/data/sub-01.edf"
+        spike = _annotation("a1", "Spike")
+        spike.notes = "sub-01 interictal"
+        self.db.replace_annotations_for_file(
+            path, [spike, _annotation("a2", "Artifact")]
+        )
+        self.db.save_dda_result(_dda_result("r1"))
+        self.db.save_session_payload({"openFiles": [path, "/data/sub-02.edf"]})
+
+        hits = self.db.search("sub-01")
+        self.assertEqual(
+            [(hit.kind, hit.id) for hit in hits],
+            [
+                ("dda_result", "r1"),
+                ("file", path),
+                ("annotation", "a2"),
+                ("annotation", "a1"),
+            ],
+        )
+        self.assertEqual(hits[1].title, "sub-01.edf")
+
+        [hit] = self.db.search("SPIKE interictal")
+        self.assertEqual((hit.kind, hit.id, hit.file_path), ("annotation", "a1", path))
+        self.assertEqual(self.db.search("spike sub-02"), [])
+        self.assertEqual(self.db.search("   "), [])
+
+    def test_search_skips_trash_and_escapes_wildcards(self) -> None:
+        path = "/data/sub-01.edf"
+        self.db.replace_annotations_for_file(path, [_annotation("a1", "50% drop")])
+        self.db.save_dda_result(_dda_result("r1"))
+
+        self.assertEqual([hit.id for hit in self.db.search("50%")], ["a1"])
+        self.assertEqual(self.db.search("5_%"), [])
+
+        self.db.replace_annotations_for_file(path, [])
+        self.db.delete_dda_result("r1")
+        self.assertEqual(self.db.search("sub-01"), [])
+
+
 class UpdateManagerTests(unittest.TestCase):
     @classmethod
     def setUpClass(cls) -> None:
@@ -0,0 +1,146 @@
+from __future__ import annotations
+
+import struct
+import sys
+import unittest
+import zlib
+from pathlib import Path
+from types import SimpleNamespace
+
+# ruff: noqa: E402
+PACKAGE_ROOT = Path(__file__).resolve().parents[1]
+if str(PACKAGE_ROOT) not in sys.path:
+    sys.path.insert(0, str(PACKAGE_ROOT))
+
+from qt.app.integrations.overview_preview import (
+    LIGHT_PREVIEW_COLORS,
+    render_file_preview_png,
+    render_overview_png,
+)
+from qt.domain.models import WaveformOverview, WaveformOverviewChannel
+
+
+def _channel(name: str, mins: list[float], maxs: list[float]) -> WaveformOverviewChannel:
+    return WaveformOverviewChannel(
+        name=name,
+        bucket_duration_seconds=1.0,
+        mins=mins,
+        maxs=maxs,
+        min_value=min(mins),
+        max_value=max(maxs),
+    )
+
+
+def _overview(channels: list[WaveformOverviewChannel]) -> WaveformOverview:
+    return WaveformOverview(
+        dataset_file_path="/data/sub-01.edf",
+        duration_seconds=float(len(channels[0].mins)) if channels else 0.0,
+        channels=channels,
+        from_cache=False,
+    )
+
+
+def _decode_png(png: bytes) -> tuple[int, int, list[bytes]]:
+    """Width, height and RGB rows of an unfiltered 8-bit RGB PNG."""
+    if not png.startswith(b"\x89PNG\r\n\x1a\n"):
+        raise AssertionError("missing PNG signature")
+    position = 8
+    chunks: dict[bytes, bytes] = {}
+    while position < len(png):
+        (length,) = struct.unpack(">I", png[position : position + 4])
+        kind = png[position + 4 : position + 8]
+        data = png[position + 8 : position + 8 + length]
+        (checksum,) = struct.unpack(
+            ">I", png[position + 8 + length : position + 12 + length]
+        )
+        if zlib.crc32(kind + data) & 0xFFFFFFFF != checksum:
+            raise AssertionError(f"bad CRC in {kind!r}")
+        chunks[kind] = chunks.get(kind, b"") + data
+        position += 12 + length
+    width, height, depth, color_type = struct.unpack(">IIBB", chunks[b"IHDR"][:10])
+    if (depth, color_type) != (8, 2):
+        raise AssertionError("expected 8-bit RGB")
+    raw = zlib.decompress(chunks[b"IDAT"])
+    stride = width * 3 + 1
+    rows = [raw[index * stride + 1 : (index + 1) * stride] for index in range(height)]
+    return width, height, rows
+
+
+def _pixel(rows: list[bytes], x: int, y: int) -> tuple[int, int, int]:
+    return tuple(rows[y][x * 3 : x * 3 + 3])
+
+
+class OverviewPreviewTests(unittest.TestCase):
+    def test_each_channel_draws_its_envelope_in_its_own_band(self) -> None:
+        # A flat first channel and a second one that only swings at the end
+        overview = _overview(
+            [
+                _channel("Fp1", [0.0] * 8, [1.0] * 8),
+                _channel("Fp2", [0.0] * 7 + [-5.0], [0.0] * 7 + [5.0]),
+            ]
+        )
+
+        width, height, rows = _decode_png(
+            render_overview_png(overview, width=16, height=40)
+        )
+
+        self.assertEqual((width, height), (16, 40))
+        trace = LIGHT_PREVIEW_COLORS.trace
+        background = LIGHT_PREVIEW_COLORS.background
+        # The first band is filled top to bottom by its envelope
+        self.assertEqual(_pixel(rows, 0, 1), trace)
+        self.assertEqual(_pixel(rows, 0, 18), trace)
+        # The second band stays near its center until the last buckets
+        self.assertEqual(_pixel(rows, 0, 21), background)
+        self.assertEqual(_pixel(rows, 15, 21), trace)
+        self.assertEqual(_pixel(rows, 15, 38), trace)
+
+    def test_empty_overview_renders_a_blank_thumbnail(self) -> None:
+        width, height, rows = _decode_png(
+            render_overview_png(_overview([]), width=4, height=3)
+        )
+
+        self.assertEqual((width, height), (4, 3))
+        self.assertEqual(
+            {_pixel(rows, x, y) for x in range(4) for y in range(3)},
+            {LIGHT_PREVIEW_COLORS.background},
+        )
+
+    def test_rejects_oversized_previews(self) -> None:
+        with self.assertRaises(ValueError):
+            render_overview_png(_overview([]), width=0, height=10)
+        with self.assertRaises(ValueError):
+            render_overview_png(_overview([]), width=10, height=4096)
+
+    def test_file_preview_loads_first_channels_at_thumbnail_resolution(self) -> None:
+        calls: list[tuple[str, list[str], int]] = []
+
+        class FakeBackend:
+            def load_dataset(self, path: str):
+                return SimpleNamespace(
+                    file_path=f"/resolved{path}",
+                    channel_names=["C3", "C4", "O1"],
+                )
+
+            def load_waveform_overview(
+                self, path: str, channel_names: list[str], max_buckets: int = 1600
+            ) -> WaveformOverview:
+                calls.append((path, channel_names, max_buckets))
+                return _overview(
+                    [_channel(name, [0.0, -1.0], [1.0, 2.0]) for name in channel_names]
+                )
+
+        png = render_file_preview_png(
+            FakeBackend(), "/data/sub-01.edf", width=32, height=24, max_channels=2
+        )
+        self.assertEqual(_decode_png(png)[:2], (32, 24))
+        self.assertEqual(calls, [("/resolved/data/sub-01.edf", ["C3", "C4"], 32)])
+
+        render_file_preview_png(
+            FakeBackend(), "/resolved/data/sub-01.edf", channel_names=["O1"]
+        )
+        self.assertEqual(calls[-1], ("/resolved/data/sub-01.edf", ["O1"], 256))
+
+
+if __name__ == "__main__":
+    unittest.main()